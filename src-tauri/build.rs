@@ -1,3 +1,17 @@
 fn main() {
+  #[cfg(feature = "grpc")]
+  {
+    use std::path::PathBuf;
+
+    // Vendored so building the gRPC API doesn't need protoc installed
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().expect("no vendored protoc for this platform"));
+    let well_known_types = protoc_bin_vendored::include_path().expect("no vendored protobuf includes");
+    tonic_build::configure()
+      .build_client(false)
+      .compile_protos(&[PathBuf::from("proto/companion.proto")], &[PathBuf::from("proto"), well_known_types])
+      .expect("failed to compile proto/companion.proto");
+  }
+
+  #[cfg(feature = "desktop")]
   tauri_build::build()
 }
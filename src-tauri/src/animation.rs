@@ -0,0 +1,67 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use image::{codecs::gif::GifDecoder, AnimationDecoder, DynamicImage};
+use std::io::Cursor;
+use tracing::info;
+
+use crate::{recording::Storyboard, InvalidImage, ScreenshotMetadata, ScreenshotProcessor};
+
+fn frames(bytes: &[u8]) -> Result<image::Frames<'_>> {
+    let decoder = GifDecoder::new(Cursor::new(bytes)).map_err(|e| InvalidImage(format!("Failed to decode GIF: {}", e)))?;
+    Ok(decoder.into_frames())
+}
+
+/// Whether `bytes` are a GIF with more than one frame, as screen capture tools like LICEcap and
+/// Kap save.
+pub(crate) fn is_animated_gif(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"GIF8") && frames(bytes).is_ok_and(|frames| frames.take(2).count() > 1)
+}
+
+/// Whether a base64 upload, which may be a data URL, starts like a GIF.
+pub(crate) fn is_gif_base64(data: &str) -> bool {
+    let data = data.rsplit(',').next().unwrap_or_default();
+    data.get(..8)
+        .and_then(|head| general_purpose::STANDARD.decode(head).ok())
+        .is_some_and(|bytes| bytes.starts_with(b"GIF8"))
+}
+
+/// Up to `max_frames` frames spread evenly over the animation, first and last included. Every
+/// frame is decoded twice, once to count them, so only the sampled ones are ever held at once.
+fn sample_frames(bytes: &[u8], max_frames: u32) -> Result<Vec<DynamicImage>> {
+    let total = frames(bytes)?.count();
+    let wanted = (max_frames.max(1) as usize).min(total);
+    let picks: Vec<usize> = match wanted {
+        0 => Vec::new(),
+        1 => vec![0],
+        _ => (0..wanted).map(|i| i * (total - 1) / (wanted - 1)).collect(),
+    };
+
+    let mut sampled = Vec::with_capacity(picks.len());
+    for (index, frame) in frames(bytes)?.enumerate() {
+        if picks.binary_search(&index).is_ok() {
+            let frame = frame.map_err(|e| InvalidImage(format!("Failed to decode GIF frame: {}", e)))?;
+            sampled.push(DynamicImage::ImageRgba8(frame.into_buffer()));
+        }
+        if sampled.len() == picks.len() {
+            break;
+        }
+    }
+    Ok(sampled)
+}
+
+impl ScreenshotProcessor {
+    /// Turns an animated GIF into a storyboard of evenly spaced frames.
+    pub(crate) async fn storyboard_animation(
+        &self,
+        bytes: &[u8],
+        metadata: &mut Option<ScreenshotMetadata>,
+    ) -> Result<Storyboard> {
+        let bytes = bytes.to_vec();
+        let max_frames = self.config.recordings.max_frames;
+        let frames = tokio::task::spawn_blocking(move || sample_frames(&bytes, max_frames))
+            .await
+            .map_err(|e| anyhow!("GIF decoding task failed: {}", e))??;
+        info!("🎞️ Storyboarded animated GIF as {} frames", frames.len());
+        Storyboard::new(frames, metadata).await
+    }
+}
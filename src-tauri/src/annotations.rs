@@ -0,0 +1,292 @@
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use image::{ImageOutputFormat, Rgba, RgbaImage};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use tracing::{info, warn};
+
+use crate::{ImageVariant, ScreenshotProcessor};
+
+const DEFAULT_COLOR: &str = "#ff3b30";
+const MAX_NOTE_CHARS: usize = 500;
+
+/// Fonts tried in order for text notes: macOS, Windows, then common Linux ones.
+const FONT_PATHS: &[&str] = &[
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    r"C:\Windows\Fonts\arial.ttf",
+    r"C:\Windows\Fonts\segoeui.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+];
+
+static FONT: Lazy<Option<FontVec>> = Lazy::new(|| {
+    FONT_PATHS
+        .iter()
+        .find_map(|path| std::fs::read(path).ok().and_then(|bytes| FontVec::try_from_vec(bytes).ok()))
+});
+
+/// What an annotation draws, in pixels of the screenshot's full image.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnnotationShape {
+    /// An arrow pointing from one point to another.
+    Arrow { from_x: i32, from_y: i32, to_x: i32, to_y: i32 },
+    /// An outlined rectangle.
+    #[serde(rename = "box")]
+    Rectangle { x: i32, y: i32, width: u32, height: u32 },
+    /// A note whose top left corner is at the point.
+    Text { x: i32, y: i32, text: String },
+}
+
+/// A mark drawn on a screenshot in the studio.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub id: String,
+    pub analysis_id: String,
+    #[serde(flatten)]
+    pub shape: AnnotationShape,
+    /// `#rrggbb`.
+    pub color: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An annotation as added or edited by the user.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AnnotationInput {
+    #[serde(flatten)]
+    pub shape: AnnotationShape,
+    /// `#rrggbb`; red when not given.
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+pub(crate) fn parse_color(color: &str) -> Option<Rgba<u8>> {
+    let hex = color.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Rgba([channel(0)?, channel(2)?, channel(4)?, 255]))
+}
+
+impl AnnotationInput {
+    /// The color to store, checking the shape on the way.
+    fn validate(&self) -> Result<String> {
+        match &self.shape {
+            AnnotationShape::Arrow { from_x, from_y, to_x, to_y } if (from_x, from_y) == (to_x, to_y) => {
+                return Err(anyhow!("An arrow needs two different points"));
+            }
+            AnnotationShape::Rectangle { width, height, .. } if *width == 0 || *height == 0 => {
+                return Err(anyhow!("A box needs a width and height"));
+            }
+            AnnotationShape::Text { text, .. } if text.trim().is_empty() => {
+                return Err(anyhow!("A note can't be empty"));
+            }
+            AnnotationShape::Text { text, .. } if text.chars().count() > MAX_NOTE_CHARS => {
+                return Err(anyhow!("A note is limited to {} characters", MAX_NOTE_CHARS));
+            }
+            _ => {}
+        }
+        let color = self.color.as_deref().map(str::trim).unwrap_or(DEFAULT_COLOR).to_lowercase();
+        parse_color(&color).ok_or_else(|| anyhow!("Invalid color '{}', expected #rrggbb", color))?;
+        Ok(color)
+    }
+}
+
+/// Draws with the annotations' color over a copy of the screenshot, sizing strokes and text to it.
+struct Canvas {
+    image: RgbaImage,
+    stroke: f32,
+}
+
+impl Canvas {
+    fn new(image: RgbaImage) -> Self {
+        let stroke = (image.width().min(image.height()) as f32 / 300.0).max(2.0);
+        Self { image, stroke }
+    }
+
+    fn blend(&mut self, x: i32, y: i32, color: Rgba<u8>, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.image.width() as i32 || y >= self.image.height() as i32 {
+            return;
+        }
+        let pixel = self.image.get_pixel_mut(x as u32, y as u32);
+        for channel in 0..3 {
+            let over = color[channel] as f32 * coverage + pixel[channel] as f32 * (1.0 - coverage);
+            pixel[channel] = over.round() as u8;
+        }
+        pixel[3] = pixel[3].max((coverage * 255.0) as u8);
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: i32, height: i32, color: Rgba<u8>, coverage: f32) {
+        for py in y..y + height {
+            for px in x..x + width {
+                self.blend(px, py, color, coverage);
+            }
+        }
+    }
+
+    /// A line `stroke` wide, drawn as a run of filled dots.
+    fn line(&mut self, from: (f32, f32), to: (f32, f32), color: Rgba<u8>) {
+        let radius = self.stroke / 2.0;
+        let length = ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt();
+        let steps = length.ceil().max(1.0) as i32;
+        let reach = radius.ceil() as i32;
+        for step in 0..=steps {
+            let t = step as f32 / steps as f32;
+            let (cx, cy) = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+            for dy in -reach..=reach {
+                for dx in -reach..=reach {
+                    if ((dx * dx + dy * dy) as f32) <= radius * radius {
+                        self.blend(cx.round() as i32 + dx, cy.round() as i32 + dy, color, 1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    fn arrow(&mut self, from: (f32, f32), to: (f32, f32), color: Rgba<u8>) {
+        self.line(from, to, color);
+        let head = (self.stroke * 5.0).max(12.0);
+        let angle = (from.1 - to.1).atan2(from.0 - to.0);
+        for side in [-0.5f32, 0.5] {
+            let tip = (to.0 + head * (angle + side).cos(), to.1 + head * (angle + side).sin());
+            self.line(to, tip, color);
+        }
+    }
+
+    fn rectangle(&mut self, x: f32, y: f32, width: f32, height: f32, color: Rgba<u8>) {
+        let corners = [(x, y), (x + width, y), (x + width, y + height), (x, y + height)];
+        for i in 0..4 {
+            self.line(corners[i], corners[(i + 1) % 4], color);
+        }
+    }
+
+    /// The note on a white label so it reads on any background.
+    fn text(&mut self, x: i32, y: i32, text: &str, color: Rgba<u8>) {
+        let Some(font) = FONT.as_ref() else {
+            warn!("No font found for annotation notes; drawing them as boxes");
+            self.rectangle(x as f32, y as f32, 24.0 * self.stroke, 8.0 * self.stroke, color);
+            return;
+        };
+        let size = (self.image.width().min(self.image.height()) as f32 / 40.0).max(16.0);
+        let scaled = font.as_scaled(PxScale::from(size));
+        let line_height = scaled.height() + scaled.line_gap();
+        let padding = (size / 4.0) as i32;
+
+        let lines: Vec<&str> = text.lines().collect();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().map(|c| scaled.h_advance(font.glyph_id(c))).sum::<f32>())
+            .fold(0.0, f32::max);
+        self.fill_rect(
+            x,
+            y,
+            width.ceil() as i32 + 2 * padding,
+            (line_height * lines.len() as f32).ceil() as i32 + 2 * padding,
+            Rgba([255, 255, 255, 255]),
+            0.85,
+        );
+
+        for (row, line) in lines.iter().enumerate() {
+            let baseline = y as f32 + padding as f32 + scaled.ascent() + line_height * row as f32;
+            let mut caret = x as f32 + padding as f32;
+            for c in line.chars() {
+                let glyph_id = font.glyph_id(c);
+                let glyph = glyph_id.with_scale_and_position(PxScale::from(size), ab_glyph::point(caret, baseline));
+                caret += scaled.h_advance(glyph_id);
+                let Some(outlined) = font.outline_glyph(glyph) else {
+                    continue;
+                };
+                let bounds = outlined.px_bounds();
+                outlined.draw(|gx, gy, coverage| {
+                    self.blend(bounds.min.x as i32 + gx as i32, bounds.min.y as i32 + gy as i32, color, coverage);
+                });
+            }
+        }
+    }
+
+    fn draw(&mut self, annotation: &Annotation) {
+        let color = parse_color(&annotation.color).unwrap_or(Rgba([255, 59, 48, 255]));
+        match &annotation.shape {
+            AnnotationShape::Arrow { from_x, from_y, to_x, to_y } => {
+                self.arrow((*from_x as f32, *from_y as f32), (*to_x as f32, *to_y as f32), color)
+            }
+            AnnotationShape::Rectangle { x, y, width, height } => {
+                self.rectangle(*x as f32, *y as f32, *width as f32, *height as f32, color)
+            }
+            AnnotationShape::Text { x, y, text } => self.text(*x, *y, text, color),
+        }
+    }
+}
+
+/// The image with the annotations drawn over it, oldest first, as PNG.
+fn burn_in(bytes: &[u8], annotations: &[Annotation]) -> Result<Vec<u8>> {
+    let image = image::load_from_memory(bytes).map_err(|e| anyhow!("Failed to decode stored image: {}", e))?;
+    let mut canvas = Canvas::new(image.to_rgba8());
+    for annotation in annotations {
+        canvas.draw(annotation);
+    }
+    let mut png = Vec::new();
+    image::DynamicImage::ImageRgba8(canvas.image).write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+    Ok(png)
+}
+
+impl ScreenshotProcessor {
+    /// A screenshot's annotations, oldest first.
+    pub fn annotations(&self, analysis_id: &str) -> Result<Vec<Annotation>> {
+        self.store.annotations(analysis_id)
+    }
+
+    pub fn add_annotation(&self, analysis_id: &str, input: AnnotationInput) -> Result<Annotation> {
+        let color = input.validate()?;
+        if self.get_analysis(analysis_id, false)?.is_none() {
+            return Err(anyhow!("Analysis {} not found", analysis_id));
+        }
+        let now = Utc::now();
+        let annotation = Annotation {
+            id: uuid::Uuid::new_v4().to_string(),
+            analysis_id: analysis_id.to_string(),
+            shape: input.shape,
+            color,
+            created_at: now,
+            updated_at: now,
+        };
+        self.store.save_annotation(&annotation)?;
+        info!("✏️ Annotated analysis {}", analysis_id);
+        Ok(annotation)
+    }
+
+    pub fn update_annotation(&self, id: &str, input: AnnotationInput) -> Result<Annotation> {
+        let color = input.validate()?;
+        let mut annotation = self
+            .store
+            .get_annotation(id)?
+            .ok_or_else(|| anyhow!("Annotation {} not found", id))?;
+        annotation.shape = input.shape;
+        annotation.color = color;
+        annotation.updated_at = Utc::now();
+        self.store.save_annotation(&annotation)?;
+        Ok(annotation)
+    }
+
+    /// Returns false if the annotation didn't exist.
+    pub fn delete_annotation(&self, id: &str) -> Result<bool> {
+        self.store.delete_annotation(id)
+    }
+
+    /// A copy of the screenshot's full image with its annotations drawn in, as PNG, or `None`
+    /// if there's no such analysis.
+    pub fn annotated_image(&self, analysis_id: &str) -> Result<Option<Vec<u8>>> {
+        let Some((bytes, _)) = self.analysis_image(analysis_id, ImageVariant::Full)? else {
+            return Ok(None);
+        };
+        let annotations = self.store.annotations(analysis_id)?;
+        burn_in(&bytes, &annotations).map(Some)
+    }
+}
@@ -0,0 +1,195 @@
+use axum::{
+    body::{to_bytes, Body},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Json as ResponseJson, Response},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    budget::BudgetExceeded, exclusions::Excluded, image_policy::InvalidImage, inflight::AnalysisCancelled, outbox::QueuedOffline,
+    pause::Paused, providers::ProviderError, queue::QueueFull, redaction::SensitiveContentBlocked,
+};
+
+/// Largest plain-text error body kept as the message when it's given a typed body.
+const MAX_ERROR_TEXT: usize = 16 * 1024;
+
+/// Why an API request failed, for clients to act on without parsing the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    PayloadTooLarge,
+    /// The upload isn't an image the server accepts.
+    InvalidImage,
+    /// Redaction turned the screenshot away for containing sensitive data.
+    SensitiveContent,
+    /// An exclusion rule turned the screenshot away, e.g. it was taken in a password manager.
+    Excluded,
+    Cancelled,
+    RateLimited,
+    QueueFull,
+    BudgetExceeded,
+    /// The provider couldn't be reached; the screenshot was saved and will be analyzed later.
+    /// Sending it again would analyze it twice.
+    QueuedOffline,
+    /// Processing is paused and the screenshot was dropped; send it again after resuming.
+    Paused,
+    /// The AI provider rejected the request or failed.
+    ProviderError,
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::BadRequest => StatusCode::BAD_REQUEST,
+            ErrorCode::Unauthorized => StatusCode::UNAUTHORIZED,
+            ErrorCode::Forbidden => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::InvalidImage | ErrorCode::SensitiveContent | ErrorCode::Excluded => {
+                StatusCode::UNPROCESSABLE_ENTITY
+            }
+            ErrorCode::Cancelled => StatusCode::CONFLICT,
+            ErrorCode::RateLimited | ErrorCode::QueueFull => StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::BudgetExceeded => StatusCode::PAYMENT_REQUIRED,
+            ErrorCode::QueuedOffline => StatusCode::ACCEPTED,
+            ErrorCode::ProviderError => StatusCode::BAD_GATEWAY,
+            ErrorCode::Paused | ErrorCode::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// The code for a bare error status from a handler, extractor or middleware.
+    fn for_status(status: StatusCode) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED => ErrorCode::Unauthorized,
+            StatusCode::FORBIDDEN => ErrorCode::Forbidden,
+            StatusCode::NOT_FOUND => ErrorCode::NotFound,
+            StatusCode::PAYLOAD_TOO_LARGE => ErrorCode::PayloadTooLarge,
+            StatusCode::TOO_MANY_REQUESTS => ErrorCode::RateLimited,
+            StatusCode::PAYMENT_REQUIRED => ErrorCode::BudgetExceeded,
+            StatusCode::BAD_GATEWAY => ErrorCode::ProviderError,
+            StatusCode::SERVICE_UNAVAILABLE => ErrorCode::Unavailable,
+            status if status.is_server_error() => ErrorCode::Internal,
+            _ => ErrorCode::BadRequest,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorDetail {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+/// The body of every error response: `{"error": {"code": "...", "message": "..."}}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub error: ErrorDetail,
+}
+
+/// An error returned from an HTTP handler, sent with the status its code maps to.
+#[derive(Debug)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(error: anyhow::Error) -> Self {
+        let code = if error.is::<QueueFull>() {
+            ErrorCode::QueueFull
+        } else if error.is::<BudgetExceeded>() {
+            ErrorCode::BudgetExceeded
+        } else if error.is::<QueuedOffline>() {
+            ErrorCode::QueuedOffline
+        } else if let Some(paused) = error.downcast_ref::<Paused>() {
+            // Queued while paused is the same promise as queued while offline
+            match paused {
+                Paused::Queued => ErrorCode::QueuedOffline,
+                Paused::Dropped => ErrorCode::Paused,
+            }
+        } else if error.is::<InvalidImage>() {
+            ErrorCode::InvalidImage
+        } else if error.is::<SensitiveContentBlocked>() {
+            ErrorCode::SensitiveContent
+        } else if error.is::<Excluded>() {
+            ErrorCode::Excluded
+        } else if error.is::<AnalysisCancelled>() {
+            ErrorCode::Cancelled
+        } else if error.chain().any(|cause| cause.is::<ProviderError>()) {
+            ErrorCode::ProviderError
+        } else {
+            ErrorCode::Internal
+        };
+        Self::new(code, error.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorBody {
+            error: ErrorDetail {
+                code: self.code,
+                message: self.message,
+            },
+        };
+        (self.code.status(), ResponseJson(body)).into_response()
+    }
+}
+
+/// Gives error statuses returned without a JSON body, by handlers, extractors and middleware,
+/// the same typed body as `ApiError`, keeping any plain-text message they had.
+pub async fn typed_error_bodies(response: Response) -> Response {
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !(status.is_client_error() || status.is_server_error()) || is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let text = to_bytes(body, MAX_ERROR_TEXT)
+        .await
+        .map(|bytes| String::from_utf8_lossy(&bytes).trim().to_string())
+        .unwrap_or_default();
+    let message = if text.is_empty() {
+        status.canonical_reason().unwrap_or("Request failed").to_string()
+    } else {
+        text
+    };
+    let body = serde_json::to_vec(&ErrorBody {
+        error: ErrorDetail {
+            code: ErrorCode::for_status(status),
+            message,
+        },
+    })
+    .unwrap_or_default();
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Response::from_parts(parts, Body::from(body))
+}
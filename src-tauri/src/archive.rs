@@ -0,0 +1,217 @@
+use anyhow::Result;
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Cursor, Write};
+use tracing::{error, info, warn};
+use zip::{write::FileOptions, ZipWriter};
+
+use crate::{vault, AnalysisData, AnalysisRecord, ScreenshotProcessor};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    pub(crate) fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "application/json",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+}
+
+/// Which analyses to export and how.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportOptions {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Analyses from this time on; everything when `None`.
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// Analyses before this time; up to now when `None`.
+    #[serde(default)]
+    pub to: Option<DateTime<Utc>>,
+    /// Bundle the data file and every image into a zip archive.
+    #[serde(default)]
+    pub include_images: bool,
+    /// Only analyses in this project, by id.
+    #[serde(default)]
+    pub project: Option<String>,
+}
+
+/// An export ready to be saved or downloaded.
+#[derive(Debug, Clone)]
+pub struct ExportArchive {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    pub analyses: usize,
+}
+
+pub(crate) const CSV_COLUMNS: &[&str] = &[
+    "id",
+    "timestamp",
+    "source",
+    "content_type",
+    "summary",
+    "webpage_url",
+    "research_topics",
+    "user_intent",
+    "follow_up",
+    "tags",
+    "links",
+    "app",
+    "filename",
+    "starred",
+    "note",
+    "project_id",
+];
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One analysis as a CSV line, in the order of `CSV_COLUMNS`.
+pub(crate) fn csv_row(id: &str, analysis: &AnalysisData) -> String {
+    let content = &analysis.content_analysis;
+    let links: Vec<&str> = content.links.iter().map(|link| link.url.as_str()).collect();
+    let row = [
+        id,
+        &analysis.timestamp.to_rfc3339(),
+        &analysis.source,
+        &content.content_type,
+        &analysis.brief_summary,
+        content.webpage_url.as_deref().unwrap_or_default(),
+        &content.research_topics.join("; "),
+        &content.user_intent,
+        &content.follow_up,
+        &content.tags.join("; "),
+        &links.join("; "),
+        analysis.metadata.app.as_deref().unwrap_or_default(),
+        analysis.metadata.filename.as_deref().unwrap_or_default(),
+        if analysis.starred { "true" } else { "false" },
+        analysis.note.as_deref().unwrap_or_default(),
+        analysis.project_id.as_deref().unwrap_or_default(),
+    ];
+    format!("{}\r\n", row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","))
+}
+
+fn to_csv(analyses: &[(String, AnalysisData)]) -> String {
+    let mut csv = format!("{}\r\n", CSV_COLUMNS.join(","));
+    for (id, analysis) in analyses {
+        csv.push_str(&csv_row(id, analysis));
+    }
+    csv
+}
+
+fn render(analyses: &[(String, AnalysisData)], format: ExportFormat) -> Result<Vec<u8>> {
+    Ok(match format {
+        ExportFormat::Json => {
+            let records: Vec<AnalysisRecord> = analyses
+                .iter()
+                .map(|(id, analysis)| AnalysisRecord::from_analysis(id.clone(), analysis.clone(), false))
+                .collect();
+            serde_json::to_vec_pretty(&records)?
+        }
+        ExportFormat::Csv => to_csv(analyses).into_bytes(),
+    })
+}
+
+fn zip_with_images(analyses: &[(String, AnalysisData)], format: ExportFormat, data: &[u8]) -> Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default();
+
+    zip.start_file(format!("analyses.{}", format.extension()), options)?;
+    zip.write_all(data)?;
+
+    for (id, analysis) in analyses {
+        let image = &analysis.image_data;
+        let bytes = match general_purpose::STANDARD.decode(&image.base64_data) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Skipping unreadable image for analysis {}: {}", id, e);
+                continue;
+            }
+        };
+        // Images are already compressed
+        let stored = options.compression_method(zip::CompressionMethod::Stored);
+        zip.start_file(format!("images/{}.{}", id, vault::image_extension(&image.media_type)), stored)?;
+        zip.write_all(&bytes)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+impl ScreenshotProcessor {
+    /// Dumps stored analyses as JSON or CSV, optionally zipped with their images.
+    pub fn export_history(&self, options: &ExportOptions) -> Result<ExportArchive> {
+        let from = options.from.unwrap_or(DateTime::UNIX_EPOCH);
+        let to = options.to.unwrap_or_else(|| Utc::now() + Duration::seconds(1));
+        let mut analyses = self.store.between(from, to)?;
+        if let Some(project) = &options.project {
+            analyses.retain(|(_, analysis)| analysis.project_id.as_ref() == Some(project));
+        }
+        if options.include_images {
+            self.store.load_images(&mut analyses);
+        }
+
+        let data = render(&analyses, options.format)?;
+        let archive = if options.include_images {
+            ExportArchive {
+                bytes: zip_with_images(&analyses, options.format, &data)?,
+                content_type: "application/zip",
+                extension: "zip",
+                analyses: analyses.len(),
+            }
+        } else {
+            ExportArchive {
+                bytes: data,
+                content_type: options.format.content_type(),
+                extension: options.format.extension(),
+                analyses: analyses.len(),
+            }
+        };
+
+        info!("📦 Exported {} analyses as {}", archive.analyses, archive.extension);
+        Ok(archive)
+    }
+}
+
+pub async fn handle_export_analyses(
+    State(processor): State<ScreenshotProcessor>,
+    Query(options): Query<ExportOptions>,
+) -> Result<([(header::HeaderName, String); 2], Vec<u8>), StatusCode> {
+    let archive = processor.export_history(&options).map_err(|e| {
+        error!("Failed to export analyses: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let filename = format!("screenshot-analyses-{}.{}", Utc::now().format("%Y%m%d"), archive.extension);
+    Ok((
+        [
+            (header::CONTENT_TYPE, archive.content_type.to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        archive.bytes,
+    ))
+}
@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use teloxide::utils::html;
+use tracing::{info, warn};
+
+const ARXIV_API_URL: &str = "http://export.arxiv.org/api/query";
+const ATOM_NS: &str = "http://www.w3.org/2005/Atom";
+
+/// Results fetched per topic before ranking.
+const RESULTS_PER_TOPIC: usize = 5;
+/// Papers returned after ranking across all topics.
+const MAX_PAPERS: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArxivPaper {
+    pub id: String,
+    pub title: String,
+    pub authors: Vec<String>,
+    pub summary: String,
+    pub published: Option<DateTime<Utc>>,
+    pub url: String,
+    pub pdf_url: Option<String>,
+    pub matched_topics: Vec<String>,
+    pub score: f64,
+}
+
+/// Searches arXiv for each topic and returns the best papers across all of them.
+pub async fn search_papers(client: &Client, topics: &[String]) -> Result<Vec<ArxivPaper>> {
+    let topics: Vec<&str> = topics
+        .iter()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .take(5)
+        .collect();
+
+    if topics.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut papers: HashMap<String, ArxivPaper> = HashMap::new();
+
+    for topic in &topics {
+        let results = match query_topic(client, topic).await {
+            Ok(results) => results,
+            Err(e) => {
+                warn!("arXiv query for '{}' failed: {}", topic, e);
+                continue;
+            }
+        };
+
+        for paper in results {
+            papers
+                .entry(paper.id.clone())
+                .and_modify(|existing| existing.matched_topics.push(topic.to_string()))
+                .or_insert(paper);
+        }
+    }
+
+    let mut ranked: Vec<ArxivPaper> = papers
+        .into_values()
+        .map(|mut paper| {
+            paper.score = score_paper(&paper, &topics);
+            paper
+        })
+        .collect();
+
+    ranked.sort_by(|a, b| b.score.total_cmp(&a.score));
+    ranked.truncate(MAX_PAPERS);
+
+    info!("🔬 Found {} arXiv papers for {} topics", ranked.len(), topics.len());
+    Ok(ranked)
+}
+
+async fn query_topic(client: &Client, topic: &str) -> Result<Vec<ArxivPaper>> {
+    let search_query = format!("all:\"{}\"", topic.replace('"', ""));
+    let max_results = RESULTS_PER_TOPIC.to_string();
+
+    let response = client
+        .get(ARXIV_API_URL)
+        .query(&[
+            ("search_query", search_query.as_str()),
+            ("start", "0"),
+            ("max_results", max_results.as_str()),
+            ("sortBy", "relevance"),
+        ])
+        .send()
+        .await
+        .map_err(|e| anyhow!("arXiv request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!("arXiv API error: {}", response.status()));
+    }
+
+    let body = response.text().await?;
+    let mut papers = parse_feed(&body)?;
+    for paper in &mut papers {
+        paper.matched_topics.push(topic.to_string());
+    }
+    Ok(papers)
+}
+
+fn parse_feed(xml: &str) -> Result<Vec<ArxivPaper>> {
+    let doc = roxmltree::Document::parse(xml).map_err(|e| anyhow!("Invalid arXiv response: {}", e))?;
+
+    let child_text = |node: roxmltree::Node, name: &str| -> Option<String> {
+        node.children()
+            .find(|c| c.has_tag_name((ATOM_NS, name)))
+            .and_then(|c| c.text())
+            .map(|t| t.split_whitespace().collect::<Vec<_>>().join(" "))
+    };
+
+    let papers = doc
+        .descendants()
+        .filter(|n| n.has_tag_name((ATOM_NS, "entry")))
+        .filter_map(|entry| {
+            let url = child_text(entry, "id")?;
+            let id = url.rsplit("/abs/").next().unwrap_or(&url).to_string();
+
+            let authors = entry
+                .children()
+                .filter(|c| c.has_tag_name((ATOM_NS, "author")))
+                .filter_map(|author| child_text(author, "name"))
+                .collect();
+
+            let pdf_url = entry
+                .children()
+                .filter(|c| c.has_tag_name((ATOM_NS, "link")))
+                .find(|link| link.attribute("title") == Some("pdf"))
+                .and_then(|link| link.attribute("href"))
+                .map(str::to_string);
+
+            Some(ArxivPaper {
+                id,
+                title: child_text(entry, "title").unwrap_or_default(),
+                authors,
+                summary: child_text(entry, "summary").unwrap_or_default(),
+                published: child_text(entry, "published")
+                    .and_then(|p| DateTime::parse_from_rfc3339(&p).ok())
+                    .map(|p| p.with_timezone(&Utc)),
+                url,
+                pdf_url,
+                matched_topics: Vec::new(),
+                score: 0.0,
+            })
+        })
+        .collect();
+
+    Ok(papers)
+}
+
+/// Scores by topic coverage, term overlap with the title/abstract, and recency.
+fn score_paper(paper: &ArxivPaper, topics: &[&str]) -> f64 {
+    let title = paper.title.to_lowercase();
+    let summary = paper.summary.to_lowercase();
+
+    let term_score: f64 = topics
+        .iter()
+        .flat_map(|topic| topic.split_whitespace())
+        .map(|term| term.to_lowercase())
+        .filter(|term| term.len() > 2)
+        .map(|term| {
+            if title.contains(&term) {
+                2.0
+            } else if summary.contains(&term) {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .sum();
+
+    let recency = paper
+        .published
+        .map(|p| {
+            let years = (Utc::now() - p).num_days() as f64 / 365.0;
+            (5.0 - years).clamp(0.0, 5.0) / 5.0
+        })
+        .unwrap_or(0.0);
+
+    paper.matched_topics.len() as f64 * 3.0 + term_score + recency
+}
+
+/// Formats papers as a Telegram HTML message.
+pub fn format_papers_html(papers: &[ArxivPaper]) -> String {
+    if papers.is_empty() {
+        return "🔬 <b>Research Papers</b>\n\nNo matching arXiv papers were found.".to_string();
+    }
+
+    let entries: Vec<String> = papers
+        .iter()
+        .enumerate()
+        .map(|(i, paper)| {
+            let authors = match paper.authors.len() {
+                0 => "Unknown authors".to_string(),
+                1..=3 => paper.authors.join(", "),
+                _ => format!("{} et al.", paper.authors[0]),
+            };
+            let year = paper
+                .published
+                .map(|p| p.format("%Y").to_string())
+                .unwrap_or_default();
+            let summary: String = paper.summary.chars().take(220).collect();
+            let ellipsis = if paper.summary.chars().count() > 220 { "..." } else { "" };
+
+            format!(
+                "{}. <a href=\"{}\">{}</a>\n<i>{} {}</i>\n{}{}",
+                i + 1,
+                html::escape(&paper.url),
+                html::escape(&paper.title),
+                html::escape(&authors),
+                year,
+                html::escape(&summary),
+                ellipsis
+            )
+        })
+        .collect();
+
+    format!("🔬 <b>Research Papers</b>\n\n{}", entries.join("\n\n"))
+}
@@ -0,0 +1,136 @@
+use anyhow::Result;
+use axum::{
+    extract::{ConnectInfo, Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{Json as ResponseJson, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{net::SocketAddr, sync::Arc, time::Instant};
+use tracing::{error, warn};
+
+use crate::{usage::TokenUsage, ScreenshotProcessor};
+
+/// Header clients send to identify themselves in the audit log, e.g. "Work iPhone".
+pub const DEVICE_NAME_HEADER: &str = "x-device-name";
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+/// One HTTP request to the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    pub client_ip: String,
+    pub device_name: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    /// From `Content-Length`, when the client sent one.
+    pub request_bytes: Option<u64>,
+    pub latency_ms: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+/// Filters for the audit log; every field is optional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub client_ip: Option<String>,
+    pub device_name: Option<String>,
+    /// Requests whose path starts with this, e.g. "/screenshot".
+    pub path: Option<String>,
+    /// Only requests answered with a 4xx or 5xx status.
+    #[serde(default)]
+    pub errors_only: bool,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl AuditQuery {
+    pub(crate) fn limit(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT)
+    }
+}
+
+tokio::task_local! {
+    static REQUEST_USAGE: Arc<Mutex<TokenUsage>>;
+}
+
+/// Adds tokens spent on the current HTTP request to its audit entry.
+pub(crate) fn add_usage(input_tokens: u64, output_tokens: u64) {
+    let _ = REQUEST_USAGE.try_with(|usage| {
+        let mut usage = usage.lock();
+        usage.input_tokens += input_tokens;
+        usage.output_tokens += output_tokens;
+    });
+}
+
+pub(crate) fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+}
+
+impl ScreenshotProcessor {
+    fn record_audit(&self, entry: &AuditEntry) {
+        let cutoff = (self.config.audit_retention_days > 0)
+            .then(|| Utc::now() - Duration::days(self.config.audit_retention_days.into()));
+        if let Err(e) = self.store.record_audit(entry, cutoff) {
+            warn!("Failed to record audit entry: {}", e);
+        }
+    }
+
+    /// Recorded requests matching `query`, newest first.
+    pub fn audit_log(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        self.store.audit_log(query)
+    }
+}
+
+/// Records every request with its client, outcome, latency and the tokens it used.
+pub async fn audit_requests(
+    State(processor): State<ScreenshotProcessor>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let started = Instant::now();
+    let device_name = header_value(request.headers(), DEVICE_NAME_HEADER).map(str::to_string);
+    let request_bytes =
+        header_value(request.headers(), header::CONTENT_LENGTH.as_str()).and_then(|v| v.parse().ok());
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    let usage = Arc::new(Mutex::new(TokenUsage::default()));
+    let response = REQUEST_USAGE.scope(usage.clone(), next.run(request)).await;
+    let usage = *usage.lock();
+
+    processor.record_audit(&AuditEntry {
+        timestamp: Utc::now(),
+        client_ip: addr.ip().to_string(),
+        device_name,
+        method,
+        path,
+        status: response.status().as_u16(),
+        request_bytes,
+        latency_ms: started.elapsed().as_millis() as u64,
+        input_tokens: usage.input_tokens,
+        output_tokens: usage.output_tokens,
+    });
+    response
+}
+
+pub async fn handle_audit_log(
+    State(processor): State<ScreenshotProcessor>,
+    Query(query): Query<AuditQuery>,
+) -> Result<ResponseJson<Vec<AuditEntry>>, StatusCode> {
+    processor.audit_log(&query).map(ResponseJson).map_err(|e| {
+        error!("Failed to read audit log: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
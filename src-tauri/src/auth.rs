@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::{ApiError, ErrorCode, ScreenshotProcessor};
+
+/// Generates a fresh random API key for HTTP clients.
+pub fn generate_api_key() -> String {
+    format!("sk-ss-{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple())
+}
+
+/// Reads the client key from `Authorization: Bearer <key>` or `X-API-Key: <key>`.
+pub(crate) fn extract_api_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .or_else(|| headers.get("x-api-key").and_then(|v| v.to_str().ok()))
+        .map(str::trim)
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Rejects requests without a valid API key when one is configured.
+pub async fn require_api_key(
+    State(processor): State<ScreenshotProcessor>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(expected) = processor.api_key() else {
+        return next.run(request).await;
+    };
+
+    match extract_api_key(request.headers()) {
+        Some(provided) if constant_time_eq(provided.as_bytes(), expected.as_bytes()) => {
+            next.run(request).await
+        }
+        provided => {
+            warn!(
+                "🔒 Rejected unauthenticated request to {} ({})",
+                request.uri().path(),
+                if provided.is_some() { "invalid key" } else { "missing key" }
+            );
+            (
+                [(header::WWW_AUTHENTICATE, "Bearer")],
+                ApiError::new(ErrorCode::Unauthorized, "Missing or invalid API key"),
+            )
+                .into_response()
+        }
+    }
+}
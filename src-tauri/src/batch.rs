@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use futures::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{usage, ApiError, ProcessingResponse, ScreenshotProcessor, ScreenshotRequest};
+
+pub const MAX_BATCH_SIZE: usize = 10;
+/// Screenshots analyzed at the same time, to stay clear of provider rate limits.
+const BATCH_CONCURRENCY: usize = 3;
+const SYNTHESIS_MAX_TOKENS: u32 = 400;
+
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub images: Vec<ScreenshotRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchResponse {
+    pub success: bool,
+    /// One entry per submitted image, in the order they were sent.
+    pub results: Vec<ProcessingResponse>,
+    /// What the successfully analyzed screenshots show when taken together.
+    pub synthesis: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+pub(crate) fn check_batch_size(images: &[ScreenshotRequest]) -> Result<()> {
+    if images.is_empty() {
+        return Err(anyhow!("No images provided"));
+    }
+    if images.len() > MAX_BATCH_SIZE {
+        return Err(anyhow!("Too many images (max {})", MAX_BATCH_SIZE));
+    }
+    Ok(())
+}
+
+impl ScreenshotProcessor {
+    /// Analyzes several screenshots concurrently, then describes them as a set.
+    pub async fn process_batch(&self, images: Vec<ScreenshotRequest>) -> Result<BatchResponse> {
+        check_batch_size(&images)?;
+
+        info!("📚 Processing batch of {} screenshots", images.len());
+
+        let results: Vec<ProcessingResponse> = stream::iter(images)
+            .map(|request| async move {
+                self.process_screenshot(&request.image, request.metadata)
+                    .await
+                    .unwrap_or_else(|e| {
+                        warn!("Batch screenshot failed: {}", e);
+                        ProcessingResponse::failed(ApiError::from(e))
+                    })
+            })
+            .buffered(BATCH_CONCURRENCY)
+            .collect()
+            .await;
+
+        let analysis_ids: Vec<&str> = results
+            .iter()
+            .filter_map(|r| r.analysis_id.as_deref())
+            .collect();
+
+        let synthesis = if analysis_ids.len() > 1 {
+            self.synthesize(&analysis_ids).await.unwrap_or_else(|e| {
+                warn!("Batch synthesis failed: {}", e);
+                None
+            })
+        } else {
+            None
+        };
+
+        Ok(BatchResponse {
+            success: results.iter().any(|r| r.success),
+            results,
+            synthesis,
+            timestamp: Utc::now(),
+        })
+    }
+
+    /// Asks the LLM what a set of stored analyses have in common.
+    async fn synthesize(&self, analysis_ids: &[&str]) -> Result<Option<String>> {
+        let mut summaries = Vec::new();
+        let mut images = Vec::new();
+
+        for id in analysis_ids {
+            if let Some(analysis) = self.store.get(id)? {
+                summaries.push(format!("{}. {}", summaries.len() + 1, analysis.brief_summary));
+                images.push(self.llm_image(&analysis.image_data).await);
+            }
+        }
+
+        if images.len() < 2 {
+            return Ok(None);
+        }
+
+        let prompt = format!(
+            "These {} screenshots were shared together, in this order. \
+             Individual summaries:\n\n{}\n\n\
+             In 2-3 sentences, describe what they show as a set (for example a checkout flow, \
+             a conversation, or a comparison between products) and what the user is most likely trying to do.",
+            images.len(),
+            summaries.join("\n")
+        );
+
+        let image_refs: Vec<_> = images.iter().collect();
+        let (synthesis, records) =
+            usage::track(self.provider.complete(&prompt, &image_refs, SYNTHESIS_MAX_TOKENS)).await;
+        self.save_usage(None, &records);
+        let synthesis = synthesis?;
+
+        Ok(Some(synthesis))
+    }
+}
@@ -0,0 +1,169 @@
+// src-tauri/src/bin/screenshot-ai.rs
+//
+// Runs the screenshot server without the app window or tray, e.g. on a headless Mac mini or
+// Linux box, using the same settings and library code as the app.
+
+use anyhow::{anyhow, Context, Result};
+use app::{settings, AppConfig, ExportFormat, ExportOptions, ScreenshotMetadata, ScreenshotProcessor, ServerHandle};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+#[derive(Debug, Parser)]
+#[command(name = "screenshot-ai", version, about = "Headless Screenshot AI server")]
+struct Cli {
+    /// Settings file (TOML, JSON or YAML). Defaults to the app's saved settings; configuration
+    /// env vars override either.
+    #[arg(long, short, global = true)]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run the server and watchers until interrupted.
+    Serve,
+    /// Show the status of a server running with these settings.
+    Status,
+    /// Analyze an image file and print the result as JSON.
+    Analyze {
+        file: PathBuf,
+        /// Analyze with the local model only, sending nothing off the machine.
+        #[arg(long)]
+        local_only: bool,
+    },
+    /// Save stored analyses to a file.
+    Export {
+        /// Where to write the export.
+        #[arg(long, short)]
+        output: PathBuf,
+        /// `json` or `csv`.
+        #[arg(long, default_value = "json", value_parser = parse_format)]
+        format: ExportFormat,
+        /// Only analyses taken at or after this RFC 3339 time.
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        /// Only analyses taken before this RFC 3339 time.
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+        /// Write a zip with the images alongside the data.
+        #[arg(long)]
+        include_images: bool,
+        /// Only analyses in the project with this id.
+        #[arg(long)]
+        project: Option<String>,
+    },
+}
+
+fn parse_format(value: &str) -> Result<ExportFormat, String> {
+    serde_json::from_value(serde_json::Value::String(value.to_lowercase()))
+        .map_err(|_| format!("unknown export format `{}`, expected `json` or `csv`", value))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let server_config = settings::load(cli.config.as_deref())?;
+    let log_filter = server_config.log_filter.clone().filter(|f| !f.trim().is_empty());
+    let config = settings::app_config(server_config);
+
+    match cli.command {
+        Command::Serve => {
+            let _log_guard = app::logging::init(log_filter.as_deref());
+            serve(config).await
+        }
+        Command::Status => status(&config).await,
+        Command::Analyze { file, local_only } => analyze(config, &file, local_only).await,
+        Command::Export {
+            output,
+            format,
+            from,
+            to,
+            include_images,
+            project,
+        } => {
+            let options = ExportOptions {
+                format,
+                from,
+                to,
+                include_images,
+                project,
+            };
+            export(config, &output, &options)
+        }
+    }
+}
+
+async fn serve(config: AppConfig) -> Result<()> {
+    info!("🚀 Starting Screenshot AI server");
+    let mut handle = ServerHandle::start(config).await?;
+    info!(
+        "📡 Listening on {}://0.0.0.0:{}",
+        handle.processor.url_scheme(),
+        handle.config.server_port
+    );
+
+    let server_task = handle.server_task.as_mut().ok_or_else(|| anyhow!("Server task is missing"))?;
+    let stopped = tokio::select! {
+        signal = tokio::signal::ctrl_c() => signal.context("Failed to listen for Ctrl-C"),
+        // The task only ends early if the server failed, which it has already logged
+        _ = server_task => Err(anyhow!("Screenshot server stopped unexpectedly")),
+    };
+    handle.stop();
+    if let Err(e) = &stopped {
+        error!("{:#}", e);
+    }
+    stopped
+}
+
+/// Asks the running server for its status over the HTTP API, as a phone would.
+async fn status(config: &AppConfig) -> Result<()> {
+    let scheme = if config.tls_enabled { "https" } else { "http" };
+    let url = format!("{}://127.0.0.1:{}/status", scheme, config.server_port);
+    // The server's certificate is usually self-signed, and this only ever talks to localhost
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(config.tls_enabled)
+        .build()?;
+    let mut request = client.get(&url);
+    if let Some(key) = config.api_key.as_deref().filter(|key| !key.is_empty()) {
+        request = request.bearer_auth(key);
+    }
+
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("Server is not running on port {}", config.server_port))?
+        .error_for_status()?;
+    let status: serde_json::Value = response.json().await?;
+    println!("{}", serde_json::to_string_pretty(&status)?);
+    Ok(())
+}
+
+async fn analyze(config: AppConfig, file: &Path, local_only: bool) -> Result<()> {
+    let bytes = std::fs::read(file).with_context(|| format!("Failed to read {}", file.display()))?;
+    let processor = ScreenshotProcessor::new(config)?;
+    let metadata = ScreenshotMetadata {
+        source: Some("cli".to_string()),
+        filename: file.file_name().map(|name| name.to_string_lossy().into_owned()),
+        file_path: Some(file.display().to_string()),
+        local_only: local_only.then_some(true),
+        ..Default::default()
+    };
+
+    let response = processor
+        .process_screenshot(&general_purpose::STANDARD.encode(bytes), Some(metadata))
+        .await?;
+    println!("{}", serde_json::to_string_pretty(&response)?);
+    Ok(())
+}
+
+fn export(config: AppConfig, output: &Path, options: &ExportOptions) -> Result<()> {
+    let processor = ScreenshotProcessor::new(config)?;
+    let archive = processor.export_history(options)?;
+    std::fs::write(output, &archive.bytes).with_context(|| format!("Failed to write {}", output.display()))?;
+    eprintln!("Exported {} analyses to {}", archive.analyses, output.display());
+    Ok(())
+}
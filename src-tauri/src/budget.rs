@@ -0,0 +1,112 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use teloxide::utils::html;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{desktop_notification, usage, ScreenshotProcessor};
+
+#[derive(Debug, Error)]
+#[error("Monthly LLM budget of ${limit:.2} reached (${spent:.2} spent this month); override the budget to keep analyzing")]
+pub struct BudgetExceeded {
+    pub spent: f64,
+    pub limit: f64,
+}
+
+/// This month's spend against the configured limits.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetStatus {
+    pub month_spend_usd: f64,
+    pub soft_limit_usd: Option<f64>,
+    pub hard_limit_usd: Option<f64>,
+    /// The hard limit is ignored for the rest of this month.
+    pub overridden: bool,
+    /// New analyses are currently refused.
+    pub blocked: bool,
+}
+
+fn month_start() -> DateTime<Utc> {
+    usage::period_starts().2
+}
+
+impl ScreenshotProcessor {
+    fn month_spend(&self) -> f64 {
+        self.store
+            .usage_totals(Some(month_start()))
+            .map(|totals| totals.cost_usd)
+            .unwrap_or_else(|e| {
+                warn!("Failed to read this month's usage: {}", e);
+                0.0
+            })
+    }
+
+    fn budget_overridden(&self) -> bool {
+        *self.budget_override.lock() == Some(month_start())
+    }
+
+    pub fn budget_status(&self) -> BudgetStatus {
+        let spent = self.month_spend();
+        let overridden = self.budget_overridden();
+        BudgetStatus {
+            month_spend_usd: spent,
+            soft_limit_usd: self.config.budget_soft_limit_usd,
+            hard_limit_usd: self.config.budget_hard_limit_usd,
+            overridden,
+            blocked: !overridden && self.config.budget_hard_limit_usd.is_some_and(|limit| spent >= limit),
+        }
+    }
+
+    /// Lifts (or restores) the hard limit until the end of the current month.
+    pub fn set_budget_override(&self, enabled: bool) {
+        *self.budget_override.lock() = enabled.then(month_start);
+        info!("💰 Budget override {}", if enabled { "enabled for this month" } else { "cleared" });
+    }
+
+    /// Refuses new analyses once the hard monthly limit has been reached.
+    pub(crate) fn check_budget(&self) -> Result<(), BudgetExceeded> {
+        let Some(limit) = self.config.budget_hard_limit_usd else {
+            return Ok(());
+        };
+        if self.budget_overridden() {
+            return Ok(());
+        }
+
+        let spent = self.month_spend();
+        if spent >= limit {
+            return Err(BudgetExceeded { spent, limit });
+        }
+        Ok(())
+    }
+
+    /// Notifies the user when recording `cost` moves this month's spend past a limit.
+    pub(crate) fn notify_budget_thresholds(&self, cost: f64) {
+        if cost <= 0.0 {
+            return;
+        }
+        let spent = self.month_spend();
+        let before = spent - cost;
+        let crossed = |limit: Option<f64>| limit.filter(|&limit| before < limit && spent >= limit);
+
+        let message = if let Some(limit) = crossed(self.config.budget_hard_limit_usd) {
+            format!(
+                "Monthly LLM budget of ${:.2} reached (${:.2} spent). New screenshots won't be analyzed until next month or until you override the budget.",
+                limit, spent
+            )
+        } else if let Some(limit) = crossed(self.config.budget_soft_limit_usd) {
+            format!("LLM spend this month is ${:.2}, past the ${:.2} warning threshold.", spent, limit)
+        } else {
+            return;
+        };
+
+        warn!("💰 {}", message);
+        desktop_notification::show("Screenshot AI budget", &message);
+
+        let processor = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = processor.broadcast_telegram(&format!("💰 {}", html::escape(&message))).await {
+                warn!("Failed to send budget warning to Telegram: {}", e);
+            }
+        });
+    }
+}
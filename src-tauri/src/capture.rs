@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use tracing::info;
+use xcap::{Monitor, Window};
+
+use crate::{ProcessingResponse, ScreenshotMetadata, ScreenshotProcessor};
+
+/// A window that can be captured, for the frontend to offer in a picker.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturableWindow {
+    pub id: u32,
+    pub app_name: String,
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Re-encodes a capture as PNG. The capture library uses a newer `image` than this crate, so the
+/// pixels are handed over raw.
+fn encode_png(capture: xcap::image::RgbaImage) -> Result<Vec<u8>> {
+    let (width, height) = capture.dimensions();
+    let buffer = image::RgbaImage::from_raw(width, height, capture.into_raw())
+        .ok_or_else(|| anyhow!("Capture has unexpected dimensions"))?;
+
+    let mut png_bytes = Vec::new();
+    image::DynamicImage::ImageRgba8(buffer).write_to(&mut Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)?;
+    Ok(png_bytes)
+}
+
+/// Captures the part of the screen at `x, y` (logical screen coordinates) of the given size, clipped to
+/// the monitor containing its top-left corner.
+fn capture_region_png(x: i32, y: i32, width: u32, height: u32) -> Result<Vec<u8>> {
+    if width == 0 || height == 0 {
+        return Err(anyhow!("Region is empty"));
+    }
+
+    let monitor = Monitor::from_point(x, y).map_err(|e| anyhow!("No monitor at {}, {}: {}", x, y, e))?;
+    let capture = monitor
+        .capture_image()
+        .map_err(|e| anyhow!("Screen capture failed: {}", e))?;
+
+    // Captures are in physical pixels, which differ from screen coordinates on scaled displays
+    let scale = capture.width() as f64 / monitor.width().max(1) as f64;
+    let to_pixels = |value: f64| (value * scale).round().max(0.0) as u32;
+    let left = to_pixels((x - monitor.x()) as f64).min(capture.width());
+    let top = to_pixels((y - monitor.y()) as f64).min(capture.height());
+    let right = to_pixels((x - monitor.x()) as f64 + width as f64).min(capture.width());
+    let bottom = to_pixels((y - monitor.y()) as f64 + height as f64).min(capture.height());
+    if right <= left || bottom <= top {
+        return Err(anyhow!("Region is outside the screen"));
+    }
+
+    let region = xcap::image::imageops::crop_imm(&capture, left, top, right - left, bottom - top).to_image();
+    encode_png(region)
+}
+
+fn find_window(window_id: u32) -> Result<Window> {
+    Window::all()
+        .map_err(|e| anyhow!("Failed to list windows: {}", e))?
+        .into_iter()
+        .find(|window| window.id() == window_id)
+        .ok_or_else(|| anyhow!("Window {} not found", window_id))
+}
+
+/// Visible windows, skipping minimized ones which can't be captured.
+pub fn list_windows() -> Result<Vec<CapturableWindow>> {
+    Ok(Window::all()
+        .map_err(|e| anyhow!("Failed to list windows: {}", e))?
+        .into_iter()
+        .filter(|window| !window.is_minimized() && window.width() > 0 && window.height() > 0)
+        .map(|window| CapturableWindow {
+            id: window.id(),
+            app_name: window.app_name().to_string(),
+            title: window.title().to_string(),
+            width: window.width(),
+            height: window.height(),
+        })
+        .collect())
+}
+
+/// The window most likely in front: windows are listed front to back, so the first visible one with an
+/// owning app.
+pub(crate) fn frontmost_window() -> Option<Window> {
+    Window::all().ok()?.into_iter().find(|window| {
+        !window.is_minimized() && window.width() > 0 && window.height() > 0 && !window.app_name().is_empty()
+    })
+}
+
+/// The frontmost app's name and its window's title.
+pub(crate) fn frontmost_app_and_title() -> Option<(String, String)> {
+    frontmost_window().map(|window| (window.app_name().to_string(), window.title().to_string()))
+}
+
+/// Captures the display the frontmost window is on, or the primary display. Returns the PNG and the
+/// frontmost app's name, or nothing without capturing when that app is in `excluded_apps`.
+pub(crate) fn capture_active_display(excluded_apps: &[String]) -> Result<Option<(Vec<u8>, Option<String>)>> {
+    let front = frontmost_window();
+    if let Some(window) = &front {
+        let app = window.app_name().to_lowercase();
+        if excluded_apps.iter().any(|excluded| app.contains(&excluded.to_lowercase())) {
+            return Ok(None);
+        }
+    }
+
+    let monitor = match &front {
+        Some(window) => window.current_monitor(),
+        None => Monitor::all()
+            .map_err(|e| anyhow!("Failed to list monitors: {}", e))?
+            .into_iter()
+            .find(|monitor| monitor.is_primary())
+            .ok_or_else(|| anyhow!("No display to capture"))?,
+    };
+    let capture = monitor
+        .capture_image()
+        .map_err(|e| anyhow!("Screen capture failed: {}", e))?;
+    Ok(Some((encode_png(capture)?, front.map(|window| window.app_name().to_string()))))
+}
+
+fn capture_filename(kind: &str) -> String {
+    format!("{}-{}.png", kind, chrono::Local::now().format("%Y%m%d-%H%M%S"))
+}
+
+impl ScreenshotProcessor {
+    /// Captures a region of the screen and analyzes it.
+    pub async fn capture_region(&self, x: i32, y: i32, width: u32, height: u32) -> Result<ProcessingResponse> {
+        let png_bytes = tokio::task::spawn_blocking(move || capture_region_png(x, y, width, height))
+            .await
+            .map_err(|e| anyhow!("Capture task failed: {}", e))??;
+        info!("📸 Captured {}x{} region at {}, {}", width, height, x, y);
+
+        let metadata = ScreenshotMetadata {
+            source: Some("region_capture".to_string()),
+            filename: Some(capture_filename("region")),
+            ..Default::default()
+        };
+        self.process_screenshot(&general_purpose::STANDARD.encode(&png_bytes), Some(metadata))
+            .await
+    }
+
+    /// Captures a single window, by the id from [`list_windows`], and analyzes it.
+    pub async fn capture_window(&self, window_id: u32) -> Result<ProcessingResponse> {
+        let (png_bytes, app_name, title) = tokio::task::spawn_blocking(move || {
+            let window = find_window(window_id)?;
+            let capture = window
+                .capture_image()
+                .map_err(|e| anyhow!("Window capture failed: {}", e))?;
+            Ok::<_, anyhow::Error>((
+                encode_png(capture)?,
+                window.app_name().to_string(),
+                window.title().to_string(),
+            ))
+        })
+        .await
+        .map_err(|e| anyhow!("Capture task failed: {}", e))??;
+        info!("📸 Captured window \"{}\" of {}", title, app_name);
+
+        let metadata = ScreenshotMetadata {
+            source: Some("window_capture".to_string()),
+            app: Some(app_name).filter(|name| !name.is_empty()),
+            window_title: Some(title).filter(|title| !title.is_empty()),
+            filename: Some(capture_filename("window")),
+            ..Default::default()
+        };
+        self.process_screenshot(&general_purpose::STANDARD.encode(&png_bytes), Some(metadata))
+            .await
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    io::Cursor,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+
+use crate::{capture, Excluded, Paused, ScreenshotMetadata, ScreenshotProcessor};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1000);
+
+/// A new image copied to the clipboard, already encoded as PNG.
+struct ClipboardImage {
+    png_bytes: Vec<u8>,
+    width: usize,
+    height: usize,
+}
+
+// Clipboard screenshot watcher
+pub struct ClipboardWatcher {
+    running: Arc<AtomicBool>,
+    task_handle: tokio::task::JoinHandle<()>,
+}
+
+impl std::fmt::Debug for ClipboardWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClipboardWatcher")
+            .field("running", &self.running.load(Ordering::Relaxed))
+            .field("task_handle", &"JoinHandle { ... }")
+            .finish()
+    }
+}
+
+impl ClipboardWatcher {
+    pub fn new(processor: ScreenshotProcessor) -> Result<Self> {
+        // Fail early if the platform clipboard is unavailable
+        arboard::Clipboard::new().map_err(|e| anyhow!("Clipboard unavailable: {}", e))?;
+
+        let (tx, mut rx) = mpsc::unbounded_channel::<ClipboardImage>();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let running_poller = running.clone();
+        std::thread::spawn(move || Self::poll_clipboard(tx, running_poller));
+
+        let task_handle = tokio::spawn(async move {
+            while let Some(image) = rx.recv().await {
+                info!("📋 New clipboard image detected ({}x{})", image.width, image.height);
+                match Self::process_clipboard_image(&processor, image, true).await {
+                    // Already logged when it was held back or skipped
+                    Err(e) if e.is::<Paused>() || e.is::<Excluded>() => {}
+                    Err(e) => error!("Failed to process clipboard image: {}", e),
+                    Ok(()) => {}
+                }
+            }
+        });
+
+        info!("📋 Clipboard screenshot detection started");
+
+        Ok(Self {
+            running,
+            task_handle,
+        })
+    }
+
+    /// Polls the clipboard on a dedicated thread, since clipboard handles are not `Send` everywhere.
+    fn poll_clipboard(tx: mpsc::UnboundedSender<ClipboardImage>, running: Arc<AtomicBool>) {
+        let mut clipboard = match arboard::Clipboard::new() {
+            Ok(clipboard) => clipboard,
+            Err(e) => {
+                error!("Failed to open clipboard: {}", e);
+                return;
+            }
+        };
+
+        // Ignore whatever was already on the clipboard when watching started
+        let mut last_hash = clipboard.get_image().ok().map(|image| Self::hash_image(&image));
+
+        while running.load(Ordering::Relaxed) {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let Ok(image) = clipboard.get_image() else {
+                continue;
+            };
+
+            let hash = Self::hash_image(&image);
+            if last_hash == Some(hash) {
+                continue;
+            }
+            last_hash = Some(hash);
+
+            match Self::encode_png(&image) {
+                Ok(png_bytes) => {
+                    let message = ClipboardImage {
+                        png_bytes,
+                        width: image.width,
+                        height: image.height,
+                    };
+                    if tx.send(message).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("Failed to encode clipboard image: {}", e),
+            }
+        }
+
+        info!("📋 Clipboard polling stopped");
+    }
+
+    fn hash_image(image: &arboard::ImageData) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        image.width.hash(&mut hasher);
+        image.height.hash(&mut hasher);
+        image.bytes.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn encode_png(image: &arboard::ImageData) -> Result<Vec<u8>> {
+        let buffer = image::RgbaImage::from_raw(
+            image.width as u32,
+            image.height as u32,
+            image.bytes.to_vec(),
+        )
+        .ok_or_else(|| anyhow!("Clipboard image has unexpected dimensions"))?;
+
+        let mut png_bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(buffer)
+            .write_to(&mut Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)?;
+        Ok(png_bytes)
+    }
+
+    async fn process_clipboard_image(
+        processor: &ScreenshotProcessor,
+        image: ClipboardImage,
+        auto_detected: bool,
+    ) -> Result<()> {
+        let image_base64 = general_purpose::STANDARD.encode(&image.png_bytes);
+        // The app the image was most likely copied from, so exclusion rules apply to it
+        let front = tokio::task::spawn_blocking(capture::frontmost_app_and_title)
+            .await
+            .ok()
+            .flatten();
+
+        let metadata = ScreenshotMetadata {
+            source: Some("clipboard".to_string()),
+            app: Some(
+                front
+                    .as_ref()
+                    .map(|(app, _)| app.clone())
+                    .unwrap_or_else(|| "Clipboard".to_string()),
+            ),
+            window_title: front.and_then(|(_, title)| Some(title).filter(|title| !title.is_empty())),
+            filename: Some(format!("clipboard-{}.png", chrono::Local::now().format("%Y%m%d-%H%M%S"))),
+            auto_detected: Some(auto_detected),
+            ..Default::default()
+        };
+
+        let result = processor
+            .process_screenshot(&image_base64, Some(metadata.clone()))
+            .await?;
+
+        crate::events::emit_screenshot_processed(&result, &metadata, image.png_bytes.len(), "image/png", &image_base64);
+        processor.notify_analysis_ready(&result, "clipboard");
+
+        info!(
+            "✅ Clipboard screenshot processed (ID: {})",
+            result.analysis_id.unwrap_or_default()
+        );
+
+        Ok(())
+    }
+}
+
+impl ScreenshotProcessor {
+    /// Analyzes the image on the clipboard now, whether or not clipboard detection is on.
+    pub async fn analyze_clipboard(&self) -> Result<()> {
+        let image = tokio::task::spawn_blocking(|| -> Result<ClipboardImage> {
+            let mut clipboard = arboard::Clipboard::new().map_err(|e| anyhow!("Clipboard unavailable: {}", e))?;
+            let image = clipboard
+                .get_image()
+                .map_err(|_| anyhow!("There is no image on the clipboard"))?;
+            Ok(ClipboardImage {
+                png_bytes: ClipboardWatcher::encode_png(&image)?,
+                width: image.width,
+                height: image.height,
+            })
+        })
+        .await??;
+
+        info!("📋 Analyzing clipboard image ({}x{})", image.width, image.height);
+        ClipboardWatcher::process_clipboard_image(self, image, false).await
+    }
+}
+
+impl Drop for ClipboardWatcher {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        self.task_handle.abort();
+    }
+}
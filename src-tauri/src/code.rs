@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{debug, info, warn};
+
+use crate::{usage, AnalysisData, OutputSchema, ScreenshotProcessor};
+
+const CODE_MAX_TOKENS: u32 = 4_000;
+
+/// Code read verbatim off a screenshot, e.g. a Stack Overflow answer or an editor window.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CodeSnippet {
+    pub analysis_id: String,
+    /// Lowercase language name, e.g. `rust`, or `text` if unclear.
+    pub language: String,
+    pub code: String,
+    /// A name to save it under, with the language's extension.
+    pub file_name: String,
+    pub extracted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExtractedCode {
+    language: String,
+    code: String,
+}
+
+fn code_schema() -> OutputSchema {
+    OutputSchema {
+        name: "record_code",
+        description: "Record the code shown in the screenshot, exactly as written.",
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "language": {
+                    "type": "string",
+                    "description": "Programming language in lowercase, e.g. python, rust, bash, or text if unclear"
+                },
+                "code": {
+                    "type": "string",
+                    "description": "The code verbatim, with its indentation and line breaks"
+                }
+            },
+            "required": ["language", "code"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+const CODE_PROMPT: &str = "This screenshot shows source code. Transcribe the code exactly as written, keeping \
+indentation, blank lines and comments, so it can be pasted and run. Leave out line numbers, editor UI, \
+prompts such as `$ ` or `>>> `, and any prose around the code. If there are several separate snippets, \
+join them in order separated by a blank line.";
+
+const TEXT_FORMAT: &str = "\n\nAnswer with the language on the first line as `LANGUAGE: <name>`, \
+then the code in a single fenced code block.";
+
+/// Parses a response in `TEXT_FORMAT`, for providers without structured output.
+fn parse_code_text(text: &str) -> ExtractedCode {
+    let language = text
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("LANGUAGE:"))
+        .map(|language| language.trim().to_string())
+        .unwrap_or_default();
+
+    let code = match text.split_once("```") {
+        // Skip the language tag after the opening fence
+        Some((_, rest)) => {
+            let rest = rest.split_once('\n').map(|(_, code)| code).unwrap_or(rest);
+            rest.rsplit_once("```").map(|(code, _)| code).unwrap_or(rest).to_string()
+        }
+        None => text
+            .lines()
+            .filter(|line| !line.trim().starts_with("LANGUAGE:"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+    };
+    ExtractedCode { language, code }
+}
+
+/// Lowercases a language name and maps common aliases to one name.
+fn normalize_language(language: &str) -> String {
+    let language = language.trim().trim_start_matches('.').to_lowercase();
+    match language.as_str() {
+        "" | "plain" | "plaintext" | "unknown" | "none" => "text",
+        "py" | "python3" => "python",
+        "js" | "node" | "nodejs" => "javascript",
+        "ts" => "typescript",
+        "rs" => "rust",
+        "sh" | "shell" | "zsh" | "console" => "bash",
+        "c++" => "cpp",
+        "c#" | "cs" => "csharp",
+        "golang" => "go",
+        "yml" => "yaml",
+        other => other,
+    }
+    .to_string()
+}
+
+fn extension_for(language: &str) -> &'static str {
+    match language {
+        "python" => "py",
+        "javascript" => "js",
+        "typescript" => "ts",
+        "rust" => "rs",
+        "bash" => "sh",
+        "go" => "go",
+        "java" => "java",
+        "kotlin" => "kt",
+        "swift" => "swift",
+        "c" => "c",
+        "cpp" => "cpp",
+        "csharp" => "cs",
+        "ruby" => "rb",
+        "php" => "php",
+        "sql" => "sql",
+        "html" => "html",
+        "css" => "css",
+        "json" => "json",
+        "yaml" => "yaml",
+        "toml" => "toml",
+        "markdown" => "md",
+        _ => "txt",
+    }
+}
+
+pub(crate) fn is_code(content_type: &str) -> bool {
+    content_type.trim().eq_ignore_ascii_case("code")
+}
+
+impl ScreenshotProcessor {
+    async fn extract_code(&self, analysis_id: &str, analysis: &AnalysisData) -> Result<CodeSnippet> {
+        let image = self.llm_image(&analysis.image_data).await;
+        let (structured, records) = usage::track(self.provider.complete_json(
+            CODE_PROMPT,
+            &[&image],
+            &code_schema(),
+            CODE_MAX_TOKENS,
+        ))
+        .await;
+        self.save_usage(Some(analysis_id), &records);
+
+        let extracted = match structured.and_then(|json| Ok(serde_json::from_str::<ExtractedCode>(&json)?)) {
+            Ok(extracted) => extracted,
+            Err(e) => {
+                debug!("Structured code extraction unavailable, parsing text instead: {}", e);
+                let prompt = format!("{}{}", CODE_PROMPT, TEXT_FORMAT);
+                let (text, records) = usage::track(self.provider.complete(&prompt, &[&image], CODE_MAX_TOKENS)).await;
+                self.save_usage(Some(analysis_id), &records);
+                parse_code_text(&text?)
+            }
+        };
+
+        let code = extracted.code.trim_matches('\n').trim_end().to_string();
+        if code.trim().is_empty() {
+            return Err(anyhow!("No code found in the screenshot"));
+        }
+        let language = normalize_language(&extracted.language);
+        Ok(CodeSnippet {
+            analysis_id: analysis_id.to_string(),
+            file_name: format!("snippet-{}.{}", &analysis_id[..8.min(analysis_id.len())], extension_for(&language)),
+            language,
+            code: format!("{}\n", code),
+            extracted_at: Utc::now(),
+        })
+    }
+
+    /// Extracts the code from a new analysis of a code screenshot in the background.
+    pub(crate) fn process_code(&self, analysis_id: &str, analysis: &AnalysisData) {
+        if !self.config.code_extraction || !is_code(&analysis.content_analysis.content_type) {
+            return;
+        }
+
+        let processor = self.clone();
+        let analysis_id = analysis_id.to_string();
+        let analysis = analysis.clone();
+        tokio::spawn(async move {
+            let result = processor
+                .extract_code(&analysis_id, &analysis)
+                .await
+                .and_then(|snippet| processor.store.save_code_snippet(&snippet).map(|_| snippet));
+            match result {
+                Ok(snippet) => info!(
+                    "💻 Extracted {} lines of {} from {}",
+                    snippet.code.lines().count(),
+                    snippet.language,
+                    analysis_id
+                ),
+                Err(e) => warn!("Code extraction for {} failed: {}", analysis_id, e),
+            }
+        });
+    }
+
+    pub fn get_code_snippet(&self, analysis_id: &str) -> Result<Option<CodeSnippet>> {
+        self.store.code_snippet(analysis_id)
+    }
+
+    fn code_snippet(&self, analysis_id: &str) -> Result<CodeSnippet> {
+        self.store
+            .code_snippet(analysis_id)?
+            .ok_or_else(|| anyhow!("No code was extracted from analysis {}", analysis_id))
+    }
+
+    /// Puts an analysis's extracted code on the clipboard.
+    pub fn copy_code_to_clipboard(&self, analysis_id: &str) -> Result<()> {
+        let snippet = self.code_snippet(analysis_id)?;
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.set_text(snippet.code))
+            .map_err(|e| anyhow!("Failed to copy to the clipboard: {}", e))
+    }
+
+    /// Writes an analysis's extracted code to `path`.
+    pub fn save_code_to_file(&self, analysis_id: &str, path: &Path) -> Result<()> {
+        let snippet = self.code_snippet(analysis_id)?;
+        std::fs::write(path, snippet.code).with_context(|| format!("Failed to write {}", path.display()))?;
+        info!("💾 Saved code from {} to {}", analysis_id, path.display());
+        Ok(())
+    }
+}
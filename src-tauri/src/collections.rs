@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    events::{emit_event, CollectionsUpdated, UiEvent},
+    AnalysisPage, ScreenshotProcessor,
+};
+
+/// What a collection rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    ContentType,
+    /// Matches if any research topic does.
+    Topic,
+    /// Matches if any tag does.
+    Tag,
+    App,
+    Source,
+    Summary,
+    Url,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOp {
+    Equals,
+    Contains,
+}
+
+/// One predicate, compared case-insensitively, e.g. `topic contains "rust"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollectionRule {
+    pub field: RuleField,
+    pub op: RuleOp,
+    pub value: String,
+}
+
+/// Whether an analysis must match every rule or just one.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleMatch {
+    #[default]
+    All,
+    Any,
+}
+
+/// A saved search: the analyses matching its rules, kept up to date as new ones arrive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "match", default)]
+    pub match_mode: RuleMatch,
+    pub rules: Vec<CollectionRule>,
+    pub created_at: DateTime<Utc>,
+    /// Analyses currently in the collection.
+    #[serde(default)]
+    pub count: usize,
+}
+
+/// A collection as created or edited by the user.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CollectionInput {
+    pub name: String,
+    #[serde(rename = "match", default)]
+    pub match_mode: RuleMatch,
+    pub rules: Vec<CollectionRule>,
+}
+
+/// A collection's new size, sent to the UI when an analysis is added to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionCount {
+    pub id: String,
+    pub name: String,
+    pub count: usize,
+}
+
+impl CollectionInput {
+    fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("Collection name can't be empty"));
+        }
+        if self.rules.is_empty() {
+            return Err(anyhow!("A collection needs at least one rule"));
+        }
+        if self.rules.iter().any(|rule| rule.value.trim().is_empty()) {
+            return Err(anyhow!("Rule values can't be empty"));
+        }
+        Ok(())
+    }
+}
+
+/// A SQL condition on the `analyses` table and its parameters, for `?` placeholders in order.
+pub(crate) fn where_clause(match_mode: RuleMatch, rules: &[CollectionRule]) -> (String, Vec<String>) {
+    let compare = |column: &str, op: RuleOp| match op {
+        RuleOp::Equals => format!("lower({}) = lower(?)", column),
+        RuleOp::Contains => format!("instr(lower({}), lower(?)) > 0", column),
+    };
+    let any_element = |path: &str, op: RuleOp| {
+        format!(
+            "EXISTS (SELECT 1 FROM json_each(content_analysis, '{}') WHERE {})",
+            path,
+            compare("value", op)
+        )
+    };
+
+    let conditions: Vec<String> = rules
+        .iter()
+        .map(|rule| match rule.field {
+            RuleField::ContentType => compare("json_extract(content_analysis, '$.content_type')", rule.op),
+            RuleField::Topic => any_element("$.research_topics", rule.op),
+            RuleField::Tag => any_element("$.tags", rule.op),
+            RuleField::App => compare("json_extract(metadata, '$.app')", rule.op),
+            RuleField::Source => compare("source", rule.op),
+            RuleField::Summary => compare("brief_summary", rule.op),
+            RuleField::Url => compare("json_extract(content_analysis, '$.webpage_url')", rule.op),
+        })
+        .map(|condition| format!("({})", condition))
+        .collect();
+
+    let joiner = match match_mode {
+        RuleMatch::All => " AND ",
+        RuleMatch::Any => " OR ",
+    };
+    let sql = if conditions.is_empty() { "0".to_string() } else { conditions.join(joiner) };
+    (sql, rules.iter().map(|rule| rule.value.trim().to_string()).collect())
+}
+
+impl ScreenshotProcessor {
+    /// Every collection with its current size, oldest first.
+    pub fn list_collections(&self) -> Result<Vec<Collection>> {
+        self.store
+            .list_collections()?
+            .into_iter()
+            .map(|mut collection| {
+                let (sql, params) = where_clause(collection.match_mode, &collection.rules);
+                collection.count = self.store.count_where(&sql, &params)?;
+                Ok(collection)
+            })
+            .collect()
+    }
+
+    pub fn create_collection(&self, input: CollectionInput) -> Result<Collection> {
+        input.validate()?;
+        let collection = Collection {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: input.name.trim().to_string(),
+            match_mode: input.match_mode,
+            rules: input.rules,
+            created_at: Utc::now(),
+            count: 0,
+        };
+        self.store.save_collection(&collection)?;
+        info!("📚 Created collection \"{}\"", collection.name);
+        self.collection(&collection.id)
+    }
+
+    pub fn update_collection(&self, id: &str, input: CollectionInput) -> Result<Collection> {
+        input.validate()?;
+        let mut collection = self
+            .store
+            .get_collection(id)?
+            .ok_or_else(|| anyhow!("Collection {} not found", id))?;
+        collection.name = input.name.trim().to_string();
+        collection.match_mode = input.match_mode;
+        collection.rules = input.rules;
+        self.store.save_collection(&collection)?;
+        self.collection(id)
+    }
+
+    /// Deletes a collection, leaving its analyses alone. Returns false if it didn't exist.
+    pub fn delete_collection(&self, id: &str) -> Result<bool> {
+        self.store.delete_collection(id)
+    }
+
+    fn collection(&self, id: &str) -> Result<Collection> {
+        let mut collection = self
+            .store
+            .get_collection(id)?
+            .ok_or_else(|| anyhow!("Collection {} not found", id))?;
+        let (sql, params) = where_clause(collection.match_mode, &collection.rules);
+        collection.count = self.store.count_where(&sql, &params)?;
+        Ok(collection)
+    }
+
+    /// A collection's analyses, newest first; `page` is 1-based.
+    pub fn collection_analyses(
+        &self,
+        id: &str,
+        page: usize,
+        page_size: usize,
+        include_images: bool,
+    ) -> Result<AnalysisPage> {
+        let collection = self.collection(id)?;
+        let page = page.max(1);
+        let page_size = page_size.clamp(1, 100);
+        let (sql, params) = where_clause(collection.match_mode, &collection.rules);
+
+        let analyses = self
+            .store
+            .list_where(&sql, &params, "timestamp DESC", (page - 1) * page_size, page_size)?
+            .into_iter()
+            .map(|(id, analysis)| self.analysis_record(id, analysis, include_images))
+            .collect();
+
+        Ok(AnalysisPage {
+            analyses,
+            page,
+            page_size,
+            total: collection.count,
+        })
+    }
+
+    /// Tells the UI which collections a new analysis landed in, with their new sizes.
+    pub(crate) fn notify_collections(&self, analysis_id: &str) {
+        let collections = match self.store.list_collections() {
+            Ok(collections) => collections,
+            Err(e) => {
+                warn!("Failed to load collections: {}", e);
+                return;
+            }
+        };
+
+        let mut updated = Vec::new();
+        for collection in collections {
+            let (sql, params) = where_clause(collection.match_mode, &collection.rules);
+            let count = self
+                .store
+                .matches_where(analysis_id, &sql, &params)
+                .and_then(|matched| matched.then(|| self.store.count_where(&sql, &params)).transpose());
+            match count {
+                Ok(Some(count)) => updated.push(CollectionCount {
+                    id: collection.id,
+                    name: collection.name,
+                    count,
+                }),
+                Ok(None) => {}
+                Err(e) => warn!("Failed to match collection \"{}\": {}", collection.name, e),
+            }
+        }
+
+        if updated.is_empty() {
+            return;
+        }
+        emit_event(UiEvent::CollectionsUpdated(CollectionsUpdated {
+            analysis_id: analysis_id.to_string(),
+            collections: updated,
+        }));
+    }
+}
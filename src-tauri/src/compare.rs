@@ -0,0 +1,246 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info};
+
+use crate::{usage, AnalysisData, OutputSchema, ScreenshotProcessor};
+
+const COMPARE_MAX_TOKENS: u32 = 1_200;
+
+/// How one part of the screenshot changed between the two images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScreenshotDifference {
+    pub kind: ChangeKind,
+    /// The part of the screen it concerns, e.g. "toolbar" or "Q3 revenue bar".
+    pub area: String,
+    pub description: String,
+}
+
+/// What changed from the first screenshot to the second.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisComparison {
+    pub id_a: String,
+    pub id_b: String,
+    pub summary: String,
+    pub differences: Vec<ScreenshotDifference>,
+    /// Notable things that stayed the same.
+    pub unchanged: Vec<String>,
+    pub compared_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompareRequest {
+    pub id_a: String,
+    pub id_b: String,
+}
+
+/// The part of the response the model fills in.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ComparisonBody {
+    summary: String,
+    differences: Vec<ScreenshotDifference>,
+    unchanged: Vec<String>,
+}
+
+fn comparison_schema() -> OutputSchema {
+    OutputSchema {
+        name: "record_screenshot_comparison",
+        description: "Record what changed between the first screenshot and the second.",
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "summary": {
+                    "type": "string",
+                    "description": "One or two sentences on how the second screenshot differs from the first"
+                },
+                "differences": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "kind": { "type": "string", "enum": ["added", "removed", "changed"] },
+                            "area": { "type": "string", "description": "The part of the screen it concerns" },
+                            "description": { "type": "string", "description": "What exactly changed" }
+                        },
+                        "required": ["kind", "area", "description"],
+                        "additionalProperties": false
+                    }
+                },
+                "unchanged": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Notable things that are the same in both"
+                }
+            },
+            "required": ["summary", "differences", "unchanged"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+fn comparison_prompt(a: &AnalysisData, b: &AnalysisData) -> String {
+    format!(
+        "You are given two screenshots. The first was taken {} and shows: {}\n\
+         The second was taken {} and shows: {}\n\n\
+         Compare them as a before and after, e.g. a UI before and after a change or two versions of a chart. \
+         List every visible difference (added, removed or changed elements, text and values), then the notable \
+         things that stayed the same. Ignore differences in scale or compression.",
+        a.timestamp.format("%Y-%m-%d %H:%M UTC"),
+        a.brief_summary.trim(),
+        b.timestamp.format("%Y-%m-%d %H:%M UTC"),
+        b.brief_summary.trim(),
+    )
+}
+
+const TEXT_FORMAT: &str = "\n\nAnswer in exactly this format:\n\
+SUMMARY: <one or two sentences>\n\
+DIFFERENCES:\n\
+- <added|removed|changed> | <area> | <description>\n\
+UNCHANGED:\n\
+- <item>";
+
+/// Parses a response in `TEXT_FORMAT`, for providers without structured output.
+fn parse_comparison_text(text: &str) -> ComparisonBody {
+    let mut body = ComparisonBody::default();
+    let mut section = "";
+    for line in text.lines().map(str::trim) {
+        if let Some(summary) = line.strip_prefix("SUMMARY:") {
+            body.summary = summary.trim().to_string();
+        } else if line.starts_with("DIFFERENCES:") || line.starts_with("UNCHANGED:") {
+            section = if line.starts_with("DIFFERENCES:") { "differences" } else { "unchanged" };
+        } else if let Some(item) = line.strip_prefix("- ").or_else(|| line.strip_prefix("• ")) {
+            match section {
+                "differences" => {
+                    let parts: Vec<&str> = item.splitn(3, '|').map(str::trim).collect();
+                    let difference = match parts.as_slice() {
+                        [kind, area, description] => ScreenshotDifference {
+                            kind: match kind.to_lowercase().as_str() {
+                                "added" => ChangeKind::Added,
+                                "removed" => ChangeKind::Removed,
+                                _ => ChangeKind::Changed,
+                            },
+                            area: area.to_string(),
+                            description: description.to_string(),
+                        },
+                        _ => ScreenshotDifference {
+                            kind: ChangeKind::Changed,
+                            area: String::new(),
+                            description: item.to_string(),
+                        },
+                    };
+                    body.differences.push(difference);
+                }
+                "unchanged" => body.unchanged.push(item.to_string()),
+                _ => {}
+            }
+        }
+    }
+    if body.summary.is_empty() {
+        body.summary = text.lines().next().unwrap_or_default().trim().to_string();
+    }
+    body
+}
+
+impl ScreenshotProcessor {
+    /// Sends both screenshots in one request and asks what changed from the first to the second.
+    pub async fn compare_analyses(&self, id_a: &str, id_b: &str) -> Result<AnalysisComparison> {
+        if id_a == id_b {
+            return Err(anyhow!("Can't compare an analysis with itself"));
+        }
+        let a = self
+            .store
+            .get(id_a)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", id_a))?;
+        let b = self
+            .store
+            .get(id_b)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", id_b))?;
+        self.check_budget()?;
+
+        info!("🔀 Comparing analyses {} and {}", id_a, id_b);
+
+        // A local-only screenshot isn't sent to a cloud provider, even alongside another one
+        let local_only = [&a, &b].iter().any(|analysis| analysis.metadata.local_only == Some(true));
+        let provider = if local_only && !self.config.privacy_mode {
+            self.local_only()?.provider
+        } else {
+            self.provider.clone()
+        };
+
+        let image_a = self.llm_image(&a.image_data).await;
+        let image_b = self.llm_image(&b.image_data).await;
+        let images = [&image_a, &image_b];
+        let prompt = comparison_prompt(&a, &b);
+
+        let (structured, records) = usage::track(provider.complete_json(
+            &prompt,
+            &images,
+            &comparison_schema(),
+            COMPARE_MAX_TOKENS,
+        ))
+        .await;
+        self.save_usage(None, &records);
+
+        let body = match structured.and_then(|json| Ok(serde_json::from_str::<ComparisonBody>(&json)?)) {
+            Ok(body) => body,
+            Err(e) => {
+                debug!("Structured comparison unavailable, parsing text instead: {}", e);
+                let prompt = format!("{}{}", prompt, TEXT_FORMAT);
+                let (text, records) = usage::track(provider.complete(&prompt, &images, COMPARE_MAX_TOKENS)).await;
+                self.save_usage(None, &records);
+                parse_comparison_text(&text?)
+            }
+        };
+
+        Ok(AnalysisComparison {
+            id_a: id_a.to_string(),
+            id_b: id_b.to_string(),
+            summary: body.summary,
+            differences: body.differences,
+            unchanged: body.unchanged,
+            compared_at: Utc::now(),
+        })
+    }
+}
+
+pub async fn handle_compare(
+    State(processor): State<ScreenshotProcessor>,
+    Json(request): Json<CompareRequest>,
+) -> Result<ResponseJson<AnalysisComparison>, StatusCode> {
+    if request.id_a == request.id_b {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    for id in [&request.id_a, &request.id_b] {
+        match processor.get_analysis(id, false) {
+            Ok(Some(_)) => {}
+            Ok(None) => return Err(StatusCode::NOT_FOUND),
+            Err(e) => {
+                error!("Failed to load analysis {}: {}", id, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+
+    match processor.compare_analyses(&request.id_a, &request.id_b).await {
+        Ok(comparison) => Ok(ResponseJson(comparison)),
+        Err(e) if e.is::<crate::BudgetExceeded>() => Err(StatusCode::PAYMENT_REQUIRED),
+        Err(e) => {
+            error!("Comparing {} and {} failed: {}", request.id_a, request.id_b, e);
+            Err(StatusCode::BAD_GATEWAY)
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "desktop")]
+use std::time::Duration;
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+use tracing::{info, warn};
+
+use crate::{
+    privacy,
+    redaction::{describe, SensitiveContentBlocked, SensitiveKind},
+    InvalidImage, ScreenshotMetadata, ScreenshotProcessor,
+};
+
+/// Side of the downscaled copy the classifier looks at.
+const SAMPLE_SIZE: u32 = 256;
+/// Screen content is mostly flat runs of one color; photos have almost none.
+const MAX_PHOTO_FLAT_RATIO: f32 = 0.3;
+/// Share of skin-toned pixels above which a photo is flagged.
+const MIN_SKIN_RATIO: f32 = 0.3;
+/// How long the dialog waits for an answer before the screenshot is blocked. The upload's request
+/// and queue slot are held meanwhile, so this is kept short.
+#[cfg(feature = "desktop")]
+const ASK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What to do with a screenshot the local classifier flags as a private photo.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentFilterMode {
+    /// Don't classify screenshots.
+    #[default]
+    Off,
+    /// Refuse to analyze it.
+    Block,
+    /// Ask in a dialog whether to analyze it; blocked if nobody answers.
+    Ask,
+    /// Analyze it with the local model only, so it never leaves the machine.
+    LocalOnly,
+}
+
+/// Whether JPEG `bytes` carry EXIF naming the camera, which screenshots never do.
+fn has_camera_exif(bytes: &[u8]) -> bool {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return false;
+    }
+
+    let mut offset = 2;
+    while let Some([0xFF, marker, high, low]) = bytes.get(offset..offset + 4) {
+        if !(0xE0..=0xEF).contains(marker) {
+            return false;
+        }
+        let length = u16::from_be_bytes([*high, *low]) as usize;
+        let Some(segment) = bytes.get(offset + 4..offset + 2 + length) else {
+            return false;
+        };
+        if *marker == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return tiff_has_camera(tiff);
+            }
+        }
+        offset += 2 + length;
+    }
+    false
+}
+
+/// Looks for the Make or Model tag in the first IFD of an EXIF TIFF block.
+fn tiff_has_camera(tiff: &[u8]) -> bool {
+    let little_endian = match tiff.get(..2) {
+        Some(b"II") => true,
+        Some(b"MM") => false,
+        _ => return false,
+    };
+    let read_u16 = |at: usize| {
+        tiff.get(at..at + 2).map(|b| {
+            if little_endian {
+                u16::from_le_bytes([b[0], b[1]])
+            } else {
+                u16::from_be_bytes([b[0], b[1]])
+            }
+        })
+    };
+    let read_u32 = |at: usize| {
+        tiff.get(at..at + 4).map(|b| {
+            if little_endian {
+                u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+            } else {
+                u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+            }
+        })
+    };
+
+    let Some(ifd) = read_u32(4).map(|offset| offset as usize) else {
+        return false;
+    };
+    let entries = read_u16(ifd).unwrap_or(0) as usize;
+    (0..entries)
+        .filter_map(|i| read_u16(ifd + 2 + i * 12))
+        .any(|tag| tag == 0x010F || tag == 0x0110)
+}
+
+/// Skin tones by the usual YCbCr bounds, which hold across complexions.
+fn is_skin(r: u8, g: u8, b: u8) -> bool {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let cb = 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+    let cr = 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+    (77.0..=127.0).contains(&cb) && (133.0..=173.0).contains(&cr) && r > 60.0
+}
+
+/// Flags camera photos, and photos that are mostly skin, as opposed to screen content.
+fn classify(bytes: &[u8]) -> Result<Vec<SensitiveKind>> {
+    let mut kinds = Vec::new();
+    if has_camera_exif(bytes) {
+        kinds.push(SensitiveKind::CameraPhoto);
+    }
+
+    let sample = image::load_from_memory(bytes)
+        .map_err(|e| anyhow!("Failed to decode image: {}", e))?
+        .thumbnail(SAMPLE_SIZE, SAMPLE_SIZE)
+        .to_rgb8();
+    let (width, height) = sample.dimensions();
+    let total = (width * height).max(1) as f32;
+
+    let mut flat = 0u32;
+    let mut skin = 0u32;
+    for (x, y, pixel) in sample.enumerate_pixels() {
+        if x + 1 < width && sample.get_pixel(x + 1, y) == pixel {
+            flat += 1;
+        }
+        let [r, g, b] = pixel.0;
+        if is_skin(r, g, b) {
+            skin += 1;
+        }
+    }
+
+    let photographic = (flat as f32 / total) < MAX_PHOTO_FLAT_RATIO;
+    if photographic && (skin as f32 / total) >= MIN_SKIN_RATIO {
+        kinds.push(SensitiveKind::ExposedSkin);
+    }
+    Ok(kinds)
+}
+
+/// Asks in a dialog whether to analyze a flagged screenshot. No when there's no window to ask in,
+/// or no answer in time.
+#[cfg(feature = "desktop")]
+async fn ask_to_analyze(kinds: &[SensitiveKind], metadata: &Option<ScreenshotMetadata>) -> bool {
+    let Some(window) = crate::APP_HANDLE.get().and_then(|handle| handle.get_window("main")) else {
+        return false;
+    };
+    let name = metadata
+        .as_ref()
+        .and_then(|m| m.filename.clone())
+        .unwrap_or_else(|| "A new screenshot".to_string());
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    tauri::api::dialog::ask(
+        Some(&window),
+        "Possibly Private Image",
+        format!(
+            "{} looks like {}. Send it to the AI provider for analysis anyway?",
+            name,
+            describe(kinds)
+        ),
+        move |answer| {
+            let _ = tx.send(answer);
+        },
+    );
+    matches!(tokio::time::timeout(ASK_TIMEOUT, rx).await, Ok(Ok(true)))
+}
+
+/// Without the desktop app there's no window to ask in.
+#[cfg(not(feature = "desktop"))]
+async fn ask_to_analyze(_kinds: &[SensitiveKind], _metadata: &Option<ScreenshotMetadata>) -> bool {
+    false
+}
+
+impl ScreenshotProcessor {
+    /// Classifies the image locally before it's uploaded, blocking it, asking about it or keeping it
+    /// local as configured. Returns the metadata to analyze it with.
+    pub(crate) async fn screen_content(
+        &self,
+        image_base64: &str,
+        mut metadata: Option<ScreenshotMetadata>,
+    ) -> Result<Option<ScreenshotMetadata>> {
+        let mode = self.config.content_filter;
+        // Nothing leaves the machine either way
+        if mode == ContentFilterMode::Off || self.config.privacy_mode || privacy::wants_local_only(&metadata) {
+            return Ok(metadata);
+        }
+
+        // The image may have arrived as a data URL
+        let encoded = image_base64.rsplit(',').next().unwrap_or_default();
+        let bytes = general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| InvalidImage(format!("Invalid base64: {}", e)))?;
+        // Checked before decoding, so oversized images are turned away before they fill memory
+        self.config
+            .image_policy
+            .validate(&bytes)
+            .map_err(|e| InvalidImage(e.to_string()))?;
+        let kinds = tokio::task::spawn_blocking(move || classify(&bytes))
+            .await
+            .map_err(|e| anyhow!("Content filter task failed: {}", e))??;
+        if kinds.is_empty() {
+            return Ok(metadata);
+        }
+
+        match mode {
+            ContentFilterMode::LocalOnly => {
+                info!("🛡️ Analyzing locally only: screenshot looks like {}", describe(&kinds));
+                metadata.get_or_insert_with(Default::default).local_only = Some(true);
+                Ok(metadata)
+            }
+            ContentFilterMode::Ask if ask_to_analyze(&kinds, &metadata).await => {
+                info!("🛡️ Analyzing screenshot that looks like {}, as confirmed", describe(&kinds));
+                Ok(metadata)
+            }
+            _ => {
+                warn!("🛡️ Blocked screenshot that looks like {}", describe(&kinds));
+                Err(SensitiveContentBlocked { kinds }.into())
+            }
+        }
+    }
+}
@@ -0,0 +1,288 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Path as AxumPath, State},
+    response::Json as ResponseJson,
+    Json,
+};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use image::{imageops::FilterType, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use tracing::{error, info};
+
+use crate::{
+    api_error::{ApiError, ErrorCode},
+    providers::{ChatMessage, ChatRole},
+    usage, AnalysisData, ImageVariant, InvalidImage, ProcessedImage, ScreenshotProcessor,
+};
+
+const FOLLOWUP_MAX_TOKENS: u32 = 800;
+const MAX_QUESTION_CHARS: usize = 2_000;
+/// Smaller selections are refused; there's nothing in them to read.
+const MIN_REGION_SIDE: u32 = 8;
+/// Selections are scaled up to at least this long an edge so small text stays legible to the model.
+const MIN_REGION_EDGE: u32 = 768;
+
+/// One question or answer in the follow-up conversation about a screenshot.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatTurn {
+    pub role: ChatRole,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ChatTurn {
+    fn new(role: ChatRole, content: String) -> Self {
+        Self {
+            role,
+            content,
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Part of a stored screenshot, in pixels of its full image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegionRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A question about one area of a screenshot.
+#[derive(Debug, Deserialize)]
+pub struct RegionQuestion {
+    pub rect: RegionRect,
+    pub question: String,
+}
+
+fn check_question(question: &str) -> Result<&str> {
+    let question = question.trim();
+    if question.is_empty() {
+        return Err(anyhow!("Question must not be empty"));
+    }
+    if question.chars().count() > MAX_QUESTION_CHARS {
+        return Err(anyhow!("Question is too long (max {} characters)", MAX_QUESTION_CHARS));
+    }
+    Ok(question)
+}
+
+/// Crops `bytes` to `rect`, clipped to the image, and scales small selections up, as PNG.
+/// Returns the crop along with the area it actually covers.
+fn crop_region(bytes: &[u8], rect: RegionRect) -> Result<(ProcessedImage, RegionRect)> {
+    let image = image::load_from_memory(bytes).map_err(|e| anyhow!("Failed to decode stored image: {}", e))?;
+    let (x, y) = (rect.x.min(image.width()), rect.y.min(image.height()));
+    let width = rect.width.min(image.width() - x);
+    let height = rect.height.min(image.height() - y);
+    if width < MIN_REGION_SIDE || height < MIN_REGION_SIDE {
+        return Err(InvalidImage(format!(
+            "Selected area is too small or outside the {}x{} image",
+            image.width(),
+            image.height()
+        ))
+        .into());
+    }
+
+    let mut region = image.crop_imm(x, y, width, height);
+    if width.max(height) < MIN_REGION_EDGE {
+        region = region.resize(MIN_REGION_EDGE, MIN_REGION_EDGE, FilterType::CatmullRom);
+    }
+    let mut png = Vec::new();
+    region.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+    let region = ProcessedImage {
+        size_bytes: png.len(),
+        base64_data: general_purpose::STANDARD.encode(&png),
+        media_type: "image/png".to_string(),
+    };
+    Ok((region, RegionRect { x, y, width, height }))
+}
+
+/// Opening message that carries the screenshot and what was already concluded about it.
+fn context_preamble(analysis: &AnalysisData) -> String {
+    let content = &analysis.content_analysis;
+    let topics = if content.research_topics.is_empty() {
+        "none".to_string()
+    } else {
+        content.research_topics.join(", ")
+    };
+
+    format!(
+        "This is a screenshot I captured ({}). You already analyzed it:\n\n\
+         Summary: {}\n\
+         Content type: {}\n\
+         Webpage URL: {}\n\
+         Research topics: {}\n\
+         Likely intent: {}\n\n\
+         I have some follow-up questions about it.",
+        analysis.source,
+        analysis.brief_summary,
+        content.content_type,
+        content.webpage_url.as_deref().unwrap_or("none"),
+        topics,
+        content.user_intent,
+    )
+}
+
+impl ScreenshotProcessor {
+    /// Asks a follow-up question about a stored analysis, keeping the screenshot and earlier turns in context.
+    pub async fn ask_followup(&self, analysis_id: &str, question: &str) -> Result<ChatTurn> {
+        let question = check_question(question)?;
+
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+        let history = self.store.conversation(analysis_id)?;
+
+        let mut messages = vec![
+            ChatMessage::user(context_preamble(&analysis)),
+            ChatMessage::assistant("Sure, what would you like to know?"),
+        ];
+        messages.extend(history.into_iter().map(|turn| ChatMessage {
+            role: turn.role,
+            content: turn.content,
+        }));
+        messages.push(ChatMessage::user(question));
+
+        info!("💬 Follow-up question for analysis {}", analysis_id);
+
+        // Questions about a local-only screenshot stay local too
+        let provider = if analysis.metadata.local_only == Some(true) && !self.config.privacy_mode {
+            self.local_only()?.provider
+        } else {
+            self.provider.clone()
+        };
+
+        let image = self.llm_image(&analysis.image_data).await;
+        let (answer, records) =
+            usage::track(provider.chat(&messages, &[&image], FOLLOWUP_MAX_TOKENS)).await;
+        self.save_usage(Some(analysis_id), &records);
+        let answer = answer?;
+
+        // Only persist the exchange once the provider has answered
+        self.store
+            .append_turn(analysis_id, &ChatTurn::new(ChatRole::User, question.to_string()))?;
+        let reply = ChatTurn::new(ChatRole::Assistant, answer);
+        self.store.append_turn(analysis_id, &reply)?;
+
+        Ok(reply)
+    }
+
+    /// Asks about one area of a stored screenshot, sending only that area cropped from the full
+    /// image. The exchange joins the follow-up conversation, noting which area it was about.
+    pub async fn ask_about_region(&self, analysis_id: &str, rect: RegionRect, question: &str) -> Result<ChatTurn> {
+        let question = check_question(question)?;
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+        let (bytes, _) = self
+            .analysis_image(analysis_id, ImageVariant::Full)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+        let (region, rect) = tokio::task::spawn_blocking(move || crop_region(&bytes, rect))
+            .await
+            .map_err(|e| anyhow!("Cropping task failed: {}", e))??;
+
+        let prompt = format!(
+            "{}\n\nThe image is only the area I selected in the screenshot ({}x{} pixels at {}, {}). \
+             Answer about that area: {}",
+            context_preamble(&analysis),
+            rect.width,
+            rect.height,
+            rect.x,
+            rect.y,
+            question
+        );
+
+        info!("🔍 Region question for analysis {}", analysis_id);
+
+        // Questions about a local-only screenshot stay local too
+        let provider = if analysis.metadata.local_only == Some(true) && !self.config.privacy_mode {
+            self.local_only()?.provider
+        } else {
+            self.provider.clone()
+        };
+
+        let image = self.llm_image(&region).await;
+        let (answer, records) = usage::track(provider.complete(&prompt, &[&image], FOLLOWUP_MAX_TOKENS)).await;
+        self.save_usage(Some(analysis_id), &records);
+        let answer = answer?;
+
+        let asked = format!(
+            "About the selected area ({}x{} at {}, {}): {}",
+            rect.width, rect.height, rect.x, rect.y, question
+        );
+        self.store
+            .append_turn(analysis_id, &ChatTurn::new(ChatRole::User, asked))?;
+        let reply = ChatTurn::new(ChatRole::Assistant, answer);
+        self.store.append_turn(analysis_id, &reply)?;
+
+        Ok(reply)
+    }
+
+    /// Returns the follow-up conversation for an analysis, oldest turn first.
+    pub fn conversation(&self, analysis_id: &str) -> Result<Vec<ChatTurn>> {
+        self.store.conversation(analysis_id)
+    }
+}
+
+/// `POST /analysis/:id/region`, a question about one area of the screenshot.
+pub async fn handle_region_question(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Json(request): Json<RegionQuestion>,
+) -> Result<ResponseJson<ChatTurn>, ApiError> {
+    match processor.get_analysis(&analysis_id, false) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ApiError::not_found(format!("Analysis {} not found", analysis_id))),
+        Err(e) => {
+            error!("Failed to load analysis {}: {}", analysis_id, e);
+            return Err(e.into());
+        }
+    }
+    check_question(&request.question).map_err(|e| ApiError::new(ErrorCode::BadRequest, e.to_string()))?;
+
+    processor
+        .ask_about_region(&analysis_id, request.rect, &request.question)
+        .await
+        .map(ResponseJson)
+        .map_err(|e| {
+            error!("Region question for analysis {} failed: {}", analysis_id, e);
+            e.into()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, RgbImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::new(width, height))
+            .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn crop_region_clips_rect_to_image() {
+        let rect = RegionRect { x: 150, y: 20, width: 100, height: 500 };
+
+        let (_, cropped) = crop_region(&png(200, 100), rect).unwrap();
+
+        assert_eq!(cropped, RegionRect { x: 150, y: 20, width: 50, height: 80 });
+    }
+
+    #[test]
+    fn crop_region_rejects_too_small_rect() {
+        let image = png(200, 100);
+        let too_small = |rect| crop_region(&image, rect).unwrap_err().is::<InvalidImage>();
+
+        assert!(too_small(RegionRect { x: 10, y: 10, width: 4, height: 50 }));
+        assert!(too_small(RegionRect { x: 196, y: 10, width: 50, height: 50 }));
+        assert!(too_small(RegionRect { x: 300, y: 300, width: 50, height: 50 }));
+    }
+}
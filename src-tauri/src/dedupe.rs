@@ -0,0 +1,190 @@
+use base64::{engine::general_purpose, Engine as _};
+use chrono::Utc;
+use image::{imageops::FilterType, DynamicImage};
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, time::Duration};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{revision, ProcessingResponse, ScreenshotProcessor};
+
+/// Differing bits out of 64 at which two images still count as the same screenshot.
+const NEAR_DUPLICATE_DISTANCE: u32 = 4;
+
+/// Remembers SHA-256 hashes of recently analyzed images so identical files are only analyzed once.
+#[derive(Debug)]
+pub struct RecentHashes {
+    window: Duration,
+    seen: HashMap<[u8; 32], Instant>,
+}
+
+impl RecentHashes {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Records `bytes`, returning `false` if the same content was already seen within the window.
+    pub fn insert(&mut self, bytes: &[u8]) -> bool {
+        let now = Instant::now();
+        self.seen.retain(|_, seen_at| now.duration_since(*seen_at) < self.window);
+
+        let hash: [u8; 32] = Sha256::digest(bytes).into();
+        if self.seen.contains_key(&hash) {
+            return false;
+        }
+
+        self.seen.insert(hash, now);
+        true
+    }
+
+    /// Forgets `bytes` again, e.g. after its analysis failed and should be retried.
+    pub fn remove(&mut self, bytes: &[u8]) {
+        let hash: [u8; 32] = Sha256::digest(bytes).into();
+        self.seen.remove(&hash);
+    }
+}
+
+/// 64-bit difference hash: whether each pixel of a 9x8 grayscale thumbnail is brighter than its right neighbour.
+/// Survives rescaling and recompression, so a re-uploaded copy of a screenshot hashes (nearly) the same.
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let thumbnail = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = thumbnail.get_pixel(x, y)[0];
+            let right = thumbnail.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// Perceptual hashes of recently analyzed images, to find near-duplicates from any source.
+#[derive(Debug)]
+pub struct RecentImages {
+    window: Duration,
+    seen: Vec<(u64, String, Instant)>,
+}
+
+impl RecentImages {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Vec::new(),
+        }
+    }
+
+    /// The analysis of the closest recent image within the near-duplicate distance, if any.
+    pub fn find(&mut self, hash: u64) -> Option<String> {
+        let now = Instant::now();
+        self.seen.retain(|(_, _, seen_at)| now.duration_since(*seen_at) < self.window);
+
+        self.seen
+            .iter()
+            .map(|(seen, id, _)| ((seen ^ hash).count_ones(), id))
+            .filter(|(distance, _)| *distance <= NEAR_DUPLICATE_DISTANCE)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, id)| id.clone())
+    }
+
+    pub fn insert(&mut self, hash: u64, analysis_id: &str) {
+        self.seen.push((hash, analysis_id.to_string(), Instant::now()));
+    }
+}
+
+impl ScreenshotProcessor {
+    /// The image's difference hash, unless perceptual dedupe is off or the image is being re-analyzed on purpose.
+    pub(crate) async fn perceptual_hash(&self, image_base64: &str) -> Option<u64> {
+        if !self.config.perceptual_dedupe || revision::revising().is_some() {
+            return None;
+        }
+
+        let data = image_base64
+            .strip_prefix("data:image")
+            .and_then(|url| url.split_once(',').map(|(_, data)| data))
+            .unwrap_or(image_base64)
+            .to_string();
+        tokio::task::spawn_blocking(move || {
+            let bytes = general_purpose::STANDARD.decode(data).ok()?;
+            image::load_from_memory(&bytes).ok().map(|image| dhash(&image))
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+
+    /// A response pointing at the recent analysis this image nearly duplicates.
+    pub(crate) fn near_duplicate(&self, hash: u64) -> Option<ProcessingResponse> {
+        let analysis_id = self.recent_images.lock().find(hash)?;
+        let analysis = match self.store.get(&analysis_id) {
+            Ok(Some(analysis)) => analysis,
+            Ok(None) => return None,
+            Err(e) => {
+                warn!("Failed to load analysis {}: {}", analysis_id, e);
+                return None;
+            }
+        };
+
+        info!("🔄 Near-duplicate of analysis {}, skipping the LLM", analysis_id);
+        Some(ProcessingResponse {
+            success: true,
+            summary: Some(analysis.brief_summary),
+            analysis_id: Some(analysis_id),
+            timestamp: Utc::now(),
+            follow_up_available: Some(true),
+            source: Some(analysis.source),
+            error: None,
+            error_code: None,
+            retries: 0,
+            failed_over: false,
+            usage: None,
+            duplicate: true,
+            timings: None,
+        })
+    }
+}
+
+/// Collapses bursts of file events into one per path, firing once a path has been quiet for `delay`.
+#[derive(Debug)]
+pub struct Debouncer<K> {
+    delay: Duration,
+    pending: HashMap<K, Instant>,
+}
+
+impl<K: std::hash::Hash + Eq + Clone> Debouncer<K> {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Registers an event, pushing back the deadline if one is already pending.
+    pub fn touch(&mut self, key: K) {
+        self.pending.insert(key, Instant::now() + self.delay);
+    }
+
+    /// Earliest pending deadline, if any.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.pending.values().min().copied()
+    }
+
+    /// Removes and returns every key whose deadline has passed.
+    pub fn take_due(&mut self) -> Vec<K> {
+        let now = Instant::now();
+        let due: Vec<K> = self
+            .pending
+            .iter()
+            .filter(|(_, deadline)| **deadline <= now)
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &due {
+            self.pending.remove(key);
+        }
+        due
+    }
+}
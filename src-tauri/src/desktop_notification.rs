@@ -0,0 +1,80 @@
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+#[cfg(feature = "desktop")]
+use tracing::warn;
+
+use crate::{
+    events::{emit_event, OpenAnalysis, UiEvent},
+    ProcessingResponse, ScreenshotProcessor,
+};
+
+/// Analysis from the last notification, opened when the user brings the app forward.
+static PENDING_OPEN: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// Shows a native OS notification.
+#[cfg(feature = "desktop")]
+pub(crate) fn show(title: &str, body: &str) {
+    let Some(handle) = crate::APP_HANDLE.get() else {
+        return;
+    };
+    let result = tauri::api::notification::Notification::new(&handle.config().tauri.bundle.identifier)
+        .title(title)
+        .body(body)
+        .show();
+    if let Err(e) = result {
+        warn!("Failed to show desktop notification: {}", e);
+    }
+}
+
+/// Without the desktop app notifications are only logged.
+#[cfg(not(feature = "desktop"))]
+pub(crate) fn show(title: &str, body: &str) {
+    tracing::info!("{}: {}", title, body);
+}
+
+/// Whether the main window is visible and focused, so the user already sees the analysis.
+#[cfg(feature = "desktop")]
+fn main_window_in_front() -> bool {
+    let window = crate::APP_HANDLE.get().and_then(|handle| handle.get_window("main"));
+    window.is_some_and(|w| w.is_visible().unwrap_or(false) && w.is_focused().unwrap_or(false))
+}
+
+#[cfg(not(feature = "desktop"))]
+fn main_window_in_front() -> bool {
+    false
+}
+
+/// Opens the analysis from the last notification once the main window gains focus.
+///
+/// Tauri can't tell us a notification was clicked, but clicking one activates the app,
+/// so the next focus is treated as the click.
+pub fn on_main_window_focused() {
+    let Some(analysis_id) = PENDING_OPEN.lock().take() else {
+        return;
+    };
+    emit_event(UiEvent::OpenAnalysis(OpenAnalysis { id: analysis_id }));
+}
+
+impl ScreenshotProcessor {
+    /// Notifies the user of a finished analysis when the main window isn't in front.
+    pub(crate) fn notify_analysis_ready(&self, result: &ProcessingResponse, source: &str) {
+        if !result.success || !self.config.desktop_notification_sources.iter().any(|s| s == source) {
+            return;
+        }
+        let Some(analysis_id) = result.analysis_id.clone() else {
+            return;
+        };
+
+        if main_window_in_front() {
+            return;
+        }
+
+        let summary = result.summary.as_deref().unwrap_or_default();
+        let first_line = summary.lines().find(|line| !line.trim().is_empty()).unwrap_or("Analysis complete");
+        show("📸 Screenshot analyzed", first_line);
+        *PENDING_OPEN.lock() = Some(analysis_id);
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::{
+    audit::{header_value, DEVICE_NAME_HEADER},
+    auth, ApiError, ErrorCode, ScreenshotProcessor,
+};
+
+/// Header a client sends to be tracked as its own device, e.g. the id handed out by pairing.
+pub const DEVICE_ID_HEADER: &str = "x-device-id";
+
+/// A client that has talked to the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Device {
+    /// The client's `X-Device-Id`, or `key:<hash>` for clients only identified by their API key.
+    pub id: String,
+    pub name: String,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    pub last_ip: Option<String>,
+    pub request_count: u64,
+    /// Disabled devices are refused with 403.
+    pub enabled: bool,
+}
+
+/// The device a request comes from: its `X-Device-Id`, or else the API key it presented.
+fn device_id(request: &Request) -> Option<String> {
+    header_value(request.headers(), DEVICE_ID_HEADER)
+        .map(str::to_string)
+        .or_else(|| {
+            auth::extract_api_key(request.headers())
+                .map(|key| format!("key:{}", &hex::encode(Sha256::digest(key.as_bytes()))[..12]))
+        })
+}
+
+impl ScreenshotProcessor {
+    /// Every device seen so far, most recently active first.
+    pub fn list_devices(&self) -> Result<Vec<Device>> {
+        self.store.list_devices()
+    }
+
+    pub fn rename_device(&self, id: &str, name: &str) -> Result<bool> {
+        self.store.rename_device(id, name.trim())
+    }
+
+    /// Revokes or restores a device's access. Returns false if the device is unknown.
+    pub fn set_device_enabled(&self, id: &str, enabled: bool) -> Result<bool> {
+        self.store.set_device_enabled(id, enabled)
+    }
+
+    /// Adds a device ahead of its first request, e.g. when it pairs.
+    pub(crate) fn register_device(&self, id: &str, name: &str) -> Result<()> {
+        self.store.register_device(id, name)
+    }
+}
+
+/// Records which device made each request, and refuses devices that have been revoked.
+pub async fn track_devices(State(processor): State<ScreenshotProcessor>, request: Request, next: Next) -> Response {
+    let Some(id) = device_id(&request) else {
+        return next.run(request).await;
+    };
+    let name = header_value(request.headers(), DEVICE_NAME_HEADER).map(str::to_string);
+    let ip = request
+        .extensions()
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+        .map(|info| info.0.ip().to_string());
+
+    match processor.store.touch_device(&id, name.as_deref(), ip.as_deref()) {
+        Ok(true) => next.run(request).await,
+        Ok(false) => {
+            warn!("🔒 Rejected request to {} from revoked device {}", request.uri().path(), id);
+            ApiError::new(ErrorCode::Forbidden, "This device has been revoked").into_response()
+        }
+        Err(e) => {
+            // Don't lock clients out because the registry couldn't be updated
+            warn!("Failed to record device {}: {}", id, e);
+            next.run(request).await
+        }
+    }
+}
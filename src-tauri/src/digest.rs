@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Days, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use teloxide::{
+    prelude::*,
+    types::ParseMode,
+    utils::html,
+};
+use tracing::{info, warn};
+
+use crate::{AnalysisData, Project, ScreenshotProcessor};
+
+const TOP_TOPICS: usize = 5;
+const NOTABLE_URLS: usize = 5;
+
+/// When the digest goes out: a time of day in a timezone.
+#[derive(Debug, Clone, Copy)]
+struct DigestSchedule {
+    time: NaiveTime,
+    timezone: Tz,
+}
+
+impl DigestSchedule {
+    fn from_config(time: &str, timezone: Option<&str>) -> Result<Self> {
+        let time = NaiveTime::parse_from_str(time.trim(), "%H:%M")
+            .map_err(|_| anyhow!("Invalid digest time '{}', expected HH:MM", time))?;
+        let timezone = match timezone.map(str::trim).filter(|tz| !tz.is_empty()) {
+            Some(name) => name
+                .parse()
+                .map_err(|_| anyhow!("Unknown digest timezone '{}'", name))?,
+            None => system_timezone(),
+        };
+        Ok(Self { time, timezone })
+    }
+
+    /// The first scheduled time after `now`.
+    fn next_after(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.with_timezone(&self.timezone).date_naive();
+        (0..=2)
+            .filter_map(|offset| today.checked_add_days(Days::new(offset)))
+            .filter_map(|date| {
+                self.timezone
+                    .from_local_datetime(&date.and_time(self.time))
+                    .earliest()
+            })
+            .map(|time| time.with_timezone(&Utc))
+            .find(|time| *time > now)
+            .unwrap_or_else(|| now + chrono::Duration::days(1))
+    }
+
+    /// Start of the day containing `now`, in this schedule's timezone.
+    fn day_start(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let today = now.with_timezone(&self.timezone).date_naive();
+        self.timezone
+            .from_local_datetime(&today.and_time(NaiveTime::MIN))
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+            .unwrap_or(now - chrono::Duration::days(1))
+    }
+}
+
+fn system_timezone() -> Tz {
+    iana_time_zone::get_timezone()
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// Summarizes a day's analyses as Telegram HTML, under a title naming the project if it's for one.
+fn format_digest(date: &str, project: Option<&Project>, analyses: &[(String, AnalysisData)]) -> String {
+    let title = match project {
+        Some(project) => format!("Daily digest: {}", html::escape(&project.name)),
+        None => "Daily digest".to_string(),
+    };
+    if analyses.is_empty() {
+        return format!("📰 <b>{}</b> <i>{}</i>\n\nNo screenshots were analyzed today.", title, date);
+    }
+
+    let mut by_type: HashMap<&str, usize> = HashMap::new();
+    let mut topics: HashMap<String, usize> = HashMap::new();
+    let mut urls: Vec<&str> = Vec::new();
+    for (_, analysis) in analyses {
+        let content = &analysis.content_analysis;
+        *by_type.entry(content.content_type.as_str()).or_default() += 1;
+        for topic in &content.research_topics {
+            *topics.entry(topic.trim().to_lowercase()).or_default() += 1;
+        }
+        if let Some(url) = content.webpage_url.as_deref() {
+            if !urls.contains(&url) {
+                urls.push(url);
+            }
+        }
+    }
+
+    let mut by_type: Vec<_> = by_type.into_iter().collect();
+    by_type.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let mut topics: Vec<_> = topics.into_iter().collect();
+    topics.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let mut message = format!(
+        "📰 <b>{}</b> <i>{}</i>\n\n<b>{} screenshots analyzed</b>\n",
+        title,
+        date,
+        analyses.len()
+    );
+    for (content_type, count) in &by_type {
+        message.push_str(&format!("• {}: {}\n", html::escape(content_type), count));
+    }
+
+    if !topics.is_empty() {
+        let top: Vec<String> = topics
+            .iter()
+            .take(TOP_TOPICS)
+            .map(|(topic, count)| format!("{} ({})", html::escape(topic), count))
+            .collect();
+        message.push_str(&format!("\n<b>Top topics:</b> {}\n", top.join(", ")));
+    }
+
+    if !urls.is_empty() {
+        message.push_str("\n<b>Pages:</b>\n");
+        for url in urls.iter().take(NOTABLE_URLS) {
+            message.push_str(&format!("• {}\n", html::escape(url)));
+        }
+    }
+
+    message
+}
+
+impl ScreenshotProcessor {
+    /// Sends today's digest to Telegram, returning the message that was sent. A project's digest
+    /// only covers its analyses and goes to its own chat, if it has one.
+    pub async fn send_digest(&self, project_id: Option<&str>) -> Result<String> {
+        let Some(bot) = &self.telegram_bot else {
+            return Err(anyhow!("Telegram is not configured"));
+        };
+
+        let schedule = DigestSchedule::from_config(&self.config.digest_time, self.config.digest_timezone.as_deref())?;
+        let project = project_id.map(|id| self.project(id)).transpose()?;
+        let now = Utc::now();
+        let mut analyses = self.store.between(schedule.day_start(now), now)?;
+        if let Some(project) = &project {
+            analyses.retain(|(_, analysis)| analysis.project_id.as_deref() == Some(project.id.as_str()));
+        }
+        let date = now.with_timezone(&schedule.timezone).format("%A, %b %d").to_string();
+
+        let digest = format_digest(&date, project.as_ref(), &analyses);
+        match project.as_ref().and_then(Project::recipient) {
+            Some(recipient) => {
+                bot.send_message(recipient, &digest)
+                    .parse_mode(ParseMode::Html)
+                    .disable_web_page_preview(true)
+                    .await?;
+            }
+            None => self.broadcast_telegram(&digest).await?,
+        }
+        info!("📰 Sent daily digest covering {} analyses", analyses.len());
+        Ok(digest)
+    }
+
+    /// The usual digest, then one per project with its own chat.
+    async fn send_scheduled_digests(&self) -> Result<()> {
+        self.send_digest(None).await?;
+        for project in self.list_projects()? {
+            if project.recipient().is_some() {
+                self.send_digest(Some(&project.id)).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the digest every day at the configured time, if enabled.
+    pub fn spawn_digest_scheduler(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if !self.config.digest_enabled || self.telegram_bot.is_none() {
+            return None;
+        }
+
+        let schedule = match DigestSchedule::from_config(&self.config.digest_time, self.config.digest_timezone.as_deref()) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                warn!("Daily digest disabled: {}", e);
+                return None;
+            }
+        };
+
+        info!(
+            "📰 Daily digest scheduled for {} ({})",
+            schedule.time.format("%H:%M"),
+            schedule.timezone
+        );
+
+        let processor = self.clone();
+        Some(tokio::spawn(async move {
+            loop {
+                let next = schedule.next_after(Utc::now());
+                let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                tokio::time::sleep(wait).await;
+
+                if let Err(e) = processor.send_scheduled_digests().await {
+                    warn!("Failed to send daily digest: {}", e);
+                }
+            }
+        }))
+    }
+}
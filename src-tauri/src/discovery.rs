@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use parking_lot::RwLock;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::ScreenshotProcessor;
+
+/// DNS-SD service type companion clients browse for.
+pub const SERVICE_TYPE: &str = "_screenshot-ai._tcp.local.";
+
+/// The server's mDNS registration, withdrawn when dropped.
+pub struct Advertisement {
+    daemon: ServiceDaemon,
+    fullname: String,
+    /// The processor's record of the advertised name, cleared on withdrawal.
+    name: Arc<RwLock<Option<String>>>,
+}
+
+impl Drop for Advertisement {
+    fn drop(&mut self) {
+        // Tell clients the service is gone rather than letting the record expire
+        if let Err(e) = self.daemon.unregister(&self.fullname) {
+            warn!("Failed to withdraw mDNS advertisement: {}", e);
+        }
+        let _ = self.daemon.shutdown();
+        *self.name.write() = None;
+    }
+}
+
+/// The instance name clients show for this server, "Screenshot AI on <host>", and its mDNS host name.
+fn instance_name() -> (String, String) {
+    let host = gethostname::gethostname().to_string_lossy().to_string();
+    let host = host.trim_end_matches(".local").to_string();
+    let host = if host.is_empty() { "localhost".to_string() } else { host };
+    (format!("Screenshot AI on {}", host), format!("{}.local.", host))
+}
+
+impl ScreenshotProcessor {
+    /// Advertises the screenshot endpoint on the local network as `_screenshot-ai._tcp`.
+    ///
+    /// TXT records carry the upload path, whether an API key is required, and the certificate
+    /// fingerprint when serving HTTPS.
+    pub(crate) fn advertise(&self) -> Result<Advertisement> {
+        let (name, host) = instance_name();
+        let auth = if self.api_key().is_some() { "required" } else { "none" };
+        let mut properties = vec![
+            ("path", "/screenshot".to_string()),
+            ("auth", auth.to_string()),
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ("scheme", self.url_scheme().to_string()),
+        ];
+        if let Some(fingerprint) = self.tls_fingerprint() {
+            properties.push(("fingerprint", fingerprint));
+        }
+
+        let service = ServiceInfo::new(SERVICE_TYPE, &name, &host, (), self.config.server_port, &properties[..])
+            .map_err(|e| anyhow!("Invalid mDNS service: {}", e))?
+            .enable_addr_auto();
+        let fullname = service.get_fullname().to_string();
+
+        let daemon = ServiceDaemon::new().map_err(|e| anyhow!("Failed to start mDNS responder: {}", e))?;
+        daemon
+            .register(service)
+            .map_err(|e| anyhow!("Failed to register mDNS service: {}", e))?;
+
+        info!("📡 Advertising \"{}\" as {}", name, SERVICE_TYPE);
+        *self.advertised_name.write() = Some(name);
+        Ok(Advertisement {
+            daemon,
+            fullname,
+            name: self.advertised_name.clone(),
+        })
+    }
+
+    /// The name the server is currently advertised under, if any.
+    pub fn advertised_name(&self) -> Option<String> {
+        self.advertised_name.read().clone()
+    }
+}
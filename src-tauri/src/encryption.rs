@@ -0,0 +1,212 @@
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+use ring::{aead, hkdf, hmac};
+use rusqlite::{params, Connection};
+use std::{io::Read, path::Path, sync::Arc};
+use tracing::info;
+
+use crate::{secrets, AnalysisStore};
+
+/// Marks an image file as sealed with the storage key, ahead of its nonce and ciphertext.
+const SEALED_MAGIC: &[u8] = b"SAIENC1\0";
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+/// Fixed HKDF salt; the keychain secret is already random.
+const KDF_SALT: &[u8] = b"screenshot-ai storage";
+/// How a file starts when SQLite can read it without a key.
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+/// Keys for encrypting the history at rest, all derived from one secret kept in the OS keychain.
+pub(crate) struct StorageKey {
+    database: [u8; KEY_LEN],
+    images: aead::LessSafeKey,
+    names: hmac::Key,
+}
+
+impl std::fmt::Debug for StorageKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("StorageKey { ... }")
+    }
+}
+
+fn derive(secret: &[u8], purpose: &[u8]) -> Result<[u8; KEY_LEN]> {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, KDF_SALT).extract(secret);
+    let mut key = [0u8; KEY_LEN];
+    prk.expand(&[purpose], hkdf::HKDF_SHA256)
+        .and_then(|okm| okm.fill(&mut key))
+        .map_err(|_| anyhow!("Failed to derive storage key"))?;
+    Ok(key)
+}
+
+impl StorageKey {
+    fn from_secret(secret: &[u8]) -> Result<Self> {
+        let images = aead::UnboundKey::new(&aead::AES_256_GCM, &derive(secret, b"images")?)
+            .map_err(|_| anyhow!("Invalid image key"))?;
+        Ok(Self {
+            database: derive(secret, b"database")?,
+            images: aead::LessSafeKey::new(images),
+            names: hmac::Key::new(hmac::HMAC_SHA256, &derive(secret, b"names")?),
+        })
+    }
+
+    /// The key from the keychain, generating and saving a new one if there isn't one and `create`
+    /// is set.
+    pub fn load(create: bool) -> Result<Option<Self>> {
+        secrets::storage_secret(create)?
+            .map(|secret| Self::from_secret(&secret))
+            .transpose()
+    }
+
+    /// SQLCipher's raw key syntax, which skips its own key derivation.
+    fn database_key(&self) -> String {
+        format!("\"x'{}'\"", hex::encode(self.database))
+    }
+
+    /// Unlocks an encrypted database; must come before anything else on the connection.
+    pub fn unlock(&self, conn: &Connection) -> Result<()> {
+        conn.execute_batch(&format!("PRAGMA key = {};", self.database_key()))?;
+        Ok(())
+    }
+
+    /// A file name for `bytes` that, unlike a plain hash, can't be matched against known images.
+    pub fn file_name(&self, bytes: &[u8]) -> String {
+        hex::encode(hmac::sign(&self.names, bytes))
+    }
+
+    pub fn seal(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let mut sealed = bytes.to_vec();
+        self.images
+            .seal_in_place_append_tag(aead::Nonce::assume_unique_for_key(nonce), aead::Aad::empty(), &mut sealed)
+            .map_err(|_| anyhow!("Failed to encrypt image"))?;
+        Ok([SEALED_MAGIC, &nonce, &sealed].concat())
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        let body = sealed
+            .strip_prefix(SEALED_MAGIC)
+            .filter(|body| body.len() >= NONCE_LEN)
+            .ok_or_else(|| anyhow!("Not an encrypted image"))?;
+        let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+        let nonce = aead::Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("Invalid nonce"))?;
+        let mut buffer = ciphertext.to_vec();
+        let plaintext = self
+            .images
+            .open_in_place(nonce, aead::Aad::empty(), &mut buffer)
+            .map_err(|_| anyhow!("Failed to decrypt image: wrong key or damaged file"))?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+pub(crate) fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(SEALED_MAGIC)
+}
+
+pub(crate) fn is_sealed_file(path: &Path) -> Result<bool> {
+    let mut header = [0u8; SEALED_MAGIC.len()];
+    let read = std::fs::File::open(path)?.read(&mut header)?;
+    Ok(is_sealed(&header[..read]))
+}
+
+/// Whether the database at `path` exists and can't be read without a key.
+fn is_encrypted_database(path: &Path) -> bool {
+    let mut header = [0u8; SQLITE_HEADER.len()];
+    match std::fs::File::open(path).and_then(|mut file| file.read(&mut header)) {
+        Ok(0) | Err(_) => false,
+        Ok(_) => header != SQLITE_HEADER,
+    }
+}
+
+fn remove_if_exists(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Rewrites the database at `path` from one key to another, `None` meaning unencrypted.
+fn rewrite_database(path: &Path, from: Option<&StorageKey>, to: Option<&StorageKey>) -> Result<()> {
+    let rewritten = path.with_extension("db.rewrite");
+    remove_if_exists(&rewritten)?;
+    {
+        let conn = Connection::open(path)?;
+        if let Some(key) = from {
+            key.unlock(&conn)?;
+        }
+        // Everything in the write-ahead log is folded in, so it can be dropped with the old file
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);")?;
+        let version: i64 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        let target_key = to.map(StorageKey::database_key).unwrap_or_else(|| "''".to_string());
+        conn.execute(
+            &format!("ATTACH DATABASE ?1 AS rewritten KEY {}", target_key),
+            params![rewritten.to_string_lossy()],
+        )?;
+        conn.query_row("SELECT sqlcipher_export('rewritten')", [], |_| Ok(()))?;
+        // Not copied by the export, and it's what tells the store which migrations have run
+        conn.execute_batch(&format!("PRAGMA rewritten.user_version = {}; DETACH DATABASE rewritten;", version))?;
+    }
+
+    // A leftover log would be replayed against the rewritten file
+    for suffix in ["-wal", "-shm"] {
+        remove_if_exists(Path::new(&format!("{}{}", path.display(), suffix)))?;
+    }
+    std::fs::rename(&rewritten, path)?;
+    Ok(())
+}
+
+/// Opens the history, encrypting or decrypting what's on disk first if `encrypt` has changed.
+pub(crate) fn open_store(path: &Path, encrypt: bool) -> Result<AnalysisStore> {
+    if encrypt {
+        let key = StorageKey::load(true)?.ok_or_else(|| anyhow!("No storage encryption key"))?;
+        if path.exists() && !is_encrypted_database(path) {
+            rewrite_database(path, None, Some(&key))?;
+            info!("🔒 Encrypted analysis database");
+        }
+        return AnalysisStore::open(path, Some(Arc::new(key)));
+    }
+
+    if is_encrypted_database(path) {
+        let key = StorageKey::load(false)?
+            .map(Arc::new)
+            .ok_or_else(|| anyhow!("The history is encrypted but its key is missing from the keychain"))?;
+        // Images first: if this is interrupted, the still-encrypted database brings it back here
+        AnalysisStore::decrypt_images(path, key.clone())?;
+        rewrite_database(path, Some(&key), None)?;
+        info!("🔓 Decrypted analysis database");
+    }
+    AnalysisStore::open(path, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sealed_image_opens_with_the_same_key() {
+        let key = StorageKey::from_secret(b"first secret").unwrap();
+        let sealed = key.seal(b"png bytes").unwrap();
+
+        assert!(is_sealed(&sealed));
+        assert_eq!(key.open(&sealed).unwrap(), b"png bytes");
+    }
+
+    #[test]
+    fn sealed_image_is_rejected_with_another_key() {
+        let sealed = StorageKey::from_secret(b"first secret").unwrap().seal(b"png bytes").unwrap();
+        let other = StorageKey::from_secret(b"second secret").unwrap();
+
+        let error = other.open(&sealed).unwrap_err();
+        assert!(error.to_string().contains("wrong key"));
+    }
+
+    #[test]
+    fn damaged_or_plain_files_are_rejected() {
+        let key = StorageKey::from_secret(b"first secret").unwrap();
+        let mut sealed = key.seal(b"png bytes").unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+
+        assert!(key.open(&sealed).is_err());
+        assert!(key.open(b"\x89PNG plain image").is_err());
+    }
+}
@@ -0,0 +1,271 @@
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use chrono::{DateTime, Utc};
+use futures::{future, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "desktop")]
+use tauri::Manager;
+use tokio::sync::broadcast;
+
+use crate::{
+    collections::CollectionCount, redaction::SensitiveKind, ProcessingResponse, ScreenshotMetadata,
+    ScreenshotProcessor, ServerInfo, SkippedScreenshot,
+};
+
+/// Events buffered per subscriber before slow clients start missing some.
+pub const EVENT_CAPACITY: usize = 512;
+
+/// A step in the life of one analysis, streamed to clients as it happens.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum ProgressStage {
+    Received { source: String },
+    Preprocessed { media_type: String, size_bytes: usize },
+    /// Personal data or credentials were found; the image was blurred, or not analyzed if `blocked`.
+    SensitiveContent { kinds: Vec<SensitiveKind>, blocked: bool },
+    SummaryToken { token: String },
+    SummaryDone { summary: String },
+    AnalysisDone,
+    Failed { error: String },
+}
+
+impl ProgressStage {
+    fn name(&self) -> &'static str {
+        match self {
+            ProgressStage::Received { .. } => "received",
+            ProgressStage::Preprocessed { .. } => "preprocessed",
+            ProgressStage::SensitiveContent { .. } => "sensitive_content",
+            ProgressStage::SummaryToken { .. } => "summary_token",
+            ProgressStage::SummaryDone { .. } => "summary_done",
+            ProgressStage::AnalysisDone => "analysis_done",
+            ProgressStage::Failed { .. } => "failed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressEvent {
+    pub analysis_id: String,
+    #[serde(flatten)]
+    pub stage: ProgressStage,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// An analysis has been received and is waiting for, or has, a processing slot.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisStarted {
+    pub analysis_id: String,
+    pub source: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalysisFailed {
+    pub analysis_id: String,
+    pub source: String,
+    pub error: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// An automatically captured screenshot that has been analyzed, with its image for the gallery.
+#[derive(Debug, Clone, Serialize)]
+pub struct ScreenshotProcessed {
+    pub id: String,
+    pub name: String,
+    pub timestamp: DateTime<Utc>,
+    pub source: String,
+    pub summary: String,
+    pub media_type: String,
+    pub size_bytes: usize,
+    pub image_base64: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueChanged {
+    /// Analyses holding a processing slot.
+    pub active: usize,
+    /// Analyses waiting for one.
+    pub waiting: usize,
+    pub max_concurrent: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OutboxChanged {
+    pub pending: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportProgress {
+    pub total: usize,
+    pub imported: usize,
+    pub failed: usize,
+    /// The file being imported.
+    pub current: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionsUpdated {
+    pub analysis_id: String,
+    pub collections: Vec<CollectionCount>,
+}
+
+/// Asks the UI to show an analysis, e.g. after its desktop notification was clicked.
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenAnalysis {
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServerState {
+    Running,
+    Stopped,
+    /// The server task ended with an error after starting.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessingPaused {
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerStateChanged {
+    pub state: ServerState,
+    pub error: Option<String>,
+}
+
+/// Everything sent to the desktop UI. Each variant's payload is serialized as is, under the
+/// event name the frontend listens for.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum UiEvent {
+    AnalysisProgress(ProgressEvent),
+    AnalysisStarted(AnalysisStarted),
+    AnalysisFailed(AnalysisFailed),
+    ScreenshotProcessed(ScreenshotProcessed),
+    QueueChanged(QueueChanged),
+    OutboxChanged(OutboxChanged),
+    ImportProgress(ImportProgress),
+    CollectionsUpdated(CollectionsUpdated),
+    OpenAnalysis(OpenAnalysis),
+    ServerStateChanged(ServerStateChanged),
+    /// The server was started, possibly without the setup dialog, e.g. on launch or from the tray.
+    ServerStarted(ServerInfo),
+    ProcessingPaused(ProcessingPaused),
+    /// An exclusion rule kept a screenshot from being analyzed.
+    ScreenshotSkipped(SkippedScreenshot),
+    ShowSetupDialog,
+}
+
+impl UiEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            UiEvent::AnalysisProgress(_) => "analysis-progress",
+            UiEvent::AnalysisStarted(_) => "analysis-started",
+            UiEvent::AnalysisFailed(_) => "analysis-failed",
+            UiEvent::ScreenshotProcessed(_) => "screenshot-processed",
+            UiEvent::QueueChanged(_) => "queue-changed",
+            UiEvent::OutboxChanged(_) => "outbox-changed",
+            UiEvent::ImportProgress(_) => "import-progress",
+            UiEvent::CollectionsUpdated(_) => "collections-updated",
+            UiEvent::OpenAnalysis(_) => "open-analysis",
+            UiEvent::ServerStateChanged(_) => "server-state-changed",
+            UiEvent::ServerStarted(_) => "server-started",
+            UiEvent::ProcessingPaused(_) => "processing-paused",
+            UiEvent::ScreenshotSkipped(_) => "screenshot-skipped",
+            UiEvent::ShowSetupDialog => "show-setup-dialog",
+        }
+    }
+}
+
+/// Sends an event to the main window, if it's open.
+#[cfg(feature = "desktop")]
+pub fn emit_event(event: UiEvent) {
+    if let Some(window) = crate::APP_HANDLE.get().and_then(|handle| handle.get_window("main")) {
+        let _ = window.emit(event.name(), &event);
+    }
+}
+
+/// Without the desktop app there's no window to send events to.
+#[cfg(not(feature = "desktop"))]
+pub fn emit_event(_event: UiEvent) {}
+
+/// Adds an automatically captured screenshot to the gallery, with its image.
+pub(crate) fn emit_screenshot_processed(
+    result: &ProcessingResponse,
+    metadata: &ScreenshotMetadata,
+    size_bytes: usize,
+    media_type: &str,
+    image_base64: &str,
+) {
+    let id = result.analysis_id.clone().unwrap_or_else(|| "unknown".to_string());
+    let name = metadata
+        .filename
+        .clone()
+        .unwrap_or_else(|| format!("screenshot-{}.png", id.chars().take(8).collect::<String>()));
+    emit_event(UiEvent::ScreenshotProcessed(ScreenshotProcessed {
+        id,
+        name,
+        timestamp: result.timestamp,
+        source: result.source.clone().or_else(|| metadata.source.clone()).unwrap_or_default(),
+        summary: result.summary.clone().unwrap_or_default(),
+        media_type: media_type.to_string(),
+        size_bytes,
+        image_base64: image_base64.to_string(),
+    }));
+}
+
+impl ScreenshotProcessor {
+    /// Publishes a progress event to SSE subscribers and the desktop UI.
+    pub(crate) fn emit_progress(&self, analysis_id: &str, stage: ProgressStage) {
+        let event = ProgressEvent {
+            analysis_id: analysis_id.to_string(),
+            stage,
+            timestamp: Utc::now(),
+        };
+
+        emit_event(UiEvent::AnalysisProgress(event.clone()));
+        // No subscribers is not an error
+        let _ = self.events.send(event);
+    }
+
+    pub fn subscribe_progress(&self) -> broadcast::Receiver<ProgressEvent> {
+        self.events.subscribe()
+    }
+
+    /// Progress events from now on, only those for `analysis_id` if given. Events a slow reader
+    /// misses are skipped.
+    pub(crate) fn progress_stream(&self, analysis_id: Option<String>) -> impl Stream<Item = ProgressEvent> {
+        futures::stream::unfold(self.subscribe_progress(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    // A slow client missed some events; keep streaming the newer ones
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+        .filter(move |event| future::ready(analysis_id.as_deref().is_none_or(|id| id == event.analysis_id)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    /// Only stream events for this analysis.
+    pub analysis_id: Option<String>,
+}
+
+pub async fn handle_events(
+    State(processor): State<ScreenshotProcessor>,
+    Query(query): Query<EventsQuery>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let events = processor
+        .progress_stream(query.analysis_id)
+        .map(|event| Event::default().event(event.stage.name()).json_data(&event));
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
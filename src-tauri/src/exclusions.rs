@@ -0,0 +1,236 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    events::{emit_event, UiEvent},
+    filename_filter::Matcher,
+    ScreenshotMetadata, ScreenshotProcessor,
+};
+
+/// Skipped screenshots kept for review; older records are dropped as new ones arrive.
+const MAX_SKIPPED_RECORDS: usize = 1000;
+
+/// Which part of a screenshot's metadata a rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionTarget {
+    /// The app in front when the screenshot was taken.
+    App,
+    /// The title of the window in front, e.g. to catch private browsing windows.
+    WindowTitle,
+    Filename,
+}
+
+impl ExclusionTarget {
+    fn label(self) -> &'static str {
+        match self {
+            ExclusionTarget::App => "app",
+            ExclusionTarget::WindowTitle => "window title",
+            ExclusionTarget::Filename => "filename",
+        }
+    }
+}
+
+/// Screenshots whose app, window title or filename matches `pattern` are never analyzed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExclusionRule {
+    pub target: ExclusionTarget,
+    /// A glob, or a `re:` regex; both ignore case.
+    pub pattern: String,
+}
+
+impl ExclusionRule {
+    fn new(target: ExclusionTarget, pattern: &str) -> Self {
+        Self {
+            target,
+            pattern: pattern.to_string(),
+        }
+    }
+}
+
+/// Password managers and private browsing windows. Banking apps differ too much by country to
+/// list, so those are left for users to add.
+pub fn default_exclusion_rules() -> Vec<ExclusionRule> {
+    let apps = [
+        "*1password*",
+        "*bitwarden*",
+        "*dashlane*",
+        "*enpass*",
+        "*keepass*",
+        "*keeper*",
+        "*lastpass*",
+        "*proton pass*",
+        "keychain access",
+    ];
+    let titles = ["*private browsing*", "*incognito*", "*inprivate*"];
+    apps.iter()
+        .map(|app| ExclusionRule::new(ExclusionTarget::App, app))
+        .chain(titles.iter().map(|title| ExclusionRule::new(ExclusionTarget::WindowTitle, title)))
+        .collect()
+}
+
+/// Fails if a rule's pattern isn't a valid glob or regex.
+pub fn validate_exclusion_rules(rules: &[ExclusionRule]) -> Result<()> {
+    ExclusionRules::new(rules).map(|_| ())
+}
+
+/// Exclusion rules with their patterns parsed.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ExclusionRules {
+    rules: Vec<(ExclusionRule, Matcher)>,
+}
+
+impl ExclusionRules {
+    pub(crate) fn new(rules: &[ExclusionRule]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .filter(|rule| !rule.pattern.trim().is_empty())
+            .map(|rule| Ok((rule.clone(), Matcher::parse(rule.pattern.trim())?)))
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    fn matching(&self, metadata: &ScreenshotMetadata) -> Option<&ExclusionRule> {
+        self.rules
+            .iter()
+            .find(|(rule, matcher)| {
+                let value = match rule.target {
+                    ExclusionTarget::App => &metadata.app,
+                    ExclusionTarget::WindowTitle => &metadata.window_title,
+                    ExclusionTarget::Filename => &metadata.filename,
+                };
+                value.as_deref().is_some_and(|value| matcher.is_match(value))
+            })
+            .map(|(rule, _)| rule)
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Screenshot not analyzed: its {} matches the exclusion rule \"{}\"", .rule.target.label(), .rule.pattern)]
+pub struct Excluded {
+    pub rule: ExclusionRule,
+}
+
+/// A screenshot turned away by an exclusion rule. Only what matched is kept, never the image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedScreenshot {
+    pub id: i64,
+    pub skipped_at: DateTime<Utc>,
+    pub source: String,
+    pub rule: ExclusionRule,
+    pub app: Option<String>,
+    pub window_title: Option<String>,
+    pub filename: Option<String>,
+}
+
+impl ScreenshotProcessor {
+    pub fn exclusion_rules(&self) -> Vec<ExclusionRule> {
+        self.exclusions.read().rules.iter().map(|(rule, _)| rule.clone()).collect()
+    }
+
+    /// Replaces the exclusion rules for screenshots from now on.
+    pub fn set_exclusion_rules(&self, rules: &[ExclusionRule]) -> Result<()> {
+        let parsed = ExclusionRules::new(rules)?;
+        info!("🚫 {} exclusion rules in effect", parsed.rules.len());
+        *self.exclusions.write() = parsed;
+        Ok(())
+    }
+
+    /// Turns the screenshot away if it matches an exclusion rule, recording that it was skipped.
+    pub(crate) fn check_exclusions(&self, metadata: &Option<ScreenshotMetadata>) -> Result<()> {
+        let Some(metadata) = metadata else {
+            return Ok(());
+        };
+        let Some(rule) = self.exclusions.read().matching(metadata).cloned() else {
+            return Ok(());
+        };
+
+        let skipped = SkippedScreenshot {
+            id: 0,
+            skipped_at: Utc::now(),
+            source: metadata.source.clone().unwrap_or_else(|| "iOS".to_string()),
+            rule: rule.clone(),
+            app: metadata.app.clone(),
+            window_title: metadata.window_title.clone(),
+            filename: metadata.filename.clone(),
+        };
+        info!(
+            "🚫 Skipped {} screenshot: {} matches \"{}\"",
+            skipped.source,
+            rule.target.label(),
+            rule.pattern
+        );
+        match self.store.record_skipped(&skipped, MAX_SKIPPED_RECORDS) {
+            Ok(id) => emit_event(UiEvent::ScreenshotSkipped(SkippedScreenshot { id, ..skipped })),
+            Err(e) => warn!("Failed to record skipped screenshot: {}", e),
+        }
+        Err(Excluded { rule }.into())
+    }
+
+    /// Screenshots skipped by exclusion rules, most recent first.
+    pub fn skipped_screenshots(&self, limit: usize) -> Result<Vec<SkippedScreenshot>> {
+        self.store.skipped(limit.clamp(1, MAX_SKIPPED_RECORDS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(app: &str, window_title: &str) -> ScreenshotMetadata {
+        ScreenshotMetadata {
+            app: Some(app.to_string()),
+            window_title: Some(window_title.to_string()),
+            filename: Some("Screenshot 2024-05-01 at 10.00.00.png".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn default_rules_catch_password_managers_and_private_windows() {
+        let rules = ExclusionRules::new(&default_exclusion_rules()).unwrap();
+
+        let matched = rules.matching(&metadata("1Password 8", "Vault")).unwrap();
+        assert_eq!(matched.target, ExclusionTarget::App);
+        let matched = rules.matching(&metadata("Firefox", "Docs — Private Browsing")).unwrap();
+        assert_eq!(matched.target, ExclusionTarget::WindowTitle);
+        assert!(rules.matching(&metadata("Safari", "Rust docs")).is_none());
+    }
+
+    #[test]
+    fn globs_and_regexes_ignore_case() {
+        let rules = ExclusionRules::new(&[
+            ExclusionRule::new(ExclusionTarget::App, "*BANK*"),
+            ExclusionRule::new(ExclusionTarget::Filename, r"re:^secret-\d+\.png$"),
+        ])
+        .unwrap();
+
+        assert!(rules.matching(&metadata("My Bank App", "")).is_some());
+        let secret = ScreenshotMetadata {
+            filename: Some("Secret-42.PNG".to_string()),
+            ..Default::default()
+        };
+        assert!(rules.matching(&secret).is_some());
+        assert!(rules.matching(&metadata("Notes", "bank statement")).is_none());
+    }
+
+    #[test]
+    fn rules_only_look_at_their_target() {
+        let rules = ExclusionRules::new(&[ExclusionRule::new(ExclusionTarget::WindowTitle, "*incognito*")]).unwrap();
+
+        assert!(rules.matching(&metadata("Incognito Browser", "Home")).is_none());
+        assert!(rules.matching(&ScreenshotMetadata::default()).is_none());
+    }
+
+    #[test]
+    fn blank_patterns_are_ignored_and_invalid_ones_rejected() {
+        let blank = ExclusionRules::new(&[ExclusionRule::new(ExclusionTarget::App, "  ")]).unwrap();
+        assert!(blank.matching(&metadata("Anything", "")).is_none());
+
+        assert!(validate_exclusion_rules(&[ExclusionRule::new(ExclusionTarget::App, "re:(")]).is_err());
+        assert!(validate_exclusion_rules(&[ExclusionRule::new(ExclusionTarget::App, "[a-")]).is_err());
+    }
+}
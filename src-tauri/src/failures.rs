@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{
+    exclusions::Excluded, history::AnalysisStatus, images, inflight::AnalysisCancelled, journal::JOURNAL_SOURCE, outbox::QueuedOffline, pause::Paused, queue::QueueFull, retry,
+    ProcessingResponse, ScreenshotMetadata, ScreenshotProcessor, SensitiveContentBlocked,
+};
+
+tokio::task_local! {
+    static RETRYING: String;
+}
+
+/// The id for a new analysis: the failed one the current task is retrying, so a successful
+/// retry takes its place, or else a fresh one.
+pub(crate) fn analysis_id() -> String {
+    RETRYING
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| Uuid::new_v4().to_string())
+}
+
+/// A screenshot whose analysis failed, kept so it can be retried.
+#[derive(Debug, Clone)]
+pub struct FailureRecord {
+    pub id: String,
+    pub failed_at: DateTime<Utc>,
+    pub source: String,
+    pub error: String,
+    pub image_base64: String,
+    pub metadata: Option<ScreenshotMetadata>,
+    pub attempts: u32,
+}
+
+/// A failed analysis as listed in the desktop history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedAnalysis {
+    pub id: String,
+    /// The screenshot's file name, or one made up from its id.
+    pub name: String,
+    pub status: AnalysisStatus,
+    pub failed_at: DateTime<Utc>,
+    pub source: String,
+    /// Why the last attempt failed.
+    pub error: String,
+    /// Failed attempts so far, counting retries.
+    pub attempts: u32,
+    /// Small JPEG preview, base64-encoded, if the image could be decoded.
+    pub thumbnail_base64: Option<String>,
+}
+
+impl From<FailureRecord> for FailedAnalysis {
+    fn from(failure: FailureRecord) -> Self {
+        // The image may have arrived as a data URL
+        let encoded = failure.image_base64.rsplit(',').next().unwrap_or_default();
+        let thumbnail = general_purpose::STANDARD
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| images::thumbnail(&bytes).ok());
+        let short_id: String = failure.id.chars().take(8).collect();
+        FailedAnalysis {
+            name: failure
+                .metadata
+                .and_then(|m| m.filename)
+                .unwrap_or_else(|| format!("screenshot-{}.png", short_id)),
+            id: failure.id,
+            status: AnalysisStatus::Failed,
+            failed_at: failure.failed_at,
+            source: failure.source,
+            error: failure.error,
+            attempts: failure.attempts,
+            thumbnail_base64: thumbnail.map(|bytes| general_purpose::STANDARD.encode(bytes)),
+        }
+    }
+}
+
+/// Whether a failure is kept for a retry from the UI. Not when the outbox retries it, the client
+/// was told to try again later, or the screenshot was turned away on purpose.
+fn is_recorded(source: &str, error: &anyhow::Error) -> bool {
+    source != JOURNAL_SOURCE
+        && !retry::is_transient(error)
+        && !error.is::<QueueFull>()
+        && !error.is::<SensitiveContentBlocked>()
+        && !error.is::<AnalysisCancelled>()
+        && !error.is::<Paused>()
+        && !error.is::<Excluded>()
+}
+
+impl ScreenshotProcessor {
+    /// Keeps the screenshot of a failed analysis so it can be retried.
+    pub(crate) fn record_failure(
+        &self,
+        analysis_id: &str,
+        source: &str,
+        image_base64: &str,
+        metadata: &Option<ScreenshotMetadata>,
+        error: &anyhow::Error,
+    ) {
+        if !is_recorded(source, error) {
+            return;
+        }
+        if let Err(e) = self
+            .store
+            .save_failure(analysis_id, source, &error.to_string(), image_base64, metadata)
+        {
+            warn!("Failed to record failed analysis {}: {}", analysis_id, e);
+        }
+    }
+
+    /// Failed analyses waiting for a retry, most recent failure first.
+    pub fn failed_analyses(&self) -> Result<Vec<FailedAnalysis>> {
+        Ok(self.store.failures()?.into_iter().map(FailedAnalysis::from).collect())
+    }
+
+    /// Runs a failed analysis's screenshot through the pipeline again under the same id. The
+    /// failure is dropped once the screenshot is analyzed or handed to the outbox; otherwise it
+    /// stays with the new error.
+    pub async fn retry_analysis(&self, analysis_id: &str) -> Result<ProcessingResponse> {
+        let failure = self
+            .store
+            .failure(analysis_id)?
+            .ok_or_else(|| anyhow!("Failed analysis {} not found", analysis_id))?;
+        info!("🔁 Retrying failed analysis {} ({} earlier attempts)", analysis_id, failure.attempts);
+
+        let result = RETRYING
+            .scope(
+                analysis_id.to_string(),
+                self.process_screenshot(&failure.image_base64, failure.metadata),
+            )
+            .await;
+        let settled = match &result {
+            Ok(_) => true,
+            Err(e) => e.is::<QueuedOffline>(),
+        };
+        if settled {
+            self.store.delete_failure(analysis_id)?;
+        }
+        result
+    }
+}
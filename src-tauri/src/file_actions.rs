@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+use crate::{AnalysisData, ScreenshotProcessor, WatchDirectory};
+
+/// Folder created inside a watched folder for archived screenshots, unless another is configured.
+pub const ARCHIVE_DIR_NAME: &str = "Analyzed";
+const SLUG_MAX_WORDS: usize = 8;
+const SLUG_MAX_CHARS: usize = 60;
+/// How long events for a file this app just moved are ignored by the watcher.
+const MOVED_FILE_GRACE: Duration = Duration::from_secs(30);
+
+/// What happens to a screenshot file once it has been analyzed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileAction {
+    /// Leave the file where it is.
+    #[default]
+    Keep,
+    /// Move it into `<archive>/<date>/<content type>/`.
+    Archive,
+    /// Rename it in place after its summary.
+    Rename,
+    /// Delete it; the image is kept in the history.
+    Delete,
+}
+
+/// Files this app recently moved or rewrote, so the watcher doesn't pick them up as new screenshots.
+#[derive(Debug, Default)]
+pub struct MovedFiles {
+    paths: HashMap<PathBuf, Instant>,
+}
+
+impl MovedFiles {
+    pub(crate) fn insert(&mut self, path: PathBuf) {
+        let now = Instant::now();
+        self.paths.retain(|_, moved| now.duration_since(*moved) < MOVED_FILE_GRACE);
+        self.paths.insert(path, now);
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.paths
+            .get(path)
+            .is_some_and(|moved| moved.elapsed() < MOVED_FILE_GRACE)
+    }
+}
+
+/// A lowercase, hyphenated filename stem made from the first words of the summary.
+fn summary_slug(summary: &str) -> String {
+    let mut slug = String::new();
+    let words = summary
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .take(SLUG_MAX_WORDS);
+    for word in words {
+        if slug.chars().count() + word.chars().count() + 1 > SLUG_MAX_CHARS {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_lowercase());
+    }
+    if slug.is_empty() { "screenshot".to_string() } else { slug }
+}
+
+/// `dir/stem.ext`, or `dir/stem-2.ext` and so on if that already exists.
+fn unique_path(dir: &Path, stem: &str, extension: Option<&str>) -> PathBuf {
+    let name = |suffix: Option<u32>| {
+        let stem = match suffix {
+            Some(n) => format!("{}-{}", stem, n),
+            None => stem.to_string(),
+        };
+        match extension {
+            Some(extension) => format!("{}.{}", stem, extension),
+            None => stem,
+        }
+    };
+    std::iter::once(None)
+        .chain((2..).map(Some))
+        .map(|suffix| dir.join(name(suffix)))
+        .find(|path| !path.exists())
+        .expect("unbounded candidates")
+}
+
+/// Moves a file, copying it when the destination is on another filesystem.
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if std::fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).with_context(|| format!("Failed to copy {} to {}", from.display(), to.display()))?;
+    std::fs::remove_file(from).with_context(|| format!("Failed to remove {}", from.display()))?;
+    Ok(())
+}
+
+/// Where `action` puts the file at `path`, or `None` if it's deleted.
+fn destination(
+    action: FileAction,
+    path: &Path,
+    directory: &WatchDirectory,
+    analysis: &AnalysisData,
+) -> Result<Option<PathBuf>> {
+    let extension = path.extension().and_then(|e| e.to_str());
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("No filename: {}", path.display()))?;
+
+    match action {
+        FileAction::Keep => Ok(Some(path.to_path_buf())),
+        FileAction::Delete => Ok(None),
+        FileAction::Rename => {
+            let dir = path.parent().ok_or_else(|| anyhow!("No parent folder: {}", path.display()))?;
+            Ok(Some(unique_path(dir, &summary_slug(&analysis.brief_summary), extension)))
+        }
+        FileAction::Archive => {
+            let content_type = match analysis.content_analysis.content_type.trim() {
+                "" => "other".to_string(),
+                content_type => summary_slug(content_type),
+            };
+            let dir = directory
+                .archive_dir
+                .clone()
+                .unwrap_or_else(|| directory.resolved_path().join(ARCHIVE_DIR_NAME))
+                .join(analysis.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string())
+                .join(content_type);
+            std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            Ok(Some(unique_path(&dir, &stem, extension)))
+        }
+    }
+}
+
+impl ScreenshotProcessor {
+    /// Applies the watched folder's file action to an analyzed screenshot and records where the file
+    /// ended up on the analysis. Returns the file's new path, or `None` if it was deleted.
+    fn apply_file_action(
+        &self,
+        analysis_id: &str,
+        path: &Path,
+        directory: &WatchDirectory,
+        moved_files: &parking_lot::Mutex<MovedFiles>,
+    ) -> Result<Option<PathBuf>> {
+        let action = directory.after_analysis;
+        if action == FileAction::Keep {
+            return Ok(Some(path.to_path_buf()));
+        }
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis {} not found", analysis_id))?;
+
+        let target = destination(action, path, directory, &analysis)?;
+        match target {
+            Some(ref target) => {
+                // Register first: the watcher may see the new file before the move returns
+                moved_files.lock().insert(target.clone());
+                move_file(path, target)?;
+                info!("📂 Moved {} to {}", path.display(), target.display());
+                self.store.set_file_path(analysis_id, Some(&target.to_string_lossy()))?;
+            }
+            None => {
+                std::fs::remove_file(path).with_context(|| format!("Failed to delete {}", path.display()))?;
+                info!("🗑️ Deleted analyzed screenshot {}", path.display());
+                self.store.set_file_path(analysis_id, None)?;
+            }
+        }
+        Ok(target)
+    }
+
+    /// Applies the file action and metadata write-back to a screenshot from a watched folder. Failures
+    /// are only logged, since the analysis itself succeeded.
+    pub(crate) fn finish_watched_file(
+        &self,
+        analysis_id: &str,
+        path: &Path,
+        directory: Option<&WatchDirectory>,
+        moved_files: &parking_lot::Mutex<MovedFiles>,
+    ) {
+        let final_path = match directory {
+            Some(directory) => match self.apply_file_action(analysis_id, path, directory, moved_files) {
+                Ok(final_path) => final_path,
+                Err(e) => {
+                    warn!("File action failed for {}: {}", path.display(), e);
+                    path.exists().then(|| path.to_path_buf())
+                }
+            },
+            None => Some(path.to_path_buf()),
+        };
+
+        if let Some(final_path) = final_path {
+            if let Err(e) = self.write_back_metadata(analysis_id, &final_path, moved_files) {
+                warn!("Failed to write analysis into {}: {}", final_path.display(), e);
+            }
+        }
+    }
+}
+
+/// The watched folder a path belongs to, preferring the most specific when folders are nested.
+pub(crate) fn watch_directory_for<'a>(directories: &'a [WatchDirectory], path: &Path) -> Option<&'a WatchDirectory> {
+    directories
+        .iter()
+        .filter(|d| path.starts_with(d.resolved_path()))
+        .max_by_key(|d| d.resolved_path().components().count())
+}
@@ -0,0 +1,123 @@
+use anyhow::{anyhow, Result};
+use globset::{Glob, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+
+use crate::{recording::is_recording_path, AppConfig};
+
+/// Filename patterns used by macOS, Windows and Linux screenshot tools across common locales.
+pub const DEFAULT_INCLUDE_PATTERNS: &[&str] = &[
+    // English and tools
+    "*screenshot*",
+    "*screen shot*",
+    "*capture*",
+    "*cleanshot*",
+    "*shottr*",
+    "*flameshot*",
+    "*licecap*",
+    "kapture*",
+    "*screen recording*",
+    "rpreplay*",
+    // German, French, Spanish/Portuguese, Italian, Dutch, Swedish
+    "*bildschirmfoto*",
+    "*capture d’écran*",
+    "*captura de pantalla*",
+    "*captura de tela*",
+    "*schermata*",
+    "*schermafbeelding*",
+    "*skärmavbild*",
+    "*bildschirmaufnahme*",
+    "*enregistrement de l’écran*",
+    "*grabación de pantalla*",
+    // Russian, Japanese, Chinese, Korean
+    "*снимок экрана*",
+    "*スクリーンショット*",
+    "*截屏*",
+    "*屏幕快照*",
+    "*스크린샷*",
+];
+
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif"];
+
+#[derive(Debug, Clone)]
+pub(crate) enum Matcher {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl Matcher {
+    /// Parses `re:<regex>` as a regular expression and anything else as a glob; both ignore case.
+    pub(crate) fn parse(pattern: &str) -> Result<Self> {
+        match pattern.strip_prefix("re:") {
+            Some(regex) => RegexBuilder::new(regex)
+                .case_insensitive(true)
+                .build()
+                .map(Matcher::Regex)
+                .map_err(|e| anyhow!("Invalid regex '{}': {}", regex, e)),
+            None => globset::GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .build()
+                .map(|glob: Glob| Matcher::Glob(glob.compile_matcher()))
+                .map_err(|e| anyhow!("Invalid glob '{}': {}", pattern, e)),
+        }
+    }
+
+    pub(crate) fn is_match(&self, name: &str) -> bool {
+        match self {
+            Matcher::Glob(glob) => glob.is_match(name),
+            Matcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+/// Decides which new image files in watched folders are screenshots.
+#[derive(Debug, Clone)]
+pub struct ScreenshotFilter {
+    include: Vec<Matcher>,
+    exclude: Vec<Matcher>,
+    /// Also match screen recordings.
+    recordings: bool,
+}
+
+impl ScreenshotFilter {
+    pub fn from_config(config: &AppConfig) -> Result<Self> {
+        let parse_all = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| p.trim())
+                .filter(|p| !p.is_empty())
+                .map(Matcher::parse)
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            include: parse_all(&config.screenshot_include_patterns)?,
+            exclude: parse_all(&config.screenshot_exclude_patterns)?,
+            recordings: config.recordings.enabled,
+        })
+    }
+
+    /// Matches visible PNG/JPEG/GIF files, and screen recordings if they're accepted, whose name hits no exclude pattern and, unless
+    /// `any_name` is set for a dedicated screenshot folder, an include pattern.
+    pub fn is_screenshot(&self, path: &Path, any_name: bool) -> bool {
+        let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+            return false;
+        };
+
+        // Skip hidden files (starting with .)
+        if name.starts_with('.') {
+            return false;
+        }
+
+        let is_image = path
+            .extension()
+            .map(|ext| IMAGE_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+            .unwrap_or(false);
+        if !(is_image || self.recordings && is_recording_path(path)) {
+            return false;
+        }
+
+        (any_name || self.include.iter().any(|m| m.is_match(&name)))
+            && !self.exclude.iter().any(|m| m.is_match(&name))
+    }
+}
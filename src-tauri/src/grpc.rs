@@ -0,0 +1,303 @@
+// tonic's handlers and interceptors return its `Status` unboxed
+#![allow(clippy::result_large_err)]
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use tonic::{
+    metadata::MetadataValue,
+    service::interceptor::InterceptedService,
+    transport::{server::TcpIncoming, Identity, Server, ServerTlsConfig},
+    Code, Request, Response, Status,
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    auth,
+    events::{ProgressEvent, ProgressStage},
+    history::{AnalysisFilter, AnalysisSort, DateRange},
+    tags, tls, AnalysisRecord, ApiError, ErrorCode, ScreenshotMetadata, ScreenshotProcessor,
+};
+
+mod proto {
+    tonic::include_proto!("screenshot_ai.companion.v1");
+}
+
+use proto::companion_server::{Companion, CompanionServer};
+
+/// Metadata key failed calls carry the HTTP API's error code in, e.g. `queued_offline`.
+const ERROR_CODE_KEY: &str = "error-code";
+
+fn timestamp(time: DateTime<Utc>) -> prost_types::Timestamp {
+    prost_types::Timestamp {
+        seconds: time.timestamp(),
+        nanos: time.timestamp_subsec_nanos() as i32,
+    }
+}
+
+fn date_time(timestamp: prost_types::Timestamp) -> Result<DateTime<Utc>, Status> {
+    u32::try_from(timestamp.nanos)
+        .ok()
+        .and_then(|nanos| DateTime::from_timestamp(timestamp.seconds, nanos))
+        .ok_or_else(|| Status::invalid_argument("Invalid timestamp"))
+}
+
+impl From<ApiError> for Status {
+    fn from(error: ApiError) -> Self {
+        let code = match error.code {
+            ErrorCode::BadRequest | ErrorCode::InvalidImage => Code::InvalidArgument,
+            ErrorCode::SensitiveContent | ErrorCode::Excluded => Code::FailedPrecondition,
+            ErrorCode::Unauthorized => Code::Unauthenticated,
+            ErrorCode::Forbidden => Code::PermissionDenied,
+            ErrorCode::NotFound => Code::NotFound,
+            ErrorCode::PayloadTooLarge
+            | ErrorCode::RateLimited
+            | ErrorCode::QueueFull
+            | ErrorCode::BudgetExceeded => Code::ResourceExhausted,
+            // Not Unavailable, which clients retry: the screenshot would be analyzed twice
+            ErrorCode::Cancelled | ErrorCode::QueuedOffline => Code::Aborted,
+            ErrorCode::Paused | ErrorCode::ProviderError | ErrorCode::Unavailable => Code::Unavailable,
+            ErrorCode::Internal => Code::Internal,
+        };
+        let mut status = Status::new(code, error.message);
+        if let Some(name) = serde_json::to_value(error.code)
+            .ok()
+            .and_then(|name| name.as_str().and_then(|name| MetadataValue::try_from(name).ok()))
+        {
+            status.metadata_mut().insert(ERROR_CODE_KEY, name);
+        }
+        status
+    }
+}
+
+impl From<proto::ScreenshotMetadata> for ScreenshotMetadata {
+    fn from(metadata: proto::ScreenshotMetadata) -> Self {
+        Self {
+            source: metadata.source,
+            app: metadata.app,
+            window_title: metadata.window_title,
+            filename: metadata.filename,
+            captured_at: metadata.captured_at.and_then(|time| date_time(time).ok()),
+            local_only: metadata.local_only,
+            ..Default::default()
+        }
+    }
+}
+
+impl From<AnalysisRecord> for proto::Analysis {
+    fn from(record: AnalysisRecord) -> Self {
+        Self {
+            id: record.id,
+            timestamp: Some(timestamp(record.timestamp)),
+            source: record.source,
+            summary: record.summary,
+            content_type: record.content_analysis.content_type,
+            tags: record.content_analysis.tags,
+            media_type: record.media_type,
+            size_bytes: record.size_bytes as u64,
+        }
+    }
+}
+
+impl From<ProgressEvent> for proto::ProgressEvent {
+    fn from(event: ProgressEvent) -> Self {
+        use proto::progress_event::{self as stage, Stage};
+
+        let stage = match event.stage {
+            ProgressStage::Received { source } => Stage::Received(stage::Received { source }),
+            ProgressStage::Preprocessed { media_type, size_bytes } => Stage::Preprocessed(stage::Preprocessed {
+                media_type,
+                size_bytes: size_bytes as u64,
+            }),
+            ProgressStage::SensitiveContent { kinds, blocked } => Stage::SensitiveContent(stage::SensitiveContent {
+                kinds: kinds
+                    .iter()
+                    .filter_map(|kind| serde_json::to_value(kind).ok())
+                    .filter_map(|kind| kind.as_str().map(str::to_string))
+                    .collect(),
+                blocked,
+            }),
+            ProgressStage::SummaryToken { token } => Stage::SummaryToken(stage::SummaryToken { token }),
+            ProgressStage::SummaryDone { summary } => Stage::SummaryDone(stage::SummaryDone { summary }),
+            ProgressStage::AnalysisDone => Stage::AnalysisDone(stage::AnalysisDone {}),
+            ProgressStage::Failed { error } => Stage::Failed(stage::Failed { error }),
+        };
+        Self {
+            analysis_id: event.analysis_id,
+            timestamp: Some(timestamp(event.timestamp)),
+            stage: Some(stage),
+        }
+    }
+}
+
+/// The companion API, a typed layer over the same processor the HTTP API uses.
+struct CompanionService {
+    processor: ScreenshotProcessor,
+}
+
+#[tonic::async_trait]
+impl Companion for CompanionService {
+    async fn submit_screenshot(
+        &self,
+        request: Request<proto::SubmitScreenshotRequest>,
+    ) -> Result<Response<proto::SubmitScreenshotResponse>, Status> {
+        let request = request.into_inner();
+        if request.image.is_empty() {
+            return Err(Status::invalid_argument("No image"));
+        }
+
+        let image_base64 = general_purpose::STANDARD.encode(&request.image);
+        let (response, _) = self
+            .processor
+            .process_screenshot_idempotent(
+                request.idempotency_key.as_deref(),
+                &image_base64,
+                request.metadata.map(ScreenshotMetadata::from),
+            )
+            .await
+            .map_err(|e| {
+                warn!("gRPC screenshot failed: {}", e);
+                Status::from(ApiError::from(e))
+            })?;
+
+        Ok(Response::new(proto::SubmitScreenshotResponse {
+            analysis_id: response.analysis_id.unwrap_or_default(),
+            summary: response.summary.unwrap_or_default(),
+            timestamp: Some(timestamp(response.timestamp)),
+            duplicate: response.duplicate,
+            retries: response.retries,
+        }))
+    }
+
+    async fn list_analyses(
+        &self,
+        request: Request<proto::ListAnalysesRequest>,
+    ) -> Result<Response<proto::ListAnalysesResponse>, Status> {
+        let request = request.into_inner();
+        if request.tag.as_deref().is_some_and(|tag| tags::normalize_tag(tag).is_none()) {
+            return Err(Status::invalid_argument("Invalid tag"));
+        }
+        let sort = match request.sort() {
+            proto::AnalysisSort::Newest => AnalysisSort::Newest,
+            proto::AnalysisSort::Oldest => AnalysisSort::Oldest,
+            proto::AnalysisSort::Largest => AnalysisSort::Largest,
+            proto::AnalysisSort::Smallest => AnalysisSort::Smallest,
+        };
+        let from = request.from.map(date_time).transpose()?;
+        let to = request.to.map(date_time).transpose()?;
+        let filter = AnalysisFilter {
+            source: request.source,
+            content_type: request.content_type,
+            date_range: (from.is_some() || to.is_some()).then_some(DateRange { from, to }),
+            tag: request.tag,
+            ..Default::default()
+        };
+
+        // Unset (0) fields take the same defaults as `GET /analyses`
+        let page_size = match request.page_size {
+            0 => 20,
+            size => size as usize,
+        };
+        let page = self
+            .processor
+            .list_analyses(request.page as usize, page_size, &filter, sort, false)
+            .map_err(|e| {
+                error!("Failed to list analyses: {}", e);
+                Status::from(ApiError::from(e))
+            })?;
+
+        Ok(Response::new(proto::ListAnalysesResponse {
+            analyses: page.analyses.into_iter().map(proto::Analysis::from).collect(),
+            page: page.page as u32,
+            page_size: page.page_size as u32,
+            total: page.total as u64,
+        }))
+    }
+
+    type WatchEventsStream = Pin<Box<dyn Stream<Item = Result<proto::ProgressEvent, Status>> + Send>>;
+
+    async fn watch_events(
+        &self,
+        request: Request<proto::WatchEventsRequest>,
+    ) -> Result<Response<Self::WatchEventsStream>, Status> {
+        let events = self
+            .processor
+            .progress_stream(request.into_inner().analysis_id)
+            .map(|event| Ok(proto::ProgressEvent::from(event)));
+        Ok(Response::new(Box::pin(events)))
+    }
+}
+
+/// Rejects calls over the rate limit, then calls without a valid API key when one is configured,
+/// as the HTTP API does.
+fn check_request(processor: &ScreenshotProcessor, request: Request<()>) -> Result<Request<()>, Status> {
+    let headers = request.metadata().clone().into_headers();
+    let provided = auth::extract_api_key(&headers);
+
+    if let Some(ip) = request.remote_addr().map(|addr| addr.ip()) {
+        if let Err(retry_after) = processor.check_rate_limit(provided, ip) {
+            // Round up so clients don't retry a moment too early
+            let retry_after = retry_after.as_secs() + 1;
+            warn!("🚦 Rate limited gRPC client {}, retry in {}s", ip, retry_after);
+            return Err(ApiError::new(
+                ErrorCode::RateLimited,
+                format!("Too many requests, try again in {} seconds", retry_after),
+            )
+            .into());
+        }
+    }
+
+    match (processor.api_key(), provided) {
+        (None, _) => Ok(request),
+        (Some(expected), Some(provided)) if auth::constant_time_eq(provided.as_bytes(), expected.as_bytes()) => {
+            Ok(request)
+        }
+        (Some(_), provided) => {
+            warn!(
+                "🔒 Rejected unauthenticated gRPC call ({})",
+                if provided.is_some() { "invalid key" } else { "missing key" }
+            );
+            Err(ApiError::new(ErrorCode::Unauthorized, "Missing or invalid API key").into())
+        }
+    }
+}
+
+/// Serves the companion gRPC API on `listener`, over TLS with the HTTP server's certificate when
+/// it serves HTTPS.
+pub(crate) async fn serve(processor: ScreenshotProcessor, listener: tokio::net::TcpListener) -> Result<()> {
+    let port = listener.local_addr()?.port();
+    let max_message = processor
+        .config
+        .image_policy
+        .max_upload_body()
+        .max(processor.config.recordings.max_upload_body());
+    let companion = CompanionServer::new(CompanionService {
+        processor: processor.clone(),
+    })
+    .max_decoding_message_size(max_message);
+    let interceptor_processor = processor.clone();
+    let service = InterceptedService::new(companion, move |request| check_request(&interceptor_processor, request));
+
+    let mut server = Server::builder();
+    if let Some(identity) = processor.tls.as_deref() {
+        tls::install_crypto_provider();
+        let cert = std::fs::read(&identity.cert_path)
+            .with_context(|| format!("Can't read certificate {}", identity.cert_path.display()))?;
+        let key = std::fs::read(&identity.key_path)
+            .with_context(|| format!("Can't read key {}", identity.key_path.display()))?;
+        server = server
+            .tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+            .map_err(|e| anyhow!("Failed to load TLS certificate: {}", e))?;
+    }
+
+    let incoming = TcpIncoming::from_listener(listener, true, None).map_err(|e| anyhow!("{}", e))?;
+    info!(
+        "🛰️ Companion gRPC API running on port {} ({})",
+        port,
+        if processor.tls.is_some() { "TLS" } else { "plaintext" }
+    );
+    server.add_service(service).serve_with_incoming(incoming).await?;
+    Ok(())
+}
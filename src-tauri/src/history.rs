@@ -0,0 +1,221 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{tags::normalize_tag, AnalysisData, AnalysisPage, Link, ScreenshotProcessor};
+
+/// Times an analysis was taken within: `from` inclusive, `to` exclusive. Either end may be open.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DateRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Narrows a history listing; unset fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisFilter {
+    /// e.g. `desktop`, `iphone` or `clipboard`, compared ignoring case.
+    pub source: Option<String>,
+    /// Compared ignoring case.
+    pub content_type: Option<String>,
+    pub date_range: Option<DateRange>,
+    pub tag: Option<String>,
+    pub starred: Option<bool>,
+    /// A project id.
+    pub project: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisSort {
+    #[default]
+    Newest,
+    Oldest,
+    Largest,
+    Smallest,
+}
+
+impl AnalysisSort {
+    fn order_by(self) -> &'static str {
+        // Ties fall back to newest first so pages don't shuffle between requests
+        match self {
+            AnalysisSort::Newest => "timestamp DESC, id",
+            AnalysisSort::Oldest => "timestamp, id",
+            AnalysisSort::Largest => "size_bytes DESC, timestamp DESC, id",
+            AnalysisSort::Smallest => "size_bytes, timestamp DESC, id",
+        }
+    }
+}
+
+/// Whether a history entry holds an analysis or a screenshot that failed to analyze.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalysisStatus {
+    #[default]
+    Completed,
+    Failed,
+}
+
+/// One row of the desktop history: enough to draw it without loading the full image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisSummary {
+    pub id: String,
+    /// The screenshot's file name, or one made up from its id.
+    pub name: String,
+    pub status: AnalysisStatus,
+    pub timestamp: DateTime<Utc>,
+    pub source: String,
+    pub summary: String,
+    pub content_type: String,
+    pub tags: Vec<String>,
+    pub links: Vec<Link>,
+    pub note: Option<String>,
+    pub starred: bool,
+    pub project_id: Option<String>,
+    pub media_type: String,
+    pub size_bytes: usize,
+    /// Small JPEG preview, base64-encoded.
+    pub thumbnail_base64: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalysisSummaryPage {
+    pub analyses: Vec<AnalysisSummary>,
+    pub page: usize,
+    pub page_size: usize,
+    pub total: usize,
+}
+
+impl AnalysisFilter {
+    /// A SQL condition on the `analyses` table and its parameters, for `?` placeholders in order.
+    fn where_clause(&self) -> Result<(String, Vec<String>)> {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        let non_empty = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+
+        if let Some(source) = non_empty(&self.source) {
+            conditions.push("lower(source) = lower(?)");
+            params.push(source);
+        }
+        if let Some(content_type) = non_empty(&self.content_type) {
+            conditions.push("lower(json_extract(content_analysis, '$.content_type')) = lower(?)");
+            params.push(content_type);
+        }
+        if let Some(tag) = non_empty(&self.tag) {
+            let tag = normalize_tag(&tag).ok_or_else(|| anyhow!("Invalid tag: {}", tag))?;
+            conditions.push("EXISTS (SELECT 1 FROM json_each(content_analysis, '$.tags') WHERE value = ?)");
+            params.push(tag);
+        }
+        if let Some(project) = non_empty(&self.project) {
+            conditions.push("project_id = ?");
+            params.push(project);
+        }
+        if let Some(starred) = self.starred {
+            conditions.push(if starred { "starred = 1" } else { "starred = 0" });
+        }
+        if let Some(range) = &self.date_range {
+            if let Some(from) = range.from {
+                conditions.push("timestamp >= ?");
+                params.push(from.to_rfc3339());
+            }
+            if let Some(to) = range.to {
+                conditions.push("timestamp < ?");
+                params.push(to.to_rfc3339());
+            }
+        }
+
+        let sql = if conditions.is_empty() { "1".to_string() } else { conditions.join(" AND ") };
+        Ok((sql, params))
+    }
+}
+
+/// Clamps paging arguments the way every history listing does; `page` is 1-based.
+fn page_bounds(page: usize, page_size: usize) -> (usize, usize) {
+    (page.max(1), page_size.clamp(1, 100))
+}
+
+impl ScreenshotProcessor {
+    fn analysis_summary(&self, id: String, analysis: AnalysisData) -> AnalysisSummary {
+        let thumbnail = self.store.thumbnail(&id).unwrap_or_else(|e| {
+            warn!("Failed to load thumbnail for analysis {}: {}", id, e);
+            None
+        });
+        let short_id: String = id.chars().take(8).collect();
+        AnalysisSummary {
+            name: analysis
+                .metadata
+                .filename
+                .unwrap_or_else(|| format!("screenshot-{}.png", short_id)),
+            id,
+            status: AnalysisStatus::Completed,
+            timestamp: analysis.timestamp,
+            source: analysis.source,
+            summary: analysis.brief_summary,
+            content_type: analysis.content_analysis.content_type,
+            tags: analysis.content_analysis.tags,
+            links: analysis.content_analysis.links,
+            note: analysis.note,
+            starred: analysis.starred,
+            project_id: analysis.project_id,
+            media_type: analysis.image_data.media_type,
+            size_bytes: analysis.image_data.size_bytes,
+            thumbnail_base64: thumbnail.map(|bytes| general_purpose::STANDARD.encode(bytes)),
+        }
+    }
+
+    /// Lists stored analyses matching `filter` in `sort` order; `page` is 1-based.
+    pub fn list_analyses(
+        &self,
+        page: usize,
+        page_size: usize,
+        filter: &AnalysisFilter,
+        sort: AnalysisSort,
+        include_images: bool,
+    ) -> Result<AnalysisPage> {
+        let (page, page_size) = page_bounds(page, page_size);
+        let (sql, params) = filter.where_clause()?;
+
+        let analyses = self
+            .store
+            .list_where(&sql, &params, sort.order_by(), (page - 1) * page_size, page_size)?
+            .into_iter()
+            .map(|(id, analysis)| self.analysis_record(id, analysis, include_images))
+            .collect();
+
+        Ok(AnalysisPage {
+            analyses,
+            page,
+            page_size,
+            total: self.store.count_where(&sql, &params)?,
+        })
+    }
+
+    /// Like `list_analyses`, as thumbnails and summaries for the desktop history.
+    pub fn list_analysis_summaries(
+        &self,
+        page: usize,
+        page_size: usize,
+        filter: &AnalysisFilter,
+        sort: AnalysisSort,
+    ) -> Result<AnalysisSummaryPage> {
+        let (page, page_size) = page_bounds(page, page_size);
+        let (sql, params) = filter.where_clause()?;
+
+        let analyses = self
+            .store
+            .list_where(&sql, &params, sort.order_by(), (page - 1) * page_size, page_size)?
+            .into_iter()
+            .map(|(id, analysis)| self.analysis_summary(id, analysis))
+            .collect();
+
+        Ok(AnalysisSummaryPage {
+            analyses,
+            page,
+            page_size,
+            total: self.store.count_where(&sql, &params)?,
+        })
+    }
+}
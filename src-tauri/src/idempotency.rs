@@ -0,0 +1,188 @@
+use anyhow::Result;
+use std::{collections::HashMap, future::Future, sync::Arc, time::Duration};
+use tokio::{sync::OnceCell, time::Instant};
+use tracing::info;
+
+use crate::{Paused, ProcessingResponse, QueuedOffline, ScreenshotMetadata, ScreenshotProcessor};
+
+/// Header a client sets to the same value when retrying a submission.
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+/// Set on responses replayed from an earlier request with the same key.
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// Responses to recent submissions, by idempotency key.
+///
+/// Each key gets a cell the first request fills in. Retries arriving while it's still running wait for
+/// the same result. Screenshots saved to analyze later are recorded too, so retries get the same 202
+/// instead of queuing them again; other failures leave the cell empty so the next retry runs again.
+#[derive(Debug)]
+pub struct IdempotencyCache {
+    window: Duration,
+    entries: HashMap<String, (Instant, Slot)>,
+}
+
+type Slot = Arc<OnceCell<Result<ProcessingResponse, Deferred>>>;
+
+/// Why a submission was saved to analyze later instead of answered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Deferred {
+    Offline,
+    Paused,
+}
+
+impl Deferred {
+    fn of(error: &anyhow::Error) -> Option<Self> {
+        if error.is::<QueuedOffline>() {
+            Some(Deferred::Offline)
+        } else if matches!(error.downcast_ref::<Paused>(), Some(Paused::Queued)) {
+            Some(Deferred::Paused)
+        } else {
+            None
+        }
+    }
+
+    fn error(self) -> anyhow::Error {
+        match self {
+            Deferred::Offline => QueuedOffline.into(),
+            Deferred::Paused => Paused::Queued.into(),
+        }
+    }
+}
+
+impl IdempotencyCache {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            entries: HashMap::new(),
+        }
+    }
+
+    fn slot(&mut self, key: &str) -> Slot {
+        let now = Instant::now();
+        self.entries
+            .retain(|_, (created, _)| now.duration_since(*created) < self.window);
+        self.entries
+            .entry(key.to_string())
+            .or_insert_with(|| (now, Arc::new(OnceCell::new())))
+            .1
+            .clone()
+    }
+}
+
+impl ScreenshotProcessor {
+    /// Processes a screenshot once per idempotency key within the configured window.
+    ///
+    /// Returns the response and whether it was replayed from an earlier request with the same key.
+    pub(crate) async fn process_screenshot_idempotent(
+        &self,
+        key: Option<&str>,
+        image_base64: &str,
+        metadata: Option<ScreenshotMetadata>,
+    ) -> Result<(ProcessingResponse, bool)> {
+        let key = key.map(str::trim).filter(|key| !key.is_empty());
+        let Some(key) = key.filter(|_| self.config.idempotency_window_secs > 0) else {
+            return Ok((self.process_screenshot(image_base64, metadata).await?, false));
+        };
+
+        let slot = self.idempotency.lock().slot(key);
+        run_once(&slot, key, || self.process_screenshot(image_base64, metadata)).await
+    }
+}
+
+/// Runs `process` unless an earlier request with `key` filled in `slot`, whose outcome is replayed instead.
+async fn run_once<F, Fut>(
+    slot: &OnceCell<Result<ProcessingResponse, Deferred>>,
+    key: &str,
+    process: F,
+) -> Result<(ProcessingResponse, bool)>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<ProcessingResponse>>,
+{
+    let mut replayed = true;
+    // The first request reports its own error, with the cause it was deferred for
+    let mut first_error = None;
+    let outcome = slot
+        .get_or_try_init(|| {
+            replayed = false;
+            let first_error = &mut first_error;
+            async move {
+                match process().await {
+                    Ok(response) => Ok(Ok(response)),
+                    Err(e) => match Deferred::of(&e) {
+                        Some(deferred) => {
+                            *first_error = Some(e);
+                            Ok(Err(deferred))
+                        }
+                        None => Err(e),
+                    },
+                }
+            }
+        })
+        .await?;
+
+    if replayed {
+        info!("🔁 Replaying response for idempotency key {}", key);
+    }
+    match outcome {
+        Ok(response) => Ok((response.clone(), replayed)),
+        Err(deferred) => Err(first_error.unwrap_or_else(|| deferred.error())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    #[tokio::test]
+    async fn retry_after_queued_result_replays_it_without_queuing_again() {
+        let slot = OnceCell::new();
+        let queued = AtomicU32::new(0);
+        // Stands in for `process_screenshot` saving the screenshot to the outbox
+        let submit = || async {
+            queued.fetch_add(1, Ordering::Relaxed);
+            Err(anyhow!("connection refused").context(QueuedOffline))
+        };
+
+        let first = run_once(&slot, "key", submit).await.unwrap_err();
+        let retry = run_once(&slot, "key", submit).await.unwrap_err();
+
+        assert_eq!(queued.load(Ordering::Relaxed), 1);
+        assert!(first.is::<QueuedOffline>());
+        assert!(retry.is::<QueuedOffline>());
+        assert_eq!(retry.to_string(), first.to_string());
+    }
+
+    #[tokio::test]
+    async fn retry_after_paused_queue_replays_it() {
+        let slot = OnceCell::new();
+        let queued = AtomicU32::new(0);
+        let submit = || async {
+            queued.fetch_add(1, Ordering::Relaxed);
+            Err(Paused::Queued.into())
+        };
+
+        run_once(&slot, "key", submit).await.unwrap_err();
+        let retry = run_once(&slot, "key", submit).await.unwrap_err();
+
+        assert_eq!(queued.load(Ordering::Relaxed), 1);
+        assert!(matches!(retry.downcast_ref::<Paused>(), Some(Paused::Queued)));
+    }
+
+    #[tokio::test]
+    async fn other_failures_run_again_on_retry() {
+        let slot = OnceCell::new();
+        let calls = AtomicU32::new(0);
+        let submit = || async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err(Paused::Dropped.into())
+        };
+
+        run_once(&slot, "key", submit).await.unwrap_err();
+        run_once(&slot, "key", submit).await.unwrap_err();
+
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::{anyhow, Result};
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use thiserror::Error;
+
+/// An upload that isn't an image the policy accepts, or isn't an image at all.
+#[derive(Debug, Error)]
+#[error("{0}")]
+pub struct InvalidImage(pub String);
+
+/// Image formats screenshots may arrive in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AcceptedFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+}
+
+impl AcceptedFormat {
+    fn detect(bytes: &[u8]) -> Option<Self> {
+        match image::guess_format(bytes).ok()? {
+            ImageFormat::Png => Some(Self::Png),
+            ImageFormat::Jpeg => Some(Self::Jpeg),
+            ImageFormat::WebP => Some(Self::Webp),
+            ImageFormat::Gif => Some(Self::Gif),
+            _ => None,
+        }
+    }
+
+    pub fn media_type(self) -> &'static str {
+        match self {
+            Self::Png => "image/png",
+            Self::Jpeg => "image/jpeg",
+            Self::Webp => "image/webp",
+            Self::Gif => "image/gif",
+        }
+    }
+}
+
+/// Which images are accepted for analysis, applied to HTTP uploads, watched folders, the clipboard and imports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImagePolicy {
+    pub max_bytes: usize,
+    /// Anything smaller is almost certainly not a real screenshot.
+    pub min_bytes: usize,
+    /// Longest edge in pixels; 0 for no limit.
+    pub max_dimension: u32,
+    pub accepted_formats: Vec<AcceptedFormat>,
+}
+
+impl Default for ImagePolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: 15 * 1024 * 1024,
+            min_bytes: 1024,
+            max_dimension: 16_384,
+            accepted_formats: vec![
+                AcceptedFormat::Png,
+                AcceptedFormat::Jpeg,
+                AcceptedFormat::Webp,
+                AcceptedFormat::Gif,
+            ],
+        }
+    }
+}
+
+fn megabytes(bytes: usize) -> String {
+    format!("{:.1}MB", bytes as f64 / 1024.0 / 1024.0)
+}
+
+impl ImagePolicy {
+    /// Checks a file's size before it's read, so oversized files can be skipped cheaply.
+    pub fn check_size(&self, size_bytes: u64) -> Result<()> {
+        let size = usize::try_from(size_bytes).unwrap_or(usize::MAX);
+        if size > self.max_bytes {
+            return Err(anyhow!(
+                "Image too large ({}, max {})",
+                megabytes(size),
+                megabytes(self.max_bytes)
+            ));
+        }
+        if size < self.min_bytes {
+            return Err(anyhow!("Image too small ({} bytes, min {})", size, self.min_bytes));
+        }
+        Ok(())
+    }
+
+    /// Validates an image's size, format and dimensions, returning its media type.
+    pub fn validate(&self, bytes: &[u8]) -> Result<&'static str> {
+        self.check_size(bytes.len() as u64)?;
+
+        let format = AcceptedFormat::detect(bytes)
+            .filter(|format| self.accepted_formats.contains(format))
+            .ok_or_else(|| anyhow!("Unsupported image format"))?;
+
+        if self.max_dimension > 0 {
+            // Only the header is read, so this is cheap even for huge images
+            let (width, height) = image::io::Reader::new(Cursor::new(bytes))
+                .with_guessed_format()?
+                .into_dimensions()
+                .map_err(|e| anyhow!("Failed to read image dimensions: {}", e))?;
+            if width.max(height) > self.max_dimension {
+                return Err(anyhow!(
+                    "Image too large ({}x{}px, max {}px)",
+                    width,
+                    height,
+                    self.max_dimension
+                ));
+            }
+        }
+
+        Ok(format.media_type())
+    }
+
+    /// Request body limit for a single base64-encoded upload, leaving room for the JSON around it.
+    pub fn max_upload_body(&self) -> usize {
+        self.max_bytes.div_ceil(3) * 4 + 64 * 1024
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, StatusCode},
+};
+use image::ImageOutputFormat;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{io::Cursor, path::PathBuf, sync::Arc};
+use tracing::{error, info};
+
+use crate::{
+    encryption::{self, StorageKey},
+    ScreenshotProcessor,
+};
+
+/// Longest edge of a thumbnail, in pixels.
+const THUMBNAIL_SIZE: u32 = 320;
+const THUMBNAIL_QUALITY: u8 = 75;
+
+/// Image files stored by the SHA-256 of their contents, so identical images are kept once.
+/// With a storage key they're encrypted and named by a keyed hash instead.
+#[derive(Debug, Clone)]
+pub(crate) struct ImageFiles {
+    dir: PathBuf,
+    key: Option<Arc<StorageKey>>,
+}
+
+impl ImageFiles {
+    pub fn new(dir: PathBuf, key: Option<Arc<StorageKey>>) -> Self {
+        Self { dir, key }
+    }
+
+    /// `<dir>/ab/abcdef…`, fanned out by the first two hex digits to keep directories small.
+    fn path(&self, hash: &str) -> Result<PathBuf> {
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(anyhow!("Invalid image hash: {}", hash));
+        }
+        Ok(self.dir.join(&hash[..2]).join(hash))
+    }
+
+    /// Writes `bytes` unless an identical file exists, returning its hash.
+    pub fn put(&self, bytes: &[u8]) -> Result<String> {
+        let hash = match &self.key {
+            Some(key) => key.file_name(bytes),
+            None => hex::encode(Sha256::digest(bytes)),
+        };
+        let path = self.path(&hash)?;
+        if path.exists() {
+            return Ok(hash);
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match &self.key {
+            Some(key) => write_file(&path, &key.seal(bytes)?)?,
+            None => write_file(&path, bytes)?,
+        }
+        Ok(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>> {
+        let path = self.path(hash)?;
+        let bytes = std::fs::read(&path).with_context(|| format!("Failed to read image {}", path.display()))?;
+        if !encryption::is_sealed(&bytes) {
+            return Ok(bytes);
+        }
+        match &self.key {
+            Some(key) => key.open(&bytes),
+            None => Err(anyhow!("Image {} is encrypted but storage encryption is off", hash)),
+        }
+    }
+
+    /// Every stored image file.
+    fn files(&self) -> Result<Vec<PathBuf>> {
+        let shards = match std::fs::read_dir(&self.dir) {
+            Ok(shards) => shards,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut files = Vec::new();
+        for shard in shards.flatten().filter(|entry| entry.path().is_dir()) {
+            for file in std::fs::read_dir(shard.path())?.flatten() {
+                let path = file.path();
+                if path.extension().is_none() {
+                    files.push(path);
+                }
+            }
+        }
+        Ok(files)
+    }
+
+    /// Encrypts the images stored before encryption was turned on, or decrypts them all when
+    /// `encrypt` is false. Files keep their names, so the database needn't change.
+    pub fn convert(&self, encrypt: bool) -> Result<()> {
+        let key = self
+            .key
+            .as_ref()
+            .ok_or_else(|| anyhow!("Converting images needs the storage key"))?;
+        let mut converted = 0;
+        for path in self.files()? {
+            // Only the header is read for files that are already as they should be
+            if encryption::is_sealed_file(&path)? == encrypt {
+                continue;
+            }
+            let bytes = std::fs::read(&path)?;
+            let rewritten = if encrypt { key.seal(&bytes)? } else { key.open(&bytes)? };
+            write_file(&path, &rewritten)?;
+            converted += 1;
+        }
+        if converted > 0 {
+            info!("{} {} images", if encrypt { "🔒 Encrypted" } else { "🔓 Decrypted" }, converted);
+        }
+        Ok(())
+    }
+
+    /// Deletes every stored image.
+    pub fn clear(&self) -> Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Total size of the stored images, in bytes.
+    pub fn size(&self) -> Result<u64> {
+        Ok(self
+            .files()?
+            .iter()
+            .map(|path| std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0))
+            .sum())
+    }
+
+    pub fn remove(&self, hash: &str) -> Result<()> {
+        match std::fs::remove_file(self.path(hash)?) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Writes under a temporary name first so a crash never leaves a truncated image behind.
+fn write_file(path: &std::path::Path, bytes: &[u8]) -> Result<()> {
+    let partial = path.with_extension("partial");
+    std::fs::write(&partial, bytes).with_context(|| format!("Failed to write {}", partial.display()))?;
+    std::fs::rename(&partial, path)?;
+    Ok(())
+}
+
+/// The media type of encoded image bytes, if they're in a format the image crate recognizes.
+pub(crate) fn sniff_media_type(bytes: &[u8]) -> Option<&'static str> {
+    match image::guess_format(bytes).ok()? {
+        image::ImageFormat::Png => Some("image/png"),
+        image::ImageFormat::Jpeg => Some("image/jpeg"),
+        image::ImageFormat::Gif => Some("image/gif"),
+        image::ImageFormat::WebP => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// A small JPEG preview of an image for history lists.
+pub(crate) fn thumbnail(bytes: &[u8]) -> Result<Vec<u8>> {
+    let image = image::load_from_memory(bytes)?.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    let mut encoded = Vec::new();
+    // JPEG has no alpha channel
+    image::DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_to(&mut Cursor::new(&mut encoded), ImageOutputFormat::Jpeg(THUMBNAIL_QUALITY))?;
+    Ok(encoded)
+}
+
+/// Which stored copy of a screenshot to return.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageVariant {
+    /// The screenshot as it was received, or its redacted copy.
+    #[default]
+    Full,
+    /// A small JPEG preview.
+    Thumb,
+    /// The full image with its annotations drawn in, as PNG.
+    Annotated,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ImageQuery {
+    #[serde(default)]
+    pub variant: ImageVariant,
+}
+
+impl ScreenshotProcessor {
+    /// A stored screenshot's image bytes and media type, or `None` if there's no such analysis.
+    pub fn analysis_image(&self, analysis_id: &str, variant: ImageVariant) -> Result<Option<(Vec<u8>, String)>> {
+        Ok(match variant {
+            ImageVariant::Full => self.store.original_image(analysis_id)?,
+            ImageVariant::Thumb => self
+                .store
+                .thumbnail(analysis_id)?
+                .map(|bytes| (bytes, "image/jpeg".to_string())),
+            ImageVariant::Annotated => self
+                .annotated_image(analysis_id)?
+                .map(|bytes| (bytes, "image/png".to_string())),
+        })
+    }
+}
+
+/// `GET /image/:id?variant=thumb|full|annotated`, so clients fetch images only when they show them.
+pub async fn handle_get_image(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Query(query): Query<ImageQuery>,
+) -> Result<([(header::HeaderName, String); 2], Vec<u8>), StatusCode> {
+    match processor.analysis_image(&analysis_id, query.variant) {
+        Ok(Some((bytes, media_type))) => Ok((
+            [
+                (header::CONTENT_TYPE, media_type),
+                // Content-addressed, so an id's image never changes; its annotations do
+                (
+                    header::CACHE_CONTROL,
+                    match query.variant {
+                        ImageVariant::Annotated => "no-cache".to_string(),
+                        _ => "private, max-age=31536000, immutable".to_string(),
+                    },
+                ),
+            ],
+            bytes,
+        )),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to load image for {}: {}", analysis_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// `GET /image/:id/frames/:index`, one frame of a screen recording or animated GIF, as PNG.
+pub async fn handle_get_frame(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath((analysis_id, index)): AxumPath<(String, usize)>,
+) -> Result<([(header::HeaderName, String); 2], Vec<u8>), StatusCode> {
+    match processor.analysis_frames(&analysis_id) {
+        Ok(mut frames) if index < frames.len() => Ok((
+            [
+                (header::CONTENT_TYPE, "image/png".to_string()),
+                (header::CACHE_CONTROL, "private, max-age=31536000, immutable".to_string()),
+            ],
+            frames.swap_remove(index),
+        )),
+        Ok(_) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to load frames for {}: {}", analysis_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+use crate::{
+    events::{emit_event, ImportProgress, UiEvent},
+    filename_filter::ScreenshotFilter,
+    recording::{is_recording_path, RecordingConfig},
+    ImagePolicy, ScreenshotMetadata, ScreenshotProcessor,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    /// Include images in subfolders.
+    pub recursive: bool,
+    /// Import every image, not only files named like screenshots.
+    pub all_images: bool,
+    /// Pause between screenshots to stay under provider rate limits.
+    pub delay_ms: u64,
+    /// Stop after this many screenshots.
+    pub limit: Option<usize>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            recursive: false,
+            all_images: false,
+            delay_ms: 1000,
+            limit: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub total: usize,
+    pub imported: usize,
+    pub failed: usize,
+}
+
+/// Screenshots under `dir` with their modification times, oldest first, skipping files the image policy
+/// (or for screen recordings, the recording limits) would reject for their size.
+fn find_images(
+    dir: &Path,
+    filter: &ScreenshotFilter,
+    policy: &ImagePolicy,
+    recordings: &RecordingConfig,
+    options: &ImportOptions,
+) -> Vec<(PathBuf, SystemTime)> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Skipping unreadable folder {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            if metadata.is_dir() {
+                if options.recursive {
+                    pending.push(path);
+                }
+            } else {
+                let size_ok = if is_recording_path(&path) {
+                    recordings.check_size(metadata.len()).is_ok()
+                } else {
+                    policy.check_size(metadata.len()).is_ok()
+                };
+                if size_ok && filter.is_screenshot(&path, options.all_images) {
+                    found.push((path, metadata.modified().unwrap_or(SystemTime::now())));
+                }
+            }
+        }
+    }
+
+    found.sort_by_key(|(_, modified)| *modified);
+    if let Some(limit) = options.limit {
+        found.truncate(limit);
+    }
+    found
+}
+
+fn emit_import_progress(summary: &ImportSummary, current: &Path) {
+    emit_event(UiEvent::ImportProgress(ImportProgress {
+        total: summary.total,
+        imported: summary.imported,
+        failed: summary.failed,
+        current: current.display().to_string(),
+    }));
+}
+
+impl ScreenshotProcessor {
+    /// Analyzes the screenshots already in a folder, dated by their file modification time.
+    pub async fn import_folder(&self, dir: &Path, options: ImportOptions) -> Result<ImportSummary> {
+        if !dir.is_dir() {
+            return Err(anyhow!("{} is not a folder", dir.display()));
+        }
+
+        let filter = ScreenshotFilter::from_config(&self.config)?;
+        let files = {
+            let dir = dir.to_path_buf();
+            let policy = self.config.image_policy.clone();
+            let recordings = self.config.recordings.clone();
+            let options = options.clone();
+            tokio::task::spawn_blocking(move || find_images(&dir, &filter, &policy, &recordings, &options)).await?
+        };
+
+        let mut summary = ImportSummary {
+            total: files.len(),
+            ..Default::default()
+        };
+        info!("📂 Importing {} screenshots from {}", summary.total, dir.display());
+
+        for (index, (path, modified)) in files.iter().enumerate() {
+            if index > 0 && options.delay_ms > 0 {
+                tokio::time::sleep(Duration::from_millis(options.delay_ms)).await;
+            }
+
+            match self.import_file(path, *modified).await {
+                Ok(()) => summary.imported += 1,
+                Err(e) => {
+                    warn!("Failed to import {}: {}", path.display(), e);
+                    summary.failed += 1;
+                }
+            }
+            emit_import_progress(&summary, path);
+        }
+
+        info!(
+            "📂 Import finished: {} analyzed, {} failed",
+            summary.imported, summary.failed
+        );
+        Ok(summary)
+    }
+
+    async fn import_file(&self, path: &Path, modified: SystemTime) -> Result<()> {
+        let bytes = tokio::fs::read(path).await?;
+        let metadata = ScreenshotMetadata {
+            source: Some("import".to_string()),
+            filename: path.file_name().map(|n| n.to_string_lossy().to_string()),
+            captured_at: Some(DateTime::<Utc>::from(modified)),
+            ..Default::default()
+        };
+
+        let result = self
+            .process_screenshot(&general_purpose::STANDARD.encode(&bytes), Some(metadata))
+            .await?;
+        match result.error {
+            Some(error) if !result.success => Err(anyhow!(error)),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+};
+use parking_lot::Mutex;
+use std::{collections::HashMap, future::Future, sync::Arc};
+use thiserror::Error;
+use tokio_util::sync::CancellationToken;
+use tracing::info;
+
+use crate::ScreenshotProcessor;
+
+#[derive(Debug, Error)]
+#[error("Analysis {0} was cancelled")]
+pub struct AnalysisCancelled(pub String);
+
+/// Analyses currently running, by id, with the tokens that cancel them.
+pub(crate) type InflightAnalyses = Arc<Mutex<HashMap<String, CancellationToken>>>;
+
+/// Keeps an analysis cancellable until it's dropped, however the analysis ends.
+pub(crate) struct Inflight {
+    analyses: InflightAnalyses,
+    analysis_id: String,
+    token: CancellationToken,
+}
+
+impl Inflight {
+    /// Runs `future` unless the analysis is cancelled first. Dropping it on cancellation drops
+    /// any request it has outstanding, so the provider stops before spending more tokens.
+    pub async fn run<T>(&self, future: impl Future<Output = Result<T>>) -> Result<T> {
+        self.token
+            .run_until_cancelled(future)
+            .await
+            .unwrap_or_else(|| Err(AnalysisCancelled(self.analysis_id.clone()).into()))
+    }
+}
+
+impl Drop for Inflight {
+    fn drop(&mut self) {
+        self.analyses.lock().remove(&self.analysis_id);
+    }
+}
+
+impl ScreenshotProcessor {
+    pub(crate) fn track_inflight(&self, analysis_id: &str) -> Inflight {
+        let token = CancellationToken::new();
+        self.inflight
+            .lock()
+            .insert(analysis_id.to_string(), token.clone());
+        Inflight {
+            analyses: self.inflight.clone(),
+            analysis_id: analysis_id.to_string(),
+            token,
+        }
+    }
+
+    /// Stops an analysis that is still running, returning whether there was one.
+    pub fn cancel_analysis(&self, analysis_id: &str) -> bool {
+        let Some(token) = self.inflight.lock().get(analysis_id).cloned() else {
+            return false;
+        };
+        token.cancel();
+        info!("🛑 Cancelled analysis {}", analysis_id);
+        true
+    }
+}
+
+/// `DELETE /analysis/:id/inflight`: stops an analysis before it's finished.
+pub async fn handle_cancel_analysis(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+) -> StatusCode {
+    if processor.cancel_analysis(&analysis_id) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
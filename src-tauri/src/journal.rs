@@ -0,0 +1,220 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tokio::time::MissedTickBehavior;
+use tracing::{error, info, warn};
+
+use crate::{capture, AppConfig, Excluded, ScreenshotMetadata, ScreenshotProcessor};
+
+/// Source recorded on journal captures, which keeps them apart from real screenshots in the history.
+pub const JOURNAL_SOURCE: &str = "journal";
+/// Journal entries only need a line or two, and the image a fraction of the usual detail.
+const JOURNAL_MAX_TOKENS: u32 = 150;
+const JOURNAL_IMAGE_DIMENSION: u32 = 1024;
+const JOURNAL_PROMPT: &str = "This is a periodic capture of the user's screen for their personal activity \
+journal. In one short sentence, say what they are working on (the app and the task), without quoting \
+any personal or sensitive details.";
+
+/// Settings for the activity journal, which captures the screen every few minutes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JournalConfig {
+    /// Start capturing with the server.
+    pub enabled: bool,
+    pub interval_minutes: u32,
+    /// Apps never captured while in front, matched case-insensitively against the app name.
+    pub excluded_apps: Vec<String>,
+    /// Journal captures are deleted after this many days; 0 keeps them.
+    pub retention_days: u32,
+    /// Model for journal entries, e.g. a cheaper one than for screenshots.
+    pub model: Option<String>,
+}
+
+impl Default for JournalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_minutes: 10,
+            excluded_apps: Vec::new(),
+            retention_days: 7,
+            model: None,
+        }
+    }
+}
+
+/// One capture on the day's timeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub analysis_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub app: Option<String>,
+    pub summary: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JournalQuery {
+    /// Local date as `YYYY-MM-DD`; today if omitted.
+    pub date: Option<String>,
+}
+
+/// The UTC bounds of a local calendar day.
+fn local_day(date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start_of = |date: NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now)
+    };
+    (start_of(date), start_of(date + Duration::days(1)))
+}
+
+fn parse_date(date: Option<&str>) -> Result<NaiveDate> {
+    match date.map(str::trim).filter(|d| !d.is_empty()) {
+        Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|e| anyhow!("Invalid date \"{}\": {}", date, e)),
+        None => Ok(Local::now().date_naive()),
+    }
+}
+
+impl ScreenshotProcessor {
+    /// A copy that makes cheap, private journal entries: a one-line summary from a small image, with no
+    /// Telegram, webhooks, Notion, push notifications or vault export.
+    fn journal_processor(&self) -> Result<Self> {
+        let journal = &self.config.journal;
+        let mut processor = self.clone();
+        processor.config = AppConfig {
+            model: journal.model.clone().or_else(|| self.config.model.clone()),
+            summary_prompt: Some(JOURNAL_PROMPT.to_string()),
+            summary_max_tokens: JOURNAL_MAX_TOKENS,
+            analysis_max_tokens: JOURNAL_MAX_TOKENS,
+            app_prompt_templates: HashMap::new(),
+            content_type_prompt_templates: HashMap::new(),
+            max_image_dimension: self.config.max_image_dimension.min(JOURNAL_IMAGE_DIMENSION),
+            webhooks: Vec::new(),
+            notion_token: None,
+            vault_auto_export: false,
+            ..self.config.clone()
+        };
+        if journal.model.is_some() {
+            processor.provider = crate::build_resilient_provider(&processor.config, &processor.client)?;
+        }
+        processor.telegram_bot = None;
+        processor.notifiers = Arc::new(Vec::new());
+        Ok(processor)
+    }
+
+    /// Captures the active display and adds it to the journal, unless an excluded app is in front.
+    async fn capture_journal_entry(&self) -> Result<()> {
+        let excluded_apps = self.config.journal.excluded_apps.clone();
+        let captured = tokio::task::spawn_blocking(move || capture::capture_active_display(&excluded_apps))
+            .await
+            .map_err(|e| anyhow!("Capture task failed: {}", e))??;
+        let Some((png_bytes, app)) = captured else {
+            info!("📓 Skipped journal capture: an excluded app is in front");
+            return Ok(());
+        };
+
+        let metadata = ScreenshotMetadata {
+            source: Some(JOURNAL_SOURCE.to_string()),
+            app,
+            auto_detected: Some(true),
+            ..Default::default()
+        };
+        // Not through the outbox: a missed capture is simply a gap in the timeline
+        let response = match self
+            .journal_processor()?
+            .process_tracked(&general_purpose::STANDARD.encode(&png_bytes), Some(metadata))
+            .await
+        {
+            Ok(response) => response,
+            // Already logged and recorded as skipped
+            Err(e) if e.is::<Excluded>() => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        info!("📓 Journal entry: {}", response.summary.unwrap_or_default());
+        Ok(())
+    }
+
+    fn prune_journal(&self) {
+        let days = self.config.journal.retention_days;
+        if days == 0 {
+            return;
+        }
+        match self
+            .store
+            .delete_source_before(JOURNAL_SOURCE, Utc::now() - Duration::days(days.into()))
+        {
+            Ok(0) => {}
+            Ok(deleted) => info!("📓 Deleted {} journal captures older than {} days", deleted, days),
+            Err(e) => warn!("Failed to delete old journal captures: {}", e),
+        }
+    }
+
+    /// Captures the screen every `interval_minutes` until aborted, skipping captures while paused.
+    pub fn spawn_journal(&self) -> tokio::task::JoinHandle<()> {
+        let processor = self.clone();
+        let minutes = u64::from(self.config.journal.interval_minutes.max(1));
+        info!("📓 Activity journal capturing every {} minutes", minutes);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(minutes * 60));
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                processor.prune_journal();
+                // Nothing on screen during a pause should be captured, even for later
+                if processor.journal_paused_until().is_some() || processor.is_paused() {
+                    continue;
+                }
+                if let Err(e) = processor.capture_journal_entry().await {
+                    warn!("Journal capture failed: {}", e);
+                }
+            }
+        })
+    }
+
+    /// Stops journal captures for `minutes`.
+    pub fn pause_journal(&self, minutes: u32) -> DateTime<Utc> {
+        let until = Utc::now() + Duration::minutes(minutes.into());
+        *self.journal_paused_until.lock() = Some(until);
+        info!("📓 Activity journal paused until {}", until.with_timezone(&Local).format("%H:%M"));
+        until
+    }
+
+    pub fn resume_journal(&self) {
+        *self.journal_paused_until.lock() = None;
+    }
+
+    /// When a pause ends, if the journal is paused.
+    pub fn journal_paused_until(&self) -> Option<DateTime<Utc>> {
+        let mut paused_until = self.journal_paused_until.lock();
+        if paused_until.is_some_and(|until| until <= Utc::now()) {
+            *paused_until = None;
+        }
+        *paused_until
+    }
+
+    /// The journal entries for a local day (`YYYY-MM-DD`, today if omitted), oldest first.
+    pub fn journal_timeline(&self, date: Option<&str>) -> Result<Vec<JournalEntry>> {
+        let (from, to) = local_day(parse_date(date)?);
+        self.store.journal_entries(JOURNAL_SOURCE, from, to)
+    }
+}
+
+pub async fn handle_journal(
+    State(processor): State<ScreenshotProcessor>,
+    Query(query): Query<JournalQuery>,
+) -> Result<ResponseJson<Vec<JournalEntry>>, StatusCode> {
+    parse_date(query.date.as_deref()).map_err(|_| StatusCode::BAD_REQUEST)?;
+    processor.journal_timeline(query.date.as_deref()).map(ResponseJson).map_err(|e| {
+        error!("Failed to load journal: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
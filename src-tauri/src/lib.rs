@@ -1,52 +1,239 @@
 use anyhow::{anyhow, Result};
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
-    response::Json as ResponseJson,
-    routing::{get, post},
+    extract::{DefaultBodyLimit, Json, Path as AxumPath, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Json as ResponseJson, Response},
+    routing::{delete, get, post, put},
     Router,
 };
+use axum_server::tls_rustls::RustlsConfig;
 use base64::{engine::general_purpose, Engine as _};
 use chrono::{DateTime, Utc};
-use dashmap::DashMap;
 use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+#[cfg(feature = "desktop")]
 use once_cell::sync::OnceCell;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashSet, HashMap},
+    collections::HashMap,
+    net::SocketAddr,
     path::{Path, PathBuf},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::{Duration, SystemTime},
+    time::{Duration, Instant},
 };
-use tauri::{AppHandle, Manager};
-use teloxide::{prelude::*, types::{InlineKeyboardMarkup, InputFile}, Bot};
-use tokio::{sync::{mpsc, RwLock}, time::sleep};
+#[cfg(feature = "desktop")]
+use tauri::AppHandle;
+use teloxide::{prelude::*, types::InputFile, Bot};
+use tokio::{sync::{broadcast, mpsc, RwLock}, time::sleep};
 use tower_http::cors::CorsLayer;
-use tracing::{error, info, warn};
-use uuid::Uuid;
+use tracing::{debug, error, info, warn};
+
+mod animation;
+mod annotations;
+mod api_error;
+mod archive;
+mod arxiv;
+mod audit;
+mod auth;
+mod batch;
+mod budget;
+mod capture;
+mod clipboard;
+mod code;
+mod collections;
+mod compare;
+mod content_filter;
+mod conversation;
+mod dedupe;
+mod desktop_notification;
+mod devices;
+mod discovery;
+mod digest;
+mod encryption;
+mod events;
+mod exclusions;
+mod failures;
+mod file_actions;
+mod filename_filter;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod history;
+mod idempotency;
+mod image_policy;
+mod images;
+mod import;
+mod inflight;
+mod journal;
+mod links;
+pub mod logging;
+pub mod login_item;
+mod notes;
+mod notifier;
+mod notion;
+mod openapi;
+mod outbox;
+mod pairing;
+mod pause;
+mod pipeline;
+mod platform;
+mod preprocess;
+mod privacy;
+mod profiles;
+mod projects;
+pub mod prompts;
+mod providers;
+mod queue;
+mod rate_limit;
+mod receipts;
+mod recording;
+mod redaction;
+mod research;
+mod retention;
+mod retry;
+mod revision;
+mod rules;
+pub mod secrets;
+mod self_test;
+mod server;
+mod sessions;
+pub mod settings;
+mod spotlight;
+mod storage;
+mod tables;
+mod tags;
+mod telegram;
+mod tls;
+mod translation;
+mod tts;
+mod usage;
+mod vault;
+mod webhook;
+mod webpage;
+mod writeback;
+
+pub use annotations::{Annotation, AnnotationInput, AnnotationShape};
+pub use api_error::{ApiError, ErrorBody, ErrorCode, ErrorDetail};
+pub use archive::{ExportArchive, ExportFormat, ExportOptions};
+pub use arxiv::ArxivPaper;
+pub use audit::{AuditEntry, AuditQuery};
+pub use auth::generate_api_key;
+pub use batch::{BatchRequest, BatchResponse};
+pub use budget::{BudgetExceeded, BudgetStatus};
+pub use capture::{list_windows as list_capturable_windows, CapturableWindow};
+pub use clipboard::ClipboardWatcher;
+pub use code::CodeSnippet;
+pub use collections::{Collection, CollectionInput};
+pub use compare::{AnalysisComparison, ChangeKind, ScreenshotDifference};
+pub use content_filter::ContentFilterMode;
+pub use conversation::{ChatTurn, RegionRect};
+pub use desktop_notification::on_main_window_focused;
+pub use devices::Device;
+pub use discovery::SERVICE_TYPE as MDNS_SERVICE_TYPE;
+pub use events::{emit_event, OpenAnalysis, ProgressEvent, ProgressStage, ServerState, ServerStateChanged, UiEvent};
+pub use exclusions::{
+    default_exclusion_rules, validate_exclusion_rules, Excluded, ExclusionRule, ExclusionTarget, SkippedScreenshot,
+};
+pub use failures::FailedAnalysis;
+pub use file_actions::FileAction;
+pub use filename_filter::{ScreenshotFilter, DEFAULT_INCLUDE_PATTERNS};
+pub use history::{AnalysisFilter, AnalysisSort, AnalysisStatus, AnalysisSummary, AnalysisSummaryPage, DateRange};
+pub use image_policy::{AcceptedFormat, ImagePolicy, InvalidImage};
+pub use images::ImageVariant;
+pub use import::{ImportOptions, ImportSummary};
+pub use inflight::AnalysisCancelled;
+pub use journal::{JournalConfig, JournalEntry};
+pub use links::Link;
+pub use notifier::{Notification, Notifier, PushNotifierConfig};
+pub use notion::{check_database as check_notion_database, NotionDatabaseCheck, NotionProperties};
+pub use outbox::QueuedOffline;
+pub use pairing::PairingQr;
+pub use pause::{PauseMode, Paused};
+pub use pipeline::StageTimings;
+pub use platform::default_watch_directories;
+pub use profiles::ProcessingProfile;
+pub use projects::{Project, ProjectInput, ProjectRule, ProjectRuleTarget};
+pub use prompts::{PromptTemplate, TEMPLATE_VARIABLES};
+pub use providers::{ChatRole, OutputSchema, ProviderKind, VisionProvider};
+pub use queue::QueueFull;
+pub use receipts::{LineItem, ReceiptData, ReceiptKind};
+pub use recording::{RecordingConfig, RECORDING_EXTENSIONS};
+pub use redaction::{RedactionMode, SensitiveContentBlocked, SensitiveKind};
+pub use research::{ResearchConfig, ResearchReport, ResearchSource, SearchProvider};
+pub use retention::{CleanupReport, RetentionConfig, StorageStats};
+pub use revision::ReanalyzeRequest;
+pub use rules::{ActionOutcome, Rule, RuleAction, RuleInput, RuleRun};
+pub use self_test::{validate_config, CheckStatus, ConfigCheck, ValidationReport};
+pub use server::{ServerHandle, ServerInfo};
+pub use sessions::Session;
+pub use tls::TlsIdentity;
+pub use translation::Translation;
+pub use tts::{TtsConfig, TtsProvider};
+pub use storage::AnalysisStore;
+pub use telegram::{TelegramChat, TelegramMessageRef};
+pub use usage::{ModelPrice, TokenUsage, UsageStats, UsageTotals};
+pub use tables::{DataTable, ExtractedTables};
+pub use tags::TagCount;
+pub use webhook::WebhookConfig;
+pub use webpage::WebpageContent;
+pub use writeback::MetadataWriteback;
 
 // Global app handle for emitting events
+#[cfg(feature = "desktop")]
 static APP_HANDLE: OnceCell<AppHandle> = OnceCell::new();
 
+#[cfg(feature = "desktop")]
 pub fn set_app_handle(handle: AppHandle) {
     APP_HANDLE.set(handle).expect("Failed to set app handle");
 }
 
+#[cfg(feature = "desktop")]
 pub fn get_app_handle() -> Option<&'static AppHandle> {
     APP_HANDLE.get()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Directory for persisted app data, preferring Tauri's resolved app data dir.
+pub fn app_data_dir() -> PathBuf {
+    #[cfg(feature = "desktop")]
+    if let Some(dir) = APP_HANDLE.get().and_then(|handle| handle.path_resolver().app_data_dir()) {
+        return dir;
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join("com.screenshotai.studio"))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Directory for user settings, preferring Tauri's resolved app config dir.
+pub fn app_config_dir() -> PathBuf {
+    #[cfg(feature = "desktop")]
+    if let Some(dir) = APP_HANDLE.get().and_then(|handle| handle.path_resolver().app_config_dir()) {
+        return dir;
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("com.screenshotai.studio"))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ScreenshotMetadata {
     pub source: Option<String>,
     pub app: Option<String>,
+    /// Title of the window in front when the screenshot was taken, e.g. the page or document open.
+    pub window_title: Option<String>,
     pub filename: Option<String>,
+    /// Where the screenshot file is on disk, updated when a file action moves it.
+    pub file_path: Option<String>,
     pub location: Option<String>,
     pub auto_detected: Option<bool>,
+    /// When the screenshot was taken, if earlier than its analysis (e.g. imported files).
+    pub captured_at: Option<DateTime<Utc>>,
+    /// Analyze with the local Ollama model and send nothing off the machine, as in privacy mode.
+    pub local_only: Option<bool>,
+    /// Set when the image is a storyboard of this many frames from a screen recording or animated GIF.
+    pub recording_frames: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -57,12 +244,23 @@ pub struct ProcessedImage {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ContentAnalysis {
     pub content_type: String,
     pub webpage_url: Option<String>,
     pub research_topics: Vec<String>,
     pub user_intent: String,
     pub follow_up: String,
+    /// Short lowercase labels for organizing screenshots, from the model and added by hand.
+    pub tags: Vec<String>,
+    /// Every URL visible in the screenshot, normalized, with titles once they've been fetched.
+    pub links: Vec<Link>,
+    /// Whether the screenshot shows a table of data worth extracting.
+    pub has_table: bool,
+    /// Main language of the text in the screenshot, in English, e.g. `Japanese`.
+    pub language: Option<String>,
+    /// The readable text in the screenshot, as plain text, so searches can find what it said.
+    pub extracted_text: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,15 +272,31 @@ pub struct AnalysisData {
     pub timestamp: DateTime<Utc>,
     pub source: String,
     pub image_base64: String, // Store the original base64 data for thumbnails
+    /// Telegram messages posted about this analysis, so follow-ups can thread under them.
+    #[serde(default)]
+    pub telegram_messages: Vec<TelegramMessageRef>,
+    /// The analysis this one re-analyzed with a different prompt or model.
+    #[serde(default)]
+    pub revision_of: Option<String>,
+    /// Written by the user, not the model.
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub project_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ScreenshotRequest {
     pub image: String,
     pub metadata: Option<ScreenshotMetadata>,
+    /// Same as the `Idempotency-Key` header, for clients that can't set headers.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProcessingResponse {
     pub success: bool,
     pub summary: Option<String>,
@@ -91,6 +305,103 @@ pub struct ProcessingResponse {
     pub follow_up_available: Option<bool>,
     pub source: Option<String>,
     pub error: Option<String>,
+    /// Why a batch item failed, with the same codes as error responses.
+    #[serde(default)]
+    pub error_code: Option<ErrorCode>,
+    /// LLM requests retried after transient provider errors.
+    #[serde(default)]
+    pub retries: u32,
+    /// Whether the fallback provider had to answer for the primary one.
+    #[serde(default)]
+    pub failed_over: bool,
+    /// Tokens and estimated cost of the LLM requests made for this screenshot.
+    #[serde(default)]
+    pub usage: Option<UsageTotals>,
+    /// The image nearly matched a recent one, whose analysis is returned instead.
+    #[serde(default)]
+    pub duplicate: bool,
+    /// Time spent in each stage of the analysis.
+    #[serde(default)]
+    pub timings: Option<StageTimings>,
+}
+
+impl ProcessingResponse {
+    pub fn failed(error: ApiError) -> Self {
+        Self {
+            success: false,
+            summary: None,
+            analysis_id: None,
+            timestamp: Utc::now(),
+            follow_up_available: None,
+            source: None,
+            error: Some(error.message),
+            error_code: Some(error.code),
+            retries: 0,
+            failed_over: false,
+            usage: None,
+            duplicate: false,
+            timings: None,
+        }
+    }
+}
+
+/// An analysis as returned by the history endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisRecord {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub source: String,
+    pub summary: String,
+    pub content_analysis: ContentAnalysis,
+    pub metadata: ScreenshotMetadata,
+    pub media_type: String,
+    pub size_bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_base64: Option<String>,
+    #[serde(default)]
+    pub revision_of: Option<String>,
+    #[serde(default)]
+    pub note: Option<String>,
+    #[serde(default)]
+    pub starred: bool,
+    #[serde(default)]
+    pub project_id: Option<String>,
+}
+
+impl AnalysisRecord {
+    pub fn from_analysis(id: String, analysis: AnalysisData, include_image: bool) -> Self {
+        Self {
+            id,
+            timestamp: analysis.timestamp,
+            source: analysis.source,
+            summary: analysis.brief_summary,
+            content_analysis: analysis.content_analysis,
+            metadata: analysis.metadata,
+            media_type: analysis.image_data.media_type,
+            size_bytes: analysis.image_data.size_bytes,
+            image_base64: include_image.then_some(analysis.image_data.base64_data),
+            revision_of: analysis.revision_of,
+            note: analysis.note,
+            starred: analysis.starred,
+            project_id: analysis.project_id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalysisPage {
+    pub analyses: Vec<AnalysisRecord>,
+    pub page: usize,
+    pub page_size: usize,
+    pub total: usize,
+}
+
+/// A full-text search hit; `snippet` wraps matched terms in `<mark>` tags.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub analysis: AnalysisRecord,
+    pub snippet: String,
+    pub rank: f64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -104,48 +415,717 @@ pub struct ServerStatus {
     pub active_analyses: usize,
     pub telegram_configured: bool,
     pub desktop_detection_enabled: bool,
+    pub clipboard_detection_enabled: bool,
+    /// Screenshots waiting for a free processing slot.
+    pub queue_depth: usize,
+    pub active_requests: usize,
+    pub max_concurrent_requests: usize,
+    /// Screenshots saved while offline, waiting to be analyzed.
+    pub outbox_pending: usize,
+    /// Name the server is advertised under via mDNS (`_screenshot-ai._tcp`), when advertising.
+    pub mdns_name: Option<String>,
+    /// SHA-256 fingerprint of the server certificate when serving HTTPS, for clients to pin.
+    pub tls_fingerprint: Option<String>,
+    /// Requests allowed per client per minute; 0 when unlimited.
+    pub rate_limit_per_minute: u32,
+    /// Requests rejected with 429 since the server started.
+    pub rate_limited_requests: u64,
+    /// Clients currently waiting out their limit.
+    pub rate_limited_clients: usize,
+    /// New screenshots are being held back instead of analyzed.
+    pub paused: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppConfig {
-    pub anthropic_api_key: String,
+    #[serde(default)]
+    pub provider: ProviderKind,
+    pub anthropic_api_key: Option<String>,
+    #[serde(default)]
+    pub openai_api_key: Option<String>,
+    #[serde(default)]
+    pub gemini_api_key: Option<String>,
+    #[serde(default)]
+    pub ollama_url: Option<String>,
+    /// Overrides the provider's default model id.
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default = "default_summary_max_tokens")]
+    pub summary_max_tokens: u32,
+    #[serde(default = "default_analysis_max_tokens")]
+    pub analysis_max_tokens: u32,
+    /// Ask for the summary and content analysis in one structured request instead of two.
+    #[serde(default = "default_true")]
+    pub combine_llm_requests: bool,
+    /// Prompt templates; `{source_type}`, `{device}`, `{app}`, `{filename}` and `{date}` are substituted.
+    #[serde(default)]
+    pub summary_prompt: Option<String>,
+    #[serde(default)]
+    pub analysis_prompt: Option<String>,
+    /// Prompt template names by `metadata.app`, taking precedence over the prompts above.
+    #[serde(default)]
+    pub app_prompt_templates: HashMap<String, String>,
+    /// Prompt template names by detected content type, used to write the summary for screenshots
+    /// whose app has no template.
+    #[serde(default)]
+    pub content_type_prompt_templates: HashMap<String, String>,
     pub telegram_bot_token: Option<String>,
     pub telegram_chat_id: Option<String>,
+    /// Chats that get notifications and may use the bot, in addition to `telegram_chat_id`.
+    #[serde(default)]
+    pub telegram_chats: Vec<TelegramChat>,
+    /// Forum topic per content type for `telegram_chat_id`; see [`TelegramChat::topics`].
+    #[serde(default)]
+    pub telegram_topics: HashMap<String, i32>,
+    /// Screenshots arriving within this many seconds of each other are sent to Telegram as one
+    /// session message once the burst ends; 0 sends each one right away.
+    #[serde(default)]
+    pub session_window_secs: u64,
+    /// Send a summary of the day's analyses to Telegram once a day.
+    #[serde(default)]
+    pub digest_enabled: bool,
+    /// Time of day (`HH:MM`) the digest is sent.
+    #[serde(default = "default_digest_time")]
+    pub digest_time: String,
+    /// IANA timezone for `digest_time` and the digest's day boundaries; `None` uses the system timezone.
+    #[serde(default)]
+    pub digest_timezone: Option<String>,
     pub enable_desktop_detection: bool,
+    #[serde(default)]
+    pub enable_clipboard_detection: bool,
+    /// Screenshot sources (e.g. `desktop_auto`, `clipboard`) that show a native notification when analyzed.
+    #[serde(default = "default_desktop_notification_sources")]
+    pub desktop_notification_sources: Vec<String>,
+    /// Folders the desktop watcher monitors; empty watches the platform's screenshot folders.
+    #[serde(default)]
+    pub watch_directories: Vec<WatchDirectory>,
+    /// Filename globs (or `re:` regexes) that mark a new image as a screenshot.
+    #[serde(default = "default_include_patterns")]
+    pub screenshot_include_patterns: Vec<String>,
+    /// Filename globs (or `re:` regexes) that are never treated as screenshots.
+    #[serde(default)]
+    pub screenshot_exclude_patterns: Vec<String>,
+    /// Periodic screen captures building a timeline of the day.
+    #[serde(default)]
+    pub journal: JournalConfig,
+    /// Web search and limits for the deep research agent.
+    #[serde(default)]
+    pub research: ResearchConfig,
+    /// Text-to-speech for summaries sent as Telegram voice messages.
+    #[serde(default)]
+    pub tts: TtsConfig,
+    /// How much history to keep before the oldest analyses are deleted.
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    /// Which screenshots are accepted at all, whatever their source.
+    #[serde(default)]
+    pub image_policy: ImagePolicy,
+    /// Screen recordings accepted in place of screenshots, and how they're storyboarded.
+    #[serde(default)]
+    pub recordings: RecordingConfig,
+    /// Longest edge, in pixels, of images sent to the LLM.
+    #[serde(default = "default_max_image_dimension")]
+    pub max_image_dimension: u32,
+    /// Size budget for images sent to the LLM; larger images are recompressed as JPEG.
+    #[serde(default = "default_max_image_bytes")]
+    pub max_image_bytes: usize,
+    /// Screenshots analyzed at the same time; others wait in the queue.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Screenshots allowed to wait before new ones are rejected.
+    #[serde(default = "default_max_queue_depth")]
+    pub max_queue_depth: usize,
+    /// Identical images detected again within this many seconds are not re-analyzed.
+    #[serde(default = "default_duplicate_window_secs")]
+    pub duplicate_window_secs: u64,
+    /// Return the existing analysis for near-identical images (rescaled, recompressed) seen within the window.
+    #[serde(default = "default_true")]
+    pub perceptual_dedupe: bool,
+    /// Submissions repeating an `Idempotency-Key` within this many seconds get the original response; 0 disables.
+    #[serde(default = "default_idempotency_window_secs")]
+    pub idempotency_window_secs: u64,
+    /// Scan screenshots locally with Tesseract OCR for emails, phone numbers, API keys and card
+    /// numbers, then blur them or refuse the screenshot.
+    #[serde(default)]
+    pub redaction_mode: RedactionMode,
+    /// Redaction mode by screenshot source (`iOS`, `desktop_auto`, `clipboard`, ...), overriding the above.
+    #[serde(default)]
+    pub redaction_sources: HashMap<String, RedactionMode>,
+    /// Model, prompt, notification chat and retention by screenshot source, overriding the global ones.
+    #[serde(default)]
+    pub profiles: HashMap<String, ProcessingProfile>,
+    /// Times a request failing with a transient error (overloaded, rate limited, timeout) is retried.
+    #[serde(default = "default_retry_attempts")]
+    pub retry_attempts: u32,
+    /// First retry delay; later retries back off exponentially with jitter.
+    #[serde(default = "default_retry_base_delay_ms")]
+    pub retry_base_delay_ms: u64,
+    /// Analyze every screenshot with the local Ollama model and send nothing off the machine:
+    /// Telegram, webhooks, Notion and push notifications are all skipped.
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// Whether screenshots arriving while processing is paused are dropped or kept for later.
+    #[serde(default)]
+    pub pause_mode: PauseMode,
+    /// What to do with images the local classifier takes for private photos, before any upload.
+    #[serde(default)]
+    pub content_filter: ContentFilterMode,
+    /// Screenshots taken in these apps or windows, or with these filenames, are never analyzed.
+    #[serde(default = "default_exclusion_rules")]
+    pub exclusion_rules: Vec<ExclusionRule>,
+    /// Encrypt the history database and screenshot files with a key kept in the OS keychain.
+    /// Turning this on or off converts what's already stored at the next start.
+    #[serde(default)]
+    pub encrypt_storage: bool,
+    /// Ollama model used in privacy mode and for local-only requests, e.g. `llava` or `qwen2.5vl`.
+    #[serde(default)]
+    pub local_model: Option<String>,
+    /// Provider to fall back to once retries against the primary one are exhausted.
+    #[serde(default)]
+    pub fallback_provider: Option<ProviderKind>,
+    /// Overrides the fallback provider's default model id.
+    #[serde(default)]
+    pub fallback_model: Option<String>,
+    /// Per-model prices keyed by model id prefix, overriding the built-in table.
+    #[serde(default)]
+    pub model_prices: HashMap<String, ModelPrice>,
+    /// Monthly LLM spend, in USD, at which a warning is sent.
+    #[serde(default)]
+    pub budget_soft_limit_usd: Option<f64>,
+    /// Monthly LLM spend, in USD, after which new analyses are refused.
+    #[serde(default)]
+    pub budget_hard_limit_usd: Option<f64>,
+    /// Endpoints that receive a signed JSON POST whenever an analysis completes.
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// Base URL this server is reachable at, used to link images from webhooks and Notion pages.
+    #[serde(default)]
+    pub public_url: Option<String>,
+    /// ntfy, Pushover and Gotify backends notified when an analysis completes.
+    #[serde(default)]
+    pub push_notifiers: Vec<PushNotifierConfig>,
+    /// Obsidian vault (or any folder) analyses are exported to as Markdown notes.
+    #[serde(default)]
+    pub vault_export_dir: Option<PathBuf>,
+    /// Export every analysis to the vault as soon as it completes.
+    #[serde(default)]
+    pub vault_auto_export: bool,
+    /// Write a text stub per analysis into `spotlight_dir` so system search finds screenshots by summary.
+    #[serde(default)]
+    pub spotlight_index: bool,
+    /// Folder for the search stubs; `Documents/Screenshot AI` if unset.
+    #[serde(default)]
+    pub spotlight_dir: Option<PathBuf>,
+    /// Write analyses of watched screenshots into the files' XMP metadata or a Markdown sidecar.
+    #[serde(default)]
+    pub metadata_writeback: MetadataWriteback,
+    /// Fetch the titles of links found in screenshots. Off for local-only screenshots regardless.
+    #[serde(default = "default_true")]
+    pub unfurl_links: bool,
+    /// Read merchant, line items and totals off screenshots of receipts and invoices.
+    #[serde(default = "default_true")]
+    pub receipt_extraction: bool,
+    /// CSV file every extracted receipt is appended to.
+    #[serde(default)]
+    pub receipt_ledger: Option<PathBuf>,
+    /// Transcribe the code in screenshots of code so it can be copied or saved.
+    #[serde(default = "default_true")]
+    pub code_extraction: bool,
+    /// Extract tables (pricing pages, spreadsheets, dashboards) to CSV and JSON.
+    #[serde(default = "default_true")]
+    pub table_extraction: bool,
+    /// Append a translation into `translation_language` to summaries of screenshots in another language.
+    #[serde(default)]
+    pub auto_translate: bool,
+    /// Language translations are made into, e.g. `English` or `German`.
+    #[serde(default = "default_translation_language")]
+    pub translation_language: String,
+    /// Language summaries, analyses and session summaries are written in, e.g. `German`; English when unset.
+    /// Templates can also refer to it as `{language}`.
+    #[serde(default)]
+    pub response_language: Option<String>,
+    /// Notion integration token; analyses are added to `notion_database_id` when both are set.
+    #[serde(default)]
+    pub notion_token: Option<String>,
+    #[serde(default)]
+    pub notion_database_id: Option<String>,
+    #[serde(default)]
+    pub notion_properties: NotionProperties,
     pub server_port: u16,
+    /// Requests each client may make per minute, counted per API key or per IP without one; 0 disables.
+    #[serde(default = "default_rate_limit_per_minute")]
+    pub rate_limit_per_minute: u32,
+    /// Days of request history kept in the audit log; 0 keeps it forever.
+    #[serde(default = "default_audit_retention_days")]
+    pub audit_retention_days: u32,
+    /// How many ports above `server_port` to try when it's taken; 0 uses `server_port` or fails.
+    #[serde(default = "default_server_port_range")]
+    pub server_port_range: u16,
+    /// Port the companion gRPC API is served on, in builds with the `grpc` feature; `None` doesn't serve it.
+    #[serde(default)]
+    pub grpc_port: Option<u16>,
+    /// Key HTTP clients must present; `None` leaves the server open.
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Advertise the server on the local network so clients can find it without its IP.
+    #[serde(default = "default_true")]
+    pub mdns_enabled: bool,
+    /// Serve HTTPS instead of HTTP, with a self-signed certificate unless one is configured.
+    #[serde(default)]
+    pub tls_enabled: bool,
+    /// PEM certificate chain to serve instead of the generated one; needs `tls_key_path` too.
+    #[serde(default)]
+    pub tls_cert_path: Option<PathBuf>,
+    #[serde(default)]
+    pub tls_key_path: Option<PathBuf>,
+}
+
+/// A folder watched for new screenshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchDirectory {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub recursive: bool,
+    /// Treat every image here as a screenshot, regardless of its filename.
+    #[serde(default)]
+    pub accept_all_images: bool,
+    /// What to do with a screenshot from here once it has been analyzed.
+    #[serde(default)]
+    pub after_analysis: FileAction,
+    /// Where archived screenshots go; an `Analyzed` folder inside this one if unset.
+    #[serde(default)]
+    pub archive_dir: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone)]
+impl WatchDirectory {
+    /// The user's Desktop, watched when nothing else is configured.
+    pub fn desktop() -> Self {
+        let path = dirs::desktop_dir().unwrap_or_else(|| {
+            dirs::home_dir()
+                .map(|h| h.join("Desktop"))
+                .unwrap_or_else(|| PathBuf::from("."))
+        });
+
+        Self {
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// The path with a leading `~` expanded to the home directory.
+    pub fn resolved_path(&self) -> PathBuf {
+        match (self.path.strip_prefix("~"), dirs::home_dir()) {
+            (Ok(rest), Some(home)) => home.join(rest),
+            _ => self.path.clone(),
+        }
+    }
+
+    fn recursive_mode(&self) -> RecursiveMode {
+        if self.recursive {
+            RecursiveMode::Recursive
+        } else {
+            RecursiveMode::NonRecursive
+        }
+    }
+}
+
+fn default_include_patterns() -> Vec<String> {
+    DEFAULT_INCLUDE_PATTERNS.iter().map(|p| p.to_string()).collect()
+}
+
+fn default_desktop_notification_sources() -> Vec<String> {
+    vec!["desktop_auto".to_string()]
+}
+
+fn default_max_image_dimension() -> u32 {
+    1568
+}
+
+fn default_max_image_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_concurrent_requests() -> usize {
+    2
+}
+
+fn default_max_queue_depth() -> usize {
+    20
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_translation_language() -> String {
+    "English".to_string()
+}
+
+fn default_server_port_range() -> u16 {
+    10
+}
+
+fn default_rate_limit_per_minute() -> u32 {
+    60
+}
+
+fn default_audit_retention_days() -> u32 {
+    30
+}
+
+fn default_duplicate_window_secs() -> u64 {
+    600
+}
+
+fn default_idempotency_window_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_digest_time() -> String {
+    "20:00".to_string()
+}
+
+fn default_summary_max_tokens() -> u32 {
+    200
+}
+
+fn default_analysis_max_tokens() -> u32 {
+    // Room for the screenshot's text as well as the rest of the analysis
+    800
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            anthropic_api_key: None,
+            openai_api_key: None,
+            gemini_api_key: None,
+            ollama_url: None,
+            model: None,
+            summary_max_tokens: default_summary_max_tokens(),
+            analysis_max_tokens: default_analysis_max_tokens(),
+            combine_llm_requests: true,
+            summary_prompt: None,
+            analysis_prompt: None,
+            app_prompt_templates: HashMap::new(),
+            content_type_prompt_templates: HashMap::new(),
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            telegram_chats: Vec::new(),
+            telegram_topics: HashMap::new(),
+            session_window_secs: 0,
+            digest_enabled: false,
+            digest_time: default_digest_time(),
+            digest_timezone: None,
+            enable_desktop_detection: false,
+            enable_clipboard_detection: false,
+            desktop_notification_sources: default_desktop_notification_sources(),
+            watch_directories: Vec::new(),
+            screenshot_include_patterns: default_include_patterns(),
+            screenshot_exclude_patterns: Vec::new(),
+            journal: JournalConfig::default(),
+            research: ResearchConfig::default(),
+            tts: TtsConfig::default(),
+            retention: RetentionConfig::default(),
+            image_policy: ImagePolicy::default(),
+            recordings: RecordingConfig::default(),
+            max_image_dimension: default_max_image_dimension(),
+            max_image_bytes: default_max_image_bytes(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            max_queue_depth: default_max_queue_depth(),
+            duplicate_window_secs: default_duplicate_window_secs(),
+            idempotency_window_secs: default_idempotency_window_secs(),
+            perceptual_dedupe: true,
+            redaction_mode: RedactionMode::Off,
+            redaction_sources: HashMap::new(),
+            profiles: HashMap::new(),
+            retry_attempts: default_retry_attempts(),
+            retry_base_delay_ms: default_retry_base_delay_ms(),
+            privacy_mode: false,
+            pause_mode: PauseMode::default(),
+            content_filter: ContentFilterMode::default(),
+            exclusion_rules: default_exclusion_rules(),
+            encrypt_storage: false,
+            local_model: None,
+            fallback_provider: None,
+            fallback_model: None,
+            model_prices: HashMap::new(),
+            budget_soft_limit_usd: None,
+            budget_hard_limit_usd: None,
+            webhooks: Vec::new(),
+            public_url: None,
+            push_notifiers: Vec::new(),
+            vault_export_dir: None,
+            vault_auto_export: false,
+            spotlight_index: false,
+            spotlight_dir: None,
+            metadata_writeback: MetadataWriteback::Off,
+            unfurl_links: true,
+            receipt_extraction: true,
+            receipt_ledger: None,
+            code_extraction: true,
+            table_extraction: true,
+            auto_translate: false,
+            translation_language: default_translation_language(),
+            response_language: None,
+            notion_token: None,
+            notion_database_id: None,
+            notion_properties: NotionProperties::default(),
+            server_port: 5001,
+            server_port_range: default_server_port_range(),
+            grpc_port: None,
+            rate_limit_per_minute: default_rate_limit_per_minute(),
+            audit_retention_days: default_audit_retention_days(),
+            api_key: None,
+            mdns_enabled: true,
+            tls_enabled: false,
+            tls_cert_path: None,
+            tls_key_path: None,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct ScreenshotProcessor {
     config: AppConfig,
     client: Client,
-    pending_analyses: Arc<DashMap<String, AnalysisData>>,
+    provider: Arc<dyn VisionProvider>,
+    store: Arc<AnalysisStore>,
     request_count: Arc<AtomicU64>,
     last_request_time: Arc<RwLock<Option<DateTime<Utc>>>>,
     telegram_bot: Option<Bot>,
+    api_key: Arc<parking_lot::RwLock<Option<String>>>,
+    events: broadcast::Sender<ProgressEvent>,
+    queue: queue::WorkQueue,
+    /// Start of the month for which the hard budget limit was overridden.
+    budget_override: Arc<parking_lot::Mutex<Option<DateTime<Utc>>>>,
+    notifiers: Arc<Vec<Arc<dyn Notifier>>>,
+    recent_images: Arc<parking_lot::Mutex<dedupe::RecentImages>>,
+    advertised_name: Arc<parking_lot::RwLock<Option<String>>>,
+    pairing: Arc<parking_lot::Mutex<Option<pairing::PendingPairing>>>,
+    tls: Option<Arc<TlsIdentity>>,
+    idempotency: Arc<parking_lot::Mutex<idempotency::IdempotencyCache>>,
+    rate_limiter: Arc<rate_limit::RateLimiter>,
+    journal_paused_until: Arc<parking_lot::Mutex<Option<DateTime<Utc>>>>,
+    session: Arc<parking_lot::Mutex<Option<sessions::PendingSession>>>,
+    last_cleanup: Arc<parking_lot::Mutex<Option<CleanupReport>>>,
+    inflight: inflight::InflightAnalyses,
+    /// Whether new screenshots are held back instead of analyzed.
+    paused: Arc<tokio::sync::watch::Sender<bool>>,
+    exclusions: Arc<parking_lot::RwLock<exclusions::ExclusionRules>>,
+}
+
+impl std::fmt::Debug for ScreenshotProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScreenshotProcessor")
+            .field("config", &self.config)
+            .field("provider", &self.provider.name())
+            .field("store", &self.store)
+            .finish()
+    }
+}
+
+/// The configured provider, with retries and failover.
+fn build_resilient_provider(config: &AppConfig, client: &Client) -> Result<Arc<dyn VisionProvider>> {
+    Ok(Arc::new(retry::ResilientProvider::new(
+        providers::build_provider(config, client.clone())?,
+        providers::build_fallback_provider(config, client.clone()),
+        retry::RetryPolicy {
+            max_retries: config.retry_attempts,
+            base_delay: Duration::from_millis(config.retry_base_delay_ms),
+        },
+    )))
 }
+
 impl ScreenshotProcessor {
-    pub fn new(config: AppConfig) -> Self {
+    pub fn new(config: AppConfig) -> Result<Self> {
+        let config = if config.privacy_mode {
+            info!("🔒 Privacy mode: analyzing locally with Ollama, nothing leaves this machine");
+            privacy::local_config(&config)
+        } else {
+            config
+        };
         let telegram_bot = config
             .telegram_bot_token
             .as_ref()
-            .map(|token| Bot::new(token));
+            .filter(|_| !config.privacy_mode)
+            .map(Bot::new);
+
+        let client = Client::new();
+        let provider = build_resilient_provider(&config, &client)?;
+        let store = encryption::open_store(&app_data_dir().join("analyses.db"), config.encrypt_storage)?;
+        let api_key = config.api_key.clone().filter(|key| !key.trim().is_empty());
+        let queue = queue::WorkQueue::new(config.max_concurrent_requests, config.max_queue_depth);
+        let recent_images = dedupe::RecentImages::new(Duration::from_secs(config.duplicate_window_secs));
+        let notifiers = config
+            .push_notifiers
+            .iter()
+            .filter(|_| !config.privacy_mode)
+            .map(|notifier| notifier.build(client.clone()))
+            .collect();
+        let rate_limiter = rate_limit::RateLimiter::new(config.rate_limit_per_minute);
+        let exclusions = exclusions::ExclusionRules::new(&config.exclusion_rules)?;
+        let idempotency = idempotency::IdempotencyCache::new(Duration::from_secs(config.idempotency_window_secs));
+        let tls = if config.tls_enabled {
+            let identity = tls::load_identity(&config)?;
+            info!("🔐 Serving HTTPS, certificate fingerprint {}", identity.fingerprint);
+            Some(Arc::new(identity))
+        } else {
+            None
+        };
 
-        Self {
+        Ok(Self {
             config,
-            client: Client::new(),
-            pending_analyses: Arc::new(DashMap::new()),
+            client,
+            provider,
+            store: Arc::new(store),
             request_count: Arc::new(AtomicU64::new(0)),
             last_request_time: Arc::new(RwLock::new(None)),
             telegram_bot,
+            api_key: Arc::new(parking_lot::RwLock::new(api_key)),
+            events: broadcast::channel(events::EVENT_CAPACITY).0,
+            queue,
+            budget_override: Arc::new(parking_lot::Mutex::new(None)),
+            notifiers: Arc::new(notifiers),
+            recent_images: Arc::new(parking_lot::Mutex::new(recent_images)),
+            advertised_name: Arc::new(parking_lot::RwLock::new(None)),
+            pairing: Arc::new(parking_lot::Mutex::new(None)),
+            tls,
+            idempotency: Arc::new(parking_lot::Mutex::new(idempotency)),
+            rate_limiter: Arc::new(rate_limiter),
+            journal_paused_until: Arc::new(parking_lot::Mutex::new(None)),
+            session: Arc::new(parking_lot::Mutex::new(None)),
+            last_cleanup: Arc::new(parking_lot::Mutex::new(None)),
+            inflight: Arc::new(parking_lot::Mutex::new(HashMap::new())),
+            paused: Arc::new(tokio::sync::watch::channel(false).0),
+            exclusions: Arc::new(parking_lot::RwLock::new(exclusions)),
+        })
+    }
+
+    /// The API key currently required from HTTP clients, if any.
+    pub fn api_key(&self) -> Option<String> {
+        self.api_key.read().clone()
+    }
+
+    /// SHA-256 fingerprint of the certificate the server presents, when serving HTTPS.
+    pub fn tls_fingerprint(&self) -> Option<String> {
+        self.tls.as_ref().map(|identity| identity.fingerprint.clone())
+    }
+
+    /// "https" or "http", for building URLs clients can reach the server at.
+    pub fn url_scheme(&self) -> &'static str {
+        if self.tls.is_some() {
+            "https"
+        } else {
+            "http"
         }
     }
 
+    /// Replaces the required API key on the running server.
+    pub fn set_api_key(&self, api_key: Option<String>) {
+        *self.api_key.write() = api_key;
+    }
+
     pub async fn process_screenshot(
         &self,
         image_base64: &str,
         metadata: Option<ScreenshotMetadata>,
     ) -> Result<ProcessingResponse> {
+        // Recordings and animated GIFs are analyzed as a storyboard of their frames, which is also
+        // what's queued or kept
+        let mut metadata = metadata;
+        if recording::may_need_storyboard(image_base64) {
+            let bytes = general_purpose::STANDARD
+                .decode(image_base64.rsplit(',').next().unwrap_or_default())
+                .map_err(|e| InvalidImage(format!("Invalid base64: {}", e)))?;
+            if let Some(storyboard) = self.storyboard(&bytes, &mut metadata).await? {
+                return self.process_storyboard(&storyboard, metadata).await;
+            }
+        }
+        self.process_image(image_base64, metadata).await
+    }
+
+    /// Analyzes a single image, unless processing is paused or the image is held back.
+    pub(crate) async fn process_image(
+        &self,
+        image_base64: &str,
+        metadata: Option<ScreenshotMetadata>,
+    ) -> Result<ProcessingResponse> {
+        if self.is_paused() {
+            return Err(self.hold_while_paused(image_base64, &metadata));
+        }
+        let metadata = self.screen_content(image_base64, metadata).await?;
+
+        // Local-only screenshots keep to the local model whatever their source's profile says
+        let source = metadata.as_ref().and_then(|m| m.source.as_deref()).unwrap_or("iOS");
+        let routed;
+        let processor = if privacy::wants_local_only(&metadata) && !self.config.privacy_mode {
+            routed = self.local_only()?;
+            &routed
+        } else if let Some(profile) = self.config.profile(source) {
+            routed = self.with_profile(profile)?;
+            &routed
+        } else {
+            self
+        };
+
+        match processor.process_tracked(image_base64, metadata.clone()).await {
+            // Keep screenshots the provider couldn't be reached for, rather than losing them
+            Err(e) if retry::is_transient(&e) => Err(self.enqueue_offline(image_base64, &metadata, e)),
+            result => result,
+        }
+    }
+
+    /// Analyzes a screenshot, reporting the LLM retries and tokens it took in the response.
+    async fn process_tracked(
+        &self,
+        image_base64: &str,
+        metadata: Option<ScreenshotMetadata>,
+    ) -> Result<ProcessingResponse> {
+        self.check_exclusions(&metadata)?;
+
+        // Near-duplicates of a recent screenshot reuse its analysis without calling the LLM
+        let image_hash = self.perceptual_hash(image_base64).await;
+        if let Some(response) = image_hash.and_then(|hash| self.near_duplicate(hash)) {
+            return Ok(response);
+        }
+
+        self.check_budget()?;
+
+        let ((result, records), stats) =
+            retry::track(usage::track(self.analyze_screenshot(image_base64, metadata))).await;
+
+        // Tokens spent on a failed analysis still count towards the totals
+        let analysis_id = result.as_ref().ok().and_then(|r| r.analysis_id.as_deref());
+        let usage = self.save_usage(analysis_id, &records);
+        if let (Some(hash), Some(id)) = (image_hash, analysis_id) {
+            self.recent_images.lock().insert(hash, id);
+        }
+
+        result.map(|response| ProcessingResponse {
+            retries: stats.retries(),
+            failed_over: stats.failovers() > 0,
+            usage: Some(usage),
+            ..response
+        })
+    }
+
+    async fn analyze_screenshot(
+        &self,
+        image_base64: &str,
+        metadata: Option<ScreenshotMetadata>,
+    ) -> Result<ProcessingResponse> {
+        let started = Instant::now();
+        let mut timings = StageTimings::default();
         let count = self.request_count.fetch_add(1, Ordering::Relaxed) + 1;
         let now = Utc::now();
         *self.last_request_time.write().await = Some(now);
@@ -158,51 +1138,152 @@ impl ScreenshotProcessor {
 
         info!("📱 Processing screenshot #{} (source: {})", count, source_type);
 
-        // Prepare image data
-        let processed_image = self.prepare_image_data(image_base64)?;
+        // Generate the analysis ID up front so progress events can refer to it
+        let analysis_id = failures::analysis_id();
+        self.emit_progress(&analysis_id, ProgressStage::Received { source: source_type.to_string() });
+        emit_event(UiEvent::AnalysisStarted(events::AnalysisStarted {
+            analysis_id: analysis_id.clone(),
+            source: source_type.to_string(),
+            timestamp: now,
+        }));
+        let report_failure = |e: &anyhow::Error| {
+            self.emit_progress(&analysis_id, ProgressStage::Failed { error: e.to_string() });
+            emit_event(UiEvent::AnalysisFailed(events::AnalysisFailed {
+                analysis_id: analysis_id.clone(),
+                source: source_type.to_string(),
+                error: e.to_string(),
+                timestamp: Utc::now(),
+            }));
+            self.record_failure(&analysis_id, source_type, image_base64, &metadata, e);
+        };
+        // Cancelling stops the analysis at whichever wait it's in, before anything is stored
+        let inflight = self.track_inflight(&analysis_id);
 
-        // Generate analysis ID
-        let analysis_id = Uuid::new_v4().to_string();
+        // Hold a processing slot for the whole analysis to bound concurrent LLM requests
+        let _permit = inflight
+            .run(async { self.queue.acquire().await.map_err(anyhow::Error::from) })
+            .await
+            .inspect_err(report_failure)?;
+        timings.queue_ms = pipeline::elapsed_ms(started);
+
+        // Prepare image data
+        let preprocess_started = Instant::now();
+        let processed_image = self.prepare_image_data(image_base64).inspect_err(report_failure)?;
+        // A redacted image replaces the original everywhere, including storage and Telegram
+        let redacted = inflight
+            .run(self.redact_sensitive(&analysis_id, &processed_image, source_type))
+            .await
+            .inspect_err(report_failure)?;
+        let processed_image = redacted.clone().unwrap_or(processed_image);
 
         // Get AI analysis
-        let brief_summary = self.get_brief_summary(&processed_image, source_type).await?;
-        let content_analysis = self.analyze_for_content_type(&processed_image).await?;
+        let project = self.project_for(metadata.as_ref());
+        let mut prompt_vars =
+            prompts::template_variables(source_type, metadata.as_ref(), self.config.response_language.as_deref());
+        prompt_vars.insert("project", project.as_ref().map(|p| p.name.clone()).unwrap_or_default());
+        let llm_image = self.llm_image(&processed_image).await;
+        timings.preprocess_ms = pipeline::elapsed_ms(preprocess_started);
+        self.emit_progress(
+            &analysis_id,
+            ProgressStage::Preprocessed {
+                media_type: llm_image.media_type.clone(),
+                size_bytes: llm_image.size_bytes,
+            },
+        );
+
+        let (brief_summary, content_analysis) = inflight
+            .run(self.summarize_and_analyze(
+                &analysis_id,
+                &llm_image,
+                &prompt_vars,
+                project.as_ref().and_then(|p| p.prompt_template.as_deref()),
+                &mut timings,
+            ))
+            .await
+            .inspect_err(report_failure)?;
+        // Foreign-language screenshots carry their translation wherever the summary is shown
+        let translation = inflight
+            .run(async { Ok(self.auto_translate(&analysis_id, &llm_image, &content_analysis).await) })
+            .await
+            .inspect_err(report_failure)?;
+        let brief_summary = match translation {
+            Some(ref translation) => translation::append_translation(&brief_summary, translation),
+            None => brief_summary,
+        };
+        self.emit_progress(&analysis_id, ProgressStage::SummaryDone { summary: brief_summary.clone() });
 
-        // Store analysis data WITH original base64 for thumbnails
+        // Store the original image, not the downscaled copy sent to the LLM
         let analysis_data = AnalysisData {
             image_data: processed_image,
             brief_summary: brief_summary.clone(),
             content_analysis: content_analysis.clone(),
             metadata: metadata.clone().unwrap_or_default(),
-            timestamp: now,
+            timestamp: metadata.as_ref().and_then(|m| m.captured_at).unwrap_or(now),
             source: source_type.to_string(),
-            image_base64: image_base64.to_string(), // Store original base64
+            // Store the original base64, unless it had to be redacted
+            image_base64: redacted.map(|image| image.base64_data).unwrap_or_else(|| image_base64.to_string()),
+            telegram_messages: Vec::new(),
+            revision_of: revision::revising(),
+            note: None,
+            starred: false,
+            project_id: project.map(|p| p.id),
         };
 
-        self.pending_analyses
-            .insert(analysis_id.clone(), analysis_data);
-
-        // Send to Telegram if configured
-        if let Some(ref bot) = self.telegram_bot {
-            if let Some(ref chat_id) = self.config.telegram_chat_id {
+        let store_started = Instant::now();
+        self.store.insert(&analysis_id, &analysis_data).inspect_err(report_failure)?;
+        if let Some(translation) = translation {
+            if let Err(e) = self.store.save_translation(&translation) {
+                warn!("Failed to save translation for {}: {}", analysis_id, e);
+            }
+        }
+        timings.store_ms = pipeline::elapsed_ms(store_started);
+        self.emit_progress(&analysis_id, ProgressStage::AnalysisDone);
+        let skip_telegram = self.run_rules(&analysis_id, &analysis_data);
+
+        // Privacy mode keeps the analysis on this machine
+        if !self.config.privacy_mode {
+            // Send to Telegram if configured, held back to go out with the rest of a burst
+            if skip_telegram {
+                info!("⚙️ Rules kept analysis {} out of Telegram", analysis_id);
+            } else if self.groups_sessions() {
+                self.add_to_session(&analysis_id);
+            } else {
                 if let Err(e) = self
                     .send_telegram_notification(
-                        bot,
-                        chat_id,
                         &brief_summary,
                         &analysis_id,
                         &content_analysis,
-                        &metadata,
+                        &analysis_data.image_data,
                         source_type,
+                        analysis_data.project_id.as_deref(),
                     )
                     .await
                 {
                     warn!("Failed to send Telegram notification: {}", e);
                 }
+                self.send_voice_notes(&analysis_id);
             }
+            self.notify_webhooks(&analysis_id, &analysis_data);
+            self.export_to_notion(&analysis_id, &analysis_data);
+            self.push_notification(Notification {
+                title: format!("📸 New {} screenshot", content_analysis.content_type),
+                message: brief_summary.clone(),
+                url: content_analysis.webpage_url.clone(),
+            });
+            self.unfurl_links(&analysis_id, &analysis_data);
         }
-
-        info!("✅ Screenshot processed successfully (ID: {})", analysis_id);
+        self.auto_export(&analysis_id, &analysis_data);
+        self.index_for_search(&analysis_id, &analysis_data);
+        self.notify_collections(&analysis_id);
+        self.process_receipt(&analysis_id, &analysis_data);
+        self.process_code(&analysis_id, &analysis_data);
+        self.process_tables(&analysis_id, &analysis_data);
+
+        timings.total_ms = pipeline::elapsed_ms(started);
+        info!(
+            "✅ Screenshot processed successfully (ID: {}) in {} ms",
+            analysis_id, timings.total_ms
+        );
 
         let response = ProcessingResponse {
             success: true,
@@ -212,6 +1293,12 @@ impl ScreenshotProcessor {
             follow_up_available: Some(true),
             source: Some(source_type.to_string()),
             error: None,
+            error_code: None,
+            retries: 0,
+            failed_over: false,
+            usage: None,
+            duplicate: false,
+            timings: Some(timings),
         };
 
         Ok(response)
@@ -223,7 +1310,7 @@ impl ScreenshotProcessor {
             image_base64
                 .split(',')
                 .nth(1)
-                .ok_or_else(|| anyhow!("Invalid data URL format"))?
+                .ok_or_else(|| InvalidImage("Invalid data URL format".to_string()))?
         } else {
             image_base64
         };
@@ -231,24 +1318,13 @@ impl ScreenshotProcessor {
         // Decode and validate
         let image_bytes = general_purpose::STANDARD
             .decode(clean_base64)
-            .map_err(|e| anyhow!("Invalid base64: {}", e))?;
+            .map_err(|e| InvalidImage(format!("Invalid base64: {}", e)))?;
 
-        // Size limits
-        if image_bytes.len() > 15 * 1024 * 1024 {
-            return Err(anyhow!("Image too large (max 15MB)"));
-        }
-        if image_bytes.len() < 1024 {
-            return Err(anyhow!("Image too small"));
-        }
-
-        // Determine media type
-        let media_type = if image_bytes.starts_with(&[0x89, 0x50, 0x4E, 0x47]) {
-            "image/png"
-        } else if image_bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
-            "image/jpeg"
-        } else {
-            "image/png" // Default
-        };
+        let media_type = self
+            .config
+            .image_policy
+            .validate(&image_bytes)
+            .map_err(|e| InvalidImage(e.to_string()))?;
 
         Ok(ProcessedImage {
             base64_data: clean_base64.to_string(),
@@ -257,174 +1333,168 @@ impl ScreenshotProcessor {
         })
     }
 
-    async fn get_brief_summary(&self, processed_image: &ProcessedImage, source_type: &str) -> Result<String> {
-        let prompt = if source_type.starts_with("desktop") {
-            "Analyze this desktop screenshot briefly. What is shown and what might be the user's intent?"
-        } else {
-            "Analyze this iPhone screenshot briefly. What is shown and what might be the user's intent?"
-        };
+    /// The image as sent to the LLM: downscaled and recompressed, falling back to the original.
+    async fn llm_image(&self, original: &ProcessedImage) -> ProcessedImage {
+        let image = original.clone();
+        let max_dimension = self.config.max_image_dimension;
+        let max_bytes = self.config.max_image_bytes;
 
-        let request_body = serde_json::json!({
-            "model": "claude-3-5-sonnet-20241022",
-            "max_tokens": 200,
-            "messages": [{
-                "role": "user",
-                "content": [
-                    {
-                        "type": "text",
-                        "text": prompt
-                    },
-                    {
-                        "type": "image",
-                        "source": {
-                            "type": "base64",
-                            "media_type": processed_image.media_type,
-                            "data": processed_image.base64_data
-                        }
-                    }
-                ]
-            }]
-        });
+        let prepared = tokio::task::spawn_blocking(move || {
+            preprocess::prepare_for_llm(&image, max_dimension, max_bytes)
+        })
+        .await
+        .map_err(|e| anyhow!("Preprocessing task failed: {}", e))
+        .and_then(|result| result);
+
+        prepared.unwrap_or_else(|e| {
+            warn!("Image preprocessing failed, sending original: {}", e);
+            original.clone()
+        })
+    }
 
-        let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.config.anthropic_api_key)
-            .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .json(&request_body)
-            .send()
+    /// Generates the short summary, streaming its tokens as progress events.
+    async fn get_brief_summary(
+        &self,
+        analysis_id: &str,
+        processed_image: &ProcessedImage,
+        prompt_vars: &HashMap<&'static str, String>,
+    ) -> Result<String> {
+        let template = self
+            .config
+            .summary_prompt
+            .as_deref()
+            .unwrap_or(prompts::DEFAULT_SUMMARY_PROMPT);
+        let prompt = format!(
+            "{}{}",
+            prompts::render(template, prompt_vars),
+            prompts::language_instruction(self.config.response_language.as_deref())
+        );
+
+        let on_token = |token: &str| {
+            self.emit_progress(analysis_id, ProgressStage::SummaryToken { token: token.to_string() })
+        };
+
+        self.provider
+            .complete_stream(&prompt, &[processed_image], self.config.summary_max_tokens, &on_token)
             .await
-            .map_err(|e| anyhow!("API request failed: {}", e))?;
+    }
 
-        if !response.status().is_success() {
-            return Err(anyhow!("Claude API error: {}", response.status()));
+    async fn analyze_for_content_type(
+        &self,
+        processed_image: &ProcessedImage,
+        prompt_vars: &HashMap<&'static str, String>,
+    ) -> Result<ContentAnalysis> {
+        let template = self
+            .config
+            .analysis_prompt
+            .as_deref()
+            .unwrap_or(prompts::DEFAULT_ANALYSIS_PROMPT);
+        let analysis_prompt = format!(
+            "{}{}",
+            prompts::render(template, prompt_vars),
+            prompts::language_instruction(self.config.response_language.as_deref())
+        );
+
+        let structured = self
+            .provider
+            .complete_json(
+                &analysis_prompt,
+                &[processed_image],
+                &ContentAnalysis::schema(),
+                self.config.analysis_max_tokens,
+            )
+            .await
+            .and_then(|json| Ok(serde_json::from_str::<ContentAnalysis>(&json)?));
+        match structured {
+            Ok(analysis) => return Ok(analysis.normalized()),
+            Err(e) => debug!("Structured content analysis unavailable, parsing text instead: {}", e),
         }
 
-        let response_json: serde_json::Value = response
-            .json()
+        match self
+            .provider
+            .complete(&analysis_prompt, &[processed_image], self.config.analysis_max_tokens)
             .await
-            .map_err(|e| anyhow!("Failed to parse response: {}", e))?;
-
-        let summary = response_json["content"][0]["text"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Invalid response format"))?;
-
-        Ok(summary.to_string())
-    }
-
-    async fn analyze_for_content_type(&self, processed_image: &ProcessedImage) -> Result<ContentAnalysis> {
-        let analysis_prompt = r#"Analyze this screenshot and determine:
-
-1. Content type (webpage, app, document, social media, etc.)
-2. If webpage: extract any visible URLs or domains
-3. If research-related: identify key topics
-4. User context: what might they want to do with this?
-
-Respond with:
-CONTENT_TYPE: [webpage/app/document/social/game/other]
-WEBPAGE_URL: [URL if visible, or "none"]
-RESEARCH_TOPICS: [comma-separated topics if research-related]
-USER_INTENT: [likely user intent]
-FOLLOW_UP: [suggested follow-up actions]"#;
-
-        let request_body = serde_json::json!({
-            "model": "claude-3-5-sonnet-20241022",
-            "max_tokens": 300,
-            "messages": [{
-                "role": "user",
-                "content": [
-                    {
-                        "type": "text",
-                        "text": analysis_prompt
-                    },
-                    {
-                        "type": "image",
-                        "source": {
-                            "type": "base64",
-                            "media_type": processed_image.media_type,
-                            "data": processed_image.base64_data
-                        }
-                    }
-                ]
-            }]
-        });
-
-        let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
-            .header("x-api-key", &self.config.anthropic_api_key)
-            .header("Content-Type", "application/json")
-            .header("anthropic-version", "2023-06-01")
-            .json(&request_body)
-            .send()
-            .await?;
-
-        if response.status().is_success() {
-            let response_json: serde_json::Value = response.json().await?;
-            let analysis_text = response_json["content"][0]["text"]
-                .as_str()
-                .unwrap_or("");
-            Ok(self.parse_content_analysis(analysis_text))
-        } else {
-            Ok(ContentAnalysis::default())
+        {
+            Ok(analysis_text) => Ok(self.parse_content_analysis(&analysis_text)),
+            Err(e) => {
+                warn!("Content analysis failed, using defaults: {}", e);
+                Ok(ContentAnalysis::default())
+            }
         }
     }
 
+    /// Reads `FIELD: value` lines, for providers without structured output.
     fn parse_content_analysis(&self, analysis_text: &str) -> ContentAnalysis {
         let mut result = ContentAnalysis::default();
 
         for line in analysis_text.lines() {
-            let line = line.trim();
-            if line.starts_with("CONTENT_TYPE:") {
-                result.content_type = line
-                    .split(':')
-                    .nth(1)
-                    .unwrap_or("unknown")
-                    .trim()
-                    .to_string();
-            } else if line.starts_with("WEBPAGE_URL:") {
-                let url = line.split(':').nth(1).unwrap_or("none").trim();
-                if url != "none" && url != "unknown" {
-                    result.webpage_url = Some(url.to_string());
+            // Only the first colon separates the field, so URLs survive intact
+            let Some((field, value)) = line.trim().split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match field.trim() {
+                "CONTENT_TYPE" => result.content_type = value.to_string(),
+                "WEBPAGE_URL" => result.webpage_url = Some(value.to_string()),
+                "RESEARCH_TOPICS" => {
+                    result.research_topics = value
+                        .split(',')
+                        .map(|t| t.trim().to_string())
+                        .collect();
+                }
+                "USER_INTENT" => result.user_intent = value.to_string(),
+                "FOLLOW_UP" => result.follow_up = value.to_string(),
+                "TAGS" => result.tags = value.split(',').map(|t| t.trim().to_string()).collect(),
+                "LINKS" => {
+                    result.links = value
+                        .split([',', ' '])
+                        .filter(|url| !url.trim().is_empty())
+                        .map(|url| Link {
+                            url: url.trim().to_string(),
+                            title: None,
+                        })
+                        .collect();
                 }
-            } else if line.starts_with("RESEARCH_TOPICS:") {
-                let topics = line.split(':').nth(1).unwrap_or("").trim();
-                result.research_topics = topics
-                    .split(',')
-                    .map(|t| t.trim().to_string())
-                    .filter(|t| !t.is_empty())
-                    .collect();
-            } else if line.starts_with("USER_INTENT:") {
-                result.user_intent = line
-                    .split(':')
-                    .nth(1)
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
-            } else if line.starts_with("FOLLOW_UP:") {
-                result.follow_up = line
-                    .split(':')
-                    .nth(1)
-                    .unwrap_or("")
-                    .trim()
-                    .to_string();
+                "HAS_TABLE" => result.has_table = value.eq_ignore_ascii_case("yes"),
+                "LANGUAGE" => result.language = Some(value.to_string()),
+                "EXTRACTED_TEXT" => result.extracted_text = Some(value.to_string()),
+                _ => {}
             }
         }
 
-        result
+        result.normalized()
     }
 
     async fn send_telegram_notification(
         &self,
-        bot: &Bot,
-        chat_id: &str,
         summary: &str,
         analysis_id: &str,
         content_analysis: &ContentAnalysis,
-        _metadata: &Option<ScreenshotMetadata>,
+        image: &ProcessedImage,
         source_type: &str,
+        project_id: Option<&str>,
     ) -> Result<()> {
+        let recipients = self.notification_recipients(source_type, &content_analysis.content_type, project_id);
+        self.send_telegram_notification_to(recipients, summary, analysis_id, content_analysis, image, source_type)
+            .await
+    }
+
+    pub(crate) async fn send_telegram_notification_to(
+        &self,
+        recipients: Vec<(teloxide::types::Recipient, Option<i32>)>,
+        summary: &str,
+        analysis_id: &str,
+        content_analysis: &ContentAnalysis,
+        image: &ProcessedImage,
+        source_type: &str,
+    ) -> Result<()> {
+        let Some(bot) = &self.telegram_bot else {
+            return Ok(());
+        };
+        if recipients.is_empty() {
+            return Ok(());
+        }
+
         let source_emoji = if source_type.starts_with("desktop") {
             "🖥️"
         } else {
@@ -438,109 +1508,177 @@ FOLLOW_UP: [suggested follow-up actions]"#;
         };
 
         let timestamp = Utc::now().format("%H:%M:%S");
-        
-        // Truncate summary if too long for Telegram (1024 char limit for captions)
-        let header = format!("<b>{} {}</b> <i>{}</i>\n\n<b>AI Analysis:</b>\n\n", 
+
+        let header = format!("<b>{} {}</b> <i>{}</i>\n\n<b>AI Analysis:</b>\n\n",
                             source_emoji, source_name, timestamp);
-        
-        let max_summary_length = 950 - header.len(); // Leave some buffer for the header
-        let truncated_summary = if summary.len() > max_summary_length {
-            format!("{}...\n\n<i>[Analysis truncated - see full analysis in app]</i>", 
-                    &summary[..max_summary_length.min(summary.len())])
-        } else {
-            summary.to_string()
-        };
-        
-        // Create the caption for the photo
-        let caption = format!("{}{}", header, truncated_summary);
-
-        // Create inline keyboard
-        let mut buttons = vec![
-            vec![teloxide::types::InlineKeyboardButton::callback(
-                "🔬 Research Papers",
-                format!("arxiv_research_{}", analysis_id),
-            )],
-            vec![teloxide::types::InlineKeyboardButton::callback(
-                "🧠 Deep Research",
-                format!("deep_research_{}", analysis_id),
-            )],
-        ];
-
-        if content_analysis.webpage_url.is_some() {
-            buttons.push(vec![teloxide::types::InlineKeyboardButton::callback(
-                "🌐 Webpage Content",
-                format!("full_webpage_{}", analysis_id),
-            )]);
-        }
-
-        let keyboard = InlineKeyboardMarkup::new(buttons);
-
-        // Get the image data from pending_analyses
-        if let Some(analysis_data) = self.pending_analyses.get(analysis_id) {
-            // Decode the base64 image data
-            let image_bytes = general_purpose::STANDARD
-                .decode(&analysis_data.image_data.base64_data)
-                .map_err(|e| anyhow!("Failed to decode image for Telegram: {}", e))?;
-
-            // Create InputFile from bytes
-            let input_file = InputFile::memory(image_bytes)
-                .file_name(format!("screenshot_{}.png", &analysis_id[..8]));
-
-            let chat_id: teloxide::types::ChatId = teloxide::types::ChatId(chat_id.parse::<i64>()?);
-
-            // Send photo with caption and keyboard
-            bot.send_photo(chat_id, input_file)
-                .caption(caption)
-                .reply_markup(keyboard)
-                .parse_mode(teloxide::types::ParseMode::Html)
-                .await?;
-        } else {
-            // Fallback to text message if image data not found
-            warn!("Analysis data not found for ID: {}, sending text-only message", analysis_id);
-            
-            let full_message = format!("{}{}", header, truncated_summary);
 
-            let chat_id: teloxide::types::ChatId = teloxide::types::ChatId(chat_id.parse::<i64>()?);
+        let keyboard =
+            telegram::notification_keyboard(analysis_id, content_analysis.webpage_url.is_some(), &[]);
 
-            bot.send_message(chat_id, full_message)
-                .reply_markup(keyboard)
-                .parse_mode(teloxide::types::ParseMode::Html)
-                .await?;
+        let image_bytes = general_purpose::STANDARD
+            .decode(&image.base64_data)
+            .map_err(|e| anyhow!("Failed to decode image for Telegram: {}", e))?;
+
+        match telegram::photo_limit_violation(&image_bytes) {
+            None => {
+                // Captions are limited to 1024 characters, leave room for the truncation note
+                let caption = format!(
+                    "{}{}",
+                    header,
+                    telegram::truncate_html(summary, 950usize.saturating_sub(header.chars().count()))
+                );
+
+                let extension = if image.media_type == "image/jpeg" { "jpg" } else { "png" };
+                let input_file = InputFile::memory(image_bytes)
+                    .file_name(format!("screenshot_{}.{}", &analysis_id[..8], extension));
+
+                for (chat_id, thread_id) in recipients {
+                    let mut request = bot
+                        .send_photo(chat_id.clone(), input_file.clone())
+                        .caption(caption.clone())
+                        .reply_markup(keyboard.clone())
+                        .parse_mode(teloxide::types::ParseMode::Html);
+                    if let Some(thread_id) = thread_id {
+                        request = request.message_thread_id(thread_id);
+                    }
+                    match request.await {
+                        Ok(message) => self.track_telegram_message(analysis_id, &message),
+                        Err(e) => warn!("Failed to notify Telegram chat {}: {}", chat_id, e),
+                    }
+                }
+            }
+            Some(reason) => {
+                warn!("Screenshot {} not attached to Telegram message: {}", analysis_id, reason);
+
+                let full_message = format!(
+                    "{}{}\n\n<i>[Image not attached: {}]</i>",
+                    header,
+                    telegram::truncate_html(summary, 3500),
+                    reason
+                );
+
+                for (chat_id, thread_id) in recipients {
+                    let mut request = bot
+                        .send_message(chat_id.clone(), full_message.clone())
+                        .reply_markup(keyboard.clone())
+                        .parse_mode(teloxide::types::ParseMode::Html);
+                    if let Some(thread_id) = thread_id {
+                        request = request.message_thread_id(thread_id);
+                    }
+                    match request.await {
+                        Ok(message) => self.track_telegram_message(analysis_id, &message),
+                        Err(e) => warn!("Failed to notify Telegram chat {}: {}", chat_id, e),
+                    }
+                }
+            }
         }
 
         Ok(())
     }
 
-    pub async fn get_recent_analyses(&self) -> Vec<serde_json::Value> {
-        let mut analyses: Vec<_> = self
-            .pending_analyses
-            .iter()
-            .map(|entry| {
-                let (id, analysis) = (entry.key(), entry.value());
-                serde_json::json!({
-                    "id": id,
-                    "name": analysis.metadata.filename.as_ref().unwrap_or(&format!("screenshot-{}.png", &id[..8])),
-                    "size": analysis.image_data.size_bytes,
-                    "type": analysis.image_data.media_type,
-                    "timestamp": analysis.timestamp,
-                    "status": "completed",
-                    "analysis": analysis.brief_summary,
-                    "source": analysis.source,
-                    "imageData": analysis.image_base64  // Include image data for thumbnails
-                })
+    /// Finds arXiv papers related to the research topics of a stored analysis.
+    pub async fn research_papers(&self, analysis_id: &str) -> Result<Vec<ArxivPaper>> {
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+
+        arxiv::search_papers(&self.client, &analysis.content_analysis.research_topics).await
+    }
+
+    /// Fetches the webpage detected in a stored analysis and summarizes its readable content.
+    pub async fn fetch_webpage(&self, analysis_id: &str) -> Result<WebpageContent> {
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+
+        let raw_url = analysis
+            .content_analysis
+            .webpage_url
+            .ok_or_else(|| anyhow!("No webpage URL was detected in this screenshot"))?;
+        let url = webpage::normalize_url(&raw_url)?;
+
+        let (title, text) = webpage::fetch_readable(&self.client, &url).await?;
+        let (summary, records) = usage::track(
+            self.provider
+                .complete(&webpage::summary_prompt(title.as_deref(), &text), &[], 600),
+        )
+        .await;
+        self.save_usage(Some(analysis_id), &records);
+        let summary = summary?;
+
+        Ok(WebpageContent {
+            url: url.to_string(),
+            title,
+            text,
+            summary,
+            fetched_at: Utc::now(),
+        })
+    }
+
+    /// An analysis as the API returns it, reading its image from disk only when it's included.
+    pub(crate) fn analysis_record(&self, id: String, mut analysis: AnalysisData, include_image: bool) -> AnalysisRecord {
+        if include_image {
+            if let Err(e) = self.store.load_image(&id, &mut analysis) {
+                warn!("Failed to load image for analysis {}: {}", id, e);
+            }
+        }
+        AnalysisRecord::from_analysis(id, analysis, include_image)
+    }
+
+    /// Searches stored analyses, best match first, within one project when `project_id` is given.
+    pub fn search_analyses(
+        &self,
+        query: &str,
+        limit: usize,
+        project_id: Option<&str>,
+        include_images: bool,
+    ) -> Result<Vec<SearchResult>> {
+        Ok(self
+            .store
+            .search(query, limit.clamp(1, 100), project_id)?
+            .into_iter()
+            .map(|(id, analysis, snippet, rank)| SearchResult {
+                analysis: self.analysis_record(id, analysis, include_images),
+                snippet,
+                rank,
             })
-            .collect();
+            .collect())
+    }
 
-        // Sort by timestamp (newest first)
-        analyses.sort_by(|a, b| {
-            let a_time = a["timestamp"].as_str().unwrap_or("");
-            let b_time = b["timestamp"].as_str().unwrap_or("");
-            b_time.cmp(a_time)
-        });
+    pub fn get_analysis(&self, analysis_id: &str, include_image: bool) -> Result<Option<AnalysisRecord>> {
+        Ok(self
+            .store
+            .get(analysis_id)?
+            .map(|analysis| AnalysisRecord::from_analysis(analysis_id.to_string(), analysis, include_image)))
+    }
 
-        // Return last 50 analyses
-        analyses.truncate(50);
-        analyses
+    /// Public link to an analysis's image, when `public_url` is configured.
+    pub(crate) fn image_url(&self, analysis_id: &str) -> Option<String> {
+        self.config
+            .public_url
+            .as_deref()
+            .map(|base| format!("{}/analysis/{}/image", base.trim_end_matches('/'), analysis_id))
+    }
+
+    /// Deletes an analysis and its stored image, returning whether it existed.
+    pub fn delete_analysis(&self, analysis_id: &str) -> Result<bool> {
+        // Failed analyses are listed in the history too, and deleted the same way
+        let deleted = self.store.delete(analysis_id)? | self.store.delete_failure(analysis_id)?;
+        if deleted {
+            self.remove_from_search_index(Some(analysis_id));
+            info!("🗑️ Deleted analysis {}", analysis_id);
+        }
+        Ok(deleted)
+    }
+
+    /// Deletes every stored analysis, returning how many were removed.
+    pub fn clear_all_analyses(&self) -> Result<usize> {
+        let deleted = self.store.clear()?;
+        self.remove_from_search_index(None);
+        info!("🗑️ Cleared {} analyses", deleted);
+        Ok(deleted)
     }
 
     pub async fn get_status(&self) -> ServerStatus {
@@ -555,22 +1693,112 @@ FOLLOW_UP: [suggested follow-up actions]"#;
             port: self.config.server_port,
             total_requests: self.request_count.load(Ordering::Relaxed),
             last_request: *self.last_request_time.read().await,
-            active_analyses: self.pending_analyses.len(),
+            active_analyses: self.store.count().unwrap_or(0),
             telegram_configured: self.config.telegram_bot_token.is_some(),
             desktop_detection_enabled: self.config.enable_desktop_detection,
+            clipboard_detection_enabled: self.config.enable_clipboard_detection,
+            queue_depth: self.queue.depth(),
+            active_requests: self.queue.active(),
+            max_concurrent_requests: self.queue.max_concurrent(),
+            outbox_pending: self.outbox_pending(),
+            mdns_name: self.advertised_name(),
+            tls_fingerprint: self.tls_fingerprint(),
+            rate_limit_per_minute: self.rate_limiter.per_minute(),
+            rate_limited_requests: self.rate_limiter.rejected(),
+            rate_limited_clients: self.rate_limiter.limited_clients(),
+            paused: self.is_paused(),
         }
     }
 }
 
-impl Default for ScreenshotMetadata {
-    fn default() -> Self {
-        Self {
-            source: None,
-            app: None,
-            filename: None,
-            location: None,
-            auto_detected: None,
+impl ContentAnalysis {
+    /// Schema providers fill in when asked for a structured analysis.
+    pub fn schema() -> OutputSchema {
+        OutputSchema {
+            name: "record_screenshot_analysis",
+            description: "Record what the screenshot shows and what the user likely wants to do with it.",
+            schema: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "content_type": {
+                        "type": "string",
+                        "enum": ["webpage", "app", "document", "code", "social", "game", "receipt", "other"],
+                        "description": "Kind of content in the screenshot"
+                    },
+                    "webpage_url": {
+                        "type": ["string", "null"],
+                        "description": "URL or domain visible in the screenshot, or null"
+                    },
+                    "research_topics": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Key topics, if the content is research-related"
+                    },
+                    "user_intent": {
+                        "type": "string",
+                        "description": "What the user likely wants to do with this"
+                    },
+                    "follow_up": {
+                        "type": "string",
+                        "description": "Suggested follow-up actions"
+                    },
+                    "tags": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "3 to 6 short lowercase tags for organizing the screenshot, e.g. rust, invoice, travel"
+                    },
+                    "links": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "url": { "type": "string", "description": "The URL or domain exactly as shown" }
+                            },
+                            "required": ["url"],
+                            "additionalProperties": false
+                        },
+                        "description": "Every URL or domain visible anywhere in the screenshot, in reading order"
+                    },
+                    "has_table": {
+                        "type": "boolean",
+                        "description": "Whether the screenshot shows a table of data, e.g. a pricing table, spreadsheet or dashboard"
+                    },
+                    "language": {
+                        "type": ["string", "null"],
+                        "description": "Main language of the text in the screenshot, named in English, e.g. Japanese, or null if there is no text"
+                    },
+                    "extracted_text": {
+                        "type": ["string", "null"],
+                        "description": "The readable text in the screenshot in reading order, leaving out buttons and menus; the first 300 words if there is more. Null if there is no text"
+                    }
+                },
+                "required": ["content_type", "webpage_url", "research_topics", "user_intent", "follow_up", "tags", "links", "has_table", "language", "extracted_text"],
+                "additionalProperties": false
+            }),
+        }
+    }
+
+    /// Drops placeholder values models use for missing fields.
+    fn normalized(mut self) -> Self {
+        if self.content_type.trim().is_empty() {
+            self.content_type = "unknown".to_string();
         }
+        self.webpage_url = self
+            .webpage_url
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty() && url != "none" && url != "unknown");
+        self.language = self
+            .language
+            .map(|language| language.trim().to_string())
+            .filter(|language| !language.is_empty() && !language.eq_ignore_ascii_case("none"));
+        self.extracted_text = self
+            .extracted_text
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty() && !text.eq_ignore_ascii_case("none"));
+        self.research_topics.retain(|t| !t.trim().is_empty());
+        self.tags = tags::normalize_tags(&self.tags);
+        self.links = links::normalize_links(&self.links, self.webpage_url.as_deref());
+        self
     }
 }
 
@@ -580,6 +1808,11 @@ impl Default for ContentAnalysis {
             content_type: "unknown".to_string(),
             webpage_url: None,
             research_topics: Vec::new(),
+            tags: Vec::new(),
+            links: Vec::new(),
+            has_table: false,
+            language: None,
+            extracted_text: None,
             user_intent: String::new(),
             follow_up: String::new(),
         }
@@ -589,28 +1822,46 @@ impl Default for ContentAnalysis {
 // HTTP handlers for the server
 pub async fn handle_screenshot(
     State(processor): State<ScreenshotProcessor>,
+    headers: HeaderMap,
     Json(request): Json<ScreenshotRequest>,
-) -> Result<ResponseJson<ProcessingResponse>, StatusCode> {
+) -> Result<Response, ApiError> {
+    let idempotency_key = audit::header_value(&headers, idempotency::IDEMPOTENCY_KEY_HEADER)
+        .map(str::to_string)
+        .or(request.idempotency_key);
+
     match processor
-        .process_screenshot(&request.image, request.metadata)
+        .process_screenshot_idempotent(idempotency_key.as_deref(), &request.image, request.metadata)
         .await
     {
-        Ok(response) => Ok(ResponseJson(response)),
+        Ok((response, replayed)) => {
+            let replayed = [(idempotency::IDEMPOTENT_REPLAYED_HEADER, replayed.to_string())];
+            Ok((replayed, ResponseJson(response)).into_response())
+        }
+        Err(e) if e.is::<QueueFull>() || e.is::<BudgetExceeded>() => {
+            warn!("Rejecting screenshot: {}", e);
+            Err(e.into())
+        }
         Err(e) => {
             error!("Screenshot processing failed: {}", e);
-            Ok(ResponseJson(ProcessingResponse {
-                success: false,
-                summary: None,
-                analysis_id: None,
-                timestamp: Utc::now(),
-                follow_up_available: None,
-                source: None,
-                error: Some(e.to_string()),
-            }))
+            Err(e.into())
         }
     }
 }
 
+/// Several base64-encoded screenshots easily exceed axum's 2MB default.
+const BATCH_BODY_LIMIT: usize = 100 * 1024 * 1024;
+
+pub async fn handle_screenshot_batch(
+    State(processor): State<ScreenshotProcessor>,
+    Json(request): Json<BatchRequest>,
+) -> Result<ResponseJson<BatchResponse>, ApiError> {
+    batch::check_batch_size(&request.images).map_err(|e| ApiError::new(ErrorCode::BadRequest, e.to_string()))?;
+    processor.process_batch(request.images).await.map(ResponseJson).map_err(|e| {
+        error!("Batch processing failed: {}", e);
+        e.into()
+    })
+}
+
 pub async fn handle_health() -> ResponseJson<serde_json::Value> {
     ResponseJson(serde_json::json!({
         "status": "healthy",
@@ -625,32 +1876,325 @@ pub async fn handle_status(
     ResponseJson(processor.get_status().await)
 }
 
-pub async fn start_screenshot_server(config: AppConfig) -> Result<()> {
-    let processor = ScreenshotProcessor::new(config.clone());
+pub async fn handle_stats(
+    State(processor): State<ScreenshotProcessor>,
+) -> Result<ResponseJson<UsageStats>, StatusCode> {
+    processor.usage_stats().map(ResponseJson).map_err(|e| {
+        error!("Failed to load usage stats: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListAnalysesQuery {
+    pub page: Option<usize>,
+    pub page_size: Option<usize>,
+    /// Only analyses with this tag.
+    pub tag: Option<String>,
+    pub source: Option<String>,
+    pub content_type: Option<String>,
+    /// Only analyses taken at or after this time.
+    pub from: Option<DateTime<Utc>>,
+    /// Only analyses taken before this time.
+    pub to: Option<DateTime<Utc>>,
+    /// Only starred analyses if true, only unstarred ones if false.
+    pub starred: Option<bool>,
+    /// Only analyses in this project, by id.
+    pub project: Option<String>,
+    #[serde(default)]
+    pub sort: AnalysisSort,
+    #[serde(default)]
+    pub include_images: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchAnalysesQuery {
+    pub q: String,
+    pub limit: Option<usize>,
+    /// Only analyses in this project, by id.
+    pub project: Option<String>,
+    #[serde(default)]
+    pub include_images: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetAnalysisQuery {
+    #[serde(default)]
+    pub include_image: bool,
+}
+
+pub async fn handle_list_analyses(
+    State(processor): State<ScreenshotProcessor>,
+    Query(query): Query<ListAnalysesQuery>,
+) -> Result<ResponseJson<AnalysisPage>, StatusCode> {
+    if query.tag.as_deref().is_some_and(|tag| tags::normalize_tag(tag).is_none()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let filter = AnalysisFilter {
+        source: query.source,
+        content_type: query.content_type,
+        date_range: (query.from.is_some() || query.to.is_some()).then_some(DateRange {
+            from: query.from,
+            to: query.to,
+        }),
+        tag: query.tag,
+        starred: query.starred,
+        project: query.project,
+    };
+    processor
+        .list_analyses(
+            query.page.unwrap_or(1),
+            query.page_size.unwrap_or(20),
+            &filter,
+            query.sort,
+            query.include_images,
+        )
+        .map(ResponseJson)
+        .map_err(|e| {
+            error!("Failed to list analyses: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn handle_search_analyses(
+    State(processor): State<ScreenshotProcessor>,
+    Query(query): Query<SearchAnalysesQuery>,
+) -> Result<ResponseJson<Vec<SearchResult>>, StatusCode> {
+    if query.q.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    processor
+        .search_analyses(&query.q, query.limit.unwrap_or(20), query.project.as_deref(), query.include_images)
+        .map(ResponseJson)
+        .map_err(|e| {
+            error!("Analysis search failed: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub async fn handle_get_analysis(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Query(query): Query<GetAnalysisQuery>,
+) -> Result<ResponseJson<AnalysisRecord>, StatusCode> {
+    match processor.get_analysis(&analysis_id, query.include_image) {
+        Ok(Some(record)) => Ok(ResponseJson(record)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to load analysis {}: {}", analysis_id, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn handle_get_analysis_image(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+) -> Result<([(header::HeaderName, String); 1], Vec<u8>), StatusCode> {
+    let analysis = match processor.store.get(&analysis_id) {
+        Ok(Some(analysis)) => analysis,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to load analysis {}: {}", analysis_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let bytes = general_purpose::STANDARD
+        .decode(&analysis.image_data.base64_data)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(([(header::CONTENT_TYPE, analysis.image_data.media_type)], bytes))
+}
+
+pub async fn handle_delete_analysis(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+) -> StatusCode {
+    match processor.delete_analysis(&analysis_id) {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to delete analysis {}: {}", analysis_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FollowUpRequest {
+    pub question: String,
+}
+
+pub async fn handle_analysis_chat(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Json(request): Json<FollowUpRequest>,
+) -> Result<ResponseJson<ChatTurn>, StatusCode> {
+    match processor.get_analysis(&analysis_id, false) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to load analysis {}: {}", analysis_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    if request.question.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    processor
+        .ask_followup(&analysis_id, &request.question)
+        .await
+        .map(ResponseJson)
+        .map_err(|e| {
+            error!("Follow-up for analysis {} failed: {}", analysis_id, e);
+            StatusCode::BAD_GATEWAY
+        })
+}
+
+/// Binds the first free port from `server_port` up to `server_port + server_port_range`.
+pub async fn bind_screenshot_listener(config: &AppConfig) -> Result<tokio::net::TcpListener> {
+    let first = config.server_port;
+    let last = first.saturating_add(config.server_port_range);
+
+    for port in first..=last {
+        match tokio::net::TcpListener::bind(("0.0.0.0", port)).await {
+            Ok(listener) => {
+                if port != first {
+                    warn!("Port {} is in use, using port {} instead", first, port);
+                }
+                return Ok(listener);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => {
+                debug!("Port {} is in use", port);
+            }
+            Err(e) => return Err(anyhow!("Failed to bind to port {}: {}", port, e)),
+        }
+    }
+
+    if first == last {
+        Err(anyhow!("Port {} is already in use", first))
+    } else {
+        Err(anyhow!("Ports {}-{} are all in use", first, last))
+    }
+}
 
+/// Serves the HTTP API on `listener`, which should be bound to the processor's `server_port`.
+pub async fn start_screenshot_server(processor: ScreenshotProcessor, listener: tokio::net::TcpListener) -> Result<()> {
+    let config = processor.config.clone();
+
+    let protected = Router::new()
+        .route(
+            "/screenshot",
+            post(handle_screenshot).layer(DefaultBodyLimit::max(
+                config.image_policy.max_upload_body().max(config.recordings.max_upload_body()),
+            )),
+        )
+        .route(
+            "/screenshots/batch",
+            post(handle_screenshot_batch).layer(DefaultBodyLimit::max(BATCH_BODY_LIMIT)),
+        )
+        .route("/status", get(handle_status))
+        .route("/stats", get(handle_stats))
+        .route("/events", get(events::handle_events))
+        .route("/analyses", get(handle_list_analyses))
+        .route("/analyses/search", get(handle_search_analyses))
+        .route("/analyses/export", get(archive::handle_export_analyses))
+        .route("/audit", get(audit::handle_audit_log))
+        .route("/journal", get(journal::handle_journal))
+        .route("/tags", get(tags::handle_list_tags))
+        .route("/compare", post(compare::handle_compare))
+        .route(
+            "/analysis/:id",
+            get(handle_get_analysis).delete(handle_delete_analysis),
+        )
+        .route("/analysis/:id/image", get(handle_get_analysis_image))
+        .route("/image/:id", get(images::handle_get_image))
+        .route("/image/:id/frames/:index", get(images::handle_get_frame))
+        .route("/analysis/:id/table", get(tables::handle_get_table))
+        .route("/analysis/:id/chat", post(handle_analysis_chat))
+        .route("/analysis/:id/region", post(conversation::handle_region_question))
+        .route("/analysis/:id/reanalyze", post(revision::handle_reanalyze))
+        .route("/analysis/:id/inflight", delete(inflight::handle_cancel_analysis))
+        .route("/analysis/:id/tags", post(tags::handle_add_tag))
+        .route("/analysis/:id/tags/:tag", delete(tags::handle_remove_tag))
+        .route("/analysis/:id/note", put(notes::handle_set_note))
+        .route("/analysis/:id/starred", put(notes::handle_set_starred))
+        // Inside auth, so only clients with a valid key are registered
+        .route_layer(middleware::from_fn_with_state(
+            processor.clone(),
+            devices::track_devices,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            processor.clone(),
+            auth::require_api_key,
+        ));
+
+    let advertiser = processor.clone();
+    let tls = processor.tls.clone();
     let app = Router::new()
-        .route("/screenshot", post(handle_screenshot))
+        // Authenticated by the single-use pairing code instead of the API key
+        .route("/pair", post(pairing::handle_pair))
+        .merge(protected)
+        // Outside auth, so guessing keys or pairing codes is throttled too
+        .route_layer(middleware::from_fn_with_state(
+            processor.clone(),
+            rate_limit::limit_requests,
+        ))
         .route("/health", get(handle_health))
-        .route("/status", get(handle_status))
+        .route("/openapi.json", get(openapi::handle_openapi))
+        .layer(middleware::from_fn_with_state(
+            processor.clone(),
+            audit::audit_requests,
+        ))
+        // Outermost, so rejections from extractors and middleware get typed bodies too
+        .layer(middleware::map_response(api_error::typed_error_bodies))
         .with_state(processor)
         .layer(CorsLayer::permissive());
 
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", config.server_port))
-        .await
-        .map_err(|e| anyhow!("Failed to bind to port {}: {}", config.server_port, e))?;
-
-    info!("🌐 Screenshot server running on port {}", config.server_port);
-
-    axum::serve(listener, app).await?;
+    info!(
+        "🌐 Screenshot server running on port {} ({})",
+        config.server_port,
+        advertiser.url_scheme()
+    );
+
+    // Withdrawn when this future is dropped, which is how the server is stopped
+    let _advertisement = if config.mdns_enabled {
+        advertiser
+            .advertise()
+            .inspect_err(|e| warn!("mDNS advertisement unavailable: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    match tls {
+        Some(identity) => {
+            tls::install_crypto_provider();
+            let rustls = RustlsConfig::from_pem_file(&identity.cert_path, &identity.key_path)
+                .await
+                .map_err(|e| anyhow!("Failed to load TLS certificate: {}", e))?;
+            axum_server::from_tcp_rustls(listener.into_std()?, rustls)
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await?;
+        }
+        None => axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?,
+    }
 
     Ok(())
 }
 
 // Desktop screenshot watcher
+/// Quiet period after the last file event before a screenshot is picked up.
+const EVENT_DEBOUNCE: Duration = Duration::from_millis(500);
+
 pub struct DesktopWatcher {
     #[allow(dead_code)]
     processor: ScreenshotProcessor,
-    _watcher: RecommendedWatcher,
+    watcher: parking_lot::Mutex<RecommendedWatcher>,
+    directories: Arc<parking_lot::Mutex<Vec<WatchDirectory>>>,
     _task_handle: tokio::task::JoinHandle<()>,
 }
 
@@ -658,39 +2202,80 @@ impl std::fmt::Debug for DesktopWatcher {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("DesktopWatcher")
             .field("processor", &"ScreenshotProcessor { ... }")
-            .field("_watcher", &"RecommendedWatcher { ... }")
+            .field("watcher", &"RecommendedWatcher { ... }")
+            .field("directories", &*self.directories.lock())
             .field("_task_handle", &"JoinHandle { ... }")
             .finish()
     }
 }
 
 impl DesktopWatcher {
-    pub fn new(processor: ScreenshotProcessor) -> Result<Self> {
+    /// Starts watching `directories`, or the platform's screenshot folders if none are given.
+    pub fn new(processor: ScreenshotProcessor, directories: Vec<WatchDirectory>) -> Result<Self> {
+        let filter = ScreenshotFilter::from_config(&processor.config)?;
         let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
-        
-        // Track recently processed files to avoid duplicates
-        let processed_files = Arc::new(std::sync::Mutex::new(HashSet::<PathBuf>::new()));
-        
-        // Spawn a task to handle file processing
+        let duplicate_window = Duration::from_secs(processor.config.duplicate_window_secs);
+        let watched_directories = Arc::new(parking_lot::Mutex::new(Vec::<WatchDirectory>::new()));
+        let moved_files = Arc::new(parking_lot::Mutex::new(file_actions::MovedFiles::default()));
+
+        // Spawn a task that waits for each path's events to go quiet before processing it
         let processor_clone = processor.clone();
+        let directories_task = watched_directories.clone();
+        let moved_files_task = moved_files.clone();
         let task_handle = tokio::spawn(async move {
-            while let Some(path) = rx.recv().await {
-                info!("🚀 Starting to process screenshot: {}", path.display());
-                if let Err(e) = Self::process_desktop_screenshot(&processor_clone, &path).await {
-                    error!("Failed to process desktop screenshot: {}", e);
+            let mut debouncer = dedupe::Debouncer::new(EVENT_DEBOUNCE);
+            let mut recent_hashes = dedupe::RecentHashes::new(duplicate_window);
+
+            loop {
+                let next_deadline = debouncer.next_deadline();
+                tokio::select! {
+                    received = rx.recv() => match received {
+                        Some(path) => debouncer.touch(path),
+                        None => break,
+                    },
+                    _ = async {
+                        match next_deadline {
+                            Some(deadline) => tokio::time::sleep_until(deadline).await,
+                            None => std::future::pending().await,
+                        }
+                    } => {
+                        for path in debouncer.take_due() {
+                            info!("🚀 Starting to process screenshot: {}", path.display());
+                            let directory =
+                                file_actions::watch_directory_for(&directories_task.lock(), &path).cloned();
+                            match Self::process_desktop_screenshot(&processor_clone, &path, &mut recent_hashes).await {
+                                Ok(Some(analysis_id)) => processor_clone.finish_watched_file(
+                                    &analysis_id,
+                                    &path,
+                                    directory.as_ref(),
+                                    &moved_files_task,
+                                ),
+                                Ok(None) => {}
+                                // Already logged when it was held back or skipped
+                                Err(e) if e.is::<Paused>() || e.is::<Excluded>() => {}
+                                Err(e) => error!("Failed to process desktop screenshot: {}", e),
+                            }
+                        }
+                    }
                 }
             }
         });
 
         // Clone for use in the watcher closure
-        let processed_files_watcher = processed_files.clone();
+        let watched_directories_watcher = watched_directories.clone();
         
         let mut watcher = notify::recommended_watcher(move |res: Result<Event, notify::Error>| {
     if let Ok(event) = res {
         info!("🔍 File system event detected: {:?}", event.kind);
         
         // Process both Create events AND rename events (which create the final screenshot)
-        if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(notify::event::ModifyKind::Name(_))) {
+        // Linux tools often write in place, so a closed-after-write event marks a finished file
+        if matches!(
+            event.kind,
+            EventKind::Create(_)
+                | EventKind::Modify(notify::event::ModifyKind::Name(_))
+                | EventKind::Access(notify::event::AccessKind::Close(notify::event::AccessMode::Write))
+        ) {
             for path in event.paths {
                 info!("🔍 Examining path: {}", path.display());
                 
@@ -703,6 +2288,12 @@ impl DesktopWatcher {
                     }
                 }
                 
+                // Screenshots this app just archived or renamed have been analyzed already
+                if moved_files.lock().contains(&path) {
+                    info!("⏭️ Skipping file moved after analysis: {}", path.display());
+                    continue;
+                }
+
                 // Check if file exists
                 if !path.exists() {
                     info!("❌ File doesn't exist: {}", path.display());
@@ -710,34 +2301,19 @@ impl DesktopWatcher {
                 }
                 
                 // Check if it's a screenshot file
-                if Self::is_screenshot_file(&path) {
+                let any_name = watched_directories_watcher
+                    .lock()
+                    .iter()
+                    .any(|d| d.accept_all_images && path.starts_with(d.resolved_path()));
+
+                if filter.is_screenshot(&path, any_name) {
                     info!("📸 Screenshot file detected: {}", path.display());
                     
-                    // Check for duplicates BEFORE sending to queue
-                    {
-                        let mut processed = processed_files_watcher.lock().unwrap();
-                        if processed.contains(&path) {
-                            info!("🔄 Skipping already queued file: {}", path.display());
-                            continue;
-                        }
-                        processed.insert(path.clone());
-                    }
-                    
-                    // Send to async task for processing
+                    // Repeated events for the same file are collapsed by the debouncer
                     if let Err(e) = tx.send(path.clone()) {
                         error!("Failed to send file path for processing: {}", e);
                     } else {
                         info!("✉️ Sent to processing queue: {}", path.display());
-                        
-                        // Clean up tracking after 30 seconds
-                        let processed_files_cleanup = processed_files_watcher.clone();
-                        let path_cleanup = path.clone();
-                        std::thread::spawn(move || {
-                            std::thread::sleep(Duration::from_secs(30));
-                            let mut processed = processed_files_cleanup.lock().unwrap();
-                            processed.remove(&path_cleanup);
-                            info!("🧹 Cleaned up tracking for: {}", path_cleanup.display());
-                        });
                     }
                 } else {
                     info!("❌ Not a screenshot file: {}", path.display());
@@ -751,114 +2327,195 @@ impl DesktopWatcher {
     }
 })?;
 
-        let desktop_path = dirs::desktop_dir().unwrap_or_else(|| {
-            dirs::home_dir()
-                .map(|h| h.join("Desktop"))
-                .unwrap_or_else(|| PathBuf::from("."))
-        });
+        let directories = if directories.is_empty() {
+            platform::default_watch_directories()
+        } else {
+            directories
+        };
+
+        // A missing folder (e.g. an unused CleanShot export dir) shouldn't stop the others
+        let mut watched = Vec::new();
+        for directory in directories {
+            let path = directory.resolved_path();
+            match watcher.watch(&path, directory.recursive_mode()) {
+                Ok(()) => {
+                    info!("📁 Monitoring: {}", path.display());
+                    watched.push(directory);
+                }
+                Err(e) => warn!("Cannot watch {}: {}", path.display(), e),
+            }
+        }
 
-        watcher.watch(&desktop_path, RecursiveMode::NonRecursive)?;
+        if watched.is_empty() {
+            return Err(anyhow!("None of the configured screenshot folders could be watched"));
+        }
 
         info!("🔍 Desktop screenshot auto-detection started");
-        info!("📁 Monitoring: {}", desktop_path.display());
+
+        *watched_directories.lock() = watched;
 
         Ok(Self {
             processor,
-            _watcher: watcher,
+            watcher: parking_lot::Mutex::new(watcher),
+            directories: watched_directories,
             _task_handle: task_handle,
         })
     }
 
-    fn is_screenshot_file(path: &Path) -> bool {
-        // Skip hidden files (starting with .)
-        if let Some(name) = path.file_name() {
-            let name_str = name.to_string_lossy();
-            if name_str.starts_with('.') {
-                return false;
-            }
-        } else {
-            return false;
+    /// Folders currently being watched.
+    pub fn directories(&self) -> Vec<WatchDirectory> {
+        self.directories.lock().clone()
+    }
+
+    /// Starts watching another folder, replacing the mode if it is already watched.
+    pub fn add_directory(&self, directory: WatchDirectory) -> Result<()> {
+        let path = directory.resolved_path();
+        if !path.is_dir() {
+            return Err(anyhow!("Not a directory: {}", path.display()));
         }
 
-        if let Some(extension) = path.extension() {
-            let ext = extension.to_string_lossy().to_lowercase();
-            if !matches!(ext.as_str(), "png" | "jpg" | "jpeg") {
-                return false;
-            }
-        } else {
-            return false;
+        // The event callback locks `directories`, so never hold it while (un)watching
+        let mut watcher = self.watcher.lock();
+        let _ = watcher.unwatch(&path);
+        watcher.watch(&path, directory.recursive_mode())?;
+
+        let mut directories = self.directories.lock();
+        directories.retain(|d| d.resolved_path() != path);
+        directories.push(directory);
+
+        info!("📁 Monitoring: {}", path.display());
+        Ok(())
+    }
+
+    /// Stops watching a folder, returning whether it was watched.
+    pub fn remove_directory(&self, path: &Path) -> Result<bool> {
+        let target = WatchDirectory {
+            path: path.to_path_buf(),
+            ..Default::default()
+        }
+        .resolved_path();
+
+        let is_watched = self.directories.lock().iter().any(|d| d.resolved_path() == target);
+        if !is_watched {
+            return Ok(false);
         }
 
-        if let Some(name) = path.file_name() {
-            let name = name.to_string_lossy().to_lowercase();
-            let screenshot_patterns = [
-                "screenshot", "screen shot", "capture", "cleanshot",
-            ];
+        self.watcher.lock().unwatch(&target)?;
+        self.directories.lock().retain(|d| d.resolved_path() != target);
+
+        info!("📁 Stopped monitoring: {}", target.display());
+        Ok(true)
+    }
+
+    /// Waits until the file stops growing and can be opened, returning its final size.
+    async fn wait_until_settled(path: &Path) -> Result<u64> {
+        let interval = platform::settle_interval();
+        let mut last_size = None;
+
+        for _ in 0..40 {
+            sleep(interval).await;
 
-            return screenshot_patterns.iter().any(|pattern| name.contains(pattern));
+            if !path.exists() {
+                return Err(anyhow!("File not found: {}", path.display()));
+            }
+
+            let size = std::fs::metadata(path)?.len();
+            // Windows keeps the file locked while the capture tool is still writing
+            let readable = std::fs::File::open(path).is_ok();
+
+            if size > 0 && readable && last_size == Some(size) {
+                return Ok(size);
+            }
+            last_size = Some(size);
         }
 
-        false
+        Err(anyhow!("Timed out waiting for {} to finish writing", path.display()))
     }
 
     async fn process_desktop_screenshot(
         processor: &ScreenshotProcessor,
         path: &Path,
-    ) -> Result<()> {
-        // Wait a bit longer for file to be fully written
-        sleep(Duration::from_millis(1500)).await;
+        recent_hashes: &mut dedupe::RecentHashes,
+    ) -> Result<Option<String>> {
+        // Look before the file settles, while the window that was captured is most likely still in front
+        let front = tokio::task::spawn_blocking(capture::frontmost_app_and_title)
+            .await
+            .ok()
+            .flatten();
 
-        if !path.exists() {
-            return Err(anyhow!("File not found: {}", path.display()));
+        let file_size = Self::wait_until_settled(path).await?;
+        let size_check = if recording::is_recording_path(path) {
+            processor.config.recordings.check_size(file_size)
+        } else {
+            processor.config.image_policy.check_size(file_size)
+        };
+        if let Err(e) = size_check {
+            warn!("Skipping {}: {}", path.display(), e);
+            return Ok(None);
         }
 
-        let file_size = std::fs::metadata(path)?.len();
-        if file_size > 15 * 1024 * 1024 {
-            warn!("Screenshot too large ({:.1}MB), skipping", file_size as f64 / 1024.0 / 1024.0);
-            return Ok(());
+        let file_bytes = std::fs::read(path)?;
+        if !recent_hashes.insert(&file_bytes) {
+            info!("🔄 Skipping duplicate screenshot: {}", path.display());
+            return Ok(None);
         }
 
-        let image_bytes = std::fs::read(path)?;
-        let image_base64 = general_purpose::STANDARD.encode(&image_bytes);
-
         let metadata = ScreenshotMetadata {
             source: Some("desktop_auto".to_string()),
-            app: Some("macOS Screenshot".to_string()),
+            app: Some(
+                front
+                    .as_ref()
+                    .map(|(app, _)| app.clone())
+                    .unwrap_or_else(|| platform::screenshot_app_name().to_string()),
+            ),
+            window_title: front.and_then(|(_, title)| Some(title).filter(|title| !title.is_empty())),
             filename: path.file_name().map(|n| n.to_string_lossy().to_string()),
+            file_path: Some(path.to_string_lossy().to_string()),
             auto_detected: Some(true),
             ..Default::default()
         };
 
-        let result = processor
-            .process_screenshot(&image_base64, Some(metadata))
-            .await?;
+        // Recordings and animated GIFs are shown and analyzed as their storyboard
+        let mut storyboard_metadata = Some(metadata);
+        let storyboard = processor
+            .storyboard(&file_bytes, &mut storyboard_metadata)
+            .await
+            .inspect_err(|_| {
+                recent_hashes.remove(&file_bytes);
+            })?;
+        let metadata = storyboard_metadata.unwrap_or_default();
+        let image_bytes = storyboard.as_ref().map_or(&file_bytes, |storyboard| &storyboard.image);
+        let image_base64 = general_purpose::STANDARD.encode(image_bytes);
+
+        let processed = match &storyboard {
+            Some(storyboard) => processor.process_storyboard(storyboard, Some(metadata.clone())).await,
+            None => processor.process_screenshot(&image_base64, Some(metadata.clone())).await,
+        };
+        let result = match processed {
+            Ok(result) => result,
+            Err(e) => {
+                // Let a later event for the same image try again, unless it is already queued
+                if !e.is::<QueuedOffline>() && !e.is::<Paused>() && !e.is::<Excluded>() {
+                    recent_hashes.remove(&file_bytes);
+                }
+                return Err(e);
+            }
+        };
 
         // Emit event to frontend for desktop auto-detected screenshots WITH image data
-        if let Some(app_handle) = APP_HANDLE.get() {
-            if let Some(window) = app_handle.get_window("main") {
-                let screenshot_data = serde_json::json!({
-                    "id": result.analysis_id.as_ref().unwrap_or(&"unknown".to_string()),
-                    "name": path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_else(|| format!("screenshot-{}.png", result.analysis_id.as_ref().unwrap_or(&"unknown".to_string())[..8].to_string())),
-                    "size": image_bytes.len(),
-                    "type": "image/png",
-                    "timestamp": result.timestamp,
-                    "status": "completed",
-                    "analysis": result.summary.as_ref().unwrap_or(&"".to_string()),
-                    "source": result.source.as_ref().unwrap_or(&"desktop_auto".to_string()),
-                    "imageData": image_base64  // Include the image data in the emit
-                });
-                
-                let _ = window.emit("screenshot-processed", screenshot_data);
-            }
-        }
+        let media_type = images::sniff_media_type(image_bytes).unwrap_or("image/png");
+        events::emit_screenshot_processed(&result, &metadata, image_bytes.len(), media_type, &image_base64);
+        processor.notify_analysis_ready(&result, "desktop_auto");
 
-        if result.success {
-            info!(
-                "✅ Desktop screenshot processed (ID: {})",
-                result.analysis_id.unwrap_or_default()
-            );
+        if !result.success {
+            return Ok(None);
         }
+        info!(
+            "✅ Desktop screenshot processed (ID: {})",
+            result.analysis_id.as_deref().unwrap_or_default()
+        );
 
-        Ok(())
+        // A near-duplicate's analysis belongs to the earlier screenshot, so leave this file alone
+        Ok(result.analysis_id.filter(|_| !result.duplicate))
     }
 }
\ No newline at end of file
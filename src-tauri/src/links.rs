@@ -0,0 +1,108 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::{webpage, AnalysisData, ScreenshotProcessor};
+
+/// Links kept per analysis; screenshots of search results or link lists can show dozens.
+const MAX_LINKS: usize = 20;
+
+/// A URL visible in a screenshot, with its page title once fetched.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub url: String,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+/// Validates and normalizes links read off a screenshot, keeping the first of any duplicates. The
+/// detected webpage URL goes first.
+pub(crate) fn normalize_links(links: &[Link], webpage_url: Option<&str>) -> Vec<Link> {
+    let candidates = webpage_url
+        .map(|url| Link {
+            url: url.to_string(),
+            title: None,
+        })
+        .into_iter()
+        .chain(links.iter().cloned());
+
+    let mut normalized: Vec<Link> = Vec::new();
+    for link in candidates {
+        // Text read off a screen often carries the sentence's punctuation along
+        let raw = link.url.trim().trim_end_matches(['.', ',', ';', ':', ')', ']', '!', '?']);
+        let Ok(url) = webpage::normalize_url(raw) else {
+            continue;
+        };
+        // Drops "none", bare words and other things that parse but aren't addresses
+        let valid_host = url
+            .host_str()
+            .is_some_and(|host| host == "localhost" || host.contains('.'));
+        if !valid_host {
+            continue;
+        }
+        let url = url.to_string();
+        match normalized.iter_mut().find(|existing| existing.url == url) {
+            Some(existing) => {
+                if existing.title.is_none() {
+                    existing.title = link.title;
+                }
+            }
+            None => normalized.push(Link { url, title: link.title }),
+        }
+    }
+    normalized.truncate(MAX_LINKS);
+    normalized
+}
+
+impl ScreenshotProcessor {
+    /// Fetches the titles of an analysis's links in the background and saves them with the links.
+    /// Screenshots marked local-only are left alone, since fetching reveals the URLs.
+    pub(crate) fn unfurl_links(&self, analysis_id: &str, analysis: &AnalysisData) {
+        let links = analysis.content_analysis.links.clone();
+        if !self.config.unfurl_links || links.is_empty() || analysis.metadata.local_only == Some(true) {
+            return;
+        }
+
+        let processor = self.clone();
+        let analysis_id = analysis_id.to_string();
+        tokio::spawn(async move {
+            let titles = futures::future::join_all(links.iter().map(|link| async {
+                if link.title.is_some() {
+                    return link.title.clone();
+                }
+                let url = webpage::normalize_url(&link.url).ok()?;
+                webpage::fetch_title(&processor.client, &url)
+                    .await
+                    .inspect_err(|e| debug!("No title for {}: {}", link.url, e))
+                    .ok()
+                    .flatten()
+            }))
+            .await;
+
+            let links: Vec<Link> = links
+                .into_iter()
+                .zip(titles)
+                .map(|(link, title)| Link { title, ..link })
+                .collect();
+            let found = links.iter().filter(|link| link.title.is_some()).count();
+            match processor.store.set_links(&analysis_id, &links) {
+                Ok(()) => info!("🔗 Fetched {} of {} link titles for {}", found, links.len(), analysis_id),
+                Err(e) => warn!("Failed to save links for {}: {}", analysis_id, e),
+            }
+        });
+    }
+
+    /// The URL of an analysis's link at `index`, checked again before it's handed to the browser.
+    pub fn link_url(&self, analysis_id: &str, index: usize) -> Result<String> {
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+        let link = analysis
+            .content_analysis
+            .links
+            .get(index)
+            .ok_or_else(|| anyhow!("Analysis {} has no link {}", analysis_id, index))?;
+        Ok(webpage::normalize_url(&link.url)?.to_string())
+    }
+}
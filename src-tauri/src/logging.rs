@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use std::{
+    collections::VecDeque,
+    io::{BufRead, BufReader},
+    path::PathBuf,
+};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry};
+
+/// Log files are named `screenshot-ai.<date>.log`.
+const FILE_PREFIX: &str = "screenshot-ai";
+const FILE_SUFFIX: &str = "log";
+/// Daily files kept before the oldest is deleted.
+const MAX_LOG_FILES: usize = 7;
+pub const DEFAULT_FILTER: &str = "info";
+
+static FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
+
+/// Where the rolling log files are written.
+pub fn log_dir() -> PathBuf {
+    crate::app_data_dir().join("logs")
+}
+
+fn parse_filter(filter: &str) -> Result<EnvFilter> {
+    EnvFilter::try_new(filter).map_err(|e| anyhow!("Invalid log filter \"{}\": {}", filter, e))
+}
+
+/// Logs to stdout and to daily files in [`log_dir`], using `filter` (e.g. "info" or "app=debug,warn").
+///
+/// `RUST_LOG` wins over `filter` when set. Keep the returned guard alive for the life of the app so
+/// buffered lines are flushed on exit.
+pub fn init(filter: Option<&str>) -> Option<WorkerGuard> {
+    let filter = std::env::var("RUST_LOG")
+        .ok()
+        .or_else(|| filter.map(str::to_string))
+        .unwrap_or_else(|| DEFAULT_FILTER.to_string());
+    let (filter_layer, handle) =
+        reload::Layer::new(parse_filter(&filter).unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER)));
+    let _ = FILTER.set(handle);
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(FILE_PREFIX)
+        .filename_suffix(FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir());
+    let (file_layer, guard) = match appender {
+        Ok(appender) => {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(fmt::layer().with_writer(writer).with_ansi(false)), Some(guard))
+        }
+        Err(e) => {
+            eprintln!("File logging unavailable: {}", e);
+            (None, None)
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(fmt::layer())
+        .with(file_layer)
+        .init();
+    guard
+}
+
+/// Changes which logs are recorded without restarting.
+pub fn set_filter(filter: &str) -> Result<()> {
+    let filter = parse_filter(filter)?;
+    FILTER
+        .get()
+        .ok_or_else(|| anyhow!("Logging is not initialized"))?
+        .reload(filter)
+        .map_err(|e| anyhow!("Failed to change log filter: {}", e))
+}
+
+/// The last `lines` lines logged, oldest first, reading back through earlier files as needed.
+pub fn tail(lines: usize) -> Result<Vec<String>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(log_dir())?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(FILE_PREFIX) && name.ends_with(FILE_SUFFIX))
+        })
+        .collect();
+    // Dated names sort chronologically; start from the newest
+    files.sort_unstable_by(|a, b| b.cmp(a));
+
+    let mut tail = VecDeque::with_capacity(lines);
+    for path in files {
+        if tail.len() >= lines {
+            break;
+        }
+        let mut file_lines = VecDeque::with_capacity(lines);
+        for line in BufReader::new(std::fs::File::open(&path)?).lines() {
+            if file_lines.len() == lines {
+                file_lines.pop_front();
+            }
+            file_lines.push_back(line?);
+        }
+        while tail.len() < lines {
+            match file_lines.pop_back() {
+                Some(line) => tail.push_front(line),
+                None => break,
+            }
+        }
+    }
+    Ok(tail.into())
+}
@@ -0,0 +1,132 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+use tracing::info;
+
+/// Argument the login item launches the app with: start hidden in the tray, with the server
+/// running on the saved settings.
+pub const BACKGROUND_ARG: &str = "--background";
+
+/// Name of the login item, and the label of the macOS launch agent.
+const LABEL: &str = "com.screenshotai.studio";
+const WINDOWS_RUN_KEY: &str = r"HKCU\Software\Microsoft\Windows\CurrentVersion\Run";
+const WINDOWS_VALUE_NAME: &str = "Screenshot AI Studio";
+
+/// The launch agent plist on macOS, or the XDG autostart entry on Linux.
+fn login_item_path() -> Option<PathBuf> {
+    if cfg!(target_os = "macos") {
+        dirs::home_dir().map(|home| {
+            home.join("Library")
+                .join("LaunchAgents")
+                .join(format!("{}.plist", LABEL))
+        })
+    } else if cfg!(target_os = "windows") {
+        None
+    } else {
+        dirs::config_dir().map(|dir| dir.join("autostart").join("screenshot-ai-studio.desktop"))
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn launch_agent(exe: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{}</string>
+        <string>{}</string>
+    </array>
+    <key>RunAtLoad</key>
+    <true/>
+</dict>
+</plist>
+"#,
+        LABEL,
+        xml_escape(exe),
+        BACKGROUND_ARG
+    )
+}
+
+fn autostart_entry(exe: &str) -> String {
+    // Desktop entries quote arguments with double quotes, escaping these inside them
+    let quoted = exe.replace('\\', "\\\\").replace('"', "\\\"").replace('`', "\\`").replace('$', "\\$");
+    format!(
+        "[Desktop Entry]\nType=Application\nName=Screenshot AI Studio\nExec=\"{}\" {}\nX-GNOME-Autostart-enabled=true\n",
+        quoted, BACKGROUND_ARG
+    )
+}
+
+fn reg(args: &[&str]) -> Result<bool> {
+    let status = std::process::Command::new("reg")
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .context("Failed to run reg")?;
+    Ok(status.success())
+}
+
+/// Whether the app is registered to start when the user logs in.
+pub fn is_enabled() -> bool {
+    match login_item_path() {
+        Some(path) => path.exists(),
+        None => reg(&["query", WINDOWS_RUN_KEY, "/v", WINDOWS_VALUE_NAME]).unwrap_or(false),
+    }
+}
+
+/// Registers the app to start in the background when the user logs in, or removes it.
+pub fn set_enabled(enabled: bool) -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to find the app executable")?;
+    let exe = exe.to_string_lossy();
+
+    match login_item_path() {
+        Some(path) if enabled => {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let contents = if cfg!(target_os = "macos") {
+                launch_agent(&exe)
+            } else {
+                autostart_entry(&exe)
+            };
+            std::fs::write(&path, contents).with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        Some(path) => {
+            if path.exists() {
+                std::fs::remove_file(&path).with_context(|| format!("Failed to remove {}", path.display()))?;
+            }
+        }
+        None if enabled => {
+            let command = format!("\"{}\" {}", exe, BACKGROUND_ARG);
+            if !reg(&["add", WINDOWS_RUN_KEY, "/v", WINDOWS_VALUE_NAME, "/t", "REG_SZ", "/d", &command, "/f"])? {
+                return Err(anyhow!("Failed to add the app to the startup programs"));
+            }
+        }
+        None => {
+            if is_enabled() && !reg(&["delete", WINDOWS_RUN_KEY, "/v", WINDOWS_VALUE_NAME, "/f"])? {
+                return Err(anyhow!("Failed to remove the app from the startup programs"));
+            }
+        }
+    }
+
+    info!(
+        "🔑 Launch at login {}",
+        if enabled { "enabled" } else { "disabled" }
+    );
+    Ok(())
+}
+
+/// Whether this launch came from the login item rather than the user opening the app.
+pub fn launched_in_background() -> bool {
+    std::env::args().skip(1).any(|arg| arg == BACKGROUND_ARG)
+}
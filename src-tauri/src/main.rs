@@ -5,281 +5,1848 @@
     windows_subsystem = "windows"
 )]
 
-mod lib;
-
-use anyhow::Result;
-use lib::{start_screenshot_server, AppConfig, DesktopWatcher, ScreenshotProcessor, set_app_handle, get_app_handle};
+use app::{generate_api_key, Annotation, AnnotationInput, ChatTurn, ClipboardWatcher, emit_event, on_main_window_focused, DesktopWatcher, NotionProperties, OpenAnalysis, ProgressStage, PromptTemplate, RegionRect, ServerHandle, ServerInfo, ServerState, ServerStateChanged, UiEvent, WatchDirectory, set_app_handle, secrets::{self, SecretName}, settings::{self, ServerConfig}};
+use base64::{engine::general_purpose, Engine as _};
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{path::PathBuf, sync::Arc};
 use tauri::{
     api::dialog::{ask, message},
     CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
-    SystemTrayMenuItem,
+    SystemTrayMenuItem, SystemTraySubmenu,
 };
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use tracing::{error, info};
 
 static SERVER_STATE: OnceCell<Arc<RwLock<Option<ServerHandle>>>> = OnceCell::new();
 
-#[derive(Debug)]
-struct ServerHandle {
-    config: AppConfig,
-    processor: ScreenshotProcessor,
-    desktop_watcher: Option<DesktopWatcher>,
-    server_task: Option<tokio::task::JoinHandle<()>>,
+// Tauri Commands
+
+#[tauri::command]
+async fn greet(name: &str) -> Result<String, String> {
+    Ok(format!("Hello, {}! You've been greeted from Rust!", name))
+}
+
+#[tauri::command]
+async fn start_server(config: ServerConfig) -> Result<ServerInfo, String> {
+    // Held while starting, so a start from the UI and one on launch can't both go ahead
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut running = server_state.write().await;
+    if running.is_some() {
+        return Err("Server is already running".to_string());
+    }
+
+    info!("Starting screenshot server with config: {:?}", config);
+
+    let server_handle = ServerHandle::start(settings::app_config(config))
+        .await
+        .map_err(|e| format!("{:#}", e))?;
+    let server_info = server_handle.info();
+    let mut progress = server_handle.processor.subscribe_progress();
+    let mut paused = server_handle.processor.subscribe_paused();
+
+    // Store server handle globally
+    *running = Some(server_handle);
+    drop(running);
+    emit_event(UiEvent::ServerStateChanged(ServerStateChanged {
+        state: ServerState::Running,
+        error: None,
+    }));
+    emit_event(UiEvent::ServerStarted(server_info.clone()));
+
+    // Keep the tray's last analysis time and recent analyses current until the server stops
+    tokio::spawn(refresh_tray());
+    tokio::spawn(async move {
+        loop {
+            match progress.recv().await {
+                Ok(event) if matches!(event.stage, ProgressStage::AnalysisDone) => refresh_tray().await,
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+    // Pausing from Telegram or the API changes the tray too
+    tokio::spawn(async move {
+        while paused.changed().await.is_ok() {
+            refresh_tray().await;
+        }
+    });
+
+    Ok(server_info)
+}
+
+/// Checks the settings in the setup dialog against the services they name: the provider key,
+/// the Telegram bot and chats (which get a test message) and the server port.
+#[tauri::command]
+async fn validate_config(config: ServerConfig) -> Result<app::ValidationReport, String> {
+    let running_port = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        server_handle.as_ref().map(|handle| handle.config.server_port)
+    };
+
+    Ok(app::validate_config(&settings::app_config(config), running_port).await)
+}
+
+#[tauri::command]
+async fn stop_server() -> Result<String, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(handle) = server_handle.take() {
+        handle.stop();
+        emit_event(UiEvent::ServerStateChanged(ServerStateChanged {
+            state: ServerState::Stopped,
+            error: None,
+        }));
+        tokio::spawn(refresh_tray());
+        Ok("Server stopped successfully".to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_server_status() -> Result<Option<ServerInfo>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    Ok(server_handle.as_ref().map(ServerHandle::info))
+}
+
+#[tauri::command]
+async fn toggle_desktop_detection(enable: bool) -> Result<String, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        tokio::spawn(refresh_tray());
+        if enable && handle.desktop_watcher.is_none() {
+            match DesktopWatcher::new(handle.processor.clone(), handle.config.watch_directories.clone()) {
+                Ok(watcher) => {
+                    handle.desktop_watcher = Some(watcher);
+                    Ok("Desktop detection enabled".to_string())
+                }
+                Err(e) => Err(format!("Failed to enable desktop detection: {}", e)),
+            }
+        } else if !enable && handle.desktop_watcher.is_some() {
+            handle.desktop_watcher = None;
+            Ok("Desktop detection disabled".to_string())
+        } else {
+            Ok(format!(
+                "Desktop detection already {}",
+                if enable { "enabled" } else { "disabled" }
+            ))
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Stops or restarts analyzing new screenshots while the server and watchers keep running.
+/// Returns whether processing is now paused.
+#[tauri::command]
+async fn set_processing_paused(paused: bool) -> Result<bool, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    match *server_handle {
+        Some(ref handle) => {
+            handle.processor.set_paused(paused);
+            Ok(handle.processor.is_paused())
+        }
+        None => Err("Server is not running".to_string()),
+    }
+}
+
+/// Directories the desktop watcher uses, or would use once enabled.
+fn current_watch_directories(handle: &ServerHandle) -> Vec<WatchDirectory> {
+    match handle.desktop_watcher {
+        Some(ref watcher) => watcher.directories(),
+        None if handle.config.watch_directories.is_empty() => app::default_watch_directories(),
+        None => handle.config.watch_directories.clone(),
+    }
+}
+
+#[tauri::command]
+async fn list_watch_directories() -> Result<Vec<WatchDirectory>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        Ok(current_watch_directories(handle))
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn add_watch_directory(
+    path: String,
+    recursive: bool,
+    accept_all_images: Option<bool>,
+    after_analysis: Option<app::FileAction>,
+    archive_dir: Option<String>,
+) -> Result<Vec<WatchDirectory>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        let directory = WatchDirectory {
+            path: PathBuf::from(path),
+            recursive,
+            accept_all_images: accept_all_images.unwrap_or(false),
+            after_analysis: after_analysis.unwrap_or_default(),
+            archive_dir: archive_dir.filter(|dir| !dir.trim().is_empty()).map(PathBuf::from),
+        };
+
+        let mut directories = current_watch_directories(handle);
+        if let Some(ref watcher) = handle.desktop_watcher {
+            watcher.add_directory(directory.clone()).map_err(|e| e.to_string())?;
+        } else if !directory.resolved_path().is_dir() {
+            return Err(format!("Not a directory: {}", directory.resolved_path().display()));
+        }
+
+        directories.retain(|d| d.resolved_path() != directory.resolved_path());
+        directories.push(directory);
+        handle.config.watch_directories = directories.clone();
+        Ok(directories)
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn remove_watch_directory(path: String) -> Result<Vec<WatchDirectory>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        let target = WatchDirectory {
+            path: PathBuf::from(path),
+            ..Default::default()
+        };
+
+        if let Some(ref watcher) = handle.desktop_watcher {
+            watcher.remove_directory(&target.path).map_err(|e| e.to_string())?;
+        }
+
+        let mut directories = current_watch_directories(handle);
+        directories.retain(|d| d.resolved_path() != target.resolved_path());
+        handle.config.watch_directories = directories.clone();
+        Ok(directories)
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// The rules in effect, or the saved ones while the server is stopped.
+#[tauri::command]
+async fn get_exclusion_rules() -> Vec<app::ExclusionRule> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    match *server_handle {
+        Some(ref handle) => handle.processor.exclusion_rules(),
+        None => settings::load(None)
+            .unwrap_or_default()
+            .exclusion_rules
+            .unwrap_or_else(app::default_exclusion_rules),
+    }
+}
+
+/// Saves the exclusion rules and applies them to the running server straight away.
+#[tauri::command]
+async fn set_exclusion_rules(rules: Vec<app::ExclusionRule>) -> Result<Vec<app::ExclusionRule>, String> {
+    app::validate_exclusion_rules(&rules).map_err(|e| e.to_string())?;
+
+    let mut saved = settings::read_config_file().unwrap_or_default();
+    saved.exclusion_rules = Some(rules.clone());
+    settings::write_config_file(&saved).map_err(|e| e.to_string())?;
+
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+    if let Some(ref mut handle) = *server_handle {
+        handle.processor.set_exclusion_rules(&rules).map_err(|e| e.to_string())?;
+        handle.config.exclusion_rules = rules.clone();
+    }
+    Ok(rules)
+}
+
+#[tauri::command]
+async fn list_skipped_screenshots(limit: Option<usize>) -> Result<Vec<app::SkippedScreenshot>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .skipped_screenshots(limit.unwrap_or(100))
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn toggle_clipboard_detection(enable: bool) -> Result<String, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        if enable && handle.clipboard_watcher.is_none() {
+            match ClipboardWatcher::new(handle.processor.clone()) {
+                Ok(watcher) => {
+                    handle.clipboard_watcher = Some(watcher);
+                    Ok("Clipboard detection enabled".to_string())
+                }
+                Err(e) => Err(format!("Failed to enable clipboard detection: {}", e)),
+            }
+        } else if !enable && handle.clipboard_watcher.is_some() {
+            handle.clipboard_watcher = None;
+            Ok("Clipboard detection disabled".to_string())
+        } else {
+            Ok(format!(
+                "Clipboard detection already {}",
+                if enable { "enabled" } else { "disabled" }
+            ))
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Saves the server's API key with the rest of the settings, so a new one outlives a restart.
+fn save_api_key(api_key: &str) -> Result<(), String> {
+    let mut saved = settings::read_config_file().unwrap_or_default();
+    saved.api_key = Some(api_key.to_string());
+    settings::write_config_file(&saved).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn rotate_api_key() -> Result<String, String> {
+    let api_key = generate_api_key();
+    save_api_key(&api_key)?;
+
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        handle.processor.set_api_key(Some(api_key.clone()));
+        handle.config.api_key = Some(api_key.clone());
+        info!("🔑 API key rotated on running server");
+    }
+
+    Ok(api_key)
+}
+
+/// Renders a QR code the phone scans to get the endpoint and API key.
+#[tauri::command]
+async fn get_pairing_qr() -> Result<app::PairingQr, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        let qr = handle.processor.start_pairing().map_err(|e| e.to_string())?;
+        handle.config.api_key = Some(qr.api_key.clone());
+        save_api_key(&qr.api_key)?;
+        Ok(qr)
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn process_screenshot_direct(
+    image_base64: String,
+    metadata: Option<app::ScreenshotMetadata>,
+) -> Result<app::ProcessingResponse, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .process_screenshot(&image_base64, metadata)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn research_papers(analysis_id: String) -> Result<Vec<app::ArxivPaper>, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .research_papers(&analysis_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn fetch_webpage_content(analysis_id: String) -> Result<app::WebpageContent, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .fetch_webpage(&analysis_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_analysis(analysis_id: String) -> Result<bool, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .delete_analysis(&analysis_id)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Stops an analysis that is still running, returning whether there was one to stop.
+#[tauri::command]
+async fn cancel_analysis(analysis_id: String) -> Result<bool, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        Ok(handle.processor.cancel_analysis(&analysis_id))
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Screenshots whose analysis failed, shown in the history with their error.
+#[tauri::command]
+async fn list_failed_analyses() -> Result<Vec<app::FailedAnalysis>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.failed_analyses().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Runs a failed analysis's stored screenshot through the pipeline again.
+#[tauri::command]
+async fn retry_analysis(analysis_id: String) -> Result<app::ProcessingResponse, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor.retry_analysis(&analysis_id).await.map_err(|e| e.to_string())
+}
+
+/// Applies the retention limits now rather than at the next sweep.
+#[tauri::command]
+async fn run_cleanup_now() -> Result<app::CleanupReport, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    tokio::task::spawn_blocking(move || processor.run_cleanup())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// How much history is stored and what the last cleanup freed.
+#[tauri::command]
+async fn get_storage_stats() -> Result<app::StorageStats, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.storage_stats().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn clear_all_analyses() -> Result<usize, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .clear_all_analyses()
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn list_windows() -> Result<Vec<app::CapturableWindow>, String> {
+    tokio::task::spawn_blocking(app::list_capturable_windows)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Captures a region of the screen, in screen coordinates, and returns its analysis.
+#[tauri::command]
+async fn capture_region(x: i32, y: i32, width: u32, height: u32) -> Result<app::ProcessingResponse, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .capture_region(x, y, width, height)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn capture_window(window_id: u32) -> Result<app::ProcessingResponse, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .capture_window(window_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn start_journal() -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        if handle.journal_task.is_some() {
+            return Err("Activity journal is already running".to_string());
+        }
+        handle.journal_task = Some(handle.processor.spawn_journal());
+        Ok(())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn stop_journal() -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let mut server_handle = server_state.write().await;
+
+    if let Some(ref mut handle) = *server_handle {
+        if let Some(task) = handle.journal_task.take() {
+            task.abort();
+        }
+        Ok(())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Pauses journal captures for `minutes`, returning when they resume.
+#[tauri::command]
+async fn pause_journal(minutes: u32) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        Ok(handle.processor.pause_journal(minutes))
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn resume_journal() -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.resume_journal();
+        Ok(())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// The journal for a local date (`YYYY-MM-DD`), today if none is given.
+#[tauri::command]
+async fn get_journal_timeline(date: Option<String>) -> Result<Vec<app::JournalEntry>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .journal_timeline(date.as_deref())
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn list_devices() -> Result<Vec<app::Device>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.list_devices().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn rename_device(id: String, name: String) -> Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("Device name can't be empty".to_string());
+    }
+
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        match handle.processor.rename_device(&id, &name) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Device {} not found", id)),
+            Err(e) => Err(e.to_string()),
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Revokes a device's access with `enabled: false`, or restores it.
+#[tauri::command]
+async fn set_device_enabled(id: String, enabled: bool) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        match handle.processor.set_device_enabled(&id, enabled) {
+            Ok(true) => {
+                info!("{} device {}", if enabled { "Restored" } else { "Revoked" }, id);
+                Ok(())
+            }
+            Ok(false) => Err(format!("Device {} not found", id)),
+            Err(e) => Err(e.to_string()),
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_audit_log(query: Option<app::AuditQuery>) -> Result<Vec<app::AuditEntry>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .audit_log(&query.unwrap_or_default())
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_usage_stats() -> Result<app::UsageStats, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.usage_stats().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn get_budget_status() -> Result<app::BudgetStatus, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        Ok(handle.processor.budget_status())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Keeps analyzing past the hard monthly budget until the month ends.
+#[tauri::command]
+async fn override_budget(enabled: bool) -> Result<app::BudgetStatus, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.set_budget_override(enabled);
+        Ok(handle.processor.budget_status())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Checks that the Notion database is reachable and has the mapped properties.
+#[tauri::command]
+async fn test_notion_connection(
+    token: Option<String>,
+    database_id: String,
+    properties: Option<NotionProperties>,
+) -> Result<app::NotionDatabaseCheck, String> {
+    let token = match token.filter(|t| !t.trim().is_empty()) {
+        Some(token) => token,
+        None => secrets::get(SecretName::NotionToken)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| "No Notion token is configured".to_string())?,
+    };
+
+    app::check_notion_database(
+        &reqwest::Client::new(),
+        token.trim(),
+        database_id.trim(),
+        &properties.unwrap_or_default(),
+    )
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Analyzes a stored screenshot again with another prompt or model, keeping the original.
+#[tauri::command]
+async fn reanalyze(
+    analysis_id: String,
+    prompt_override: Option<String>,
+    model_override: Option<String>,
+) -> Result<app::ProcessingResponse, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .reanalyze(&analysis_id, prompt_override, model_override)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Analyzes the screenshots already in a folder, reporting progress through `import-progress` events.
+#[tauri::command]
+async fn import_folder(path: PathBuf, options: Option<app::ImportOptions>) -> Result<app::ImportSummary, String> {
+    // Clone the processor so a long import doesn't hold the server lock
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .import_folder(&path, options.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+struct DateRange {
+    from: Option<chrono::DateTime<chrono::Utc>>,
+    to: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Saves stored analyses to `path` as JSON or CSV, or a zip with images, returning how many were exported.
+#[tauri::command]
+async fn export_history(
+    format: app::ExportFormat,
+    date_range: Option<DateRange>,
+    path: PathBuf,
+    include_images: Option<bool>,
+    project_id: Option<String>,
+) -> Result<usize, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        let options = app::ExportOptions {
+            format,
+            from: date_range.as_ref().and_then(|range| range.from),
+            to: date_range.as_ref().and_then(|range| range.to),
+            include_images: include_images.unwrap_or(false),
+            project: project_id,
+        };
+        let archive = handle.processor.export_history(&options).map_err(|e| e.to_string())?;
+        std::fs::write(&path, &archive.bytes).map_err(|e| e.to_string())?;
+        Ok(archive.analyses)
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Rewrites the system search stubs for every analysis, returning how many were written.
+#[tauri::command]
+async fn reindex_spotlight() -> Result<usize, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    tokio::task::spawn_blocking(move || processor.reindex_spotlight())
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Writes an analysis into the configured vault folder as a Markdown note.
+#[tauri::command]
+async fn export_analysis(id: String) -> Result<String, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .export_analysis(&id)
+            .map(|path| path.display().to_string())
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Sends today's digest to Telegram right away, for checking what it looks like; only one
+/// project's when `project_id` is given.
+#[tauri::command]
+async fn send_digest_now(project_id: Option<String>) -> Result<String, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .send_digest(project_id.as_deref())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn search_analyses(
+    query: String,
+    limit: Option<usize>,
+    project_id: Option<String>,
+) -> Result<Vec<app::SearchResult>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .search_analyses(&query, limit.unwrap_or(20), project_id.as_deref(), true)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn list_tags() -> Result<Vec<app::TagCount>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.list_tags().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// History filtered to one tag, newest first; `page` is 1-based.
+#[tauri::command]
+async fn get_analyses_by_tag(
+    tag: String,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<app::AnalysisPage, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .list_analyses_by_tag(&tag, page.unwrap_or(1), page_size.unwrap_or(50), true)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn add_tag(analysis_id: String, tag: String) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        match handle.processor.add_tag(&analysis_id, &tag) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Analysis {} not found", analysis_id)),
+            Err(e) => Err(e.to_string()),
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn remove_tag(analysis_id: String, tag: String) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        match handle.processor.remove_tag(&analysis_id, &tag) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Analysis {} not found", analysis_id)),
+            Err(e) => Err(e.to_string()),
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Replaces the note on an analysis; an empty note clears it.
+#[tauri::command]
+async fn set_note(analysis_id: String, note: String) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        match handle.processor.set_note(&analysis_id, &note) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Analysis {} not found", analysis_id)),
+            Err(e) => Err(e.to_string()),
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn set_starred(analysis_id: String, starred: bool) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        match handle.processor.set_starred(&analysis_id, starred) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Analysis {} not found", analysis_id)),
+            Err(e) => Err(e.to_string()),
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Saved searches with their current sizes.
+#[tauri::command]
+async fn list_collections() -> Result<Vec<app::Collection>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.list_collections().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn create_collection(collection: app::CollectionInput) -> Result<app::Collection, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .create_collection(collection)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn update_collection(id: String, collection: app::CollectionInput) -> Result<app::Collection, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .update_collection(&id, collection)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn delete_collection(id: String) -> Result<bool, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.delete_collection(&id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// The analyses in a collection, newest first; `page` is 1-based.
+#[tauri::command]
+async fn get_collection_analyses(
+    id: String,
+    page: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<app::AnalysisPage, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .collection_analyses(&id, page.unwrap_or(1), page_size.unwrap_or(50), true)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Projects with their sizes, oldest first.
+#[tauri::command]
+async fn list_projects() -> Result<Vec<app::Project>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.list_projects().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn create_project(project: app::ProjectInput) -> Result<app::Project, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.create_project(project).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn update_project(id: String, project: app::ProjectInput) -> Result<app::Project, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .update_project(&id, project)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn delete_project(id: String) -> Result<bool, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.delete_project(&id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Makes a project the one new screenshots go to, or none when `id` is null.
+#[tauri::command]
+async fn set_active_project(id: Option<String>) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .set_active_project(id.as_deref())
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Moves an analysis to a project, or out of any when `project_id` is null.
+#[tauri::command]
+async fn assign_project(analysis_id: String, project_id: Option<String>) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        match handle.processor.assign_project(&analysis_id, project_id.as_deref()) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("Analysis {} not found", analysis_id)),
+            Err(e) => Err(e.to_string()),
+        }
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn list_rules() -> Result<Vec<app::Rule>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.list_rules().map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn create_rule(rule: app::RuleInput) -> Result<app::Rule, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.create_rule(rule).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn update_rule(id: String, rule: app::RuleInput) -> Result<app::Rule, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.update_rule(&id, rule).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn delete_rule(id: String) -> Result<bool, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.delete_rule(&id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// What rules did to new analyses, most recent first, optionally only one rule's.
+#[tauri::command]
+async fn get_rule_runs(rule_id: Option<String>, limit: Option<usize>) -> Result<Vec<app::RuleRun>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .rule_runs(rule_id.as_deref(), limit.unwrap_or(100))
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Opens one of the links found in a screenshot in the default browser.
+#[tauri::command]
+async fn open_link(app_handle: tauri::AppHandle, analysis_id: String, index: usize) -> Result<(), String> {
+    let url = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.link_url(&analysis_id, index).map_err(|e| e.to_string())?,
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    tauri::api::shell::open(&app_handle.shell_scope(), url, None).map_err(|e| e.to_string())
+}
+
+/// The receipt or invoice details read off an analysis, if it was one.
+#[tauri::command]
+async fn get_receipt(analysis_id: String) -> Result<Option<app::ReceiptData>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.get_receipt(&analysis_id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Saves every extracted receipt to `path` as CSV, returning how many were exported.
+#[tauri::command]
+async fn export_receipts(path: PathBuf) -> Result<usize, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.export_receipts(&path).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// The code transcribed from a screenshot of code, if any.
+#[tauri::command]
+async fn get_code_snippet(analysis_id: String) -> Result<Option<app::CodeSnippet>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.get_code_snippet(&analysis_id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn copy_code(analysis_id: String) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.copy_code_to_clipboard(&analysis_id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+#[tauri::command]
+async fn save_code(analysis_id: String, path: PathBuf) -> Result<(), String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.save_code_to_file(&analysis_id, &path).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// The tables read off a screenshot, if any.
+#[tauri::command]
+async fn get_table(analysis_id: String) -> Result<Option<app::ExtractedTables>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle.processor.get_tables(&analysis_id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Saves the tables read off a screenshot to `path` as CSV or JSON, returning how many were written.
+#[tauri::command]
+async fn export_table(analysis_id: String, path: PathBuf, format: app::ExportFormat) -> Result<usize, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .export_tables(&analysis_id, format, &path)
+            .map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ServerConfig {
-    anthropic_api_key: Option<String>,
-    telegram_bot_token: Option<String>,
-    telegram_chat_id: Option<String>,
-    enable_desktop_detection: bool,
-    server_port: u16,
-}
+/// The deep research report for an analysis, if the research has been run.
+#[tauri::command]
+async fn get_research_report(analysis_id: String) -> Result<Option<app::ResearchReport>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
 
-impl Default for ServerConfig {
-    fn default() -> Self {
-        Self {
-            anthropic_api_key: None,
-            telegram_bot_token: None,
-            telegram_chat_id: None,
-            enable_desktop_detection: false,
-            server_port: 5001,
-        }
+    if let Some(ref handle) = *server_handle {
+        handle.processor.get_research_report(&analysis_id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ServerInfo {
-    status: String,
-    local_ip: String,
-    port: u16,
-    endpoint_url: String,
-    desktop_detection: bool,
-    telegram_configured: bool,
-}
+/// Recent bursts of screenshots grouped into sessions, newest first.
+#[tauri::command]
+async fn list_sessions(limit: Option<usize>) -> Result<Vec<app::Session>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
 
-// Tauri Commands
+    if let Some(ref handle) = *server_handle {
+        handle.processor.list_sessions(limit.unwrap_or(20)).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
 
+/// Asks the model what changed from the first screenshot to the second.
 #[tauri::command]
-async fn greet(name: &str) -> Result<String, String> {
-    Ok(format!("Hello, {}! You've been greeted from Rust!", name))
+async fn compare_analyses(id_a: String, id_b: String) -> Result<app::AnalysisComparison, String> {
+    // Clone the processor so the request doesn't hold the server lock
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor.compare_analyses(&id_a, &id_b).await.map_err(|e| e.to_string())
 }
 
+/// Translates the text in a screenshot into `target_lang`, e.g. `English`.
 #[tauri::command]
-async fn start_server(config: ServerConfig) -> Result<ServerInfo, String> {
-    info!("Starting screenshot server with config: {:?}", config);
-
-    // Validate required fields
-    let anthropic_api_key = config
-        .anthropic_api_key
-        .ok_or("Anthropic API key is required")?;
-
-    let server_config = AppConfig {
-        anthropic_api_key,
-        telegram_bot_token: config.telegram_bot_token,
-        telegram_chat_id: config.telegram_chat_id,
-        enable_desktop_detection: config.enable_desktop_detection,
-        server_port: config.server_port,
+async fn translate_analysis(analysis_id: String, target_lang: String) -> Result<app::Translation, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
     };
 
-    let processor = ScreenshotProcessor::new(server_config.clone());
+    processor
+        .translate_analysis(&analysis_id, &target_lang)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    // Start desktop watcher if enabled
-    let desktop_watcher = if server_config.enable_desktop_detection {
-        match DesktopWatcher::new(processor.clone()) {
-            Ok(watcher) => Some(watcher),
-            Err(e) => {
-                error!("Failed to start desktop watcher: {}", e);
-                None
-            }
-        }
+/// A screenshot's full image, for the viewer; history lists only carry thumbnails.
+#[tauri::command]
+async fn get_screenshot_image(analysis_id: String) -> Result<Option<serde_json::Value>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+
+    if let Some(ref handle) = *server_handle {
+        let image = handle
+            .processor
+            .analysis_image(&analysis_id, app::ImageVariant::Full)
+            .map_err(|e| e.to_string())?;
+        Ok(image.map(|(bytes, media_type)| {
+            serde_json::json!({
+                "type": media_type,
+                "imageData": general_purpose::STANDARD.encode(bytes),
+            })
+        }))
     } else {
-        None
-    };
+        Err("Server is not running".to_string())
+    }
+}
 
-    // Start HTTP server in background
-    let server_config_clone = server_config.clone();
-    let server_task = tokio::spawn(async move {
-        if let Err(e) = start_screenshot_server(server_config_clone).await {
-            error!("Screenshot server error: {}", e);
-        }
-    });
+/// The frames of a screen recording or animated GIF analysis as base64 PNGs, in order.
+#[tauri::command]
+async fn get_analysis_frames(analysis_id: String) -> Result<Vec<String>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
 
-    let local_ip = local_ip_address::local_ip()
-        .map(|ip| ip.to_string())
-        .unwrap_or_else(|_| "127.0.0.1".to_string());
+    if let Some(ref handle) = *server_handle {
+        let frames = handle
+            .processor
+            .analysis_frames(&analysis_id)
+            .map_err(|e| e.to_string())?;
+        Ok(frames.iter().map(|frame| general_purpose::STANDARD.encode(frame)).collect())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
 
-    let server_handle = ServerHandle {
-        config: server_config.clone(),
-        processor,
-        desktop_watcher,
-        server_task: Some(server_task),
+#[tauri::command]
+async fn ask_followup(analysis_id: String, question: String) -> Result<ChatTurn, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
     };
 
-    // Store server handle globally
+    processor
+        .ask_followup(&analysis_id, &question)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_annotations(analysis_id: String) -> Result<Vec<Annotation>, String> {
     let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
-    *server_state.write().await = Some(server_handle);
+    let server_handle = server_state.read().await;
 
-    Ok(ServerInfo {
-        status: "running".to_string(),
-        local_ip: local_ip.clone(),
-        port: server_config.server_port,
-        endpoint_url: format!("http://{}:{}/screenshot", local_ip, server_config.server_port),
-        desktop_detection: server_config.enable_desktop_detection,
-        telegram_configured: server_config.telegram_bot_token.is_some(),
-    })
+    if let Some(ref handle) = *server_handle {
+        handle.processor.annotations(&analysis_id).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
 }
 
 #[tauri::command]
-async fn stop_server() -> Result<String, String> {
+async fn add_annotation(analysis_id: String, annotation: AnnotationInput) -> Result<Annotation, String> {
     let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
-    let mut server_handle = server_state.write().await;
+    let server_handle = server_state.read().await;
 
-    if let Some(handle) = server_handle.take() {
-        if let Some(task) = handle.server_task {
-            task.abort();
-        }
-        info!("Screenshot server stopped");
-        Ok("Server stopped successfully".to_string())
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .add_annotation(&analysis_id, annotation)
+            .map_err(|e| e.to_string())
     } else {
         Err("Server is not running".to_string())
     }
 }
 
 #[tauri::command]
-async fn get_server_status() -> Result<Option<ServerInfo>, String> {
+async fn update_annotation(id: String, annotation: AnnotationInput) -> Result<Annotation, String> {
     let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
     let server_handle = server_state.read().await;
 
     if let Some(ref handle) = *server_handle {
-        let local_ip = local_ip_address::local_ip()
-            .map(|ip| ip.to_string())
-            .unwrap_or_else(|_| "127.0.0.1".to_string());
-
-        Ok(Some(ServerInfo {
-            status: "running".to_string(),
-            local_ip: local_ip.clone(),
-            port: handle.config.server_port,
-            endpoint_url: format!(
-                "http://{}:{}/screenshot",
-                local_ip, handle.config.server_port
-            ),
-            desktop_detection: handle.config.enable_desktop_detection,
-            telegram_configured: handle.config.telegram_bot_token.is_some(),
-        }))
+        handle
+            .processor
+            .update_annotation(&id, annotation)
+            .map_err(|e| e.to_string())
     } else {
-        Ok(None)
+        Err("Server is not running".to_string())
     }
 }
 
 #[tauri::command]
-async fn toggle_desktop_detection(enable: bool) -> Result<String, String> {
+async fn delete_annotation(id: String) -> Result<bool, String> {
     let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
-    let mut server_handle = server_state.write().await;
+    let server_handle = server_state.read().await;
 
-    if let Some(ref mut handle) = *server_handle {
-        if enable && handle.desktop_watcher.is_none() {
-            match DesktopWatcher::new(handle.processor.clone()) {
-                Ok(watcher) => {
-                    handle.desktop_watcher = Some(watcher);
-                    Ok("Desktop detection enabled".to_string())
-                }
-                Err(e) => Err(format!("Failed to enable desktop detection: {}", e)),
-            }
-        } else if !enable && handle.desktop_watcher.is_some() {
-            handle.desktop_watcher = None;
-            Ok("Desktop detection disabled".to_string())
-        } else {
-            Ok(format!(
-                "Desktop detection already {}",
-                if enable { "enabled" } else { "disabled" }
-            ))
-        }
+    if let Some(ref handle) = *server_handle {
+        handle.processor.delete_annotation(&id).map_err(|e| e.to_string())
     } else {
         Err("Server is not running".to_string())
     }
 }
 
+/// Saves a copy of the screenshot with its annotations drawn in, as PNG.
 #[tauri::command]
-async fn process_screenshot_direct(
-    image_base64: String,
-    metadata: Option<lib::ScreenshotMetadata>,
-) -> Result<lib::ProcessingResponse, String> {
+async fn export_annotated_image(analysis_id: String, path: PathBuf) -> Result<(), String> {
     let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
     let server_handle = server_state.read().await;
 
     if let Some(ref handle) = *server_handle {
-        let result = handle
+        let image = handle
             .processor
-            .process_screenshot(&image_base64, metadata)
-            .await
-            .map_err(|e| e.to_string())?;
+            .annotated_image(&analysis_id)
+            .map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("Analysis {} not found", analysis_id))?;
+        std::fs::write(&path, image).map_err(|e| e.to_string())
+    } else {
+        Err("Server is not running".to_string())
+    }
+}
+
+/// Asks about the area of a screenshot selected in the viewer.
+#[tauri::command]
+async fn ask_about_region(analysis_id: String, rect: RegionRect, question: String) -> Result<ChatTurn, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .ask_about_region(&analysis_id, rect, &question)
+        .await
+        .map_err(|e| e.to_string())
+}
 
-        // REMOVE THIS ENTIRE EMIT BLOCK:
-        // (The emit is already handled in lib.rs for desktop screenshots)
-        //
-        // if let Some(app_handle) = get_app_handle() {
-        //     if let Some(window) = app_handle.get_window("main") {
-        //         let screenshot_data = serde_json::json!({...});
-        //         let _ = window.emit("screenshot-processed", screenshot_data);
-        //     }
-        // }
+#[tauri::command]
+async fn get_conversation(analysis_id: String) -> Result<Vec<ChatTurn>, String> {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
 
-        Ok(result)
+    if let Some(ref handle) = *server_handle {
+        handle
+            .processor
+            .conversation(&analysis_id)
+            .map_err(|e| e.to_string())
     } else {
         Err("Server is not running".to_string())
     }
 }
 
+/// A page of the history with thumbnails, filtered and sorted; `page` is 1-based.
 #[tauri::command]
-async fn get_recent_screenshots() -> Result<Vec<serde_json::Value>, String> {
+async fn list_analyses(
+    page: Option<usize>,
+    page_size: Option<usize>,
+    filter: Option<app::AnalysisFilter>,
+    sort: Option<app::AnalysisSort>,
+) -> Result<app::AnalysisSummaryPage, String> {
     let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
     let server_handle = server_state.read().await;
 
     if let Some(ref handle) = *server_handle {
-        let analyses = handle.processor.get_recent_analyses().await;
-        Ok(analyses)
+        handle
+            .processor
+            .list_analysis_summaries(
+                page.unwrap_or(1),
+                page_size.unwrap_or(50),
+                &filter.unwrap_or_default(),
+                sort.unwrap_or_default(),
+            )
+            .map_err(|e| e.to_string())
     } else {
-        Ok(Vec::new())
+        Err("Server is not running".to_string())
     }
 }
 
+#[derive(Debug, Serialize)]
+struct PromptTemplateList {
+    templates: Vec<PromptTemplate>,
+    /// Placeholders templates can use as `{name}`.
+    variables: Vec<String>,
+}
+
+/// Prompt templates, built-in and saved, with the variables they can use.
+#[tauri::command]
+async fn list_prompt_templates() -> Result<PromptTemplateList, String> {
+    Ok(PromptTemplateList {
+        templates: app::prompts::list_templates().map_err(|e| e.to_string())?,
+        variables: app::TEMPLATE_VARIABLES.iter().map(|v| v.to_string()).collect(),
+    })
+}
+
+#[tauri::command]
+async fn create_prompt_template(template: PromptTemplate) -> Result<Vec<PromptTemplate>, String> {
+    app::prompts::create_template(template).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn update_prompt_template(name: String, template: PromptTemplate) -> Result<Vec<PromptTemplate>, String> {
+    app::prompts::update_template(&name, template).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn delete_prompt_template(name: String) -> Result<bool, String> {
+    app::prompts::delete_template(&name).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_secret(name: SecretName, value: String) -> Result<(), String> {
+    secrets::save(name, &value).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn has_secret(name: SecretName) -> bool {
+    secrets::has(name)
+}
+
+#[tauri::command]
+async fn process_screenshot_batch(images: Vec<app::ScreenshotRequest>) -> Result<app::BatchResponse, String> {
+    let processor = {
+        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+        let server_handle = server_state.read().await;
+        match *server_handle {
+            Some(ref handle) => handle.processor.clone(),
+            None => return Err("Server is not running".to_string()),
+        }
+    };
+
+    processor
+        .process_batch(images)
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 async fn load_env_config() -> ServerConfig {
-    // Try to load from environment variables or config file
-    ServerConfig {
-        anthropic_api_key: std::env::var("ANTHROPIC_API_KEY").ok(),
-        telegram_bot_token: std::env::var("TELEGRAM_BOT_TOKEN").ok(),
-        telegram_chat_id: std::env::var("TELEGRAM_CHAT_ID").ok(),
-        enable_desktop_detection: std::env::var("ENABLE_DESKTOP_DETECTION")
-            .map(|v| v.to_lowercase() == "true")
-            .unwrap_or(false),
-        server_port: std::env::var("SERVER_PORT")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(5001),
+    let mut config = ServerConfig::default();
+    settings::apply_env_overrides(&mut config);
+    config
+}
+
+/// Loads the saved config file, with any configuration env vars taking precedence.
+#[tauri::command]
+async fn load_config() -> ServerConfig {
+    let mut config = settings::read_config_file().unwrap_or_default();
+    settings::apply_env_overrides(&mut config);
+    config
+}
+
+#[tauri::command]
+async fn save_config(config: ServerConfig) -> Result<(), String> {
+    if let Some(filter) = config.log_filter.as_deref().filter(|f| !f.trim().is_empty()) {
+        app::logging::set_filter(filter).map_err(|e| e.to_string())?;
     }
+    settings::write_config_file(&config).map_err(|e| e.to_string())
+}
+
+/// Whether the app starts in the background when the user logs in.
+#[tauri::command]
+async fn get_launch_at_login() -> bool {
+    app::login_item::is_enabled()
+}
+
+#[tauri::command]
+async fn set_launch_at_login(enabled: bool) -> Result<(), String> {
+    app::login_item::set_enabled(enabled).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_log_tail(lines: Option<usize>) -> Result<Vec<String>, String> {
+    app::logging::tail(lines.unwrap_or(200)).map_err(|e| e.to_string())
 }
 
+
 // System tray setup
-fn create_system_tray() -> SystemTray {
-    let quit = CustomMenuItem::new("quit".to_string(), "Quit");
-    let hide = CustomMenuItem::new("hide".to_string(), "Hide");
-    let show = CustomMenuItem::new("show".to_string(), "Show");
-    let server_status = CustomMenuItem::new("server_status".to_string(), "Server Status");
-
-    let tray_menu = SystemTrayMenu::new()
-        .add_item(show)
-        .add_item(hide)
+
+/// How many analyses the tray's "Recent Analyses" submenu lists.
+const TRAY_RECENT_ANALYSES: usize = 5;
+/// Menu ids of recent analyses are this followed by the analysis id.
+const TRAY_RECENT_PREFIX: &str = "recent:";
+static TRAY_ICON: &[u8] = include_bytes!("../icons/icon.png");
+
+/// What the tray shows, from the running server if there is one.
+#[derive(Debug, Default)]
+struct TrayState {
+    port: Option<u16>,
+    desktop_detection: bool,
+    paused: bool,
+    recent: Vec<app::AnalysisSummary>,
+}
+
+async fn tray_state() -> TrayState {
+    let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+    let server_handle = server_state.read().await;
+    let Some(ref handle) = *server_handle else {
+        return TrayState::default();
+    };
+
+    let recent = handle
+        .processor
+        .list_analysis_summaries(1, TRAY_RECENT_ANALYSES, &Default::default(), Default::default())
+        .map(|page| page.analyses)
+        .unwrap_or_else(|e| {
+            error!("Failed to list recent analyses for the tray: {}", e);
+            Vec::new()
+        });
+    TrayState {
+        port: Some(handle.config.server_port),
+        desktop_detection: handle.desktop_watcher.is_some(),
+        paused: handle.processor.is_paused(),
+        recent,
+    }
+}
+
+fn tray_menu(state: &TrayState) -> SystemTrayMenu {
+    let running = state.port.is_some();
+    let server_status = CustomMenuItem::new(
+        "server_status".to_string(),
+        match state.port {
+            Some(port) => format!("Server Running on Port {}", port),
+            None => "Server Stopped".to_string(),
+        },
+    );
+    let start_stop = CustomMenuItem::new(
+        "start_stop".to_string(),
+        if running { "Stop Server" } else { "Start Server" },
+    );
+    let mut desktop_detection = CustomMenuItem::new(
+        "toggle_desktop_detection".to_string(),
+        if state.desktop_detection {
+            "Pause Desktop Detection"
+        } else {
+            "Resume Desktop Detection"
+        },
+    );
+    let mut pause_processing = CustomMenuItem::new(
+        "pause_processing".to_string(),
+        if state.paused { "Resume Processing" } else { "Pause Processing" },
+    );
+    let mut analyze_clipboard = CustomMenuItem::new("analyze_clipboard".to_string(), "Analyze Clipboard");
+    if !running {
+        desktop_detection = desktop_detection.disabled();
+        pause_processing = pause_processing.disabled();
+        analyze_clipboard = analyze_clipboard.disabled();
+    }
+
+    let mut recent = SystemTrayMenu::new();
+    for analysis in &state.recent {
+        let text = if analysis.summary.is_empty() { &analysis.name } else { &analysis.summary };
+        let mut title: String = text.chars().take(40).collect();
+        if title.len() < text.len() {
+            title.push('…');
+        }
+        recent = recent.add_item(CustomMenuItem::new(
+            format!("{}{}", TRAY_RECENT_PREFIX, analysis.id),
+            format!("{}  {}", analysis.timestamp.with_timezone(&chrono::Local).format("%H:%M"), title),
+        ));
+    }
+    if state.recent.is_empty() {
+        recent = recent.add_item(CustomMenuItem::new("no_recent".to_string(), "No Analyses Yet").disabled());
+    }
+
+    SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new("show".to_string(), "Show"))
+        .add_item(CustomMenuItem::new("hide".to_string(), "Hide"))
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(server_status)
+        .add_item(start_stop)
+        .add_item(desktop_detection)
+        .add_item(pause_processing)
+        .add_item(analyze_clipboard)
+        .add_submenu(SystemTraySubmenu::new("Recent Analyses", recent))
         .add_native_item(SystemTrayMenuItem::Separator)
-        .add_item(quit);
+        .add_item(CustomMenuItem::new("quit".to_string(), "Quit"))
+}
+
+fn tray_tooltip(state: &TrayState) -> String {
+    let Some(port) = state.port else {
+        return "Screenshot AI Studio: server stopped".to_string();
+    };
+    let last_analysis = state
+        .recent
+        .first()
+        .map(|analysis| {
+            format!(
+                "\nLast analysis {}",
+                analysis.timestamp.with_timezone(&chrono::Local).format("%b %-d, %H:%M")
+            )
+        })
+        .unwrap_or_default();
+    format!(
+        "Screenshot AI Studio: {} on port {}{}",
+        if state.paused { "paused" } else { "running" },
+        port,
+        last_analysis
+    )
+}
+
+/// The app icon, faded while the server is stopped.
+fn tray_icon(running: bool) -> Option<tauri::Icon> {
+    let mut icon = image::load_from_memory(TRAY_ICON).ok()?.to_rgba8();
+    if !running {
+        for pixel in icon.pixels_mut() {
+            pixel[3] /= 3;
+        }
+    }
+    Some(tauri::Icon::Rgba {
+        width: icon.width(),
+        height: icon.height(),
+        rgba: icon.into_raw(),
+    })
+}
+
+fn create_system_tray() -> SystemTray {
+    let state = TrayState::default();
+    SystemTray::new()
+        .with_menu(tray_menu(&state))
+        .with_tooltip(&tray_tooltip(&state))
+}
 
-    SystemTray::new().with_menu(tray_menu)
+/// Updates the tray's icon, tooltip and menu to match the server.
+async fn refresh_tray() {
+    let Some(app) = app::get_app_handle() else {
+        return;
+    };
+    let state = tray_state().await;
+    let tray = app.tray_handle();
+
+    if let Some(icon) = tray_icon(state.port.is_some()) {
+        let _ = tray.set_icon(icon);
+        #[cfg(target_os = "macos")]
+        let _ = tray.set_icon_as_template(true);
+    }
+    let _ = tray.set_tooltip(&tray_tooltip(&state));
+    if let Err(e) = tray.set_menu(tray_menu(&state)) {
+        error!("Failed to update the tray menu: {}", e);
+    }
+}
+
+fn show_tray_message(app: &tauri::AppHandle, title: &str, text: &str) {
+    if let Some(window) = app.get_window("main") {
+        message(Some(&window), title, text);
+    }
 }
 
 fn handle_system_tray_event(app: &tauri::AppHandle, event: SystemTrayEvent) {
@@ -318,18 +1885,71 @@ fn handle_system_tray_event(app: &tauri::AppHandle, event: SystemTrayEvent) {
                             "Server Status: {}\nEndpoint: {}\nDesktop Detection: {}",
                             status.status, status.endpoint_url, status.desktop_detection
                         );
-                        
-                        if let Some(window) = app_clone.get_window("main") {
-                            message(Some(&window), "Server Status", &msg);
-                        }
+                        show_tray_message(&app_clone, "Server Status", &msg);
                     } else {
-                        if let Some(window) = app_clone.get_window("main") {
-                            message(Some(&window), "Server Status", "Server is not running");
-                        }
+                        show_tray_message(&app_clone, "Server Status", "Server is not running");
+                    }
+                });
+            }
+            "start_stop" => {
+                let app_clone = app.clone();
+                tokio::spawn(async move {
+                    let result = if matches!(get_server_status().await, Ok(Some(_))) {
+                        stop_server().await.map(|_| ())
+                    } else {
+                        start_server(settings::load(None).unwrap_or_default()).await.map(|_| ())
+                    };
+                    if let Err(e) = result {
+                        show_tray_message(&app_clone, "Screenshot Server", &e);
+                    }
+                });
+            }
+            "toggle_desktop_detection" => {
+                let app_clone = app.clone();
+                tokio::spawn(async move {
+                    let enabled = matches!(get_server_status().await, Ok(Some(status)) if status.desktop_detection);
+                    if let Err(e) = toggle_desktop_detection(!enabled).await {
+                        show_tray_message(&app_clone, "Desktop Detection", &e);
+                    }
+                });
+            }
+            "pause_processing" => {
+                let app_clone = app.clone();
+                tokio::spawn(async move {
+                    let paused = matches!(get_server_status().await, Ok(Some(status)) if status.processing_paused);
+                    if let Err(e) = set_processing_paused(!paused).await {
+                        show_tray_message(&app_clone, "Processing", &e);
+                    }
+                });
+            }
+            "analyze_clipboard" => {
+                let app_clone = app.clone();
+                tokio::spawn(async move {
+                    let processor = {
+                        let server_state = SERVER_STATE.get_or_init(|| Arc::new(RwLock::new(None)));
+                        let server_handle = server_state.read().await;
+                        server_handle.as_ref().map(|handle| handle.processor.clone())
+                    };
+                    let result = match processor {
+                        Some(processor) => processor.analyze_clipboard().await.map_err(|e| e.to_string()),
+                        None => Err("Server is not running".to_string()),
+                    };
+                    if let Err(e) = result {
+                        show_tray_message(&app_clone, "Analyze Clipboard", &e);
                     }
                 });
             }
-            _ => {}
+            id => {
+                if let Some(analysis_id) = id.strip_prefix(TRAY_RECENT_PREFIX) {
+                    if let Some(window) = app.get_window("main") {
+                        let _ = window.show();
+                        let _ = window.set_focus();
+                    }
+                    emit_event(UiEvent::OpenAnalysis(OpenAnalysis {
+                        id: analysis_id.to_string(),
+                    }));
+                }
+            }
         },
         _ => {}
     }
@@ -337,18 +1957,44 @@ fn handle_system_tray_event(app: &tauri::AppHandle, event: SystemTrayEvent) {
 
 #[tokio::main]
 async fn main() {
-    // Initialize logging
-    tracing_subscriber::fmt::init();
+    // Initialize logging, to stdout and rolling files in the app data dir
+    let saved_config = settings::load(None).unwrap_or_default();
+    let _log_guard = app::logging::init(saved_config.log_filter.as_deref().filter(|f| !f.trim().is_empty()));
 
     info!("🚀 Starting Screenshot AI Studio");
 
     let context = tauri::generate_context!();
 
     tauri::Builder::default()
-        .setup(|app| {
+        .setup(move |app| {
             // Store app handle for emitting events
             set_app_handle(app.handle());
-            
+
+            // Fade the tray icon until the server is running
+            tokio::spawn(refresh_tray());
+
+            // Started by the login item: stay in the tray
+            let background = app::login_item::launched_in_background();
+            if background {
+                if let Some(window) = app.get_window("main") {
+                    let _ = window.hide();
+                }
+            }
+
+            // Start the server on the saved settings; they're already set up, so skip the welcome
+            if background || saved_config.auto_start_server {
+                tokio::spawn(async move {
+                    if let Err(e) = start_server(saved_config).await {
+                        error!("Failed to start server on launch: {}", e);
+                        emit_event(UiEvent::ServerStateChanged(ServerStateChanged {
+                            state: ServerState::Failed,
+                            error: Some(e),
+                        }));
+                    }
+                });
+                return Ok(());
+            }
+
             // The main window is already created by tauri.conf.json
             // Show setup dialog on first run
             let app_handle = app.handle();
@@ -356,7 +2002,6 @@ async fn main() {
                 tokio::time::sleep(tokio::time::Duration::from_millis(2000)).await;
                 
                 if let Some(window) = app_handle.get_window("main") {
-                    let app_handle_clone = app_handle.clone();
                     ask(
                         Some(&window),
                         "Welcome to Screenshot AI Studio",
@@ -364,9 +2009,7 @@ async fn main() {
                         move |show_setup| {
                             if show_setup {
                                 // Emit event to frontend to show setup dialog
-                                if let Some(window) = app_handle_clone.get_window("main") {
-                                    let _ = window.emit("show-setup-dialog", ());
-                                }
+                                emit_event(UiEvent::ShowSetupDialog);
                             }
                         }
                     );
@@ -375,17 +2018,123 @@ async fn main() {
 
             Ok(())
         })
+        .on_window_event(|event| {
+            if let tauri::WindowEvent::Focused(true) = event.event() {
+                if event.window().label() == "main" {
+                    on_main_window_focused();
+                }
+            }
+        })
         .system_tray(create_system_tray())
         .on_system_tray_event(handle_system_tray_event)
         .invoke_handler(tauri::generate_handler![
             greet,
             start_server,
+            validate_config,
             stop_server,
             get_server_status,
             toggle_desktop_detection,
+            set_processing_paused,
+            get_exclusion_rules,
+            set_exclusion_rules,
+            list_skipped_screenshots,
+            toggle_clipboard_detection,
+            list_watch_directories,
+            add_watch_directory,
+            remove_watch_directory,
+            rotate_api_key,
+            get_pairing_qr,
             process_screenshot_direct,
+            process_screenshot_batch,
             load_env_config,
-            get_recent_screenshots,
+            load_config,
+            save_config,
+            get_launch_at_login,
+            set_launch_at_login,
+            list_prompt_templates,
+            create_prompt_template,
+            update_prompt_template,
+            delete_prompt_template,
+            save_secret,
+            has_secret,
+            list_analyses,
+            research_papers,
+            fetch_webpage_content,
+            delete_analysis,
+            clear_all_analyses,
+            search_analyses,
+            list_tags,
+            get_analyses_by_tag,
+            add_tag,
+            remove_tag,
+            set_note,
+            set_starred,
+            list_collections,
+            create_collection,
+            update_collection,
+            delete_collection,
+            get_collection_analyses,
+            list_projects,
+            create_project,
+            update_project,
+            delete_project,
+            set_active_project,
+            assign_project,
+            list_rules,
+            create_rule,
+            update_rule,
+            delete_rule,
+            get_rule_runs,
+            compare_analyses,
+            list_sessions,
+            open_link,
+            get_research_report,
+            get_receipt,
+            export_receipts,
+            get_code_snippet,
+            copy_code,
+            save_code,
+            get_table,
+            export_table,
+            translate_analysis,
+            get_screenshot_image,
+            get_analysis_frames,
+            list_annotations,
+            add_annotation,
+            update_annotation,
+            delete_annotation,
+            export_annotated_image,
+            run_cleanup_now,
+            get_storage_stats,
+            list_failed_analyses,
+            retry_analysis,
+            cancel_analysis,
+            get_usage_stats,
+            get_audit_log,
+            list_devices,
+            list_windows,
+            capture_region,
+            capture_window,
+            start_journal,
+            stop_journal,
+            pause_journal,
+            resume_journal,
+            get_journal_timeline,
+            rename_device,
+            set_device_enabled,
+            get_log_tail,
+            get_budget_status,
+            override_budget,
+            send_digest_now,
+            export_analysis,
+            reindex_spotlight,
+            export_history,
+            import_folder,
+            reanalyze,
+            test_notion_connection,
+            ask_followup,
+            ask_about_region,
+            get_conversation,
         ])
         .run(context)
         .expect("error while running tauri application");
@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Path as AxumPath, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::{
+    api_error::{ApiError, ErrorCode},
+    ScreenshotProcessor,
+};
+
+const MAX_NOTE_CHARS: usize = 5000;
+
+#[derive(Debug, Deserialize)]
+pub struct SetNoteRequest {
+    /// Empty to clear the note.
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetStarredRequest {
+    pub starred: bool,
+}
+
+/// The note as stored: trimmed, and `None` when there's nothing left.
+fn normalize_note(text: &str) -> Result<Option<String>> {
+    let text = text.trim();
+    if text.chars().count() > MAX_NOTE_CHARS {
+        return Err(anyhow!("A note is limited to {} characters", MAX_NOTE_CHARS));
+    }
+    Ok((!text.is_empty()).then(|| text.to_string()))
+}
+
+impl ScreenshotProcessor {
+    /// Replaces the user's note on an analysis; empty text clears it. Returns false if the
+    /// analysis doesn't exist.
+    pub fn set_note(&self, analysis_id: &str, text: &str) -> Result<bool> {
+        let note = normalize_note(text)?;
+        let found = self.store.set_note(analysis_id, note.as_deref())?;
+        if found {
+            info!("📝 Updated note on analysis {}", analysis_id);
+        }
+        Ok(found)
+    }
+
+    /// Returns false if the analysis doesn't exist.
+    pub fn set_starred(&self, analysis_id: &str, starred: bool) -> Result<bool> {
+        let found = self.store.set_starred(analysis_id, starred)?;
+        if found {
+            info!("⭐ {} analysis {}", if starred { "Starred" } else { "Unstarred" }, analysis_id);
+        }
+        Ok(found)
+    }
+}
+
+pub async fn handle_set_note(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Json(request): Json<SetNoteRequest>,
+) -> Result<StatusCode, ApiError> {
+    normalize_note(&request.note).map_err(|e| ApiError::new(ErrorCode::BadRequest, e.to_string()))?;
+    match processor.set_note(&analysis_id, &request.note) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(ApiError::not_found(format!("Analysis {} not found", analysis_id))),
+        Err(e) => {
+            error!("Failed to save note on analysis {}: {}", analysis_id, e);
+            Err(e.into())
+        }
+    }
+}
+
+pub async fn handle_set_starred(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Json(request): Json<SetStarredRequest>,
+) -> Result<StatusCode, ApiError> {
+    match processor.set_starred(&analysis_id, request.starred) {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(ApiError::not_found(format!("Analysis {} not found", analysis_id))),
+        Err(e) => {
+            error!("Failed to star analysis {}: {}", analysis_id, e);
+            Err(e.into())
+        }
+    }
+}
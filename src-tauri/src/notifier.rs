@@ -0,0 +1,194 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::{providers, ScreenshotProcessor};
+
+const DEFAULT_NTFY_SERVER: &str = "https://ntfy.sh";
+const PUSHOVER_API: &str = "https://api.pushover.net/1/messages.json";
+
+/// A short push notification.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub title: String,
+    pub message: String,
+    /// Opened when the notification is tapped.
+    pub url: Option<String>,
+}
+
+/// A service that delivers push notifications.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn notify(&self, notification: &Notification) -> Result<()>;
+}
+
+/// A push notification backend and its settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum PushNotifierConfig {
+    Ntfy {
+        /// Defaults to the public ntfy.sh server.
+        #[serde(default)]
+        server_url: Option<String>,
+        topic: String,
+        /// Access token for protected topics.
+        #[serde(default)]
+        token: Option<String>,
+        /// 1 (min) to 5 (max).
+        #[serde(default)]
+        priority: Option<u8>,
+    },
+    Pushover {
+        app_token: String,
+        user_key: String,
+        /// -2 (lowest) to 2 (emergency).
+        #[serde(default)]
+        priority: Option<i8>,
+    },
+    Gotify {
+        server_url: String,
+        app_token: String,
+        /// 0 to 10.
+        #[serde(default)]
+        priority: Option<u8>,
+    },
+}
+
+impl PushNotifierConfig {
+    pub fn build(&self, client: Client) -> Arc<dyn Notifier> {
+        match self.clone() {
+            PushNotifierConfig::Ntfy { server_url, topic, token, priority } => Arc::new(NtfyNotifier {
+                client,
+                server_url: server_url.unwrap_or_else(|| DEFAULT_NTFY_SERVER.to_string()),
+                topic,
+                token,
+                priority,
+            }),
+            PushNotifierConfig::Pushover { app_token, user_key, priority } => Arc::new(PushoverNotifier {
+                client,
+                app_token,
+                user_key,
+                priority,
+            }),
+            PushNotifierConfig::Gotify { server_url, app_token, priority } => Arc::new(GotifyNotifier {
+                client,
+                server_url,
+                app_token,
+                priority,
+            }),
+        }
+    }
+}
+
+pub struct NtfyNotifier {
+    client: Client,
+    server_url: String,
+    topic: String,
+    token: Option<String>,
+    priority: Option<u8>,
+}
+
+#[async_trait]
+impl Notifier for NtfyNotifier {
+    fn name(&self) -> &'static str {
+        "ntfy"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::json!({
+            "topic": self.topic,
+            "title": notification.title,
+            "message": notification.message,
+            "priority": self.priority,
+            "click": notification.url,
+        });
+        let mut request = self
+            .client
+            .post(self.server_url.trim_end_matches('/'))
+            .json(&body);
+        if let Some(ref token) = self.token {
+            request = request.bearer_auth(token);
+        }
+        providers::send(request, self.name()).await?;
+        Ok(())
+    }
+}
+
+pub struct PushoverNotifier {
+    client: Client,
+    app_token: String,
+    user_key: String,
+    priority: Option<i8>,
+}
+
+#[async_trait]
+impl Notifier for PushoverNotifier {
+    fn name(&self) -> &'static str {
+        "Pushover"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let body = serde_json::json!({
+            "token": self.app_token,
+            "user": self.user_key,
+            "title": notification.title,
+            "message": notification.message,
+            "priority": self.priority,
+            "url": notification.url,
+        });
+        providers::send(self.client.post(PUSHOVER_API).json(&body), self.name()).await?;
+        Ok(())
+    }
+}
+
+pub struct GotifyNotifier {
+    client: Client,
+    server_url: String,
+    app_token: String,
+    priority: Option<u8>,
+}
+
+#[async_trait]
+impl Notifier for GotifyNotifier {
+    fn name(&self) -> &'static str {
+        "Gotify"
+    }
+
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let mut body = serde_json::json!({
+            "title": notification.title,
+            "message": notification.message,
+            "priority": self.priority,
+        });
+        if let Some(ref url) = notification.url {
+            body["extras"] = serde_json::json!({ "client::notification": { "click": { "url": url } } });
+        }
+        let request = self
+            .client
+            .post(format!("{}/message", self.server_url.trim_end_matches('/')))
+            .header("X-Gotify-Key", &self.app_token)
+            .json(&body);
+        providers::send(request, self.name()).await?;
+        Ok(())
+    }
+}
+
+impl ScreenshotProcessor {
+    /// Sends `notification` through every configured push backend in the background.
+    pub(crate) fn push_notification(&self, notification: Notification) {
+        for notifier in self.notifiers.iter() {
+            let notifier = notifier.clone();
+            let notification = notification.clone();
+            tokio::spawn(async move {
+                if let Err(e) = notifier.notify(&notification).await {
+                    warn!("Failed to send {} notification: {}", notifier.name(), e);
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::{providers, AnalysisData, ScreenshotProcessor};
+
+const NOTION_API: &str = "https://api.notion.com/v1";
+const NOTION_VERSION: &str = "2022-06-28";
+/// Notion rejects rich text longer than this per text object.
+const TEXT_LIMIT: usize = 2000;
+
+/// Names of the database properties each analysis field is written to; empty skips the field.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotionProperties {
+    /// Title property, filled with the start of the summary.
+    pub title: String,
+    pub date: String,
+    /// Select property.
+    pub source: String,
+    /// Select property.
+    pub content_type: String,
+    pub url: String,
+    /// Multi-select property.
+    pub topics: String,
+}
+
+impl Default for NotionProperties {
+    fn default() -> Self {
+        Self {
+            title: "Name".to_string(),
+            date: "Date".to_string(),
+            source: "Source".to_string(),
+            content_type: "Content Type".to_string(),
+            url: "URL".to_string(),
+            topics: "Topics".to_string(),
+        }
+    }
+}
+
+impl NotionProperties {
+    /// Configured property names with the Notion type each must have.
+    fn expected(&self) -> Vec<(&str, &'static str)> {
+        [
+            (self.title.as_str(), "title"),
+            (self.date.as_str(), "date"),
+            (self.source.as_str(), "select"),
+            (self.content_type.as_str(), "select"),
+            (self.url.as_str(), "url"),
+            (self.topics.as_str(), "multi_select"),
+        ]
+        .into_iter()
+        .filter(|(name, _)| !name.is_empty())
+        .collect()
+    }
+}
+
+/// Result of checking a database against the property mapping.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotionDatabaseCheck {
+    pub title: String,
+    /// Every property in the database, by name, with its Notion type.
+    pub properties: HashMap<String, String>,
+    /// Mapped properties that are missing or have the wrong type.
+    pub problems: Vec<String>,
+}
+
+fn notion_request(client: &Client, method: reqwest::Method, path: &str, token: &str) -> reqwest::RequestBuilder {
+    client
+        .request(method, format!("{}/{}", NOTION_API, path))
+        .bearer_auth(token)
+        .header("Notion-Version", NOTION_VERSION)
+}
+
+fn text(content: &str) -> Value {
+    let content: String = content.chars().take(TEXT_LIMIT).collect();
+    json!([{ "type": "text", "text": { "content": content } }])
+}
+
+/// Select option names can't contain commas.
+fn option(name: &str) -> Value {
+    json!({ "name": name.replace(',', " ").chars().take(100).collect::<String>() })
+}
+
+/// Looks up a database and checks it has the mapped properties.
+pub async fn check_database(
+    client: &Client,
+    token: &str,
+    database_id: &str,
+    mapping: &NotionProperties,
+) -> Result<NotionDatabaseCheck> {
+    let request = notion_request(client, reqwest::Method::GET, &format!("databases/{}", database_id), token);
+    let database: Value = providers::send(request, "Notion").await?.json().await?;
+
+    let title = database["title"]
+        .as_array()
+        .map(|parts| parts.iter().filter_map(|p| p["plain_text"].as_str()).collect::<String>())
+        .unwrap_or_default();
+    let properties: HashMap<String, String> = database["properties"]
+        .as_object()
+        .map(|props| {
+            props
+                .iter()
+                .map(|(name, prop)| (name.clone(), prop["type"].as_str().unwrap_or_default().to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let problems = mapping
+        .expected()
+        .into_iter()
+        .filter_map(|(name, kind)| match properties.get(name) {
+            None => Some(format!("No property named \"{}\"", name)),
+            Some(actual) if actual != kind => Some(format!("\"{}\" is a {} property, expected {}", name, actual, kind)),
+            Some(_) => None,
+        })
+        .collect();
+
+    Ok(NotionDatabaseCheck { title, properties, problems })
+}
+
+impl ScreenshotProcessor {
+    /// Adds a completed analysis to the Notion database in the background.
+    pub(crate) fn export_to_notion(&self, analysis_id: &str, analysis: &AnalysisData) {
+        let (Some(token), Some(database_id)) = (
+            self.config.notion_token.clone(),
+            self.config.notion_database_id.clone(),
+        ) else {
+            return;
+        };
+
+        let page = self.notion_page(&database_id, analysis_id, analysis);
+        let client = self.client.clone();
+        let analysis_id = analysis_id.to_string();
+        tokio::spawn(async move {
+            let request = notion_request(&client, reqwest::Method::POST, "pages", &token).json(&page);
+            match providers::send(request, "Notion").await {
+                Ok(_) => info!("🗒️ Added analysis {} to Notion", analysis_id),
+                Err(e) => warn!("Failed to add analysis {} to Notion: {}", analysis_id, e),
+            }
+        });
+    }
+
+    fn notion_page(&self, database_id: &str, analysis_id: &str, analysis: &AnalysisData) -> Value {
+        let mapping = &self.config.notion_properties;
+        let content = &analysis.content_analysis;
+        let title = analysis.brief_summary.lines().next().unwrap_or("Screenshot");
+
+        let mut properties = serde_json::Map::new();
+        let mut set = |name: &str, value: Value| {
+            if !name.is_empty() {
+                properties.insert(name.to_string(), value);
+            }
+        };
+        set(&mapping.title, json!({ "title": text(title) }));
+        set(&mapping.date, json!({ "date": { "start": analysis.timestamp.to_rfc3339() } }));
+        set(&mapping.source, json!({ "select": option(&analysis.source) }));
+        set(&mapping.content_type, json!({ "select": option(&content.content_type) }));
+        if let Some(ref url) = content.webpage_url {
+            set(&mapping.url, json!({ "url": url }));
+        }
+        set(
+            &mapping.topics,
+            json!({ "multi_select": content.research_topics.iter().map(|t| option(t)).collect::<Vec<_>>() }),
+        );
+
+        let mut children = vec![json!({
+            "object": "block",
+            "type": "paragraph",
+            "paragraph": { "rich_text": text(&analysis.brief_summary) },
+        })];
+        if !content.user_intent.trim().is_empty() {
+            children.push(json!({
+                "object": "block",
+                "type": "quote",
+                "quote": { "rich_text": text(&content.user_intent) },
+            }));
+        }
+        // Notion only embeds images it can fetch from a URL
+        if let Some(url) = self.image_url(analysis_id) {
+            children.push(json!({
+                "object": "block",
+                "type": "image",
+                "image": { "type": "external", "external": { "url": url } },
+            }));
+        }
+
+        json!({
+            "parent": { "database_id": database_id },
+            "properties": properties,
+            "children": children,
+        })
+    }
+}
@@ -0,0 +1,742 @@
+use axum::response::Json as ResponseJson;
+use serde_json::{json, Value};
+
+/// A `$ref` to one of the schemas under `components`.
+fn schema(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn json_content(name: &str) -> Value {
+    json!({ "application/json": { "schema": schema(name) } })
+}
+
+/// A response documented by its schema.
+fn response(description: &str, name: &str) -> Value {
+    json!({ "description": description, "content": json_content(name) })
+}
+
+/// An error response; every error carries an `ErrorBody`.
+fn error(description: &str) -> Value {
+    response(description, "ErrorBody")
+}
+
+fn path_id() -> Value {
+    json!({ "name": "id", "in": "path", "required": true, "schema": { "type": "string" } })
+}
+
+fn query(name: &str, kind: &str, description: &str) -> Value {
+    json!({ "name": name, "in": "query", "required": false, "description": description, "schema": { "type": kind } })
+}
+
+/// A downloaded file, sent with `Content-Disposition: attachment`.
+fn file(description: &str, media_types: &[&str]) -> Value {
+    let content: serde_json::Map<String, Value> = media_types
+        .iter()
+        .map(|media_type| (media_type.to_string(), json!({})))
+        .collect();
+    json!({ "description": description, "content": content })
+}
+
+fn paths() -> Value {
+    let mut paths = server_paths();
+    if let (Some(paths), Value::Object(analysis_paths)) = (paths.as_object_mut(), analysis_paths()) {
+        paths.extend(analysis_paths);
+    }
+    paths
+}
+
+fn server_paths() -> Value {
+    json!({
+        "/health": {
+            "get": {
+                "summary": "Liveness check; needs no API key",
+                "security": [],
+                "responses": { "200": { "description": "The server is up" } }
+            }
+        },
+        "/openapi.json": {
+            "get": {
+                "summary": "This document; needs no API key",
+                "security": [],
+                "responses": { "200": { "description": "The OpenAPI description", "content": { "application/json": {} } } }
+            }
+        },
+        "/pair": {
+            "post": {
+                "summary": "Exchange a pairing code from the desktop app for the server's address and API key",
+                "security": [],
+                "requestBody": { "required": true, "content": json_content("PairRequest") },
+                "responses": {
+                    "200": response("How to reach the server", "PairResponse"),
+                    "401": error("`unauthorized`: invalid or expired pairing code"),
+                    "429": error("`rate_limited`")
+                }
+            }
+        },
+        "/screenshot": {
+            "post": {
+                "summary": "Analyze a screenshot",
+                "parameters": [{
+                    "name": "Idempotency-Key", "in": "header", "required": false,
+                    "description": "Replays the earlier response for a retried upload instead of analyzing it again",
+                    "schema": { "type": "string" }
+                }],
+                "requestBody": { "required": true, "content": json_content("ScreenshotRequest") },
+                "responses": {
+                    "200": response("The analysis", "ProcessingResponse"),
+                    "202": error("`queued_offline`: the provider is unreachable or processing is paused; the screenshot was saved and will be analyzed later, so don't send it again"),
+                    "401": error("`unauthorized`: missing or wrong API key"),
+                    "402": error("`budget_exceeded`: the monthly LLM budget is spent"),
+                    "409": error("`cancelled`: the analysis was cancelled while running"),
+                    "413": error("`payload_too_large`: the upload is over the size limit"),
+                    "422": error("`invalid_image`, `sensitive_content` or `excluded`: the image can't be or wasn't analyzed"),
+                    "429": error("`queue_full` or `rate_limited`: try again later"),
+                    "502": error("`provider_error`: the AI provider rejected the request or failed"),
+                    "503": error("`paused`: processing is paused and the screenshot was dropped"),
+                    "500": error("`internal`")
+                }
+            }
+        },
+        "/screenshots/batch": {
+            "post": {
+                "summary": "Analyze several screenshots and describe them as a set",
+                "requestBody": { "required": true, "content": json_content("BatchRequest") },
+                "responses": {
+                    "200": response("One result per image, failures included", "BatchResponse"),
+                    "400": error("`bad_request`")
+                }
+            }
+        },
+        "/status": {
+            "get": {
+                "summary": "Server status, queue and configuration flags",
+                "responses": { "200": response("Current status", "ServerStatus") }
+            }
+        },
+        "/stats": {
+            "get": {
+                "summary": "LLM token usage and estimated cost",
+                "responses": { "200": { "description": "Usage totals" } }
+            }
+        },
+        "/events": {
+            "get": {
+                "summary": "Server-sent events for each step of every analysis, or of one",
+                "parameters": [query("analysis_id", "string", "Only events for this analysis")],
+                "responses": {
+                    "200": {
+                        "description": "An event stream; each event is named after its `stage` and carries a `ProgressEvent`",
+                        "content": { "text/event-stream": { "schema": schema("ProgressEvent") } }
+                    }
+                }
+            }
+        },
+        "/analyses": {
+            "get": {
+                "summary": "List stored analyses, newest first by default",
+                "parameters": [
+                    query("page", "integer", "1-based page number"),
+                    query("page_size", "integer", "At most 100"),
+                    query("tag", "string", "Only analyses with this tag"),
+                    query("source", "string", "e.g. `iphone`, `desktop` or `clipboard`"),
+                    query("content_type", "string", "e.g. `article`"),
+                    query("from", "string", "RFC 3339 time; only analyses taken at or after it"),
+                    query("to", "string", "RFC 3339 time; only analyses taken before it"),
+                    query("starred", "boolean", "Only starred analyses if true, only unstarred ones if false"),
+                    query("project", "string", "Only analyses in the project with this id"),
+                    query("sort", "string", "`newest`, `oldest`, `largest` or `smallest`"),
+                    query("include_images", "boolean", "Include each image, base64-encoded")
+                ],
+                "responses": {
+                    "200": response("A page of analyses", "AnalysisPage"),
+                    "400": error("`bad_request`: invalid filter")
+                }
+            }
+        },
+        "/analyses/search": {
+            "get": {
+                "summary": "Full-text search over summaries, topics, notes, filenames and the text in each screenshot",
+                "parameters": [
+                    { "name": "q", "in": "query", "required": true, "schema": { "type": "string" } },
+                    query("limit", "integer", "Defaults to 20"),
+                    query("project", "string", "Only analyses in the project with this id"),
+                    query("include_images", "boolean", "Include each image, base64-encoded")
+                ],
+                "responses": {
+                    "200": { "description": "Matches, best first" },
+                    "400": error("`bad_request`: empty query")
+                }
+            }
+        },
+        "/analyses/export": {
+            "get": {
+                "summary": "Download stored analyses as a file",
+                "parameters": [
+                    query("format", "string", "`json` (default) or `csv`"),
+                    query("from", "string", "RFC 3339 time; only analyses taken at or after it"),
+                    query("to", "string", "RFC 3339 time; only analyses taken before it"),
+                    query("include_images", "boolean", "Bundle the data file and every image into a zip archive"),
+                    query("project", "string", "Only analyses in the project with this id")
+                ],
+                "responses": {
+                    "200": file("The export", &["application/json", "text/csv", "application/zip"]),
+                    "400": error("`bad_request`: invalid option"),
+                    "500": error("`internal`")
+                }
+            }
+        },
+        "/audit": {
+            "get": {
+                "summary": "Requests the server has answered, newest first",
+                "parameters": [
+                    query("since", "string", "RFC 3339 time"),
+                    query("until", "string", "RFC 3339 time"),
+                    query("client_ip", "string", "Only requests from this address"),
+                    query("device_name", "string", "Only requests from this device"),
+                    query("path", "string", "Only requests whose path starts with this, e.g. `/screenshot`"),
+                    query("errors_only", "boolean", "Only requests answered with a 4xx or 5xx status"),
+                    query("limit", "integer", "At most 1000"),
+                    query("offset", "integer", "Entries to skip")
+                ],
+                "responses": {
+                    "200": {
+                        "description": "Matching entries",
+                        "content": { "application/json": { "schema": { "type": "array", "items": schema("AuditEntry") } } }
+                    },
+                    "400": error("`bad_request`: invalid filter"),
+                    "500": error("`internal`")
+                }
+            }
+        },
+        "/journal": {
+            "get": {
+                "summary": "The journal's captures for one local day",
+                "parameters": [query("date", "string", "Local date as `YYYY-MM-DD`; today if omitted")],
+                "responses": {
+                    "200": {
+                        "description": "The day's entries, oldest first",
+                        "content": { "application/json": { "schema": { "type": "array", "items": schema("JournalEntry") } } }
+                    },
+                    "400": error("`bad_request`: invalid date"),
+                    "500": error("`internal`")
+                }
+            }
+        },
+        "/tags": {
+            "get": {
+                "summary": "Every tag in use, with how many analyses have it",
+                "responses": {
+                    "200": {
+                        "description": "Tags, most used first",
+                        "content": { "application/json": { "schema": { "type": "array", "items": schema("TagCount") } } }
+                    },
+                    "500": error("`internal`")
+                }
+            }
+        },
+        "/compare": {
+            "post": {
+                "summary": "Describe what changed between two stored screenshots",
+                "requestBody": { "required": true, "content": json_content("CompareRequest") },
+                "responses": {
+                    "200": response("The differences", "AnalysisComparison"),
+                    "400": error("`bad_request`: both ids are the same"),
+                    "402": error("`budget_exceeded`"),
+                    "404": error("`not_found`: no analysis with one of the ids"),
+                    "502": error("`provider_error`")
+                }
+            }
+        },
+    })
+}
+
+/// Paths for one stored analysis, kept apart as a single `json!` for every path passes the macro's
+/// recursion limit.
+fn analysis_paths() -> Value {
+    json!({
+        "/analysis/{id}": {
+            "get": {
+                "summary": "One stored analysis",
+                "parameters": [path_id(), query("include_image", "boolean", "Include the image, base64-encoded")],
+                "responses": {
+                    "200": response("The analysis", "AnalysisRecord"),
+                    "404": error("`not_found`")
+                }
+            },
+            "delete": {
+                "summary": "Delete an analysis and its image",
+                "parameters": [path_id()],
+                "responses": {
+                    "204": { "description": "Deleted" },
+                    "404": error("`not_found`")
+                }
+            }
+        },
+        "/analysis/{id}/image": {
+            "get": {
+                "summary": "The screenshot an analysis was made from, as uploaded",
+                "parameters": [path_id()],
+                "responses": {
+                    "200": { "description": "The image bytes", "content": { "image/*": {} } },
+                    "404": error("`not_found`")
+                }
+            }
+        },
+        "/analysis/{id}/table": {
+            "get": {
+                "summary": "The tables of data in a screenshot, extracted on first request",
+                "parameters": [path_id(), query("format", "string", "`json` (default) or `csv`")],
+                "responses": {
+                    "200": {
+                        "description": "The tables, as an attachment",
+                        "content": {
+                            "application/json": { "schema": schema("ExtractedTables") },
+                            "text/csv": {}
+                        }
+                    },
+                    "404": error("`not_found`"),
+                    "500": error("`internal`")
+                }
+            }
+        },
+        "/analysis/{id}/chat": {
+            "post": {
+                "summary": "Ask a follow-up question about an analysis",
+                "parameters": [path_id()],
+                "requestBody": { "required": true, "content": json_content("ChatQuestion") },
+                "responses": {
+                    "200": response("The answer", "ChatTurn"),
+                    "400": error("`bad_request`: empty question"),
+                    "404": error("`not_found`"),
+                    "502": error("`provider_error`")
+                }
+            }
+        },
+        "/analysis/{id}/region": {
+            "post": {
+                "summary": "Ask about one area of a screenshot; only that area is sent to the model",
+                "parameters": [path_id()],
+                "requestBody": { "required": true, "content": json_content("RegionQuestion") },
+                "responses": {
+                    "200": response("The answer", "ChatTurn"),
+                    "400": error("`bad_request`: empty or too long question"),
+                    "404": error("`not_found`"),
+                    "422": error("`invalid_image`: the area is too small or outside the image"),
+                    "502": error("`provider_error`")
+                }
+            }
+        },
+        "/analysis/{id}/tags": {
+            "post": {
+                "summary": "Add a tag to an analysis",
+                "parameters": [path_id()],
+                "requestBody": { "required": true, "content": json_content("AddTagRequest") },
+                "responses": {
+                    "204": { "description": "Added" },
+                    "400": error("`bad_request`: invalid tag"),
+                    "404": error("`not_found`")
+                }
+            }
+        },
+        "/analysis/{id}/tags/{tag}": {
+            "delete": {
+                "summary": "Remove a tag from an analysis",
+                "parameters": [
+                    path_id(),
+                    { "name": "tag", "in": "path", "required": true, "schema": { "type": "string" } }
+                ],
+                "responses": {
+                    "204": { "description": "Removed" },
+                    "400": error("`bad_request`: invalid tag"),
+                    "404": error("`not_found`: no such analysis, or it doesn't have the tag")
+                }
+            }
+        },
+        "/image/{id}": {
+            "get": {
+                "summary": "A stored screenshot's image",
+                "parameters": [path_id(), query("variant", "string", "`full` (default), `thumb` or `annotated`")],
+                "responses": {
+                    "200": { "description": "The image bytes", "content": { "image/*": {} } },
+                    "404": error("`not_found`")
+                }
+            }
+        },
+        "/image/{id}/frames/{index}": {
+            "get": {
+                "summary": "One frame of a stored screen recording or animated GIF, counting from 0",
+                "parameters": [
+                    path_id(),
+                    { "name": "index", "in": "path", "required": true, "schema": { "type": "integer" } }
+                ],
+                "responses": {
+                    "200": { "description": "The frame as PNG", "content": { "image/png": {} } },
+                    "404": error("`not_found`: no such analysis or frame")
+                }
+            }
+        },
+        "/analysis/{id}/reanalyze": {
+            "post": {
+                "summary": "Analyze a stored screenshot again as a new revision",
+                "parameters": [path_id()],
+                "responses": {
+                    "200": response("The new revision", "ProcessingResponse"),
+                    "404": error("`not_found`"),
+                    "402": error("`budget_exceeded`"),
+                    "429": error("`queue_full`")
+                }
+            }
+        },
+        "/analysis/{id}/note": {
+            "put": {
+                "summary": "Replace the user's note on an analysis; an empty note clears it",
+                "parameters": [path_id()],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "required": ["note"],
+                        "properties": { "note": { "type": "string", "maxLength": 5000 } }
+                    } } }
+                },
+                "responses": {
+                    "204": { "description": "Saved" },
+                    "400": error("`bad_request`: note too long"),
+                    "404": error("`not_found`")
+                }
+            }
+        },
+        "/analysis/{id}/starred": {
+            "put": {
+                "summary": "Star or unstar an analysis",
+                "parameters": [path_id()],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "required": ["starred"],
+                        "properties": { "starred": { "type": "boolean" } }
+                    } } }
+                },
+                "responses": {
+                    "204": { "description": "Saved" },
+                    "404": error("`not_found`")
+                }
+            }
+        },
+        "/analysis/{id}/inflight": {
+            "delete": {
+                "summary": "Cancel an analysis that is still running",
+                "parameters": [path_id()],
+                "responses": {
+                    "204": { "description": "Cancelled" },
+                    "404": error("`not_found`: no such analysis is running")
+                }
+            }
+        }
+    })
+}
+
+fn schemas() -> Value {
+    json!({
+        "ErrorBody": {
+            "type": "object",
+            "required": ["error"],
+            "properties": {
+                "error": {
+                    "type": "object",
+                    "required": ["code", "message"],
+                    "properties": {
+                        "code": {
+                            "type": "string",
+                            "enum": [
+                                "bad_request", "unauthorized", "forbidden", "not_found", "payload_too_large",
+                                "invalid_image", "sensitive_content", "excluded", "cancelled", "rate_limited", "queue_full",
+                                "budget_exceeded", "queued_offline", "paused", "provider_error", "unavailable", "internal"
+                            ]
+                        },
+                        "message": { "type": "string" }
+                    }
+                }
+            }
+        },
+        "ScreenshotMetadata": {
+            "type": "object",
+            "properties": {
+                "source": { "type": "string", "description": "e.g. `iOS`, `desktop` or `clipboard`" },
+                "app": { "type": "string" },
+                "window_title": { "type": "string" },
+                "filename": { "type": "string" },
+                "file_path": { "type": "string" },
+                "location": { "type": "string" },
+                "auto_detected": { "type": "boolean" },
+                "captured_at": { "type": "string", "format": "date-time" },
+                "local_only": { "type": "boolean", "description": "Analyze with the local model only" },
+                "recording_frames": { "type": "integer", "description": "Set by the server for the storyboard of a screen recording or animated GIF" }
+            }
+        },
+        "ScreenshotRequest": {
+            "type": "object",
+            "required": ["image"],
+            "properties": {
+                "image": { "type": "string", "description": "Base64 image, animated GIF or MP4/QuickTime screen recording, optionally as a data URL" },
+                "metadata": schema("ScreenshotMetadata"),
+                "idempotency_key": { "type": "string", "description": "Same as the Idempotency-Key header" }
+            }
+        },
+        "ProcessingResponse": {
+            "type": "object",
+            "required": ["success", "timestamp"],
+            "properties": {
+                "success": { "type": "boolean" },
+                "summary": { "type": "string" },
+                "analysis_id": { "type": "string" },
+                "timestamp": { "type": "string", "format": "date-time" },
+                "follow_up_available": { "type": "boolean" },
+                "source": { "type": "string" },
+                "error": { "type": "string", "description": "Set only on failed batch items" },
+                "error_code": { "type": "string", "description": "Set only on failed batch items; the same codes as error responses" },
+                "retries": { "type": "integer" },
+                "failed_over": { "type": "boolean" },
+                "usage": { "type": "object" },
+                "duplicate": { "type": "boolean" },
+                "timings": { "type": "object" }
+            }
+        },
+        "BatchRequest": {
+            "type": "object",
+            "required": ["images"],
+            "properties": { "images": { "type": "array", "items": schema("ScreenshotRequest") } }
+        },
+        "BatchResponse": {
+            "type": "object",
+            "properties": {
+                "success": { "type": "boolean" },
+                "results": { "type": "array", "items": schema("ProcessingResponse") },
+                "synthesis": { "type": "string" },
+                "timestamp": { "type": "string", "format": "date-time" }
+            }
+        },
+        "ServerStatus": {
+            "type": "object",
+            "properties": {
+                "server": { "type": "string" },
+                "status": { "type": "string" },
+                "local_ip": { "type": "string" },
+                "port": { "type": "integer" },
+                "total_requests": { "type": "integer" },
+                "queue_depth": { "type": "integer" },
+                "outbox_pending": { "type": "integer" },
+                "tls_fingerprint": { "type": "string" },
+                "paused": { "type": "boolean", "description": "New screenshots are held back instead of analyzed" }
+            }
+        },
+        "AnalysisRecord": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string" },
+                "timestamp": { "type": "string", "format": "date-time" },
+                "source": { "type": "string" },
+                "summary": { "type": "string" },
+                "content_analysis": { "type": "object" },
+                "metadata": schema("ScreenshotMetadata"),
+                "media_type": { "type": "string" },
+                "size_bytes": { "type": "integer" },
+                "image_base64": { "type": "string" },
+                "revision_of": { "type": "string" },
+                "note": { "type": "string", "description": "Written by the user" },
+                "starred": { "type": "boolean" },
+                "project_id": { "type": "string" }
+            }
+        },
+        "AnalysisPage": {
+            "type": "object",
+            "properties": {
+                "analyses": { "type": "array", "items": schema("AnalysisRecord") },
+                "page": { "type": "integer" },
+                "page_size": { "type": "integer" },
+                "total": { "type": "integer" }
+            }
+        },
+        "ProgressEvent": {
+            "type": "object",
+            "required": ["analysis_id", "stage", "timestamp"],
+            "description": "The remaining fields depend on `stage`",
+            "properties": {
+                "analysis_id": { "type": "string" },
+                "stage": {
+                    "type": "string",
+                    "enum": [
+                        "received", "preprocessed", "sensitive_content", "summary_token", "summary_done",
+                        "analysis_done", "failed"
+                    ]
+                },
+                "timestamp": { "type": "string", "format": "date-time" },
+                "source": { "type": "string", "description": "`received`" },
+                "media_type": { "type": "string", "description": "`preprocessed`" },
+                "size_bytes": { "type": "integer", "description": "`preprocessed`" },
+                "kinds": { "type": "array", "items": { "type": "string" }, "description": "`sensitive_content`" },
+                "blocked": { "type": "boolean", "description": "`sensitive_content`: the image wasn't analyzed" },
+                "token": { "type": "string", "description": "`summary_token`" },
+                "summary": { "type": "string", "description": "`summary_done`" },
+                "error": { "type": "string", "description": "`failed`" }
+            }
+        },
+        "AuditEntry": {
+            "type": "object",
+            "properties": {
+                "timestamp": { "type": "string", "format": "date-time" },
+                "client_ip": { "type": "string" },
+                "device_name": { "type": "string" },
+                "method": { "type": "string" },
+                "path": { "type": "string" },
+                "status": { "type": "integer" },
+                "request_bytes": { "type": "integer", "description": "From `Content-Length`, when the client sent one" },
+                "latency_ms": { "type": "integer" },
+                "input_tokens": { "type": "integer" },
+                "output_tokens": { "type": "integer" }
+            }
+        },
+        "JournalEntry": {
+            "type": "object",
+            "properties": {
+                "analysis_id": { "type": "string" },
+                "timestamp": { "type": "string", "format": "date-time" },
+                "app": { "type": "string" },
+                "summary": { "type": "string" }
+            }
+        },
+        "TagCount": {
+            "type": "object",
+            "properties": {
+                "tag": { "type": "string" },
+                "count": { "type": "integer" }
+            }
+        },
+        "AddTagRequest": {
+            "type": "object",
+            "required": ["tag"],
+            "properties": { "tag": { "type": "string", "description": "Stored lowercase, with spaces as hyphens and without a leading `#`" } }
+        },
+        "CompareRequest": {
+            "type": "object",
+            "required": ["id_a", "id_b"],
+            "properties": {
+                "id_a": { "type": "string" },
+                "id_b": { "type": "string" }
+            }
+        },
+        "AnalysisComparison": {
+            "type": "object",
+            "properties": {
+                "id_a": { "type": "string" },
+                "id_b": { "type": "string" },
+                "summary": { "type": "string" },
+                "differences": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "kind": { "type": "string", "enum": ["added", "removed", "changed"] },
+                            "area": { "type": "string", "description": "The part of the screen it concerns" },
+                            "description": { "type": "string" }
+                        }
+                    }
+                },
+                "unchanged": { "type": "array", "items": { "type": "string" } },
+                "compared_at": { "type": "string", "format": "date-time" }
+            }
+        },
+        "PairRequest": {
+            "type": "object",
+            "required": ["token"],
+            "properties": {
+                "token": { "type": "string", "description": "The pairing code shown by the desktop app" },
+                "device_name": { "type": "string", "description": "Shown in the log, e.g. \"Work iPhone\"" }
+            }
+        },
+        "PairResponse": {
+            "type": "object",
+            "properties": {
+                "endpoint_url": { "type": "string" },
+                "api_key": { "type": "string" },
+                "device_id": { "type": "string", "description": "Send as `X-Device-Id`" }
+            }
+        },
+        "ExtractedTables": {
+            "type": "object",
+            "properties": {
+                "analysis_id": { "type": "string" },
+                "tables": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "title": { "type": "string" },
+                            "headers": { "type": "array", "items": { "type": "string" } },
+                            "rows": { "type": "array", "items": { "type": "array", "items": { "type": "string" } } }
+                        }
+                    }
+                },
+                "extracted_at": { "type": "string", "format": "date-time" }
+            }
+        },
+        "ChatQuestion": {
+            "type": "object",
+            "required": ["question"],
+            "properties": { "question": { "type": "string" } }
+        },
+        "RegionQuestion": {
+            "type": "object",
+            "required": ["rect", "question"],
+            "properties": {
+                "rect": {
+                    "type": "object",
+                    "description": "In pixels of the full image; clipped to it",
+                    "required": ["x", "y", "width", "height"],
+                    "properties": {
+                        "x": { "type": "integer", "minimum": 0 },
+                        "y": { "type": "integer", "minimum": 0 },
+                        "width": { "type": "integer", "minimum": 0 },
+                        "height": { "type": "integer", "minimum": 0 }
+                    }
+                },
+                "question": { "type": "string" }
+            }
+        },
+        "ChatTurn": {
+            "type": "object",
+            "properties": {
+                "role": { "type": "string", "enum": ["user", "assistant"] },
+                "content": { "type": "string" },
+                "timestamp": { "type": "string", "format": "date-time" }
+            }
+        }
+    })
+}
+
+/// The OpenAPI 3.1 description of the HTTP API.
+pub fn spec() -> Value {
+    json!({
+        "openapi": "3.1.0",
+        "info": {
+            "title": "Screenshot AI Server",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Analyzes screenshots sent from phones and desktops. Errors use the `ErrorBody` schema, with a machine-readable `code`. Endpoints that need an API key can also answer 401 `unauthorized`, 403 `forbidden` for a revoked device and 429 `rate_limited`."
+        },
+        "security": [{ "bearer": [] }, { "apiKey": [] }],
+        "paths": paths(),
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer" },
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-API-Key" }
+            }
+        }
+    })
+}
+
+/// `GET /openapi.json`, public so API clients can be generated without a key.
+pub async fn handle_openapi() -> ResponseJson<Value> {
+    ResponseJson(spec())
+}
@@ -0,0 +1,161 @@
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+use thiserror::Error;
+use tracing::{debug, info, warn};
+
+use crate::{
+    budget::BudgetExceeded,
+    events::{emit_event, OutboxChanged, UiEvent},
+    privacy, retry, ScreenshotMetadata, ScreenshotProcessor,
+};
+
+/// How often queued screenshots are retried while the network is down.
+const OUTBOX_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Error)]
+#[error("Network unavailable; the screenshot was saved and will be analyzed once connectivity returns")]
+pub struct QueuedOffline;
+
+/// A screenshot saved to disk because it could not be analyzed when it arrived.
+#[derive(Debug, Clone)]
+pub struct OutboxItem {
+    pub id: i64,
+    pub image_base64: String,
+    pub metadata: Option<ScreenshotMetadata>,
+    pub queued_at: DateTime<Utc>,
+    pub attempts: u32,
+}
+
+impl ScreenshotProcessor {
+    /// Stores a screenshot for a later retry, returning the error to report to the caller.
+    pub(crate) fn enqueue_offline(
+        &self,
+        image_base64: &str,
+        metadata: &Option<ScreenshotMetadata>,
+        error: anyhow::Error,
+    ) -> anyhow::Error {
+        match self.store.outbox_push(image_base64, metadata) {
+            Ok(id) => {
+                warn!("📮 Analysis failed ({}), queued screenshot {} for retry", error, id);
+                self.emit_outbox_changed();
+                error.context(QueuedOffline)
+            }
+            Err(e) => {
+                warn!("Failed to queue screenshot for retry: {}", e);
+                error
+            }
+        }
+    }
+
+    /// Screenshots waiting in the outbox.
+    pub fn outbox_pending(&self) -> usize {
+        self.store.outbox_len().unwrap_or(0)
+    }
+
+    pub(crate) fn emit_outbox_changed(&self) {
+        emit_event(UiEvent::OutboxChanged(OutboxChanged {
+            pending: self.outbox_pending(),
+        }));
+    }
+
+    /// Periodically retries queued screenshots until the outbox is empty.
+    pub fn spawn_outbox_worker(&self) -> tokio::task::JoinHandle<()> {
+        let processor = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(OUTBOX_RETRY_INTERVAL);
+            loop {
+                interval.tick().await;
+                processor.drain_outbox().await;
+            }
+        })
+    }
+
+    pub(crate) async fn drain_outbox(&self) {
+        loop {
+            // Queued screenshots wait out a pause too
+            if self.is_paused() {
+                return;
+            }
+
+            let item = match self.store.outbox_next() {
+                Ok(Some(item)) => item,
+                Ok(None) => return,
+                Err(e) => {
+                    warn!("Failed to read outbox: {}", e);
+                    return;
+                }
+            };
+
+            // Screenshots queued during a pause haven't been through the content filter yet
+            let metadata = match self.screen_content(&item.image_base64, item.metadata.clone()).await {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    warn!("Dropping queued screenshot {}: {}", item.id, e);
+                    self.finish_outbox_item(&item);
+                    continue;
+                }
+            };
+            let local;
+            let processor = if privacy::wants_local_only(&metadata) && !self.config.privacy_mode {
+                local = match self.local_only() {
+                    Ok(local) => local,
+                    Err(e) => {
+                        warn!("Outbox can't analyze screenshot {} locally: {}", item.id, e);
+                        return;
+                    }
+                };
+                &local
+            } else {
+                self
+            };
+
+            match processor.process_tracked(&item.image_base64, metadata.clone()).await {
+                Ok(result) => {
+                    info!(
+                        "📮 Analyzed queued screenshot {} (queued {}, {} earlier attempts)",
+                        item.id, item.queued_at, item.attempts
+                    );
+                    self.finish_outbox_item(&item);
+                    if let Some(metadata) = metadata.as_ref().filter(|m| m.auto_detected == Some(true)) {
+                        emit_recovered(&result, metadata, &item.image_base64);
+                        processor.notify_analysis_ready(&result, metadata.source.as_deref().unwrap_or_default());
+                    }
+                }
+                Err(e) if e.is::<BudgetExceeded>() => {
+                    // Keep the screenshot until the budget allows analyzing it
+                    debug!("Outbox paused: {}", e);
+                    return;
+                }
+                Err(e) if retry::is_transient(&e) => {
+                    debug!("Outbox retry for screenshot {} failed: {}", item.id, e);
+                    let _ = self.store.outbox_record_failure(item.id, &e.to_string());
+                    // Still offline; try again on the next tick
+                    return;
+                }
+                Err(e) => {
+                    warn!("Dropping queued screenshot {}: {}", item.id, e);
+                    self.finish_outbox_item(&item);
+                }
+            }
+        }
+    }
+
+    fn finish_outbox_item(&self, item: &OutboxItem) {
+        if let Err(e) = self.store.outbox_remove(item.id) {
+            warn!("Failed to remove screenshot {} from outbox: {}", item.id, e);
+        }
+        self.emit_outbox_changed();
+    }
+}
+
+/// Shows an automatically captured screenshot in the gallery once it has finally been analyzed.
+fn emit_recovered(result: &crate::ProcessingResponse, metadata: &ScreenshotMetadata, image_base64: &str) {
+    let Ok(bytes) = general_purpose::STANDARD.decode(image_base64) else {
+        return;
+    };
+    let media_type = image::guess_format(&bytes)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("image/png");
+    crate::events::emit_screenshot_processed(result, metadata, bytes.len(), media_type, image_base64);
+}
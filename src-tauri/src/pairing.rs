@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use chrono::{DateTime, Duration, Utc};
+use qrcode::{render::svg, QrCode};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{auth, ScreenshotProcessor};
+
+/// How long a pairing code can be scanned before a new one is needed.
+const PAIRING_TTL_MINUTES: i64 = 10;
+
+/// A pairing code waiting to be redeemed from the phone.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingPairing {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// What the QR code encodes, as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PairingPayload {
+    /// Where to POST `{"token": ...}` to get the API key.
+    pair_url: String,
+    endpoint_url: String,
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A QR code for setting up a phone, for the desktop app to display.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairingQr {
+    /// The QR code as an SVG document.
+    pub svg: String,
+    /// The JSON encoded in the QR code.
+    pub payload: String,
+    pub endpoint_url: String,
+    pub expires_at: DateTime<Utc>,
+    /// The key paired devices receive, generated if the server had none.
+    pub api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PairRequest {
+    pub token: String,
+    /// Shown in the log, e.g. "Work iPhone".
+    #[serde(default)]
+    pub device_name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PairResponse {
+    pub endpoint_url: String,
+    pub api_key: String,
+    /// Send as `X-Device-Id` so the phone shows up under its own name in the device list.
+    pub device_id: String,
+}
+
+impl ScreenshotProcessor {
+    fn base_url(&self) -> String {
+        let local_ip = local_ip_address::local_ip()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|_| "127.0.0.1".to_string());
+        format!("{}://{}:{}", self.url_scheme(), local_ip, self.config.server_port)
+    }
+
+    /// Issues a single-use pairing code and renders it with the endpoint as a QR code.
+    ///
+    /// Turns on API key authentication if it was off, since pairing hands the phone a key.
+    pub fn start_pairing(&self) -> Result<PairingQr> {
+        let api_key = match self.api_key() {
+            Some(key) => key,
+            None => {
+                let key = auth::generate_api_key();
+                self.set_api_key(Some(key.clone()));
+                info!("🔑 Generated an API key for pairing");
+                key
+            }
+        };
+
+        let pending = PendingPairing {
+            token: Uuid::new_v4().simple().to_string(),
+            expires_at: Utc::now() + Duration::minutes(PAIRING_TTL_MINUTES),
+        };
+        let base_url = self.base_url();
+        let payload = PairingPayload {
+            pair_url: format!("{}/pair", base_url),
+            endpoint_url: format!("{}/screenshot", base_url),
+            token: pending.token.clone(),
+            expires_at: pending.expires_at,
+        };
+        let payload_json = serde_json::to_string(&payload)?;
+
+        let svg = QrCode::new(payload_json.as_bytes())
+            .map_err(|e| anyhow!("Failed to encode pairing QR code: {}", e))?
+            .render::<svg::Color>()
+            .min_dimensions(256, 256)
+            .build();
+
+        *self.pairing.lock() = Some(pending);
+        info!("📱 Pairing code issued, valid for {} minutes", PAIRING_TTL_MINUTES);
+
+        Ok(PairingQr {
+            svg,
+            payload: payload_json,
+            endpoint_url: payload.endpoint_url,
+            expires_at: payload.expires_at,
+            api_key,
+        })
+    }
+
+    /// Redeems a pairing code for the API key and registers the device. Each code works once.
+    fn complete_pairing(&self, token: &str, device_name: &str) -> Option<PairResponse> {
+        let mut pairing = self.pairing.lock();
+        let pending = pairing.as_ref()?;
+        if pending.expires_at < Utc::now() || !auth::constant_time_eq(pending.token.as_bytes(), token.trim().as_bytes()) {
+            return None;
+        }
+        *pairing = None;
+
+        let device_id = Uuid::new_v4().to_string();
+        if let Err(e) = self.register_device(&device_id, device_name) {
+            warn!("Failed to register paired device: {}", e);
+        }
+        Some(PairResponse {
+            endpoint_url: format!("{}/screenshot", self.base_url()),
+            api_key: self.api_key()?,
+            device_id,
+        })
+    }
+}
+
+pub async fn handle_pair(
+    State(processor): State<ScreenshotProcessor>,
+    Json(request): Json<PairRequest>,
+) -> Result<ResponseJson<PairResponse>, StatusCode> {
+    let device = request
+        .device_name
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .unwrap_or("iPhone");
+    match processor.complete_pairing(&request.token, device) {
+        Some(response) => {
+            info!("📱 Paired with {}", device);
+            Ok(ResponseJson(response))
+        }
+        None => {
+            warn!("🔒 Rejected pairing attempt from {}: invalid or expired code", device);
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+}
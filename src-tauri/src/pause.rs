@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use crate::{
+    events::{emit_event, ProcessingPaused, UiEvent},
+    ScreenshotMetadata, ScreenshotProcessor,
+};
+
+/// What happens to screenshots that arrive while processing is paused.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PauseMode {
+    /// Discard them, so nothing captured during the pause is ever analyzed.
+    #[default]
+    Drop,
+    /// Save them to the outbox and analyze them once processing resumes.
+    Queue,
+}
+
+#[derive(Debug, Error)]
+pub enum Paused {
+    #[error("Processing is paused; the screenshot was not analyzed")]
+    Dropped,
+    #[error("Processing is paused; the screenshot was saved and will be analyzed on resume")]
+    Queued,
+}
+
+impl ScreenshotProcessor {
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Stops or restarts analyzing new screenshots, leaving the server and watchers running.
+    /// Returns whether that changed anything.
+    pub fn set_paused(&self, paused: bool) -> bool {
+        if !self.paused.send_if_modified(|current| std::mem::replace(current, paused) != paused) {
+            return false;
+        }
+
+        if paused {
+            info!("⏸️ Processing paused, new screenshots will be {}", match self.config.pause_mode {
+                PauseMode::Drop => "dropped",
+                PauseMode::Queue => "queued",
+            });
+        } else {
+            info!("▶️ Processing resumed");
+            // Analyze whatever was queued during the pause without waiting for the next retry
+            let processor = self.clone();
+            tokio::spawn(async move { processor.drain_outbox().await });
+        }
+        emit_event(UiEvent::ProcessingPaused(ProcessingPaused { paused }));
+        true
+    }
+
+    /// Notifies of every pause and resume, for status displays outside the UI.
+    pub fn subscribe_paused(&self) -> watch::Receiver<bool> {
+        self.paused.subscribe()
+    }
+
+    /// Keeps or discards a screenshot that arrived while paused, returning the error to report.
+    pub(crate) fn hold_while_paused(
+        &self,
+        image_base64: &str,
+        metadata: &Option<ScreenshotMetadata>,
+    ) -> anyhow::Error {
+        if self.config.pause_mode == PauseMode::Queue {
+            match self.store.outbox_push(image_base64, metadata) {
+                Ok(id) => {
+                    info!("⏸️ Queued screenshot {} until processing resumes", id);
+                    self.emit_outbox_changed();
+                    return Paused::Queued.into();
+                }
+                Err(e) => warn!("Failed to queue screenshot while paused: {}", e),
+            }
+        }
+        info!("⏸️ Dropped screenshot while processing is paused");
+        Paused::Dropped.into()
+    }
+}
@@ -0,0 +1,230 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, future::Future, time::Instant};
+use tracing::{debug, warn};
+
+use crate::{
+    prompts::{self, PromptTemplate},
+    retry, ContentAnalysis, OutputSchema, ProcessedImage, ProgressStage, ScreenshotProcessor,
+};
+
+/// How long each stage of an analysis took, in milliseconds.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StageTimings {
+    /// Waiting for a processing slot.
+    pub queue_ms: u64,
+    /// Decoding, validating and downscaling the image.
+    pub preprocess_ms: u64,
+    pub summary_ms: u64,
+    pub analysis_ms: u64,
+    pub store_ms: u64,
+    pub total_ms: u64,
+    /// The summary and content analysis came from one request, so both fields time the same call.
+    pub combined: bool,
+}
+
+pub(crate) fn elapsed_ms(started: Instant) -> u64 {
+    started.elapsed().as_millis() as u64
+}
+
+async fn timed<T>(future: impl Future<Output = T>) -> (T, u64) {
+    let started = Instant::now();
+    let output = future.await;
+    (output, elapsed_ms(started))
+}
+
+#[derive(Debug, Deserialize)]
+struct CombinedAnalysis {
+    summary: String,
+    #[serde(flatten)]
+    analysis: ContentAnalysis,
+}
+
+/// The content analysis schema with a `summary` field added.
+fn combined_schema() -> OutputSchema {
+    let mut schema = ContentAnalysis::schema();
+    schema.name = "record_screenshot_summary_and_analysis";
+    schema.description = "Record a short summary of the screenshot along with what it shows and what the user likely wants to do with it.";
+    schema.schema["properties"]["summary"] = serde_json::json!({
+        "type": "string",
+        "description": "The short summary requested in the first part of the prompt"
+    });
+    if let Some(required) = schema.schema["required"].as_array_mut() {
+        required.insert(0, "summary".into());
+    }
+    schema
+}
+
+/// The template `mappings` assigns to `key`, compared ignoring case.
+fn mapped_template(mappings: &HashMap<String, String>, key: &str) -> Option<PromptTemplate> {
+    let (_, name) = mappings.iter().find(|(k, _)| k.trim().eq_ignore_ascii_case(key.trim()))?;
+    match prompts::find_template(name) {
+        Ok(Some(template)) => Some(template),
+        Ok(None) => {
+            warn!("Prompt template \"{}\" mapped to \"{}\" doesn't exist", name, key);
+            None
+        }
+        Err(e) => {
+            warn!("Failed to load prompt templates: {}", e);
+            None
+        }
+    }
+}
+
+impl ScreenshotProcessor {
+    /// A copy that uses `template`'s prompts in place of the configured ones.
+    pub(crate) fn with_template(&self, template: PromptTemplate) -> Self {
+        let mut processor = self.clone();
+        processor.config.summary_prompt = Some(template.summary_prompt);
+        if let Some(analysis_prompt) = template.analysis_prompt {
+            processor.config.analysis_prompt = Some(analysis_prompt);
+        }
+        processor
+    }
+
+    /// The template for a screenshot from `app`: the screen recording one for a recording's
+    /// storyboard, then its mapped one, then its project's, otherwise the (possibly edited)
+    /// "default" template unless prompts are set in the config.
+    fn app_template(
+        &self,
+        app: Option<&str>,
+        recording: bool,
+        project_template: Option<&str>,
+    ) -> (Option<PromptTemplate>, bool) {
+        if recording {
+            if let Ok(Some(template)) = prompts::find_template(prompts::RECORDING_TEMPLATE) {
+                return (Some(template), true);
+            }
+        }
+        if let Some(template) = app.and_then(|app| mapped_template(&self.config.app_prompt_templates, app)) {
+            return (Some(template), true);
+        }
+        // Content type templates may still refine a project's summary, as they do the default's
+        if let Some(name) = project_template {
+            match prompts::find_template(name) {
+                Ok(Some(template)) => return (Some(template), false),
+                Ok(None) => warn!("Prompt template \"{}\" of the screenshot's project doesn't exist", name),
+                Err(e) => warn!("Failed to load prompt templates: {}", e),
+            }
+        }
+        if self.config.summary_prompt.is_some() || self.config.analysis_prompt.is_some() {
+            return (None, false);
+        }
+        (prompts::find_template("default").ok().flatten(), false)
+    }
+
+    /// Gets the summary and content analysis using the prompts mapped to the screenshot's app or
+    /// project or, failing that, its detected content type.
+    pub(crate) async fn summarize_and_analyze(
+        &self,
+        analysis_id: &str,
+        image: &ProcessedImage,
+        prompt_vars: &HashMap<&'static str, String>,
+        project_template: Option<&str>,
+        timings: &mut StageTimings,
+    ) -> Result<(String, ContentAnalysis)> {
+        let recording = prompt_vars.get("frames").is_some_and(|frames| !frames.is_empty());
+        let (template, app_mapped) =
+            self.app_template(prompt_vars.get("app").map(String::as_str), recording, project_template);
+        let (summary, analysis) = match template {
+            Some(template) => {
+                self.with_template(template)
+                    .run_llm_stages(analysis_id, image, prompt_vars, timings)
+                    .await?
+            }
+            None => self.run_llm_stages(analysis_id, image, prompt_vars, timings).await?,
+        };
+        if app_mapped {
+            return Ok((summary, analysis));
+        }
+
+        // The content type is only known after the first pass, so its summary takes another request
+        let Some(template) = mapped_template(&self.config.content_type_prompt_templates, &analysis.content_type) else {
+            return Ok((summary, analysis));
+        };
+        debug!("Summarizing {} screenshot with \"{}\" template", analysis.content_type, template.name);
+        let prompt = format!(
+            "{}{}",
+            prompts::render(&template.summary_prompt, prompt_vars),
+            prompts::language_instruction(self.config.response_language.as_deref())
+        );
+        let (summary, elapsed) = timed(
+            self.provider
+                .complete(&prompt, &[image], self.config.summary_max_tokens),
+        )
+        .await;
+        timings.summary_ms += elapsed;
+        Ok((summary?, analysis))
+    }
+
+    /// Gets the summary and content analysis: from one structured request when enabled and supported,
+    /// otherwise from two concurrent ones.
+    async fn run_llm_stages(
+        &self,
+        analysis_id: &str,
+        image: &ProcessedImage,
+        prompt_vars: &HashMap<&'static str, String>,
+        timings: &mut StageTimings,
+    ) -> Result<(String, ContentAnalysis)> {
+        if self.config.combine_llm_requests {
+            match timed(self.combined_analysis(image, prompt_vars)).await {
+                (Ok(combined), elapsed) => {
+                    timings.summary_ms = elapsed;
+                    timings.analysis_ms = elapsed;
+                    timings.combined = true;
+                    // Structured output can't be streamed, so the summary arrives as one token
+                    self.emit_progress(
+                        analysis_id,
+                        ProgressStage::SummaryToken { token: combined.summary.clone() },
+                    );
+                    return Ok((combined.summary, combined.analysis.normalized()));
+                }
+                // Two more requests to an unreachable provider would only fail again
+                (Err(e), _) if retry::is_transient(&e) => return Err(e),
+                (Err(e), _) => debug!("Combined analysis unavailable, making separate requests: {}", e),
+            }
+        }
+
+        let ((summary, summary_ms), (analysis, analysis_ms)) = tokio::join!(
+            timed(self.get_brief_summary(analysis_id, image, prompt_vars)),
+            timed(self.analyze_for_content_type(image, prompt_vars)),
+        );
+        timings.summary_ms = summary_ms;
+        timings.analysis_ms = analysis_ms;
+        Ok((summary?, analysis?))
+    }
+
+    async fn combined_analysis(
+        &self,
+        image: &ProcessedImage,
+        prompt_vars: &HashMap<&'static str, String>,
+    ) -> Result<CombinedAnalysis> {
+        let summary_template = self
+            .config
+            .summary_prompt
+            .as_deref()
+            .unwrap_or(prompts::DEFAULT_SUMMARY_PROMPT);
+        let analysis_template = self
+            .config
+            .analysis_prompt
+            .as_deref()
+            .unwrap_or(prompts::DEFAULT_ANALYSIS_PROMPT);
+        let prompt = format!(
+            "{}\n\nAlso:\n{}\n\nRecord the summary in `summary` and the analysis in the remaining fields.{}",
+            prompts::render(summary_template, prompt_vars),
+            prompts::render(analysis_template, prompt_vars),
+            prompts::language_instruction(self.config.response_language.as_deref()),
+        );
+
+        let json = self
+            .provider
+            .complete_json(
+                &prompt,
+                &[image],
+                &combined_schema(),
+                self.config.summary_max_tokens + self.config.analysis_max_tokens,
+            )
+            .await?;
+        Ok(serde_json::from_str(&json)?)
+    }
+}
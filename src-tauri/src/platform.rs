@@ -0,0 +1,110 @@
+use std::{path::PathBuf, time::Duration};
+
+use crate::WatchDirectory;
+
+/// Label used for the `app` metadata of auto-detected screenshots.
+pub fn screenshot_app_name() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "macOS Screenshot"
+    } else if cfg!(target_os = "windows") {
+        "Windows Screenshot"
+    } else {
+        "Linux Screenshot"
+    }
+}
+
+/// How long a new file must keep the same size before it is considered fully written.
+///
+/// macOS renames a finished hidden temp file into place, so its files settle almost immediately.
+/// The Windows Snipping Tool and Game Bar write in several passes and briefly lock the file,
+/// and most Linux tools write in place after creating the file.
+pub fn settle_interval() -> Duration {
+    if cfg!(target_os = "macos") {
+        Duration::from_millis(300)
+    } else if cfg!(target_os = "windows") {
+        Duration::from_millis(750)
+    } else {
+        Duration::from_millis(500)
+    }
+}
+
+/// Where the platform's screenshot tools save by default; only existing folders are returned.
+pub fn default_watch_directories() -> Vec<WatchDirectory> {
+    let pictures = dirs::picture_dir();
+    let mut directories = Vec::new();
+
+    if cfg!(target_os = "macos") {
+        if let Some(location) = macos_screencapture_location() {
+            directories.push(WatchDirectory {
+                path: location,
+                ..Default::default()
+            });
+        }
+        directories.push(WatchDirectory::desktop());
+    } else if cfg!(target_os = "windows") {
+        // Win+PrtScn and the Snipping Tool auto-save here, possibly redirected into OneDrive
+        if let Some(ref pictures) = pictures {
+            directories.push(dedicated(pictures.join("Screenshots")));
+        }
+        if let Some(home) = dirs::home_dir() {
+            directories.push(dedicated(home.join("OneDrive").join("Pictures").join("Screenshots")));
+        }
+        // Xbox Game Bar names captures after the foreground app, so accept any image
+        if let Some(videos) = dirs::video_dir() {
+            directories.push(dedicated(videos.join("Captures")));
+        }
+        directories.push(WatchDirectory::desktop());
+    } else {
+        // GNOME 42+ and newer Spectacle use Pictures/Screenshots; older tools and Flameshot use Pictures
+        if let Some(ref pictures) = pictures {
+            directories.push(dedicated(pictures.join("Screenshots")));
+            directories.push(WatchDirectory {
+                path: pictures.clone(),
+                ..Default::default()
+            });
+        }
+        directories.push(WatchDirectory::desktop());
+    }
+
+    let mut seen = Vec::new();
+    directories.retain(|d| {
+        let path = d.resolved_path();
+        let keep = path.is_dir() && !seen.contains(&path);
+        seen.push(path);
+        keep
+    });
+    directories
+}
+
+/// A folder only screenshot tools write to, so every image in it counts.
+fn dedicated(path: PathBuf) -> WatchDirectory {
+    WatchDirectory {
+        path,
+        accept_all_images: true,
+        ..Default::default()
+    }
+}
+
+/// The folder set with `defaults write com.apple.screencapture location`, if any.
+fn macos_screencapture_location() -> Option<PathBuf> {
+    if !cfg!(target_os = "macos") {
+        return None;
+    }
+
+    let output = std::process::Command::new("defaults")
+        .args(["read", "com.apple.screencapture", "location"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+
+    let location = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let path = WatchDirectory {
+        path: PathBuf::from(location),
+        ..Default::default()
+    }
+    .resolved_path();
+
+    path.is_dir().then_some(path)
+}
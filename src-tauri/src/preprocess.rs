@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, GenericImageView};
+use tracing::info;
+
+use crate::ProcessedImage;
+
+const JPEG_QUALITIES: &[u8] = &[85, 75, 65, 55];
+/// Smallest long edge we shrink to while trying to meet the size budget.
+const MIN_DIMENSION: u32 = 512;
+
+/// Downscales and recompresses an image for the LLM request, leaving smaller images untouched.
+///
+/// The result fits within `max_dimension` on its long edge and, where possible, within `max_bytes`.
+pub fn prepare_for_llm(original: &ProcessedImage, max_dimension: u32, max_bytes: usize) -> Result<ProcessedImage> {
+    let bytes = general_purpose::STANDARD
+        .decode(&original.base64_data)
+        .map_err(|e| anyhow!("Invalid base64: {}", e))?;
+    let image = image::load_from_memory(&bytes).map_err(|e| anyhow!("Failed to decode image: {}", e))?;
+
+    let (width, height) = image.dimensions();
+    if width.max(height) <= max_dimension && bytes.len() <= max_bytes {
+        return Ok(original.clone());
+    }
+
+    // JPEG has no alpha channel, so flatten before encoding
+    let mut target = max_dimension.max(1);
+    let mut resized = DynamicImage::ImageRgb8(shrink(&image, target).to_rgb8());
+
+    let encoded = loop {
+        let (encoded, fits) = encode_within(&resized, max_bytes)?;
+        if fits || target <= MIN_DIMENSION {
+            break encoded;
+        }
+
+        target = (target * 3 / 4).max(MIN_DIMENSION);
+        resized = shrink(&resized, target);
+    };
+
+    let (new_width, new_height) = resized.dimensions();
+    info!(
+        "🗜️ Downscaled image {}x{} ({} KB) -> {}x{} ({} KB)",
+        width,
+        height,
+        bytes.len() / 1024,
+        new_width,
+        new_height,
+        encoded.len() / 1024
+    );
+
+    Ok(ProcessedImage {
+        base64_data: general_purpose::STANDARD.encode(&encoded),
+        media_type: "image/jpeg".to_string(),
+        size_bytes: encoded.len(),
+    })
+}
+
+fn shrink(image: &DynamicImage, max_dimension: u32) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    if width.max(height) <= max_dimension {
+        return image.clone();
+    }
+    image.resize(max_dimension, max_dimension, FilterType::Triangle)
+}
+
+/// Encodes at decreasing JPEG qualities until the output fits, returning the last attempt either way.
+fn encode_within(image: &DynamicImage, max_bytes: usize) -> Result<(Vec<u8>, bool)> {
+    let mut encoded = Vec::new();
+
+    for &quality in JPEG_QUALITIES {
+        encoded.clear();
+        JpegEncoder::new_with_quality(&mut encoded, quality).encode_image(image)?;
+        if encoded.len() <= max_bytes {
+            return Ok((encoded, true));
+        }
+    }
+
+    Ok((encoded, false))
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use std::{net::IpAddr, sync::Arc};
+use tracing::warn;
+
+use crate::{providers::ProviderKind, AppConfig, ScreenshotMetadata, ScreenshotProcessor};
+
+/// `config` switched to the local Ollama model, with no cloud provider to fail over to.
+pub(crate) fn local_config(config: &AppConfig) -> AppConfig {
+    let model = config.local_model.clone().or_else(|| {
+        // Keep the model already chosen for Ollama
+        (config.provider == ProviderKind::Ollama)
+            .then(|| config.model.clone())
+            .flatten()
+    });
+
+    let local = AppConfig {
+        provider: ProviderKind::Ollama,
+        model,
+        fallback_provider: None,
+        fallback_model: None,
+        privacy_mode: true,
+        ..config.clone()
+    };
+    if let Some(url) = local.ollama_url.as_deref().filter(|url| !is_loopback_url(url)) {
+        warn!("Privacy mode sends screenshots to {}, which isn't on this machine", url);
+    }
+    local
+}
+
+fn is_loopback_url(url: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(url) else {
+        return false;
+    };
+    let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']);
+    host.eq_ignore_ascii_case("localhost") || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Whether the screenshot asked to be analyzed without anything leaving the machine.
+pub(crate) fn wants_local_only(metadata: &Option<ScreenshotMetadata>) -> bool {
+    metadata.as_ref().and_then(|m| m.local_only).unwrap_or(false)
+}
+
+impl ScreenshotProcessor {
+    /// A copy that analyzes with the local Ollama model and sends nothing off the machine: no Telegram,
+    /// webhooks, Notion or push notifications.
+    pub(crate) fn local_only(&self) -> Result<Self> {
+        let mut processor = self.clone();
+        processor.config = local_config(&self.config);
+        processor.provider = crate::build_resilient_provider(&processor.config, &processor.client)?;
+        processor.telegram_bot = None;
+        processor.notifiers = Arc::new(Vec::new());
+        Ok(processor)
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use teloxide::types::Recipient;
+use tracing::warn;
+
+use crate::{prompts, telegram::parse_recipient, AppConfig, ScreenshotProcessor};
+
+/// How screenshots from one source are processed, in place of the global settings. Unset fields
+/// keep the global ones.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessingProfile {
+    /// Model for this source, e.g. a cheaper one for clipboard images.
+    pub model: Option<String>,
+    /// Prompt template used in place of the configured prompts. Templates mapped to the app or
+    /// the screenshot's project still take precedence.
+    pub prompt_template: Option<String>,
+    /// Chat notified in place of the configured ones, unless the screenshot's project has its own.
+    pub telegram_chat_id: Option<String>,
+    /// Analyses from this source are deleted after this many days; 0 leaves them to the global
+    /// retention limits.
+    pub retention_days: u32,
+}
+
+impl AppConfig {
+    /// The profile for screenshots from `source`, e.g. `iOS`, `desktop_auto` or `clipboard`.
+    pub(crate) fn profile(&self, source: &str) -> Option<&ProcessingProfile> {
+        self.profiles.get(source)
+    }
+}
+
+impl ScreenshotProcessor {
+    /// A copy that processes screenshots with the source's model and prompts. Privacy mode keeps
+    /// to the local model.
+    pub(crate) fn with_profile(&self, profile: &ProcessingProfile) -> Result<Self> {
+        let mut processor = self.clone();
+        let model = profile.model.as_deref().map(str::trim).filter(|m| !m.is_empty());
+        if let Some(model) = model.filter(|_| !self.config.privacy_mode) {
+            processor.config.model = Some(model.to_string());
+            processor.provider = crate::build_resilient_provider(&processor.config, &processor.client)?;
+        }
+        if let Some(name) = profile.prompt_template.as_deref() {
+            match prompts::find_template(name) {
+                Ok(Some(template)) => return Ok(processor.with_template(template)),
+                Ok(None) => warn!("Prompt template \"{}\" of a processing profile doesn't exist", name),
+                Err(e) => warn!("Failed to load prompt templates: {}", e),
+            }
+        }
+        Ok(processor)
+    }
+
+    /// The chat a source's notifications go to in place of the configured ones, if it has one.
+    pub(crate) fn profile_recipient(&self, source: &str) -> Option<Recipient> {
+        parse_recipient(self.config.profile(source)?.telegram_chat_id.as_deref()?)
+    }
+}
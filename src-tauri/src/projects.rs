@@ -0,0 +1,237 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use teloxide::types::Recipient;
+use tracing::{info, warn};
+
+use crate::{
+    annotations::parse_color, filename_filter::Matcher, prompts, telegram::parse_recipient, ScreenshotMetadata,
+    ScreenshotProcessor,
+};
+
+const DEFAULT_COLOR: &str = "#007aff";
+
+/// Which part of a screenshot's metadata a project rule looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectRuleTarget {
+    App,
+    WindowTitle,
+    Filename,
+    /// e.g. `desktop`, `iphone` or `clipboard`.
+    Source,
+}
+
+/// Screenshots whose app, window title, filename or source matches `pattern` go to the project.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectRule {
+    pub target: ProjectRuleTarget,
+    /// A glob, or a `re:` regex; both ignore case.
+    pub pattern: String,
+}
+
+impl ProjectRule {
+    fn matches(&self, metadata: &ScreenshotMetadata) -> bool {
+        let value = match self.target {
+            ProjectRuleTarget::App => &metadata.app,
+            ProjectRuleTarget::WindowTitle => &metadata.window_title,
+            ProjectRuleTarget::Filename => &metadata.filename,
+            ProjectRuleTarget::Source => &metadata.source,
+        };
+        let Some(value) = value.as_deref() else {
+            return false;
+        };
+        match Matcher::parse(self.pattern.trim()) {
+            Ok(matcher) => matcher.is_match(value),
+            Err(e) => {
+                warn!("Skipping project rule: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// A workspace that history is split by, e.g. one per client. New screenshots go to the first
+/// project with a matching rule, otherwise to the active one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub id: String,
+    pub name: String,
+    /// `#rrggbb`.
+    pub color: String,
+    /// Prompt template for the project's screenshots when their app has none of its own.
+    pub prompt_template: Option<String>,
+    /// Chat notified about the project's screenshots in place of the configured ones.
+    pub telegram_chat_id: Option<String>,
+    pub rules: Vec<ProjectRule>,
+    /// Screenshots no rule claims go to this project.
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    /// Analyses in the project.
+    #[serde(default)]
+    pub count: usize,
+}
+
+impl Project {
+    /// The chat its notifications and digests go to in place of the configured ones, if it has one.
+    pub(crate) fn recipient(&self) -> Option<Recipient> {
+        parse_recipient(self.telegram_chat_id.as_deref()?)
+    }
+}
+
+/// A project as created or edited by the user.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProjectInput {
+    pub name: String,
+    /// `#rrggbb`; blue when not given.
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub prompt_template: Option<String>,
+    #[serde(default)]
+    pub telegram_chat_id: Option<String>,
+    #[serde(default)]
+    pub rules: Vec<ProjectRule>,
+}
+
+/// Trimmed, and `None` when empty.
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+impl ProjectInput {
+    /// The color to store, checking everything else on the way.
+    fn validate(&self) -> Result<String> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("Project name can't be empty"));
+        }
+        if let Some(name) = non_empty(&self.prompt_template) {
+            if prompts::find_template(&name)?.is_none() {
+                return Err(anyhow!("Prompt template \"{}\" doesn't exist", name));
+            }
+        }
+        if let Some(chat_id) = non_empty(&self.telegram_chat_id) {
+            parse_recipient(&chat_id)
+                .ok_or_else(|| anyhow!("Invalid Telegram chat '{}', expected a chat id or @channel", chat_id))?;
+        }
+        for rule in &self.rules {
+            if rule.pattern.trim().is_empty() {
+                return Err(anyhow!("Rule patterns can't be empty"));
+            }
+            Matcher::parse(rule.pattern.trim())?;
+        }
+        let color = non_empty(&self.color).unwrap_or_else(|| DEFAULT_COLOR.to_string()).to_lowercase();
+        parse_color(&color).ok_or_else(|| anyhow!("Invalid color '{}', expected #rrggbb", color))?;
+        Ok(color)
+    }
+}
+
+impl ScreenshotProcessor {
+    /// Every project with its size, oldest first.
+    pub fn list_projects(&self) -> Result<Vec<Project>> {
+        self.store
+            .list_projects()?
+            .into_iter()
+            .map(|mut project| {
+                project.count = self.store.count_where("project_id = ?", &[project.id.clone()])?;
+                Ok(project)
+            })
+            .collect()
+    }
+
+    pub fn create_project(&self, input: ProjectInput) -> Result<Project> {
+        let color = input.validate()?;
+        let project = Project {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: input.name.trim().to_string(),
+            color,
+            prompt_template: non_empty(&input.prompt_template),
+            telegram_chat_id: non_empty(&input.telegram_chat_id),
+            rules: input.rules,
+            active: false,
+            created_at: Utc::now(),
+            count: 0,
+        };
+        self.store.save_project(&project)?;
+        info!("🗂️ Created project \"{}\"", project.name);
+        self.project(&project.id)
+    }
+
+    pub fn update_project(&self, id: &str, input: ProjectInput) -> Result<Project> {
+        let color = input.validate()?;
+        let mut project = self
+            .store
+            .get_project(id)?
+            .ok_or_else(|| anyhow!("Project {} not found", id))?;
+        project.name = input.name.trim().to_string();
+        project.color = color;
+        project.prompt_template = non_empty(&input.prompt_template);
+        project.telegram_chat_id = non_empty(&input.telegram_chat_id);
+        project.rules = input.rules;
+        self.store.save_project(&project)?;
+        self.project(id)
+    }
+
+    /// Deletes a project, leaving its analyses in no project. Returns false if it didn't exist.
+    pub fn delete_project(&self, id: &str) -> Result<bool> {
+        self.store.delete_project(id)
+    }
+
+    pub fn project(&self, id: &str) -> Result<Project> {
+        let mut project = self
+            .store
+            .get_project(id)?
+            .ok_or_else(|| anyhow!("Project {} not found", id))?;
+        project.count = self.store.count_where("project_id = ?", &[project.id.clone()])?;
+        Ok(project)
+    }
+
+    /// Sends screenshots no rule claims to this project from now on, or to none.
+    pub fn set_active_project(&self, id: Option<&str>) -> Result<()> {
+        if !self.store.set_active_project(id)? {
+            return Err(anyhow!("Project {} not found", id.unwrap_or_default()));
+        }
+        match id {
+            Some(id) => info!("🗂️ Active project is now {}", id),
+            None => info!("🗂️ No active project"),
+        }
+        Ok(())
+    }
+
+    /// Moves an analysis to a project by hand, or out of any. Returns false if the analysis
+    /// doesn't exist.
+    pub fn assign_project(&self, analysis_id: &str, project_id: Option<&str>) -> Result<bool> {
+        if let Some(project_id) = project_id {
+            if self.store.get_project(project_id)?.is_none() {
+                return Err(anyhow!("Project {} not found", project_id));
+            }
+        }
+        self.store.set_project(analysis_id, project_id)
+    }
+
+    /// The project a new screenshot belongs to: the first one with a matching rule, otherwise
+    /// the active one.
+    pub(crate) fn project_for(&self, metadata: Option<&ScreenshotMetadata>) -> Option<Project> {
+        let projects = self
+            .store
+            .list_projects()
+            .inspect_err(|e| warn!("Failed to load projects: {}", e))
+            .ok()?;
+        let matched = metadata.and_then(|metadata| {
+            projects
+                .iter()
+                .find(|project| project.rules.iter().any(|rule| rule.matches(metadata)))
+                .cloned()
+        });
+        matched.or_else(|| projects.into_iter().find(|project| project.active))
+    }
+
+    /// The chat a project's notifications go to in place of the configured ones, if it has one.
+    pub(crate) fn project_recipient(&self, project_id: Option<&str>) -> Option<Recipient> {
+        self.store.get_project(project_id?).ok().flatten()?.recipient()
+    }
+}
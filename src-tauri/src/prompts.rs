@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::PathBuf};
+use tracing::info;
+
+use crate::ScreenshotMetadata;
+
+pub const DEFAULT_SUMMARY_PROMPT: &str =
+    "Analyze this {device} screenshot briefly. What is shown and what might be the user's intent?";
+
+pub const DEFAULT_ANALYSIS_PROMPT: &str = r#"Analyze this screenshot and determine:
+
+1. Content type (webpage, app, document, source code, social media, receipt or invoice, etc.)
+2. If webpage: the page's URL or domain, plus every other URL visible anywhere
+3. If research-related: identify key topics
+4. User context: what might they want to do with this?
+5. A few short tags to file it under
+6. The readable text it shows
+
+Respond with:
+CONTENT_TYPE: [webpage/app/document/code/social/game/receipt/other]
+WEBPAGE_URL: [URL if visible, or "none"]
+RESEARCH_TOPICS: [comma-separated topics if research-related]
+USER_INTENT: [likely user intent]
+FOLLOW_UP: [suggested follow-up actions]
+TAGS: [3-6 short lowercase tags, comma-separated]
+LINKS: [every visible URL or domain, comma-separated, or "none"]
+HAS_TABLE: [yes if a table of data is shown, otherwise no]
+LANGUAGE: [main language of the text shown, in English, e.g. Japanese, or "none"]
+EXTRACTED_TEXT: [the readable text shown, on one line, up to 300 words, or "none"]"#;
+
+/// Names of the variables `template_variables` provides.
+pub const TEMPLATE_VARIABLES: &[&str] =
+    &["source_type", "device", "app", "window_title", "filename", "date", "language", "frames", "project"];
+
+/// Template used for storyboards of screen recordings.
+pub const RECORDING_TEMPLATE: &str = "screen recording";
+
+/// A named pair of summary and analysis prompts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromptTemplate {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub summary_prompt: String,
+    /// Content analysis prompt; the default one when `None`.
+    #[serde(default)]
+    pub analysis_prompt: Option<String>,
+    /// Shipped with the app. Saving a template with the same name replaces it.
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+fn builtin(name: &str, description: &str, summary_prompt: &str) -> PromptTemplate {
+    PromptTemplate {
+        name: name.to_string(),
+        description: description.to_string(),
+        summary_prompt: summary_prompt.to_string(),
+        analysis_prompt: None,
+        builtin: true,
+    }
+}
+
+pub fn builtin_templates() -> Vec<PromptTemplate> {
+    vec![
+        builtin("default", "General-purpose summary", DEFAULT_SUMMARY_PROMPT),
+        builtin(
+            "code review",
+            "Reviews code in editors and terminals",
+            "This {device} screenshot shows code in {app}. Name the language and what the code does, \
+             then point out bugs, risky patterns or readability problems worth fixing.",
+        ),
+        builtin(
+            "receipt extraction",
+            "Pulls the purchase details out of receipts and invoices",
+            "This {device} screenshot shows a receipt or invoice. Extract the merchant, date, line items \
+             with prices, taxes, total and payment method. Write \"unknown\" for anything not visible.",
+        ),
+        builtin(
+            "UI critique",
+            "Critiques the design of the app or page shown",
+            "Critique the user interface in this {device} screenshot of {app}: layout, visual hierarchy, \
+             readability, accessibility and consistency. List the most important improvements first.",
+        ),
+        builtin(
+            RECORDING_TEMPLATE,
+            "Summarizes the flow shown in a screen recording",
+            "This image is a storyboard of {frames} keyframes from a {device} screen recording, in order \
+             left to right, top to bottom. Summarize the flow as a whole: the app, what the user did step \
+             by step, and what they ended up at or seemed to be trying to do.",
+        ),
+    ]
+}
+
+fn library_path() -> PathBuf {
+    crate::app_config_dir().join("prompt_templates.json")
+}
+
+fn read_user_templates() -> Result<Vec<PromptTemplate>> {
+    match std::fs::read_to_string(library_path()) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn write_user_templates(templates: &[PromptTemplate]) -> Result<()> {
+    let path = library_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(templates)?)?;
+    Ok(())
+}
+
+/// Built-in templates, replaced by saved ones of the same name, followed by the other saved templates.
+pub fn list_templates() -> Result<Vec<PromptTemplate>> {
+    let mut user = read_user_templates()?;
+    let mut templates: Vec<PromptTemplate> = builtin_templates()
+        .into_iter()
+        .map(|template| match user.iter().position(|t| t.name == template.name) {
+            Some(index) => user.remove(index),
+            None => template,
+        })
+        .collect();
+    templates.extend(user);
+    Ok(templates)
+}
+
+/// Looks up a template by name, ignoring case.
+pub fn find_template(name: &str) -> Result<Option<PromptTemplate>> {
+    Ok(list_templates()?
+        .into_iter()
+        .find(|t| t.name.eq_ignore_ascii_case(name.trim())))
+}
+
+fn validate(template: &PromptTemplate) -> Result<()> {
+    if template.name.trim().is_empty() {
+        return Err(anyhow!("Template name is required"));
+    }
+    if template.summary_prompt.trim().is_empty() {
+        return Err(anyhow!("Template \"{}\" needs a summary prompt", template.name));
+    }
+    Ok(())
+}
+
+/// Saves a new template, failing if one with the same name exists.
+pub fn create_template(template: PromptTemplate) -> Result<Vec<PromptTemplate>> {
+    validate(&template)?;
+    if find_template(&template.name)?.is_some() {
+        return Err(anyhow!("A template named \"{}\" already exists", template.name.trim()));
+    }
+    save_template(template)
+}
+
+/// Replaces the saved template called `name`, which may be a built-in one, and may rename it.
+pub fn update_template(name: &str, template: PromptTemplate) -> Result<Vec<PromptTemplate>> {
+    validate(&template)?;
+    let existing = find_template(name)?.ok_or_else(|| anyhow!("No template named \"{}\"", name))?;
+    if !template.name.trim().eq_ignore_ascii_case(&existing.name) && find_template(&template.name)?.is_some() {
+        return Err(anyhow!("A template named \"{}\" already exists", template.name.trim()));
+    }
+
+    let mut user = read_user_templates()?;
+    user.retain(|t| t.name != existing.name);
+    write_user_templates(&user)?;
+    save_template(template)
+}
+
+/// Removes a saved template; deleting an edited built-in one restores the original.
+pub fn delete_template(name: &str) -> Result<bool> {
+    let mut user = read_user_templates()?;
+    let before = user.len();
+    user.retain(|t| !t.name.eq_ignore_ascii_case(name.trim()));
+    if user.len() == before {
+        return Ok(false);
+    }
+    write_user_templates(&user)?;
+    info!("🗑️ Deleted prompt template \"{}\"", name);
+    Ok(true)
+}
+
+fn save_template(template: PromptTemplate) -> Result<Vec<PromptTemplate>> {
+    let template = PromptTemplate {
+        name: template.name.trim().to_string(),
+        analysis_prompt: template.analysis_prompt.filter(|p| !p.trim().is_empty()),
+        builtin: false,
+        ..template
+    };
+
+    let mut user = read_user_templates()?;
+    info!("📝 Saved prompt template \"{}\"", template.name);
+    user.push(template);
+    write_user_templates(&user)?;
+    list_templates()
+}
+
+/// Variables available to prompt templates as `{name}`.
+pub fn template_variables(
+    source_type: &str,
+    metadata: Option<&ScreenshotMetadata>,
+    response_language: Option<&str>,
+) -> HashMap<&'static str, String> {
+    let device = if source_type.starts_with("desktop") {
+        "desktop"
+    } else {
+        "iPhone"
+    };
+
+    let mut vars = HashMap::new();
+    vars.insert("source_type", source_type.to_string());
+    vars.insert("device", device.to_string());
+    vars.insert(
+        "app",
+        metadata.and_then(|m| m.app.clone()).unwrap_or_else(|| "unknown".to_string()),
+    );
+    vars.insert(
+        "window_title",
+        metadata.and_then(|m| m.window_title.clone()).unwrap_or_default(),
+    );
+    vars.insert(
+        "filename",
+        metadata.and_then(|m| m.filename.clone()).unwrap_or_default(),
+    );
+    vars.insert(
+        "date",
+        chrono::Local::now().format("%Y-%m-%d").to_string(),
+    );
+    vars.insert(
+        "language",
+        response_language_name(response_language).unwrap_or("English").to_string(),
+    );
+    vars.insert(
+        "frames",
+        metadata
+            .and_then(|m| m.recording_frames)
+            .map(|frames| frames.to_string())
+            .unwrap_or_default(),
+    );
+    vars
+}
+
+fn response_language_name(language: Option<&str>) -> Option<&str> {
+    language.map(str::trim).filter(|language| !language.is_empty())
+}
+
+/// Appended to prompts whose answer the user reads, so it comes back in their configured language.
+/// Empty when no response language is set.
+pub fn language_instruction(response_language: Option<&str>) -> String {
+    match response_language_name(response_language) {
+        Some(language) => format!(
+            "\n\nWrite your answer in {}. Keep URLs, code, names and the labels of any requested format as they are.",
+            language
+        ),
+        None => String::new(),
+    }
+}
+
+/// Substitutes `{name}` placeholders; unknown placeholders are left untouched.
+pub fn render(template: &str, vars: &HashMap<&'static str, String>) -> String {
+    vars.iter().fold(template.to_string(), |acc, (name, value)| {
+        acc.replace(&format!("{{{}}}", name), value)
+    })
+}
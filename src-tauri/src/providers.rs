@@ -0,0 +1,821 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use thiserror::Error;
+use tracing::{info, warn};
+
+use crate::{
+    usage::{self, TokenUsage},
+    AppConfig, ProcessedImage,
+};
+
+/// Which vision-capable LLM backend analyzes screenshots.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    Anthropic,
+    OpenAI,
+    Gemini,
+    Ollama,
+}
+
+impl ProviderKind {
+    pub fn default_model(&self) -> &'static str {
+        match self {
+            ProviderKind::Anthropic => "claude-3-5-sonnet-20241022",
+            ProviderKind::OpenAI => "gpt-4o",
+            ProviderKind::Gemini => "gemini-1.5-flash",
+            ProviderKind::Ollama => "llava",
+        }
+    }
+}
+
+/// Who authored a message in a multi-turn exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatRole {
+    User,
+    Assistant,
+}
+
+impl ChatRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChatRole::User => "user",
+            ChatRole::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: ChatRole,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn user(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        Self {
+            role: ChatRole::Assistant,
+            content: content.into(),
+        }
+    }
+}
+
+/// Receives generated text incrementally while a response streams in.
+pub type TokenCallback<'a> = &'a (dyn Fn(&str) + Send + Sync);
+
+/// A JSON Schema a structured response must match.
+#[derive(Debug, Clone)]
+pub struct OutputSchema {
+    /// Identifier for the tool or format, as some APIs require one.
+    pub name: &'static str,
+    pub description: &'static str,
+    pub schema: serde_json::Value,
+}
+
+/// A backend that can hold a conversation about zero or more images.
+#[async_trait]
+pub trait VisionProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Runs a multi-turn exchange; `images` are attached to the first user message.
+    async fn chat(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32) -> Result<String>;
+
+    /// Like `chat`, but passes text to `on_token` as it is generated. Returns the full response.
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        images: &[&ProcessedImage],
+        max_tokens: u32,
+        on_token: TokenCallback<'_>,
+    ) -> Result<String> {
+        let text = self.chat(messages, images, max_tokens).await?;
+        on_token(&text);
+        Ok(text)
+    }
+
+    /// Answers a single prompt about `images`.
+    async fn complete(&self, prompt: &str, images: &[&ProcessedImage], max_tokens: u32) -> Result<String> {
+        self.chat(&[ChatMessage::user(prompt)], images, max_tokens).await
+    }
+
+    /// Answers a single prompt about `images`, streaming the response to `on_token`.
+    async fn complete_stream(
+        &self,
+        prompt: &str,
+        images: &[&ProcessedImage],
+        max_tokens: u32,
+        on_token: TokenCallback<'_>,
+    ) -> Result<String> {
+        self.chat_stream(&[ChatMessage::user(prompt)], images, max_tokens, on_token)
+            .await
+    }
+
+    /// Answers a single prompt about `images` with a JSON object matching `schema`, returned as text.
+    ///
+    /// Fails for providers that can't constrain their output, so callers can fall back to `complete`.
+    async fn complete_json(
+        &self,
+        _prompt: &str,
+        _images: &[&ProcessedImage],
+        _schema: &OutputSchema,
+        _max_tokens: u32,
+    ) -> Result<String> {
+        Err(anyhow!("{} does not support structured output", self.name()))
+    }
+}
+
+/// Builds the provider selected in `config`, failing if its credentials are missing.
+pub fn build_provider(config: &AppConfig, client: Client) -> Result<Arc<dyn VisionProvider>> {
+    let model = config
+        .model
+        .clone()
+        .filter(|m| !m.trim().is_empty())
+        .unwrap_or_else(|| config.provider.default_model().to_string());
+
+    let provider: Arc<dyn VisionProvider> = match config.provider {
+        ProviderKind::Anthropic => Arc::new(AnthropicProvider {
+            client,
+            api_key: required_key(&config.anthropic_api_key, "Anthropic")?,
+            model,
+        }),
+        ProviderKind::OpenAI => Arc::new(OpenAIProvider {
+            client,
+            api_key: required_key(&config.openai_api_key, "OpenAI")?,
+            model,
+        }),
+        ProviderKind::Gemini => Arc::new(GeminiProvider {
+            client,
+            api_key: required_key(&config.gemini_api_key, "Gemini")?,
+            model,
+        }),
+        ProviderKind::Ollama => Arc::new(OllamaProvider {
+            client,
+            base_url: config
+                .ollama_url
+                .clone()
+                .filter(|url| !url.trim().is_empty())
+                .unwrap_or_else(|| "http://localhost:11434".to_string()),
+            model,
+        }),
+    };
+
+    Ok(provider)
+}
+
+/// Builds the provider to fail over to, if one is configured and has credentials.
+pub fn build_fallback_provider(config: &AppConfig, client: Client) -> Option<Arc<dyn VisionProvider>> {
+    let kind = config.fallback_provider?;
+    let fallback_config = AppConfig {
+        provider: kind,
+        model: config.fallback_model.clone(),
+        ..config.clone()
+    };
+
+    match build_provider(&fallback_config, client) {
+        Ok(provider) => {
+            info!("🛟 Falling back to {} when the primary provider is unavailable", provider.name());
+            Some(provider)
+        }
+        Err(e) => {
+            warn!("Fallback provider disabled: {}", e);
+            None
+        }
+    }
+}
+
+/// Makes the cheapest authenticated request the configured provider offers, to confirm its key
+/// or URL works without spending tokens. Returns the provider's name.
+pub(crate) async fn check_credentials(config: &AppConfig, client: &Client) -> Result<&'static str> {
+    let (request, provider) = match config.provider {
+        ProviderKind::Anthropic => (
+            client
+                .get("https://api.anthropic.com/v1/models")
+                .query(&[("limit", "1")])
+                .header("x-api-key", required_key(&config.anthropic_api_key, "Anthropic")?)
+                .header("anthropic-version", "2023-06-01"),
+            "Anthropic",
+        ),
+        ProviderKind::OpenAI => (
+            client
+                .get("https://api.openai.com/v1/models")
+                .bearer_auth(required_key(&config.openai_api_key, "OpenAI")?),
+            "OpenAI",
+        ),
+        ProviderKind::Gemini => (
+            client
+                .get("https://generativelanguage.googleapis.com/v1beta/models")
+                .query(&[("pageSize", "1"), ("key", &required_key(&config.gemini_api_key, "Gemini")?)]),
+            "Gemini",
+        ),
+        ProviderKind::Ollama => {
+            let base_url = config
+                .ollama_url
+                .clone()
+                .filter(|url| !url.trim().is_empty())
+                .unwrap_or_else(|| "http://localhost:11434".to_string());
+            (client.get(format!("{}/api/tags", base_url.trim_end_matches('/'))), "Ollama")
+        }
+    };
+    send(request, provider).await?;
+    Ok(provider)
+}
+
+fn required_key(key: &Option<String>, provider: &str) -> Result<String> {
+    key.clone()
+        .filter(|k| !k.trim().is_empty())
+        .ok_or_else(|| anyhow!("{} API key is required", provider))
+}
+
+/// Failures talking to a provider's API, as opposed to malformed responses.
+#[derive(Debug, Error)]
+pub enum ProviderError {
+    #[error("API request failed: {0}")]
+    Transport(reqwest::Error),
+    #[error("{provider} API error: {status}")]
+    Status { provider: String, status: StatusCode },
+}
+
+impl ProviderError {
+    /// Whether trying again later may succeed: network trouble, rate limits and overloaded servers.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            ProviderError::Transport(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+            ProviderError::Status { status, .. } => {
+                matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504 | 529)
+            }
+        }
+    }
+}
+
+/// Sends a request, turning transport failures and error statuses into a `ProviderError`.
+pub(crate) async fn send(request: reqwest::RequestBuilder, provider: &str) -> Result<reqwest::Response> {
+    let response = request.send().await.map_err(ProviderError::Transport)?;
+
+    if !response.status().is_success() {
+        return Err(ProviderError::Status {
+            provider: provider.to_string(),
+            status: response.status(),
+        }
+        .into());
+    }
+
+    Ok(response)
+}
+
+async fn send_json(request: reqwest::RequestBuilder, provider: &str) -> Result<serde_json::Value> {
+    let response = send(request, provider).await?;
+
+    response
+        .json()
+        .await
+        .map_err(|e| anyhow!("Failed to parse response: {}", e))
+}
+
+/// Sends a streaming request and collects the text `extract` finds in each event, forwarding it to `on_token`.
+/// Token counts found by `usage` are combined, as providers report them cumulatively.
+///
+/// Handles both server-sent events (`data: {...}` lines) and newline-delimited JSON.
+async fn read_stream(
+    request: reqwest::RequestBuilder,
+    provider: &str,
+    on_token: TokenCallback<'_>,
+    extract: impl Fn(&serde_json::Value) -> Option<&str>,
+    usage: impl Fn(&serde_json::Value) -> Option<TokenUsage>,
+) -> Result<(String, TokenUsage)> {
+    let mut response = send(request, provider).await?;
+
+    let mut text = String::new();
+    let mut total_usage = TokenUsage::default();
+    let mut buffer = Vec::new();
+    let mut handle_line = |line: &[u8]| {
+        if let Some(event) = parse_stream_line(line) {
+            if let Some(token) = extract(&event).filter(|t| !t.is_empty()) {
+                text.push_str(token);
+                on_token(token);
+            }
+            if let Some(event_usage) = usage(&event) {
+                total_usage = total_usage.max(event_usage);
+            }
+        }
+    };
+
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| anyhow!("Failed to read {} stream: {}", provider, e))?
+    {
+        buffer.extend_from_slice(&chunk);
+        while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            handle_line(&line);
+        }
+    }
+    handle_line(&buffer);
+
+    if text.is_empty() {
+        return Err(anyhow!("{} stream returned no text", provider));
+    }
+    Ok((text, total_usage))
+}
+
+/// Reads token counts from `value[field]`, using the provider's names for input and output tokens.
+fn usage_counts(value: &serde_json::Value, input: &str, output: &str) -> Option<TokenUsage> {
+    let usage = value.as_object()?;
+    Some(TokenUsage {
+        input_tokens: usage.get(input).and_then(|v| v.as_u64()).unwrap_or(0),
+        output_tokens: usage.get(output).and_then(|v| v.as_u64()).unwrap_or(0),
+    })
+}
+
+fn anthropic_usage(value: &serde_json::Value) -> Option<TokenUsage> {
+    // `message_start` events nest the usage block in the message
+    let usage = value.get("usage").or_else(|| value["message"].get("usage"))?;
+    usage_counts(usage, "input_tokens", "output_tokens")
+}
+
+fn openai_usage(value: &serde_json::Value) -> Option<TokenUsage> {
+    usage_counts(&value["usage"], "prompt_tokens", "completion_tokens")
+}
+
+fn gemini_usage(value: &serde_json::Value) -> Option<TokenUsage> {
+    usage_counts(&value["usageMetadata"], "promptTokenCount", "candidatesTokenCount")
+}
+
+fn ollama_usage(value: &serde_json::Value) -> Option<TokenUsage> {
+    usage_counts(value, "prompt_eval_count", "eval_count")
+}
+
+fn parse_stream_line(line: &[u8]) -> Option<serde_json::Value> {
+    let line = std::str::from_utf8(line).ok()?.trim();
+    let payload = line.strip_prefix("data:").map(str::trim).unwrap_or(line);
+    if payload.is_empty() || payload == "[DONE]" {
+        return None;
+    }
+    serde_json::from_str(payload).ok()
+}
+
+/// Index of the message that carries the images: the first user message.
+fn image_message_index(messages: &[ChatMessage]) -> Option<usize> {
+    messages.iter().position(|m| m.role == ChatRole::User)
+}
+
+pub struct AnthropicProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+}
+
+impl AnthropicProvider {
+    fn body(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32, stream: bool) -> serde_json::Value {
+        let image_index = image_message_index(messages);
+
+        let messages: Vec<serde_json::Value> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let mut content = vec![serde_json::json!({
+                    "type": "text",
+                    "text": message.content
+                })];
+                if Some(i) == image_index {
+                    content.extend(images.iter().map(|image| {
+                        serde_json::json!({
+                            "type": "image",
+                            "source": {
+                                "type": "base64",
+                                "media_type": image.media_type,
+                                "data": image.base64_data
+                            }
+                        })
+                    }));
+                }
+                serde_json::json!({
+                    "role": message.role.as_str(),
+                    "content": content
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "model": self.model,
+            "max_tokens": max_tokens,
+            "messages": messages,
+            "stream": stream
+        })
+    }
+
+    fn post(&self, request_body: &serde_json::Value) -> reqwest::RequestBuilder {
+        self.client
+            .post("https://api.anthropic.com/v1/messages")
+            .header("x-api-key", &self.api_key)
+            .header("Content-Type", "application/json")
+            .header("anthropic-version", "2023-06-01")
+            .json(request_body)
+    }
+
+    fn request(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32, stream: bool) -> reqwest::RequestBuilder {
+        self.post(&self.body(messages, images, max_tokens, stream))
+    }
+}
+
+#[async_trait]
+impl VisionProvider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32) -> Result<String> {
+        let response_json = send_json(self.request(messages, images, max_tokens, false), "Claude").await?;
+        if let Some(usage) = anthropic_usage(&response_json) {
+            usage::record(self.name(), &self.model, usage);
+        }
+
+        response_json["content"][0]["text"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Invalid response format"))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        images: &[&ProcessedImage],
+        max_tokens: u32,
+        on_token: TokenCallback<'_>,
+    ) -> Result<String> {
+        let (text, usage) = read_stream(
+            self.request(messages, images, max_tokens, true),
+            "Claude",
+            on_token,
+            |event| {
+                (event["type"] == "content_block_delta")
+                    .then(|| event["delta"]["text"].as_str())
+                    .flatten()
+            },
+            anthropic_usage,
+        )
+        .await?;
+        usage::record(self.name(), &self.model, usage);
+        Ok(text)
+    }
+
+    /// Forces a call to a single tool whose input schema is `schema`, and returns that input.
+    async fn complete_json(
+        &self,
+        prompt: &str,
+        images: &[&ProcessedImage],
+        schema: &OutputSchema,
+        max_tokens: u32,
+    ) -> Result<String> {
+        let mut request_body = self.body(&[ChatMessage::user(prompt)], images, max_tokens, false);
+        request_body["tools"] = serde_json::json!([{
+            "name": schema.name,
+            "description": schema.description,
+            "input_schema": schema.schema
+        }]);
+        request_body["tool_choice"] = serde_json::json!({ "type": "tool", "name": schema.name });
+
+        let response_json = send_json(self.post(&request_body), "Claude").await?;
+        if let Some(usage) = anthropic_usage(&response_json) {
+            usage::record(self.name(), &self.model, usage);
+        }
+
+        response_json["content"]
+            .as_array()
+            .and_then(|blocks| blocks.iter().find(|block| block["type"] == "tool_use"))
+            .map(|block| block["input"].to_string())
+            .ok_or_else(|| anyhow!("Response contained no {} tool call", schema.name))
+    }
+}
+
+pub struct OpenAIProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+}
+
+impl OpenAIProvider {
+    fn body(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32, stream: bool) -> serde_json::Value {
+        let image_index = image_message_index(messages);
+
+        let messages: Vec<serde_json::Value> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                if Some(i) != image_index {
+                    return serde_json::json!({
+                        "role": message.role.as_str(),
+                        "content": message.content
+                    });
+                }
+
+                let mut content = vec![serde_json::json!({
+                    "type": "text",
+                    "text": message.content
+                })];
+                content.extend(images.iter().map(|image| {
+                    serde_json::json!({
+                        "type": "image_url",
+                        "image_url": {
+                            "url": format!("data:{};base64,{}", image.media_type, image.base64_data)
+                        }
+                    })
+                }));
+                serde_json::json!({
+                    "role": message.role.as_str(),
+                    "content": content
+                })
+            })
+            .collect();
+
+        let mut request_body = serde_json::json!({
+            "model": self.model,
+            "max_tokens": max_tokens,
+            "messages": messages,
+            "stream": stream
+        });
+        if stream {
+            // Usage is only reported in a final chunk when asked for
+            request_body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+        request_body
+    }
+
+    fn post(&self, request_body: &serde_json::Value) -> reqwest::RequestBuilder {
+        self.client
+            .post("https://api.openai.com/v1/chat/completions")
+            .bearer_auth(&self.api_key)
+            .json(request_body)
+    }
+
+    fn request(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32, stream: bool) -> reqwest::RequestBuilder {
+        self.post(&self.body(messages, images, max_tokens, stream))
+    }
+}
+
+#[async_trait]
+impl VisionProvider for OpenAIProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32) -> Result<String> {
+        let response_json = send_json(self.request(messages, images, max_tokens, false), "OpenAI").await?;
+        if let Some(usage) = openai_usage(&response_json) {
+            usage::record(self.name(), &self.model, usage);
+        }
+
+        response_json["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Invalid response format"))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        images: &[&ProcessedImage],
+        max_tokens: u32,
+        on_token: TokenCallback<'_>,
+    ) -> Result<String> {
+        let (text, usage) = read_stream(
+            self.request(messages, images, max_tokens, true),
+            "OpenAI",
+            on_token,
+            |event| {
+                event["choices"][0]["delta"]["content"].as_str()
+            },
+            openai_usage,
+        )
+        .await?;
+        usage::record(self.name(), &self.model, usage);
+        Ok(text)
+    }
+
+    async fn complete_json(
+        &self,
+        prompt: &str,
+        images: &[&ProcessedImage],
+        schema: &OutputSchema,
+        max_tokens: u32,
+    ) -> Result<String> {
+        let mut request_body = self.body(&[ChatMessage::user(prompt)], images, max_tokens, false);
+        request_body["response_format"] = serde_json::json!({
+            "type": "json_schema",
+            "json_schema": {
+                "name": schema.name,
+                "description": schema.description,
+                "schema": schema.schema,
+                "strict": true
+            }
+        });
+
+        let response_json = send_json(self.post(&request_body), "OpenAI").await?;
+        if let Some(usage) = openai_usage(&response_json) {
+            usage::record(self.name(), &self.model, usage);
+        }
+
+        response_json["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Invalid response format"))
+    }
+}
+
+pub struct GeminiProvider {
+    client: Client,
+    api_key: String,
+    model: String,
+}
+
+impl GeminiProvider {
+    fn request(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32, stream: bool) -> reqwest::RequestBuilder {
+        let image_index = image_message_index(messages);
+
+        let contents: Vec<serde_json::Value> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let mut parts = vec![serde_json::json!({ "text": message.content })];
+                if Some(i) == image_index {
+                    parts.extend(images.iter().map(|image| {
+                        serde_json::json!({
+                            "inline_data": {
+                                "mime_type": image.media_type,
+                                "data": image.base64_data
+                            }
+                        })
+                    }));
+                }
+                let role = match message.role {
+                    ChatRole::User => "user",
+                    ChatRole::Assistant => "model",
+                };
+                serde_json::json!({
+                    "role": role,
+                    "parts": parts
+                })
+            })
+            .collect();
+
+        let request_body = serde_json::json!({
+            "contents": contents,
+            "generationConfig": {
+                "maxOutputTokens": max_tokens
+            }
+        });
+
+        let method = if stream {
+            "streamGenerateContent"
+        } else {
+            "generateContent"
+        };
+
+        let mut request = self
+            .client
+            .post(format!(
+                "https://generativelanguage.googleapis.com/v1beta/models/{}:{}",
+                self.model, method
+            ))
+            .query(&[("key", &self.api_key)]);
+        if stream {
+            request = request.query(&[("alt", "sse")]);
+        }
+        request.json(&request_body)
+    }
+}
+
+#[async_trait]
+impl VisionProvider for GeminiProvider {
+    fn name(&self) -> &'static str {
+        "gemini"
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32) -> Result<String> {
+        let response_json = send_json(self.request(messages, images, max_tokens, false), "Gemini").await?;
+        if let Some(usage) = gemini_usage(&response_json) {
+            usage::record(self.name(), &self.model, usage);
+        }
+
+        response_json["candidates"][0]["content"]["parts"][0]["text"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Invalid response format"))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        images: &[&ProcessedImage],
+        max_tokens: u32,
+        on_token: TokenCallback<'_>,
+    ) -> Result<String> {
+        let (text, usage) = read_stream(
+            self.request(messages, images, max_tokens, true),
+            "Gemini",
+            on_token,
+            |event| {
+                event["candidates"][0]["content"]["parts"][0]["text"].as_str()
+            },
+            gemini_usage,
+        )
+        .await?;
+        usage::record(self.name(), &self.model, usage);
+        Ok(text)
+    }
+}
+
+pub struct OllamaProvider {
+    client: Client,
+    base_url: String,
+    model: String,
+}
+
+impl OllamaProvider {
+    fn request(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32, stream: bool) -> reqwest::RequestBuilder {
+        let image_index = image_message_index(messages);
+
+        let messages: Vec<serde_json::Value> = messages
+            .iter()
+            .enumerate()
+            .map(|(i, message)| {
+                let mut value = serde_json::json!({
+                    "role": message.role.as_str(),
+                    "content": message.content
+                });
+                if Some(i) == image_index {
+                    value["images"] = images
+                        .iter()
+                        .map(|image| serde_json::Value::String(image.base64_data.clone()))
+                        .collect();
+                }
+                value
+            })
+            .collect();
+
+        let request_body = serde_json::json!({
+            "model": self.model,
+            "messages": messages,
+            "stream": stream,
+            "options": {
+                "num_predict": max_tokens
+            }
+        });
+
+        self.client
+            .post(format!("{}/api/chat", self.base_url.trim_end_matches('/')))
+            .json(&request_body)
+    }
+}
+
+#[async_trait]
+impl VisionProvider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32) -> Result<String> {
+        let response_json = send_json(self.request(messages, images, max_tokens, false), "Ollama").await?;
+        if let Some(usage) = ollama_usage(&response_json) {
+            usage::record(self.name(), &self.model, usage);
+        }
+
+        response_json["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("Invalid response format"))
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        images: &[&ProcessedImage],
+        max_tokens: u32,
+        on_token: TokenCallback<'_>,
+    ) -> Result<String> {
+        let (text, usage) = read_stream(
+            self.request(messages, images, max_tokens, true),
+            "Ollama",
+            on_token,
+            |event| {
+                event["message"]["content"].as_str()
+            },
+            ollama_usage,
+        )
+        .await?;
+        usage::record(self.name(), &self.model, usage);
+        Ok(text)
+    }
+}
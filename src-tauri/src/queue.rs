@@ -0,0 +1,108 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use thiserror::Error;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::events::{emit_event, QueueChanged, UiEvent};
+
+#[derive(Debug, Error)]
+#[error("Too many screenshots queued ({0} waiting), try again later")]
+pub struct QueueFull(pub usize);
+
+/// Limits how many screenshots are analyzed at once and how many may wait for a slot.
+#[derive(Debug, Clone)]
+pub struct WorkQueue {
+    slots: Arc<Semaphore>,
+    max_concurrent: usize,
+    max_waiting: usize,
+    waiting: Arc<AtomicUsize>,
+}
+
+/// Decrements the waiting count when a queued request gets a slot or gives up.
+struct WaitingGuard(Arc<AtomicUsize>);
+
+impl Drop for WaitingGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A processing slot, given back when dropped.
+pub struct QueueSlot {
+    permit: Option<OwnedSemaphorePermit>,
+    queue: WorkQueue,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        // Released first so the event counts the slot as free
+        drop(self.permit.take());
+        self.queue.emit_changed();
+    }
+}
+
+impl WorkQueue {
+    pub fn new(max_concurrent: usize, max_waiting: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            max_concurrent,
+            max_waiting,
+            waiting: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Waits for a processing slot, or fails immediately if the queue is already full.
+    pub async fn acquire(&self) -> Result<QueueSlot, QueueFull> {
+        let permit = match self.slots.clone().try_acquire_owned() {
+            Ok(permit) => permit,
+            Err(_) => self.wait_for_slot().await?,
+        };
+        self.emit_changed();
+        Ok(QueueSlot {
+            permit: Some(permit),
+            queue: self.clone(),
+        })
+    }
+
+    async fn wait_for_slot(&self) -> Result<OwnedSemaphorePermit, QueueFull> {
+        let waiting = self.waiting.fetch_add(1, Ordering::Relaxed);
+        let _guard = WaitingGuard(self.waiting.clone());
+        if waiting >= self.max_waiting {
+            return Err(QueueFull(waiting));
+        }
+        self.emit_changed();
+
+        // The semaphore is never closed
+        Ok(self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("work queue semaphore closed"))
+    }
+
+    fn emit_changed(&self) {
+        emit_event(UiEvent::QueueChanged(QueueChanged {
+            active: self.active(),
+            waiting: self.depth(),
+            max_concurrent: self.max_concurrent,
+        }));
+    }
+
+    /// Requests waiting for a slot.
+    pub fn depth(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
+    /// Requests currently being processed.
+    pub fn active(&self) -> usize {
+        self.max_concurrent - self.slots.available_permits()
+    }
+
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+}
@@ -0,0 +1,188 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use parking_lot::Mutex;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::{auth, ApiError, ErrorCode, ScreenshotProcessor};
+
+const WINDOW: Duration = Duration::from_secs(60);
+/// Expired windows are swept once this many clients are being tracked.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/// What a request counts against: the API key it authenticated with, or otherwise its address.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum ClientKey {
+    /// SHA-256 of the key, so the key itself isn't kept around.
+    ApiKey([u8; 32]),
+    Ip(IpAddr),
+}
+
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    count: u32,
+}
+
+/// Fixed one-minute request windows per client.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    per_minute: u32,
+    windows: Mutex<HashMap<ClientKey, Window>>,
+    rejected: AtomicU64,
+}
+
+impl RateLimiter {
+    /// A limit of 0 lets every request through.
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            windows: Mutex::new(HashMap::new()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts a request made at `now`, returning how long the client must wait if it's over the limit.
+    fn check(&self, client: ClientKey, now: Instant) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+
+        let mut windows = self.windows.lock();
+        if windows.len() >= MAX_TRACKED_CLIENTS {
+            windows.retain(|_, window| now.duration_since(window.started) < WINDOW);
+        }
+
+        let window = windows.entry(client).or_insert(Window { started: now, count: 0 });
+        if now.duration_since(window.started) >= WINDOW {
+            *window = Window { started: now, count: 0 };
+        }
+        if window.count >= self.per_minute {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(WINDOW.saturating_sub(now.duration_since(window.started)));
+        }
+        window.count += 1;
+        Ok(())
+    }
+
+    pub fn per_minute(&self) -> u32 {
+        self.per_minute
+    }
+
+    /// Requests turned away with 429 since the server started.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Clients that have used up the current window.
+    pub fn limited_clients(&self) -> usize {
+        let now = Instant::now();
+        self.windows
+            .lock()
+            .values()
+            .filter(|window| now.duration_since(window.started) < WINDOW && window.count >= self.per_minute)
+            .count()
+    }
+}
+
+impl ScreenshotProcessor {
+    /// Counts a request presenting `api_key` from `ip` against its client's limit, returning how
+    /// long the client must wait if it's over.
+    pub(crate) fn check_rate_limit(&self, api_key: Option<&str>, ip: IpAddr) -> Result<(), Duration> {
+        // Only a valid key gets its own bucket, so rotating made-up keys doesn't escape the IP limit
+        let client = match (api_key, self.api_key()) {
+            (Some(provided), Some(expected)) if auth::constant_time_eq(provided.as_bytes(), expected.as_bytes()) => {
+                ClientKey::ApiKey(Sha256::digest(provided.as_bytes()).into())
+            }
+            _ => ClientKey::Ip(ip),
+        };
+        self.rate_limiter.check(client, Instant::now())
+    }
+}
+
+/// Rejects requests beyond `rate_limit_per_minute` per API key, or per IP for clients without a valid key.
+pub async fn limit_requests(
+    State(processor): State<ScreenshotProcessor>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match processor.check_rate_limit(auth::extract_api_key(request.headers()), addr.ip()) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            // Round up so clients don't retry a moment too early
+            let retry_after = retry_after.as_secs() + 1;
+            warn!(
+                "🚦 Rate limited {} on {}, retry in {}s",
+                addr.ip(),
+                request.uri().path(),
+                retry_after
+            );
+            (
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                ApiError::new(
+                    ErrorCode::RateLimited,
+                    format!("Too many requests, try again in {} seconds", retry_after),
+                ),
+            )
+                .into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn client(last_octet: u8) -> ClientKey {
+        ClientKey::Ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last_octet)))
+    }
+
+    #[test]
+    fn rejects_requests_over_the_limit_until_the_window_ends() {
+        let limiter = RateLimiter::new(3);
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check(client(1), start).is_ok());
+        }
+        let wait = limiter.check(client(1), start + Duration::from_secs(20)).unwrap_err();
+        assert_eq!(wait, Duration::from_secs(40));
+        assert_eq!(limiter.rejected(), 1);
+
+        assert!(limiter.check(client(1), start + WINDOW).is_ok());
+    }
+
+    #[test]
+    fn counts_each_client_separately() {
+        let limiter = RateLimiter::new(1);
+        let now = Instant::now();
+
+        assert!(limiter.check(client(1), now).is_ok());
+        assert!(limiter.check(client(1), now).is_err());
+        assert!(limiter.check(client(2), now).is_ok());
+        assert!(limiter.check(ClientKey::ApiKey([0; 32]), now).is_ok());
+    }
+
+    #[test]
+    fn zero_is_unlimited() {
+        let limiter = RateLimiter::new(0);
+        let now = Instant::now();
+
+        for _ in 0..1000 {
+            assert!(limiter.check(client(1), now).is_ok());
+        }
+        assert_eq!(limiter.rejected(), 0);
+    }
+}
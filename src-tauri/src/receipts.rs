@@ -0,0 +1,308 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{io::Write, path::Path};
+use tracing::{debug, info, warn};
+
+use crate::{archive::csv_field, usage, AnalysisData, OutputSchema, ScreenshotProcessor};
+
+const RECEIPT_MAX_TOKENS: u32 = 1_500;
+/// Content types that get the receipt pipeline.
+const RECEIPT_CONTENT_TYPES: &[&str] = &["receipt", "invoice"];
+
+const CSV_COLUMNS: &[&str] = &[
+    "date",
+    "merchant",
+    "kind",
+    "currency",
+    "subtotal",
+    "tax",
+    "total",
+    "items",
+    "analysis_id",
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReceiptKind {
+    #[default]
+    Receipt,
+    Invoice,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LineItem {
+    pub description: String,
+    pub quantity: Option<f64>,
+    pub unit_price: Option<f64>,
+    pub amount: Option<f64>,
+}
+
+/// What was bought, from whom and for how much, read off a receipt or invoice.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReceiptData {
+    pub analysis_id: String,
+    pub kind: ReceiptKind,
+    pub merchant: String,
+    /// The date on the document, as `YYYY-MM-DD`.
+    pub date: Option<String>,
+    /// ISO 4217 code, e.g. `EUR`.
+    pub currency: Option<String>,
+    pub line_items: Vec<LineItem>,
+    pub subtotal: Option<f64>,
+    pub tax: Option<f64>,
+    pub total: Option<f64>,
+    pub extracted_at: Option<DateTime<Utc>>,
+}
+
+fn receipt_schema() -> OutputSchema {
+    let amount = serde_json::json!({ "type": ["number", "null"] });
+    OutputSchema {
+        name: "record_receipt",
+        description: "Record the merchant, date, line items and totals of the receipt or invoice.",
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "kind": { "type": "string", "enum": ["receipt", "invoice"] },
+                "merchant": { "type": "string", "description": "Business that issued it" },
+                "date": {
+                    "type": ["string", "null"],
+                    "description": "Date on the document as YYYY-MM-DD, or null"
+                },
+                "currency": {
+                    "type": ["string", "null"],
+                    "description": "ISO 4217 currency code, e.g. USD, or null if unclear"
+                },
+                "line_items": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "description": { "type": "string" },
+                            "quantity": amount,
+                            "unit_price": amount,
+                            "amount": amount
+                        },
+                        "required": ["description", "quantity", "unit_price", "amount"],
+                        "additionalProperties": false
+                    }
+                },
+                "subtotal": amount,
+                "tax": amount,
+                "total": amount
+            },
+            "required": ["kind", "merchant", "date", "currency", "line_items", "subtotal", "tax", "total"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+const RECEIPT_PROMPT: &str = "This screenshot shows a receipt or invoice. Extract the merchant, the date, \
+every line item, the subtotal, tax and total, and the currency, exactly as printed. Amounts are plain \
+numbers without currency symbols; use null for anything not shown.";
+
+const TEXT_FORMAT: &str = "\n\nAnswer in exactly this format:\n\
+KIND: <receipt|invoice>\n\
+MERCHANT: <name>\n\
+DATE: <YYYY-MM-DD or none>\n\
+CURRENCY: <ISO code or none>\n\
+ITEM: <description> | <quantity> | <unit price> | <amount>\n\
+SUBTOTAL: <amount or none>\n\
+TAX: <amount or none>\n\
+TOTAL: <amount or none>";
+
+/// Parses an amount such as `1,234.50` or `$12`, ignoring currency symbols.
+fn parse_amount(value: &str) -> Option<f64> {
+    let cleaned: String = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '.' | '-'))
+        .collect();
+    cleaned.parse().ok()
+}
+
+/// Parses a response in `TEXT_FORMAT`, for providers without structured output.
+fn parse_receipt_text(text: &str) -> ReceiptData {
+    let mut receipt = ReceiptData::default();
+    let optional = |value: &str| {
+        let value = value.trim();
+        (!value.is_empty() && !value.eq_ignore_ascii_case("none")).then(|| value.to_string())
+    };
+
+    for line in text.lines() {
+        let Some((field, value)) = line.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match field.trim() {
+            "KIND" if value.eq_ignore_ascii_case("invoice") => receipt.kind = ReceiptKind::Invoice,
+            "MERCHANT" => receipt.merchant = value.to_string(),
+            "DATE" => receipt.date = optional(value),
+            "CURRENCY" => receipt.currency = optional(value),
+            "ITEM" => {
+                let parts: Vec<&str> = value.split('|').map(str::trim).collect();
+                receipt.line_items.push(LineItem {
+                    description: parts.first().unwrap_or(&"").to_string(),
+                    quantity: parts.get(1).and_then(|v| parse_amount(v)),
+                    unit_price: parts.get(2).and_then(|v| parse_amount(v)),
+                    amount: parts.get(3).and_then(|v| parse_amount(v)),
+                });
+            }
+            "SUBTOTAL" => receipt.subtotal = parse_amount(value),
+            "TAX" => receipt.tax = parse_amount(value),
+            "TOTAL" => receipt.total = parse_amount(value),
+            _ => {}
+        }
+    }
+    receipt
+}
+
+fn format_amount(amount: Option<f64>) -> String {
+    amount.map(|amount| format!("{:.2}", amount)).unwrap_or_default()
+}
+
+fn csv_row(receipt: &ReceiptData) -> String {
+    let items: Vec<String> = receipt
+        .line_items
+        .iter()
+        .map(|item| match (item.quantity, item.amount) {
+            (Some(quantity), Some(amount)) => format!("{} x{} {:.2}", item.description, quantity, amount),
+            (None, Some(amount)) => format!("{} {:.2}", item.description, amount),
+            _ => item.description.clone(),
+        })
+        .collect();
+    let kind = match receipt.kind {
+        ReceiptKind::Receipt => "receipt",
+        ReceiptKind::Invoice => "invoice",
+    };
+    let row = [
+        receipt.date.as_deref().unwrap_or_default(),
+        &receipt.merchant,
+        kind,
+        receipt.currency.as_deref().unwrap_or_default(),
+        &format_amount(receipt.subtotal),
+        &format_amount(receipt.tax),
+        &format_amount(receipt.total),
+        &items.join("; "),
+        &receipt.analysis_id,
+    ];
+    format!("{}\r\n", row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","))
+}
+
+/// Receipts as CSV, one row per receipt.
+pub(crate) fn to_csv(receipts: &[ReceiptData]) -> String {
+    let mut csv = format!("{}\r\n", CSV_COLUMNS.join(","));
+    for receipt in receipts {
+        csv.push_str(&csv_row(receipt));
+    }
+    csv
+}
+
+/// Appends a receipt to the ledger CSV, writing the header first if the file is new.
+fn append_to_ledger(path: &Path, receipt: &ReceiptData) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let is_new = std::fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open ledger {}", path.display()))?;
+    if is_new {
+        write!(file, "{}\r\n", CSV_COLUMNS.join(","))?;
+    }
+    file.write_all(csv_row(receipt).as_bytes())?;
+    Ok(())
+}
+
+pub(crate) fn is_receipt(content_type: &str) -> bool {
+    RECEIPT_CONTENT_TYPES
+        .iter()
+        .any(|kind| content_type.trim().eq_ignore_ascii_case(kind))
+}
+
+impl ScreenshotProcessor {
+    /// Reads the details off a receipt or invoice screenshot.
+    async fn extract_receipt(&self, analysis_id: &str, analysis: &AnalysisData) -> Result<ReceiptData> {
+        let image = self.llm_image(&analysis.image_data).await;
+        let (structured, records) = usage::track(self.provider.complete_json(
+            RECEIPT_PROMPT,
+            &[&image],
+            &receipt_schema(),
+            RECEIPT_MAX_TOKENS,
+        ))
+        .await;
+        self.save_usage(Some(analysis_id), &records);
+
+        let mut receipt = match structured.and_then(|json| Ok(serde_json::from_str::<ReceiptData>(&json)?)) {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                debug!("Structured receipt extraction unavailable, parsing text instead: {}", e);
+                let prompt = format!("{}{}", RECEIPT_PROMPT, TEXT_FORMAT);
+                let (text, records) =
+                    usage::track(self.provider.complete(&prompt, &[&image], RECEIPT_MAX_TOKENS)).await;
+                self.save_usage(Some(analysis_id), &records);
+                parse_receipt_text(&text?)
+            }
+        };
+
+        receipt.analysis_id = analysis_id.to_string();
+        receipt.merchant = receipt.merchant.trim().to_string();
+        receipt.currency = receipt
+            .currency
+            .map(|currency| currency.trim().to_uppercase())
+            .filter(|currency| currency.len() == 3);
+        receipt.line_items.retain(|item| !item.description.trim().is_empty());
+        receipt.extracted_at = Some(Utc::now());
+        Ok(receipt)
+    }
+
+    /// Runs receipt extraction in the background when a new analysis is a receipt or invoice, saving
+    /// the result and appending it to the ledger if one is configured.
+    pub(crate) fn process_receipt(&self, analysis_id: &str, analysis: &AnalysisData) {
+        if !self.config.receipt_extraction || !is_receipt(&analysis.content_analysis.content_type) {
+            return;
+        }
+
+        let processor = self.clone();
+        let analysis_id = analysis_id.to_string();
+        let analysis = analysis.clone();
+        tokio::spawn(async move {
+            let receipt = match processor.extract_receipt(&analysis_id, &analysis).await {
+                Ok(receipt) => receipt,
+                Err(e) => {
+                    warn!("Receipt extraction for {} failed: {}", analysis_id, e);
+                    return;
+                }
+            };
+            if let Err(e) = processor.store.save_receipt(&receipt) {
+                warn!("Failed to save receipt for {}: {}", analysis_id, e);
+                return;
+            }
+            info!(
+                "🧾 Extracted {} from {} ({} items)",
+                receipt.total.map(|total| format!("{:.2}", total)).unwrap_or_else(|| "a receipt".to_string()),
+                receipt.merchant,
+                receipt.line_items.len()
+            );
+            if let Some(ref ledger) = processor.config.receipt_ledger {
+                if let Err(e) = append_to_ledger(ledger, &receipt) {
+                    warn!("Failed to append receipt to {}: {}", ledger.display(), e);
+                }
+            }
+        });
+    }
+
+    pub fn get_receipt(&self, analysis_id: &str) -> Result<Option<ReceiptData>> {
+        self.store.receipt(analysis_id)
+    }
+
+    /// Writes every extracted receipt to `path` as CSV, returning how many were written.
+    pub fn export_receipts(&self, path: &Path) -> Result<usize> {
+        let receipts = self.store.receipts()?;
+        std::fs::write(path, to_csv(&receipts)).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(receipts.len())
+    }
+}
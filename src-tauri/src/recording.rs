@@ -0,0 +1,330 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use image::{imageops::FilterType, DynamicImage, GenericImage, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+use tokio::process::Command;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{animation, dedupe, InvalidImage, ProcessingResponse, ScreenshotMetadata, ScreenshotProcessor};
+
+/// Screen recordings as saved by iOS, macOS and most Windows and Linux recorders.
+pub const RECORDING_EXTENSIONS: &[&str] = &["mov", "mp4", "m4v"];
+
+/// Frames are extracted with `ffmpeg` and recordings measured with `ffprobe`.
+const FFMPEG: &str = "ffmpeg";
+const FFPROBE: &str = "ffprobe";
+/// Width of each frame in the storyboard, which keeps portrait phone frames legible.
+const FRAME_WIDTH: u32 = 480;
+/// Pixels between frames in the storyboard.
+const FRAME_GAP: u32 = 12;
+/// Sampled frames whose perceptual hashes differ by at most this many bits show the same screen.
+const SAME_SCREEN_DISTANCE: u32 = 4;
+/// Brands of the ISO media files HEIC and AVIF photos are, which aren't recordings.
+const IMAGE_BRANDS: &[&[u8]] = &[b"heic", b"heix", b"hevc", b"heim", b"heis", b"mif1", b"msf1", b"avif", b"avis"];
+
+/// Which screen recordings are analyzed, as a storyboard of their keyframes. Animated GIFs are
+/// storyboarded the same way.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordingConfig {
+    /// Accept recordings from watched folders and uploads; needs `ffmpeg` installed.
+    pub enabled: bool,
+    /// Longest recording accepted, in seconds.
+    pub max_duration_secs: u32,
+    /// Largest recording file accepted, in bytes.
+    pub max_bytes: usize,
+    /// Most keyframes put on the storyboard of a recording or animated GIF.
+    pub max_frames: u32,
+}
+
+impl Default for RecordingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_duration_secs: 180,
+            max_bytes: 200 * 1024 * 1024,
+            max_frames: 9,
+        }
+    }
+}
+
+impl RecordingConfig {
+    /// Checks a recording's size before it's read.
+    pub fn check_size(&self, size_bytes: u64) -> Result<()> {
+        let size = usize::try_from(size_bytes).unwrap_or(usize::MAX);
+        if size > self.max_bytes {
+            return Err(anyhow!(
+                "Recording too large ({:.1}MB, max {:.1}MB)",
+                size as f64 / 1024.0 / 1024.0,
+                self.max_bytes as f64 / 1024.0 / 1024.0
+            ));
+        }
+        Ok(())
+    }
+
+    /// Request body limit for a single base64-encoded recording upload.
+    pub fn max_upload_body(&self) -> usize {
+        if self.enabled {
+            self.max_bytes.div_ceil(3) * 4 + 64 * 1024
+        } else {
+            0
+        }
+    }
+}
+
+/// Whether `bytes` start like an MP4 or QuickTime movie, as opposed to an image.
+pub fn is_recording(bytes: &[u8]) -> bool {
+    match bytes.get(4..8) {
+        Some(b"ftyp") => bytes.get(8..12).is_some_and(|brand| !IMAGE_BRANDS.contains(&brand)),
+        // Older QuickTime files start with other atoms
+        Some(b"moov" | b"mdat" | b"wide" | b"free") => true,
+        _ => false,
+    }
+}
+
+/// Like `is_recording`, decoding only the start of a base64 upload, which may be a data URL.
+pub fn is_recording_base64(data: &str) -> bool {
+    let data = data.rsplit(',').next().unwrap_or_default();
+    // 16 base64 characters are the first 12 bytes, enough for the file type box
+    let Some(head) = data.get(..16) else {
+        return false;
+    };
+    general_purpose::STANDARD
+        .decode(head)
+        .is_ok_and(|bytes| is_recording(&bytes))
+}
+
+pub fn is_recording_path(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| RECORDING_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// A recording written out for ffmpeg, deleted when dropped.
+struct TempRecording(PathBuf);
+
+impl TempRecording {
+    fn write(bytes: &[u8]) -> Result<Self> {
+        let path = std::env::temp_dir().join(format!("screenshot-ai-{}.mov", Uuid::new_v4()));
+        std::fs::write(&path, bytes)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for TempRecording {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+async fn run(program: &str, args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| anyhow!("Couldn't run {} for the screen recording (is it installed?): {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(InvalidImage(format!(
+            "Couldn't read the screen recording: {}",
+            stderr.lines().next().unwrap_or("unknown error")
+        ))
+        .into());
+    }
+    Ok(output.stdout)
+}
+
+async fn duration_secs(path: &Path) -> Result<f64> {
+    let path = path.to_string_lossy();
+    let output = run(
+        FFPROBE,
+        &["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1", &path],
+    )
+    .await?;
+    String::from_utf8_lossy(&output)
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| secs.is_finite() && *secs > 0.0)
+        .ok_or_else(|| InvalidImage("Couldn't read the screen recording's duration".to_string()).into())
+}
+
+async fn frame_at(path: &Path, secs: f64) -> Result<DynamicImage> {
+    let path = path.to_string_lossy();
+    let at = format!("{:.3}", secs);
+    let scale = format!("scale={}:-2", FRAME_WIDTH);
+    let png = run(
+        FFMPEG,
+        &["-v", "error", "-ss", &at, "-i", &path, "-frames:v", "1", "-vf", &scale, "-f", "image2pipe", "-vcodec", "png", "-"],
+    )
+    .await?;
+    image::load_from_memory(&png).map_err(|e| anyhow!("Failed to decode recording frame: {}", e))
+}
+
+/// Samples twice as many frames as wanted, evenly over the recording, and keeps those that show
+/// a new screen, thinned out evenly if there are still too many.
+async fn keyframes(path: &Path, duration: f64, max_frames: u32) -> Result<Vec<DynamicImage>> {
+    let samples = max_frames.max(1) * 2;
+    let mut frames: Vec<(u64, DynamicImage)> = Vec::new();
+    for i in 0..samples {
+        let frame = frame_at(path, duration * (i as f64 + 0.5) / samples as f64).await?;
+        let hash = dedupe::dhash(&frame);
+        if frames.last().is_none_or(|(last, _)| (last ^ hash).count_ones() > SAME_SCREEN_DISTANCE) {
+            frames.push((hash, frame));
+        }
+    }
+
+    let max_frames = max_frames.max(1) as usize;
+    let kept = frames.len();
+    let mut frames: Vec<_> = frames.into_iter().map(|(_, frame)| Some(frame)).collect();
+    Ok((0..kept.min(max_frames))
+        .filter_map(|i| frames[i * kept / kept.min(max_frames)].take())
+        .collect())
+}
+
+/// Lays the frames out in reading order on a white grid, encoded as JPEG. Frames wider than
+/// `FRAME_WIDTH` are scaled down to it.
+fn compose(frames: &[DynamicImage]) -> Result<Vec<u8>> {
+    let frames: Vec<DynamicImage> = frames
+        .iter()
+        .map(|frame| match frame.width() > FRAME_WIDTH {
+            true => frame.resize(FRAME_WIDTH, u32::MAX, FilterType::Triangle),
+            false => frame.clone(),
+        })
+        .collect();
+    let columns = (frames.len() as f64).sqrt().ceil().max(1.0) as u32;
+    let rows = (frames.len() as u32).div_ceil(columns);
+    let cell_width = frames.iter().map(|f| f.width()).max().unwrap_or(FRAME_WIDTH);
+    let cell_height = frames.iter().map(|f| f.height()).max().unwrap_or(FRAME_WIDTH);
+
+    let mut canvas = RgbImage::from_pixel(
+        columns * cell_width + (columns + 1) * FRAME_GAP,
+        rows * cell_height + (rows + 1) * FRAME_GAP,
+        Rgb([255, 255, 255]),
+    );
+    for (i, frame) in frames.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        canvas.copy_from(
+            &frame.to_rgb8(),
+            FRAME_GAP + column * (cell_width + FRAME_GAP),
+            FRAME_GAP + row * (cell_height + FRAME_GAP),
+        )?;
+    }
+
+    let mut jpeg = Vec::new();
+    DynamicImage::ImageRgb8(canvas).write_to(&mut Cursor::new(&mut jpeg), image::ImageOutputFormat::Jpeg(85))?;
+    Ok(jpeg)
+}
+
+/// A recording or animated GIF as one image of its frames, with the frames kept for storage.
+#[derive(Debug, Clone)]
+pub(crate) struct Storyboard {
+    /// The frames laid out on a grid, as JPEG.
+    pub image: Vec<u8>,
+    pub frames: Vec<DynamicImage>,
+}
+
+impl Storyboard {
+    /// Lays out `frames`, noting their count in the metadata so they're analyzed as one flow.
+    pub(crate) async fn new(frames: Vec<DynamicImage>, metadata: &mut Option<ScreenshotMetadata>) -> Result<Self> {
+        let count = frames.len();
+        let (image, frames) = tokio::task::spawn_blocking(move || compose(&frames).map(|image| (image, frames)))
+            .await
+            .map_err(|e| anyhow!("Storyboard task failed: {}", e))??;
+        metadata.get_or_insert_with(Default::default).recording_frames = Some(count as u32);
+        Ok(Self { image, frames })
+    }
+}
+
+/// Whether a base64 upload might be a recording or animated GIF, judging by its first bytes only.
+pub(crate) fn may_need_storyboard(data: &str) -> bool {
+    is_recording_base64(data) || animation::is_gif_base64(data)
+}
+
+impl ScreenshotProcessor {
+    /// Storyboards a screen recording or animated GIF; `None` for any other image.
+    pub(crate) async fn storyboard(
+        &self,
+        bytes: &[u8],
+        metadata: &mut Option<ScreenshotMetadata>,
+    ) -> Result<Option<Storyboard>> {
+        if is_recording(bytes) {
+            return self.storyboard_recording(bytes, metadata).await.map(Some);
+        }
+        if animation::is_animated_gif(bytes) {
+            return self.storyboard_animation(bytes, metadata).await.map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Turns a screen recording into a storyboard of its keyframes.
+    async fn storyboard_recording(&self, bytes: &[u8], metadata: &mut Option<ScreenshotMetadata>) -> Result<Storyboard> {
+        let config = &self.config.recordings;
+        if !config.enabled {
+            return Err(InvalidImage("Screen recordings aren't accepted".to_string()).into());
+        }
+        config
+            .check_size(bytes.len() as u64)
+            .map_err(|e| InvalidImage(e.to_string()))?;
+
+        let recording = TempRecording::write(bytes)?;
+        let duration = duration_secs(&recording.0).await?;
+        if duration > config.max_duration_secs as f64 {
+            return Err(InvalidImage(format!(
+                "Recording too long ({:.0}s, max {}s)",
+                duration, config.max_duration_secs
+            ))
+            .into());
+        }
+
+        let frames = keyframes(&recording.0, duration, config.max_frames).await?;
+        info!("🎞️ Storyboarded {:.0}s screen recording as {} keyframes", duration, frames.len());
+        Storyboard::new(frames, metadata).await
+    }
+
+    /// Analyzes a storyboard as one screenshot, then stores its frames with the analysis.
+    pub(crate) async fn process_storyboard(
+        &self,
+        storyboard: &Storyboard,
+        metadata: Option<ScreenshotMetadata>,
+    ) -> Result<ProcessingResponse> {
+        let image_base64 = general_purpose::STANDARD.encode(&storyboard.image);
+        let response = self.process_image(&image_base64, metadata).await?;
+
+        // A near-duplicate's analysis already has its own frames
+        if let Some(analysis_id) = response.analysis_id.as_deref().filter(|_| !response.duplicate) {
+            let frames = storyboard.frames.clone();
+            let encoded = tokio::task::spawn_blocking(move || {
+                frames
+                    .iter()
+                    .map(|frame| {
+                        let mut png = Vec::new();
+                        frame.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+                        Ok(png)
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await
+            .map_err(|e| anyhow!("Frame encoding task failed: {}", e))
+            .and_then(|result| result);
+            if let Err(e) = encoded.and_then(|frames| self.store.save_frames(analysis_id, &frames)) {
+                warn!("Failed to store the frames of analysis {}: {}", analysis_id, e);
+            }
+        }
+        Ok(response)
+    }
+
+    /// The frames of a recording or animated GIF analysis, as PNG, in order.
+    pub fn analysis_frames(&self, analysis_id: &str) -> Result<Vec<Vec<u8>>> {
+        self.store.frames(analysis_id)
+    }
+}
@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{io::Cursor, process::Stdio};
+use thiserror::Error;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{info, warn};
+
+use crate::{ProcessedImage, ProgressStage, ScreenshotProcessor};
+
+/// The OCR engine, run as `tesseract stdin stdout tsv`.
+const TESSERACT: &str = "tesseract";
+/// Pixels added around each detected region so glyph edges don't survive the blur.
+const REGION_PADDING: u32 = 4;
+
+static EMAIL: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap());
+static PHONE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{2,4}\)|\d{2,4})[\s.-]?\d{3,4}[\s.-]?\d{3,4}").unwrap()
+});
+static API_KEY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(concat!(
+        r"\b(?:sk-(?:ant-)?[A-Za-z0-9_-]{20,}",
+        r"|AKIA[0-9A-Z]{16}",
+        r"|gh[pousr]_[A-Za-z0-9]{36,}",
+        r"|xox[abpr]-[A-Za-z0-9-]{10,}",
+        r"|AIza[0-9A-Za-z_-]{35}",
+        r"|\d{8,10}:[A-Za-z0-9_-]{35})",
+    ))
+    .unwrap()
+});
+static CARD_NUMBER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap());
+
+/// What to do when a screenshot appears to contain personal data or credentials.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionMode {
+    /// Send images as they are.
+    #[default]
+    Off,
+    /// Blur the detected text before the image is sent anywhere.
+    Blur,
+    /// Refuse to analyze the screenshot.
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensitiveKind {
+    Email,
+    Phone,
+    ApiKey,
+    CardNumber,
+    /// A photo from a camera rather than a screenshot, flagged by the content filter.
+    CameraPhoto,
+    /// A photo showing a lot of skin, flagged by the content filter.
+    ExposedSkin,
+}
+
+#[derive(Debug, Error)]
+#[error("Screenshot not analyzed: it appears to contain {}", describe(.kinds))]
+pub struct SensitiveContentBlocked {
+    pub kinds: Vec<SensitiveKind>,
+}
+
+pub(crate) fn describe(kinds: &[SensitiveKind]) -> String {
+    kinds
+        .iter()
+        .map(|kind| match kind {
+            SensitiveKind::Email => "an email address",
+            SensitiveKind::Phone => "a phone number",
+            SensitiveKind::ApiKey => "an API key",
+            SensitiveKind::CardNumber => "a card number",
+            SensitiveKind::CameraPhoto => "a camera photo",
+            SensitiveKind::ExposedSkin => "a revealing photo",
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    left: u32,
+    top: u32,
+    right: u32,
+    bottom: u32,
+}
+
+impl Region {
+    fn union(self, other: Region) -> Region {
+        Region {
+            left: self.left.min(other.left),
+            top: self.top.min(other.top),
+            right: self.right.max(other.right),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct OcrWord {
+    /// Block, paragraph and line numbers, identifying the line the word is on.
+    line: (u32, u32, u32),
+    region: Region,
+    text: String,
+}
+
+/// Parses the word rows of Tesseract's TSV output.
+fn parse_tsv(tsv: &str) -> Vec<OcrWord> {
+    tsv.lines()
+        .skip(1)
+        .filter_map(|row| {
+            let cols: Vec<&str> = row.split('\t').collect();
+            // level 5 rows are words; the text column is last
+            if cols.len() < 12 || cols[0] != "5" || cols[11].trim().is_empty() {
+                return None;
+            }
+            let num = |i: usize| cols[i].parse::<u32>().ok();
+            let (left, top, width, height) = (num(6)?, num(7)?, num(8)?, num(9)?);
+            Some(OcrWord {
+                line: (num(2)?, num(3)?, num(4)?),
+                region: Region {
+                    left,
+                    top,
+                    right: left + width,
+                    bottom: top + height,
+                },
+                text: cols[11].trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Whether `number` passes the Luhn checksum card numbers carry.
+fn luhn_valid(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2 == 1, d * 2) {
+            (true, doubled) if doubled > 9 => doubled - 9,
+            (true, doubled) => doubled,
+            (false, _) => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// Sensitive matches in the OCR text, with the image regions of the words they cover.
+fn find_sensitive(words: &[OcrWord]) -> Vec<(SensitiveKind, Region)> {
+    let mut findings = Vec::new();
+
+    let mut start = 0;
+    while start < words.len() {
+        let end = start + words[start..].iter().take_while(|w| w.line == words[start].line).count();
+        let line = &words[start..end];
+        start = end;
+
+        // Join the line's words, remembering where each one sits in the text
+        let mut text = String::new();
+        let mut spans = Vec::with_capacity(line.len());
+        for word in line {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            spans.push((text.len(), text.len() + word.text.len()));
+            text.push_str(&word.text);
+        }
+
+        let mut claimed: Vec<(usize, usize)> = Vec::new();
+        let patterns: [(SensitiveKind, &Regex); 4] = [
+            (SensitiveKind::ApiKey, &API_KEY),
+            (SensitiveKind::Email, &EMAIL),
+            (SensitiveKind::CardNumber, &CARD_NUMBER),
+            (SensitiveKind::Phone, &PHONE),
+        ];
+        for (kind, pattern) in patterns {
+            for m in pattern.find_iter(&text) {
+                // Earlier patterns win, so a card number isn't also reported as a phone number
+                if claimed.iter().any(|&(s, e)| m.start() < e && s < m.end()) {
+                    continue;
+                }
+                if kind == SensitiveKind::CardNumber && !luhn_valid(m.as_str()) {
+                    continue;
+                }
+                if kind == SensitiveKind::Phone && m.as_str().chars().filter(char::is_ascii_digit).count() < 9 {
+                    continue;
+                }
+                let region = spans
+                    .iter()
+                    .zip(line)
+                    .filter(|((s, e), _)| m.start() < *e && *s < m.end())
+                    .map(|(_, word)| word.region)
+                    .reduce(Region::union);
+                if let Some(region) = region {
+                    claimed.push((m.start(), m.end()));
+                    findings.push((kind, region));
+                }
+            }
+        }
+    }
+
+    findings
+}
+
+async fn ocr(bytes: &[u8]) -> Result<Vec<OcrWord>> {
+    let mut child = Command::new(TESSERACT)
+        .args(["stdin", "stdout", "tsv"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Couldn't run {} for the sensitive content scan (is it installed?): {}", TESSERACT, e))?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("OCR stdin unavailable"))?;
+    let write = async move {
+        let result = stdin.write_all(bytes).await;
+        drop(stdin);
+        result
+    };
+    let (written, output) = tokio::join!(write, child.wait_with_output());
+    written?;
+    let output = output?;
+    if !output.status.success() {
+        return Err(anyhow!("{} exited with {}", TESSERACT, output.status));
+    }
+    Ok(parse_tsv(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Blurs every region, re-encoding the result as PNG.
+fn blur_regions(image: &DynamicImage, regions: &[Region]) -> Result<ProcessedImage> {
+    let mut image = image.clone();
+    let (width, height) = image.dimensions();
+
+    for region in regions {
+        let left = region.left.saturating_sub(REGION_PADDING);
+        let top = region.top.saturating_sub(REGION_PADDING);
+        let right = (region.right + REGION_PADDING).min(width);
+        let bottom = (region.bottom + REGION_PADDING).min(height);
+        if right <= left || bottom <= top {
+            continue;
+        }
+
+        // A sigma on the order of the text height leaves nothing legible
+        let sigma = ((bottom - top) as f32).max(8.0);
+        let blurred = image.crop_imm(left, top, right - left, bottom - top).blur(sigma);
+        image::imageops::replace(&mut image, &blurred, left.into(), top.into());
+    }
+
+    let mut encoded = Vec::new();
+    image.write_to(&mut Cursor::new(&mut encoded), ImageOutputFormat::Png)?;
+    Ok(ProcessedImage {
+        base64_data: general_purpose::STANDARD.encode(&encoded),
+        media_type: "image/png".to_string(),
+        size_bytes: encoded.len(),
+    })
+}
+
+impl ScreenshotProcessor {
+    fn redaction_mode(&self, source: &str) -> RedactionMode {
+        self.config
+            .redaction_sources
+            .get(source)
+            .copied()
+            .unwrap_or(self.config.redaction_mode)
+    }
+
+    /// Scans the screenshot for personal data and credentials before it leaves the machine.
+    ///
+    /// Returns a blurred copy when anything was found in blur mode, and fails in block mode. The scan
+    /// failing also fails the analysis, rather than sending an image that wasn't checked.
+    pub(crate) async fn redact_sensitive(
+        &self,
+        analysis_id: &str,
+        image: &ProcessedImage,
+        source: &str,
+    ) -> Result<Option<ProcessedImage>> {
+        let mode = self.redaction_mode(source);
+        if mode == RedactionMode::Off {
+            return Ok(None);
+        }
+
+        let bytes = general_purpose::STANDARD
+            .decode(&image.base64_data)
+            .map_err(|e| anyhow!("Invalid base64: {}", e))?;
+        let findings = find_sensitive(&ocr(&bytes).await?);
+        if findings.is_empty() {
+            return Ok(None);
+        }
+
+        let mut kinds: Vec<SensitiveKind> = Vec::new();
+        for (kind, _) in &findings {
+            if !kinds.contains(kind) {
+                kinds.push(*kind);
+            }
+        }
+        self.emit_progress(
+            analysis_id,
+            ProgressStage::SensitiveContent {
+                kinds: kinds.clone(),
+                blocked: mode == RedactionMode::Block,
+            },
+        );
+
+        if mode == RedactionMode::Block {
+            warn!("🛡️ Blocked screenshot containing {}", describe(&kinds));
+            return Err(SensitiveContentBlocked { kinds }.into());
+        }
+
+        let regions: Vec<Region> = findings.into_iter().map(|(_, region)| region).collect();
+        let redacted = tokio::task::spawn_blocking(move || {
+            let decoded = image::load_from_memory(&bytes).map_err(|e| anyhow!("Failed to decode image: {}", e))?;
+            blur_regions(&decoded, &regions)
+        })
+        .await
+        .map_err(|e| anyhow!("Redaction task failed: {}", e))??;
+
+        info!("🛡️ Blurred {} in screenshot", describe(&kinds));
+        Ok(Some(redacted))
+    }
+}
@@ -0,0 +1,476 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use reqwest::{header, Client, Url};
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use teloxide::utils::html;
+use tracing::{debug, info, warn};
+
+use crate::{prompts, usage, webpage, AnalysisData, OutputSchema, ScreenshotProcessor};
+
+const DUCKDUCKGO_URL: &str = "https://html.duckduckgo.com/html/";
+const BRAVE_URL: &str = "https://api.search.brave.com/res/v1/web/search";
+const TAVILY_URL: &str = "https://api.tavily.com/search";
+
+const QUERY_MAX_TOKENS: u32 = 300;
+const SOURCE_NOTES_MAX_TOKENS: u32 = 500;
+const REPORT_MAX_TOKENS: u32 = 3_000;
+/// Characters of each fetched page passed on to be summarized.
+const SOURCE_TEXT_MAX_CHARS: usize = 6_000;
+/// Characters per Telegram message, leaving room for HTML escaping.
+const TELEGRAM_CHUNK_CHARS: usize = 3_500;
+
+/// Web search backend used by deep research.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchProvider {
+    /// DuckDuckGo's HTML results page; needs no key.
+    #[default]
+    DuckDuckGo,
+    Brave,
+    Tavily,
+    /// A SearXNG instance at `searxng_url`.
+    Searxng,
+}
+
+/// Settings for the deep research agent behind the "Deep Research" button.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResearchConfig {
+    pub search_provider: SearchProvider,
+    /// Key for Brave or Tavily.
+    pub search_api_key: Option<String>,
+    pub searxng_url: Option<String>,
+    /// Search queries generated from the screenshot's topics.
+    pub max_queries: usize,
+    /// Results read per query.
+    pub results_per_query: usize,
+}
+
+impl Default for ResearchConfig {
+    fn default() -> Self {
+        Self {
+            search_provider: SearchProvider::DuckDuckGo,
+            search_api_key: None,
+            searxng_url: None,
+            max_queries: 3,
+            results_per_query: 3,
+        }
+    }
+}
+
+/// A web page the report draws on, numbered as it's cited.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResearchSource {
+    pub number: usize,
+    pub title: String,
+    pub url: String,
+    /// What the page says about the research question.
+    pub notes: String,
+}
+
+/// A long-form report on a screenshot's topics, written from web sources.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResearchReport {
+    pub analysis_id: String,
+    pub queries: Vec<String>,
+    pub sources: Vec<ResearchSource>,
+    /// The report text, citing sources as `[n]`.
+    pub report: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+struct SearchResult {
+    title: String,
+    url: String,
+    snippet: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchQueries {
+    queries: Vec<String>,
+}
+
+fn queries_schema() -> OutputSchema {
+    OutputSchema {
+        name: "record_search_queries",
+        description: "Record web search queries for researching the screenshot's subject.",
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "queries": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Distinct, specific web search queries, most important first"
+                }
+            },
+            "required": ["queries"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+/// What the research is about, from the screenshot's analysis.
+fn research_brief(analysis: &AnalysisData) -> String {
+    let content = &analysis.content_analysis;
+    let topics = if content.research_topics.is_empty() {
+        "none identified".to_string()
+    } else {
+        content.research_topics.join(", ")
+    };
+    format!(
+        "Screenshot summary: {}\nTopics: {}\nLikely intent: {}\nWebpage: {}",
+        analysis.brief_summary.trim(),
+        topics,
+        content.user_intent,
+        content.webpage_url.as_deref().unwrap_or("none"),
+    )
+}
+
+/// Strips list markers and quotes from a query the model wrote as a line of text.
+fn clean_query(line: &str) -> Option<String> {
+    let query = line
+        .trim()
+        .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '.' | ')' | '-' | '*' | '•'))
+        .trim()
+        .trim_matches('"')
+        .trim();
+    (!query.is_empty()).then(|| query.to_string())
+}
+
+async fn search_duckduckgo(client: &Client, query: &str, count: usize) -> Result<Vec<SearchResult>> {
+    let page = client
+        .get(DUCKDUCKGO_URL)
+        .query(&[("q", query)])
+        .header(header::USER_AGENT, "Mozilla/5.0 (compatible; ScreenshotAIStudio/0.1)")
+        .timeout(Duration::from_secs(15))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let document = Html::parse_document(&page);
+    let result_selector = Selector::parse(".result").expect("valid selector");
+    let link_selector = Selector::parse("a.result__a").expect("valid selector");
+    let snippet_selector = Selector::parse(".result__snippet").expect("valid selector");
+
+    Ok(document
+        .select(&result_selector)
+        .filter_map(|result| {
+            let link = result.select(&link_selector).next()?;
+            // Links go through a redirect that carries the target in `uddg`
+            let href = link.value().attr("href")?;
+            let href = Url::parse(href)
+                .or_else(|_| Url::parse(&format!("https:{}", href)))
+                .ok()?;
+            let url = href
+                .query_pairs()
+                .find(|(key, _)| key == "uddg")
+                .map(|(_, value)| value.to_string())
+                .unwrap_or_else(|| href.to_string());
+            Some(SearchResult {
+                title: link.text().collect::<String>().trim().to_string(),
+                url,
+                snippet: result
+                    .select(&snippet_selector)
+                    .next()
+                    .map(|s| s.text().collect::<String>().trim().to_string())
+                    .unwrap_or_default(),
+            })
+        })
+        .take(count)
+        .collect())
+}
+
+/// Reads `title`, `url` and a text field from each object in a JSON results array.
+fn json_results(results: Option<&serde_json::Value>, snippet_field: &str, count: usize) -> Vec<SearchResult> {
+    results
+        .and_then(|results| results.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|result| {
+            Some(SearchResult {
+                title: result["title"].as_str().unwrap_or_default().to_string(),
+                url: result["url"].as_str()?.to_string(),
+                snippet: result[snippet_field].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .take(count)
+        .collect()
+}
+
+impl ResearchConfig {
+    async fn search(&self, client: &Client, query: &str) -> Result<Vec<SearchResult>> {
+        let count = self.results_per_query.max(1);
+        let api_key = || {
+            self.search_api_key
+                .as_deref()
+                .filter(|key| !key.trim().is_empty())
+                .ok_or_else(|| anyhow!("{:?} search needs an API key", self.search_provider))
+        };
+
+        match self.search_provider {
+            SearchProvider::DuckDuckGo => search_duckduckgo(client, query, count).await,
+            SearchProvider::Brave => {
+                let response: serde_json::Value = client
+                    .get(BRAVE_URL)
+                    .query(&[("q", query), ("count", &count.to_string())])
+                    .header("X-Subscription-Token", api_key()?)
+                    .header(header::ACCEPT, "application/json")
+                    .timeout(Duration::from_secs(15))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(json_results(response.pointer("/web/results"), "description", count))
+            }
+            SearchProvider::Tavily => {
+                let response: serde_json::Value = client
+                    .post(TAVILY_URL)
+                    .json(&serde_json::json!({ "api_key": api_key()?, "query": query, "max_results": count }))
+                    .timeout(Duration::from_secs(30))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(json_results(response.get("results"), "content", count))
+            }
+            SearchProvider::Searxng => {
+                let base = self
+                    .searxng_url
+                    .as_deref()
+                    .filter(|url| !url.trim().is_empty())
+                    .ok_or_else(|| anyhow!("SearXNG search needs searxng_url"))?;
+                let response: serde_json::Value = client
+                    .get(format!("{}/search", base.trim_end_matches('/')))
+                    .query(&[("q", query), ("format", "json")])
+                    .timeout(Duration::from_secs(15))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                Ok(json_results(response.get("results"), "content", count))
+            }
+        }
+    }
+}
+
+/// Splits plain text into pieces of at most `max_chars`, preferring paragraph and line breaks.
+fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if !current.is_empty() && current.chars().count() + line.chars().count() + 1 > max_chars {
+            chunks.push(std::mem::take(&mut current).trim_end().to_string());
+        }
+        // A single line longer than a message is cut where it has to be
+        let mut line: Vec<char> = line.chars().collect();
+        while line.len() > max_chars {
+            chunks.push(line.drain(..max_chars).collect());
+        }
+        current.push_str(&line.into_iter().collect::<String>());
+        current.push('\n');
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current.trim_end().to_string());
+    }
+    chunks
+}
+
+/// The report as a thread of Telegram HTML messages, ending with the numbered sources.
+pub(crate) fn format_report_messages(report: &ResearchReport) -> Vec<String> {
+    let mut messages: Vec<String> = split_text(&report.report, TELEGRAM_CHUNK_CHARS)
+        .iter()
+        .map(|chunk| html::escape(chunk))
+        .collect();
+    if let Some(first) = messages.first_mut() {
+        *first = format!("🧠 <b>Deep Research</b>\n\n{}", first);
+    }
+
+    let mut sources = String::from("📚 <b>Sources</b>\n");
+    for source in &report.sources {
+        let entry = format!(
+            "\n[{}] <a href=\"{}\">{}</a>",
+            source.number,
+            html::escape(&source.url),
+            html::escape(&source.title)
+        );
+        if sources.chars().count() + entry.chars().count() > TELEGRAM_CHUNK_CHARS {
+            messages.push(std::mem::replace(&mut sources, String::from("📚 <b>Sources</b> (continued)\n")));
+        }
+        sources.push_str(&entry);
+    }
+    if !report.sources.is_empty() {
+        messages.push(sources);
+    }
+    messages
+}
+
+impl ScreenshotProcessor {
+    async fn research_queries(&self, brief: &str) -> Result<Vec<String>> {
+        let max_queries = self.config.research.max_queries.max(1);
+        let prompt = format!(
+            "I want to research the subject of a screenshot I took.\n\n{}\n\n\
+             Write up to {} distinct web search queries that together would find authoritative, \
+             in-depth sources on it. Make each query specific; don't repeat the same query reworded.",
+            brief, max_queries
+        );
+
+        let (structured, records) = usage::track(self.provider.complete_json(
+            &prompt,
+            &[],
+            &queries_schema(),
+            QUERY_MAX_TOKENS,
+        ))
+        .await;
+        self.save_usage(None, &records);
+
+        let queries = match structured.and_then(|json| Ok(serde_json::from_str::<SearchQueries>(&json)?)) {
+            Ok(parsed) => parsed.queries,
+            Err(e) => {
+                debug!("Structured search queries unavailable, parsing text instead: {}", e);
+                let prompt = format!("{}\n\nReply with one query per line and nothing else.", prompt);
+                let (text, records) = usage::track(self.provider.complete(&prompt, &[], QUERY_MAX_TOKENS)).await;
+                self.save_usage(None, &records);
+                text?.lines().map(str::to_string).collect()
+            }
+        };
+
+        let mut cleaned: Vec<String> = Vec::new();
+        for query in queries.iter().filter_map(|query| clean_query(query)) {
+            if !cleaned.iter().any(|existing| existing.eq_ignore_ascii_case(&query)) {
+                cleaned.push(query);
+            }
+        }
+        cleaned.truncate(max_queries);
+        if cleaned.is_empty() {
+            return Err(anyhow!("Could not come up with search queries"));
+        }
+        Ok(cleaned)
+    }
+
+    /// Reads a search result and notes what it says about the research brief.
+    async fn source_notes(&self, brief: &str, result: &SearchResult) -> String {
+        let text = match webpage::normalize_url(&result.url) {
+            Ok(url) => match webpage::fetch_readable(&self.client, &url).await {
+                Ok((_, text)) => text.chars().take(SOURCE_TEXT_MAX_CHARS).collect(),
+                Err(e) => {
+                    debug!("Using the search snippet for {}: {}", result.url, e);
+                    result.snippet.clone()
+                }
+            },
+            Err(_) => result.snippet.clone(),
+        };
+        if text.trim().is_empty() {
+            return String::new();
+        }
+
+        let prompt = format!(
+            "Research brief:\n{}\n\nSource: {} ({})\n\n{}\n\n\
+             Write concise notes on what this source contributes to the brief: key facts, figures, \
+             claims and caveats. If it's irrelevant, reply with just \"irrelevant\".",
+            brief, result.title, result.url, text
+        );
+        let (notes, records) = usage::track(self.provider.complete(&prompt, &[], SOURCE_NOTES_MAX_TOKENS)).await;
+        self.save_usage(None, &records);
+        notes
+            .inspect_err(|e| warn!("Failed to summarize {}: {}", result.url, e))
+            .unwrap_or_else(|_| result.snippet.clone())
+    }
+
+    /// Researches an analysis's topics on the web and writes a cited report, which is stored with
+    /// the analysis.
+    pub async fn run_deep_research(&self, analysis_id: &str) -> Result<ResearchReport> {
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+        // Searching would send what's on the screenshot to a search engine
+        if analysis.metadata.local_only == Some(true) || self.config.privacy_mode {
+            return Err(anyhow!("Deep research isn't available for local-only screenshots"));
+        }
+        self.check_budget()?;
+
+        info!("🧠 Starting deep research for analysis {}", analysis_id);
+        let brief = research_brief(&analysis);
+        let queries = self.research_queries(&brief).await?;
+
+        let mut results: Vec<SearchResult> = Vec::new();
+        for query in &queries {
+            match self.config.research.search(&self.client, query).await {
+                Ok(found) => {
+                    for result in found {
+                        if !results.iter().any(|existing| existing.url == result.url) {
+                            results.push(result);
+                        }
+                    }
+                }
+                Err(e) => warn!("Search for '{}' failed: {}", query, e),
+            }
+        }
+        if results.is_empty() {
+            return Err(anyhow!("Web search returned no results"));
+        }
+
+        let notes = futures::future::join_all(results.iter().map(|result| self.source_notes(&brief, result))).await;
+        let sources: Vec<ResearchSource> = results
+            .into_iter()
+            .zip(notes)
+            .filter(|(_, notes)| !notes.trim().is_empty() && !notes.trim().eq_ignore_ascii_case("irrelevant"))
+            .enumerate()
+            .map(|(i, (result, notes))| ResearchSource {
+                number: i + 1,
+                title: if result.title.is_empty() { result.url.clone() } else { result.title },
+                url: result.url,
+                notes: notes.trim().to_string(),
+            })
+            .collect();
+        if sources.is_empty() {
+            return Err(anyhow!("None of the sources found were relevant"));
+        }
+
+        let source_notes: String = sources
+            .iter()
+            .map(|source| format!("[{}] {} ({})\n{}\n\n", source.number, source.title, source.url, source.notes))
+            .collect();
+        let prompt = format!(
+            "Research brief:\n{}\n\nNotes from web sources:\n\n{}\
+             Write a thorough research report on the brief from these notes. Start with a short overview, \
+             then cover the subject in sections with plain-text headings, and end with open questions or \
+             next steps. Cite sources inline as [n] after each claim they support, and don't cite anything \
+             not in the notes. Use plain text, no Markdown.{}",
+            brief,
+            source_notes,
+            prompts::language_instruction(self.config.response_language.as_deref())
+        );
+        let (report, records) = usage::track(self.provider.complete(&prompt, &[], REPORT_MAX_TOKENS)).await;
+        self.save_usage(Some(analysis_id), &records);
+
+        let report = ResearchReport {
+            analysis_id: analysis_id.to_string(),
+            queries,
+            sources,
+            report: report?.trim().to_string(),
+            created_at: Utc::now(),
+        };
+        self.store.save_research_report(&report)?;
+        info!(
+            "🧠 Deep research for {} done: {} sources from {} queries",
+            analysis_id,
+            report.sources.len(),
+            report.queries.len()
+        );
+        Ok(report)
+    }
+
+    /// The latest deep research report for an analysis, if one was run.
+    pub fn get_research_report(&self, analysis_id: &str) -> Result<Option<ResearchReport>> {
+        self.store.research_report(analysis_id)
+    }
+}
@@ -0,0 +1,210 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::time::MissedTickBehavior;
+use tracing::{info, warn};
+
+use crate::{AppConfig, ScreenshotProcessor};
+
+/// Analyses removed per pass while the history is over its disk limit.
+const DISK_SWEEP_BATCH: usize = 25;
+
+/// Limits on how much history is kept. Whichever limit is hit first applies, oldest analyses
+/// going first; 0 means no limit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionConfig {
+    pub max_items: usize,
+    pub max_age_days: u32,
+    /// Database and image files together, in megabytes.
+    pub max_disk_mb: u64,
+    pub sweep_interval_minutes: u32,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            max_items: 0,
+            max_age_days: 0,
+            max_disk_mb: 0,
+            sweep_interval_minutes: 60,
+        }
+    }
+}
+
+impl RetentionConfig {
+    fn is_unlimited(&self) -> bool {
+        self.max_items == 0 && self.max_age_days == 0 && self.max_disk_mb == 0
+    }
+}
+
+/// When analyses older than `days` expire, or `None` if they never do: for 0, and for ages reaching
+/// back before the earliest representable date.
+fn age_cutoff(now: DateTime<Utc>, days: u32) -> Option<DateTime<Utc>> {
+    if days == 0 {
+        return None;
+    }
+    now.checked_sub_signed(Duration::try_days(days.into())?)
+}
+
+/// Whether nothing is ever deleted, globally or by a source's processing profile.
+fn keeps_everything(config: &AppConfig) -> bool {
+    config.retention.is_unlimited() && config.profiles.values().all(|profile| profile.retention_days == 0)
+}
+
+/// What a cleanup pass removed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CleanupReport {
+    /// Including those past their source's retention.
+    pub deleted_by_age: usize,
+    pub deleted_by_count: usize,
+    pub deleted_by_size: usize,
+    /// Disk space freed, in bytes.
+    pub reclaimed_bytes: u64,
+    pub disk_bytes_after: u64,
+    pub ran_at: DateTime<Utc>,
+}
+
+impl CleanupReport {
+    pub fn deleted(&self) -> usize {
+        self.deleted_by_age + self.deleted_by_count + self.deleted_by_size
+    }
+}
+
+/// How much history is stored, and what the last cleanup freed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub analyses: usize,
+    pub database_bytes: u64,
+    pub image_bytes: u64,
+    pub last_cleanup: Option<CleanupReport>,
+}
+
+impl ScreenshotProcessor {
+    fn delete_expired(&self, ids: &[String]) -> Result<usize> {
+        let deleted = self.store.delete_many(ids)?;
+        for id in ids {
+            self.remove_from_search_index(Some(id));
+        }
+        Ok(deleted)
+    }
+
+    /// Deletes the analyses over the configured retention limits, then compacts the database.
+    pub fn run_cleanup(&self) -> Result<CleanupReport> {
+        let retention = &self.config.retention;
+        let before = self.store.disk_usage()?.total();
+        let mut report = CleanupReport {
+            ran_at: Utc::now(),
+            ..Default::default()
+        };
+
+        if let Some(cutoff) = age_cutoff(report.ran_at, retention.max_age_days) {
+            report.deleted_by_age = self.delete_expired(&self.store.ids_before(cutoff)?)?;
+        }
+        for (source, profile) in &self.config.profiles {
+            if let Some(cutoff) = age_cutoff(report.ran_at, profile.retention_days) {
+                report.deleted_by_age += self.delete_expired(&self.store.ids_from_source_before(source, cutoff)?)?;
+            }
+        }
+        if retention.max_items > 0 {
+            report.deleted_by_count = self.delete_expired(&self.store.ids_after_newest(retention.max_items)?)?;
+        }
+        if retention.max_disk_mb > 0 {
+            let limit = retention.max_disk_mb * 1024 * 1024;
+            while self.store.disk_usage()?.total() > limit {
+                let oldest = self.store.oldest_ids(DISK_SWEEP_BATCH)?;
+                if oldest.is_empty() {
+                    break;
+                }
+                report.deleted_by_size += self.delete_expired(&oldest)?;
+            }
+        }
+
+        // Deleted rows only give their space back to the file system once the database is rebuilt
+        if report.deleted() > 0 {
+            self.store.compact()?;
+        }
+        report.disk_bytes_after = self.store.disk_usage()?.total();
+        report.reclaimed_bytes = before.saturating_sub(report.disk_bytes_after);
+        if report.deleted() > 0 {
+            info!(
+                "🧹 Deleted {} old analyses, freeing {} KB",
+                report.deleted(),
+                report.reclaimed_bytes / 1024
+            );
+        }
+        *self.last_cleanup.lock() = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Applies the retention limits every `sweep_interval_minutes`, if any are set.
+    pub fn spawn_retention_sweeper(&self) -> Option<tokio::task::JoinHandle<()>> {
+        if keeps_everything(&self.config) {
+            return None;
+        }
+
+        let processor = self.clone();
+        let minutes = u64::from(self.config.retention.sweep_interval_minutes.max(1));
+        Some(tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(minutes * 60));
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                let sweeper = processor.clone();
+                match tokio::task::spawn_blocking(move || sweeper.run_cleanup()).await {
+                    Ok(Err(e)) => warn!("History cleanup failed: {}", e),
+                    Err(e) => warn!("History cleanup panicked: {}", e),
+                    Ok(Ok(_)) => {}
+                }
+            }
+        }))
+    }
+
+    pub fn storage_stats(&self) -> Result<StorageStats> {
+        let usage = self.store.disk_usage()?;
+        Ok(StorageStats {
+            analyses: self.store.count()?,
+            database_bytes: usage.database_bytes,
+            image_bytes: usage.image_bytes,
+            last_cleanup: self.last_cleanup.lock().clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profiles::ProcessingProfile;
+
+    #[test]
+    fn age_cutoff_is_that_many_days_back() {
+        let now = Utc::now();
+        assert_eq!(age_cutoff(now, 30), Some(now - Duration::days(30)));
+    }
+
+    #[test]
+    fn zero_days_never_expires() {
+        assert_eq!(age_cutoff(Utc::now(), 0), None);
+    }
+
+    #[test]
+    fn ages_before_the_earliest_date_keep_everything() {
+        assert_eq!(age_cutoff(Utc::now(), u32::MAX), None);
+        assert_eq!(age_cutoff(DateTime::<Utc>::MIN_UTC + Duration::days(1), 2), None);
+    }
+
+    #[test]
+    fn any_limit_or_profile_retention_turns_cleanup_on() {
+        let mut config = AppConfig::default();
+        assert!(keeps_everything(&config));
+
+        config.profiles.insert("clipboard".to_string(), ProcessingProfile::default());
+        assert!(keeps_everything(&config));
+        config.profiles.get_mut("clipboard").unwrap().retention_days = 7;
+        assert!(!keeps_everything(&config));
+
+        config.profiles.clear();
+        config.retention.max_disk_mb = 500;
+        assert!(!keeps_everything(&config));
+    }
+}
@@ -0,0 +1,293 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use rand::Rng;
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tracing::warn;
+
+use crate::{
+    providers::{ChatMessage, OutputSchema, ProviderError, TokenCallback},
+    ProcessedImage, VisionProvider,
+};
+
+const MAX_BACKOFF: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt, per provider.
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter, capped at `MAX_BACKOFF`.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_BACKOFF);
+        rand::thread_rng().gen_range(Duration::ZERO..=ceiling)
+    }
+}
+
+/// Retries and failovers performed while producing one result.
+#[derive(Debug, Default)]
+pub struct RetryStats {
+    retries: AtomicU32,
+    failovers: AtomicU32,
+}
+
+impl RetryStats {
+    pub fn retries(&self) -> u32 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub fn failovers(&self) -> u32 {
+        self.failovers.load(Ordering::Relaxed)
+    }
+}
+
+tokio::task_local! {
+    static STATS: Arc<RetryStats>;
+}
+
+/// Runs `future`, collecting the retries and failovers of every LLM call it makes.
+pub async fn track<F: std::future::Future>(future: F) -> (F::Output, Arc<RetryStats>) {
+    let stats = Arc::new(RetryStats::default());
+    let output = STATS.scope(stats.clone(), future).await;
+    (output, stats)
+}
+
+fn record(update: impl FnOnce(&RetryStats)) {
+    let _ = STATS.try_with(|stats| update(stats));
+}
+
+/// Wraps a provider with retries on transient errors and an optional fallback provider.
+pub struct ResilientProvider {
+    primary: Arc<dyn VisionProvider>,
+    fallback: Option<Arc<dyn VisionProvider>>,
+    policy: RetryPolicy,
+}
+
+impl ResilientProvider {
+    pub fn new(
+        primary: Arc<dyn VisionProvider>,
+        fallback: Option<Arc<dyn VisionProvider>>,
+        policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            primary,
+            fallback,
+            policy,
+        }
+    }
+
+    async fn run<'a>(
+        &'a self,
+        call: impl Fn(&'a dyn VisionProvider) -> BoxFuture<'a, Result<String>> + Send + Sync + 'a,
+    ) -> Result<String> {
+        let primary_error = match self.with_retries(self.primary.as_ref(), &call).await {
+            Ok(text) => return Ok(text),
+            Err(e) => e,
+        };
+
+        let Some(ref fallback) = self.fallback else {
+            return Err(primary_error);
+        };
+        if !is_transient(&primary_error) {
+            return Err(primary_error);
+        }
+
+        warn!(
+            "{} unavailable ({}), failing over to {}",
+            self.primary.name(),
+            primary_error,
+            fallback.name()
+        );
+        record(|stats| {
+            stats.failovers.fetch_add(1, Ordering::Relaxed);
+        });
+        self.with_retries(fallback.as_ref(), &call).await
+    }
+
+    async fn with_retries<'a>(
+        &self,
+        provider: &'a dyn VisionProvider,
+        call: &(impl Fn(&'a dyn VisionProvider) -> BoxFuture<'a, Result<String>> + Send + Sync),
+    ) -> Result<String> {
+        let mut retry = 0;
+        loop {
+            match call(provider).await {
+                Err(e) if retry < self.policy.max_retries && is_transient(&e) => {
+                    let delay = self.policy.delay(retry);
+                    retry += 1;
+                    warn!(
+                        "{} request failed ({}), retry {}/{} in {:?}",
+                        provider.name(),
+                        e,
+                        retry,
+                        self.policy.max_retries,
+                        delay
+                    );
+                    record(|stats| {
+                        stats.retries.fetch_add(1, Ordering::Relaxed);
+                    });
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Whether `error` came from a provider failure that may succeed if tried again later.
+pub(crate) fn is_transient(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ProviderError>()
+        .is_some_and(ProviderError::is_transient)
+}
+
+#[async_trait]
+impl VisionProvider for ResilientProvider {
+    fn name(&self) -> &'static str {
+        self.primary.name()
+    }
+
+    async fn chat(&self, messages: &[ChatMessage], images: &[&ProcessedImage], max_tokens: u32) -> Result<String> {
+        self.run(|provider| provider.chat(messages, images, max_tokens)).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[ChatMessage],
+        images: &[&ProcessedImage],
+        max_tokens: u32,
+        on_token: TokenCallback<'_>,
+    ) -> Result<String> {
+        // Only request-level errors are retried, so no tokens have been streamed yet
+        self.run(|provider| provider.chat_stream(messages, images, max_tokens, on_token))
+            .await
+    }
+
+    async fn complete_json(
+        &self,
+        prompt: &str,
+        images: &[&ProcessedImage],
+        schema: &OutputSchema,
+        max_tokens: u32,
+    ) -> Result<String> {
+        self.run(|provider| provider.complete_json(prompt, images, schema, max_tokens))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use reqwest::StatusCode;
+    use std::collections::VecDeque;
+
+    /// Answers with `responses` in order, as the status codes of failed calls or the text of a
+    /// successful one.
+    struct Scripted {
+        name: &'static str,
+        responses: Mutex<VecDeque<Result<&'static str, StatusCode>>>,
+        calls: AtomicU32,
+    }
+
+    impl Scripted {
+        fn new(name: &'static str, responses: &[Result<&'static str, StatusCode>]) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                responses: Mutex::new(responses.iter().copied().collect()),
+                calls: AtomicU32::new(0),
+            })
+        }
+
+        fn calls(&self) -> u32 {
+            self.calls.load(Ordering::Relaxed)
+        }
+    }
+
+    #[async_trait]
+    impl VisionProvider for Scripted {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn chat(&self, _: &[ChatMessage], _: &[&ProcessedImage], _: u32) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+            match self.responses.lock().pop_front().expect("no more scripted responses") {
+                Ok(text) => Ok(text.to_string()),
+                Err(status) => Err(ProviderError::Status {
+                    provider: self.name.to_string(),
+                    status,
+                }
+                .into()),
+            }
+        }
+    }
+
+    const POLICY: RetryPolicy = RetryPolicy {
+        max_retries: 2,
+        base_delay: Duration::ZERO,
+    };
+
+    async fn ask(provider: &ResilientProvider) -> (Result<String>, Arc<RetryStats>) {
+        track(provider.complete("hi", &[], 100)).await
+    }
+
+    #[tokio::test]
+    async fn retries_transient_errors() {
+        let primary = Scripted::new("primary", &[Err(StatusCode::SERVICE_UNAVAILABLE), Ok("done")]);
+        let provider = ResilientProvider::new(primary.clone(), None, POLICY);
+
+        let (result, stats) = ask(&provider).await;
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(primary.calls(), 2);
+        assert_eq!(stats.retries(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let primary = Scripted::new("primary", &[Err(StatusCode::TOO_MANY_REQUESTS); 3]);
+        let provider = ResilientProvider::new(primary.clone(), None, POLICY);
+
+        let (result, stats) = ask(&provider).await;
+        assert!(is_transient(&result.unwrap_err()));
+        assert_eq!(primary.calls(), 3);
+        assert_eq!(stats.retries(), 2);
+    }
+
+    #[tokio::test]
+    async fn fatal_errors_are_neither_retried_nor_failed_over() {
+        let primary = Scripted::new("primary", &[Err(StatusCode::UNAUTHORIZED)]);
+        let fallback = Scripted::new("fallback", &[Ok("fallback")]);
+        let provider = ResilientProvider::new(primary.clone(), Some(fallback.clone()), POLICY);
+
+        let (result, stats) = ask(&provider).await;
+        assert!(!is_transient(&result.unwrap_err()));
+        assert_eq!(primary.calls(), 1);
+        assert_eq!(fallback.calls(), 0);
+        assert_eq!((stats.retries(), stats.failovers()), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn fails_over_once_the_primary_is_out_of_retries() {
+        let primary = Scripted::new("primary", &[Err(StatusCode::BAD_GATEWAY); 3]);
+        let fallback = Scripted::new("fallback", &[Err(StatusCode::SERVICE_UNAVAILABLE), Ok("fallback")]);
+        let provider = ResilientProvider::new(primary.clone(), Some(fallback.clone()), POLICY);
+
+        let (result, stats) = ask(&provider).await;
+        assert_eq!(result.unwrap(), "fallback");
+        assert_eq!((primary.calls(), fallback.calls()), (3, 2));
+        assert_eq!((stats.retries(), stats.failovers()), (3, 1));
+    }
+}
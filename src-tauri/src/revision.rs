@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Json, Path as AxumPath, State},
+    response::Json as ResponseJson,
+};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use crate::{
+    api_error::ApiError, budget::BudgetExceeded, queue::QueueFull, ProcessingResponse, ScreenshotMetadata,
+    ScreenshotProcessor,
+};
+
+tokio::task_local! {
+    static REVISION_OF: String;
+}
+
+/// The analysis being re-analyzed by the current task, if any.
+pub(crate) fn revising() -> Option<String> {
+    REVISION_OF.try_with(Clone::clone).ok()
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReanalyzeRequest {
+    /// Replaces the summary prompt template for this pass.
+    #[serde(default)]
+    pub prompt: Option<String>,
+    /// Replaces the provider's model for this pass.
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl ScreenshotProcessor {
+    /// Analyzes a stored screenshot again, saving the result as a new revision of the original.
+    pub async fn reanalyze(
+        &self,
+        analysis_id: &str,
+        prompt_override: Option<String>,
+        model_override: Option<String>,
+    ) -> Result<ProcessingResponse> {
+        let original = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis {} not found", analysis_id))?;
+
+        let mut processor = self.clone();
+        if let Some(prompt) = prompt_override.filter(|p| !p.trim().is_empty()) {
+            processor.config.summary_prompt = Some(prompt);
+            // An explicit prompt wins over any mapped template
+            processor.config.app_prompt_templates.clear();
+            processor.config.content_type_prompt_templates.clear();
+        }
+        if let Some(model) = model_override.filter(|m| !m.trim().is_empty()) {
+            processor.config.model = Some(model);
+            processor.provider = crate::build_resilient_provider(&processor.config, &processor.client)?;
+        }
+
+        info!(
+            "🔁 Re-analyzing {} with {}",
+            analysis_id,
+            processor.config.model.as_deref().unwrap_or("the default model")
+        );
+
+        // Keep the revision dated with the screenshot it re-analyzes
+        let metadata = ScreenshotMetadata {
+            captured_at: Some(original.timestamp),
+            ..original.metadata
+        };
+        REVISION_OF
+            .scope(
+                analysis_id.to_string(),
+                processor.process_tracked(&original.image_data.base64_data, Some(metadata)),
+            )
+            .await
+    }
+}
+
+pub async fn handle_reanalyze(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    request: Option<Json<ReanalyzeRequest>>,
+) -> Result<ResponseJson<ProcessingResponse>, ApiError> {
+    match processor.get_analysis(&analysis_id, false) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err(ApiError::not_found(format!("Analysis {} not found", analysis_id))),
+        Err(e) => {
+            error!("Failed to load analysis {}: {}", analysis_id, e);
+            return Err(e.into());
+        }
+    }
+
+    let Json(request) = request.unwrap_or_default();
+    match processor.reanalyze(&analysis_id, request.prompt, request.model).await {
+        Ok(response) => Ok(ResponseJson(response)),
+        Err(e) if e.is::<QueueFull>() || e.is::<BudgetExceeded>() => {
+            warn!("Rejecting re-analysis: {}", e);
+            Err(e.into())
+        }
+        Err(e) => {
+            error!("Re-analysis of {} failed: {}", analysis_id, e);
+            Err(e.into())
+        }
+    }
+}
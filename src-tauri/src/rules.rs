@@ -0,0 +1,285 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{io::Write, path::Path, path::PathBuf};
+use tracing::{info, warn};
+
+use crate::{
+    archive::{csv_row, CSV_COLUMNS},
+    collections::{where_clause, CollectionRule, RuleMatch},
+    tags::normalize_tag,
+    AnalysisData, ScreenshotProcessor,
+};
+
+const MAX_RULE_RUNS: usize = 1000;
+
+/// What a rule does to an analysis it matches, in the order listed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// Appends the analysis to a CSV file, writing the header first if the file is new.
+    ExportCsv { path: PathBuf },
+    /// Keeps the analysis out of Telegram notifications.
+    SkipTelegram,
+    Star,
+    Tag { tag: String },
+    /// Posts the summary to a Slack incoming webhook. Skipped in privacy mode.
+    NotifySlack { webhook_url: String },
+}
+
+/// Actions run on new analyses matching its conditions, e.g. "if content type is receipt then
+/// export CSV and don't notify Telegram".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+    pub enabled: bool,
+    #[serde(rename = "match", default)]
+    pub match_mode: RuleMatch,
+    /// The same predicates collections use.
+    pub conditions: Vec<CollectionRule>,
+    pub actions: Vec<RuleAction>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A rule as created or edited by the user.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RuleInput {
+    pub name: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+    #[serde(rename = "match", default)]
+    pub match_mode: RuleMatch,
+    pub conditions: Vec<CollectionRule>,
+    pub actions: Vec<RuleAction>,
+}
+
+fn enabled_by_default() -> bool {
+    true
+}
+
+/// How one action went.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionOutcome {
+    pub action: RuleAction,
+    pub ok: bool,
+    /// Why it failed or was skipped.
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// A rule having matched an analysis, with what its actions did.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleRun {
+    pub id: i64,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub analysis_id: String,
+    pub ran_at: DateTime<Utc>,
+    pub outcomes: Vec<ActionOutcome>,
+}
+
+impl RuleInput {
+    fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(anyhow!("Rule name can't be empty"));
+        }
+        if self.conditions.is_empty() {
+            return Err(anyhow!("A rule needs at least one condition"));
+        }
+        if self.conditions.iter().any(|condition| condition.value.trim().is_empty()) {
+            return Err(anyhow!("Condition values can't be empty"));
+        }
+        if self.actions.is_empty() {
+            return Err(anyhow!("A rule needs at least one action"));
+        }
+        for action in &self.actions {
+            match action {
+                RuleAction::ExportCsv { path } if path.as_os_str().is_empty() => {
+                    return Err(anyhow!("A CSV export needs a file path"));
+                }
+                RuleAction::Tag { tag } if normalize_tag(tag).is_none() => {
+                    return Err(anyhow!("Tag is empty"));
+                }
+                RuleAction::NotifySlack { webhook_url } => {
+                    let url = reqwest::Url::parse(webhook_url.trim())
+                        .map_err(|e| anyhow!("Invalid Slack webhook URL '{}': {}", webhook_url, e))?;
+                    if url.scheme() != "https" {
+                        return Err(anyhow!("Slack webhook URLs must use https"));
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Appends an analysis to a CSV file, writing the header first if the file is new.
+fn append_csv(path: &Path, analysis_id: &str, analysis: &AnalysisData) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let is_new = std::fs::metadata(path).map(|m| m.len() == 0).unwrap_or(true);
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    if is_new {
+        write!(file, "{}\r\n", CSV_COLUMNS.join(","))?;
+    }
+    file.write_all(csv_row(analysis_id, analysis).as_bytes())?;
+    Ok(())
+}
+
+impl ScreenshotProcessor {
+    /// Every rule, oldest first.
+    pub fn list_rules(&self) -> Result<Vec<Rule>> {
+        self.store.list_rules()
+    }
+
+    pub fn create_rule(&self, input: RuleInput) -> Result<Rule> {
+        input.validate()?;
+        let rule = Rule {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: input.name.trim().to_string(),
+            enabled: input.enabled,
+            match_mode: input.match_mode,
+            conditions: input.conditions,
+            actions: input.actions,
+            created_at: Utc::now(),
+        };
+        self.store.save_rule(&rule)?;
+        info!("⚙️ Created rule \"{}\"", rule.name);
+        Ok(rule)
+    }
+
+    pub fn update_rule(&self, id: &str, input: RuleInput) -> Result<Rule> {
+        input.validate()?;
+        let mut rule = self.store.get_rule(id)?.ok_or_else(|| anyhow!("Rule {} not found", id))?;
+        rule.name = input.name.trim().to_string();
+        rule.enabled = input.enabled;
+        rule.match_mode = input.match_mode;
+        rule.conditions = input.conditions;
+        rule.actions = input.actions;
+        self.store.save_rule(&rule)?;
+        Ok(rule)
+    }
+
+    /// Deletes a rule, keeping its past runs in the log. Returns false if it didn't exist.
+    pub fn delete_rule(&self, id: &str) -> Result<bool> {
+        self.store.delete_rule(id)
+    }
+
+    /// Logged rule runs, most recent first, optionally only one rule's.
+    pub fn rule_runs(&self, rule_id: Option<&str>, limit: usize) -> Result<Vec<RuleRun>> {
+        self.store.rule_runs(rule_id, limit.clamp(1, MAX_RULE_RUNS))
+    }
+
+    /// Matches a stored analysis against the enabled rules and runs their actions in the
+    /// background, logging each run. Returns whether a matched rule keeps it out of Telegram.
+    pub(crate) fn run_rules(&self, analysis_id: &str, analysis: &AnalysisData) -> bool {
+        let rules = match self.store.list_rules() {
+            Ok(rules) => rules,
+            Err(e) => {
+                warn!("Failed to load rules: {}", e);
+                return false;
+            }
+        };
+
+        let matched: Vec<Rule> = rules
+            .into_iter()
+            .filter(|rule| rule.enabled)
+            .filter(|rule| {
+                let (sql, params) = where_clause(rule.match_mode, &rule.conditions);
+                self.store
+                    .matches_where(analysis_id, &sql, &params)
+                    .inspect_err(|e| warn!("Failed to match rule \"{}\": {}", rule.name, e))
+                    .unwrap_or(false)
+            })
+            .collect();
+        if matched.is_empty() {
+            return false;
+        }
+        let skip_telegram = matched
+            .iter()
+            .any(|rule| rule.actions.contains(&RuleAction::SkipTelegram));
+
+        let processor = self.clone();
+        let analysis_id = analysis_id.to_string();
+        let mut analysis = analysis.clone();
+        tokio::spawn(async move {
+            for rule in matched {
+                let mut outcomes = Vec::with_capacity(rule.actions.len());
+                for action in &rule.actions {
+                    let result = processor.run_action(action, &rule, &analysis_id, &mut analysis).await;
+                    if let Err(e) = &result {
+                        warn!("Rule \"{}\" failed on {}: {}", rule.name, analysis_id, e);
+                    }
+                    outcomes.push(ActionOutcome {
+                        action: action.clone(),
+                        ok: result.is_ok(),
+                        message: result.err().map(|e| e.to_string()),
+                    });
+                }
+                info!("⚙️ Rule \"{}\" ran on analysis {}", rule.name, analysis_id);
+                let run = RuleRun {
+                    id: 0,
+                    rule_id: rule.id,
+                    rule_name: rule.name,
+                    analysis_id: analysis_id.clone(),
+                    ran_at: Utc::now(),
+                    outcomes,
+                };
+                if let Err(e) = processor.store.record_rule_run(&run, MAX_RULE_RUNS) {
+                    warn!("Failed to log rule run: {}", e);
+                }
+            }
+        });
+        skip_telegram
+    }
+
+    async fn run_action(
+        &self,
+        action: &RuleAction,
+        rule: &Rule,
+        analysis_id: &str,
+        analysis: &mut AnalysisData,
+    ) -> Result<()> {
+        match action {
+            RuleAction::ExportCsv { path } => append_csv(path, analysis_id, analysis),
+            // Already applied when the rule matched
+            RuleAction::SkipTelegram => Ok(()),
+            RuleAction::Star => {
+                self.set_starred(analysis_id, true)?;
+                analysis.starred = true;
+                Ok(())
+            }
+            RuleAction::Tag { tag } => {
+                self.add_tag(analysis_id, tag)?;
+                if let Some(tag) = normalize_tag(tag).filter(|tag| !analysis.content_analysis.tags.contains(tag)) {
+                    analysis.content_analysis.tags.push(tag);
+                }
+                Ok(())
+            }
+            RuleAction::NotifySlack { webhook_url } => {
+                // Privacy mode keeps the analysis on this machine
+                if self.config.privacy_mode {
+                    return Err(anyhow!("Skipped in privacy mode"));
+                }
+                let text = format!(
+                    "📸 *{}* ({})\n{}",
+                    rule.name, analysis.content_analysis.content_type, analysis.brief_summary
+                );
+                self.client
+                    .post(webhook_url.trim())
+                    .json(&serde_json::json!({ "text": text }))
+                    .send()
+                    .await?
+                    .error_for_status()?;
+                Ok(())
+            }
+        }
+    }
+}
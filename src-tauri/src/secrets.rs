@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use keyring::Entry;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::AppConfig;
+
+/// Keychain service name all secrets are filed under.
+const SERVICE: &str = "com.screenshotai.studio";
+/// Account for the secret the history's encryption keys are derived from. Never shown or set by
+/// the user, so it isn't a `SecretName`.
+const STORAGE_KEY_ACCOUNT: &str = "storage_key";
+
+/// Credentials that can be kept in the OS keychain instead of plain config.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretName {
+    AnthropicApiKey,
+    OpenaiApiKey,
+    GeminiApiKey,
+    TelegramBotToken,
+    NotionToken,
+    SearchApiKey,
+}
+
+impl SecretName {
+    fn account(&self) -> &'static str {
+        match self {
+            SecretName::AnthropicApiKey => "anthropic_api_key",
+            SecretName::OpenaiApiKey => "openai_api_key",
+            SecretName::GeminiApiKey => "gemini_api_key",
+            SecretName::TelegramBotToken => "telegram_bot_token",
+            SecretName::NotionToken => "notion_token",
+            SecretName::SearchApiKey => "search_api_key",
+        }
+    }
+
+    fn entry(&self) -> Result<Entry> {
+        Entry::new(SERVICE, self.account()).map_err(|e| anyhow!("Keychain unavailable: {}", e))
+    }
+}
+
+/// Stores a secret in the keychain; an empty value removes it.
+pub fn save(name: SecretName, value: &str) -> Result<()> {
+    let entry = name.entry()?;
+    let value = value.trim();
+
+    if value.is_empty() {
+        match entry.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(anyhow!("Failed to remove {} from keychain: {}", name.account(), e)),
+        }
+        info!("🔐 Removed {} from keychain", name.account());
+        return Ok(());
+    }
+
+    entry
+        .set_password(value)
+        .map_err(|e| anyhow!("Failed to save {} to keychain: {}", name.account(), e))?;
+    info!("🔐 Saved {} to keychain", name.account());
+    Ok(())
+}
+
+pub fn get(name: SecretName) -> Result<Option<String>> {
+    match name.entry()?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(anyhow!("Failed to read {} from keychain: {}", name.account(), e)),
+    }
+}
+
+pub fn has(name: SecretName) -> bool {
+    matches!(get(name), Ok(Some(_)))
+}
+
+/// The secret for encrypting the history at rest, generated and saved first if there isn't one
+/// and `create` is set.
+pub(crate) fn storage_secret(create: bool) -> Result<Option<Vec<u8>>> {
+    let entry =
+        Entry::new(SERVICE, STORAGE_KEY_ACCOUNT).map_err(|e| anyhow!("Keychain unavailable: {}", e))?;
+    match entry.get_password() {
+        Ok(value) => {
+            return general_purpose::STANDARD
+                .decode(value)
+                .map(Some)
+                .map_err(|e| anyhow!("Invalid storage key in keychain: {}", e))
+        }
+        Err(keyring::Error::NoEntry) if create => {}
+        Err(keyring::Error::NoEntry) => return Ok(None),
+        Err(e) => return Err(anyhow!("Failed to read storage key from keychain: {}", e)),
+    }
+
+    let mut secret = vec![0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    entry
+        .set_password(&general_purpose::STANDARD.encode(&secret))
+        .map_err(|e| anyhow!("Failed to save storage key to keychain: {}", e))?;
+    info!("🔐 Created storage encryption key in keychain");
+    Ok(Some(secret))
+}
+
+/// Fills credentials missing from `config` with the ones stored in the keychain.
+pub fn apply_to_config(config: &mut AppConfig) {
+    let fields = [
+        (SecretName::AnthropicApiKey, &mut config.anthropic_api_key),
+        (SecretName::OpenaiApiKey, &mut config.openai_api_key),
+        (SecretName::GeminiApiKey, &mut config.gemini_api_key),
+        (SecretName::TelegramBotToken, &mut config.telegram_bot_token),
+        (SecretName::NotionToken, &mut config.notion_token),
+        (SecretName::SearchApiKey, &mut config.research.search_api_key),
+    ];
+
+    for (name, field) in fields {
+        if field.as_deref().is_some_and(|v| !v.trim().is_empty()) {
+            continue;
+        }
+        match get(name) {
+            Ok(Some(value)) => *field = Some(value),
+            Ok(None) => {}
+            Err(e) => warn!("{}", e),
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::{future::Future, time::Duration};
+use teloxide::prelude::*;
+
+use crate::{bind_screenshot_listener, privacy, providers, telegram::configured_chats, AppConfig};
+
+/// Longest any one check may take, so an unreachable service can't stall the setup dialog.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// Not configured, or not needed with the current settings.
+    Skipped,
+}
+
+/// The outcome of checking one configured item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigCheck {
+    /// What was checked, e.g. `provider`, `telegram_bot`, `telegram_chat` or `server_port`.
+    pub item: String,
+    /// Human-readable name of the item, e.g. "Telegram chat -100123".
+    pub label: String,
+    pub status: CheckStatus,
+    pub message: String,
+}
+
+impl ConfigCheck {
+    fn new(item: &str, label: impl Into<String>, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            item: item.to_string(),
+            label: label.into(),
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub checks: Vec<ConfigCheck>,
+    /// Whether every check passed or was skipped.
+    pub ok: bool,
+}
+
+/// Runs `check`, failing it if it takes longer than `CHECK_TIMEOUT`.
+async fn timed<T>(check: impl Future<Output = anyhow::Result<T>>) -> anyhow::Result<T> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out after {} seconds", CHECK_TIMEOUT.as_secs())))
+}
+
+async fn check_provider(config: &AppConfig) -> ConfigCheck {
+    let client = Client::new();
+    match timed(providers::check_credentials(config, &client)).await {
+        Ok(provider) => ConfigCheck::new("provider", provider, CheckStatus::Pass, "Credentials accepted"),
+        Err(e) => ConfigCheck::new("provider", "AI provider", CheckStatus::Fail, e.to_string()),
+    }
+}
+
+/// Checks the bot token, then sends a test message to every configured chat.
+async fn check_telegram(config: &AppConfig) -> Vec<ConfigCheck> {
+    let Some(token) = config.telegram_bot_token.as_deref().filter(|t| !t.trim().is_empty()) else {
+        return vec![ConfigCheck::new(
+            "telegram_bot",
+            "Telegram bot",
+            CheckStatus::Skipped,
+            "No bot token configured",
+        )];
+    };
+    if config.privacy_mode {
+        return vec![ConfigCheck::new(
+            "telegram_bot",
+            "Telegram bot",
+            CheckStatus::Skipped,
+            "Telegram is off in privacy mode",
+        )];
+    }
+
+    let bot = Bot::new(token.trim());
+    let mut checks = vec![match timed(async { Ok(bot.get_me().await?) }).await {
+        Ok(me) => ConfigCheck::new(
+            "telegram_bot",
+            "Telegram bot",
+            CheckStatus::Pass,
+            format!("Connected as @{}", me.username()),
+        ),
+        // Without a working token there's no point messaging the chats
+        Err(e) => {
+            return vec![ConfigCheck::new(
+                "telegram_bot",
+                "Telegram bot",
+                CheckStatus::Fail,
+                e.to_string(),
+            )]
+        }
+    }];
+
+    let chats = configured_chats(config);
+    if chats.is_empty() {
+        checks.push(ConfigCheck::new(
+            "telegram_chat",
+            "Telegram chat",
+            CheckStatus::Fail,
+            "No chat configured, so notifications have nowhere to go",
+        ));
+    }
+    for chat in chats {
+        let label = format!("Telegram chat {}", chat.name.as_deref().unwrap_or(chat.chat_id.trim()));
+        let Some(recipient) = chat.recipient() else {
+            checks.push(ConfigCheck::new(
+                "telegram_chat",
+                label,
+                CheckStatus::Fail,
+                "Not a numeric chat id or @channel name",
+            ));
+            continue;
+        };
+        let sent = timed(async {
+            Ok(bot
+                .send_message(recipient, "✅ Screenshot AI test message: notifications will arrive here.")
+                .await?)
+        })
+        .await;
+        checks.push(match sent {
+            Ok(_) => ConfigCheck::new("telegram_chat", label, CheckStatus::Pass, "Test message sent"),
+            Err(e) => ConfigCheck::new("telegram_chat", label, CheckStatus::Fail, e.to_string()),
+        });
+    }
+    checks
+}
+
+/// Checks the server could bind its port, or the fallback port it would use instead.
+async fn check_port(config: &AppConfig, running_port: Option<u16>) -> ConfigCheck {
+    let label = format!("Server port {}", config.server_port);
+    let last = config.server_port.saturating_add(config.server_port_range);
+    if let Some(port) = running_port.filter(|port| (config.server_port..=last).contains(port)) {
+        return ConfigCheck::new(
+            "server_port",
+            label,
+            CheckStatus::Pass,
+            format!("In use by the running server on port {}", port),
+        );
+    }
+
+    match bind_screenshot_listener(config).await.and_then(|listener| Ok(listener.local_addr()?.port())) {
+        Ok(port) if port == config.server_port => {
+            ConfigCheck::new("server_port", label, CheckStatus::Pass, "Available")
+        }
+        Ok(port) => ConfigCheck::new(
+            "server_port",
+            label,
+            CheckStatus::Pass,
+            format!("In use; the server would use port {} instead", port),
+        ),
+        Err(e) => ConfigCheck::new("server_port", label, CheckStatus::Fail, e.to_string()),
+    }
+}
+
+/// Checks `config` against the services it names, for the setup dialog to show before starting
+/// the server. `running_port` is the port of a server that's already running, which is expected
+/// to be taken.
+pub async fn validate_config(config: &AppConfig, running_port: Option<u16>) -> ValidationReport {
+    // Privacy mode analyzes with the local model whatever provider is selected
+    let config = if config.privacy_mode {
+        privacy::local_config(config)
+    } else {
+        config.clone()
+    };
+
+    let (provider, telegram, port) =
+        tokio::join!(check_provider(&config), check_telegram(&config), check_port(&config, running_port));
+    let mut checks = vec![provider];
+    checks.extend(telegram);
+    checks.push(port);
+
+    ValidationReport {
+        ok: checks.iter().all(|check| check.status != CheckStatus::Fail),
+        checks,
+    }
+}
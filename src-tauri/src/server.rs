@@ -0,0 +1,200 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use crate::{
+    bind_screenshot_listener, emit_event, start_screenshot_server, AppConfig, ClipboardWatcher, DesktopWatcher,
+    ScreenshotProcessor, ServerState, ServerStateChanged, UiEvent,
+};
+
+/// How to reach a running server, as the app shows it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerInfo {
+    pub status: String,
+    pub local_ip: String,
+    pub port: u16,
+    pub endpoint_url: String,
+    pub desktop_detection: bool,
+    pub clipboard_detection: bool,
+    pub telegram_configured: bool,
+    pub auth_required: bool,
+    pub outbox_pending: usize,
+    /// New screenshots are being held back rather than analyzed.
+    pub processing_paused: bool,
+    /// Name the server is advertised under on the local network.
+    pub mdns_name: Option<String>,
+    /// SHA-256 fingerprint of the server certificate, for pinning it on the phone.
+    pub tls_fingerprint: Option<String>,
+}
+
+/// A running screenshot server with its watchers and background tasks, as started by the app
+/// or by `screenshot-ai serve`.
+#[derive(Debug)]
+pub struct ServerHandle {
+    /// The config it was started with, its port being the one actually bound.
+    pub config: AppConfig,
+    pub processor: ScreenshotProcessor,
+    pub desktop_watcher: Option<DesktopWatcher>,
+    pub clipboard_watcher: Option<ClipboardWatcher>,
+    pub server_task: Option<JoinHandle<()>>,
+    pub telegram_task: Option<JoinHandle<()>>,
+    pub outbox_task: JoinHandle<()>,
+    pub digest_task: Option<JoinHandle<()>>,
+    pub journal_task: Option<JoinHandle<()>>,
+    pub retention_task: Option<JoinHandle<()>>,
+    pub grpc_task: Option<JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    /// Binds the server port, then starts the HTTP server, the enabled watchers and the
+    /// background tasks.
+    pub async fn start(mut config: AppConfig) -> Result<Self> {
+        // Bind before anything else starts so a taken port is reported here, not lost in the server task
+        let listener = bind_screenshot_listener(&config)
+            .await
+            .context("Failed to start server")?;
+        config.server_port = listener.local_addr().context("Failed to start server")?.port();
+        #[cfg(feature = "grpc")]
+        let grpc_listener = match config.grpc_port {
+            Some(port) => Some(
+                tokio::net::TcpListener::bind(("0.0.0.0", port))
+                    .await
+                    .with_context(|| format!("Failed to bind gRPC port {}", port))?,
+            ),
+            None => None,
+        };
+        #[cfg(not(feature = "grpc"))]
+        if config.grpc_port.is_some() {
+            tracing::warn!("grpc_port is set, but this build has no gRPC support");
+        }
+
+        let processor =
+            ScreenshotProcessor::new(config.clone()).context("Failed to initialize screenshot processor")?;
+
+        // Start desktop watcher if enabled
+        let desktop_watcher = if config.enable_desktop_detection {
+            match DesktopWatcher::new(processor.clone(), config.watch_directories.clone()) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    error!("Failed to start desktop watcher: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Start clipboard watcher if enabled
+        let clipboard_watcher = if config.enable_clipboard_detection {
+            match ClipboardWatcher::new(processor.clone()) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    error!("Failed to start clipboard watcher: {}", e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Start HTTP server in background
+        let server_processor = processor.clone();
+        let server_task = tokio::spawn(async move {
+            if let Err(e) = start_screenshot_server(server_processor, listener).await {
+                error!("Screenshot server error: {}", e);
+                // Let the UI show the failure and tear down the rest of the server
+                emit_event(UiEvent::ServerStateChanged(ServerStateChanged {
+                    state: ServerState::Failed,
+                    error: Some(e.to_string()),
+                }));
+            }
+        });
+
+        // Serve the companion gRPC API next to the HTTP one
+        #[cfg(feature = "grpc")]
+        let grpc_task = grpc_listener.map(|listener| {
+            let grpc_processor = processor.clone();
+            tokio::spawn(async move {
+                if let Err(e) = crate::grpc::serve(grpc_processor, listener).await {
+                    error!("gRPC server error: {}", e);
+                }
+            })
+        });
+        #[cfg(not(feature = "grpc"))]
+        let grpc_task = None;
+
+        // Listen for inline keyboard callbacks from the Telegram notifications
+        let telegram_task = processor.spawn_telegram_dispatcher();
+
+        // Retry screenshots that were saved while the network was down
+        let outbox_task = processor.spawn_outbox_worker();
+
+        // Send the daily digest to Telegram at the configured time
+        let digest_task = processor.spawn_digest_scheduler();
+
+        // Capture the screen into the activity journal every few minutes
+        let journal_task = config.journal.enabled.then(|| processor.spawn_journal());
+
+        // Delete the oldest analyses once the history is over its retention limits
+        let retention_task = processor.spawn_retention_sweeper();
+
+        Ok(Self {
+            config,
+            processor,
+            desktop_watcher,
+            clipboard_watcher,
+            server_task: Some(server_task),
+            telegram_task,
+            outbox_task,
+            digest_task,
+            journal_task,
+            retention_task,
+            grpc_task,
+        })
+    }
+
+    pub fn info(&self) -> ServerInfo {
+        let local_ip = local_ip_address::local_ip()
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|_| "127.0.0.1".to_string());
+
+        ServerInfo {
+            status: "running".to_string(),
+            local_ip: local_ip.clone(),
+            port: self.config.server_port,
+            endpoint_url: format!(
+                "{}://{}:{}/screenshot",
+                self.processor.url_scheme(),
+                local_ip,
+                self.config.server_port
+            ),
+            desktop_detection: self.desktop_watcher.is_some(),
+            clipboard_detection: self.clipboard_watcher.is_some(),
+            telegram_configured: self.config.telegram_bot_token.is_some(),
+            auth_required: self.processor.api_key().is_some(),
+            outbox_pending: self.processor.outbox_pending(),
+            processing_paused: self.processor.is_paused(),
+            // Advertising starts with the server task, so this is `None` right after starting
+            mdns_name: self.processor.advertised_name(),
+            tls_fingerprint: self.processor.tls_fingerprint(),
+        }
+    }
+
+    /// Stops the server and its background tasks; the watchers stop as they're dropped.
+    pub fn stop(self) {
+        let tasks = [
+            self.server_task,
+            self.telegram_task,
+            Some(self.outbox_task),
+            self.digest_task,
+            self.journal_task,
+            self.retention_task,
+            self.grpc_task,
+        ];
+        for task in tasks.into_iter().flatten() {
+            task.abort();
+        }
+        info!("Screenshot server stopped");
+    }
+}
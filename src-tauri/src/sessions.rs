@@ -0,0 +1,264 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, time::Duration};
+use teloxide::{
+    prelude::*,
+    types::{ParseMode, Recipient},
+    utils::html,
+};
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{prompts, telegram, usage, AnalysisData, ScreenshotProcessor};
+
+const SESSION_SUMMARY_MAX_TOKENS: u32 = 400;
+/// Screenshots listed by name in a session message; the rest are counted.
+const MAX_LISTED_SCREENSHOTS: usize = 15;
+const LISTED_SUMMARY_MAX_CHARS: usize = 160;
+
+/// A chat and the forum topic in it that notifications go to.
+type Route = (Recipient, Option<i32>);
+
+/// Screenshots that arrived in one burst, with a summary of them together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub summary: Option<String>,
+    pub analysis_ids: Vec<String>,
+}
+
+/// The session still collecting screenshots.
+#[derive(Debug)]
+pub(crate) struct PendingSession {
+    id: String,
+    started_at: DateTime<Utc>,
+    ended_at: DateTime<Utc>,
+    last_arrival: Instant,
+    analysis_ids: Vec<String>,
+}
+
+fn session_prompt(analyses: &[(String, AnalysisData)]) -> String {
+    let screenshots: String = analyses
+        .iter()
+        .enumerate()
+        .map(|(i, (_, analysis))| {
+            let app = analysis.metadata.app.as_deref().unwrap_or(&analysis.source);
+            format!(
+                "{}. [{}, {}] {}\n",
+                i + 1,
+                analysis.timestamp.with_timezone(&Local).format("%H:%M:%S"),
+                app,
+                analysis.brief_summary.trim()
+            )
+        })
+        .collect();
+
+    format!(
+        "I took these {} screenshots in quick succession. Here is what each one shows:\n\n{}\n\
+         In 2 to 4 sentences, summarize what I was doing or looking at across the whole session, \
+         then say what I'd likely want to do next. Don't describe the screenshots one by one.",
+        analyses.len(),
+        screenshots
+    )
+}
+
+fn format_session_message(analyses: &[&(String, AnalysisData)], summary: Option<&str>) -> String {
+    let time = |analysis: &AnalysisData| analysis.timestamp.with_timezone(&Local).format("%H:%M").to_string();
+    let first = analyses.first().map(|(_, a)| time(a)).unwrap_or_default();
+    let last = analyses.last().map(|(_, a)| time(a)).unwrap_or_default();
+    let span = if first == last { first } else { format!("{}–{}", first, last) };
+
+    let mut message = format!(
+        "<b>🗂️ Screenshot Session</b> <i>{} · {} screenshots</i>\n\n",
+        span,
+        analyses.len()
+    );
+    if let Some(summary) = summary {
+        message.push_str(&format!("<b>Session Summary:</b>\n{}\n\n", telegram::truncate_html(summary, 1500)));
+    }
+    message.push_str("<b>Screenshots:</b>\n");
+    for (i, (_, analysis)) in analyses.iter().take(MAX_LISTED_SCREENSHOTS).enumerate() {
+        let line = analysis.brief_summary.lines().next().unwrap_or_default().trim();
+        let line = if line.chars().count() > LISTED_SUMMARY_MAX_CHARS {
+            format!("{}…", line.chars().take(LISTED_SUMMARY_MAX_CHARS).collect::<String>())
+        } else {
+            line.to_string()
+        };
+        message.push_str(&format!("{}. <i>{}</i> {}\n", i + 1, time(analysis), html::escape(&line)));
+    }
+    if analyses.len() > MAX_LISTED_SCREENSHOTS {
+        message.push_str(&format!("…and {} more\n", analyses.len() - MAX_LISTED_SCREENSHOTS));
+    }
+    message
+}
+
+impl ScreenshotProcessor {
+    /// Whether Telegram notifications are held back and grouped into sessions.
+    pub(crate) fn groups_sessions(&self) -> bool {
+        self.config.session_window_secs > 0 && self.telegram_bot.is_some()
+    }
+
+    /// Adds a finished analysis to the current session, starting one if none is open. The session
+    /// closes once no screenshot has arrived for `session_window_secs`.
+    pub(crate) fn add_to_session(&self, analysis_id: &str) {
+        let mut pending = self.session.lock();
+        match pending.as_mut() {
+            Some(session) => {
+                session.analysis_ids.push(analysis_id.to_string());
+                session.ended_at = Utc::now();
+                session.last_arrival = Instant::now();
+            }
+            None => {
+                *pending = Some(PendingSession {
+                    id: uuid::Uuid::new_v4().to_string(),
+                    started_at: Utc::now(),
+                    ended_at: Utc::now(),
+                    last_arrival: Instant::now(),
+                    analysis_ids: vec![analysis_id.to_string()],
+                });
+                let processor = self.clone();
+                tokio::spawn(async move { processor.close_session_when_quiet().await });
+            }
+        }
+    }
+
+    async fn close_session_when_quiet(&self) {
+        let window = Duration::from_secs(self.config.session_window_secs);
+        let mut deadline = Instant::now() + window;
+        let session = loop {
+            tokio::time::sleep_until(deadline).await;
+            let mut pending = self.session.lock();
+            let Some(last_arrival) = pending.as_ref().map(|session| session.last_arrival) else {
+                return;
+            };
+            if last_arrival + window > Instant::now() {
+                deadline = last_arrival + window;
+            } else if let Some(session) = pending.take() {
+                break session;
+            }
+        };
+
+        if let Err(e) = self.finish_session(session).await {
+            warn!("Failed to send session notification: {}", e);
+        }
+    }
+
+    /// A summary of the whole session, or `None` if the provider couldn't produce one.
+    async fn summarize_session(&self, analyses: &[(String, AnalysisData)]) -> Option<String> {
+        if let Err(e) = self.check_budget() {
+            warn!("Skipping session summary: {}", e);
+            return None;
+        }
+        // Local-only screenshots keep their session summary local too
+        let local_only = analyses.iter().any(|(_, a)| a.metadata.local_only == Some(true));
+        let provider = if local_only && !self.config.privacy_mode {
+            self.local_only().ok()?.provider
+        } else {
+            self.provider.clone()
+        };
+
+        let prompt = format!(
+            "{}{}",
+            session_prompt(analyses),
+            prompts::language_instruction(self.config.response_language.as_deref())
+        );
+        let (summary, records) = usage::track(provider.complete(&prompt, &[], SESSION_SUMMARY_MAX_TOKENS)).await;
+        self.save_usage(None, &records);
+        summary
+            .inspect_err(|e| warn!("Failed to summarize session: {}", e))
+            .ok()
+            .map(|summary| summary.trim().to_string())
+            .filter(|summary| !summary.is_empty())
+    }
+
+    /// Sends one message per chat for a finished session. A chat that only receives one of its
+    /// screenshots gets the usual notification instead.
+    async fn finish_session(&self, session: PendingSession) -> Result<()> {
+        let analyses: Vec<(String, AnalysisData)> = session
+            .analysis_ids
+            .iter()
+            .filter_map(|id| self.store.get(id).ok().flatten().map(|analysis| (id.clone(), analysis)))
+            .collect();
+
+        // Each chat gets the screenshots its filters let through
+        let mut routes: Vec<(Route, Vec<usize>)> = Vec::new();
+        for (i, (_, analysis)) in analyses.iter().enumerate() {
+            let recipients = self.notification_recipients(
+                &analysis.source,
+                &analysis.content_analysis.content_type,
+                analysis.project_id.as_deref(),
+            );
+            for recipient in recipients {
+                match routes.iter_mut().find(|(route, _)| *route == recipient) {
+                    Some((_, indices)) => indices.push(i),
+                    None => routes.push((recipient, vec![i])),
+                }
+            }
+        }
+
+        let Some(bot) = &self.telegram_bot else {
+            return Ok(());
+        };
+        let mut summaries: HashMap<Vec<usize>, Option<String>> = HashMap::new();
+        for (recipient, indices) in routes {
+            if let [i] = indices[..] {
+                let (id, analysis) = &analyses[i];
+                self.send_telegram_notification_to(
+                    vec![recipient],
+                    &analysis.brief_summary,
+                    id,
+                    &analysis.content_analysis,
+                    &analysis.image_data,
+                    &analysis.source,
+                )
+                .await?;
+                continue;
+            }
+
+            let included: Vec<&(String, AnalysisData)> = indices.iter().map(|&i| &analyses[i]).collect();
+            if !summaries.contains_key(&indices) {
+                let subset: Vec<(String, AnalysisData)> = included.iter().map(|&item| item.clone()).collect();
+                summaries.insert(indices.clone(), self.summarize_session(&subset).await);
+            }
+            let summary = summaries[&indices].as_deref();
+
+            let (chat_id, thread_id) = recipient;
+            let mut request = bot
+                .send_message(chat_id.clone(), format_session_message(&included, summary))
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true);
+            if let Some(thread_id) = thread_id {
+                request = request.message_thread_id(thread_id);
+            }
+            if let Err(e) = request.await {
+                warn!("Failed to send session to Telegram chat {}: {}", chat_id, e);
+            }
+        }
+
+        let count = analyses.len();
+        if count > 1 {
+            let all: Vec<usize> = (0..count).collect();
+            let summary = match summaries.remove(&all) {
+                Some(summary) => summary,
+                None => self.summarize_session(&analyses).await,
+            };
+            self.store.save_session(&Session {
+                id: session.id,
+                started_at: session.started_at,
+                ended_at: session.ended_at,
+                summary,
+                analysis_ids: analyses.into_iter().map(|(id, _)| id).collect(),
+            })?;
+            info!("🗂️ Closed session of {} screenshots", count);
+        }
+        Ok(())
+    }
+
+    /// Recent sessions of more than one screenshot, newest first.
+    pub fn list_sessions(&self, limit: usize) -> Result<Vec<Session>> {
+        self.store.list_sessions(limit.clamp(1, 100))
+    }
+}
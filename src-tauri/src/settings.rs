@@ -0,0 +1,743 @@
+//! Settings as the setup dialog edits them, and the config file they're saved to. Both the app
+//! and the headless `screenshot-ai` binary load them from here.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+use tracing::{error, info};
+
+use crate::{
+    app_config_dir, secrets, AppConfig, ContentFilterMode, ExclusionRule, ImagePolicy, JournalConfig, MetadataWriteback, ModelPrice, NotionProperties,
+    PauseMode, ProcessingProfile, ProviderKind, PushNotifierConfig, RecordingConfig, RedactionMode, ResearchConfig, RetentionConfig, TelegramChat, TtsConfig,
+    WatchDirectory, WebhookConfig,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    #[serde(default)]
+    pub provider: ProviderKind,
+    pub anthropic_api_key: Option<String>,
+    #[serde(default)]
+    pub openai_api_key: Option<String>,
+    #[serde(default)]
+    pub gemini_api_key: Option<String>,
+    #[serde(default)]
+    pub ollama_url: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub summary_max_tokens: Option<u32>,
+    #[serde(default)]
+    pub analysis_max_tokens: Option<u32>,
+    #[serde(default)]
+    pub combine_llm_requests: Option<bool>,
+    #[serde(default)]
+    pub summary_prompt: Option<String>,
+    #[serde(default)]
+    pub analysis_prompt: Option<String>,
+    #[serde(default)]
+    pub app_prompt_templates: HashMap<String, String>,
+    #[serde(default)]
+    pub content_type_prompt_templates: HashMap<String, String>,
+    pub telegram_bot_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+    #[serde(default)]
+    pub telegram_chats: Vec<TelegramChat>,
+    #[serde(default)]
+    pub telegram_topics: HashMap<String, i32>,
+    #[serde(default)]
+    pub session_window_secs: Option<u64>,
+    #[serde(default)]
+    pub digest_enabled: bool,
+    #[serde(default)]
+    pub digest_time: Option<String>,
+    #[serde(default)]
+    pub digest_timezone: Option<String>,
+    pub enable_desktop_detection: bool,
+    #[serde(default)]
+    pub enable_clipboard_detection: bool,
+    #[serde(default)]
+    pub desktop_notification_sources: Option<Vec<String>>,
+    #[serde(default)]
+    pub watch_directories: Vec<WatchDirectory>,
+    #[serde(default)]
+    pub screenshot_include_patterns: Option<Vec<String>>,
+    #[serde(default)]
+    pub screenshot_exclude_patterns: Vec<String>,
+    #[serde(default)]
+    pub max_image_dimension: Option<u32>,
+    #[serde(default)]
+    pub max_image_bytes: Option<usize>,
+    #[serde(default)]
+    pub max_concurrent_requests: Option<usize>,
+    #[serde(default)]
+    pub max_queue_depth: Option<usize>,
+    #[serde(default)]
+    pub duplicate_window_secs: Option<u64>,
+    #[serde(default)]
+    pub idempotency_window_secs: Option<u64>,
+    #[serde(default)]
+    pub image_policy: Option<ImagePolicy>,
+    #[serde(default)]
+    pub recordings: Option<RecordingConfig>,
+    #[serde(default)]
+    pub journal: Option<JournalConfig>,
+    #[serde(default)]
+    pub research: Option<ResearchConfig>,
+    #[serde(default)]
+    pub tts: Option<TtsConfig>,
+    #[serde(default)]
+    pub retention: Option<RetentionConfig>,
+    #[serde(default)]
+    pub perceptual_dedupe: Option<bool>,
+    #[serde(default)]
+    pub redaction_mode: Option<RedactionMode>,
+    #[serde(default)]
+    pub redaction_sources: HashMap<String, RedactionMode>,
+    #[serde(default)]
+    pub profiles: HashMap<String, ProcessingProfile>,
+    #[serde(default)]
+    pub retry_attempts: Option<u32>,
+    #[serde(default)]
+    pub retry_base_delay_ms: Option<u64>,
+    #[serde(default)]
+    pub privacy_mode: bool,
+    #[serde(default)]
+    pub pause_mode: Option<PauseMode>,
+    #[serde(default)]
+    pub content_filter: Option<ContentFilterMode>,
+    #[serde(default)]
+    pub exclusion_rules: Option<Vec<ExclusionRule>>,
+    #[serde(default)]
+    pub encrypt_storage: bool,
+    #[serde(default)]
+    pub local_model: Option<String>,
+    #[serde(default)]
+    pub fallback_provider: Option<ProviderKind>,
+    #[serde(default)]
+    pub fallback_model: Option<String>,
+    #[serde(default)]
+    pub model_prices: HashMap<String, ModelPrice>,
+    #[serde(default)]
+    pub budget_soft_limit_usd: Option<f64>,
+    #[serde(default)]
+    pub budget_hard_limit_usd: Option<f64>,
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    #[serde(default)]
+    pub public_url: Option<String>,
+    #[serde(default)]
+    pub push_notifiers: Vec<PushNotifierConfig>,
+    #[serde(default)]
+    pub vault_export_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub vault_auto_export: bool,
+    #[serde(default)]
+    pub spotlight_index: bool,
+    #[serde(default)]
+    pub spotlight_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub metadata_writeback: Option<MetadataWriteback>,
+    #[serde(default)]
+    pub unfurl_links: Option<bool>,
+    #[serde(default)]
+    pub receipt_extraction: Option<bool>,
+    #[serde(default)]
+    pub receipt_ledger: Option<PathBuf>,
+    #[serde(default)]
+    pub code_extraction: Option<bool>,
+    #[serde(default)]
+    pub table_extraction: Option<bool>,
+    #[serde(default)]
+    pub auto_translate: Option<bool>,
+    #[serde(default)]
+    pub translation_language: Option<String>,
+    #[serde(default)]
+    pub response_language: Option<String>,
+    #[serde(default)]
+    pub notion_token: Option<String>,
+    #[serde(default)]
+    pub notion_database_id: Option<String>,
+    #[serde(default)]
+    pub notion_properties: NotionProperties,
+    pub server_port: u16,
+    #[serde(default)]
+    pub server_port_range: Option<u16>,
+    #[serde(default)]
+    pub grpc_port: Option<u16>,
+    #[serde(default)]
+    pub rate_limit_per_minute: Option<u32>,
+    #[serde(default)]
+    pub audit_retention_days: Option<u32>,
+    /// Which logs to record, as a `tracing` filter such as "info" or "app=debug,warn".
+    #[serde(default)]
+    pub log_filter: Option<String>,
+    #[serde(default)]
+    pub api_key: Option<String>,
+    #[serde(default)]
+    pub mdns_enabled: Option<bool>,
+    #[serde(default)]
+    pub tls_enabled: bool,
+    #[serde(default)]
+    pub tls_cert_path: Option<PathBuf>,
+    #[serde(default)]
+    pub tls_key_path: Option<PathBuf>,
+    /// Start the server with these settings whenever the app is opened.
+    #[serde(default)]
+    pub auto_start_server: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            provider: ProviderKind::default(),
+            anthropic_api_key: None,
+            openai_api_key: None,
+            gemini_api_key: None,
+            ollama_url: None,
+            model: None,
+            summary_max_tokens: None,
+            analysis_max_tokens: None,
+            combine_llm_requests: None,
+            summary_prompt: None,
+            analysis_prompt: None,
+            app_prompt_templates: HashMap::new(),
+            content_type_prompt_templates: HashMap::new(),
+            telegram_bot_token: None,
+            telegram_chat_id: None,
+            telegram_chats: Vec::new(),
+            telegram_topics: HashMap::new(),
+            session_window_secs: None,
+            digest_enabled: false,
+            digest_time: None,
+            digest_timezone: None,
+            enable_desktop_detection: false,
+            enable_clipboard_detection: false,
+            desktop_notification_sources: None,
+            watch_directories: Vec::new(),
+            screenshot_include_patterns: None,
+            screenshot_exclude_patterns: Vec::new(),
+            max_image_dimension: None,
+            max_image_bytes: None,
+            max_concurrent_requests: None,
+            max_queue_depth: None,
+            duplicate_window_secs: None,
+            idempotency_window_secs: None,
+            image_policy: None,
+            recordings: None,
+            journal: None,
+            research: None,
+            tts: None,
+            retention: None,
+            perceptual_dedupe: None,
+            redaction_mode: None,
+            redaction_sources: HashMap::new(),
+            profiles: HashMap::new(),
+            retry_attempts: None,
+            retry_base_delay_ms: None,
+            privacy_mode: false,
+            pause_mode: None,
+            content_filter: None,
+            exclusion_rules: None,
+            encrypt_storage: false,
+            local_model: None,
+            fallback_provider: None,
+            fallback_model: None,
+            model_prices: HashMap::new(),
+            budget_soft_limit_usd: None,
+            budget_hard_limit_usd: None,
+            webhooks: Vec::new(),
+            public_url: None,
+            push_notifiers: Vec::new(),
+            vault_export_dir: None,
+            vault_auto_export: false,
+            spotlight_index: false,
+            spotlight_dir: None,
+            metadata_writeback: None,
+            unfurl_links: None,
+            receipt_extraction: None,
+            receipt_ledger: None,
+            code_extraction: None,
+            table_extraction: None,
+            auto_translate: None,
+            translation_language: None,
+            response_language: None,
+            notion_token: None,
+            notion_database_id: None,
+            notion_properties: NotionProperties::default(),
+            server_port: 5001,
+            server_port_range: None,
+            grpc_port: None,
+            rate_limit_per_minute: None,
+            audit_retention_days: None,
+            log_filter: None,
+            api_key: None,
+            mdns_enabled: None,
+            tls_enabled: false,
+            tls_cert_path: None,
+            tls_key_path: None,
+            auto_start_server: false,
+        }
+    }
+}
+
+/// The app config for settings from the UI, with defaults filled in and keychain secrets applied.
+pub fn app_config(config: ServerConfig) -> AppConfig {
+    let defaults = AppConfig::default();
+    let non_empty = |value: Option<String>| value.filter(|v| !v.trim().is_empty());
+
+    let mut app_config = AppConfig {
+        provider: config.provider,
+        anthropic_api_key: config.anthropic_api_key,
+        openai_api_key: config.openai_api_key,
+        gemini_api_key: config.gemini_api_key,
+        ollama_url: config.ollama_url,
+        model: non_empty(config.model),
+        summary_max_tokens: config.summary_max_tokens.unwrap_or(defaults.summary_max_tokens),
+        analysis_max_tokens: config.analysis_max_tokens.unwrap_or(defaults.analysis_max_tokens),
+        combine_llm_requests: config.combine_llm_requests.unwrap_or(defaults.combine_llm_requests),
+        summary_prompt: non_empty(config.summary_prompt),
+        analysis_prompt: non_empty(config.analysis_prompt),
+        app_prompt_templates: config.app_prompt_templates,
+        content_type_prompt_templates: config.content_type_prompt_templates,
+        telegram_bot_token: config.telegram_bot_token,
+        telegram_chat_id: config.telegram_chat_id,
+        telegram_chats: config.telegram_chats,
+        telegram_topics: config.telegram_topics,
+        session_window_secs: config.session_window_secs.unwrap_or(defaults.session_window_secs),
+        digest_enabled: config.digest_enabled,
+        digest_time: non_empty(config.digest_time).unwrap_or(defaults.digest_time),
+        digest_timezone: non_empty(config.digest_timezone),
+        enable_desktop_detection: config.enable_desktop_detection,
+        enable_clipboard_detection: config.enable_clipboard_detection,
+        desktop_notification_sources: config
+            .desktop_notification_sources
+            .unwrap_or(defaults.desktop_notification_sources),
+        watch_directories: config.watch_directories,
+        screenshot_include_patterns: config
+            .screenshot_include_patterns
+            .filter(|patterns| !patterns.is_empty())
+            .unwrap_or(defaults.screenshot_include_patterns),
+        screenshot_exclude_patterns: config.screenshot_exclude_patterns,
+        max_image_dimension: config.max_image_dimension.unwrap_or(defaults.max_image_dimension),
+        max_image_bytes: config.max_image_bytes.unwrap_or(defaults.max_image_bytes),
+        max_concurrent_requests: config.max_concurrent_requests.unwrap_or(defaults.max_concurrent_requests),
+        max_queue_depth: config.max_queue_depth.unwrap_or(defaults.max_queue_depth),
+        duplicate_window_secs: config.duplicate_window_secs.unwrap_or(defaults.duplicate_window_secs),
+        idempotency_window_secs: config.idempotency_window_secs.unwrap_or(defaults.idempotency_window_secs),
+        image_policy: config.image_policy.unwrap_or(defaults.image_policy),
+        recordings: config.recordings.unwrap_or(defaults.recordings),
+        journal: config.journal.unwrap_or(defaults.journal),
+        research: config.research.unwrap_or(defaults.research),
+        tts: config.tts.unwrap_or(defaults.tts),
+        retention: config.retention.unwrap_or(defaults.retention),
+        perceptual_dedupe: config.perceptual_dedupe.unwrap_or(defaults.perceptual_dedupe),
+        redaction_mode: config.redaction_mode.unwrap_or(defaults.redaction_mode),
+        redaction_sources: config.redaction_sources,
+        profiles: config.profiles,
+        retry_attempts: config.retry_attempts.unwrap_or(defaults.retry_attempts),
+        retry_base_delay_ms: config.retry_base_delay_ms.unwrap_or(defaults.retry_base_delay_ms),
+        privacy_mode: config.privacy_mode,
+        pause_mode: config.pause_mode.unwrap_or(defaults.pause_mode),
+        content_filter: config.content_filter.unwrap_or(defaults.content_filter),
+        exclusion_rules: config.exclusion_rules.unwrap_or(defaults.exclusion_rules),
+        encrypt_storage: config.encrypt_storage,
+        local_model: non_empty(config.local_model),
+        fallback_provider: config.fallback_provider,
+        fallback_model: config.fallback_model,
+        model_prices: config.model_prices,
+        budget_soft_limit_usd: config.budget_soft_limit_usd,
+        budget_hard_limit_usd: config.budget_hard_limit_usd,
+        webhooks: config.webhooks.into_iter().filter(|w| !w.url.trim().is_empty()).collect(),
+        public_url: non_empty(config.public_url),
+        push_notifiers: config.push_notifiers,
+        vault_export_dir: config.vault_export_dir.filter(|dir| !dir.as_os_str().is_empty()),
+        vault_auto_export: config.vault_auto_export,
+        spotlight_index: config.spotlight_index,
+        spotlight_dir: config.spotlight_dir,
+        metadata_writeback: config.metadata_writeback.unwrap_or(defaults.metadata_writeback),
+        unfurl_links: config.unfurl_links.unwrap_or(defaults.unfurl_links),
+        receipt_extraction: config.receipt_extraction.unwrap_or(defaults.receipt_extraction),
+        receipt_ledger: config.receipt_ledger.filter(|path| !path.as_os_str().is_empty()),
+        code_extraction: config.code_extraction.unwrap_or(defaults.code_extraction),
+        table_extraction: config.table_extraction.unwrap_or(defaults.table_extraction),
+        auto_translate: config.auto_translate.unwrap_or(defaults.auto_translate),
+        translation_language: non_empty(config.translation_language).unwrap_or(defaults.translation_language),
+        response_language: non_empty(config.response_language),
+        notion_token: non_empty(config.notion_token),
+        notion_database_id: non_empty(config.notion_database_id),
+        notion_properties: config.notion_properties,
+        server_port: config.server_port,
+        server_port_range: config.server_port_range.unwrap_or(defaults.server_port_range),
+        grpc_port: config.grpc_port,
+        rate_limit_per_minute: config.rate_limit_per_minute.unwrap_or(defaults.rate_limit_per_minute),
+        audit_retention_days: config.audit_retention_days.unwrap_or(defaults.audit_retention_days),
+        api_key: config.api_key,
+        mdns_enabled: config.mdns_enabled.unwrap_or(defaults.mdns_enabled),
+        tls_enabled: config.tls_enabled,
+        tls_cert_path: config.tls_cert_path.filter(|path| !path.as_os_str().is_empty()),
+        tls_key_path: config.tls_key_path.filter(|path| !path.as_os_str().is_empty()),
+    };
+    secrets::apply_to_config(&mut app_config);
+    app_config
+}
+
+pub fn config_file_path() -> PathBuf {
+    app_config_dir().join("config.json")
+}
+
+pub fn read_config_file() -> Option<ServerConfig> {
+    let path = config_file_path();
+    let contents = std::fs::read_to_string(&path).ok()?;
+
+    match serde_json::from_str(&contents) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            error!("Ignoring invalid config file {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Reads settings from a TOML, JSON or YAML file, going by its extension. Settings the file
+/// leaves out keep their defaults.
+pub fn load_config_file(path: &Path) -> Result<ServerConfig> {
+    config::Config::builder()
+        .add_source(config::File::from(path))
+        .build()
+        .and_then(|settings| settings.try_deserialize())
+        .with_context(|| format!("Failed to load config file {}", path.display()))
+}
+
+/// Settings from `path`, or from the app's saved config file without one, with any
+/// configuration env vars taking precedence.
+pub fn load(path: Option<&Path>) -> Result<ServerConfig> {
+    let mut config = match path {
+        Some(path) => load_config_file(path)?,
+        None => read_config_file().unwrap_or_default(),
+    };
+    apply_env_overrides(&mut config);
+    Ok(config)
+}
+
+pub fn write_config_file(config: &ServerConfig) -> Result<()> {
+    let path = config_file_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    std::fs::write(&path, serde_json::to_string_pretty(config)?)?;
+
+    // The file may hold API keys, keep it private to the current user
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+
+    info!("💾 Saved config to {}", path.display());
+    Ok(())
+}
+
+/// Overrides `config` with every configuration env var that is set.
+pub fn apply_env_overrides(config: &mut ServerConfig) {
+    let env = |name: &str| std::env::var(name).ok();
+    let env_flag = |name: &str| env(name).map(|v| v.to_lowercase() == "true");
+
+    if let Some(provider) = env("LLM_PROVIDER")
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v.to_lowercase())).ok())
+    {
+        config.provider = provider;
+    }
+    if let Some(key) = env("ANTHROPIC_API_KEY") {
+        config.anthropic_api_key = Some(key);
+    }
+    if let Some(key) = env("OPENAI_API_KEY") {
+        config.openai_api_key = Some(key);
+    }
+    if let Some(key) = env("GEMINI_API_KEY") {
+        config.gemini_api_key = Some(key);
+    }
+    if let Some(url) = env("OLLAMA_URL") {
+        config.ollama_url = Some(url);
+    }
+    if let Some(model) = env("LLM_MODEL") {
+        config.model = Some(model);
+    }
+    if let Some(tokens) = env("SUMMARY_MAX_TOKENS").and_then(|v| v.parse().ok()) {
+        config.summary_max_tokens = Some(tokens);
+    }
+    if let Some(tokens) = env("ANALYSIS_MAX_TOKENS").and_then(|v| v.parse().ok()) {
+        config.analysis_max_tokens = Some(tokens);
+    }
+    if let Some(enabled) = env_flag("COMBINE_LLM_REQUESTS") {
+        config.combine_llm_requests = Some(enabled);
+    }
+    if let Some(token) = env("TELEGRAM_BOT_TOKEN") {
+        config.telegram_bot_token = Some(token);
+    }
+    if let Some(chat_id) = env("TELEGRAM_CHAT_ID") {
+        config.telegram_chat_id = Some(chat_id);
+    }
+    if let Some(chat_ids) = env("TELEGRAM_CHAT_IDS") {
+        config.telegram_chats = chat_ids
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(TelegramChat::new)
+            .collect();
+    }
+    // JSON object of content type to forum topic id, e.g. `{"code": 12, "default": 1}`
+    if let Some(topics) = env("TELEGRAM_TOPICS").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.telegram_topics = topics;
+    }
+    if let Some(secs) = env("SESSION_WINDOW_SECS").and_then(|v| v.parse().ok()) {
+        config.session_window_secs = Some(secs);
+    }
+    if let Some(enabled) = env_flag("DIGEST_ENABLED") {
+        config.digest_enabled = enabled;
+    }
+    if let Some(time) = env("DIGEST_TIME") {
+        config.digest_time = Some(time);
+    }
+    if let Some(timezone) = env("DIGEST_TIMEZONE") {
+        config.digest_timezone = Some(timezone);
+    }
+    if let Some(enabled) = env_flag("ENABLE_DESKTOP_DETECTION") {
+        config.enable_desktop_detection = enabled;
+    }
+    if let Some(enabled) = env_flag("ENABLE_CLIPBOARD_DETECTION") {
+        config.enable_clipboard_detection = enabled;
+    }
+    if let Some(sources) = env("DESKTOP_NOTIFICATION_SOURCES") {
+        config.desktop_notification_sources = Some(
+            sources
+                .split(',')
+                .map(str::trim)
+                .filter(|source| !source.is_empty())
+                .map(String::from)
+                .collect(),
+        );
+    }
+    if let Some(paths) = std::env::var_os("WATCH_DIRECTORIES") {
+        config.watch_directories = std::env::split_paths(&paths)
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| WatchDirectory {
+                path,
+                ..Default::default()
+            })
+            .collect();
+    }
+    if let Some(dimension) = env("MAX_IMAGE_DIMENSION").and_then(|v| v.parse().ok()) {
+        config.max_image_dimension = Some(dimension);
+    }
+    if let Some(bytes) = env("MAX_IMAGE_BYTES").and_then(|v| v.parse().ok()) {
+        config.max_image_bytes = Some(bytes);
+    }
+    if let Some(limit) = env("MAX_CONCURRENT_REQUESTS").and_then(|v| v.parse().ok()) {
+        config.max_concurrent_requests = Some(limit);
+    }
+    if let Some(depth) = env("MAX_QUEUE_DEPTH").and_then(|v| v.parse().ok()) {
+        config.max_queue_depth = Some(depth);
+    }
+    if let Some(secs) = env("DUPLICATE_WINDOW_SECS").and_then(|v| v.parse().ok()) {
+        config.duplicate_window_secs = Some(secs);
+    }
+    if let Some(policy) = env("IMAGE_POLICY").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.image_policy = Some(policy);
+    }
+    if let Some(recordings) = env("RECORDINGS").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.recordings = Some(recordings);
+    }
+    if let Some(journal) = env("JOURNAL").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.journal = Some(journal);
+    }
+    if let Some(research) = env("RESEARCH").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.research = Some(research);
+    }
+    if let Some(key) = env("SEARCH_API_KEY") {
+        config.research.get_or_insert_with(ResearchConfig::default).search_api_key = Some(key);
+    }
+    if let Some(tts) = env("TTS").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.tts = Some(tts);
+    }
+    if let Some(retention) = env("RETENTION").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.retention = Some(retention);
+    }
+    if let Some(secs) = env("IDEMPOTENCY_WINDOW_SECS").and_then(|v| v.parse().ok()) {
+        config.idempotency_window_secs = Some(secs);
+    }
+    if let Some(enabled) = env_flag("PERCEPTUAL_DEDUPE") {
+        config.perceptual_dedupe = Some(enabled);
+    }
+    if let Some(mode) = env("REDACTION_MODE")
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v.to_lowercase())).ok())
+    {
+        config.redaction_mode = Some(mode);
+    }
+    if let Some(sources) = env("REDACTION_SOURCES").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.redaction_sources = sources;
+    }
+    if let Some(profiles) = env("PROFILES").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.profiles = profiles;
+    }
+    if let Some(attempts) = env("LLM_RETRY_ATTEMPTS").and_then(|v| v.parse().ok()) {
+        config.retry_attempts = Some(attempts);
+    }
+    if let Some(delay) = env("LLM_RETRY_BASE_DELAY_MS").and_then(|v| v.parse().ok()) {
+        config.retry_base_delay_ms = Some(delay);
+    }
+    if let Some(enabled) = env_flag("PRIVACY_MODE") {
+        config.privacy_mode = enabled;
+    }
+    if let Some(mode) = env("PAUSE_MODE")
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v.to_lowercase())).ok())
+    {
+        config.pause_mode = Some(mode);
+    }
+    if let Some(mode) = env("CONTENT_FILTER")
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v.to_lowercase())).ok())
+    {
+        config.content_filter = Some(mode);
+    }
+    if let Some(rules) = env("EXCLUSION_RULES").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.exclusion_rules = Some(rules);
+    }
+    if let Some(enabled) = env_flag("ENCRYPT_STORAGE") {
+        config.encrypt_storage = enabled;
+    }
+    if let Some(model) = env("LOCAL_MODEL") {
+        config.local_model = Some(model);
+    }
+    if let Some(provider) = env("LLM_FALLBACK_PROVIDER")
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v.to_lowercase())).ok())
+    {
+        config.fallback_provider = Some(provider);
+    }
+    if let Some(model) = env("LLM_FALLBACK_MODEL") {
+        config.fallback_model = Some(model);
+    }
+    // JSON object of model id prefix to `{"input_per_mtok": .., "output_per_mtok": ..}`
+    if let Some(prices) = env("MODEL_PRICES").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.model_prices = prices;
+    }
+    if let Some(limit) = env("BUDGET_SOFT_LIMIT_USD").and_then(|v| v.parse().ok()) {
+        config.budget_soft_limit_usd = Some(limit);
+    }
+    if let Some(limit) = env("BUDGET_HARD_LIMIT_USD").and_then(|v| v.parse().ok()) {
+        config.budget_hard_limit_usd = Some(limit);
+    }
+    // Comma-separated webhook URLs, all signed with `WEBHOOK_SECRET` if set
+    if let Some(urls) = env("WEBHOOK_URLS") {
+        let secret = env("WEBHOOK_SECRET");
+        config.webhooks = urls
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(|url| WebhookConfig {
+                url: url.to_string(),
+                secret: secret.clone(),
+            })
+            .collect();
+    }
+    if let Some(url) = env("PUBLIC_URL") {
+        config.public_url = Some(url);
+    }
+    if let Some(mappings) = env("APP_PROMPT_TEMPLATES").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.app_prompt_templates = mappings;
+    }
+    if let Some(mappings) = env("CONTENT_TYPE_PROMPT_TEMPLATES").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.content_type_prompt_templates = mappings;
+    }
+    // JSON array of backends, e.g. `[{"kind": "ntfy", "topic": "screenshots"}]`
+    if let Some(notifiers) = env("PUSH_NOTIFIERS").and_then(|v| serde_json::from_str(&v).ok()) {
+        config.push_notifiers = notifiers;
+    }
+    if let Some(dir) = std::env::var_os("VAULT_EXPORT_DIR").filter(|v| !v.is_empty()) {
+        config.vault_export_dir = Some(PathBuf::from(dir));
+    }
+    if let Some(enabled) = env_flag("VAULT_AUTO_EXPORT") {
+        config.vault_auto_export = enabled;
+    }
+    if let Some(enabled) = env_flag("SPOTLIGHT_INDEX") {
+        config.spotlight_index = enabled;
+    }
+    if let Some(dir) = std::env::var_os("SPOTLIGHT_DIR").filter(|v| !v.is_empty()) {
+        config.spotlight_dir = Some(PathBuf::from(dir));
+    }
+    if let Some(mode) = env("METADATA_WRITEBACK")
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v.to_lowercase())).ok())
+    {
+        config.metadata_writeback = Some(mode);
+    }
+    if let Some(enabled) = env_flag("UNFURL_LINKS") {
+        config.unfurl_links = Some(enabled);
+    }
+    if let Some(enabled) = env_flag("RECEIPT_EXTRACTION") {
+        config.receipt_extraction = Some(enabled);
+    }
+    if let Some(path) = std::env::var_os("RECEIPT_LEDGER").filter(|v| !v.is_empty()) {
+        config.receipt_ledger = Some(PathBuf::from(path));
+    }
+    if let Some(enabled) = env_flag("CODE_EXTRACTION") {
+        config.code_extraction = Some(enabled);
+    }
+    if let Some(enabled) = env_flag("TABLE_EXTRACTION") {
+        config.table_extraction = Some(enabled);
+    }
+    if let Some(enabled) = env_flag("AUTO_TRANSLATE") {
+        config.auto_translate = Some(enabled);
+    }
+    if let Some(language) = env("TRANSLATION_LANGUAGE") {
+        config.translation_language = Some(language);
+    }
+    if let Some(language) = env("RESPONSE_LANGUAGE") {
+        config.response_language = Some(language);
+    }
+    if let Some(token) = env("NOTION_TOKEN") {
+        config.notion_token = Some(token);
+    }
+    if let Some(database_id) = env("NOTION_DATABASE_ID") {
+        config.notion_database_id = Some(database_id);
+    }
+    if let Some(port) = env("SERVER_PORT").and_then(|v| v.parse().ok()) {
+        config.server_port = port;
+    }
+    if let Some(range) = env("SERVER_PORT_RANGE").and_then(|v| v.parse().ok()) {
+        config.server_port_range = Some(range);
+    }
+    if let Some(port) = env("GRPC_PORT").and_then(|v| v.parse().ok()) {
+        config.grpc_port = Some(port);
+    }
+    if let Some(limit) = env("RATE_LIMIT_PER_MINUTE").and_then(|v| v.parse().ok()) {
+        config.rate_limit_per_minute = Some(limit);
+    }
+    if let Some(days) = env("AUDIT_RETENTION_DAYS").and_then(|v| v.parse().ok()) {
+        config.audit_retention_days = Some(days);
+    }
+    if let Some(filter) = env("LOG_FILTER") {
+        config.log_filter = Some(filter);
+    }
+    if let Some(key) = env("SERVER_API_KEY") {
+        config.api_key = Some(key);
+    }
+    if let Some(enabled) = env_flag("MDNS_ENABLED") {
+        config.mdns_enabled = Some(enabled);
+    }
+    if let Some(enabled) = env_flag("TLS_ENABLED") {
+        config.tls_enabled = enabled;
+    }
+    if let Some(path) = std::env::var_os("TLS_CERT_PATH").filter(|v| !v.is_empty()) {
+        config.tls_cert_path = Some(PathBuf::from(path));
+    }
+    if let Some(path) = std::env::var_os("TLS_KEY_PATH").filter(|v| !v.is_empty()) {
+        config.tls_key_path = Some(PathBuf::from(path));
+    }
+    if let Some(enabled) = env_flag("AUTO_START_SERVER") {
+        config.auto_start_server = enabled;
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::{journal::JOURNAL_SOURCE, vault, AnalysisData, ScreenshotProcessor};
+
+const INDEX_DIR_NAME: &str = "Screenshot AI";
+const REINDEX_PAGE_SIZE: usize = 100;
+
+/// The folder index stubs are written to: the configured one, or one in Documents, which Spotlight,
+/// Windows Search and the Linux desktop indexers all cover.
+fn index_dir(configured: Option<&PathBuf>) -> PathBuf {
+    match configured {
+        Some(dir) => match (dir.strip_prefix("~"), dirs::home_dir()) {
+            (Ok(rest), Some(home)) => home.join(rest),
+            _ => dir.clone(),
+        },
+        None => dirs::document_dir()
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join(INDEX_DIR_NAME),
+    }
+}
+
+/// A plain Markdown stub holding the text system search should match on.
+fn render_stub(analysis_id: &str, analysis: &AnalysisData) -> String {
+    let content = &analysis.content_analysis;
+    let metadata = &analysis.metadata;
+    let mut stub = format!("# {}\n\n", analysis.brief_summary.lines().next().unwrap_or_default().trim());
+    stub.push_str(&format!("{}\n\n", analysis.brief_summary.trim()));
+
+    if !content.research_topics.is_empty() {
+        stub.push_str(&format!("Topics: {}\n", content.research_topics.join(", ")));
+    }
+    stub.push_str(&format!("Type: {}\n", content.content_type));
+    if let Some(ref app) = metadata.app {
+        match metadata.window_title {
+            Some(ref title) => stub.push_str(&format!("App: {} — {}\n", app, title)),
+            None => stub.push_str(&format!("App: {}\n", app)),
+        }
+    }
+    if let Some(ref url) = content.webpage_url {
+        stub.push_str(&format!("Page: {}\n", url));
+    }
+    if let Some(ref path) = metadata.file_path {
+        stub.push_str(&format!("File: {}\n", path));
+    }
+    stub.push_str(&format!(
+        "Taken: {}\nAnalysis: {}\n",
+        analysis.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+        analysis_id
+    ));
+    stub
+}
+
+/// The short analysis id in a stub's name, `<date> <title> (<short id>).md`.
+fn stub_short_id(name: &str) -> Option<&str> {
+    let (_, short_id) = name.strip_suffix(").md")?.rsplit_once(" (")?;
+    (short_id.len() == 8 && short_id.chars().all(|c| c.is_ascii_hexdigit())).then_some(short_id)
+}
+
+/// Stubs in `dir`, optionally only the one for an analysis.
+fn stubs(dir: &Path, analysis_id: Option<&str>) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            match (stub_short_id(&name), analysis_id) {
+                (Some(short_id), Some(id)) => id.starts_with(short_id),
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        })
+        .collect()
+}
+
+/// Asks Spotlight to pick up the folder now rather than whenever it next notices the changes.
+fn request_import(dir: &Path) {
+    if !cfg!(target_os = "macos") {
+        return;
+    }
+    if let Err(e) = std::process::Command::new("mdimport").arg(dir).status() {
+        warn!("Failed to run mdimport on {}: {}", dir.display(), e);
+    }
+}
+
+impl ScreenshotProcessor {
+    fn spotlight_dir(&self) -> PathBuf {
+        index_dir(self.config.spotlight_dir.as_ref())
+    }
+
+    fn write_stub(&self, dir: &Path, analysis_id: &str, analysis: &AnalysisData) -> Result<()> {
+        let path = dir.join(vault::note_file_name(analysis_id, analysis));
+        std::fs::write(&path, render_stub(analysis_id, analysis))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Writes a search stub for a freshly completed analysis when indexing is on. Journal captures
+    /// are left out so they don't crowd system search.
+    pub(crate) fn index_for_search(&self, analysis_id: &str, analysis: &AnalysisData) {
+        if !self.config.spotlight_index || analysis.source == JOURNAL_SOURCE {
+            return;
+        }
+        let dir = self.spotlight_dir();
+        // The summary in the name may have changed, e.g. after a re-analysis
+        self.remove_from_search_index(Some(analysis_id));
+        let result = std::fs::create_dir_all(&dir)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| self.write_stub(&dir, analysis_id, analysis));
+        if let Err(e) = result {
+            warn!("Failed to index analysis {} for search: {}", analysis_id, e);
+        }
+    }
+
+    /// Removes an analysis's stub, or every stub if no id is given.
+    pub(crate) fn remove_from_search_index(&self, analysis_id: Option<&str>) {
+        if !self.config.spotlight_index {
+            return;
+        }
+        for stub in stubs(&self.spotlight_dir(), analysis_id) {
+            if let Err(e) = std::fs::remove_file(&stub) {
+                warn!("Failed to remove {}: {}", stub.display(), e);
+            }
+        }
+    }
+
+    /// Rewrites the stubs for every analysis, returning how many were written.
+    pub fn reindex_spotlight(&self) -> Result<usize> {
+        let dir = self.spotlight_dir();
+        std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        for stub in stubs(&dir, None) {
+            std::fs::remove_file(&stub).with_context(|| format!("Failed to remove {}", stub.display()))?;
+        }
+
+        let mut written = 0;
+        let mut offset = 0;
+        loop {
+            let page = self.store.list(offset, REINDEX_PAGE_SIZE)?;
+            for (id, analysis) in &page {
+                if analysis.source != JOURNAL_SOURCE {
+                    self.write_stub(&dir, id, analysis)?;
+                    written += 1;
+                }
+            }
+            if page.len() < REINDEX_PAGE_SIZE {
+                break;
+            }
+            offset += page.len();
+        }
+
+        request_import(&dir);
+        info!("🔎 Indexed {} analyses for system search in {}", written, dir.display());
+        Ok(written)
+    }
+}
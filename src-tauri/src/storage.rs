@@ -0,0 +1,2174 @@
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tracing::{info, warn};
+
+use crate::{
+    annotations::Annotation,
+    audit::{AuditEntry, AuditQuery},
+    code::CodeSnippet,
+    collections::Collection,
+    conversation::ChatTurn,
+    devices::Device,
+    encryption::StorageKey,
+    exclusions::SkippedScreenshot,
+    failures::FailureRecord,
+    images::{sniff_media_type, thumbnail, ImageFiles},
+    journal::JournalEntry,
+    links::Link,
+    outbox::OutboxItem,
+    projects::Project,
+    providers::ChatRole,
+    receipts::ReceiptData,
+    research::ResearchReport,
+    rules::{Rule, RuleRun},
+    sessions::Session,
+    tables::ExtractedTables,
+    telegram::TelegramMessageRef,
+    translation::Translation,
+    usage::{UsageRecord, UsageTotals},
+    AnalysisData, ProcessedImage, ScreenshotMetadata,
+};
+
+/// Schema migrations, applied in order. The index of the last applied entry is
+/// tracked in SQLite's `user_version` pragma, so only append to this list.
+const MIGRATIONS: &[&str] = &[r#"
+    CREATE TABLE analyses (
+        id               TEXT PRIMARY KEY NOT NULL,
+        timestamp        TEXT NOT NULL,
+        source           TEXT NOT NULL,
+        brief_summary    TEXT NOT NULL,
+        content_analysis TEXT NOT NULL,
+        metadata         TEXT NOT NULL,
+        media_type       TEXT NOT NULL,
+        size_bytes       INTEGER NOT NULL,
+        image_base64     TEXT NOT NULL
+    );
+    CREATE INDEX idx_analyses_timestamp ON analyses (timestamp DESC);
+"#, r#"
+    CREATE TABLE conversation_turns (
+        id          INTEGER PRIMARY KEY AUTOINCREMENT,
+        analysis_id TEXT NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        role        TEXT NOT NULL,
+        content     TEXT NOT NULL,
+        timestamp   TEXT NOT NULL
+    );
+    CREATE INDEX idx_conversation_turns_analysis ON conversation_turns (analysis_id, id);
+"#, r#"
+    -- Details are joined with || rather than concat_ws, which the bundled SQLCipher's SQLite predates
+    CREATE VIRTUAL TABLE analyses_fts USING fts5 (
+        id UNINDEXED,
+        summary,
+        topics,
+        details,
+        filename,
+        tokenize = 'porter unicode61'
+    );
+
+    CREATE TRIGGER analyses_fts_insert AFTER INSERT ON analyses BEGIN
+        DELETE FROM analyses_fts WHERE id = new.id;
+        INSERT INTO analyses_fts (id, summary, topics, details, filename) VALUES (
+            new.id,
+            new.brief_summary,
+            json_extract(new.content_analysis, '$.research_topics'),
+            trim(
+                ifnull(json_extract(new.content_analysis, '$.content_type'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.webpage_url'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.user_intent'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.app'), '')),
+            json_extract(new.metadata, '$.filename')
+        );
+    END;
+
+    CREATE TRIGGER analyses_fts_delete AFTER DELETE ON analyses BEGIN
+        DELETE FROM analyses_fts WHERE id = old.id;
+    END;
+
+    INSERT INTO analyses_fts (id, summary, topics, details, filename)
+    SELECT
+        id,
+        brief_summary,
+        json_extract(content_analysis, '$.research_topics'),
+        trim(
+            ifnull(json_extract(content_analysis, '$.content_type'), '') || ' ' ||
+            ifnull(json_extract(content_analysis, '$.webpage_url'), '') || ' ' ||
+            ifnull(json_extract(content_analysis, '$.user_intent'), '') || ' ' ||
+            ifnull(json_extract(metadata, '$.app'), '')),
+        json_extract(metadata, '$.filename')
+    FROM analyses;
+"#, r#"
+    CREATE TABLE outbox (
+        id           INTEGER PRIMARY KEY AUTOINCREMENT,
+        image_base64 TEXT NOT NULL,
+        metadata     TEXT NOT NULL,
+        queued_at    TEXT NOT NULL,
+        attempts     INTEGER NOT NULL DEFAULT 0,
+        last_error   TEXT
+    );
+"#, r#"
+    CREATE TABLE llm_usage (
+        id            INTEGER PRIMARY KEY AUTOINCREMENT,
+        analysis_id   TEXT,
+        provider      TEXT NOT NULL,
+        model         TEXT NOT NULL,
+        input_tokens  INTEGER NOT NULL,
+        output_tokens INTEGER NOT NULL,
+        cost_usd      REAL NOT NULL,
+        timestamp     TEXT NOT NULL
+    );
+    CREATE INDEX idx_llm_usage_timestamp ON llm_usage (timestamp);
+"#, r#"
+    ALTER TABLE analyses ADD COLUMN telegram_messages TEXT NOT NULL DEFAULT '[]';
+"#, r#"
+    ALTER TABLE analyses ADD COLUMN revision_of TEXT REFERENCES analyses (id) ON DELETE SET NULL;
+    CREATE INDEX idx_analyses_revision_of ON analyses (revision_of);
+"#, r#"
+    CREATE TABLE audit_log (
+        id             INTEGER PRIMARY KEY AUTOINCREMENT,
+        timestamp      TEXT NOT NULL,
+        client_ip      TEXT NOT NULL,
+        device_name    TEXT,
+        method         TEXT NOT NULL,
+        path           TEXT NOT NULL,
+        status         INTEGER NOT NULL,
+        request_bytes  INTEGER,
+        latency_ms     INTEGER NOT NULL,
+        input_tokens   INTEGER NOT NULL,
+        output_tokens  INTEGER NOT NULL
+    );
+    CREATE INDEX idx_audit_log_timestamp ON audit_log (timestamp DESC);
+"#, r#"
+    CREATE TABLE devices (
+        id            TEXT PRIMARY KEY NOT NULL,
+        name          TEXT NOT NULL,
+        first_seen    TEXT NOT NULL,
+        last_seen     TEXT NOT NULL,
+        last_ip       TEXT,
+        request_count INTEGER NOT NULL DEFAULT 0,
+        enabled       INTEGER NOT NULL DEFAULT 1
+    );
+"#, r#"
+    DROP TRIGGER analyses_fts_insert;
+    CREATE TRIGGER analyses_fts_insert AFTER INSERT ON analyses BEGIN
+        DELETE FROM analyses_fts WHERE id = new.id;
+        INSERT INTO analyses_fts (id, summary, topics, details, filename) VALUES (
+            new.id,
+            new.brief_summary,
+            json_extract(new.content_analysis, '$.research_topics'),
+            trim(
+                ifnull(json_extract(new.content_analysis, '$.content_type'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.webpage_url'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.user_intent'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.app'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.window_title'), '')),
+            json_extract(new.metadata, '$.filename')
+        );
+    END;
+"#, r#"
+    CREATE TABLE collections (
+        id          TEXT PRIMARY KEY NOT NULL,
+        name        TEXT NOT NULL,
+        match_mode  TEXT NOT NULL,
+        rules       TEXT NOT NULL,
+        created_at  TEXT NOT NULL
+    );
+"#, r#"
+    CREATE TABLE sessions (
+        id            TEXT PRIMARY KEY NOT NULL,
+        started_at    TEXT NOT NULL,
+        ended_at      TEXT NOT NULL,
+        summary       TEXT,
+        analysis_ids  TEXT NOT NULL
+    );
+    CREATE INDEX idx_sessions_started_at ON sessions (started_at);
+"#, r#"
+    CREATE TABLE research_reports (
+        analysis_id  TEXT PRIMARY KEY NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        created_at   TEXT NOT NULL,
+        report       TEXT NOT NULL
+    );
+"#, r#"
+    CREATE TABLE receipts (
+        analysis_id   TEXT PRIMARY KEY NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        extracted_at  TEXT NOT NULL,
+        data          TEXT NOT NULL
+    );
+"#, r#"
+    CREATE TABLE code_snippets (
+        analysis_id   TEXT PRIMARY KEY NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        language      TEXT NOT NULL,
+        code          TEXT NOT NULL,
+        file_name     TEXT NOT NULL,
+        extracted_at  TEXT NOT NULL
+    );
+"#, r#"
+    CREATE TABLE extracted_tables (
+        analysis_id   TEXT PRIMARY KEY NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        extracted_at  TEXT NOT NULL,
+        data          TEXT NOT NULL
+    );
+"#, r#"
+    CREATE TABLE translations (
+        analysis_id      TEXT NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        target_language  TEXT NOT NULL COLLATE NOCASE,
+        translated_at    TEXT NOT NULL,
+        data             TEXT NOT NULL,
+        PRIMARY KEY (analysis_id, target_language)
+    );
+"#, r#"
+    ALTER TABLE analyses ADD COLUMN image_hash TEXT;
+    ALTER TABLE analyses ADD COLUMN original_hash TEXT;
+    ALTER TABLE analyses ADD COLUMN original_media_type TEXT;
+"#, r#"
+    ALTER TABLE analyses ADD COLUMN thumbnail_hash TEXT;
+"#, r#"
+    CREATE TABLE failed_analyses (
+        id            TEXT PRIMARY KEY NOT NULL,
+        failed_at     TEXT NOT NULL,
+        source        TEXT NOT NULL,
+        error         TEXT NOT NULL,
+        image_base64  TEXT NOT NULL,
+        metadata      TEXT NOT NULL,
+        attempts      INTEGER NOT NULL DEFAULT 1
+    );
+"#, r#"
+    CREATE TABLE skipped_screenshots (
+        id            INTEGER PRIMARY KEY AUTOINCREMENT,
+        skipped_at    TEXT NOT NULL,
+        source        TEXT NOT NULL,
+        rule          TEXT NOT NULL,
+        app           TEXT,
+        window_title  TEXT,
+        filename      TEXT
+    );
+"#, r#"
+    CREATE TABLE analysis_frames (
+        analysis_id  TEXT NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        position     INTEGER NOT NULL,
+        image_hash   TEXT NOT NULL,
+        PRIMARY KEY (analysis_id, position)
+    );
+"#, r#"
+    CREATE TABLE annotations (
+        id           TEXT PRIMARY KEY NOT NULL,
+        analysis_id  TEXT NOT NULL REFERENCES analyses (id) ON DELETE CASCADE,
+        shape        TEXT NOT NULL,
+        color        TEXT NOT NULL,
+        created_at   TEXT NOT NULL,
+        updated_at   TEXT NOT NULL
+    );
+    CREATE INDEX annotations_analysis ON annotations (analysis_id);
+"#, r#"
+    ALTER TABLE analyses ADD COLUMN note TEXT;
+    ALTER TABLE analyses ADD COLUMN starred INTEGER NOT NULL DEFAULT 0;
+    CREATE INDEX idx_analyses_starred ON analyses (starred) WHERE starred = 1;
+
+    DROP TRIGGER analyses_fts_insert;
+    CREATE TRIGGER analyses_fts_insert AFTER INSERT ON analyses BEGIN
+        DELETE FROM analyses_fts WHERE id = new.id;
+        INSERT INTO analyses_fts (id, summary, topics, details, filename) VALUES (
+            new.id,
+            new.brief_summary,
+            json_extract(new.content_analysis, '$.research_topics'),
+            trim(
+                ifnull(json_extract(new.content_analysis, '$.content_type'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.webpage_url'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.user_intent'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.app'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.window_title'), '') || ' ' ||
+                ifnull(new.note, '')),
+            json_extract(new.metadata, '$.filename')
+        );
+    END;
+
+    -- Notes are edited in place, so they're indexed again when they change
+    CREATE TRIGGER analyses_fts_note AFTER UPDATE OF note ON analyses BEGIN
+        DELETE FROM analyses_fts WHERE id = new.id;
+        INSERT INTO analyses_fts (id, summary, topics, details, filename) VALUES (
+            new.id,
+            new.brief_summary,
+            json_extract(new.content_analysis, '$.research_topics'),
+            trim(
+                ifnull(json_extract(new.content_analysis, '$.content_type'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.webpage_url'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.user_intent'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.app'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.window_title'), '') || ' ' ||
+                ifnull(new.note, '')),
+            json_extract(new.metadata, '$.filename')
+        );
+    END;
+"#, r#"
+    CREATE TABLE projects (
+        id                TEXT PRIMARY KEY NOT NULL,
+        name              TEXT NOT NULL,
+        color             TEXT NOT NULL,
+        prompt_template   TEXT,
+        telegram_chat_id  TEXT,
+        rules             TEXT NOT NULL,
+        active            INTEGER NOT NULL DEFAULT 0,
+        created_at        TEXT NOT NULL
+    );
+
+    ALTER TABLE analyses ADD COLUMN project_id TEXT REFERENCES projects (id) ON DELETE SET NULL;
+    CREATE INDEX idx_analyses_project ON analyses (project_id, timestamp DESC);
+"#, r#"
+    CREATE TABLE rules (
+        id          TEXT PRIMARY KEY NOT NULL,
+        name        TEXT NOT NULL,
+        enabled     INTEGER NOT NULL DEFAULT 1,
+        match_mode  TEXT NOT NULL,
+        conditions  TEXT NOT NULL,
+        actions     TEXT NOT NULL,
+        created_at  TEXT NOT NULL
+    );
+
+    -- Capped like skipped_screenshots; kept when the rule or analysis goes, as a log
+    CREATE TABLE rule_runs (
+        id           INTEGER PRIMARY KEY AUTOINCREMENT,
+        rule_id      TEXT NOT NULL,
+        rule_name    TEXT NOT NULL,
+        analysis_id  TEXT NOT NULL,
+        ran_at       TEXT NOT NULL,
+        outcomes     TEXT NOT NULL
+    );
+    CREATE INDEX idx_rule_runs_rule ON rule_runs (rule_id, id DESC);
+"#, r#"
+    -- Rebuilt with a column for the text read off each screenshot. Analyses from before it was read
+    -- are indexed again without it, until they're re-analyzed
+    DROP TRIGGER analyses_fts_insert;
+    DROP TRIGGER analyses_fts_note;
+    DROP TABLE analyses_fts;
+    CREATE VIRTUAL TABLE analyses_fts USING fts5 (
+        id UNINDEXED,
+        summary,
+        topics,
+        details,
+        filename,
+        extracted_text,
+        tokenize = 'porter unicode61'
+    );
+
+    CREATE TRIGGER analyses_fts_insert AFTER INSERT ON analyses BEGIN
+        DELETE FROM analyses_fts WHERE id = new.id;
+        INSERT INTO analyses_fts (id, summary, topics, details, filename, extracted_text) VALUES (
+            new.id,
+            new.brief_summary,
+            json_extract(new.content_analysis, '$.research_topics'),
+            trim(
+                ifnull(json_extract(new.content_analysis, '$.content_type'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.webpage_url'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.user_intent'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.app'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.window_title'), '') || ' ' ||
+                ifnull(new.note, '')),
+            json_extract(new.metadata, '$.filename'),
+            json_extract(new.content_analysis, '$.extracted_text')
+        );
+    END;
+
+    CREATE TRIGGER analyses_fts_note AFTER UPDATE OF note ON analyses BEGIN
+        DELETE FROM analyses_fts WHERE id = new.id;
+        INSERT INTO analyses_fts (id, summary, topics, details, filename, extracted_text) VALUES (
+            new.id,
+            new.brief_summary,
+            json_extract(new.content_analysis, '$.research_topics'),
+            trim(
+                ifnull(json_extract(new.content_analysis, '$.content_type'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.webpage_url'), '') || ' ' ||
+                ifnull(json_extract(new.content_analysis, '$.user_intent'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.app'), '') || ' ' ||
+                ifnull(json_extract(new.metadata, '$.window_title'), '') || ' ' ||
+                ifnull(new.note, '')),
+            json_extract(new.metadata, '$.filename'),
+            json_extract(new.content_analysis, '$.extracted_text')
+        );
+    END;
+
+    INSERT INTO analyses_fts (id, summary, topics, details, filename, extracted_text)
+    SELECT
+        id,
+        brief_summary,
+        json_extract(content_analysis, '$.research_topics'),
+        trim(
+            ifnull(json_extract(content_analysis, '$.content_type'), '') || ' ' ||
+            ifnull(json_extract(content_analysis, '$.webpage_url'), '') || ' ' ||
+            ifnull(json_extract(content_analysis, '$.user_intent'), '') || ' ' ||
+            ifnull(json_extract(metadata, '$.app'), '') || ' ' ||
+            ifnull(json_extract(metadata, '$.window_title'), '') || ' ' ||
+            ifnull(note, '')),
+        json_extract(metadata, '$.filename'),
+        json_extract(content_analysis, '$.extracted_text')
+    FROM analyses;
+"#];
+
+const ANALYSIS_COLUMNS: &str =
+    "id, timestamp, source, brief_summary, content_analysis, metadata, media_type, size_bytes, image_base64, telegram_messages, revision_of, note, starred, project_id";
+
+/// Image files live in `images` next to the database.
+fn images_dir(database: &Path) -> PathBuf {
+    database.parent().unwrap_or(Path::new(".")).join("images")
+}
+
+/// Bytes on disk taken by stored analyses.
+#[derive(Debug, Clone, Copy)]
+pub struct DiskUsage {
+    pub database_bytes: u64,
+    pub image_bytes: u64,
+}
+
+impl DiskUsage {
+    pub fn total(&self) -> u64 {
+        self.database_bytes + self.image_bytes
+    }
+}
+
+/// SQLite-backed store for completed analyses. Images are kept as files next to the database,
+/// named by content hash, and loaded only when asked for.
+pub struct AnalysisStore {
+    conn: Mutex<Connection>,
+    images: ImageFiles,
+}
+
+impl std::fmt::Debug for AnalysisStore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AnalysisStore")
+            .field("conn", &"Connection { ... }")
+            .finish()
+    }
+}
+
+impl AnalysisStore {
+    /// Opens the database at `path`, encrypted with `key` if given.
+    pub(crate) fn open(path: &Path, key: Option<Arc<StorageKey>>) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let conn = Connection::open(path)
+            .map_err(|e| anyhow!("Failed to open database {}: {}", path.display(), e))?;
+        if let Some(key) = &key {
+            key.unlock(&conn)?;
+        }
+        // The first statement to read the file, so a wrong key shows up here
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|e| anyhow!("Failed to read database {}: {}", path.display(), e))?;
+        // Overwrite deleted rows so purged screenshots don't linger in free pages
+        conn.pragma_update(None, "secure_delete", "ON")?;
+        conn.pragma_update(None, "foreign_keys", "ON")?;
+
+        let encrypted = key.is_some();
+        let store = Self {
+            conn: Mutex::new(conn),
+            images: ImageFiles::new(images_dir(path), key),
+        };
+        store.migrate()?;
+        store.move_images_to_disk()?;
+        if encrypted {
+            store.images.convert(true)?;
+        }
+
+        info!("🗄️ Analysis store opened at {}", path.display());
+        Ok(store)
+    }
+
+    /// Decrypts the image files of the database at `path`, before encryption is turned off.
+    pub(crate) fn decrypt_images(path: &Path, key: Arc<StorageKey>) -> Result<()> {
+        ImageFiles::new(images_dir(path), Some(key)).convert(false)
+    }
+
+    fn migrate(&self) -> Result<()> {
+        let mut conn = self.conn.lock();
+        let current: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+        for (index, migration) in MIGRATIONS.iter().enumerate().skip(current) {
+            let tx = conn.transaction()?;
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", index + 1)?;
+            tx.commit()?;
+            info!("🗄️ Applied database migration {}", index + 1);
+        }
+
+        Ok(())
+    }
+
+    /// Moves images still stored as base64 in the database, from before they were kept on disk,
+    /// into files.
+    fn move_images_to_disk(&self) -> Result<()> {
+        let legacy: Vec<(String, String)> = {
+            let conn = self.conn.lock();
+            // Unreadable images are cleared below, so they aren't picked up again on every start
+            let mut stmt =
+                conn.prepare("SELECT id, image_base64 FROM analyses WHERE image_hash IS NULL AND image_base64 != ''")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        if legacy.is_empty() {
+            return Ok(());
+        }
+
+        let mut moved = 0;
+        for (id, image_base64) in &legacy {
+            let hash = match general_purpose::STANDARD.decode(image_base64) {
+                Ok(bytes) => self.images.put(&bytes)?,
+                Err(e) => {
+                    warn!("Analysis {} has an unreadable image, dropping it: {}", id, e);
+                    self.conn
+                        .lock()
+                        .execute("UPDATE analyses SET image_base64 = '' WHERE id = ?1", params![id])?;
+                    continue;
+                }
+            };
+            self.conn.lock().execute(
+                "UPDATE analyses SET image_hash = ?2, image_base64 = '' WHERE id = ?1",
+                params![id, hash],
+            )?;
+            moved += 1;
+        }
+        if moved > 0 {
+            self.conn.lock().execute_batch("VACUUM;")?;
+            info!("🗄️ Moved {} images out of the database", moved);
+        }
+        Ok(())
+    }
+
+    pub fn insert(&self, id: &str, analysis: &AnalysisData) -> Result<()> {
+        let processed = general_purpose::STANDARD
+            .decode(&analysis.image_data.base64_data)
+            .map_err(|e| anyhow!("Invalid image data: {}", e))?;
+        let image_hash = self.images.put(&processed)?;
+        // The image as received, before downscaling and format conversion
+        let original_base64 = analysis
+            .image_base64
+            .split_once(";base64,")
+            .map(|(_, data)| data)
+            .unwrap_or(&analysis.image_base64);
+        let (original_hash, original_media_type) = match general_purpose::STANDARD.decode(original_base64) {
+            Ok(bytes) if !bytes.is_empty() => (
+                Some(self.images.put(&bytes)?),
+                sniff_media_type(&bytes).unwrap_or("application/octet-stream"),
+            ),
+            _ => (None, analysis.image_data.media_type.as_str()),
+        };
+        // A missing thumbnail is generated again the first time it's asked for
+        let thumbnail_hash = match thumbnail(&processed) {
+            Ok(bytes) => Some(self.images.put(&bytes)?),
+            Err(e) => {
+                warn!("Failed to generate thumbnail for analysis {}: {}", id, e);
+                None
+            }
+        };
+
+        self.conn.lock().execute(
+            &format!(
+                "INSERT OR REPLACE INTO analyses ({}, image_hash, original_hash, original_media_type, thumbnail_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+                ANALYSIS_COLUMNS
+            ),
+            params![
+                id,
+                analysis.timestamp.to_rfc3339(),
+                analysis.source,
+                analysis.brief_summary,
+                serde_json::to_string(&analysis.content_analysis)?,
+                serde_json::to_string(&analysis.metadata)?,
+                analysis.image_data.media_type,
+                analysis.image_data.size_bytes as i64,
+                "",
+                serde_json::to_string(&analysis.telegram_messages)?,
+                analysis.revision_of,
+                analysis.note,
+                analysis.starred,
+                analysis.project_id,
+                image_hash,
+                original_hash,
+                original_media_type,
+                thumbnail_hash,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// An analysis with its image loaded.
+    pub fn get(&self, id: &str) -> Result<Option<AnalysisData>> {
+        let analysis = self
+            .conn
+            .lock()
+            .query_row(
+                &format!("SELECT {} FROM analyses WHERE id = ?1", ANALYSIS_COLUMNS),
+                params![id],
+                Self::row_to_analysis,
+            )
+            .optional()?;
+        let Some((_, mut analysis)) = analysis else {
+            return Ok(None);
+        };
+        self.load_image(id, &mut analysis)?;
+        Ok(Some(analysis))
+    }
+
+    fn image_hash(&self, id: &str) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .lock()
+            .query_row("SELECT image_hash FROM analyses WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?
+            .flatten())
+    }
+
+    /// Reads an analysis's image from its file into `analysis`. Lists of analyses are loaded
+    /// without images; this fills one in when it's needed.
+    pub fn load_image(&self, id: &str, analysis: &mut AnalysisData) -> Result<()> {
+        if !analysis.image_data.base64_data.is_empty() {
+            return Ok(());
+        }
+        let Some(hash) = self.image_hash(id)? else {
+            return Ok(());
+        };
+        let base64_data = general_purpose::STANDARD.encode(self.images.get(&hash)?);
+        analysis.image_base64 = base64_data.clone();
+        analysis.image_data.base64_data = base64_data;
+        Ok(())
+    }
+
+    /// Loads the images of a list of analyses, skipping any whose file can't be read.
+    pub fn load_images(&self, analyses: &mut [(String, AnalysisData)]) {
+        for (id, analysis) in analyses.iter_mut() {
+            if let Err(e) = self.load_image(id, analysis) {
+                warn!("Failed to load image for analysis {}: {}", id, e);
+            }
+        }
+    }
+
+    /// The screenshot as it was received (or redacted), with its media type.
+    pub fn original_image(&self, id: &str) -> Result<Option<(Vec<u8>, String)>> {
+        // Falls back to the processed image, which is all analyses from before originals were kept have
+        let row: Option<(Option<String>, String, String)> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT COALESCE(original_hash, image_hash), COALESCE(original_media_type, media_type), image_base64
+                 FROM analyses WHERE id = ?1",
+                params![id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        let Some((hash, media_type, image_base64)) = row else {
+            return Ok(None);
+        };
+
+        let bytes = match hash {
+            Some(hash) => self.images.get(&hash)?,
+            None => general_purpose::STANDARD
+                .decode(image_base64)
+                .map_err(|e| anyhow!("Invalid stored image: {}", e))?,
+        };
+        Ok(Some((bytes, media_type)))
+    }
+
+    /// A small JPEG of an analysis's screenshot, generated and saved if it doesn't have one yet.
+    pub fn thumbnail(&self, id: &str) -> Result<Option<Vec<u8>>> {
+        let hash: Option<Option<String>> = self
+            .conn
+            .lock()
+            .query_row("SELECT thumbnail_hash FROM analyses WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        match hash {
+            None => return Ok(None),
+            Some(Some(hash)) => return self.images.get(&hash).map(Some),
+            Some(None) => {}
+        }
+
+        // Analyses from before thumbnails were kept
+        let processed = match self.image_hash(id)? {
+            Some(hash) => self.images.get(&hash)?,
+            None => match self.original_image(id)? {
+                Some((bytes, _)) => bytes,
+                None => return Ok(None),
+            },
+        };
+        let bytes = thumbnail(&processed)?;
+        let hash = self.images.put(&bytes)?;
+        self.conn.lock().execute(
+            "UPDATE analyses SET thumbnail_hash = ?1 WHERE id = ?2",
+            params![hash, id],
+        )?;
+        Ok(Some(bytes))
+    }
+
+    /// Stores the frames of a recording or animated GIF with its analysis, in order.
+    pub fn save_frames(&self, id: &str, frames: &[Vec<u8>]) -> Result<()> {
+        let hashes = frames
+            .iter()
+            .map(|frame| self.images.put(frame))
+            .collect::<Result<Vec<_>>>()?;
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        for (position, hash) in hashes.iter().enumerate() {
+            tx.execute(
+                "INSERT OR REPLACE INTO analysis_frames (analysis_id, position, image_hash) VALUES (?1, ?2, ?3)",
+                params![id, position as i64, hash],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The frames stored with an analysis, in order; empty for a single screenshot.
+    pub fn frames(&self, id: &str) -> Result<Vec<Vec<u8>>> {
+        let hashes: Vec<String> = {
+            let conn = self.conn.lock();
+            let mut stmt =
+                conn.prepare("SELECT image_hash FROM analysis_frames WHERE analysis_id = ?1 ORDER BY position")?;
+            let rows = stmt.query_map(params![id], |row| row.get(0))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+        hashes.iter().map(|hash| self.images.get(hash)).collect()
+    }
+
+    /// Deletes the files of `hashes` that no remaining analysis refers to.
+    fn remove_unreferenced_images(&self, hashes: Vec<String>) {
+        for hash in hashes {
+            let referenced = self
+                .conn
+                .lock()
+                .query_row(
+                    "SELECT EXISTS (SELECT 1 FROM analyses WHERE image_hash = ?1 OR original_hash = ?1 OR thumbnail_hash = ?1)
+                     OR EXISTS (SELECT 1 FROM analysis_frames WHERE image_hash = ?1)",
+                    params![hash],
+                    |row| row.get::<_, bool>(0),
+                )
+                .unwrap_or(true);
+            if !referenced {
+                if let Err(e) = self.images.remove(&hash) {
+                    warn!("Failed to remove image {}: {}", hash, e);
+                }
+            }
+        }
+    }
+
+    /// The image files of the analyses matching `condition`.
+    fn image_hashes_where(&self, condition: &str, values: &[String]) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT image_hash FROM analyses WHERE {0} AND image_hash IS NOT NULL
+             UNION SELECT original_hash FROM analyses WHERE {0} AND original_hash IS NOT NULL
+             UNION SELECT thumbnail_hash FROM analyses WHERE {0} AND thumbnail_hash IS NOT NULL
+             UNION SELECT image_hash FROM analysis_frames WHERE analysis_id IN (SELECT id FROM analyses WHERE {0})",
+            condition
+        ))?;
+        let bound: Vec<&String> = values.iter().chain(values).chain(values).chain(values).collect();
+        let rows = stmt.query_map(params_from_iter(bound), |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Returns the newest analyses first, paired with their ids.
+    pub fn recent(&self, limit: usize) -> Result<Vec<(String, AnalysisData)>> {
+        self.list(0, limit)
+    }
+
+    /// Returns a page of analyses, newest first.
+    pub fn list(&self, offset: usize, limit: usize) -> Result<Vec<(String, AnalysisData)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM analyses ORDER BY timestamp DESC LIMIT ?1 OFFSET ?2",
+            ANALYSIS_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![limit as i64, offset as i64], Self::row_to_analysis)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Returns analyses taken from `from` (inclusive) to `to` (exclusive), oldest first.
+    pub fn between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<(String, AnalysisData)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM analyses WHERE timestamp >= ?1 AND timestamp < ?2 ORDER BY timestamp",
+            ANALYSIS_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![from.to_rfc3339(), to.to_rfc3339()], Self::row_to_analysis)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Removes an analysis and its image data, returning whether it existed.
+    pub fn delete(&self, id: &str) -> Result<bool> {
+        let hashes = self.image_hashes_where("id = ?", &[id.to_string()])?;
+        let deleted = self
+            .conn
+            .lock()
+            .execute("DELETE FROM analyses WHERE id = ?1", params![id])?;
+        self.remove_unreferenced_images(hashes);
+        Ok(deleted > 0)
+    }
+
+    /// Removes analyses from `source` made before `before`, returning the number removed.
+    pub fn delete_source_before(&self, source: &str, before: DateTime<Utc>) -> Result<usize> {
+        let hashes =
+            self.image_hashes_where("source = ? AND timestamp < ?", &[source.to_string(), before.to_rfc3339()])?;
+        let deleted = self.conn.lock().execute(
+            "DELETE FROM analyses WHERE source = ?1 AND timestamp < ?2",
+            params![source, before.to_rfc3339()],
+        )?;
+        self.remove_unreferenced_images(hashes);
+        Ok(deleted)
+    }
+
+    /// Ids of the analyses taken before `before`.
+    pub fn ids_before(&self, before: DateTime<Utc>) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT id FROM analyses WHERE timestamp < ?1")?;
+        let rows = stmt.query_map(params![before.to_rfc3339()], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Ids of the analyses from `source` taken before `before`.
+    pub fn ids_from_source_before(&self, source: &str, before: DateTime<Utc>) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT id FROM analyses WHERE source = ?1 AND timestamp < ?2")?;
+        let rows = stmt.query_map(params![source, before.to_rfc3339()], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Ids of every analysis but the newest `keep`.
+    pub fn ids_after_newest(&self, keep: usize) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT id FROM analyses ORDER BY timestamp DESC LIMIT -1 OFFSET ?1")?;
+        let rows = stmt.query_map(params![keep as i64], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Ids of the `limit` oldest analyses, oldest first.
+    pub fn oldest_ids(&self, limit: usize) -> Result<Vec<String>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT id FROM analyses ORDER BY timestamp LIMIT ?1")?;
+        let rows = stmt.query_map(params![limit as i64], |row| row.get(0))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Removes the analyses with these ids and their image data, returning the number removed.
+    pub fn delete_many(&self, ids: &[String]) -> Result<usize> {
+        let mut deleted = 0;
+        // Each id is bound once per image column, which has to stay under SQLite's variable limit
+        for chunk in ids.chunks(200) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let condition = format!("id IN ({})", placeholders);
+            let hashes = self.image_hashes_where(&condition, chunk)?;
+            deleted += self.conn.lock().execute(
+                &format!("DELETE FROM analyses WHERE {}", condition),
+                params_from_iter(chunk),
+            )?;
+            self.remove_unreferenced_images(hashes);
+        }
+        Ok(deleted)
+    }
+
+    /// Space taken by the history: rows in the database, not counting free pages, and image files.
+    pub fn disk_usage(&self) -> Result<DiskUsage> {
+        let database_bytes: i64 = self.conn.lock().query_row(
+            "SELECT (page_count - freelist_count) * page_size
+             FROM pragma_page_count(), pragma_freelist_count(), pragma_page_size()",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(DiskUsage {
+            database_bytes: database_bytes.max(0) as u64,
+            image_bytes: self.images.size()?,
+        })
+    }
+
+    /// Rebuilds the database file so the space of deleted rows is returned to the file system.
+    pub fn compact(&self) -> Result<()> {
+        self.conn
+            .lock()
+            .execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+        Ok(())
+    }
+
+    /// Summaries of the analyses from `source` in a time range, without loading their images.
+    pub fn journal_entries(&self, source: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<JournalEntry>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, timestamp, json_extract(metadata, '$.app'), brief_summary
+             FROM analyses WHERE source = ?1 AND timestamp >= ?2 AND timestamp < ?3 ORDER BY timestamp",
+        )?;
+        let rows = stmt.query_map(params![source, from.to_rfc3339(), to.to_rfc3339()], |row| {
+            let timestamp: String = row.get(1)?;
+            Ok(JournalEntry {
+                analysis_id: row.get(0)?,
+                timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+                app: row.get(2)?,
+                summary: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Removes every analysis and compacts the database file, returning the number removed.
+    pub fn clear(&self) -> Result<usize> {
+        let conn = self.conn.lock();
+        let deleted = conn.execute("DELETE FROM analyses", [])?;
+        conn.execute("DELETE FROM failed_analyses", [])?;
+        conn.execute_batch("VACUUM; PRAGMA wal_checkpoint(TRUNCATE);")?;
+        self.images.clear()?;
+        Ok(deleted)
+    }
+
+    /// Full-text search over summaries, topics, notes, filenames and the text in each screenshot,
+    /// best match first, within one project when `project_id` is given. Each hit carries a snippet
+    /// with matches wrapped in `<mark>` tags and its bm25 rank.
+    pub fn search(
+        &self,
+        query: &str,
+        limit: usize,
+        project_id: Option<&str>,
+    ) -> Result<Vec<(String, AnalysisData, String, f64)>> {
+        let Some(fts_query) = Self::fts_query(query) else {
+            return Ok(Vec::new());
+        };
+
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, snippet(analyses_fts, -1, '<mark>', '</mark>', '…', 16), bm25(analyses_fts, 0.0, 4.0, 3.0, 1.0, 2.0, 1.0)
+             FROM analyses_fts JOIN analyses a ON a.id = analyses_fts.id
+             WHERE analyses_fts MATCH ?1 AND (?3 IS NULL OR a.project_id = ?3)
+             ORDER BY 16
+             LIMIT ?2",
+            ANALYSIS_COLUMNS
+                .split(", ")
+                .map(|column| format!("a.{}", column))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))?;
+        let rows = stmt.query_map(params![fts_query, limit as i64, project_id], |row| {
+            let (id, analysis) = Self::row_to_analysis(row)?;
+            Ok((id, analysis, row.get(14)?, row.get(15)?))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Turns free text into an FTS5 query: every word must match, the last one as a prefix.
+    fn fts_query(query: &str) -> Option<String> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        let last = terms.len().checked_sub(1)?;
+
+        Some(
+            terms
+                .iter()
+                .enumerate()
+                .map(|(i, term)| if i == last { format!("{}*", term) } else { term.clone() })
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    /// Remembers a Telegram message posted about an analysis.
+    pub fn add_telegram_message(&self, id: &str, message: &TelegramMessageRef) -> Result<()> {
+        self.conn.lock().execute(
+            "UPDATE analyses SET telegram_messages = json_insert(telegram_messages, '$[#]', json(?2)) WHERE id = ?1",
+            params![id, serde_json::to_string(message)?],
+        )?;
+        Ok(())
+    }
+
+    /// Returns false if the analysis doesn't exist.
+    pub fn set_note(&self, id: &str, note: Option<&str>) -> Result<bool> {
+        let updated = self
+            .conn
+            .lock()
+            .execute("UPDATE analyses SET note = ?2 WHERE id = ?1", params![id, note])?;
+        Ok(updated > 0)
+    }
+
+    /// Returns false if the analysis doesn't exist.
+    pub fn set_starred(&self, id: &str, starred: bool) -> Result<bool> {
+        let updated = self
+            .conn
+            .lock()
+            .execute("UPDATE analyses SET starred = ?2 WHERE id = ?1", params![id, starred])?;
+        Ok(updated > 0)
+    }
+
+    /// Moves an analysis to a project, or out of any. Returns false if the analysis doesn't exist.
+    pub fn set_project(&self, id: &str, project_id: Option<&str>) -> Result<bool> {
+        let updated = self.conn.lock().execute(
+            "UPDATE analyses SET project_id = ?2 WHERE id = ?1",
+            params![id, project_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Records where an analysis's screenshot file now is, or that it was deleted.
+    pub fn set_file_path(&self, id: &str, path: Option<&str>) -> Result<()> {
+        self.conn.lock().execute(
+            "UPDATE analyses SET metadata = json_set(metadata, '$.file_path', ?2) WHERE id = ?1",
+            params![id, path],
+        )?;
+        Ok(())
+    }
+
+    /// Analyses matching a condition built by `collections` or `history`, in `order_by` order.
+    pub fn list_where(
+        &self,
+        condition: &str,
+        values: &[String],
+        order_by: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<(String, AnalysisData)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM analyses WHERE {} ORDER BY {} LIMIT ? OFFSET ?",
+            ANALYSIS_COLUMNS, condition, order_by
+        ))?;
+        let mut bound: Vec<rusqlite::types::Value> = values.iter().cloned().map(Into::into).collect();
+        bound.push((limit as i64).into());
+        bound.push((offset as i64).into());
+        let rows = stmt.query_map(params_from_iter(bound), Self::row_to_analysis)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    pub fn count_where(&self, condition: &str, values: &[String]) -> Result<usize> {
+        let count: i64 = self.conn.lock().query_row(
+            &format!("SELECT COUNT(*) FROM analyses WHERE {}", condition),
+            params_from_iter(values),
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Whether one analysis matches a condition built by `collections`.
+    pub fn matches_where(&self, id: &str, condition: &str, values: &[String]) -> Result<bool> {
+        let matched: bool = self.conn.lock().query_row(
+            &format!("SELECT EXISTS (SELECT 1 FROM analyses WHERE id = ? AND ({}))", condition),
+            params_from_iter(std::iter::once(id).chain(values.iter().map(String::as_str))),
+            |row| row.get(0),
+        )?;
+        Ok(matched)
+    }
+
+    pub fn list_collections(&self) -> Result<Vec<Collection>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, match_mode, rules, created_at FROM collections ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map([], Self::row_to_collection)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_collection(&self, id: &str) -> Result<Option<Collection>> {
+        Ok(self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT id, name, match_mode, rules, created_at FROM collections WHERE id = ?1",
+                params![id],
+                Self::row_to_collection,
+            )
+            .optional()?)
+    }
+
+    pub fn save_collection(&self, collection: &Collection) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO collections (id, name, match_mode, rules, created_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name, match_mode = excluded.match_mode, rules = excluded.rules",
+            params![
+                collection.id,
+                collection.name,
+                serde_json::to_string(&collection.match_mode)?,
+                serde_json::to_string(&collection.rules)?,
+                collection.created_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_collection(&self, id: &str) -> Result<bool> {
+        let deleted = self
+            .conn
+            .lock()
+            .execute("DELETE FROM collections WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    fn row_to_collection(row: &Row<'_>) -> rusqlite::Result<Collection> {
+        let match_mode: String = row.get(2)?;
+        let rules: String = row.get(3)?;
+        let created_at: String = row.get(4)?;
+        Ok(Collection {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            match_mode: serde_json::from_str(&match_mode).unwrap_or_default(),
+            rules: serde_json::from_str(&rules).unwrap_or_default(),
+            created_at: DateTime::parse_from_rfc3339(&created_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            count: 0,
+        })
+    }
+
+    pub fn list_projects(&self) -> Result<Vec<Project>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, color, prompt_template, telegram_chat_id, rules, active, created_at
+             FROM projects ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map([], Self::row_to_project)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_project(&self, id: &str) -> Result<Option<Project>> {
+        Ok(self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT id, name, color, prompt_template, telegram_chat_id, rules, active, created_at
+                 FROM projects WHERE id = ?1",
+                params![id],
+                Self::row_to_project,
+            )
+            .optional()?)
+    }
+
+    /// Creates or edits a project; whether it's active is changed with `set_active_project`.
+    pub fn save_project(&self, project: &Project) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO projects (id, name, color, prompt_template, telegram_chat_id, rules, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name, color = excluded.color, prompt_template = excluded.prompt_template,
+                 telegram_chat_id = excluded.telegram_chat_id, rules = excluded.rules",
+            params![
+                project.id,
+                project.name,
+                project.color,
+                project.prompt_template,
+                project.telegram_chat_id,
+                serde_json::to_string(&project.rules)?,
+                project.created_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a project; its analyses stay, in no project. Returns false if it didn't exist.
+    pub fn delete_project(&self, id: &str) -> Result<bool> {
+        let deleted = self
+            .conn
+            .lock()
+            .execute("DELETE FROM projects WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    /// Makes one project the active one, or none. Returns false if the project doesn't exist.
+    pub fn set_active_project(&self, id: Option<&str>) -> Result<bool> {
+        let mut conn = self.conn.lock();
+        let tx = conn.transaction()?;
+        if let Some(id) = id {
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM projects WHERE id = ?1)",
+                params![id],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Ok(false);
+            }
+        }
+        tx.execute("UPDATE projects SET active = (id IS ?1)", params![id])?;
+        tx.commit()?;
+        Ok(true)
+    }
+
+    fn row_to_project(row: &Row<'_>) -> rusqlite::Result<Project> {
+        let rules: String = row.get(5)?;
+        let created_at: String = row.get(7)?;
+        Ok(Project {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            color: row.get(2)?,
+            prompt_template: row.get(3)?,
+            telegram_chat_id: row.get(4)?,
+            rules: serde_json::from_str(&rules).unwrap_or_default(),
+            active: row.get(6)?,
+            created_at: DateTime::parse_from_rfc3339(&created_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            count: 0,
+        })
+    }
+
+    pub fn list_rules(&self) -> Result<Vec<Rule>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, enabled, match_mode, conditions, actions, created_at
+             FROM rules ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map([], Self::row_to_rule)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_rule(&self, id: &str) -> Result<Option<Rule>> {
+        Ok(self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT id, name, enabled, match_mode, conditions, actions, created_at
+                 FROM rules WHERE id = ?1",
+                params![id],
+                Self::row_to_rule,
+            )
+            .optional()?)
+    }
+
+    pub fn save_rule(&self, rule: &Rule) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO rules (id, name, enabled, match_mode, conditions, actions, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name, enabled = excluded.enabled, match_mode = excluded.match_mode,
+                 conditions = excluded.conditions, actions = excluded.actions",
+            params![
+                rule.id,
+                rule.name,
+                rule.enabled,
+                serde_json::to_string(&rule.match_mode)?,
+                serde_json::to_string(&rule.conditions)?,
+                serde_json::to_string(&rule.actions)?,
+                rule.created_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a rule, keeping its runs in the log. Returns false if it didn't exist.
+    pub fn delete_rule(&self, id: &str) -> Result<bool> {
+        let deleted = self
+            .conn
+            .lock()
+            .execute("DELETE FROM rules WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    fn row_to_rule(row: &Row<'_>) -> rusqlite::Result<Rule> {
+        let match_mode: String = row.get(3)?;
+        let conditions: String = row.get(4)?;
+        let actions: String = row.get(5)?;
+        let created_at: String = row.get(6)?;
+        Ok(Rule {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            enabled: row.get(2)?,
+            match_mode: serde_json::from_str(&match_mode).unwrap_or_default(),
+            conditions: serde_json::from_str(&conditions).unwrap_or_default(),
+            actions: serde_json::from_str(&actions).unwrap_or_default(),
+            created_at: DateTime::parse_from_rfc3339(&created_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+        })
+    }
+
+    /// Logs a rule's run, dropping all but the latest `keep` runs. Returns the run's id.
+    pub fn record_rule_run(&self, run: &RuleRun, keep: usize) -> Result<i64> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO rule_runs (rule_id, rule_name, analysis_id, ran_at, outcomes)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                run.rule_id,
+                run.rule_name,
+                run.analysis_id,
+                run.ran_at.to_rfc3339(),
+                serde_json::to_string(&run.outcomes)?,
+            ],
+        )?;
+        let id = conn.last_insert_rowid();
+        conn.execute("DELETE FROM rule_runs WHERE id <= ?1", params![id - keep as i64])?;
+        Ok(id)
+    }
+
+    /// Rule runs, most recent first, optionally only one rule's.
+    pub fn rule_runs(&self, rule_id: Option<&str>, limit: usize) -> Result<Vec<RuleRun>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, rule_id, rule_name, analysis_id, ran_at, outcomes
+             FROM rule_runs WHERE ?1 IS NULL OR rule_id = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![rule_id, limit as i64], |row| {
+            let ran_at: String = row.get(4)?;
+            let outcomes: String = row.get(5)?;
+            Ok(RuleRun {
+                id: row.get(0)?,
+                rule_id: row.get(1)?,
+                rule_name: row.get(2)?,
+                analysis_id: row.get(3)?,
+                ran_at: DateTime::parse_from_rfc3339(&ran_at)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+                outcomes: serde_json::from_str(&outcomes).unwrap_or_default(),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// An analysis's annotations, oldest first.
+    pub fn annotations(&self, analysis_id: &str) -> Result<Vec<Annotation>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, analysis_id, shape, color, created_at, updated_at
+             FROM annotations WHERE analysis_id = ?1 ORDER BY created_at",
+        )?;
+        let rows = stmt.query_map(params![analysis_id], Self::row_to_annotation)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn get_annotation(&self, id: &str) -> Result<Option<Annotation>> {
+        Ok(self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT id, analysis_id, shape, color, created_at, updated_at FROM annotations WHERE id = ?1",
+                params![id],
+                Self::row_to_annotation,
+            )
+            .optional()?)
+    }
+
+    pub fn save_annotation(&self, annotation: &Annotation) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO annotations (id, analysis_id, shape, color, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (id) DO UPDATE SET
+                 shape = excluded.shape, color = excluded.color, updated_at = excluded.updated_at",
+            params![
+                annotation.id,
+                annotation.analysis_id,
+                serde_json::to_string(&annotation.shape)?,
+                annotation.color,
+                annotation.created_at.to_rfc3339(),
+                annotation.updated_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn delete_annotation(&self, id: &str) -> Result<bool> {
+        let deleted = self
+            .conn
+            .lock()
+            .execute("DELETE FROM annotations WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    fn row_to_annotation(row: &Row<'_>) -> rusqlite::Result<Annotation> {
+        let shape: String = row.get(2)?;
+        let created_at: String = row.get(4)?;
+        let updated_at: String = row.get(5)?;
+        let parse_time = |time: &str| {
+            DateTime::parse_from_rfc3339(time)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now())
+        };
+        Ok(Annotation {
+            id: row.get(0)?,
+            analysis_id: row.get(1)?,
+            shape: serde_json::from_str(&shape)
+                .map_err(|e| rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e)))?,
+            color: row.get(3)?,
+            created_at: parse_time(&created_at),
+            updated_at: parse_time(&updated_at),
+        })
+    }
+
+    pub fn save_session(&self, session: &Session) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO sessions (id, started_at, ended_at, summary, analysis_ids) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                session.id,
+                session.started_at.to_rfc3339(),
+                session.ended_at.to_rfc3339(),
+                session.summary,
+                serde_json::to_string(&session.analysis_ids)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent sessions, newest first.
+    pub fn list_sessions(&self, limit: usize) -> Result<Vec<Session>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, started_at, ended_at, summary, analysis_ids FROM sessions ORDER BY started_at DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            let parse_time = |value: String| {
+                DateTime::parse_from_rfc3339(&value)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now())
+            };
+            let analysis_ids: String = row.get(4)?;
+            Ok(Session {
+                id: row.get(0)?,
+                started_at: parse_time(row.get(1)?),
+                ended_at: parse_time(row.get(2)?),
+                summary: row.get(3)?,
+                analysis_ids: serde_json::from_str(&analysis_ids).unwrap_or_default(),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Saves a deep research report, replacing any earlier one for the analysis.
+    pub fn save_research_report(&self, report: &ResearchReport) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO research_reports (analysis_id, created_at, report) VALUES (?1, ?2, ?3)",
+            params![report.analysis_id, report.created_at.to_rfc3339(), serde_json::to_string(report)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn research_report(&self, analysis_id: &str) -> Result<Option<ResearchReport>> {
+        let report: Option<String> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT report FROM research_reports WHERE analysis_id = ?1",
+                params![analysis_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        report.map(|report| Ok(serde_json::from_str(&report)?)).transpose()
+    }
+
+    /// Saves the receipt read off an analysis, replacing any earlier one.
+    pub fn save_receipt(&self, receipt: &ReceiptData) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO receipts (analysis_id, extracted_at, data) VALUES (?1, ?2, ?3)",
+            params![
+                receipt.analysis_id,
+                receipt.extracted_at.unwrap_or_else(Utc::now).to_rfc3339(),
+                serde_json::to_string(receipt)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn receipt(&self, analysis_id: &str) -> Result<Option<ReceiptData>> {
+        let data: Option<String> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT data FROM receipts WHERE analysis_id = ?1",
+                params![analysis_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        data.map(|data| Ok(serde_json::from_str(&data)?)).transpose()
+    }
+
+    /// Every extracted receipt, oldest first.
+    pub fn receipts(&self) -> Result<Vec<ReceiptData>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare("SELECT data FROM receipts ORDER BY extracted_at")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.map(|data| Ok(serde_json::from_str(&data?)?)).collect()
+    }
+
+    /// Saves the code read off an analysis, replacing any earlier extraction.
+    pub fn save_code_snippet(&self, snippet: &CodeSnippet) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO code_snippets (analysis_id, language, code, file_name, extracted_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                snippet.analysis_id,
+                snippet.language,
+                snippet.code,
+                snippet.file_name,
+                snippet.extracted_at.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn code_snippet(&self, analysis_id: &str) -> Result<Option<CodeSnippet>> {
+        Ok(self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT analysis_id, language, code, file_name, extracted_at FROM code_snippets WHERE analysis_id = ?1",
+                params![analysis_id],
+                |row| {
+                    let extracted_at: String = row.get(4)?;
+                    Ok(CodeSnippet {
+                        analysis_id: row.get(0)?,
+                        language: row.get(1)?,
+                        code: row.get(2)?,
+                        file_name: row.get(3)?,
+                        extracted_at: DateTime::parse_from_rfc3339(&extracted_at)
+                            .map(|t| t.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now()),
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Saves the tables read off an analysis, replacing any earlier extraction.
+    pub fn save_tables(&self, tables: &ExtractedTables) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO extracted_tables (analysis_id, extracted_at, data) VALUES (?1, ?2, ?3)",
+            params![
+                tables.analysis_id,
+                tables.extracted_at.to_rfc3339(),
+                serde_json::to_string(tables)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    pub fn tables(&self, analysis_id: &str) -> Result<Option<ExtractedTables>> {
+        let data: Option<String> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT data FROM extracted_tables WHERE analysis_id = ?1",
+                params![analysis_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        data.map(|data| Ok(serde_json::from_str(&data)?)).transpose()
+    }
+
+    /// Saves a translation, replacing any earlier one into the same language.
+    pub fn save_translation(&self, translation: &Translation) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT OR REPLACE INTO translations (analysis_id, target_language, translated_at, data)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                translation.analysis_id,
+                translation.target_language,
+                translation.translated_at.to_rfc3339(),
+                serde_json::to_string(translation)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// An analysis's translation into `target_language`, ignoring case.
+    pub fn translation(&self, analysis_id: &str, target_language: &str) -> Result<Option<Translation>> {
+        let data: Option<String> = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT data FROM translations WHERE analysis_id = ?1 AND target_language = ?2",
+                params![analysis_id, target_language],
+                |row| row.get(0),
+            )
+            .optional()?;
+        data.map(|data| Ok(serde_json::from_str(&data)?)).transpose()
+    }
+
+    /// Every tag with the number of analyses carrying it, most used first.
+    pub fn tag_counts(&self) -> Result<Vec<(String, usize)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT t.value, COUNT(*) FROM analyses, json_each(analyses.content_analysis, '$.tags') t
+             GROUP BY t.value ORDER BY COUNT(*) DESC, t.value",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Analyses with a tag, newest first.
+    pub fn list_by_tag(&self, tag: &str, offset: usize, limit: usize) -> Result<Vec<(String, AnalysisData)>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM analyses
+             WHERE EXISTS (SELECT 1 FROM json_each(content_analysis, '$.tags') WHERE value = ?1)
+             ORDER BY timestamp DESC LIMIT ?2 OFFSET ?3",
+            ANALYSIS_COLUMNS
+        ))?;
+        let rows = stmt.query_map(params![tag, limit as i64, offset as i64], Self::row_to_analysis)?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    pub fn count_by_tag(&self, tag: &str) -> Result<usize> {
+        let count: i64 = self.conn.lock().query_row(
+            "SELECT COUNT(*) FROM analyses
+             WHERE EXISTS (SELECT 1 FROM json_each(content_analysis, '$.tags') WHERE value = ?1)",
+            params![tag],
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Adds a tag unless the analysis already has it. Returns false if the analysis doesn't exist.
+    pub fn add_tag(&self, id: &str, tag: &str) -> Result<bool> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "UPDATE analyses SET content_analysis = json_set(content_analysis, '$.tags',
+                json_insert(coalesce(json_extract(content_analysis, '$.tags'), json('[]')), '$[#]', ?2))
+             WHERE id = ?1
+               AND NOT EXISTS (SELECT 1 FROM json_each(content_analysis, '$.tags') WHERE value = ?2)",
+            params![id, tag],
+        )?;
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM analyses WHERE id = ?1)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        Ok(exists)
+    }
+
+    /// Replaces an analysis's links, e.g. once their titles have been fetched.
+    pub fn set_links(&self, id: &str, links: &[Link]) -> Result<()> {
+        self.conn.lock().execute(
+            "UPDATE analyses SET content_analysis = json_set(content_analysis, '$.links', json(?2)) WHERE id = ?1",
+            params![id, serde_json::to_string(links)?],
+        )?;
+        Ok(())
+    }
+
+    /// Removes a tag. Returns false if the analysis doesn't exist.
+    pub fn remove_tag(&self, id: &str, tag: &str) -> Result<bool> {
+        let updated = self.conn.lock().execute(
+            "UPDATE analyses SET content_analysis = json_set(content_analysis, '$.tags', json(
+                (SELECT json_group_array(value) FROM json_each(content_analysis, '$.tags') WHERE value != ?2)))
+             WHERE id = ?1",
+            params![id, tag],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Finds the analysis a Telegram message was posted about.
+    pub fn analysis_for_telegram_message(&self, chat_id: i64, message_id: i32) -> Result<Option<String>> {
+        let id = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT analyses.id FROM analyses, json_each(analyses.telegram_messages) AS message
+                 WHERE json_extract(message.value, '$.chat_id') = ?1
+                   AND json_extract(message.value, '$.message_id') = ?2
+                 LIMIT 1",
+                params![chat_id, message_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(id)
+    }
+
+    pub fn count(&self) -> Result<usize> {
+        let count: i64 = self
+            .conn
+            .lock()
+            .query_row("SELECT COUNT(*) FROM analyses", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn append_turn(&self, analysis_id: &str, turn: &ChatTurn) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO conversation_turns (analysis_id, role, content, timestamp) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                analysis_id,
+                turn.role.as_str(),
+                turn.content,
+                turn.timestamp.to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the follow-up conversation for an analysis, oldest turn first.
+    pub fn conversation(&self, analysis_id: &str) -> Result<Vec<ChatTurn>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT role, content, timestamp FROM conversation_turns WHERE analysis_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(params![analysis_id], |row| {
+            let role: String = row.get(0)?;
+            let timestamp: String = row.get(2)?;
+            Ok(ChatTurn {
+                role: if role == "assistant" {
+                    ChatRole::Assistant
+                } else {
+                    ChatRole::User
+                },
+                content: row.get(1)?,
+                timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                    .map(|t| t.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<Vec<_>>>()?)
+    }
+
+    /// Saves a screenshot that could not be analyzed, returning its outbox id.
+    pub fn outbox_push(&self, image_base64: &str, metadata: &Option<ScreenshotMetadata>) -> Result<i64> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO outbox (image_base64, metadata, queued_at) VALUES (?1, ?2, ?3)",
+            params![image_base64, serde_json::to_string(metadata)?, Utc::now().to_rfc3339()],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// The oldest screenshot still waiting in the outbox.
+    pub fn outbox_next(&self) -> Result<Option<OutboxItem>> {
+        let conn = self.conn.lock();
+        let item = conn
+            .query_row(
+                "SELECT id, image_base64, metadata, queued_at, attempts FROM outbox ORDER BY id LIMIT 1",
+                [],
+                |row| {
+                    let metadata: String = row.get(2)?;
+                    let queued_at: String = row.get(3)?;
+                    Ok(OutboxItem {
+                        id: row.get(0)?,
+                        image_base64: row.get(1)?,
+                        metadata: serde_json::from_str(&metadata).unwrap_or_default(),
+                        queued_at: DateTime::parse_from_rfc3339(&queued_at)
+                            .map(|t| t.with_timezone(&Utc))
+                            .unwrap_or_else(|_| Utc::now()),
+                        attempts: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(item)
+    }
+
+    pub fn outbox_record_failure(&self, id: i64, error: &str) -> Result<()> {
+        self.conn.lock().execute(
+            "UPDATE outbox SET attempts = attempts + 1, last_error = ?2 WHERE id = ?1",
+            params![id, error],
+        )?;
+        Ok(())
+    }
+
+    pub fn outbox_remove(&self, id: i64) -> Result<()> {
+        self.conn
+            .lock()
+            .execute("DELETE FROM outbox WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    pub fn outbox_len(&self) -> Result<usize> {
+        let count: i64 = self
+            .conn
+            .lock()
+            .query_row("SELECT COUNT(*) FROM outbox", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Saves a screenshot whose analysis failed, or counts another attempt at one already saved.
+    pub fn save_failure(
+        &self,
+        id: &str,
+        source: &str,
+        error: &str,
+        image_base64: &str,
+        metadata: &Option<ScreenshotMetadata>,
+    ) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO failed_analyses (id, failed_at, source, error, image_base64, metadata)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT (id) DO UPDATE SET
+                failed_at = excluded.failed_at, error = excluded.error, attempts = attempts + 1",
+            params![
+                id,
+                Utc::now().to_rfc3339(),
+                source,
+                error,
+                image_base64,
+                serde_json::to_string(metadata)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Failed analyses, most recent failure first.
+    pub fn failures(&self) -> Result<Vec<FailureRecord>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, failed_at, source, error, image_base64, metadata, attempts
+             FROM failed_analyses ORDER BY failed_at DESC",
+        )?;
+        let rows = stmt.query_map([], Self::row_to_failure)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn failure(&self, id: &str) -> Result<Option<FailureRecord>> {
+        let failure = self
+            .conn
+            .lock()
+            .query_row(
+                "SELECT id, failed_at, source, error, image_base64, metadata, attempts
+                 FROM failed_analyses WHERE id = ?1",
+                params![id],
+                Self::row_to_failure,
+            )
+            .optional()?;
+        Ok(failure)
+    }
+
+    pub fn delete_failure(&self, id: &str) -> Result<bool> {
+        let deleted = self
+            .conn
+            .lock()
+            .execute("DELETE FROM failed_analyses WHERE id = ?1", params![id])?;
+        Ok(deleted > 0)
+    }
+
+    fn row_to_failure(row: &Row<'_>) -> rusqlite::Result<FailureRecord> {
+        let failed_at: String = row.get(1)?;
+        let metadata: String = row.get(5)?;
+        Ok(FailureRecord {
+            id: row.get(0)?,
+            failed_at: DateTime::parse_from_rfc3339(&failed_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            source: row.get(2)?,
+            error: row.get(3)?,
+            image_base64: row.get(4)?,
+            metadata: serde_json::from_str(&metadata).unwrap_or_default(),
+            attempts: row.get(6)?,
+        })
+    }
+
+    /// Records a screenshot skipped by an exclusion rule, keeping only the newest `keep` records.
+    pub fn record_skipped(&self, skipped: &SkippedScreenshot, keep: usize) -> Result<i64> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO skipped_screenshots (skipped_at, source, rule, app, window_title, filename)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                skipped.skipped_at.to_rfc3339(),
+                skipped.source,
+                serde_json::to_string(&skipped.rule)?,
+                skipped.app,
+                skipped.window_title,
+                skipped.filename,
+            ],
+        )?;
+        let id = conn.last_insert_rowid();
+        conn.execute(
+            "DELETE FROM skipped_screenshots WHERE id <= ?1",
+            params![id - keep as i64],
+        )?;
+        Ok(id)
+    }
+
+    /// Skipped screenshots, most recent first.
+    pub fn skipped(&self, limit: usize) -> Result<Vec<SkippedScreenshot>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, skipped_at, source, rule, app, window_title, filename
+             FROM skipped_screenshots ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], Self::row_to_skipped)?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn row_to_skipped(row: &Row<'_>) -> rusqlite::Result<SkippedScreenshot> {
+        let skipped_at: String = row.get(1)?;
+        let rule: String = row.get(3)?;
+        Ok(SkippedScreenshot {
+            id: row.get(0)?,
+            skipped_at: DateTime::parse_from_rfc3339(&skipped_at)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            source: row.get(2)?,
+            rule: serde_json::from_str(&rule).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+            })?,
+            app: row.get(4)?,
+            window_title: row.get(5)?,
+            filename: row.get(6)?,
+        })
+    }
+
+    pub fn record_usage(&self, analysis_id: Option<&str>, record: &UsageRecord, cost_usd: f64) -> Result<()> {
+        self.conn.lock().execute(
+            "INSERT INTO llm_usage (analysis_id, provider, model, input_tokens, output_tokens, cost_usd, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                analysis_id,
+                record.provider,
+                record.model,
+                record.usage.input_tokens as i64,
+                record.usage.output_tokens as i64,
+                cost_usd,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Sums recorded usage, optionally only since `since`.
+    pub fn usage_totals(&self, since: Option<DateTime<Utc>>) -> Result<UsageTotals> {
+        // RFC 3339 timestamps in UTC sort lexically
+        let since = since.map(|t| t.to_rfc3339()).unwrap_or_default();
+        let totals = self.conn.lock().query_row(
+            "SELECT COUNT(*), COALESCE(SUM(input_tokens), 0), COALESCE(SUM(output_tokens), 0), COALESCE(SUM(cost_usd), 0.0)
+             FROM llm_usage WHERE timestamp >= ?1",
+            params![since],
+            |row| {
+                Ok(UsageTotals {
+                    requests: row.get::<_, i64>(0)? as u64,
+                    input_tokens: row.get::<_, i64>(1)? as u64,
+                    output_tokens: row.get::<_, i64>(2)? as u64,
+                    cost_usd: row.get(3)?,
+                })
+            },
+        )?;
+        Ok(totals)
+    }
+
+    /// Appends an audit entry, dropping entries older than `prune_before`.
+    pub fn record_audit(&self, entry: &AuditEntry, prune_before: Option<DateTime<Utc>>) -> Result<()> {
+        let conn = self.conn.lock();
+        conn.execute(
+            "INSERT INTO audit_log (timestamp, client_ip, device_name, method, path, status, request_bytes,
+                                    latency_ms, input_tokens, output_tokens)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                entry.timestamp.to_rfc3339(),
+                entry.client_ip,
+                entry.device_name,
+                entry.method,
+                entry.path,
+                entry.status,
+                entry.request_bytes.map(|bytes| bytes as i64),
+                entry.latency_ms as i64,
+                entry.input_tokens as i64,
+                entry.output_tokens as i64,
+            ],
+        )?;
+        if let Some(before) = prune_before {
+            conn.execute(
+                "DELETE FROM audit_log WHERE timestamp < ?1",
+                params![before.to_rfc3339()],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn audit_log(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT timestamp, client_ip, device_name, method, path, status, request_bytes,
+                    latency_ms, input_tokens, output_tokens
+             FROM audit_log
+             WHERE (?1 IS NULL OR timestamp >= ?1)
+               AND (?2 IS NULL OR timestamp <= ?2)
+               AND (?3 IS NULL OR client_ip = ?3)
+               AND (?4 IS NULL OR device_name = ?4)
+               AND (?5 IS NULL OR substr(path, 1, length(?5)) = ?5)
+               AND (?6 = 0 OR status >= 400)
+             ORDER BY timestamp DESC, id DESC
+             LIMIT ?7 OFFSET ?8",
+        )?;
+        let rows = stmt.query_map(
+            params![
+                query.since.map(|t| t.to_rfc3339()),
+                query.until.map(|t| t.to_rfc3339()),
+                query.client_ip,
+                query.device_name,
+                query.path,
+                query.errors_only,
+                query.limit() as i64,
+                query.offset.unwrap_or(0) as i64,
+            ],
+            |row| {
+                let timestamp: String = row.get(0)?;
+                Ok(AuditEntry {
+                    timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                        .map(|t| t.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                    client_ip: row.get(1)?,
+                    device_name: row.get(2)?,
+                    method: row.get(3)?,
+                    path: row.get(4)?,
+                    status: row.get(5)?,
+                    request_bytes: row.get::<_, Option<i64>>(6)?.map(|bytes| bytes as u64),
+                    latency_ms: row.get::<_, i64>(7)? as u64,
+                    input_tokens: row.get::<_, i64>(8)? as u64,
+                    output_tokens: row.get::<_, i64>(9)? as u64,
+                })
+            },
+        )?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Counts a request from `id`, adding the device if it's new. Returns whether the device is enabled.
+    pub fn touch_device(&self, id: &str, name: Option<&str>, ip: Option<&str>) -> Result<bool> {
+        let conn = self.conn.lock();
+        let enabled: Option<bool> = conn
+            .query_row("SELECT enabled FROM devices WHERE id = ?1", params![id], |row| row.get(0))
+            .optional()?;
+        if enabled == Some(false) {
+            return Ok(false);
+        }
+
+        let now = Utc::now().to_rfc3339();
+        conn.execute(
+            "INSERT INTO devices (id, name, first_seen, last_seen, last_ip, request_count)
+             VALUES (?1, ?2, ?3, ?3, ?4, 1)
+             ON CONFLICT (id) DO UPDATE SET
+                 last_seen = excluded.last_seen,
+                 last_ip = COALESCE(excluded.last_ip, last_ip),
+                 request_count = request_count + 1",
+            params![id, name.unwrap_or(id), now, ip],
+        )?;
+        Ok(true)
+    }
+
+    /// Adds a device that hasn't made any requests yet, keeping it as is if it already exists.
+    pub fn register_device(&self, id: &str, name: &str) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        self.conn.lock().execute(
+            "INSERT OR IGNORE INTO devices (id, name, first_seen, last_seen) VALUES (?1, ?2, ?3, ?3)",
+            params![id, name, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_devices(&self) -> Result<Vec<Device>> {
+        let conn = self.conn.lock();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, first_seen, last_seen, last_ip, request_count, enabled
+             FROM devices ORDER BY last_seen DESC",
+        )?;
+        let parse_time = |value: String| {
+            DateTime::parse_from_rfc3339(&value)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now())
+        };
+        let rows = stmt.query_map([], |row| {
+            Ok(Device {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                first_seen: parse_time(row.get(2)?),
+                last_seen: parse_time(row.get(3)?),
+                last_ip: row.get(4)?,
+                request_count: row.get::<_, i64>(5)? as u64,
+                enabled: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    pub fn rename_device(&self, id: &str, name: &str) -> Result<bool> {
+        let updated = self
+            .conn
+            .lock()
+            .execute("UPDATE devices SET name = ?2 WHERE id = ?1", params![id, name])?;
+        Ok(updated > 0)
+    }
+
+    pub fn set_device_enabled(&self, id: &str, enabled: bool) -> Result<bool> {
+        let updated = self
+            .conn
+            .lock()
+            .execute("UPDATE devices SET enabled = ?2 WHERE id = ?1", params![id, enabled])?;
+        Ok(updated > 0)
+    }
+
+    fn row_to_analysis(row: &Row<'_>) -> rusqlite::Result<(String, AnalysisData)> {
+        let id: String = row.get(0)?;
+        let timestamp: String = row.get(1)?;
+        let content_analysis: String = row.get(4)?;
+        let metadata: String = row.get(5)?;
+        let size_bytes: i64 = row.get(7)?;
+        let image_base64: String = row.get(8)?;
+        let telegram_messages: String = row.get(9)?;
+
+        let analysis = AnalysisData {
+            image_data: ProcessedImage {
+                base64_data: image_base64.clone(),
+                media_type: row.get(6)?,
+                size_bytes: size_bytes as usize,
+            },
+            brief_summary: row.get(3)?,
+            content_analysis: serde_json::from_str(&content_analysis).unwrap_or_default(),
+            metadata: serde_json::from_str(&metadata).unwrap_or_default(),
+            timestamp: DateTime::parse_from_rfc3339(&timestamp)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            source: row.get(2)?,
+            image_base64,
+            telegram_messages: serde_json::from_str(&telegram_messages).unwrap_or_default(),
+            revision_of: row.get(10)?,
+            note: row.get(11)?,
+            starred: row.get(12)?,
+            project_id: row.get(13)?,
+        };
+
+        Ok((id, analysis))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContentAnalysis, ProcessedImage, ScreenshotMetadata};
+
+    fn analysis() -> AnalysisData {
+        let image = general_purpose::STANDARD.encode(b"\x89PNG test image");
+        AnalysisData {
+            image_data: ProcessedImage {
+                base64_data: image.clone(),
+                media_type: "image/png".to_string(),
+                size_bytes: 15,
+            },
+            brief_summary: "A grocery receipt".to_string(),
+            content_analysis: ContentAnalysis {
+                content_type: "receipt".to_string(),
+                extracted_text: Some("Oat milk 2.49 Sourdough 4.10 Total 6.59".to_string()),
+                ..Default::default()
+            },
+            metadata: ScreenshotMetadata {
+                app: Some("Preview".to_string()),
+                window_title: Some("Expenses.pdf".to_string()),
+                ..Default::default()
+            },
+            timestamp: Utc::now(),
+            source: "desktop_auto".to_string(),
+            image_base64: image,
+            telegram_messages: Vec::new(),
+            revision_of: None,
+            note: None,
+            starred: false,
+            project_id: None,
+        }
+    }
+
+    #[test]
+    fn new_database_indexes_details_and_notes_for_search() {
+        let dir = std::env::temp_dir().join(format!("screenshot-ai-test-{}", uuid::Uuid::new_v4()));
+        let store = AnalysisStore::open(&dir.join("analyses.db"), None).unwrap();
+
+        store.insert("a1", &analysis()).unwrap();
+        let found = |query: &str| store.search(query, 10, None).unwrap().len();
+        assert_eq!(found("grocery"), 1);
+        assert_eq!(found("preview"), 1);
+        assert_eq!(found("expenses"), 1);
+        assert_eq!(found("sourdough"), 1);
+        assert_eq!(found("reimburse"), 0);
+
+        store.set_note("a1", Some("reimburse by Friday")).unwrap();
+        assert_eq!(found("reimburse"), 1);
+
+        drop(store);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use anyhow::{anyhow, Context, Result};
+use axum::{
+    extract::{Path as AxumPath, Query, State},
+    http::{header, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::{debug, error, info, warn};
+
+use crate::{archive::csv_field, usage, AnalysisData, ExportFormat, OutputSchema, ScreenshotProcessor};
+
+const TABLE_MAX_TOKENS: u32 = 4_000;
+
+/// One table read off a screenshot. Cells are kept as the text shown, e.g. `$12/mo`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataTable {
+    /// Caption or heading above the table, if there is one.
+    pub title: Option<String>,
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// The tables found in a screenshot, e.g. a pricing page, spreadsheet or dashboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractedTables {
+    pub analysis_id: String,
+    pub tables: Vec<DataTable>,
+    pub extracted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct TablesResponse {
+    tables: Vec<DataTable>,
+}
+
+/// Query for `GET /analysis/:id/table`: `?format=csv` for CSV, JSON otherwise.
+#[derive(Debug, Default, Deserialize)]
+pub struct TableQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+fn tables_schema() -> OutputSchema {
+    OutputSchema {
+        name: "record_tables",
+        description: "Record every table of data in the screenshot, cell by cell.",
+        schema: serde_json::json!({
+            "type": "object",
+            "properties": {
+                "tables": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "title": { "type": ["string", "null"], "description": "Caption or heading, or null" },
+                            "headers": { "type": "array", "items": { "type": "string" } },
+                            "rows": {
+                                "type": "array",
+                                "items": { "type": "array", "items": { "type": "string" } }
+                            }
+                        },
+                        "required": ["title", "headers", "rows"],
+                        "additionalProperties": false
+                    }
+                }
+            },
+            "required": ["tables"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+const TABLE_PROMPT: &str = "Extract every table of data in this screenshot, such as a pricing table, \
+spreadsheet range or dashboard grid. Give each table's column headers and every row, with one cell per \
+column in the same order, copying the text exactly as shown. Use an empty string for empty cells.";
+
+const TEXT_FORMAT: &str = "\n\nFormat each table as a Markdown table, preceded by a line `TABLE: <title>` \
+(or `TABLE: none`).";
+
+/// Splits a Markdown table row into its cells.
+fn markdown_cells(line: &str) -> Vec<String> {
+    line.trim()
+        .trim_start_matches('|')
+        .trim_end_matches('|')
+        .split('|')
+        .map(|cell| cell.trim().to_string())
+        .collect()
+}
+
+/// Parses Markdown tables in `TEXT_FORMAT`, for providers without structured output.
+fn parse_tables_text(text: &str) -> Vec<DataTable> {
+    let mut tables: Vec<DataTable> = Vec::new();
+    let mut current: Option<DataTable> = None;
+    let mut title: Option<String> = None;
+
+    for line in text.lines().map(str::trim) {
+        if let Some(heading) = line.strip_prefix("TABLE:") {
+            tables.extend(current.take());
+            let heading = heading.trim();
+            title = (!heading.is_empty() && !heading.eq_ignore_ascii_case("none")).then(|| heading.to_string());
+        } else if line.starts_with('|') {
+            let cells = markdown_cells(line);
+            // The `|---|---|` line under the headers
+            if cells.iter().all(|cell| !cell.is_empty() && cell.chars().all(|c| matches!(c, '-' | ':'))) {
+                continue;
+            }
+            match current.as_mut() {
+                Some(table) => table.rows.push(cells),
+                None => {
+                    current = Some(DataTable {
+                        title: title.take(),
+                        headers: cells,
+                        rows: Vec::new(),
+                    })
+                }
+            }
+        } else if !line.is_empty() {
+            tables.extend(current.take());
+        }
+    }
+    tables.extend(current);
+    tables
+}
+
+/// Tables as CSV, separated by a blank line when there are several.
+pub(crate) fn tables_to_csv(tables: &[DataTable]) -> String {
+    let row = |cells: &[String]| {
+        let fields: Vec<String> = cells.iter().map(|cell| csv_field(cell)).collect();
+        format!("{}\r\n", fields.join(","))
+    };
+    tables
+        .iter()
+        .map(|table| {
+            let mut csv = String::new();
+            if !table.headers.is_empty() {
+                csv.push_str(&row(&table.headers));
+            }
+            for cells in &table.rows {
+                csv.push_str(&row(cells));
+            }
+            csv
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n")
+}
+
+fn render(tables: &ExtractedTables, format: ExportFormat) -> Result<Vec<u8>> {
+    Ok(match format {
+        ExportFormat::Json => serde_json::to_vec_pretty(tables)?,
+        ExportFormat::Csv => tables_to_csv(&tables.tables).into_bytes(),
+    })
+}
+
+impl ScreenshotProcessor {
+    async fn extract_tables(&self, analysis_id: &str, analysis: &AnalysisData) -> Result<ExtractedTables> {
+        let image = self.llm_image(&analysis.image_data).await;
+        let (structured, records) = usage::track(self.provider.complete_json(
+            TABLE_PROMPT,
+            &[&image],
+            &tables_schema(),
+            TABLE_MAX_TOKENS,
+        ))
+        .await;
+        self.save_usage(Some(analysis_id), &records);
+
+        let mut tables = match structured.and_then(|json| Ok(serde_json::from_str::<TablesResponse>(&json)?)) {
+            Ok(response) => response.tables,
+            Err(e) => {
+                debug!("Structured table extraction unavailable, parsing text instead: {}", e);
+                let prompt = format!("{}{}", TABLE_PROMPT, TEXT_FORMAT);
+                let (text, records) =
+                    usage::track(self.provider.complete(&prompt, &[&image], TABLE_MAX_TOKENS)).await;
+                self.save_usage(Some(analysis_id), &records);
+                parse_tables_text(&text?)
+            }
+        };
+
+        tables.retain(|table| !table.rows.is_empty() || !table.headers.is_empty());
+        if tables.is_empty() {
+            return Err(anyhow!("No tables found in the screenshot"));
+        }
+        Ok(ExtractedTables {
+            analysis_id: analysis_id.to_string(),
+            tables,
+            extracted_at: Utc::now(),
+        })
+    }
+
+    /// Extracts the tables from a new analysis in the background when the screenshot shows any.
+    pub(crate) fn process_tables(&self, analysis_id: &str, analysis: &AnalysisData) {
+        if !self.config.table_extraction || !analysis.content_analysis.has_table {
+            return;
+        }
+
+        let processor = self.clone();
+        let analysis_id = analysis_id.to_string();
+        let analysis = analysis.clone();
+        tokio::spawn(async move {
+            let result = processor
+                .extract_tables(&analysis_id, &analysis)
+                .await
+                .and_then(|tables| processor.store.save_tables(&tables).map(|_| tables));
+            match result {
+                Ok(tables) => info!("📊 Extracted {} tables from {}", tables.tables.len(), analysis_id),
+                Err(e) => warn!("Table extraction for {} failed: {}", analysis_id, e),
+            }
+        });
+    }
+
+    pub fn get_tables(&self, analysis_id: &str) -> Result<Option<ExtractedTables>> {
+        self.store.tables(analysis_id)
+    }
+
+    /// Writes an analysis's tables to `path` as CSV or JSON, returning how many tables were written.
+    pub fn export_tables(&self, analysis_id: &str, format: ExportFormat, path: &Path) -> Result<usize> {
+        let tables = self
+            .store
+            .tables(analysis_id)?
+            .ok_or_else(|| anyhow!("No tables were extracted from analysis {}", analysis_id))?;
+        std::fs::write(path, render(&tables, format)?).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(tables.tables.len())
+    }
+}
+
+pub async fn handle_get_table(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Query(query): Query<TableQuery>,
+) -> Result<([(header::HeaderName, String); 2], Vec<u8>), StatusCode> {
+    let tables = match processor.get_tables(&analysis_id) {
+        Ok(Some(tables)) => tables,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!("Failed to load tables for {}: {}", analysis_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let body = render(&tables, query.format).map_err(|e| {
+        error!("Failed to render tables for {}: {}", analysis_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let filename = format!("tables-{}.{}", &analysis_id[..8.min(analysis_id.len())], query.format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, query.format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        body,
+    ))
+}
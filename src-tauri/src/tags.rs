@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use axum::{
+    extract::{Json, Path as AxumPath, State},
+    http::StatusCode,
+    response::Json as ResponseJson,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{AnalysisPage, ScreenshotProcessor};
+
+const TAG_MAX_CHARS: usize = 40;
+/// Models sometimes return a long list; only the first few are useful for filtering.
+const MAX_AUTO_TAGS: usize = 8;
+
+/// How many analyses carry a tag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddTagRequest {
+    pub tag: String,
+}
+
+/// A tag as stored: lowercase words joined by hyphens, without a leading `#`.
+pub(crate) fn normalize_tag(tag: &str) -> Option<String> {
+    let tag = tag
+        .trim()
+        .trim_start_matches('#')
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase();
+    let tag: String = tag.chars().take(TAG_MAX_CHARS).collect();
+    let tag = tag.trim_matches('-');
+    (!tag.is_empty()).then(|| tag.to_string())
+}
+
+/// Normalized tags without duplicates, in their original order.
+pub(crate) fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().filter_map(|tag| normalize_tag(tag)) {
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized.truncate(MAX_AUTO_TAGS);
+    normalized
+}
+
+impl ScreenshotProcessor {
+    /// Every tag in use, most used first.
+    pub fn list_tags(&self) -> Result<Vec<TagCount>> {
+        Ok(self
+            .store
+            .tag_counts()?
+            .into_iter()
+            .map(|(tag, count)| TagCount { tag, count })
+            .collect())
+    }
+
+    /// Analyses with a tag, newest first; `page` is 1-based.
+    pub fn list_analyses_by_tag(
+        &self,
+        tag: &str,
+        page: usize,
+        page_size: usize,
+        include_images: bool,
+    ) -> Result<AnalysisPage> {
+        let tag = normalize_tag(tag).ok_or_else(|| anyhow!("Tag is empty"))?;
+        let page = page.max(1);
+        let page_size = page_size.clamp(1, 100);
+
+        let analyses = self
+            .store
+            .list_by_tag(&tag, (page - 1) * page_size, page_size)?
+            .into_iter()
+            .map(|(id, analysis)| self.analysis_record(id, analysis, include_images))
+            .collect();
+
+        Ok(AnalysisPage {
+            analyses,
+            page,
+            page_size,
+            total: self.store.count_by_tag(&tag)?,
+        })
+    }
+
+    /// Tags an analysis by hand. Returns false if the analysis doesn't exist.
+    pub fn add_tag(&self, analysis_id: &str, tag: &str) -> Result<bool> {
+        let tag = normalize_tag(tag).ok_or_else(|| anyhow!("Tag is empty"))?;
+        let found = self.store.add_tag(analysis_id, &tag)?;
+        if found {
+            info!("🏷️ Tagged analysis {} with \"{}\"", analysis_id, tag);
+        }
+        Ok(found)
+    }
+
+    /// Removes a tag, whether the model or the user added it. Returns false if the analysis doesn't exist.
+    pub fn remove_tag(&self, analysis_id: &str, tag: &str) -> Result<bool> {
+        let tag = normalize_tag(tag).ok_or_else(|| anyhow!("Tag is empty"))?;
+        self.store.remove_tag(analysis_id, &tag)
+    }
+}
+
+pub async fn handle_list_tags(
+    State(processor): State<ScreenshotProcessor>,
+) -> Result<ResponseJson<Vec<TagCount>>, StatusCode> {
+    processor.list_tags().map(ResponseJson).map_err(|e| {
+        error!("Failed to list tags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn handle_add_tag(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath(analysis_id): AxumPath<String>,
+    Json(request): Json<AddTagRequest>,
+) -> StatusCode {
+    if normalize_tag(&request.tag).is_none() {
+        return StatusCode::BAD_REQUEST;
+    }
+    match processor.add_tag(&analysis_id, &request.tag) {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to tag analysis {}: {}", analysis_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+pub async fn handle_remove_tag(
+    State(processor): State<ScreenshotProcessor>,
+    AxumPath((analysis_id, tag)): AxumPath<(String, String)>,
+) -> StatusCode {
+    if normalize_tag(&tag).is_none() {
+        return StatusCode::BAD_REQUEST;
+    }
+    match processor.remove_tag(&analysis_id, &tag) {
+        Ok(true) => StatusCode::NO_CONTENT,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("Failed to untag analysis {}: {}", analysis_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -0,0 +1,728 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use teloxide::{
+    prelude::*,
+    types::{
+        CallbackQuery, Chat, InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, Message,
+        ParseMode, Recipient,
+    },
+    utils::{command::BotCommands, html},
+};
+use tracing::{error, info, warn};
+
+use crate::{arxiv, research, webpage, AnalysisData, AppConfig, PauseMode, ScreenshotProcessor};
+
+/// Follow-up actions offered by the inline keyboard on screenshot notifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FollowUpAction {
+    ArxivResearch,
+    DeepResearch,
+    FullWebpage,
+}
+
+impl FollowUpAction {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::ArxivResearch => "🔬 Research Papers",
+            Self::DeepResearch => "🧠 Deep Research",
+            Self::FullWebpage => "🌐 Webpage Content",
+        }
+    }
+
+    fn callback_data(&self, analysis_id: &str) -> String {
+        let prefix = match self {
+            Self::ArxivResearch => "arxiv_research_",
+            Self::DeepResearch => "deep_research_",
+            Self::FullWebpage => "full_webpage_",
+        };
+        format!("{}{}", prefix, analysis_id)
+    }
+
+    /// Splits callback data such as `arxiv_research_<id>` into the action and analysis id.
+    pub fn parse(data: &str) -> Option<(Self, &str)> {
+        [
+            ("arxiv_research_", Self::ArxivResearch),
+            ("deep_research_", Self::DeepResearch),
+            ("full_webpage_", Self::FullWebpage),
+        ]
+        .into_iter()
+        .find_map(|(prefix, action)| {
+            data.strip_prefix(prefix)
+                .filter(|id| !id.is_empty())
+                .map(|id| (action, id))
+        })
+    }
+}
+
+/// A Telegram message posted about an analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelegramMessageRef {
+    pub chat_id: i64,
+    pub message_id: i32,
+}
+
+impl TelegramMessageRef {
+    pub fn of(message: &Message) -> Self {
+        Self {
+            chat_id: message.chat.id.0,
+            message_id: message.id.0,
+        }
+    }
+}
+
+/// Follow-up buttons for a screenshot notification. `done` actions are shown as completed.
+pub fn notification_keyboard(analysis_id: &str, has_webpage: bool, done: &[FollowUpAction]) -> InlineKeyboardMarkup {
+    let mut actions = vec![FollowUpAction::ArxivResearch, FollowUpAction::DeepResearch];
+    if has_webpage {
+        actions.push(FollowUpAction::FullWebpage);
+    }
+
+    InlineKeyboardMarkup::new(actions.into_iter().map(|action| {
+        let label = if done.contains(&action) {
+            format!("✅ {}", action.label())
+        } else {
+            action.label().to_string()
+        };
+        vec![InlineKeyboardButton::callback(label, action.callback_data(analysis_id))]
+    }))
+}
+
+/// A numeric chat id, or `@username` for a public channel.
+pub(crate) fn parse_recipient(chat_id: &str) -> Option<Recipient> {
+    let chat_id = chat_id.trim();
+    if chat_id.starts_with('@') {
+        Some(Recipient::ChannelUsername(chat_id.to_string()))
+    } else {
+        chat_id.parse().ok().map(|id| Recipient::Id(ChatId(id)))
+    }
+}
+
+/// A chat the bot talks to: a personal chat, a group or a channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TelegramChat {
+    /// Numeric chat id, or `@username` for a public channel.
+    pub chat_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Send screenshot notifications to this chat.
+    #[serde(default = "default_true")]
+    pub notify: bool,
+    /// Only notify for sources starting with one of these (e.g. `desktop`, `iOS`); empty means all.
+    #[serde(default)]
+    pub sources: Vec<String>,
+    /// Only notify for these content types (e.g. `article`); empty means all.
+    #[serde(default)]
+    pub content_types: Vec<String>,
+    /// Accept commands, button presses and follow-up replies from this chat.
+    #[serde(default = "default_true")]
+    pub allow_commands: bool,
+    /// Forum topic (`message_thread_id`) per content type, for groups with topics enabled.
+    /// A `default` entry catches content types without their own topic.
+    #[serde(default)]
+    pub topics: HashMap<String, i32>,
+    /// Follow each notification with the summary read aloud as a voice message, when `tts` is set up.
+    #[serde(default)]
+    pub voice_notes: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// The chats in `config`, including the single `telegram_chat_id` from older configs.
+pub(crate) fn configured_chats(config: &AppConfig) -> Vec<TelegramChat> {
+    let mut chats = config.telegram_chats.clone();
+    if let Some(legacy) = config
+        .telegram_chat_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+    {
+        if !chats.iter().any(|chat| chat.chat_id.trim() == legacy) {
+            chats.insert(
+                0,
+                TelegramChat {
+                    topics: config.telegram_topics.clone(),
+                    ..TelegramChat::new(legacy)
+                },
+            );
+        }
+    }
+    chats
+}
+
+impl TelegramChat {
+    /// A chat that gets every notification and may use the bot.
+    pub fn new(chat_id: impl Into<String>) -> Self {
+        Self {
+            chat_id: chat_id.into(),
+            name: None,
+            notify: true,
+            sources: Vec::new(),
+            content_types: Vec::new(),
+            allow_commands: true,
+            topics: HashMap::new(),
+            voice_notes: false,
+        }
+    }
+
+    pub fn recipient(&self) -> Option<Recipient> {
+        parse_recipient(&self.chat_id)
+    }
+
+    fn is(&self, chat: &Chat) -> bool {
+        let chat_id = self.chat_id.trim();
+        match chat_id.strip_prefix('@') {
+            Some(username) => chat
+                .username()
+                .is_some_and(|name| name.eq_ignore_ascii_case(username)),
+            None => chat_id.parse::<i64>().ok() == Some(chat.id.0),
+        }
+    }
+
+    /// The forum topic a screenshot showing `content_type` should be posted in.
+    pub fn topic_for(&self, content_type: &str) -> Option<i32> {
+        self.topics
+            .iter()
+            .find(|(kind, _)| kind.eq_ignore_ascii_case(content_type))
+            .or_else(|| self.topics.get_key_value("default"))
+            .map(|(_, &thread_id)| thread_id)
+    }
+
+    /// Whether this chat's routing rules accept a screenshot from `source_type` showing `content_type`.
+    pub fn wants(&self, source_type: &str, content_type: &str) -> bool {
+        let source_matches = self.sources.is_empty()
+            || self
+                .sources
+                .iter()
+                .any(|source| source_type.to_lowercase().starts_with(&source.to_lowercase()));
+        let content_matches = self.content_types.is_empty()
+            || self
+                .content_types
+                .iter()
+                .any(|kind| kind.eq_ignore_ascii_case(content_type));
+
+        self.notify && source_matches && content_matches
+    }
+}
+
+/// Commands accepted from allowed chats.
+#[derive(BotCommands, Clone, Debug)]
+#[command(rename_rule = "lowercase", description = "Screenshot AI commands:")]
+pub enum BotCommand {
+    #[command(description = "show server health")]
+    Status,
+    #[command(description = "list the latest analyses, e.g. /recent 5")]
+    Recent(String),
+    #[command(description = "search analysis history, e.g. /search invoice")]
+    Search(String),
+    #[command(description = "stop analyzing new screenshots")]
+    Pause,
+    #[command(description = "start analyzing new screenshots again")]
+    Resume,
+    #[command(description = "show this help")]
+    Help,
+}
+
+const DEFAULT_RECENT_COUNT: usize = 5;
+const MAX_RECENT_COUNT: usize = 20;
+const SEARCH_RESULT_LIMIT: usize = 5;
+
+/// Finds the analysis a notification message belongs to via its inline keyboard.
+pub fn analysis_id_from_message(message: &Message) -> Option<String> {
+    message
+        .reply_markup()?
+        .inline_keyboard
+        .iter()
+        .flatten()
+        .find_map(|button| match &button.kind {
+            InlineKeyboardButtonKind::CallbackData(data) => {
+                FollowUpAction::parse(data).map(|(_, id)| id.to_string())
+            }
+            _ => None,
+        })
+}
+
+/// Follow-up actions already marked done on `notification`, plus `action`.
+fn completed_actions(notification: &Message, analysis_id: &str, action: FollowUpAction) -> Vec<FollowUpAction> {
+    let mut done: Vec<FollowUpAction> = notification
+        .reply_markup()
+        .into_iter()
+        .flat_map(|markup| markup.inline_keyboard.iter().flatten())
+        .filter(|button| button.text.starts_with('✅'))
+        .filter_map(|button| match &button.kind {
+            InlineKeyboardButtonKind::CallbackData(data) => FollowUpAction::parse(data)
+                .filter(|(_, id)| *id == analysis_id)
+                .map(|(action, _)| action),
+            _ => None,
+        })
+        .collect();
+    if !done.contains(&action) {
+        done.push(action);
+    }
+    done
+}
+
+/// Telegram rejects photos over 10MB or with a combined width and height above 10000px.
+const MAX_PHOTO_BYTES: usize = 10 * 1024 * 1024;
+const MAX_PHOTO_DIMENSION_SUM: u32 = 10_000;
+const MAX_PHOTO_ASPECT_RATIO: f64 = 20.0;
+
+/// Returns why `image_bytes` cannot be sent with `sendPhoto`, if anything.
+pub fn photo_limit_violation(image_bytes: &[u8]) -> Option<String> {
+    if image_bytes.len() > MAX_PHOTO_BYTES {
+        return Some(format!(
+            "image is {:.1}MB, Telegram allows 10MB",
+            image_bytes.len() as f64 / 1024.0 / 1024.0
+        ));
+    }
+
+    let (width, height) = image::io::Reader::new(std::io::Cursor::new(image_bytes))
+        .with_guessed_format()
+        .ok()?
+        .into_dimensions()
+        .ok()?;
+
+    if width + height > MAX_PHOTO_DIMENSION_SUM {
+        return Some(format!("image is {}x{}px, too large for Telegram", width, height));
+    }
+
+    let ratio = width.max(height) as f64 / width.min(height).max(1) as f64;
+    if ratio > MAX_PHOTO_ASPECT_RATIO {
+        return Some(format!("image aspect ratio {:.0}:1 is too extreme for Telegram", ratio));
+    }
+
+    None
+}
+
+/// HTML-escapes `text` and truncates it to roughly `max_chars` characters.
+pub fn truncate_html(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return html::escape(text);
+    }
+
+    let truncated: String = text.chars().take(max_chars).collect();
+    format!(
+        "{}...\n\n<i>[Analysis truncated - see full analysis in app]</i>",
+        html::escape(&truncated)
+    )
+}
+
+impl ScreenshotProcessor {
+    /// Starts a long-polling dispatcher for inline keyboard callbacks, if a bot is configured.
+    pub fn spawn_telegram_dispatcher(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let bot = self.telegram_bot.clone()?;
+        let processor = self.clone();
+
+        let handler = dptree::entry()
+            .branch(Update::filter_callback_query().endpoint(
+                |bot: Bot, query: CallbackQuery, processor: ScreenshotProcessor| async move {
+                    processor.handle_callback_query(&bot, query).await
+                },
+            ))
+            .branch(Update::filter_message().filter_command::<BotCommand>().endpoint(
+                |bot: Bot, message: Message, command: BotCommand, processor: ScreenshotProcessor| async move {
+                    processor.handle_command(&bot, message, command).await
+                },
+            ))
+            .branch(Update::filter_message().endpoint(
+                |bot: Bot, message: Message, processor: ScreenshotProcessor| async move {
+                    processor.handle_reply_message(&bot, message).await
+                },
+            ));
+
+        info!("🤖 Telegram dispatcher started");
+
+        Some(tokio::spawn(async move {
+            // Lets Telegram clients suggest the commands as you type
+            if let Err(e) = bot.set_my_commands(BotCommand::bot_commands()).await {
+                warn!("Failed to register Telegram bot commands: {}", e);
+            }
+
+            Dispatcher::builder(bot, handler)
+                .dependencies(dptree::deps![processor])
+                .default_handler(|_| async {})
+                .build()
+                .dispatch()
+                .await;
+        }))
+    }
+
+    async fn handle_callback_query(&self, bot: &Bot, query: CallbackQuery) -> ResponseResult<()> {
+        if !query
+            .message
+            .as_ref()
+            .is_some_and(|message| self.is_allowed_chat(&message.chat))
+        {
+            warn!("Ignoring Telegram callback from unknown chat (user {})", query.from.id);
+            bot.answer_callback_query(query.id).await?;
+            return Ok(());
+        }
+
+        let Some((action, analysis_id)) = query.data.as_deref().and_then(FollowUpAction::parse) else {
+            warn!("Ignoring unknown Telegram callback: {:?}", query.data);
+            bot.answer_callback_query(query.id).await?;
+            return Ok(());
+        };
+
+        info!("📲 Telegram callback {:?} for analysis {}", action, analysis_id);
+
+        let analysis = self.store.get(analysis_id).unwrap_or_else(|e| {
+            error!("Failed to load analysis {}: {}", analysis_id, e);
+            None
+        });
+        let Some(analysis) = analysis else {
+            bot.answer_callback_query(query.id)
+                .text("This analysis is no longer available")
+                .await?;
+            return Ok(());
+        };
+
+        bot.answer_callback_query(query.id).text("Working on it...").await?;
+
+        let Some(notification) = query.message else {
+            error!("Telegram callback for {} has no originating message", analysis_id);
+            return Ok(());
+        };
+
+        // Reply under the notification right away, then fill the reply in once the result is ready
+        let placeholder = bot
+            .send_message(notification.chat.id, format!("⏳ {}...", action.label()))
+            .reply_to_message_id(notification.id)
+            .await?;
+        self.track_telegram_message(analysis_id, &placeholder);
+
+        let mut replies = self.run_follow_up(action, analysis_id, &analysis).await.into_iter();
+
+        bot.edit_message_text(placeholder.chat.id, placeholder.id, replies.next().unwrap_or_default())
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .await?;
+        // Long results continue as a thread of replies
+        let mut previous = placeholder;
+        for reply in replies {
+            let message = bot
+                .send_message(previous.chat.id, reply)
+                .reply_to_message_id(previous.id)
+                .parse_mode(ParseMode::Html)
+                .disable_web_page_preview(true)
+                .await?;
+            self.track_telegram_message(analysis_id, &message);
+            previous = message;
+        }
+
+        let done = completed_actions(&notification, analysis_id, action);
+        let keyboard = notification_keyboard(
+            analysis_id,
+            analysis.content_analysis.webpage_url.is_some(),
+            &done,
+        );
+        if let Err(e) = bot
+            .edit_message_reply_markup(notification.chat.id, notification.id)
+            .reply_markup(keyboard)
+            .await
+        {
+            warn!("Failed to update Telegram notification buttons: {}", e);
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn track_telegram_message(&self, analysis_id: &str, message: &Message) {
+        if let Err(e) = self
+            .store
+            .add_telegram_message(analysis_id, &TelegramMessageRef::of(message))
+        {
+            warn!("Failed to remember Telegram message for {}: {}", analysis_id, e);
+        }
+    }
+
+    /// The analysis a replied-to message belongs to: a notification, a follow-up result or an answer.
+    fn analysis_for_reply(&self, replied_to: &Message) -> Option<String> {
+        self.store
+            .analysis_for_telegram_message(replied_to.chat.id.0, replied_to.id.0)
+            .unwrap_or_else(|e| {
+                error!("Failed to look up Telegram message: {}", e);
+                None
+            })
+            // Notifications sent before messages were tracked still carry the id in their buttons
+            .or_else(|| analysis_id_from_message(replied_to))
+    }
+
+    /// Configured chats, including the single `telegram_chat_id` from older configs.
+    pub(crate) fn telegram_chats(&self) -> Vec<TelegramChat> {
+        configured_chats(&self.config)
+    }
+
+    /// Chats, and the forum topic within each, that should receive a notification for this screenshot:
+    /// its project's chat, or else its source's, if either has one; otherwise every chat whose filters
+    /// let it through.
+    pub(crate) fn notification_recipients(
+        &self,
+        source_type: &str,
+        content_type: &str,
+        project_id: Option<&str>,
+    ) -> Vec<(Recipient, Option<i32>)> {
+        if let Some(recipient) = self
+            .project_recipient(project_id)
+            .or_else(|| self.profile_recipient(source_type))
+        {
+            return vec![(recipient, None)];
+        }
+        self.telegram_chats()
+            .iter()
+            .filter(|chat| chat.wants(source_type, content_type))
+            .filter_map(|chat| Some((chat.recipient()?, chat.topic_for(content_type))))
+            .collect()
+    }
+
+    /// Sends an HTML message to every chat that receives notifications.
+    pub(crate) async fn broadcast_telegram(&self, text: &str) -> anyhow::Result<()> {
+        let Some(bot) = &self.telegram_bot else {
+            return Ok(());
+        };
+        for chat in self.telegram_chats().iter().filter(|chat| chat.notify) {
+            if let Some(recipient) = chat.recipient() {
+                bot.send_message(recipient, text)
+                    .parse_mode(ParseMode::Html)
+                    .disable_web_page_preview(true)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `chat` may use commands and buttons; traffic from other chats is ignored.
+    fn is_allowed_chat(&self, chat: &Chat) -> bool {
+        self.telegram_chats()
+            .iter()
+            .any(|allowed| allowed.allow_commands && allowed.is(chat))
+    }
+
+    async fn handle_command(&self, bot: &Bot, message: Message, command: BotCommand) -> ResponseResult<()> {
+        if !self.is_allowed_chat(&message.chat) {
+            warn!("Ignoring Telegram command from unknown chat {}", message.chat.id);
+            return Ok(());
+        }
+
+        info!("🤖 Telegram command: {:?}", command);
+
+        let reply = match command {
+            BotCommand::Status => self.status_html().await,
+            BotCommand::Recent(count) => {
+                let count = count
+                    .trim()
+                    .parse()
+                    .unwrap_or(DEFAULT_RECENT_COUNT)
+                    .clamp(1, MAX_RECENT_COUNT);
+                self.recent_html(count)
+            }
+            BotCommand::Search(query) => self.search_html(query.trim()),
+            BotCommand::Pause => {
+                let held = match self.config.pause_mode {
+                    PauseMode::Drop => "dropped",
+                    PauseMode::Queue => "queued",
+                };
+                if self.set_paused(true) {
+                    format!("⏸️ Processing paused; new screenshots will be {} until /resume", held)
+                } else {
+                    "⏸️ Processing is already paused.".to_string()
+                }
+            }
+            BotCommand::Resume => {
+                if self.set_paused(false) {
+                    "▶️ Processing resumed.".to_string()
+                } else {
+                    "▶️ Processing isn't paused.".to_string()
+                }
+            }
+            BotCommand::Help => html::escape(&BotCommand::descriptions().to_string()),
+        };
+
+        bot.send_message(message.chat.id, reply)
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn status_html(&self) -> String {
+        let status = self.get_status().await;
+        let budget = self.budget_status();
+        let last_request = status
+            .last_request
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_string());
+
+        format!(
+            "{} <b>{}</b>\n\n\
+             <b>Address:</b> {}:{}\n\
+             <b>Requests:</b> {} (last: {})\n\
+             <b>Stored analyses:</b> {}\n\
+             <b>Processing:</b> {}/{} active, {} waiting\n\
+             <b>Offline queue:</b> {}\n\
+             <b>Spend this month:</b> ${:.2}",
+            if status.paused { "⏸️" } else { "🟢" },
+            html::escape(&status.server),
+            status.local_ip,
+            status.port,
+            status.total_requests,
+            last_request,
+            status.active_analyses,
+            status.active_requests,
+            status.max_concurrent_requests,
+            status.queue_depth,
+            status.outbox_pending,
+            budget.month_spend_usd,
+        )
+    }
+
+    fn recent_html(&self, count: usize) -> String {
+        let analyses = match self.store.recent(count) {
+            Ok(analyses) => analyses,
+            Err(e) => {
+                error!("Failed to load recent analyses for Telegram: {}", e);
+                return "📋 Could not load recent analyses.".to_string();
+            }
+        };
+
+        if analyses.is_empty() {
+            return "📋 No analyses yet.".to_string();
+        }
+
+        let entries: Vec<String> = analyses
+            .iter()
+            .map(|(_, analysis)| {
+                format!(
+                    "• <i>{}</i> {}",
+                    analysis.timestamp.with_timezone(&chrono::Local).format("%b %d %H:%M"),
+                    truncate_html(&analysis.brief_summary, 200)
+                )
+            })
+            .collect();
+
+        format!("📋 <b>Latest {} analyses</b>\n\n{}", entries.len(), entries.join("\n\n"))
+    }
+
+    fn search_html(&self, query: &str) -> String {
+        if query.is_empty() {
+            return "🔎 Usage: /search keyword".to_string();
+        }
+
+        let results = match self.search_analyses(query, SEARCH_RESULT_LIMIT, None, false) {
+            Ok(results) => results,
+            Err(e) => {
+                error!("Telegram search failed: {}", e);
+                return format!("🔎 Search failed: {}", html::escape(&e.to_string()));
+            }
+        };
+
+        if results.is_empty() {
+            return format!("🔎 No analyses match <b>{}</b>.", html::escape(query));
+        }
+
+        let entries: Vec<String> = results
+            .iter()
+            .map(|result| {
+                // Snippets mark matches with <mark>, which Telegram doesn't support
+                let snippet = html::escape(&result.snippet)
+                    .replace("&lt;mark&gt;", "<b>")
+                    .replace("&lt;/mark&gt;", "</b>");
+                format!(
+                    "• <i>{}</i> {}",
+                    result.analysis.timestamp.with_timezone(&chrono::Local).format("%b %d %H:%M"),
+                    snippet
+                )
+            })
+            .collect();
+
+        format!(
+            "🔎 <b>{} results for {}</b>\n\n{}",
+            entries.len(),
+            html::escape(query),
+            entries.join("\n\n")
+        )
+    }
+
+    /// Treats text replies to a screenshot notification as follow-up questions about it.
+    async fn handle_reply_message(&self, bot: &Bot, message: Message) -> ResponseResult<()> {
+        if !self.is_allowed_chat(&message.chat) {
+            return Ok(());
+        }
+
+        let (Some(question), Some(analysis_id)) = (
+            message.text(),
+            message.reply_to_message().and_then(|replied| self.analysis_for_reply(replied)),
+        ) else {
+            return Ok(());
+        };
+
+        info!("💬 Telegram follow-up for analysis {}", analysis_id);
+
+        let reply = match self.ask_followup(&analysis_id, question).await {
+            Ok(turn) => format!("💬 {}", truncate_html(&turn.content, 3500)),
+            Err(e) => {
+                error!("Telegram follow-up for {} failed: {}", analysis_id, e);
+                format!("💬 Could not answer: {}", html::escape(&e.to_string()))
+            }
+        };
+
+        let answer = bot
+            .send_message(message.chat.id, reply)
+            .reply_to_message_id(message.id)
+            .parse_mode(ParseMode::Html)
+            .disable_web_page_preview(true)
+            .await?;
+        self.track_telegram_message(&analysis_id, &answer);
+
+        Ok(())
+    }
+
+    /// Produces the HTML replies for a follow-up action on a stored analysis, usually just one.
+    pub(crate) async fn run_follow_up(
+        &self,
+        action: FollowUpAction,
+        analysis_id: &str,
+        analysis: &AnalysisData,
+    ) -> Vec<String> {
+        let content = &analysis.content_analysis;
+
+        let reply = match action {
+            FollowUpAction::ArxivResearch => {
+                if content.research_topics.is_empty() {
+                    return vec!["🔬 <b>Research Papers</b>\n\nNo research topics were identified in this screenshot.".to_string()];
+                }
+
+                match arxiv::search_papers(&self.client, &content.research_topics).await {
+                    Ok(papers) => arxiv::format_papers_html(&papers),
+                    Err(e) => {
+                        error!("arXiv research failed: {}", e);
+                        "🔬 <b>Research Papers</b>\n\nCould not reach arXiv, please try again later.".to_string()
+                    }
+                }
+            }
+            FollowUpAction::DeepResearch => match self.run_deep_research(analysis_id).await {
+                Ok(report) => return research::format_report_messages(&report),
+                Err(e) => {
+                    error!("Deep research failed: {}", e);
+                    format!("🧠 <b>Deep Research</b>\n\nCould not complete the research: {}", html::escape(&e.to_string()))
+                }
+            },
+            FollowUpAction::FullWebpage => {
+                if content.webpage_url.is_none() {
+                    return vec!["🌐 <b>Webpage Content</b>\n\nNo webpage URL was detected in this screenshot.".to_string()];
+                }
+
+                match self.fetch_webpage(analysis_id).await {
+                    Ok(page) => webpage::format_webpage_html(&page),
+                    Err(e) => {
+                        error!("Webpage fetch failed: {}", e);
+                        format!("🌐 <b>Webpage Content</b>\n\nCould not load the page: {}", html::escape(&e.to_string()))
+                    }
+                }
+            }
+        };
+        vec![reply]
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{anyhow, Context, Result};
+use sha2::{Digest, Sha256};
+use std::{
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+use crate::AppConfig;
+
+/// The certificate and key the server presents over HTTPS.
+#[derive(Debug, Clone)]
+pub struct TlsIdentity {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// SHA-256 of the certificate, as colon-separated hex, for clients to pin.
+    pub fingerprint: String,
+    /// Generated by the app rather than provided by the user.
+    pub self_signed: bool,
+}
+
+/// Uppercase, colon-separated SHA-256 of a DER certificate, the format browsers and `openssl` show.
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Fingerprint of the first certificate in a PEM file.
+fn pem_fingerprint(cert_path: &Path) -> Result<String> {
+    let file = std::fs::File::open(cert_path)
+        .with_context(|| format!("Can't read certificate {}", cert_path.display()))?;
+    let cert = rustls_pemfile::certs(&mut BufReader::new(file))
+        .next()
+        .ok_or_else(|| anyhow!("No certificate found in {}", cert_path.display()))??;
+    Ok(fingerprint(&cert))
+}
+
+/// Names clients may reach the server by: localhost, the LAN address and the mDNS host name.
+fn subject_alt_names() -> Vec<String> {
+    let mut names = vec!["localhost".to_string(), "127.0.0.1".to_string()];
+    if let Ok(ip) = local_ip_address::local_ip() {
+        names.push(ip.to_string());
+    }
+    let host = gethostname::gethostname().to_string_lossy().to_string();
+    if !host.is_empty() {
+        names.push(format!("{}.local", host.trim_end_matches(".local")));
+    }
+    names
+}
+
+fn write_private(path: &Path, contents: &str) -> Result<()> {
+    std::fs::write(path, contents)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+/// Creates a self-signed certificate in the app data dir, or reuses the one made earlier so
+/// pinned fingerprints stay valid.
+fn self_signed_identity() -> Result<TlsIdentity> {
+    let dir = crate::app_data_dir().join("tls");
+    let cert_path = dir.join("cert.pem");
+    let key_path = dir.join("key.pem");
+
+    if !cert_path.exists() || !key_path.exists() {
+        std::fs::create_dir_all(&dir)?;
+        let generated = rcgen::generate_simple_self_signed(subject_alt_names())
+            .map_err(|e| anyhow!("Failed to generate a certificate: {}", e))?;
+        std::fs::write(&cert_path, generated.cert.pem())?;
+        write_private(&key_path, &generated.key_pair.serialize_pem())?;
+        info!("🔐 Generated a self-signed certificate in {}", dir.display());
+    }
+
+    Ok(TlsIdentity {
+        fingerprint: pem_fingerprint(&cert_path)?,
+        cert_path,
+        key_path,
+        self_signed: true,
+    })
+}
+
+/// The identity to serve HTTPS with: the configured certificate and key, or a self-signed pair.
+pub fn load_identity(config: &AppConfig) -> Result<TlsIdentity> {
+    match (&config.tls_cert_path, &config.tls_key_path) {
+        (Some(cert_path), Some(key_path)) => Ok(TlsIdentity {
+            fingerprint: pem_fingerprint(cert_path)?,
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+            self_signed: false,
+        }),
+        (None, None) => self_signed_identity(),
+        _ => Err(anyhow!("Both a TLS certificate and key path are needed")),
+    }
+}
+
+/// rustls needs a process-wide crypto provider chosen before the first TLS config is built.
+pub fn install_crypto_provider() {
+    // Fails only if one is already installed, which is fine
+    let _ = rustls::crypto::ring::default_provider().install_default();
+}
@@ -0,0 +1,132 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{usage, ContentAnalysis, ProcessedImage, ScreenshotProcessor};
+
+const TRANSLATION_MAX_TOKENS: u32 = 2_000;
+
+/// The text of a screenshot translated into another language.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Translation {
+    pub analysis_id: String,
+    /// Language the screenshot's text was detected in, e.g. `Japanese`.
+    pub source_language: Option<String>,
+    pub target_language: String,
+    pub text: String,
+    pub translated_at: DateTime<Utc>,
+}
+
+fn translation_prompt(target_language: &str) -> String {
+    format!(
+        "Translate the text in this screenshot into {}. Keep the order it appears in and put separate \
+         messages, headings or paragraphs on separate lines. Leave names, usernames, URLs and code as \
+         they are. Reply with the translation only.",
+        target_language
+    )
+}
+
+/// Whether two language names refer to the same language, e.g. `english` and `English`.
+fn same_language(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+/// The summary with the translation appended, as it appears in the app and on Telegram.
+pub(crate) fn append_translation(summary: &str, translation: &Translation) -> String {
+    let from = translation
+        .source_language
+        .as_deref()
+        .map(|language| format!(" from {}", language))
+        .unwrap_or_default();
+    format!("{}\n\n🌐 Translation{}:\n{}", summary, from, translation.text)
+}
+
+impl ScreenshotProcessor {
+    async fn translate_image(
+        &self,
+        analysis_id: &str,
+        image: &ProcessedImage,
+        source_language: Option<&str>,
+        target_language: &str,
+    ) -> Result<Translation> {
+        let text = self
+            .provider
+            .complete(&translation_prompt(target_language), &[image], TRANSLATION_MAX_TOKENS)
+            .await?;
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(anyhow!("The provider returned an empty translation"));
+        }
+        Ok(Translation {
+            analysis_id: analysis_id.to_string(),
+            source_language: source_language.map(str::to_string),
+            target_language: target_language.trim().to_string(),
+            text: text.to_string(),
+            translated_at: Utc::now(),
+        })
+    }
+
+    /// Translates a new screenshot's text when auto-translate is on and it's in another language.
+    /// Failures are logged rather than failing the analysis.
+    pub(crate) async fn auto_translate(
+        &self,
+        analysis_id: &str,
+        image: &ProcessedImage,
+        content_analysis: &ContentAnalysis,
+    ) -> Option<Translation> {
+        let target = &self.config.translation_language;
+        let source = content_analysis.language.as_deref()?;
+        if !self.config.auto_translate || same_language(source, target) {
+            return None;
+        }
+
+        match self.translate_image(analysis_id, image, Some(source), target).await {
+            Ok(translation) => {
+                info!("🌐 Translated {} from {} to {}", analysis_id, source, target);
+                Some(translation)
+            }
+            Err(e) => {
+                warn!("Auto-translation of {} failed: {}", analysis_id, e);
+                None
+            }
+        }
+    }
+
+    /// Translates an analysis's screenshot into `target_language`, reusing an earlier translation
+    /// into the same language.
+    pub async fn translate_analysis(&self, analysis_id: &str, target_language: &str) -> Result<Translation> {
+        if target_language.trim().is_empty() {
+            return Err(anyhow!("A target language is required"));
+        }
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+        if let Some(translation) = self.store.translation(analysis_id, target_language.trim())? {
+            return Ok(translation);
+        }
+        self.check_budget()?;
+
+        // A local-only screenshot isn't sent to a cloud provider for translation either
+        let processor = if analysis.metadata.local_only == Some(true) && !self.config.privacy_mode {
+            self.local_only()?
+        } else {
+            self.clone()
+        };
+        let image = self.llm_image(&analysis.image_data).await;
+        let (translation, records) = usage::track(processor.translate_image(
+            analysis_id,
+            &image,
+            analysis.content_analysis.language.as_deref(),
+            target_language,
+        ))
+        .await;
+        self.save_usage(Some(analysis_id), &records);
+
+        let translation = translation?;
+        self.store.save_translation(&translation)?;
+        info!("🌐 Translated {} into {}", analysis_id, translation.target_language);
+        Ok(translation)
+    }
+}
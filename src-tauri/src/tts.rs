@@ -0,0 +1,262 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{path::PathBuf, process::Stdio};
+use teloxide::{
+    prelude::*,
+    types::{InputFile, MessageId, Recipient},
+};
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::{info, warn};
+
+use crate::{AnalysisData, ScreenshotProcessor};
+
+const OPENAI_SPEECH_URL: &str = "https://api.openai.com/v1/audio/speech";
+/// OpenAI's input limit is 4096 characters; summaries are far shorter, but follow-ups may not be.
+const MAX_SPEECH_CHARS: usize = 4_000;
+/// Encodes Piper's WAV output to the OGG/Opus Telegram plays as a voice message.
+const FFMPEG: &str = "ffmpeg";
+
+/// Speech engine for voice notes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TtsProvider {
+    /// No voice notes.
+    #[default]
+    Off,
+    /// OpenAI's speech API, using `openai_api_key`.
+    OpenAi,
+    /// A local Piper install; needs ffmpeg to send the audio as a voice message.
+    Piper,
+}
+
+/// Settings for reading summaries aloud as Telegram voice messages, in chats with `voice_notes` on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TtsConfig {
+    pub provider: TtsProvider,
+    /// OpenAI voice, e.g. `alloy` or `nova`.
+    pub voice: String,
+    /// OpenAI speech model.
+    pub model: String,
+    /// The `piper` executable; looked up on `PATH` when unset.
+    pub piper_binary: Option<PathBuf>,
+    /// Piper voice model (`.onnx`).
+    pub piper_model: Option<PathBuf>,
+}
+
+impl Default for TtsConfig {
+    fn default() -> Self {
+        Self {
+            provider: TtsProvider::Off,
+            voice: "alloy".to_string(),
+            model: "tts-1".to_string(),
+            piper_binary: None,
+            piper_model: None,
+        }
+    }
+}
+
+/// Synthesized speech, and whether it's OGG/Opus that Telegram accepts as a voice message.
+struct SpeechClip {
+    bytes: Vec<u8>,
+    ogg_opus: bool,
+}
+
+/// The summary as it should be read out, without Markdown markup.
+fn speakable_text(summary: &str) -> String {
+    let text: String = summary.chars().filter(|c| !matches!(c, '*' | '#' | '`' | '_')).collect();
+    text.chars().take(MAX_SPEECH_CHARS).collect::<String>().trim().to_string()
+}
+
+async fn run_with_stdin(command: &mut Command, input: &[u8]) -> Result<Vec<u8>> {
+    let program = command.as_std().get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Couldn't run {} (is it installed?): {}", program, e))?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("{} stdin unavailable", program))?;
+    let write = async move {
+        let result = stdin.write_all(input).await;
+        drop(stdin);
+        result
+    };
+    let (written, output) = tokio::join!(write, child.wait_with_output());
+    written?;
+    let output = output?;
+    if !output.status.success() {
+        return Err(anyhow!("{} exited with {}", program, output.status));
+    }
+    Ok(output.stdout)
+}
+
+impl ScreenshotProcessor {
+    async fn openai_speech(&self, text: &str) -> Result<SpeechClip> {
+        let api_key = self
+            .config
+            .openai_api_key
+            .as_deref()
+            .ok_or_else(|| anyhow!("OpenAI text-to-speech needs an OpenAI API key"))?;
+        let tts = &self.config.tts;
+        let response = self
+            .client
+            .post(OPENAI_SPEECH_URL)
+            .bearer_auth(api_key)
+            .json(&serde_json::json!({
+                "model": tts.model,
+                "voice": tts.voice,
+                "input": text,
+                "response_format": "opus",
+            }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("OpenAI speech request failed ({}): {}", status, body));
+        }
+        Ok(SpeechClip {
+            bytes: response.bytes().await?.to_vec(),
+            ogg_opus: true,
+        })
+    }
+
+    async fn piper_speech(&self, analysis_id: &str, text: &str) -> Result<SpeechClip> {
+        let tts = &self.config.tts;
+        let model = tts
+            .piper_model
+            .as_ref()
+            .ok_or_else(|| anyhow!("Piper text-to-speech needs a voice model (tts.piper_model)"))?;
+        let binary = tts.piper_binary.clone().unwrap_or_else(|| PathBuf::from("piper"));
+
+        let wav_path = std::env::temp_dir().join(format!("screenshot-ai-{}.wav", analysis_id));
+        let piped = run_with_stdin(
+            Command::new(&binary).arg("--model").arg(model).arg("--output_file").arg(&wav_path),
+            text.as_bytes(),
+        )
+        .await;
+        let wav = piped.and_then(|_| Ok(std::fs::read(&wav_path)?));
+        let _ = std::fs::remove_file(&wav_path);
+        let wav = wav?;
+
+        // Without ffmpeg the WAV still goes out, as an audio file rather than a voice message
+        let encoded = run_with_stdin(
+            Command::new(FFMPEG).args([
+                "-loglevel", "error", "-i", "pipe:0", "-c:a", "libopus", "-b:a", "32k", "-f", "ogg", "pipe:1",
+            ]),
+            &wav,
+        )
+        .await;
+        match encoded {
+            Ok(bytes) => Ok(SpeechClip { bytes, ogg_opus: true }),
+            Err(e) => {
+                warn!("Couldn't encode voice note, sending WAV instead: {}", e);
+                Ok(SpeechClip {
+                    bytes: wav,
+                    ogg_opus: false,
+                })
+            }
+        }
+    }
+
+    async fn synthesize_speech(&self, analysis_id: &str, text: &str) -> Result<SpeechClip> {
+        match self.config.tts.provider {
+            TtsProvider::Off => Err(anyhow!("Text-to-speech is off")),
+            TtsProvider::OpenAi => self.openai_speech(text).await,
+            TtsProvider::Piper => self.piper_speech(analysis_id, text).await,
+        }
+    }
+
+    /// Chats with voice notes on that should hear about this screenshot, each with its forum topic
+    /// and the notification to reply to.
+    fn voice_note_recipients(&self, analysis: &AnalysisData) -> Vec<(Recipient, Option<i32>, Option<MessageId>)> {
+        let content_type = &analysis.content_analysis.content_type;
+        self.telegram_chats()
+            .iter()
+            .filter(|chat| chat.voice_notes && chat.wants(&analysis.source, content_type))
+            .filter_map(|chat| {
+                let recipient = chat.recipient()?;
+                let notification = match recipient {
+                    Recipient::Id(chat_id) => analysis
+                        .telegram_messages
+                        .iter()
+                        .find(|message| message.chat_id == chat_id.0)
+                        .map(|message| MessageId(message.message_id)),
+                    Recipient::ChannelUsername(_) => None,
+                };
+                Some((recipient, chat.topic_for(content_type), notification))
+            })
+            .collect()
+    }
+
+    /// Reads a new analysis's summary aloud and sends it as a voice message, in reply to the
+    /// notification, to chats that have voice notes on. Runs in the background.
+    pub(crate) fn send_voice_notes(&self, analysis_id: &str) {
+        if self.config.tts.provider == TtsProvider::Off || self.telegram_bot.is_none() {
+            return;
+        }
+
+        let processor = self.clone();
+        let analysis_id = analysis_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = processor.deliver_voice_notes(&analysis_id).await {
+                warn!("Failed to send voice note for {}: {}", analysis_id, e);
+            }
+        });
+    }
+
+    async fn deliver_voice_notes(&self, analysis_id: &str) -> Result<()> {
+        let Some(bot) = &self.telegram_bot else {
+            return Ok(());
+        };
+        // Reloaded so the notifications just sent can be replied to
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis not found: {}", analysis_id))?;
+        let recipients = self.voice_note_recipients(&analysis);
+        let text = speakable_text(&analysis.brief_summary);
+        if recipients.is_empty() || text.is_empty() {
+            return Ok(());
+        }
+
+        let clip = self.synthesize_speech(analysis_id, &text).await?;
+        let short_id = &analysis_id[..8.min(analysis_id.len())];
+        let file = if clip.ogg_opus {
+            InputFile::memory(clip.bytes).file_name(format!("summary_{}.ogg", short_id))
+        } else {
+            InputFile::memory(clip.bytes).file_name(format!("summary_{}.wav", short_id))
+        };
+
+        for (recipient, thread_id, reply_to) in recipients {
+            let sent = if clip.ogg_opus {
+                let mut request = bot.send_voice(recipient.clone(), file.clone());
+                if let Some(thread_id) = thread_id {
+                    request = request.message_thread_id(thread_id);
+                }
+                if let Some(reply_to) = reply_to {
+                    request = request.reply_to_message_id(reply_to);
+                }
+                request.await
+            } else {
+                let mut request = bot.send_audio(recipient.clone(), file.clone());
+                if let Some(thread_id) = thread_id {
+                    request = request.message_thread_id(thread_id);
+                }
+                if let Some(reply_to) = reply_to {
+                    request = request.reply_to_message_id(reply_to);
+                }
+                request.await
+            };
+            match sent {
+                Ok(message) => self.track_telegram_message(analysis_id, &message),
+                Err(e) => warn!("Failed to send voice note to Telegram chat {}: {}", recipient, e),
+            }
+        }
+        info!("🔊 Sent voice note for {}", analysis_id);
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
+use tracing::warn;
+
+use crate::ScreenshotProcessor;
+
+/// USD per million tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Built-in prices, matched by model id prefix. Local models are free.
+const DEFAULT_PRICES: &[(&str, ModelPrice)] = &[
+    ("claude-3-5-sonnet", ModelPrice { input_per_mtok: 3.0, output_per_mtok: 15.0 }),
+    ("claude-3-7-sonnet", ModelPrice { input_per_mtok: 3.0, output_per_mtok: 15.0 }),
+    ("claude-sonnet-4", ModelPrice { input_per_mtok: 3.0, output_per_mtok: 15.0 }),
+    ("claude-3-5-haiku", ModelPrice { input_per_mtok: 0.8, output_per_mtok: 4.0 }),
+    ("claude-3-haiku", ModelPrice { input_per_mtok: 0.25, output_per_mtok: 1.25 }),
+    ("claude-3-opus", ModelPrice { input_per_mtok: 15.0, output_per_mtok: 75.0 }),
+    ("claude-opus-4", ModelPrice { input_per_mtok: 15.0, output_per_mtok: 75.0 }),
+    ("gpt-4o-mini", ModelPrice { input_per_mtok: 0.15, output_per_mtok: 0.6 }),
+    ("gpt-4o", ModelPrice { input_per_mtok: 2.5, output_per_mtok: 10.0 }),
+    ("gemini-1.5-flash", ModelPrice { input_per_mtok: 0.075, output_per_mtok: 0.3 }),
+    ("gemini-1.5-pro", ModelPrice { input_per_mtok: 1.25, output_per_mtok: 5.0 }),
+    ("gemini-2.0-flash", ModelPrice { input_per_mtok: 0.1, output_per_mtok: 0.4 }),
+];
+
+/// Looks up the price for `model`, preferring the longest matching prefix in `overrides`.
+pub fn price_for(model: &str, overrides: &HashMap<String, ModelPrice>) -> ModelPrice {
+    let longest_match = |prices: &mut dyn Iterator<Item = (&str, ModelPrice)>| {
+        prices
+            .filter(|(prefix, _)| model.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, price)| price)
+    };
+
+    longest_match(&mut overrides.iter().map(|(prefix, price)| (prefix.as_str(), *price)))
+        .or_else(|| longest_match(&mut DEFAULT_PRICES.iter().copied()))
+        .unwrap_or_default()
+}
+
+/// Tokens reported by a provider for one request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl TokenUsage {
+    /// Combines two cumulative counts from the same stream, keeping the larger of each.
+    pub fn max(self, other: TokenUsage) -> TokenUsage {
+        TokenUsage {
+            input_tokens: self.input_tokens.max(other.input_tokens),
+            output_tokens: self.output_tokens.max(other.output_tokens),
+        }
+    }
+}
+
+/// One LLM request's usage, as recorded by the provider that served it.
+#[derive(Debug, Clone)]
+pub struct UsageRecord {
+    pub provider: &'static str,
+    pub model: String,
+    pub usage: TokenUsage,
+}
+
+/// Token and cost totals over a set of LLM requests.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub today: UsageTotals,
+    /// Since Monday, local time.
+    pub this_week: UsageTotals,
+    pub this_month: UsageTotals,
+    pub all_time: UsageTotals,
+}
+
+tokio::task_local! {
+    static RECORDS: Arc<Mutex<Vec<UsageRecord>>>;
+}
+
+/// Runs `future`, collecting the usage of every LLM request it makes.
+pub async fn track<F: std::future::Future>(future: F) -> (F::Output, Vec<UsageRecord>) {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let output = RECORDS.scope(records.clone(), future).await;
+    let records = std::mem::take(&mut *records.lock());
+    (output, records)
+}
+
+/// Called by providers after each successful request.
+pub(crate) fn record(provider: &'static str, model: &str, usage: TokenUsage) {
+    let _ = RECORDS.try_with(|records| {
+        records.lock().push(UsageRecord {
+            provider,
+            model: model.to_string(),
+            usage,
+        })
+    });
+}
+
+/// Start of the current local day, week (Monday) and month, in UTC.
+pub(crate) fn period_starts() -> (DateTime<Utc>, DateTime<Utc>, DateTime<Utc>) {
+    let today = Local::now().date_naive();
+    let start_of = |date: chrono::NaiveDate| {
+        Local
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now)
+    };
+
+    let week_start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let month_start = today.with_day(1).unwrap_or(today);
+    (start_of(today), start_of(week_start), start_of(month_start))
+}
+
+impl ScreenshotProcessor {
+    /// Prices and stores the usage collected while handling one analysis or question.
+    pub(crate) fn save_usage(&self, analysis_id: Option<&str>, records: &[UsageRecord]) -> UsageTotals {
+        let mut totals = UsageTotals::default();
+        for record in records {
+            let price = price_for(&record.model, &self.config.model_prices);
+            let cost = (record.usage.input_tokens as f64 * price.input_per_mtok
+                + record.usage.output_tokens as f64 * price.output_per_mtok)
+                / 1_000_000.0;
+
+            if let Err(e) = self.store.record_usage(analysis_id, record, cost) {
+                warn!("Failed to record token usage: {}", e);
+            }
+
+            totals.requests += 1;
+            totals.input_tokens += record.usage.input_tokens;
+            totals.output_tokens += record.usage.output_tokens;
+            totals.cost_usd += cost;
+        }
+        self.notify_budget_thresholds(totals.cost_usd);
+        crate::audit::add_usage(totals.input_tokens, totals.output_tokens);
+        totals
+    }
+
+    pub fn usage_stats(&self) -> Result<UsageStats> {
+        let (today, week, month) = period_starts();
+        Ok(UsageStats {
+            today: self.store.usage_totals(Some(today))?,
+            this_week: self.store.usage_totals(Some(week))?,
+            this_month: self.store.usage_totals(Some(month))?,
+            all_time: self.store.usage_totals(None)?,
+        })
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::{AnalysisData, ScreenshotProcessor};
+
+/// Folder inside the vault that holds exported screenshots.
+const ATTACHMENTS_DIR: &str = "attachments";
+const TITLE_MAX_CHARS: usize = 60;
+
+/// A YAML scalar; JSON strings are valid YAML and take care of quoting.
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
+
+/// A filesystem-safe note title taken from the start of the summary.
+fn note_title(summary: &str) -> String {
+    let title: String = summary
+        .lines()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("Screenshot")
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']'))
+        .take(TITLE_MAX_CHARS)
+        .collect();
+    let title = title.trim().trim_end_matches('.');
+    if title.is_empty() { "Screenshot".to_string() } else { title.to_string() }
+}
+
+/// `<date> <title> (<short id>).md`, so notes sort by time and stay unique per analysis.
+pub(crate) fn note_file_name(analysis_id: &str, analysis: &AnalysisData) -> String {
+    let short_id: String = analysis_id.chars().take(8).collect();
+    let local_time = analysis.timestamp.with_timezone(&chrono::Local);
+    format!(
+        "{} {} ({}).md",
+        local_time.format("%Y-%m-%d %H%M"),
+        note_title(&analysis.brief_summary),
+        short_id
+    )
+}
+
+pub(crate) fn image_extension(media_type: &str) -> &'static str {
+    match media_type {
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        _ => "png",
+    }
+}
+
+pub(crate) fn render_note(analysis_id: &str, analysis: &AnalysisData, image_path: &str) -> String {
+    let content = &analysis.content_analysis;
+    let mut note = String::from("---\n");
+    note.push_str(&format!("id: {}\n", yaml_string(analysis_id)));
+    note.push_str(&format!("timestamp: {}\n", analysis.timestamp.to_rfc3339()));
+    note.push_str(&format!("source: {}\n", yaml_string(&analysis.source)));
+    note.push_str(&format!("content_type: {}\n", yaml_string(&content.content_type)));
+    if let Some(ref app) = analysis.metadata.app {
+        note.push_str(&format!("app: {}\n", yaml_string(app)));
+    }
+    if let Some(ref url) = content.webpage_url {
+        note.push_str(&format!("url: {}\n", yaml_string(url)));
+    }
+    note.push_str("topics:\n");
+    for topic in &content.research_topics {
+        note.push_str(&format!("  - {}\n", yaml_string(topic)));
+    }
+    note.push_str("tags:\n  - screenshot\n");
+    for tag in &content.tags {
+        note.push_str(&format!("  - {}\n", yaml_string(tag)));
+    }
+    note.push_str("---\n\n");
+
+    note.push_str(&format!("{}\n\n", analysis.brief_summary.trim()));
+    if !content.user_intent.trim().is_empty() {
+        note.push_str(&format!("**Intent:** {}\n\n", content.user_intent.trim()));
+    }
+    if !content.follow_up.trim().is_empty() {
+        note.push_str(&format!("**Follow up:** {}\n\n", content.follow_up.trim()));
+    }
+    if let Some(ref url) = content.webpage_url {
+        note.push_str(&format!("**Page:** <{}>\n\n", url));
+    }
+    note.push_str(&format!("![Screenshot]({})\n", image_path));
+    note
+}
+
+impl ScreenshotProcessor {
+    fn vault_dir(&self) -> Result<PathBuf> {
+        let dir = self
+            .config
+            .vault_export_dir
+            .as_ref()
+            .ok_or_else(|| anyhow!("No vault folder is configured"))?;
+        Ok(match (dir.strip_prefix("~"), dirs::home_dir()) {
+            (Ok(rest), Some(home)) => home.join(rest),
+            _ => dir.clone(),
+        })
+    }
+
+    /// Writes an analysis into the vault as a Markdown note, returning the note's path.
+    pub fn export_analysis(&self, analysis_id: &str) -> Result<PathBuf> {
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis {} not found", analysis_id))?;
+        let vault = self.vault_dir()?;
+        let path = write_note(&vault, analysis_id, &analysis)?;
+        info!("📝 Exported analysis {} to {}", analysis_id, path.display());
+        Ok(path)
+    }
+
+    /// Exports a freshly completed analysis when auto-export is on.
+    pub(crate) fn auto_export(&self, analysis_id: &str, analysis: &AnalysisData) {
+        if !self.config.vault_auto_export {
+            return;
+        }
+        let result = self.vault_dir().and_then(|vault| write_note(&vault, analysis_id, analysis));
+        if let Err(e) = result {
+            warn!("Failed to export analysis {} to the vault: {}", analysis_id, e);
+        }
+    }
+}
+
+fn write_note(vault: &Path, analysis_id: &str, analysis: &AnalysisData) -> Result<PathBuf> {
+    let attachments = vault.join(ATTACHMENTS_DIR);
+    std::fs::create_dir_all(&attachments)
+        .with_context(|| format!("Failed to create {}", attachments.display()))?;
+
+    let short_id: String = analysis_id.chars().take(8).collect();
+    let image_name = format!(
+        "screenshot-{}.{}",
+        short_id,
+        image_extension(&analysis.image_data.media_type)
+    );
+    let image_bytes = general_purpose::STANDARD
+        .decode(&analysis.image_data.base64_data)
+        .map_err(|e| anyhow!("Invalid stored image: {}", e))?;
+    std::fs::write(attachments.join(&image_name), image_bytes)?;
+
+    let note_path = vault.join(note_file_name(analysis_id, analysis));
+    let note = render_note(analysis_id, analysis, &format!("{}/{}", ATTACHMENTS_DIR, image_name));
+    std::fs::write(&note_path, note)?;
+    Ok(note_path)
+}
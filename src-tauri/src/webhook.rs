@@ -0,0 +1,126 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::Duration;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::{providers, retry, AnalysisData, ContentAnalysis, ScreenshotMetadata, ScreenshotProcessor};
+
+/// Longest a single webhook delivery attempt may take.
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An endpoint that receives a POST for every completed analysis.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Key for the `X-Webhook-Signature` HMAC; unsigned when `None`.
+    #[serde(default)]
+    pub secret: Option<String>,
+}
+
+/// Body of the `analysis.completed` webhook.
+#[derive(Debug, Clone, Serialize)]
+struct WebhookPayload<'a> {
+    event: &'static str,
+    analysis_id: &'a str,
+    summary: &'a str,
+    content_analysis: &'a ContentAnalysis,
+    metadata: &'a ScreenshotMetadata,
+    source: &'a str,
+    timestamp: DateTime<Utc>,
+    /// Where the image can be fetched, when `public_url` is configured.
+    image_url: Option<String>,
+}
+
+/// Hex HMAC-SHA256 of `"{timestamp}.{body}"`, so receivers can reject replayed deliveries.
+fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+impl ScreenshotProcessor {
+    /// Posts a completed analysis to every configured webhook in the background.
+    pub(crate) fn notify_webhooks(&self, analysis_id: &str, analysis: &AnalysisData) {
+        if self.config.webhooks.is_empty() {
+            return;
+        }
+
+        let image_url = self.image_url(analysis_id);
+        let payload = WebhookPayload {
+            event: "analysis.completed",
+            analysis_id,
+            summary: &analysis.brief_summary,
+            content_analysis: &analysis.content_analysis,
+            metadata: &analysis.metadata,
+            source: &analysis.source,
+            timestamp: analysis.timestamp,
+            image_url,
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed to serialize webhook payload: {}", e);
+                return;
+            }
+        };
+
+        for webhook in self.config.webhooks.clone() {
+            let processor = self.clone();
+            let body = body.clone();
+            tokio::spawn(async move {
+                match processor.deliver_webhook(&webhook, body).await {
+                    Ok(()) => info!("🪝 Delivered webhook to {}", webhook.url),
+                    Err(e) => warn!("Webhook delivery to {} failed: {}", webhook.url, e),
+                }
+            });
+        }
+    }
+
+    /// Sends one delivery, retrying transient failures with the LLM retry policy.
+    async fn deliver_webhook(&self, webhook: &WebhookConfig, body: Vec<u8>) -> Result<()> {
+        let policy = retry::RetryPolicy {
+            max_retries: self.config.retry_attempts,
+            base_delay: Duration::from_millis(self.config.retry_base_delay_ms),
+        };
+        // The same id on every attempt lets receivers drop duplicate deliveries
+        let delivery_id = Uuid::new_v4().to_string();
+
+        let mut attempt = 0;
+        loop {
+            let timestamp = Utc::now().timestamp();
+            let mut request = self
+                .client
+                .post(&webhook.url)
+                .timeout(WEBHOOK_TIMEOUT)
+                .header("Content-Type", "application/json")
+                .header("X-Webhook-Event", "analysis.completed")
+                .header("X-Webhook-Id", &delivery_id)
+                .header("X-Webhook-Timestamp", timestamp.to_string());
+            if let Some(secret) = webhook.secret.as_deref().filter(|s| !s.is_empty()) {
+                request = request.header(
+                    "X-Webhook-Signature",
+                    format!("sha256={}", signature(secret, timestamp, &body)),
+                );
+            }
+
+            match providers::send(request.body(body.clone()), "Webhook").await {
+                Err(e) if attempt < policy.max_retries && retry::is_transient(&e) => {
+                    let delay = policy.delay(attempt);
+                    attempt += 1;
+                    warn!(
+                        "Webhook delivery to {} failed ({}), retry {}/{} in {:?}",
+                        webhook.url, e, attempt, policy.max_retries, delay
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                result => return result.map(|_| ()),
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use reqwest::{header, Client, Url};
+use scraper::{ElementRef, Html, Selector};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use teloxide::utils::html;
+use tracing::info;
+
+/// Characters of readable text forwarded to the LLM.
+const MAX_TEXT_CHARS: usize = 12_000;
+const MAX_PAGE_BYTES: usize = 5 * 1024 * 1024;
+
+const CONTENT_TAGS: &str = "p, h1, h2, h3, h4, li, pre, blockquote, td";
+const BOILERPLATE_TAGS: &[&str] = &[
+    "nav", "header", "footer", "aside", "form", "script", "style", "noscript", "svg", "button",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebpageContent {
+    pub url: String,
+    pub title: Option<String>,
+    pub text: String,
+    pub summary: String,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Turns loosely detected URLs such as `example.com/page` into absolute http(s) URLs.
+pub fn normalize_url(raw: &str) -> Result<Url> {
+    let trimmed = raw.trim().trim_matches(|c| c == '<' || c == '>' || c == '"' || c == '\'');
+    let candidate = if trimmed.contains("://") {
+        trimmed.to_string()
+    } else {
+        format!("https://{}", trimmed)
+    };
+
+    let url = Url::parse(&candidate).map_err(|e| anyhow!("Invalid URL '{}': {}", raw, e))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Unsupported URL scheme: {}", url.scheme()));
+    }
+    Ok(url)
+}
+
+/// Downloads a page and returns its title and main readable text.
+pub async fn fetch_readable(client: &Client, url: &Url) -> Result<(Option<String>, String)> {
+    let response = client
+        .get(url.clone())
+        .header(header::USER_AGENT, "Mozilla/5.0 (compatible; ScreenshotAIStudio/0.1)")
+        .header(header::ACCEPT, "text/html,application/xhtml+xml")
+        .timeout(Duration::from_secs(20))
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to fetch {}: {}", url, e))?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!("Fetching {} returned {}", url, response.status()));
+    }
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.contains("html"))
+        .unwrap_or(true);
+    if !is_html {
+        return Err(anyhow!("{} is not an HTML page", url));
+    }
+
+    let bytes = response.bytes().await?;
+    if bytes.len() > MAX_PAGE_BYTES {
+        return Err(anyhow!("Page too large ({} bytes)", bytes.len()));
+    }
+
+    let body = String::from_utf8_lossy(&bytes);
+    let (title, text) = extract_readable_text(&body);
+
+    if text.is_empty() {
+        return Err(anyhow!("No readable content found at {}", url));
+    }
+
+    info!("🌐 Extracted {} characters from {}", text.len(), url);
+    Ok((title, text))
+}
+
+/// Downloads a page just far enough to read its title, preferring the `og:title` it shares under.
+pub async fn fetch_title(client: &Client, url: &Url) -> Result<Option<String>> {
+    let response = client
+        .get(url.clone())
+        .header(header::USER_AGENT, "Mozilla/5.0 (compatible; ScreenshotAIStudio/0.1)")
+        .header(header::ACCEPT, "text/html,application/xhtml+xml")
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .map_err(|e| anyhow!("Failed to fetch {}: {}", url, e))?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!("Fetching {} returned {}", url, response.status()));
+    }
+    if response.content_length().is_some_and(|length| length as usize > MAX_PAGE_BYTES) {
+        return Err(anyhow!("Page too large ({} bytes)", response.content_length().unwrap_or_default()));
+    }
+
+    let bytes = response.bytes().await?;
+    Ok(extract_title(&String::from_utf8_lossy(&bytes)))
+}
+
+fn extract_title(page: &str) -> Option<String> {
+    let document = Html::parse_document(page);
+    ["meta[property='og:title']", "meta[name='twitter:title']", "title"]
+        .into_iter()
+        .filter_map(|selector| Selector::parse(selector).ok())
+        .find_map(|selector| {
+            let element = document.select(&selector).next()?;
+            let text = match element.value().attr("content") {
+                Some(content) => content.to_string(),
+                None => element.text().collect(),
+            };
+            Some(collapse_whitespace(&text)).filter(|title| !title.is_empty())
+        })
+}
+
+/// Readability-style extraction: prefer `<article>`/`<main>`, skip navigation chrome.
+pub fn extract_readable_text(page: &str) -> (Option<String>, String) {
+    let document = Html::parse_document(page);
+
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|sel| document.select(&sel).next())
+        .map(|el| collapse_whitespace(&el.text().collect::<String>()))
+        .filter(|t| !t.is_empty());
+
+    let content_selector = Selector::parse(CONTENT_TAGS).expect("valid selector");
+
+    let candidates = ["article", "main", "[role=main]", "#content", ".content", "body"];
+    let mut best = String::new();
+
+    for candidate in candidates {
+        let Ok(selector) = Selector::parse(candidate) else {
+            continue;
+        };
+
+        for root in document.select(&selector) {
+            let text = collect_blocks(root, &content_selector);
+            if text.len() > best.len() {
+                best = text;
+            }
+        }
+
+        // A substantial article/main region beats scraping the whole body
+        if best.len() > 500 {
+            break;
+        }
+    }
+
+    let text: String = best.chars().take(MAX_TEXT_CHARS).collect();
+    (title, text)
+}
+
+fn collect_blocks(root: ElementRef, content_selector: &Selector) -> String {
+    root.select(content_selector)
+        .filter(|el| !is_boilerplate(el))
+        // Skip blocks nested in other content blocks to avoid duplicate text
+        .filter(|el| {
+            !el.ancestors()
+                .filter_map(ElementRef::wrap)
+                .take_while(|a| a.id() != root.id())
+                .any(|a| content_selector.matches(&a))
+        })
+        .map(|el| collapse_whitespace(&el.text().collect::<String>()))
+        .filter(|t| t.len() > 1)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn is_boilerplate(element: &ElementRef) -> bool {
+    std::iter::once(**element)
+        .chain(element.ancestors())
+        .filter_map(ElementRef::wrap)
+        .any(|el| BOILERPLATE_TAGS.contains(&el.value().name()))
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub fn summary_prompt(title: Option<&str>, text: &str) -> String {
+    format!(
+        "Summarize the following webpage for someone who saved a screenshot of it. \
+         Give a short overview followed by the key points as a bulleted list.\n\n\
+         Title: {}\n\n{}",
+        title.unwrap_or("(untitled)"),
+        text
+    )
+}
+
+/// Formats fetched webpage content as a Telegram HTML message.
+pub fn format_webpage_html(content: &WebpageContent) -> String {
+    let summary: String = content.summary.chars().take(3500).collect();
+    format!(
+        "🌐 <b>{}</b>\n{}\n\n{}",
+        html::escape(content.title.as_deref().unwrap_or("Webpage Content")),
+        html::escape(&content.url),
+        html::escape(&summary)
+    )
+}
@@ -0,0 +1,241 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::{file_actions::MovedFiles, vault, AnalysisData, ScreenshotProcessor};
+
+const XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Namespace for the fields without a standard XMP property.
+const XMP_NAMESPACE: &str = "https://screenshotai.studio/ns/1.0/";
+/// Keeps the packet well inside a single JPEG segment.
+const XMP_SUMMARY_MAX_CHARS: usize = 4000;
+
+/// How analyses are written back to watched screenshot files, so they travel with the file into
+/// Finder, Spotlight and other tools.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataWriteback {
+    /// Leave files untouched.
+    #[default]
+    Off,
+    /// Embed XMP in PNG and JPEG files, falling back to a sidecar for other formats.
+    Embed,
+    /// Write a Markdown note next to the file.
+    Sidecar,
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// An XMP packet with the summary as `dc:description`, the topics as `dc:subject` and the analysis id.
+fn xmp_packet(analysis_id: &str, analysis: &AnalysisData) -> String {
+    let summary: String = analysis.brief_summary.trim().chars().take(XMP_SUMMARY_MAX_CHARS).collect();
+    let topics: String = analysis
+        .content_analysis
+        .research_topics
+        .iter()
+        .map(|topic| format!("     <rdf:li>{}</rdf:li>\n", xml_escape(topic)))
+        .collect();
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+  <rdf:Description rdf:about=\"\"\n\
+    xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n\
+    xmlns:ssai=\"{namespace}\">\n\
+   <dc:description>\n\
+    <rdf:Alt>\n\
+     <rdf:li xml:lang=\"x-default\">{summary}</rdf:li>\n\
+    </rdf:Alt>\n\
+   </dc:description>\n\
+   <dc:subject>\n\
+    <rdf:Bag>\n\
+{topics}    </rdf:Bag>\n\
+   </dc:subject>\n\
+   <ssai:AnalysisId>{id}</ssai:AnalysisId>\n\
+   <ssai:ContentType>{content_type}</ssai:ContentType>\n\
+  </rdf:Description>\n\
+ </rdf:RDF>\n\
+</x:xmpmeta>\n\
+<?xpacket end=\"w\"?>",
+        namespace = XMP_NAMESPACE,
+        summary = xml_escape(&summary),
+        topics = topics,
+        id = xml_escape(analysis_id),
+        content_type = xml_escape(&analysis.content_analysis.content_type),
+    )
+}
+
+fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&crc.finalize().to_be_bytes());
+    chunk
+}
+
+/// Puts the packet in an uncompressed `iTXt` chunk right after `IHDR`, replacing any earlier XMP.
+fn embed_png(bytes: &[u8], xmp: &str) -> Result<Vec<u8>> {
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(anyhow!("Not a PNG file"));
+    }
+
+    let mut itxt = Vec::new();
+    itxt.extend_from_slice(XMP_KEYWORD.as_bytes());
+    // Keyword terminator, no compression, empty language tag and translated keyword
+    itxt.extend_from_slice(&[0, 0, 0, 0, 0]);
+    itxt.extend_from_slice(xmp.as_bytes());
+
+    let mut output = PNG_SIGNATURE.to_vec();
+    let mut offset = PNG_SIGNATURE.len();
+    while offset + 12 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[offset..offset + 4].try_into()?) as usize;
+        let end = offset + 12 + length;
+        if end > bytes.len() {
+            return Err(anyhow!("Truncated PNG chunk"));
+        }
+        let kind = &bytes[offset + 4..offset + 8];
+        let data = &bytes[offset + 8..offset + 8 + length];
+
+        let is_xmp = kind == b"iTXt" && data.starts_with(XMP_KEYWORD.as_bytes()) && data.get(XMP_KEYWORD.len()) == Some(&0);
+        if !is_xmp {
+            output.extend_from_slice(&bytes[offset..end]);
+        }
+        if kind == b"IHDR" {
+            output.extend_from_slice(&png_chunk(b"iTXt", &itxt));
+        }
+        offset = end;
+    }
+    Ok(output)
+}
+
+/// Puts the packet in an `APP1` segment after the leading `APPn` segments, replacing any earlier XMP.
+fn embed_jpeg(bytes: &[u8], xmp: &str) -> Result<Vec<u8>> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err(anyhow!("Not a JPEG file"));
+    }
+
+    let mut payload = JPEG_XMP_HEADER.to_vec();
+    payload.extend_from_slice(xmp.as_bytes());
+    let segment_length = u16::try_from(payload.len() + 2).map_err(|_| anyhow!("XMP packet too large"))?;
+
+    let mut output = vec![0xFF, 0xD8];
+    let mut inserted = false;
+    let mut offset = 2;
+    loop {
+        while bytes.get(offset) == Some(&0xFF) && bytes.get(offset + 1) == Some(&0xFF) {
+            offset += 1;
+        }
+        let marker = match bytes.get(offset..offset + 2) {
+            Some([0xFF, marker]) => *marker,
+            _ => return Err(anyhow!("Malformed JPEG segment")),
+        };
+        let is_app = (0xE0..=0xEF).contains(&marker);
+
+        if !is_app && !inserted {
+            output.extend_from_slice(&[0xFF, 0xE1]);
+            output.extend_from_slice(&segment_length.to_be_bytes());
+            output.extend_from_slice(&payload);
+            inserted = true;
+        }
+        // The compressed image data follows, so copy the rest as it is
+        if !is_app {
+            output.extend_from_slice(&bytes[offset..]);
+            return Ok(output);
+        }
+
+        let length = bytes
+            .get(offset + 2..offset + 4)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(|| anyhow!("Truncated JPEG segment"))?;
+        let end = offset + 2 + length;
+        if length < 2 || end > bytes.len() {
+            return Err(anyhow!("Truncated JPEG segment"));
+        }
+        let is_xmp = marker == 0xE1 && bytes[offset + 4..end].starts_with(JPEG_XMP_HEADER);
+        if !is_xmp {
+            output.extend_from_slice(&bytes[offset..end]);
+        }
+        offset = end;
+    }
+}
+
+/// Replaces a file's contents through a hidden temporary file, which the watcher ignores.
+fn replace_file(path: &Path, contents: &[u8], moved_files: &parking_lot::Mutex<MovedFiles>) -> Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("No filename: {}", path.display()))?
+        .to_string_lossy();
+    let temp = path.with_file_name(format!(".{}.tmp", name));
+    std::fs::write(&temp, contents).with_context(|| format!("Failed to write {}", temp.display()))?;
+
+    moved_files.lock().insert(path.to_path_buf());
+    std::fs::rename(&temp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".md");
+    path.with_file_name(name)
+}
+
+fn write_sidecar(path: &Path, analysis_id: &str, analysis: &AnalysisData) -> Result<PathBuf> {
+    let image_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let sidecar = sidecar_path(path);
+    let note = vault::render_note(analysis_id, analysis, &format!("<{}>", image_name));
+    std::fs::write(&sidecar, note).with_context(|| format!("Failed to write {}", sidecar.display()))?;
+    Ok(sidecar)
+}
+
+impl ScreenshotProcessor {
+    /// Writes the analysis into the screenshot file at `path`, or next to it, as configured.
+    pub(crate) fn write_back_metadata(
+        &self,
+        analysis_id: &str,
+        path: &Path,
+        moved_files: &parking_lot::Mutex<MovedFiles>,
+    ) -> Result<()> {
+        let mode = self.config.metadata_writeback;
+        if mode == MetadataWriteback::Off {
+            return Ok(());
+        }
+        let analysis = self
+            .store
+            .get(analysis_id)?
+            .ok_or_else(|| anyhow!("Analysis {} not found", analysis_id))?;
+
+        if mode == MetadataWriteback::Embed {
+            let bytes = std::fs::read(path)?;
+            let xmp = xmp_packet(analysis_id, &analysis);
+            let embedded = match image::guess_format(&bytes) {
+                Ok(image::ImageFormat::Png) => Some(embed_png(&bytes, &xmp)?),
+                Ok(image::ImageFormat::Jpeg) => Some(embed_jpeg(&bytes, &xmp)?),
+                _ => None,
+            };
+            if let Some(embedded) = embedded {
+                replace_file(path, &embedded, moved_files)?;
+                info!("🏷️ Embedded analysis {} in {}", analysis_id, path.display());
+                return Ok(());
+            }
+        }
+
+        let sidecar = write_sidecar(path, analysis_id, &analysis)?;
+        info!("🏷️ Wrote analysis {} to {}", analysis_id, sidecar.display());
+        Ok(())
+    }
+}
@@ -22,8 +22,94 @@ interface Screenshot {
   analysis?: string;
   source?: string;
   imageData?: string; // base64 image data
+  thumbnailData?: string; // base64 JPEG thumbnail
+  retryable?: boolean; // A stored failure that retry_analysis can run again
 }
 
+const thumbnailSrc = (screenshot: Screenshot) =>
+  screenshot.thumbnailData
+    ? `data:image/jpeg;base64,${screenshot.thumbnailData}`
+    : `data:${screenshot.type};base64,${screenshot.imageData}`;
+
+interface AnalysisSummary {
+  id: string;
+  name: string;
+  status: 'completed' | 'failed';
+  timestamp: string;
+  source: string;
+  summary: string;
+  content_type: string;
+  tags: string[];
+  note?: string;
+  starred: boolean;
+  project_id?: string;
+  media_type: string;
+  size_bytes: number;
+  thumbnail_base64?: string;
+}
+
+interface FailedAnalysis {
+  id: string;
+  name: string;
+  status: 'failed';
+  failed_at: string;
+  source: string;
+  error: string;
+  attempts: number;
+  thumbnail_base64?: string;
+}
+
+interface AnalysisSummaryPage {
+  analyses: AnalysisSummary[];
+  page: number;
+  page_size: number;
+  total: number;
+}
+
+interface ScreenshotProcessedEvent {
+  id: string;
+  name: string;
+  timestamp: string;
+  source: string;
+  summary: string;
+  media_type: string;
+  size_bytes: number;
+  image_base64: string;
+}
+
+const HISTORY_PAGE_SIZE = 50;
+
+const fromSummary = (item: AnalysisSummary): Screenshot => ({
+  id: item.id,
+  name: item.name,
+  size: item.size_bytes,
+  type: item.media_type,
+  timestamp: item.timestamp,
+  status: 'completed',
+  analysis: item.summary,
+  source: item.source,
+  thumbnailData: item.thumbnail_base64 // Full image is fetched when opened
+});
+
+const fromFailure = (item: FailedAnalysis): Screenshot => ({
+  id: item.id,
+  name: item.name,
+  size: 0,
+  type: 'image/jpeg',
+  timestamp: item.failed_at,
+  status: 'error',
+  analysis: item.attempts > 1 ? `${item.error} (${item.attempts} attempts)` : item.error,
+  source: item.source,
+  thumbnailData: item.thumbnail_base64,
+  retryable: true
+});
+
+// Stored failures replace their earlier copies and go first
+const mergeFailures = (prev: Screenshot[], failures: FailedAnalysis[]): Screenshot[] => {
+  const failed = failures.map(fromFailure);
+  return [...failed, ...prev.filter(existing => !failed.some(item => item.id === existing.id))];
+};
+
 interface ProcessingResponse {
   success: boolean;
   summary?: string;
@@ -43,6 +129,8 @@ function App() {
   const [activeTab, setActiveTab] = useState<ActiveTab>('gallery');
   const [selectedScreenshot, setSelectedScreenshot] = useState<Screenshot | null>(null);
   const [isViewerOpen, setIsViewerOpen] = useState(false);
+  const [historyPage, setHistoryPage] = useState(1);
+  const [historyTotal, setHistoryTotal] = useState(0);
 
   // Load existing screenshots and listen for new ones
   useEffect(() => {
@@ -51,19 +139,15 @@ function App() {
     // Load existing screenshots
     const loadScreenshots = async () => {
       try {
-        const existing = await invoke<any[]>('get_recent_screenshots');
-        console.log('🔍 Loaded existing screenshots:', existing.length, existing);
-        setScreenshots(existing.map(item => ({
-          id: item.id,
-          name: item.name,
-          size: item.size,
-          type: item.type,
-          timestamp: item.timestamp,
-          status: item.status,
-          analysis: item.analysis,
-          source: item.source,
-          imageData: item.imageData // Include image data if available
-        })));
+        const existing = await invoke<AnalysisSummaryPage>('list_analyses', {
+          page: 1,
+          pageSize: HISTORY_PAGE_SIZE
+        });
+        console.log('🔍 Loaded existing screenshots:', existing.analyses.length, 'of', existing.total);
+        const failures = await invoke<FailedAnalysis[]>('list_failed_analyses');
+        setScreenshots(mergeFailures(existing.analyses.map(fromSummary), failures));
+        setHistoryPage(1);
+        setHistoryTotal(existing.total);
       } catch (error) {
         console.error('Failed to load screenshots:', error);
       }
@@ -71,20 +155,20 @@ function App() {
 
     // Listen for new screenshots being processed
     const setupListener = async () => {
-      const unlisten = await listen('screenshot-processed', (event) => {
-        console.log('📸 Received screenshot-processed event:', event.payload);
+      const unlisten = await listen<ScreenshotProcessedEvent>('screenshot-processed', (event) => {
+        console.log('📸 Received screenshot-processed event:', event.payload.id);
         
-        const screenshotData = event.payload as any;
+        const screenshotData = event.payload;
         const newScreenshot: Screenshot = {
           id: screenshotData.id,
           name: screenshotData.name,
-          size: screenshotData.size,
-          type: screenshotData.type,
+          size: screenshotData.size_bytes,
+          type: screenshotData.media_type,
           timestamp: screenshotData.timestamp,
-          status: screenshotData.status || 'completed',
-          analysis: screenshotData.analysis,
+          status: 'completed',
+          analysis: screenshotData.summary,
           source: screenshotData.source || 'desktop_auto',
-          imageData: screenshotData.imageData // Include image data
+          imageData: screenshotData.image_base64 // Include image data
         };
         
         console.log('📸 Adding new screenshot to state:', newScreenshot);
@@ -109,9 +193,40 @@ function App() {
       return unlisten;
     };
 
+    // Open the analysis from a clicked desktop notification
+    let unlistenOpen: (() => void) | null = null;
+    const setupOpenListener = async () => {
+      unlistenOpen = await listen<{ id: string }>('open-analysis', (event) => {
+        setActiveTab('gallery');
+        setScreenshots(prev => {
+          const screenshot = prev.find(existing => existing.id === event.payload.id);
+          if (screenshot) {
+            setSelectedScreenshot(screenshot);
+            setIsViewerOpen(true);
+          }
+          return prev;
+        });
+      });
+    };
+
+    // Show analyses that failed in the background, with a way to retry them
+    let unlistenFailed: (() => void) | null = null;
+    const setupFailedListener = async () => {
+      unlistenFailed = await listen<{ analysis_id: string; error: string }>('analysis-failed', async () => {
+        try {
+          const failures = await invoke<FailedAnalysis[]>('list_failed_analyses');
+          setScreenshots(prev => mergeFailures(prev, failures));
+        } catch (error) {
+          console.error('Failed to load failed analyses:', error);
+        }
+      });
+    };
+
     const initializeApp = async () => {
       await loadScreenshots();
       await setupListener();
+      await setupOpenListener();
+      await setupFailedListener();
     };
 
     initializeApp();
@@ -122,6 +237,12 @@ function App() {
         console.log('🧹 Cleaning up screenshot event listener');
         unlistenFunction();
       }
+      if (unlistenOpen) {
+        unlistenOpen();
+      }
+      if (unlistenFailed) {
+        unlistenFailed();
+      }
     };
   }, []);
 
@@ -215,7 +336,56 @@ function App() {
     });
   };
 
-  const handleScreenshotClick = (screenshot: Screenshot) => {
+  const loadMoreScreenshots = async () => {
+    try {
+      const next = await invoke<AnalysisSummaryPage>('list_analyses', {
+        page: historyPage + 1,
+        pageSize: HISTORY_PAGE_SIZE
+      });
+      setScreenshots(prev => [
+        ...prev,
+        ...next.analyses.map(fromSummary).filter(item => !prev.some(existing => existing.id === item.id))
+      ]);
+      setHistoryPage(next.page);
+      setHistoryTotal(next.total);
+    } catch (error) {
+      console.error('Failed to load more screenshots:', error);
+    }
+  };
+
+  const retryScreenshot = async (screenshot: Screenshot) => {
+    setScreenshots(prev => prev.map(s =>
+      s.id === screenshot.id ? { ...s, status: 'processing', analysis: undefined } : s
+    ));
+    try {
+      // A successful retry keeps the failed entry's id
+      const result = await invoke<ProcessingResponse>('retry_analysis', { analysisId: screenshot.id });
+      setScreenshots(prev => prev.map(s =>
+        s.id === screenshot.id
+          ? { ...s, status: 'completed', analysis: result.summary, retryable: false }
+          : s
+      ));
+    } catch (error) {
+      setScreenshots(prev => prev.map(s =>
+        s.id === screenshot.id ? { ...s, status: 'error', analysis: `Retry failed: ${error}` } : s
+      ));
+    }
+  };
+
+  const handleScreenshotClick = async (screenshot: Screenshot) => {
+    if (!screenshot.imageData && screenshot.status === 'completed') {
+      try {
+        const image = await invoke<{ type: string; imageData: string } | null>('get_screenshot_image', {
+          analysisId: screenshot.id
+        });
+        if (image) {
+          screenshot = { ...screenshot, type: image.type, imageData: image.imageData };
+          setScreenshots(prev => prev.map(s => s.id === screenshot.id ? screenshot : s));
+        }
+      } catch (error) {
+        console.error('Failed to load screenshot image:', error);
+      }
+    }
     setSelectedScreenshot(screenshot);
     setIsViewerOpen(true);
   };
@@ -445,9 +615,9 @@ function App() {
                             onClick={() => handleScreenshotClick(screenshot)}
                           >
                             <div className="screenshot-image">
-                              {screenshot.imageData ? (
+                              {screenshot.thumbnailData || screenshot.imageData ? (
                                 <img
-                                  src={`data:${screenshot.type};base64,${screenshot.imageData}`}
+                                  src={thumbnailSrc(screenshot)}
                                   alt={screenshot.name}
                                   style={{
                                     width: '100%',
@@ -513,6 +683,17 @@ function App() {
                                 <button className="action-btn" onClick={(e) => e.stopPropagation()}>
                                   ⭐ Save
                                 </button>
+                                {screenshot.retryable && screenshot.status === 'error' && (
+                                  <button
+                                    className="action-btn"
+                                    onClick={(e) => {
+                                      e.stopPropagation();
+                                      retryScreenshot(screenshot);
+                                    }}
+                                  >
+                                    🔁 Retry
+                                  </button>
+                                )}
                                 <button className="expand-btn">View</button>
                               </div>
                             </div>
@@ -555,9 +736,9 @@ function App() {
                             onClick={() => handleScreenshotClick(screenshot)}
                           >
                             <div className="screenshot-image">
-                              {screenshot.imageData ? (
+                              {screenshot.thumbnailData || screenshot.imageData ? (
                                 <img
-                                  src={`data:${screenshot.type};base64,${screenshot.imageData}`}
+                                  src={thumbnailSrc(screenshot)}
                                   alt={screenshot.name}
                                   style={{
                                     width: '100%',
@@ -620,6 +801,17 @@ function App() {
                                 <button className="action-btn" onClick={(e) => e.stopPropagation()}>
                                   ⭐ Save
                                 </button>
+                                {screenshot.retryable && screenshot.status === 'error' && (
+                                  <button
+                                    className="action-btn"
+                                    onClick={(e) => {
+                                      e.stopPropagation();
+                                      retryScreenshot(screenshot);
+                                    }}
+                                  >
+                                    🔁 Retry
+                                  </button>
+                                )}
                                 <button className="expand-btn">View</button>
                               </div>
                             </div>
@@ -629,6 +821,14 @@ function App() {
                     </div>
                   </div>
                 )}
+
+                {historyPage * HISTORY_PAGE_SIZE < historyTotal && (
+                  <div style={{ marginTop: '24px', textAlign: 'center' }}>
+                    <button className="btn-secondary" onClick={loadMoreScreenshots}>
+                      Load more
+                    </button>
+                  </div>
+                )}
               </motion.div>
             )}
 
@@ -15,15 +15,189 @@ import {
   AlertCircle,
   Monitor,
   MessageSquare,
-  ExternalLink
+  ExternalLink,
+  QrCode,
+  Pause
 } from 'lucide-react';
 
+type ProviderKind = 'anthropic' | 'openai' | 'gemini' | 'ollama';
+
+type RedactionMode = 'off' | 'blur' | 'block';
+
+type PauseMode = 'drop' | 'queue';
+
+type ContentFilterMode = 'off' | 'block' | 'ask' | 'local_only';
+
+type ExclusionTarget = 'app' | 'window_title' | 'filename';
+
+interface ExclusionRule {
+  target: ExclusionTarget;
+  pattern: string;
+}
+
+const EXCLUSION_FIELDS: { target: ExclusionTarget; label: string; placeholder: string }[] = [
+  { target: 'app', label: 'Apps', placeholder: '*1password*, *bank*' },
+  { target: 'window_title', label: 'Window Titles', placeholder: '*private browsing*, *incognito*' },
+  { target: 'filename', label: 'Filenames', placeholder: '*private*, re:^tax-' },
+];
+
+type SecretName = 'anthropic_api_key' | 'openai_api_key' | 'gemini_api_key' | 'telegram_bot_token' | 'notion_token' | 'search_api_key';
+
+const SECRET_NAMES: SecretName[] = ['anthropic_api_key', 'openai_api_key', 'gemini_api_key', 'telegram_bot_token', 'notion_token', 'search_api_key'];
+
+interface WatchDirectory {
+  path: string;
+  recursive: boolean;
+  accept_all_images?: boolean;
+  after_analysis?: 'keep' | 'archive' | 'rename' | 'delete';
+  archive_dir?: string;
+}
+
+interface ImagePolicy {
+  max_bytes?: number;
+  min_bytes?: number;
+  max_dimension?: number;
+  accepted_formats?: ('png' | 'jpeg' | 'webp' | 'gif')[];
+}
+
+interface RecordingConfig {
+  enabled?: boolean;
+  max_duration_secs?: number;
+  max_bytes?: number;
+  max_frames?: number;
+}
+
+interface JournalConfig {
+  enabled?: boolean;
+  interval_minutes?: number;
+  excluded_apps?: string[];
+  retention_days?: number;
+  model?: string;
+}
+
+interface ResearchConfig {
+  search_provider?: 'duckduckgo' | 'brave' | 'tavily' | 'searxng';
+  search_api_key?: string;
+  searxng_url?: string;
+  max_queries?: number;
+  results_per_query?: number;
+}
+
+interface TtsConfig {
+  provider?: 'off' | 'openai' | 'piper';
+  voice?: string;
+  model?: string;
+  piper_binary?: string;
+  piper_model?: string;
+}
+
+interface ServerStateChanged {
+  state: 'running' | 'stopped' | 'failed';
+  error?: string;
+}
+
+interface RetentionConfig {
+  max_items?: number;
+  max_age_days?: number;
+  max_disk_mb?: number;
+  sweep_interval_minutes?: number;
+}
+
+interface ProcessingProfile {
+  model?: string;
+  prompt_template?: string;
+  telegram_chat_id?: string;
+  retention_days?: number;
+}
+
 interface ServerConfig {
+  provider: ProviderKind;
   anthropic_api_key?: string;
+  openai_api_key?: string;
+  gemini_api_key?: string;
+  ollama_url?: string;
+  model?: string;
+  summary_max_tokens?: number;
+  analysis_max_tokens?: number;
+  combine_llm_requests?: boolean;
+  summary_prompt?: string;
+  analysis_prompt?: string;
+  app_prompt_templates?: Record<string, string>;
+  content_type_prompt_templates?: Record<string, string>;
   telegram_bot_token?: string;
   telegram_chat_id?: string;
+  telegram_chats?: TelegramChat[];
+  telegram_topics?: Record<string, number>;
+  session_window_secs?: number;
+  digest_enabled?: boolean;
+  digest_time?: string;
+  digest_timezone?: string;
   enable_desktop_detection: boolean;
+  enable_clipboard_detection: boolean;
+  desktop_notification_sources?: string[];
+  watch_directories?: WatchDirectory[];
+  screenshot_include_patterns?: string[];
+  screenshot_exclude_patterns?: string[];
+  max_image_dimension?: number;
+  max_image_bytes?: number;
+  max_concurrent_requests?: number;
+  max_queue_depth?: number;
+  duplicate_window_secs?: number;
+  idempotency_window_secs?: number;
+  image_policy?: ImagePolicy;
+  recordings?: RecordingConfig;
+  journal?: JournalConfig;
+  research?: ResearchConfig;
+  tts?: TtsConfig;
+  retention?: RetentionConfig;
+  perceptual_dedupe?: boolean;
+  redaction_mode?: RedactionMode;
+  redaction_sources?: Record<string, RedactionMode>;
+  profiles?: Record<string, ProcessingProfile>;
+  retry_attempts?: number;
+  retry_base_delay_ms?: number;
+  privacy_mode?: boolean;
+  pause_mode?: PauseMode;
+  content_filter?: ContentFilterMode;
+  exclusion_rules?: ExclusionRule[];
+  encrypt_storage?: boolean;
+  local_model?: string;
+  fallback_provider?: ProviderKind;
+  fallback_model?: string;
+  model_prices?: Record<string, ModelPrice>;
+  budget_soft_limit_usd?: number;
+  budget_hard_limit_usd?: number;
+  webhooks?: WebhookConfig[];
+  public_url?: string;
+  push_notifiers?: PushNotifierConfig[];
+  vault_export_dir?: string;
+  vault_auto_export?: boolean;
+  spotlight_index?: boolean;
+  spotlight_dir?: string;
+  metadata_writeback?: 'off' | 'embed' | 'sidecar';
+  unfurl_links?: boolean;
+  receipt_extraction?: boolean;
+  receipt_ledger?: string;
+  code_extraction?: boolean;
+  table_extraction?: boolean;
+  auto_translate?: boolean;
+  translation_language?: string;
+  response_language?: string;
+  notion_token?: string;
+  notion_database_id?: string;
+  notion_properties?: NotionProperties;
   server_port: number;
+  server_port_range?: number;
+  grpc_port?: number;
+  rate_limit_per_minute?: number;
+  audit_retention_days?: number;
+  log_filter?: string;
+  api_key?: string;
+  mdns_enabled?: boolean;
+  tls_enabled?: boolean;
+  tls_cert_path?: string;
+  tls_key_path?: string;
+  auto_start_server?: boolean;
 }
 
 interface ServerInfo {
@@ -32,7 +206,73 @@ interface ServerInfo {
   port: number;
   endpoint_url: string;
   desktop_detection: boolean;
+  clipboard_detection: boolean;
   telegram_configured: boolean;
+  auth_required: boolean;
+  outbox_pending: number;
+  processing_paused: boolean;
+  mdns_name?: string;
+  tls_fingerprint?: string;
+}
+
+interface PairingQr {
+  svg: string;
+  payload: string;
+  endpoint_url: string;
+  expires_at: string;
+  api_key: string;
+}
+
+interface TelegramChat {
+  chat_id: string;
+  name?: string;
+  notify?: boolean;
+  sources?: string[];
+  content_types?: string[];
+  allow_commands?: boolean;
+  topics?: Record<string, number>;
+  voice_notes?: boolean;
+}
+
+interface WebhookConfig {
+  url: string;
+  secret?: string;
+}
+
+type PushNotifierConfig =
+  | { kind: 'ntfy'; server_url?: string; topic: string; token?: string; priority?: number }
+  | { kind: 'pushover'; app_token: string; user_key: string; priority?: number }
+  | { kind: 'gotify'; server_url: string; app_token: string; priority?: number };
+
+interface NotionProperties {
+  title: string;
+  date: string;
+  source: string;
+  content_type: string;
+  url: string;
+  topics: string;
+}
+
+interface ModelPrice {
+  input_per_mtok: number;
+  output_per_mtok: number;
+}
+
+interface UsageTotals {
+  requests: number;
+  input_tokens: number;
+  output_tokens: number;
+  cost_usd: number;
+}
+
+interface StageTimings {
+  queue_ms: number;
+  preprocess_ms: number;
+  summary_ms: number;
+  analysis_ms: number;
+  store_ms: number;
+  total_ms: number;
+  combined: boolean;
 }
 
 interface ProcessingResponse {
@@ -43,15 +283,39 @@ interface ProcessingResponse {
   follow_up_available?: boolean;
   source?: string;
   error?: string;
+  error_code?: string;
+  retries?: number;
+  failed_over?: boolean;
+  duplicate?: boolean;
+  usage?: UsageTotals;
+  timings?: StageTimings;
+}
+
+interface ConfigCheck {
+  item: string;
+  label: string;
+  status: 'pass' | 'fail' | 'skipped';
+  message: string;
+}
+
+interface ValidationReport {
+  checks: ConfigCheck[];
+  ok: boolean;
 }
 
 const ServerConfig: React.FC = () => {
   const [config, setConfig] = useState<ServerConfig>({
+    provider: 'anthropic',
     anthropic_api_key: '',
+    openai_api_key: '',
+    gemini_api_key: '',
+    ollama_url: '',
     telegram_bot_token: '',
     telegram_chat_id: '',
     enable_desktop_detection: false,
+    enable_clipboard_detection: false,
     server_port: 5001,
+    api_key: '',
   });
 
   const [serverInfo, setServerInfo] = useState<ServerInfo | null>(null);
@@ -59,35 +323,112 @@ const ServerConfig: React.FC = () => {
   const [showPasswords, setShowPasswords] = useState(false);
   const [showSetup, setShowSetup] = useState(false);
   const [recentProcessing, setRecentProcessing] = useState<ProcessingResponse[]>([]);
+  const [storedSecrets, setStoredSecrets] = useState<Partial<Record<SecretName, boolean>>>({});
+  const [rememberSecrets, setRememberSecrets] = useState(false);
+  const [pairingQr, setPairingQr] = useState<PairingQr | null>(null);
+  const [validation, setValidation] = useState<ValidationReport | null>(null);
+  const [isValidating, setIsValidating] = useState(false);
+  const [launchAtLogin, setLaunchAtLogin] = useState(false);
 
   // Load initial config and server status
   useEffect(() => {
     loadConfig();
+    loadStoredSecrets();
     checkServerStatus();
+    invoke<boolean>('get_launch_at_login').then(setLaunchAtLogin).catch(() => {});
 
     // Listen for setup dialog trigger
     const unlisten = listen('show-setup-dialog', () => {
       setShowSetup(true);
     });
 
+    // Screenshots saved while offline are retried in the background
+    const unlistenOutbox = listen<{ pending: number }>('outbox-changed', (event) => {
+      setServerInfo(info => info && { ...info, outbox_pending: event.payload.pending });
+    });
+
+    // Paused or resumed from the tray or Telegram
+    const unlistenPaused = listen<{ paused: boolean }>('processing-paused', (event) => {
+      setServerInfo(info => info && { ...info, processing_paused: event.payload.paused });
+    });
+
+    // Started on launch with the saved settings
+    const unlistenStarted = listen<ServerInfo>('server-started', (event) => {
+      setServerInfo(event.payload);
+    });
+
+    // The server task failed after starting, e.g. the TLS certificate couldn't be loaded
+    const unlistenServerState = listen<ServerStateChanged>('server-state-changed', async (event) => {
+      if (event.payload.state !== 'failed') {
+        checkServerStatus();
+        return;
+      }
+      try {
+        await invoke('stop_server');
+      } catch (error) {
+        console.error('Failed to stop server:', error);
+      }
+      setServerInfo(null);
+      alert(`Screenshot server stopped: ${event.payload.error}`);
+    });
+
     // Check server status periodically
     const interval = setInterval(checkServerStatus, 5000);
 
     return () => {
       unlisten.then(fn => fn());
+      unlistenOutbox.then(fn => fn());
+      unlistenStarted.then(fn => fn());
+      unlistenPaused.then(fn => fn());
+      unlistenServerState.then(fn => fn());
       clearInterval(interval);
     };
   }, []);
 
   const loadConfig = async () => {
     try {
-      const loadedConfig = await invoke<ServerConfig>('load_env_config');
-      setConfig(loadedConfig);
+      const loadedConfig = await invoke<ServerConfig>('load_config');
+      // Show the built-in rules until the user changes them
+      const exclusionRules = loadedConfig.exclusion_rules ?? await invoke<ExclusionRule[]>('get_exclusion_rules');
+      setConfig({ ...loadedConfig, exclusion_rules: exclusionRules });
     } catch (error) {
       console.error('Failed to load config:', error);
     }
   };
 
+  const toggleLaunchAtLogin = async (enabled: boolean) => {
+    try {
+      await invoke('set_launch_at_login', { enabled });
+      setLaunchAtLogin(enabled);
+    } catch (error) {
+      alert(`Failed to change launch at login: ${error}`);
+    }
+  };
+
+  const loadStoredSecrets = async () => {
+    try {
+      const entries = await Promise.all(
+        SECRET_NAMES.map(async (name) => [name, await invoke<boolean>('has_secret', { name })] as const)
+      );
+      setStoredSecrets(Object.fromEntries(entries));
+    } catch (error) {
+      console.error('Failed to check keychain:', error);
+    }
+  };
+
+  const saveSecrets = async () => {
+    for (const name of SECRET_NAMES) {
+      const value = config[name];
+      if (value) {
+        await invoke('save_secret', { name, value });
+      }
+    }
+    await loadStoredSecrets();
+  };
+
+  const secretPlaceholder = (name: SecretName, placeholder: string) =>
+    storedSecrets[name] ? 'Stored in system keychain' : placeholder;
+
   const checkServerStatus = async () => {
     try {
       const status = await invoke<ServerInfo | null>('get_server_status');
@@ -98,20 +439,46 @@ const ServerConfig: React.FC = () => {
     }
   };
 
+  const hasProviderCredentials = () => {
+    switch (config.provider) {
+      case 'anthropic':
+        return !!config.anthropic_api_key || !!storedSecrets.anthropic_api_key;
+      case 'openai':
+        return !!config.openai_api_key || !!storedSecrets.openai_api_key;
+      case 'gemini':
+        return !!config.gemini_api_key || !!storedSecrets.gemini_api_key;
+      case 'ollama':
+        return true;
+    }
+  };
+
   const startServer = async () => {
-    if (!config.anthropic_api_key) {
-      alert('Anthropic API key is required!');
+    if (!hasProviderCredentials()) {
+      alert('An API key for the selected AI provider is required!');
       return;
     }
 
     setIsLoading(true);
     try {
+      if (rememberSecrets) {
+        await saveSecrets();
+      }
+
       const info = await invoke<ServerInfo>('start_server', { config });
       setServerInfo(info);
+
+      try {
+        await invoke('save_config', { config });
+      } catch (error) {
+        console.error('Failed to save config:', error);
+      }
       setShowSetup(false);
       
       // Show success notification
-      alert(`✅ Server started successfully!\n\nEndpoint: ${info.endpoint_url}\n\nSetup your iPhone Shortcut to POST screenshots to this URL.`);
+      const portNote = info.port !== config.server_port
+        ? `\n\nPort ${config.server_port} was in use, so the server is on port ${info.port}.`
+        : '';
+      alert(`✅ Server started successfully!\n\nEndpoint: ${info.endpoint_url}${portNote}\n\nSetup your iPhone Shortcut to POST screenshots to this URL.`);
     } catch (error) {
       console.error('Failed to start server:', error);
       alert(`Failed to start server: ${error}`);
@@ -120,6 +487,22 @@ const ServerConfig: React.FC = () => {
     }
   };
 
+  // Checks the keys, Telegram chats and port without starting anything
+  const validateConfig = async () => {
+    setIsValidating(true);
+    try {
+      if (rememberSecrets) {
+        await saveSecrets();
+      }
+      setValidation(await invoke<ValidationReport>('validate_config', { config }));
+    } catch (error) {
+      console.error('Failed to validate config:', error);
+      alert(`Failed to test configuration: ${error}`);
+    } finally {
+      setIsValidating(false);
+    }
+  };
+
   const stopServer = async () => {
     setIsLoading(true);
     try {
@@ -133,6 +516,38 @@ const ServerConfig: React.FC = () => {
     }
   };
 
+  const generateApiKey = async () => {
+    try {
+      const apiKey = await invoke<string>('rotate_api_key');
+      setConfig({ ...config, api_key: apiKey });
+    } catch (error) {
+      console.error('Failed to generate API key:', error);
+      alert(`Failed to generate API key: ${error}`);
+    }
+  };
+
+  const reindexSpotlight = async () => {
+    try {
+      const count = await invoke<number>('reindex_spotlight');
+      alert(`Indexed ${count} screenshots for system search`);
+    } catch (error) {
+      console.error('Failed to reindex:', error);
+      alert(`Failed to reindex: ${error}`);
+    }
+  };
+
+  const showPairingQr = async () => {
+    try {
+      const qr = await invoke<PairingQr>('get_pairing_qr');
+      setPairingQr(qr);
+      // Pairing turns on authentication, so keep the key it uses
+      setConfig({ ...config, api_key: qr.api_key });
+    } catch (error) {
+      console.error('Failed to create pairing code:', error);
+      alert(`Failed to create pairing code: ${error}`);
+    }
+  };
+
   const toggleDesktopDetection = async () => {
     if (!serverInfo) return;
 
@@ -146,6 +561,31 @@ const ServerConfig: React.FC = () => {
     }
   };
 
+  const toggleClipboardDetection = async () => {
+    if (!serverInfo) return;
+
+    try {
+      const newState = !serverInfo.clipboard_detection;
+      await invoke('toggle_clipboard_detection', { enable: newState });
+      setServerInfo({ ...serverInfo, clipboard_detection: newState });
+    } catch (error) {
+      console.error('Failed to toggle clipboard detection:', error);
+      alert(`Failed to toggle clipboard detection: ${error}`);
+    }
+  };
+
+  const toggleProcessingPaused = async () => {
+    if (!serverInfo) return;
+
+    try {
+      const paused = await invoke<boolean>('set_processing_paused', { paused: !serverInfo.processing_paused });
+      setServerInfo({ ...serverInfo, processing_paused: paused });
+    } catch (error) {
+      console.error('Failed to pause processing:', error);
+      alert(`Failed to pause processing: ${error}`);
+    }
+  };
+
   const testScreenshotProcessing = async () => {
     try {
       // Create a simple test image (1x1 red pixel PNG in base64)
@@ -205,6 +645,12 @@ const ServerConfig: React.FC = () => {
                 <MessageSquare size={16} />
                 <span>{serverInfo.telegram_configured ? 'Telegram ON' : 'Telegram OFF'}</span>
               </div>
+              {serverInfo.outbox_pending > 0 && (
+                <div className="detail-item">
+                  <AlertCircle size={16} />
+                  <span>{serverInfo.outbox_pending} waiting for network</span>
+                </div>
+              )}
             </div>
           )}
         </div>
@@ -241,6 +687,22 @@ const ServerConfig: React.FC = () => {
             <Monitor size={16} />
             {serverInfo?.desktop_detection ? 'Disable' : 'Enable'} Desktop Detection
           </button>
+
+          <button 
+            onClick={toggleClipboardDetection}
+            className={`btn ${serverInfo?.clipboard_detection ? 'btn-success' : 'btn-secondary'}`}
+          >
+            <Monitor size={16} />
+            {serverInfo?.clipboard_detection ? 'Disable' : 'Enable'} Clipboard Detection
+          </button>
+
+          <button
+            onClick={toggleProcessingPaused}
+            className={`btn ${serverInfo?.processing_paused ? 'btn-secondary' : 'btn-outline'}`}
+          >
+            <Pause size={16} />
+            {serverInfo?.processing_paused ? 'Resume' : 'Pause'} Processing
+          </button>
           
           <button 
             onClick={testScreenshotProcessing}
@@ -259,6 +721,30 @@ const ServerConfig: React.FC = () => {
               Copy iOS Endpoint
             </button>
           )}
+
+          {serverInfo && (
+            <button
+              onClick={showPairingQr}
+              className="btn btn-outline"
+            >
+              <QrCode size={16} />
+              Pair iPhone
+            </button>
+          )}
+        </div>
+      )}
+
+      {serverInfo?.tls_fingerprint && (
+        <div className="tls-fingerprint">
+          <small>Certificate SHA-256 fingerprint (pin this on the phone):</small>
+          <code>{serverInfo.tls_fingerprint}</code>
+        </div>
+      )}
+
+      {pairingQr && (
+        <div className="pairing-qr" onClick={() => setPairingQr(null)}>
+          <div dangerouslySetInnerHTML={{ __html: pairingQr.svg }} />
+          <small>Scan with the iPhone Shortcut before {new Date(pairingQr.expires_at).toLocaleTimeString()}</small>
         </div>
       )}
 
@@ -323,28 +809,157 @@ const ServerConfig: React.FC = () => {
               </div>
 
               <div className="config-form">
-                {/* Anthropic API Key */}
+                {/* AI Provider */}
                 <div className="form-group">
                   <label>
-                    <strong>Anthropic API Key</strong> <span className="required">*</span>
+                    <strong>AI Provider</strong>
                   </label>
-                  <div className="input-with-toggle">
+                  <select
+                    value={config.provider}
+                    onChange={(e) => setConfig({...config, provider: e.target.value as ProviderKind})}
+                    className="form-input"
+                  >
+                    <option value="anthropic">Anthropic Claude</option>
+                    <option value="openai">OpenAI GPT-4o</option>
+                    <option value="gemini">Google Gemini</option>
+                    <option value="ollama">Ollama (local)</option>
+                  </select>
+                </div>
+
+                {/* Anthropic API Key */}
+                {config.provider === 'anthropic' && (
+                  <div className="form-group">
+                    <label>
+                      <strong>Anthropic API Key</strong> <span className="required">*</span>
+                    </label>
+                    <div className="input-with-toggle">
+                      <input
+                        type={showPasswords ? 'text' : 'password'}
+                        value={config.anthropic_api_key || ''}
+                        onChange={(e) => setConfig({...config, anthropic_api_key: e.target.value})}
+                        placeholder={secretPlaceholder('anthropic_api_key', 'sk-ant-...')}
+                        className="form-input"
+                      />
+                      <button
+                        type="button"
+                        onClick={() => setShowPasswords(!showPasswords)}
+                        className="toggle-password"
+                      >
+                        {showPasswords ? <EyeOff size={16} /> : <Eye size={16} />}
+                      </button>
+                    </div>
+                    <small>Get your API key from <a href="https://console.anthropic.com/" target="_blank" rel="noopener noreferrer">console.anthropic.com</a></small>
+                  </div>
+                )}
+
+                {/* OpenAI API Key */}
+                {config.provider === 'openai' && (
+                  <div className="form-group">
+                    <label>
+                      <strong>OpenAI API Key</strong> <span className="required">*</span>
+                    </label>
                     <input
                       type={showPasswords ? 'text' : 'password'}
-                      value={config.anthropic_api_key || ''}
-                      onChange={(e) => setConfig({...config, anthropic_api_key: e.target.value})}
-                      placeholder="sk-ant-..."
+                      value={config.openai_api_key || ''}
+                      onChange={(e) => setConfig({...config, openai_api_key: e.target.value})}
+                      placeholder={secretPlaceholder('openai_api_key', 'sk-...')}
                       className="form-input"
                     />
-                    <button
-                      type="button"
-                      onClick={() => setShowPasswords(!showPasswords)}
-                      className="toggle-password"
-                    >
-                      {showPasswords ? <EyeOff size={16} /> : <Eye size={16} />}
-                    </button>
+                    <small>Get your API key from <a href="https://platform.openai.com/api-keys" target="_blank" rel="noopener noreferrer">platform.openai.com</a></small>
+                  </div>
+                )}
+
+                {/* Gemini API Key */}
+                {config.provider === 'gemini' && (
+                  <div className="form-group">
+                    <label>
+                      <strong>Gemini API Key</strong> <span className="required">*</span>
+                    </label>
+                    <input
+                      type={showPasswords ? 'text' : 'password'}
+                      value={config.gemini_api_key || ''}
+                      onChange={(e) => setConfig({...config, gemini_api_key: e.target.value})}
+                      placeholder={secretPlaceholder('gemini_api_key', 'AIza...')}
+                      className="form-input"
+                    />
+                    <small>Get your API key from <a href="https://aistudio.google.com/app/apikey" target="_blank" rel="noopener noreferrer">aistudio.google.com</a></small>
+                  </div>
+                )}
+
+                {/* Ollama URL */}
+                {config.provider === 'ollama' && (
+                  <div className="form-group">
+                    <label>Ollama URL</label>
+                    <input
+                      type="text"
+                      value={config.ollama_url || ''}
+                      onChange={(e) => setConfig({...config, ollama_url: e.target.value})}
+                      placeholder="http://localhost:11434"
+                      className="form-input"
+                    />
+                    <small>Requires a vision model such as <code>llava</code> pulled locally</small>
+                  </div>
+                )}
+
+                {/* Advanced AI Settings */}
+                <div className="form-section">
+                  <h3>🧠 Advanced AI Settings (Optional)</h3>
+
+                  <div className="form-group">
+                    <label>Model</label>
+                    <input
+                      type="text"
+                      value={config.model || ''}
+                      onChange={(e) => setConfig({...config, model: e.target.value})}
+                      placeholder="Provider default"
+                      className="form-input"
+                    />
+                  </div>
+
+                  <div className="form-group">
+                    <label>Max Tokens (summary / content analysis)</label>
+                    <div className="input-row">
+                      <input
+                        type="number"
+                        value={config.summary_max_tokens ?? ''}
+                        onChange={(e) => setConfig({...config, summary_max_tokens: parseInt(e.target.value) || undefined})}
+                        placeholder="200"
+                        className="form-input"
+                        min="50"
+                      />
+                      <input
+                        type="number"
+                        value={config.analysis_max_tokens ?? ''}
+                        onChange={(e) => setConfig({...config, analysis_max_tokens: parseInt(e.target.value) || undefined})}
+                        placeholder="800"
+                        className="form-input"
+                        min="50"
+                      />
+                    </div>
+                  </div>
+
+                  <div className="form-group">
+                    <label>Summary Prompt</label>
+                    <textarea
+                      value={config.summary_prompt || ''}
+                      onChange={(e) => setConfig({...config, summary_prompt: e.target.value})}
+                      placeholder="Leave empty for the default prompt"
+                      className="form-input"
+                      rows={3}
+                    />
+                  </div>
+
+                  <div className="form-group">
+                    <label>Content Analysis Prompt</label>
+                    <textarea
+                      value={config.analysis_prompt || ''}
+                      onChange={(e) => setConfig({...config, analysis_prompt: e.target.value})}
+                      placeholder="Leave empty for the default prompt"
+                      className="form-input"
+                      rows={5}
+                    />
+                    <small>Available variables: <code>{'{source_type}'}</code>, <code>{'{device}'}</code>, <code>{'{app}'}</code>, <code>{'{filename}'}</code>, <code>{'{date}'}</code></small>
                   </div>
-                  <small>Get your API key from <a href="https://console.anthropic.com/" target="_blank" rel="noopener noreferrer">console.anthropic.com</a></small>
                 </div>
 
                 {/* Server Port */}
@@ -360,6 +975,28 @@ const ServerConfig: React.FC = () => {
                   />
                 </div>
 
+                {/* Server API Key */}
+                <div className="form-group">
+                  <label>Server API Key</label>
+                  <div className="input-with-toggle">
+                    <input
+                      type={showPasswords ? 'text' : 'password'}
+                      value={config.api_key || ''}
+                      onChange={(e) => setConfig({...config, api_key: e.target.value})}
+                      placeholder="Leave empty to allow unauthenticated requests"
+                      className="form-input"
+                    />
+                    <button
+                      type="button"
+                      onClick={generateApiKey}
+                      className="toggle-password"
+                    >
+                      <Settings size={16} />
+                    </button>
+                  </div>
+                  <small>Clients send it as <code>Authorization: Bearer &lt;key&gt;</code> or <code>X-API-Key</code></small>
+                </div>
+
                 {/* Desktop Detection */}
                 <div className="form-group">
                   <label className="checkbox-label">
@@ -373,6 +1010,192 @@ const ServerConfig: React.FC = () => {
                   <small>Automatically process screenshots taken on your Mac</small>
                 </div>
 
+                {/* Clipboard Detection */}
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={config.enable_clipboard_detection}
+                      onChange={(e) => setConfig({...config, enable_clipboard_detection: e.target.checked})}
+                    />
+                    <span>Enable Clipboard Screenshot Detection</span>
+                  </label>
+                  <small>Process screenshots copied to the clipboard (⌘⇧⌃4, Win+Shift+S)</small>
+                </div>
+
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={launchAtLogin}
+                      onChange={(e) => toggleLaunchAtLogin(e.target.checked)}
+                    />
+                    <span>Launch at Login</span>
+                  </label>
+                  <small>Starts hidden in the tray with the server running on the saved settings</small>
+                </div>
+
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={config.auto_start_server ?? false}
+                      onChange={(e) => setConfig({...config, auto_start_server: e.target.checked})}
+                    />
+                    <span>Start Server When the App Opens</span>
+                  </label>
+                  <small>Uses the settings saved the last time the server was started, skipping setup</small>
+                </div>
+
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={config.privacy_mode ?? false}
+                      onChange={(e) => setConfig({...config, privacy_mode: e.target.checked})}
+                    />
+                    <span>Privacy Mode</span>
+                  </label>
+                  <small>Analyze with the local Ollama model only; Telegram, webhooks and Notion are skipped</small>
+                </div>
+
+                <div className="form-group">
+                  <label>
+                    <strong>While Processing Is Paused</strong>
+                  </label>
+                  <select
+                    value={config.pause_mode ?? 'drop'}
+                    onChange={(e) => setConfig({...config, pause_mode: e.target.value as PauseMode})}
+                    className="form-input"
+                  >
+                    <option value="drop">Drop new screenshots</option>
+                    <option value="queue">Queue them until resumed</option>
+                  </select>
+                  <small>Pause from the tray, the quick actions or /pause in Telegram</small>
+                </div>
+
+                <div className="form-group">
+                  <label>
+                    <strong>Private Photo Filter</strong>
+                  </label>
+                  <select
+                    value={config.content_filter ?? 'off'}
+                    onChange={(e) => setConfig({...config, content_filter: e.target.value as ContentFilterMode})}
+                    className="form-input"
+                  >
+                    <option value="off">Off</option>
+                    <option value="block">Block flagged images</option>
+                    <option value="ask">Ask before analyzing them</option>
+                    <option value="local_only">Analyze them locally only</option>
+                  </select>
+                  <small>Checks each image on this machine before upload for camera photos and revealing pictures</small>
+                </div>
+
+                <div className="form-group">
+                  <label>
+                    <strong>Never Analyze Screenshots Of</strong>
+                  </label>
+                  {EXCLUSION_FIELDS.map(({ target, label, placeholder }) => (
+                    <div key={target} className="form-group">
+                      <label>{label}</label>
+                      <input
+                        type="text"
+                        value={(config.exclusion_rules || [])
+                          .filter(rule => rule.target === target)
+                          .map(rule => rule.pattern)
+                          .join(', ')}
+                        onChange={(e) => setConfig({
+                          ...config,
+                          exclusion_rules: [
+                            ...(config.exclusion_rules || []).filter(rule => rule.target !== target),
+                            ...e.target.value
+                              .split(',')
+                              .map(pattern => pattern.trim())
+                              .filter(pattern => pattern.length > 0)
+                              .map(pattern => ({ target, pattern })),
+                          ],
+                        })}
+                        placeholder={placeholder}
+                        className="form-input"
+                      />
+                    </div>
+                  ))}
+                  <small>Globs, or regexes starting with re:. Skipped screenshots are logged without their images.</small>
+                </div>
+
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={config.encrypt_storage ?? false}
+                      onChange={(e) => setConfig({...config, encrypt_storage: e.target.checked})}
+                    />
+                    <span>Encrypt Stored History</span>
+                  </label>
+                  <small>Screenshots and analyses on disk are encrypted with a key kept in the system keychain</small>
+                </div>
+
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={config.tls_enabled ?? false}
+                      onChange={(e) => setConfig({...config, tls_enabled: e.target.checked})}
+                    />
+                    <span>Serve over HTTPS</span>
+                  </label>
+                  <small>Uses a self-signed certificate unless certificate and key paths are given</small>
+                </div>
+
+                {config.tls_enabled && (
+                  <div className="form-group">
+                    <label>Certificate and Key (PEM, optional)</label>
+                    <input
+                      type="text"
+                      value={config.tls_cert_path || ''}
+                      onChange={(e) => setConfig({...config, tls_cert_path: e.target.value})}
+                      placeholder="/path/to/cert.pem"
+                      className="form-input"
+                    />
+                    <input
+                      type="text"
+                      value={config.tls_key_path || ''}
+                      onChange={(e) => setConfig({...config, tls_key_path: e.target.value})}
+                      placeholder="/path/to/key.pem"
+                      className="form-input"
+                    />
+                  </div>
+                )}
+
+                <div className="form-group">
+                  <label>Log Level</label>
+                  <input
+                    type="text"
+                    value={config.log_filter || ''}
+                    onChange={(e) => setConfig({...config, log_filter: e.target.value})}
+                    placeholder="info"
+                    className="form-input"
+                  />
+                  <small>A level (error, warn, info, debug) or a filter like <code>app=debug,warn</code></small>
+                </div>
+
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={config.spotlight_index ?? false}
+                      onChange={(e) => setConfig({...config, spotlight_index: e.target.checked})}
+                    />
+                    <span>Index summaries for system search</span>
+                  </label>
+                  <small>Writes a small note per screenshot to Documents/Screenshot AI so Spotlight can find it</small>
+                  {config.spotlight_index && serverInfo && (
+                    <button type="button" onClick={reindexSpotlight} className="btn btn-outline">
+                      Reindex All
+                    </button>
+                  )}
+                </div>
+
                 {/* Telegram Configuration */}
                 <div className="form-section">
                   <h3>📱 Telegram Notifications (Optional)</h3>
@@ -383,7 +1206,7 @@ const ServerConfig: React.FC = () => {
                       type={showPasswords ? 'text' : 'password'}
                       value={config.telegram_bot_token || ''}
                       onChange={(e) => setConfig({...config, telegram_bot_token: e.target.value})}
-                      placeholder="123456:ABC-DEF..."
+                      placeholder={secretPlaceholder('telegram_bot_token', '123456:ABC-DEF...')}
                       className="form-input"
                     />
                     <small>Get from <a href="https://t.me/BotFather" target="_blank" rel="noopener noreferrer">@BotFather</a></small>
@@ -400,9 +1223,55 @@ const ServerConfig: React.FC = () => {
                     />
                     <small>Get from <a href="https://t.me/userinfobot" target="_blank" rel="noopener noreferrer">@userinfobot</a></small>
                   </div>
+
+                  <div className="form-group">
+                    <label>Additional Chats</label>
+                    <input
+                      type="text"
+                      value={(config.telegram_chats || []).map(chat => chat.chat_id).join(', ')}
+                      onChange={(e) => setConfig({
+                        ...config,
+                        telegram_chats: e.target.value
+                          .split(',')
+                          .map(id => id.trim())
+                          .filter(id => id.length > 0)
+                          .map(id => config.telegram_chats?.find(chat => chat.chat_id === id) ?? { chat_id: id }),
+                      })}
+                      placeholder="-1001234567890, @my_channel"
+                      className="form-input"
+                    />
+                    <small>Groups or channels that also get notifications. The bot ignores every other chat.</small>
+                  </div>
+                </div>
+
+                {/* Keychain Storage */}
+                <div className="form-group">
+                  <label className="checkbox-label">
+                    <input
+                      type="checkbox"
+                      checked={rememberSecrets}
+                      onChange={(e) => setRememberSecrets(e.target.checked)}
+                    />
+                    <span>Remember API keys in the system keychain</span>
+                  </label>
+                  <small>Keys are saved to the macOS Keychain / Windows Credential Manager and used when the fields above are left empty</small>
                 </div>
               </div>
 
+              {validation && (
+                <div className="form-section">
+                  <h3>{validation.ok ? '✅ Configuration looks good' : '❌ Some checks failed'}</h3>
+                  {validation.checks.map((check, index) => (
+                    <div key={`${check.item}-${index}`} className="form-group">
+                      <span>
+                        {check.status === 'pass' ? '✅' : check.status === 'fail' ? '❌' : '➖'} {check.label}
+                      </span>
+                      <small>{check.message}</small>
+                    </div>
+                  ))}
+                </div>
+              )}
+
               <div className="modal-actions">
                 <button 
                   onClick={() => setShowSetup(false)}
@@ -411,10 +1280,17 @@ const ServerConfig: React.FC = () => {
                 >
                   Cancel
                 </button>
+                <button
+                  onClick={validateConfig}
+                  className="btn btn-outline"
+                  disabled={isLoading || isValidating}
+                >
+                  {isValidating ? 'Testing...' : 'Test Configuration'}
+                </button>
                 <button 
                   onClick={startServer}
                   className="btn btn-primary"
-                  disabled={isLoading || !config.anthropic_api_key}
+                  disabled={isLoading || !hasProviderCredentials()}
                 >
                   {isLoading ? (
                     <>
@@ -674,6 +1550,11 @@ const ServerConfig: React.FC = () => {
           position: relative;
         }
 
+        .input-row {
+          display: flex;
+          gap: 12px;
+        }
+
         .toggle-password {
           position: absolute;
           right: 12px;